@@ -0,0 +1,233 @@
+//! `c1 init --existing`: adopt a directory that already holds C code.
+//!
+//! Only what is missing is written. project.toml, CMakeLists.txt, main.c,
+//! README.md and the rest of the skeleton are created when absent and left
+//! alone otherwise; an existing .gitignore gets the entries c1 relies on
+//! appended instead. The .c and .h files already in the root, src/ and
+//! include/ are registered in the SOURCES and HEADERS lists of
+//! CMakeLists.txt, and main.c is not created when one of them defines `main`.
+//! A directory whose main.c is in src/ is adopted with `layout = "src"`.
+//! `--dry-run` prints the plan without touching anything.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{self, Context};
+use crate::{NewProject, cmake_lists, fmt, log, safe_path, templates, vendor};
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+
+pub fn cmd_init_existing(
+    project_name: &str,
+    settings: &NewProject,
+    dry_run: bool,
+) -> error::Result<()> {
+    let template = templates::find(templates::DEFAULT_TEMPLATE).expect("default template exists");
+    if dry_run {
+        log::status!("Planning adoption of '{}' (dry run)...", project_name);
+    } else {
+        log::status!("Adopting existing code as project '{}'...", project_name);
+    }
+
+    let existing = find_sources();
+    let main_file = existing.iter().find(|path| defines_main(path));
+
+    for dir in ["src", "include", "external", "build"] {
+        if !Path::new(dir).is_dir() {
+            if !dry_run {
+                fs::create_dir_all(dir).context(|| format!("failed to create {}/", dir))?;
+            }
+            report(dry_run, "create", &format!("{}/", dir));
+        }
+    }
+
+    let main_source = settings.layout.main_source();
+    let mut files = template.files(
+        project_name,
+        crate::c_standard(&settings.edition),
+        main_source,
+    );
+    files.push((
+        "project.toml".to_string(),
+        crate::project_toml(project_name, template, settings),
+    ));
+    files.push((
+        "README.md".to_string(),
+        crate::readme(project_name, settings),
+    ));
+    files.push((".clang-format".to_string(), fmt::CLANG_FORMAT.to_string()));
+    if let Some(license) = settings.license {
+        files.push(("LICENSE".to_string(), license.render(project_name)));
+    }
+
+    // CMakeLists.txt is written last, once the sources are registered in it
+    let mut cmake = None;
+    for (path, mut content) in files {
+        if Path::new(&path).exists() {
+            log::status!("Keeping existing {}", path);
+            continue;
+        }
+        if let (true, Some(main_file)) = (path == main_source, main_file) {
+            log::status!(
+                "Not creating {}: {} already defines main()",
+                main_source,
+                main_file
+            );
+            continue;
+        }
+        if path == CMAKE_PATH {
+            if main_file.is_some() {
+                content = content.replace(&format!("    {}\n", main_source), "");
+            }
+            cmake = Some(content);
+            continue;
+        }
+        if !dry_run {
+            crate::create_file(&path, &content)?;
+        }
+        report(dry_run, "create", &path);
+    }
+
+    merge_gitignore(settings.vendor, dry_run)?;
+    register_sources(&existing, cmake, dry_run)?;
+
+    if dry_run {
+        log::status!("\nNothing was changed; run without --dry-run to apply");
+        return Ok(());
+    }
+    crate::git_init(settings.git);
+    log::status!("✓ Project '{}' initialized in place", project_name);
+    Ok(())
+}
+
+/// The .c and .h files directly in the root and anywhere under src/ and
+/// include/, as paths relative to the project root
+fn find_sources() -> Vec<String> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(".")
+        .map(|entries| entries.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+    paths.retain(|p| p.is_file() && is_source(p));
+    paths.sort();
+    for dir in ["src", "include"] {
+        fmt::collect(Path::new(dir), &mut paths);
+    }
+    paths
+        .iter()
+        .map(|p| {
+            let p = p.strip_prefix(".").unwrap_or(p);
+            p.to_string_lossy().replace('\\', "/")
+        })
+        .collect()
+}
+
+fn is_source(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("c") | Some("h")
+    )
+}
+
+/// Whether a .c file has a line starting a definition of `main`
+fn defines_main(path: &str) -> bool {
+    path.ends_with(".c")
+        && fs::read_to_string(path).is_ok_and(|content| {
+            content.lines().any(|line| {
+                let line = line.trim_start();
+                ["int main(", "int main (", "void main(", "void main ("]
+                    .iter()
+                    .any(|start| line.starts_with(start))
+            })
+        })
+}
+
+/// Append the entries of c1's .gitignore that an existing one lacks
+fn merge_gitignore(vendor: bool, dry_run: bool) -> error::Result<()> {
+    let path = ".gitignore";
+    let gitignore = vendor::gitignore(crate::GITIGNORE, vendor);
+    let Ok(current) = fs::read_to_string(path) else {
+        if !dry_run {
+            crate::create_file(path, &gitignore)?;
+        }
+        report(dry_run, "create", path);
+        return Ok(());
+    };
+    let present: Vec<&str> = current.lines().map(str::trim).collect();
+    let missing: Vec<&str> = gitignore
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter(|line| !present.contains(line))
+        .collect();
+    if missing.is_empty() {
+        log::status!("Keeping existing .gitignore, it already has c1's entries");
+        return Ok(());
+    }
+    if !dry_run {
+        let mut merged = current.clone();
+        if !merged.is_empty() && !merged.ends_with('\n') {
+            merged.push('\n');
+        }
+        merged.push_str("\n# Added by c1\n");
+        for line in &missing {
+            merged.push_str(line);
+            merged.push('\n');
+        }
+        safe_path::write(path, merged).context(|| format!("failed to update {}", path))?;
+    }
+    report(
+        dry_run,
+        "update",
+        &format!(".gitignore with {}", missing.join(" ")),
+    );
+    Ok(())
+}
+
+/// Add the existing files to SOURCES and HEADERS, in `created` when c1 is
+/// writing CMakeLists.txt and in the file on disk otherwise
+fn register_sources(
+    existing: &[String],
+    created: Option<String>,
+    dry_run: bool,
+) -> error::Result<()> {
+    let is_new = created.is_some();
+    let content = match created {
+        Some(content) => content,
+        None => {
+            fs::read_to_string(CMAKE_PATH).context(|| format!("failed to read {}", CMAKE_PATH))?
+        }
+    };
+    let mut updated = content.clone();
+    for path in existing {
+        match cmake_lists::register(&mut updated, path) {
+            cmake_lists::Registration::Added => {
+                report(dry_run, "register", &format!("{} in {}", path, CMAKE_PATH))
+            }
+            cmake_lists::Registration::AlreadyListed => {}
+            cmake_lists::Registration::NoList => cmake_lists::warn_unregistered(path),
+        }
+    }
+    if is_new {
+        if !dry_run {
+            crate::create_file(CMAKE_PATH, &updated)?;
+        }
+        report(dry_run, "create", CMAKE_PATH);
+    } else if updated != content && !dry_run {
+        safe_path::write(CMAKE_PATH, updated)
+            .context(|| format!("failed to update {}", CMAKE_PATH))?;
+    }
+    Ok(())
+}
+
+/// One step of the plan, as done or as it would be done
+fn report(dry_run: bool, action: &str, what: &str) {
+    if dry_run {
+        log::status!("Would {} {}", action, what);
+    } else {
+        let done = match action {
+            "create" => "Created",
+            "update" => "Updated",
+            _ => "Registered",
+        };
+        log::status!("✓ {} {}", done, what);
+    }
+}
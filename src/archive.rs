@@ -0,0 +1,200 @@
+//! Dependencies published as release archives instead of git repositories.
+//!
+//! ```toml
+//! [dependencies]
+//! libfoo = { url = "https://example.org/libfoo-1.2.tar.gz", sha256 = "9f86d08..." }
+//! ```
+//!
+//! `c1 add <url> --archive` downloads the archive, records its SHA-256 next
+//! to the URL and extracts it into external/<name>, dropping the single
+//! top-level directory most archives wrap their files in. `c1 sync` checks
+//! the SHA-256 of every download against the manifest and fails on a
+//! mismatch; an extraction already made from the recorded archive, as its
+//! marker file says, is not downloaded again; project.lock records a hash of
+//! the extracted files. `.tar.gz`, `.tgz`, `.tar.xz`,
+//! `.tar.bz2` and `.zip` are understood, through the system's `tar` and
+//! `unzip`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+use crate::{
+    build_history, http, lockfile, log, progress, remote, safe_path, secrets, url_rewrite,
+};
+
+/// Written into the extracted directory: the SHA-256 it was extracted from
+pub const MARKER: &str = ".c1-archive";
+const EXTENSIONS: &[&str] = &[".tar.gz", ".tgz", ".tar.xz", ".tar.bz2", ".zip"];
+
+/// The extension `url` ends in, when it is an archive c1 can extract
+pub fn extension(url: &str) -> Option<&'static str> {
+    let path = url
+        .split(['?', '#'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    EXTENSIONS.iter().copied().find(|ext| path.ends_with(ext))
+}
+
+/// `libfoo` for `https://example.org/libfoo-1.2.tar.gz`
+pub fn name_from_url(url: &str) -> String {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let file = path.rsplit('/').next().unwrap_or_default();
+    let stem = match extension(url) {
+        Some(ext) => &file[..file.len() - ext.len()],
+        None => file,
+    };
+    // Drop a trailing `-1.2` or `_v1.2` version
+    let stem = match stem.rfind(['-', '_']) {
+        Some(i)
+            if stem[i + 1..]
+                .trim_start_matches('v')
+                .starts_with(|c: char| c.is_ascii_digit()) =>
+        {
+            &stem[..i]
+        }
+        _ => stem,
+    };
+    crate::extract_package_name(stem)
+}
+
+/// The SHA-256 external/<name> was extracted from, if it was
+pub fn extracted_sha256(name: &str) -> Option<String> {
+    let marker = Path::new("external").join(name).join(MARKER);
+    fs::read_to_string(marker)
+        .ok()
+        .map(|content| content.trim().to_string())
+}
+
+/// Download `url`, check it against `expected` when given, and extract it
+/// into external/<name>. Returns the archive's SHA-256.
+pub fn fetch(name: &str, url: &str, expected: Option<&str>) -> Result<String, String> {
+    let Some(ext) = extension(url) else {
+        return Err(format!(
+            "{} is not a {} archive",
+            secrets::redact(url),
+            EXTENSIONS.join(", ")
+        ));
+    };
+    if remote::is_offline() {
+        return Err(format!(
+            "{} is not in external/, and --offline forbids downloading it",
+            name
+        ));
+    }
+    let real_url = secrets::expand(&url_rewrite::rewrite(url))?;
+    let download = PathBuf::from(format!("external/.{}{}", name, ext));
+    let staging = PathBuf::from(format!("external/.{}.extract", name));
+
+    let started = Instant::now();
+    log::status!("  Downloading {}...", secrets::redact(url));
+    let mut size = 0;
+    let mut meter = progress::download(name);
+    let sha256 = http::download(&real_url, &download, expected, &mut |p| {
+        size = p.downloaded;
+        meter.update(p.downloaded, p.total);
+    })
+    .map_err(|e| e.to_string())?;
+    drop(meter);
+    log::status!(
+        "  ✓ Downloaded {} ({})",
+        name,
+        progress::summary(Some(&build_history::bytes(size)), started.elapsed())
+    );
+
+    let result = extract(name, &download, ext, &staging, &sha256);
+    let _ = safe_path::remove_file(&download);
+    if staging.exists() {
+        let _ = safe_path::remove_dir_all(&staging);
+    }
+    result?;
+    lockfile::record_vendored(name);
+    Ok(sha256)
+}
+
+fn extract(
+    name: &str,
+    archive: &Path,
+    ext: &str,
+    staging: &Path,
+    sha256: &str,
+) -> Result<(), String> {
+    if staging.exists() {
+        safe_path::remove_dir_all(staging)
+            .map_err(|e| format!("Failed to remove {}: {}", staging.display(), e))?;
+    }
+    fs::create_dir_all(staging)
+        .map_err(|e| format!("Failed to create {}: {}", staging.display(), e))?;
+    let mut command = if ext == ".zip" {
+        let mut unzip = Command::new("unzip");
+        unzip.arg("-q").arg(archive).arg("-d").arg(staging);
+        unzip
+    } else {
+        let mut tar = Command::new("tar");
+        tar.arg("-xf").arg(archive).arg("-C").arg(staging);
+        tar
+    };
+    log::command(&command);
+    let tool = if ext == ".zip" { "unzip" } else { "tar" };
+    let output = command
+        .output()
+        .map_err(|e| format!("Failed to run {} to extract {}: {}", tool, name, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to extract {}\n    {}",
+            name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // Most archives hold one directory named after the release
+    let entries: Vec<PathBuf> = fs::read_dir(staging)
+        .map_err(|e| format!("Failed to read {}: {}", staging.display(), e))?
+        .flatten()
+        .map(|e| e.path())
+        .collect();
+    let root = match entries.as_slice() {
+        [only] if only.is_dir() && !only.is_symlink() => only.clone(),
+        _ => staging.to_path_buf(),
+    };
+    fs::write(root.join(MARKER), format!("{}\n", sha256))
+        .map_err(|e| format!("Failed to write {}: {}", root.join(MARKER).display(), e))?;
+
+    let target = Path::new("external").join(name);
+    if fs::symlink_metadata(&target).is_ok() {
+        safe_path::remove_dir_all(&target)
+            .map_err(|e| format!("Failed to remove {}: {}", target.display(), e))?;
+    }
+    fs::rename(&root, &target).map_err(|e| {
+        format!(
+            "Failed to move the extracted files to {}: {}",
+            target.display(),
+            e
+        )
+    })?;
+    log::status!("  ✓ Extracted {} to {}", name, target.display());
+    Ok(())
+}
+
+/// Sync an archive dependency: nothing to do when external/<name> was
+/// extracted from the recorded archive, otherwise download and verify it
+pub fn sync(name: &str, url: &str, sha256: Option<&str>, force: bool) -> Result<(), String> {
+    let Some(sha256) = sha256 else {
+        return Err(format!(
+            "{} has no sha256 in project.toml; run `c1 add {} --archive --name {}` to record it",
+            name,
+            secrets::redact(url),
+            name
+        ));
+    };
+    if !force
+        && extracted_sha256(name).is_some_and(|extracted| extracted.eq_ignore_ascii_case(sha256))
+    {
+        let short: String = sha256.chars().take(12).collect();
+        log::status!("  ✓ {} already extracted (sha256 {})", name, short);
+        return Ok(());
+    }
+    fetch(name, url, Some(sha256)).map(|_| ())
+}
@@ -0,0 +1,532 @@
+//! `c1 audit`: the third-party code in a project, where it came from and
+//! under which license, for whoever signs off a release.
+//!
+//! Every directory in external/, and in vendor/ when there is one, is a
+//! component. Its origin, tag or branch come from its entry in project.toml
+//! or else project.lock, its commit from its checkout, and its license from
+//! the first of:
+//!
+//! - LICENSE, LICENCE or COPYING files at its root, recognized by their
+//!   SPDX line or by wording of the common licenses (MIT, BSD, Apache,
+//!   GPL/LGPL, MPL, ISC, Zlib, BSL, Unlicense)
+//! - `license` under `[project]` in its own project.toml
+//! - `SPDX-License-Identifier:` lines at the top of its sources
+//!
+//! A license file that matches none of them is "unknown", no license at all
+//! is "missing"; both are reported as problems. With `[audit] allow` in
+//! project.toml, any component whose license is not on the list (an `OR`
+//! expression needs one allowed side, an `AND` one all of them) makes the
+//! command fail. Checkouts that differ from the commit, tree or content
+//! hash in project.lock are reported too, since the report would not
+//! describe them.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{self, Error};
+use crate::lockfile::{self, Lockfile};
+use crate::{git_head, json, log};
+
+/// `[audit]` in project.toml
+#[derive(Serialize, Deserialize, Default)]
+pub struct Policy {
+    /// SPDX identifiers a dependency's license must be one of
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+const VENDOR_DIR: &str = "vendor";
+/// Files whose header is searched for an SPDX line, at most
+const MAX_SOURCES: usize = 500;
+const SOURCE_EXTENSIONS: &[&str] = &["c", "h", "cc", "cpp", "hpp", "s", "S"];
+
+/// How a component's license was found
+enum Found {
+    /// An SPDX identifier or expression, and the file it came from
+    License(String, String),
+    /// License files none of which c1 recognizes
+    Unknown(String),
+    Missing,
+}
+
+impl Found {
+    fn id(&self) -> &str {
+        match self {
+            Found::License(id, _) => id,
+            Found::Unknown(_) => "unknown",
+            Found::Missing => "missing",
+        }
+    }
+
+    fn file(&self) -> Option<&str> {
+        match self {
+            Found::License(_, file) | Found::Unknown(file) => Some(file),
+            Found::Missing => None,
+        }
+    }
+}
+
+struct Component {
+    name: String,
+    dir: PathBuf,
+    version: Option<String>,
+    commit: Option<String>,
+    origin: Option<String>,
+    license: Found,
+    /// How the checkout compares with project.lock
+    lock: String,
+    /// Whether `[audit] allow` lets the license in; `None` without one
+    allowed: Option<bool>,
+}
+
+pub fn cmd_audit(format: &str) -> error::Result<()> {
+    let config = crate::read_config()?;
+    let declared = config.all_dependencies();
+    let lock = Lockfile::load().unwrap_or_else(|e| {
+        log::problem!(
+            "Warning: ignoring unreadable {}: {}",
+            lockfile::LOCKFILE_PATH,
+            e
+        );
+        None
+    });
+    let allow = &config.audit.allow;
+
+    let mut components: Vec<Component> = Vec::new();
+    for dir in directories(Path::new("external")) {
+        let name = file_name(&dir);
+        let entry = declared.get(&name);
+        let field = |key: &str| {
+            entry
+                .and_then(|e| e.get(key))
+                .and_then(|v| v.as_str())
+                .map(str::to_string)
+        };
+        let locked = lock
+            .as_ref()
+            .and_then(|lock| lock.packages.iter().find(|p| p.name == name));
+        let origin = field("git")
+            .or_else(|| field("url"))
+            .or_else(|| field("path").map(|path| format!("path {}", path)))
+            .or_else(|| locked.map(|p| p.git.clone()));
+        let version = field("tag")
+            .or_else(|| field("branch"))
+            .or_else(|| field("rev"))
+            .or_else(|| locked.and_then(|p| p.tag.clone().or(p.branch.clone())));
+        let lock_state = if field("path").is_some() {
+            "path dependency".to_string()
+        } else {
+            lock_state(&name, &dir, lock.as_ref())
+        };
+        components.push(Component {
+            commit: git_head::read_head(&dir),
+            license: license(&dir),
+            name,
+            version,
+            origin,
+            lock: lock_state,
+            allowed: None,
+            dir,
+        });
+    }
+    for dir in directories(Path::new(VENDOR_DIR)) {
+        components.push(Component {
+            name: file_name(&dir),
+            version: None,
+            commit: git_head::read_head(&dir),
+            origin: Some(dir.display().to_string()),
+            license: license(&dir),
+            lock: "not tracked".to_string(),
+            allowed: None,
+            dir,
+        });
+    }
+    if !allow.is_empty() {
+        for component in &mut components {
+            component.allowed = Some(match &component.license {
+                Found::License(id, _) => is_allowed(id, allow),
+                _ => false,
+            });
+        }
+    }
+
+    match format {
+        "json" => print_json(&components),
+        "csv" => print_csv(&components),
+        _ => print_table(&components),
+    }
+    report(&components, allow)
+}
+
+/// The directories in `dir`, sorted, hidden ones left out
+fn directories(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir() && !file_name(path).starts_with('.'))
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// How `external/<name>` compares with its entry in project.lock
+fn lock_state(name: &str, dir: &Path, lock: Option<&Lockfile>) -> String {
+    let Some(lock) = lock else {
+        return "not locked".to_string();
+    };
+    if let Some(package) = lock.packages.iter().find(|p| p.name == name) {
+        let head = git_head::read_head(dir);
+        if head.as_deref() != Some(package.commit.as_str()) {
+            return format!(
+                "at {}, locked {}",
+                head.as_deref().map_or("no commit", crate::short_hash),
+                crate::short_hash(&package.commit)
+            );
+        }
+        let modified = package
+            .tree
+            .as_ref()
+            .is_some_and(|tree| lockfile::working_tree(dir).is_some_and(|actual| actual != *tree));
+        return if modified { "modified" } else { "matches" }.to_string();
+    }
+    if let Some(package) = lock.vendored.iter().find(|p| p.name == name) {
+        let modified = lockfile::content_hash(dir).is_some_and(|actual| actual != package.content);
+        return if modified { "modified" } else { "matches" }.to_string();
+    }
+    "not locked".to_string()
+}
+
+/// The license of the code in `dir`
+fn license(dir: &Path) -> Found {
+    let mut unknown = None;
+    let mut ids: Vec<String> = Vec::new();
+    let mut files: Vec<String> = Vec::new();
+    for path in license_files(dir) {
+        let Ok(text) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let shown = path.display().to_string();
+        match identify(&text) {
+            Some(id) => {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+                files.push(shown);
+            }
+            None => unknown = unknown.or(Some(shown)),
+        }
+    }
+    if !ids.is_empty() {
+        return Found::License(ids.join(" AND "), files.join(", "));
+    }
+    if let Some(file) = unknown {
+        return Found::Unknown(file);
+    }
+    let manifest = dir.join("project.toml");
+    if let Some(id) = fs::read_to_string(&manifest)
+        .ok()
+        .and_then(|content| content.parse::<toml::Table>().ok())
+        .and_then(|table| {
+            table
+                .get("project")?
+                .get("license")?
+                .as_str()
+                .map(str::to_string)
+        })
+    {
+        return Found::License(id, manifest.display().to_string());
+    }
+    let mut sources = 0;
+    if let Some((id, file)) = spdx_in_sources(dir, &mut sources) {
+        return Found::License(id, file);
+    }
+    Found::Missing
+}
+
+/// LICENSE*, LICENCE* and COPYING* at the root of `dir`, sorted
+fn license_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = file_name(path).to_ascii_uppercase();
+            path.is_file()
+                && (name.starts_with("LICENSE")
+                    || name.starts_with("LICENCE")
+                    || name.starts_with("COPYING"))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// The SPDX identifier a license text states or its wording gives away
+fn identify(text: &str) -> Option<String> {
+    if let Some(id) = spdx_line(text) {
+        return Some(id);
+    }
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let lower = text.to_ascii_lowercase();
+    let has = |words: &str| lower.contains(words);
+    let id = if has("apache license") && has("version 2.0") {
+        "Apache-2.0"
+    } else if has("gnu lesser general public license") {
+        if has("version 3") {
+            "LGPL-3.0"
+        } else {
+            "LGPL-2.1"
+        }
+    } else if has("gnu general public license") {
+        if has("version 3") {
+            "GPL-3.0"
+        } else {
+            "GPL-2.0"
+        }
+    } else if has("mozilla public license") && has("2.0") {
+        "MPL-2.0"
+    } else if has("permission is hereby granted, free of charge") {
+        "MIT"
+    } else if has("redistribution and use in source and binary forms") {
+        if has("neither the name") || has("may be used to endorse or promote") {
+            "BSD-3-Clause"
+        } else {
+            "BSD-2-Clause"
+        }
+    } else if has(
+        "permission to use, copy, modify, and/or distribute this software for any purpose",
+    ) {
+        "ISC"
+    } else if has("boost software license") {
+        "BSL-1.0"
+    } else if has("altered source versions must be plainly marked") {
+        "Zlib"
+    } else if has("this is free and unencumbered software released into the public domain") {
+        "Unlicense"
+    } else {
+        return None;
+    };
+    Some(id.to_string())
+}
+
+/// The expression of an `SPDX-License-Identifier:` line in `text`
+fn spdx_line(text: &str) -> Option<String> {
+    let at = text.find("SPDX-License-Identifier:")?;
+    let rest = &text[at + "SPDX-License-Identifier:".len()..];
+    let id = rest
+        .lines()
+        .next()?
+        .trim()
+        .trim_end_matches("*/")
+        .trim()
+        .to_string();
+    (!id.is_empty()).then_some(id)
+}
+
+/// The first SPDX line in the first 4 KiB of the sources under `dir`, and
+/// the file it is in
+fn spdx_in_sources(dir: &Path, seen: &mut usize) -> Option<(String, String)> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return None;
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    for path in paths {
+        if *seen >= MAX_SOURCES || file_name(&path).starts_with('.') {
+            continue;
+        }
+        if path.is_dir() && !path.is_symlink() {
+            if let Some(found) = spdx_in_sources(&path, seen) {
+                return Some(found);
+            }
+            continue;
+        }
+        let is_source = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| SOURCE_EXTENSIONS.contains(&ext));
+        if !is_source {
+            continue;
+        }
+        *seen += 1;
+        let Ok(bytes) = fs::read(&path) else {
+            continue;
+        };
+        let head = String::from_utf8_lossy(&bytes[..bytes.len().min(4096)]);
+        if let Some(id) = spdx_line(&head) {
+            return Some((id, path.display().to_string()));
+        }
+    }
+    None
+}
+
+/// Whether an SPDX expression is within `allow`: one side of an `OR`, and
+/// every part of an `AND`
+fn is_allowed(expression: &str, allow: &[String]) -> bool {
+    let expression = expression.replace(['(', ')'], " ");
+    expression.split(" OR ").any(|alternative| {
+        alternative.split(" AND ").all(|id| {
+            let id = id.trim();
+            allow.iter().any(|allowed| allowed.eq_ignore_ascii_case(id))
+        })
+    })
+}
+
+fn print_table(components: &[Component]) {
+    if components.is_empty() {
+        println!("No third-party code in external/ or vendor/");
+        return;
+    }
+    let rows: Vec<[String; 6]> = components
+        .iter()
+        .map(|c| {
+            [
+                c.name.clone(),
+                c.version.clone().unwrap_or_else(|| "-".to_string()),
+                c.commit.as_deref().map_or("-".to_string(), |commit| {
+                    crate::short_hash(commit).to_string()
+                }),
+                match c.allowed {
+                    Some(false) => format!("{} (not allowed)", c.license.id()),
+                    _ => c.license.id().to_string(),
+                },
+                c.origin.clone().unwrap_or_else(|| "unknown".to_string()),
+                c.lock.clone(),
+            ]
+        })
+        .collect();
+    let header = ["Name", "Version", "Commit", "License", "Origin", "Lock"];
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([header[i].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<w$}", cell, w = width))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(&header.map(str::to_string));
+    for row in &rows {
+        line(row);
+    }
+}
+
+fn print_json(components: &[Component]) {
+    let items: Vec<String> = components
+        .iter()
+        .map(|c| {
+            format!(
+                "{{\"name\":{},\"dir\":{},\"version\":{},\"commit\":{},\"license\":{},\"license_file\":{},\"origin\":{},\"lock\":{},\"allowed\":{}}}",
+                json::string(&c.name),
+                json::string(&c.dir.display().to_string()),
+                json::optional(c.version.as_deref()),
+                json::optional(c.commit.as_deref()),
+                json::string(c.license.id()),
+                json::optional(c.license.file()),
+                json::optional(c.origin.as_deref()),
+                json::string(&c.lock),
+                c.allowed.map_or("null".to_string(), |allowed| allowed.to_string())
+            )
+        })
+        .collect();
+    println!("[{}]", items.join(","));
+}
+
+fn print_csv(components: &[Component]) {
+    // RFC 4180: quote fields holding a comma, quote or line break
+    let field = |value: &str| {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    };
+    println!("name,dir,version,commit,license,license_file,origin,lock,allowed");
+    for c in components {
+        let dir = c.dir.display().to_string();
+        let allowed = c.allowed.map(|allowed| allowed.to_string());
+        let cells = [
+            c.name.as_str(),
+            dir.as_str(),
+            c.version.as_deref().unwrap_or(""),
+            c.commit.as_deref().unwrap_or(""),
+            c.license.id(),
+            c.license.file().unwrap_or(""),
+            c.origin.as_deref().unwrap_or(""),
+            c.lock.as_str(),
+            allowed.as_deref().unwrap_or(""),
+        ];
+        let cells: Vec<String> = cells.iter().map(|cell| field(cell)).collect();
+        println!("{}", cells.join(","));
+    }
+}
+
+/// Warn about what needs a look, and fail when the policy is broken
+fn report(components: &[Component], allow: &[String]) -> error::Result<()> {
+    for c in components {
+        match &c.license {
+            Found::Unknown(file) => log::problem!(
+                "Warning: {}: license not recognized in {}; check it by hand",
+                c.name,
+                file
+            ),
+            Found::Missing => log::problem!(
+                "Warning: {}: no license found in {}; without one it may not be redistributable",
+                c.name,
+                c.dir.display()
+            ),
+            Found::License(..) => {}
+        }
+        if c.origin.is_none() {
+            log::problem!(
+                "Warning: {}: origin unknown, neither project.toml nor {} names it",
+                c.name,
+                lockfile::LOCKFILE_PATH
+            );
+        }
+        if c.lock == "modified" || c.lock.starts_with("at ") {
+            log::problem!(
+                "Warning: {}: the checkout does not match {} ({}); run `c1 sync` before auditing a release",
+                c.name,
+                lockfile::LOCKFILE_PATH,
+                c.lock
+            );
+        }
+    }
+    let refused: Vec<String> = components
+        .iter()
+        .filter(|c| c.allowed == Some(false))
+        .map(|c| format!("{} ({})", c.name, c.license.id()))
+        .collect();
+    if !refused.is_empty() {
+        return Err(Error::failure(format!(
+            "{} of {} dependencies are outside [audit] allow: {}",
+            refused.len(),
+            components.len(),
+            refused.join(", ")
+        ))
+        .hint(format!("allowed: {}", allow.join(", "))));
+    }
+    Ok(())
+}
@@ -0,0 +1,208 @@
+//! `[build] source_mode = "auto"`: the file system, not CMakeLists.txt, says
+//! what gets compiled.
+//!
+//! ```toml
+//! [build]
+//! source_mode = "auto"
+//! exclude = ["src/experimental/**"]
+//! ```
+//!
+//! Before every build c1 lists main.c (for an executable), src/**/*.c and
+//! include/**/*.h, leaving out what .c1ignore or the `exclude` patterns
+//! match and the sources that
+//! features and platform modules add themselves, and writes them as the
+//! SOURCES and HEADERS lists of c1_sources.cmake, which CMakeLists.txt
+//! includes. Files copied in, generated or deleted outside c1 are picked up
+//! by the next build, and `c1 create` leaves CMakeLists.txt alone.
+//!
+//! Switching modes migrates CMakeLists.txt: to auto, the `set(SOURCES ...)`
+//! and `set(HEADERS ...)` blocks give way to the `include()`; back to the
+//! default `"list"`, the include gives way to blocks listing the files
+//! found, with the markers `c1 create` registers before.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cmake_lists::{HEADERS_MARKER, SOURCES_MARKER};
+use crate::error::{self, Context, Error};
+use crate::{ProjectConfig, ProjectKind, fmt, ignore, log, safe_path};
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+pub const GENERATED: &str = "c1_sources.cmake";
+const INCLUDE_LINE: &str = "include(${CMAKE_CURRENT_SOURCE_DIR}/c1_sources.cmake)";
+
+/// Whether project.toml asks for auto mode
+pub fn is_auto() -> bool {
+    crate::read_config().is_ok_and(|config| config.build.source_mode.as_deref() == Some("auto"))
+}
+
+/// Bring c1_sources.cmake and CMakeLists.txt in step with the source mode
+pub fn sync(config: &ProjectConfig) -> error::Result<()> {
+    let auto = match config.build.source_mode.as_deref() {
+        None | Some("list") => false,
+        Some("auto") => true,
+        Some(mode) => {
+            return Err(
+                Error::config(format!("unknown source_mode '{}' in [build]", mode))
+                    .hint("use \"auto\" or \"list\" (the default)"),
+            );
+        }
+    };
+    let Ok(content) = fs::read_to_string(CMAKE_PATH) else {
+        return Ok(());
+    };
+    let (sources, headers) = scan(config);
+
+    if !auto {
+        if content.contains(INCLUDE_LINE) {
+            let updated = content.replace(INCLUDE_LINE, set_blocks(&sources, &headers).trim_end());
+            safe_path::write(CMAKE_PATH, updated)
+                .context(|| format!("failed to update {}", CMAKE_PATH))?;
+            if Path::new(GENERATED).exists() {
+                safe_path::remove_file(GENERATED)
+                    .context(|| format!("failed to remove {}", GENERATED))?;
+            }
+            log::status!("✓ Moved the source lists back into CMakeLists.txt");
+        }
+        return Ok(());
+    }
+
+    let generated = generated(&sources, &headers);
+    if fs::read_to_string(GENERATED).ok().as_deref() != Some(generated.as_str()) {
+        safe_path::write(GENERATED, &generated)
+            .context(|| format!("failed to write {}", GENERATED))?;
+        log::verbose!(
+            "Listed {} source(s) and {} header(s) in {}",
+            sources.len(),
+            headers.len(),
+            GENERATED
+        );
+    }
+    if !content.contains(INCLUDE_LINE) {
+        match to_auto(&content) {
+            Some(updated) => {
+                safe_path::write(CMAKE_PATH, updated)
+                    .context(|| format!("failed to update {}", CMAKE_PATH))?;
+                log::status!(
+                    "✓ CMakeLists.txt now takes its source lists from {}",
+                    GENERATED
+                );
+            }
+            None => log::problem!(
+                "Warning: CMakeLists.txt has no set(SOURCES ...) block to replace; add `{}` to it by hand",
+                INCLUDE_LINE
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// The project's sources and headers, minus the excluded ones and those
+/// features and platform modules bring in themselves
+pub fn scan(config: &ProjectConfig) -> (Vec<String>, Vec<String>) {
+    let mut files = Vec::new();
+    fmt::collect(Path::new("src"), &mut files);
+    fmt::collect(Path::new("include"), &mut files);
+    ignore::Ignore::project().retain(&mut files);
+    // A library's main.c is its demo, not part of it, wherever it is
+    let main = PathBuf::from(crate::main_source());
+    files.retain(|file| *file != main);
+    if config.project.kind == ProjectKind::Bin && main.is_file() {
+        files.insert(0, main);
+    }
+
+    let mut managed: Vec<String> = config
+        .features
+        .values()
+        .flat_map(|feature| feature.sources().to_vec())
+        .collect();
+    managed.extend(crate::variants::sources(&config.variant));
+    for (module, platforms) in &config.platforms {
+        managed.extend(platforms.iter().map(|p| format!("src/{}_{}.c", module, p)));
+    }
+
+    let (mut sources, mut headers) = (Vec::new(), Vec::new());
+    for file in files {
+        let file = file.to_string_lossy().replace('\\', "/");
+        if managed.contains(&file)
+            || config
+                .build
+                .exclude
+                .iter()
+                .any(|pattern| ignore::glob(pattern.as_bytes(), file.as_bytes()))
+        {
+            continue;
+        }
+        if file.ends_with(".c") && !file.starts_with("include/") {
+            sources.push(file);
+        } else if file.ends_with(".h") && file.starts_with("include/") {
+            headers.push(file);
+        }
+    }
+    (sources, headers)
+}
+
+/// One indented line per file
+fn list(files: &[String]) -> String {
+    files.iter().map(|f| format!("    {}\n", f)).collect()
+}
+
+fn generated(sources: &[String], headers: &[String]) -> String {
+    format!(
+        "# Generated by c1 before every build ([build] source_mode = \"auto\"); do not edit\n\
+         set(SOURCES\n{})\n\nset(HEADERS\n{})\n",
+        list(sources),
+        list(headers)
+    )
+}
+
+/// The `set()` blocks of list mode, with the markers `c1 create` uses
+fn set_blocks(sources: &[String], headers: &[String]) -> String {
+    format!(
+        "set(SOURCES\n{}    {}\n)\n\n# Header files list\nset(HEADERS\n{}    {}\n)\n",
+        list(sources),
+        SOURCES_MARKER,
+        list(headers),
+        HEADERS_MARKER
+    )
+}
+
+/// `content` with its SOURCES block replaced by the include line and its
+/// HEADERS block removed
+fn to_auto(content: &str) -> Option<String> {
+    let (start, end) = set_block(content, "SOURCES")?;
+    let mut updated = format!("{}{}{}", &content[..start], INCLUDE_LINE, &content[end..]);
+    if let Some((start, end)) = set_block(&updated, "HEADERS") {
+        let mut start = start;
+        // Its comment and the blank line before go too
+        let comment = "# Header files list\n";
+        if updated[..start].ends_with(comment) {
+            start -= comment.len();
+        }
+        if updated[..start].ends_with("\n\n") {
+            start -= 1;
+        }
+        let end = end + updated[end..].find('\n').map_or(0, |i| i + 1);
+        updated = format!("{}{}", &updated[..start], &updated[end..]);
+    }
+    Some(updated)
+}
+
+/// Byte range of `set(<list> ...)`, from `set` through the closing `)`
+fn set_block(content: &str, list: &str) -> Option<(usize, usize)> {
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let code = line.trim_start();
+        if let Some(rest) = code
+            .strip_prefix("set(")
+            .or_else(|| code.strip_prefix("set ("))
+            && rest.trim_start().split([' ', '\t', '\n', '\r', ')']).next() == Some(list)
+        {
+            let start = offset + line.len() - code.len();
+            let end = start + content[start..].find(')')? + 1;
+            return Some((start, end));
+        }
+        offset += line.len();
+    }
+    None
+}
@@ -0,0 +1,178 @@
+//! `c1 bench`: wall-clock timing of the programs in benches/.
+//!
+//! Every `benches/<name>.c` is its own program with a `main`, compiled
+//! together with the project's sources except main.c, like tests. Benchmarks
+//! are always built in Release mode in their own build directory, so timing
+//! never depends on (or disturbs) the profile of the last `c1 build`.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::error::{self, Context, Error};
+use crate::log;
+use crate::safe_path;
+
+const BENCH_DIR: &str = "benches";
+const PLACEHOLDER: &str = "# @c1_benches";
+
+pub fn cmd_bench(name: Option<&str>, iterations: u32) -> error::Result<()> {
+    if iterations == 0 {
+        return Err(Error::usage("--iterations must be at least 1"));
+    }
+    let benches = find_benches()?;
+    let benches: Vec<String> = match name {
+        Some(name) if benches.iter().any(|b| b == name) => vec![name.to_string()],
+        Some(name) => {
+            return Err(Error::usage(format!("No benchmark named '{}'", name))
+                .hint(format!("available benchmarks: {}", benches.join(", "))));
+        }
+        None => benches,
+    };
+
+    register_benches()?;
+    let build_dir = crate::resolve_build_dir(None).join("bench");
+    log::status!("Building benchmarks (Release mode)...");
+    crate::configure_project(&build_dir, "Release", &["-DC1_BENCHES=ON".to_string()])?;
+    let mut build = Command::new("cmake");
+    build
+        .arg("--build")
+        .arg(&build_dir)
+        .args(["--config", "Release"]);
+    for bench in &benches {
+        build.args(["--target", &target(bench)]);
+    }
+    log::command(&build);
+    let status = build.status().map_err(|e| {
+        Error::failure(format!("failed to run cmake --build: {}", e))
+            .hint("make sure cmake is installed")
+    })?;
+    if !status.success() {
+        return Err(Error::build("Building benchmarks failed."));
+    }
+
+    log::status!(
+        "Running {} benchmark(s), {} iteration(s) each...\n",
+        benches.len(),
+        iterations
+    );
+    let mut results = Vec::new();
+    for bench in &benches {
+        let executable =
+            crate::executable_path(&build_dir, &target(bench), true).map_err(|_| {
+                Error::failure(format!("Executable for benchmark '{}' not found", bench))
+            })?;
+        results.push((bench, time(bench, &executable, iterations)?));
+    }
+
+    let width = benches.iter().map(String::len).max().unwrap_or(0).max(9);
+    log::status!(
+        "{:width$}  {:>10}  {:>10}  {:>10}",
+        "benchmark", "min", "avg", "max"
+    );
+    for (bench, times) in results {
+        let min = times.iter().min().copied().unwrap_or_default();
+        let max = times.iter().max().copied().unwrap_or_default();
+        let avg = times.iter().sum::<Duration>() / times.len() as u32;
+        log::status!(
+            "{:width$}  {:>10}  {:>10}  {:>10}",
+            bench,
+            format_duration(min),
+            format_duration(avg),
+            format_duration(max)
+        );
+    }
+    Ok(())
+}
+
+/// The names of the .c files in benches/, sorted
+fn find_benches() -> error::Result<Vec<String>> {
+    let layout_hint = "put each benchmark in benches/<name>.c as a program with its own main()";
+    if !Path::new(BENCH_DIR).is_dir() {
+        return Err(Error::usage("benches/ not found").hint(layout_hint));
+    }
+    let entries = fs::read_dir(BENCH_DIR).context(|| format!("failed to list {}/", BENCH_DIR))?;
+    let mut benches: Vec<String> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "c"))
+        .filter_map(|p| Some(p.file_stem()?.to_string_lossy().into_owned()))
+        .collect();
+    if benches.is_empty() {
+        return Err(Error::usage("benches/ has no .c files").hint(layout_hint));
+    }
+    benches.sort();
+    Ok(benches)
+}
+
+fn target(bench: &str) -> String {
+    format!("bench_{}", bench)
+}
+
+/// Run a benchmark `iterations` times, its output discarded
+fn time(bench: &str, executable: &Path, iterations: u32) -> error::Result<Vec<Duration>> {
+    let mut times = Vec::new();
+    for _ in 0..iterations {
+        let start = Instant::now();
+        let status = Command::new(executable)
+            .stdout(Stdio::null())
+            .status()
+            .map_err(|e| Error::failure(format!("failed to run benchmark '{}': {}", bench, e)))?;
+        times.push(start.elapsed());
+        if !status.success() {
+            return Err(Error::failure(format!(
+                "benchmark '{}' exited with {}",
+                bench, status
+            )));
+        }
+    }
+    Ok(times)
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs_f64();
+    if secs >= 1.0 {
+        format!("{:.2}s", secs)
+    } else if secs >= 0.001 {
+        format!("{:.2}ms", secs * 1e3)
+    } else {
+        format!("{:.1}µs", secs * 1e6)
+    }
+}
+
+/// Add the block that builds benches/*.c to CMakeLists.txt on first use. It
+/// only takes effect with -DC1_BENCHES=ON, so ordinary builds are unaffected.
+fn register_benches() -> error::Result<()> {
+    let cmake_path = "CMakeLists.txt";
+    let mut content =
+        fs::read_to_string(cmake_path).context(|| format!("failed to read {}", cmake_path))?;
+    if content.contains(PLACEHOLDER) {
+        return Ok(());
+    }
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(&format!(
+        r#"
+# Benchmarks: one executable per file in benches/, built and timed by `c1 bench`
+{}
+if(C1_BENCHES)
+    set(C1_BENCH_SOURCES ${{SOURCES}})
+    list(REMOVE_ITEM C1_BENCH_SOURCES {})
+    file(GLOB C1_BENCHES_FILES CONFIGURE_DEPENDS ${{CMAKE_CURRENT_SOURCE_DIR}}/benches/*.c)
+    foreach(bench_file ${{C1_BENCHES_FILES}})
+        get_filename_component(bench_name ${{bench_file}} NAME_WE)
+        add_executable(bench_${{bench_name}} ${{bench_file}} ${{C1_BENCH_SOURCES}})
+        target_include_directories(bench_${{bench_name}} PRIVATE ${{CMAKE_CURRENT_SOURCE_DIR}}/include)
+        target_link_libraries(bench_${{bench_name}} PRIVATE ${{C1_DEV_LIBRARIES}})
+    endforeach()
+endif()
+"#,
+        PLACEHOLDER,
+        crate::main_source()
+    ));
+    safe_path::write(cmake_path, content).context(|| format!("failed to update {}", cmake_path))?;
+    log::status!("✓ Registered benches/ in CMakeLists.txt");
+    Ok(())
+}
@@ -0,0 +1,139 @@
+//! Extra executables declared with `[[bin]]` in project.toml.
+//!
+//! ```toml
+//! [[bin]]
+//! name = "tool1"
+//! path = "bins/tool1.c"
+//! ```
+//!
+//! Each bin is its own program compiled with the project's sources except
+//! main.c. c1 keeps a managed block in CMakeLists.txt, `# @c1_bins` through
+//! `# @c1_bins_end`, in step with the manifest before every build. The default
+//! executable built from main.c is unaffected and keeps the project's name.
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{self, Context, Error};
+use crate::log;
+use crate::safe_path;
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+const BEGIN: &str = "# @c1_bins";
+const END: &str = "# @c1_bins_end";
+
+#[derive(Serialize, Deserialize)]
+pub struct Bin {
+    pub name: String,
+    pub path: String,
+}
+
+/// Check the `[[bin]]` entries and bring the CMakeLists block up to date
+pub fn wire(project_name: &str, bins: &[Bin]) -> error::Result<()> {
+    for (i, bin) in bins.iter().enumerate() {
+        if bin.name.is_empty()
+            || !bin
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(Error::config(format!(
+                "[[bin]] name '{}' must contain only letters, numbers, '_' and '-'",
+                bin.name
+            )));
+        }
+        if bin.name == project_name || bins[..i].iter().any(|b| b.name == bin.name) {
+            return Err(Error::config(format!(
+                "[[bin]] name '{}' is used more than once (the main executable is '{}')",
+                bin.name, project_name
+            )));
+        }
+        if !Path::new(&bin.path).is_file() {
+            return Err(Error::config(format!(
+                "[[bin]] '{}': {} not found",
+                bin.name, bin.path
+            )));
+        }
+    }
+
+    let Ok(content) = fs::read_to_string(CMAKE_PATH) else {
+        return Ok(());
+    };
+    let block = block(bins);
+    let updated = match (content.find(BEGIN), content.find(END)) {
+        (Some(start), Some(stop)) if stop > start => {
+            let stop = stop + END.len();
+            let stop = stop + content[stop..].find('\n').map_or(0, |i| i + 1);
+            // Drop the blank line that separated a removed block
+            let start = if block.is_empty() && content[..start].ends_with("\n\n") {
+                start - 1
+            } else {
+                start
+            };
+            format!("{}{}{}", &content[..start], block, &content[stop..])
+        }
+        _ if block.is_empty() => return Ok(()),
+        _ => {
+            let mut updated = content.clone();
+            if !updated.ends_with('\n') {
+                updated.push('\n');
+            }
+            updated.push('\n');
+            updated.push_str(&block);
+            updated
+        }
+    };
+    if updated != content {
+        safe_path::write(CMAKE_PATH, updated)
+            .context(|| format!("failed to update {}", CMAKE_PATH))?;
+        log::status!("✓ Updated [[bin]] targets in CMakeLists.txt");
+    }
+    Ok(())
+}
+
+fn block(bins: &[Bin]) -> String {
+    if bins.is_empty() {
+        return String::new();
+    }
+    let mut block = format!(
+        "{}\n\
+         # Executables from [[bin]] in project.toml, kept in sync by c1\n\
+         set(C1_BIN_SOURCES ${{SOURCES}})\n\
+         list(REMOVE_ITEM C1_BIN_SOURCES {})\n",
+        BEGIN,
+        crate::main_source()
+    );
+    for bin in bins {
+        block.push_str(&format!(
+            "add_executable({name} {path} ${{C1_BIN_SOURCES}})\n\
+             target_include_directories({name} PRIVATE ${{CMAKE_CURRENT_SOURCE_DIR}}/include)\n",
+            name = bin.name,
+            path = bin.path.replace('\\', "/")
+        ));
+    }
+    block.push_str(END);
+    block.push('\n');
+    block
+}
+
+/// The executable `c1 run` should start: `--bin`, or the only one there is
+pub fn select(project_name: &str, bins: &[Bin], requested: Option<&str>) -> error::Result<String> {
+    let mut available: Vec<&str> = Vec::new();
+    if bins.is_empty() || Path::new(crate::main_source()).is_file() {
+        available.push(project_name);
+    }
+    available.extend(bins.iter().map(|b| b.name.as_str()));
+
+    match requested {
+        Some(name) if available.contains(&name) => Ok(name.to_string()),
+        Some(name) => Err(Error::usage(format!("no binary named '{}'", name))
+            .hint(format!("available binaries: {}", available.join(", ")))),
+        None if available.len() == 1 => Ok(available[0].to_string()),
+        None => Err(Error::usage(
+            "this project has several binaries, choose one with --bin <name>",
+        )
+        .hint(format!("available binaries: {}", available.join(", ")))),
+    }
+}
@@ -0,0 +1,516 @@
+//! `c1 export bootstrap`: standalone scripts that fetch locked dependencies.
+//!
+//! The generated scripts reproduce the external/ layout `c1 sync` creates,
+//! using only git, so a project can be built where c1 is not installed. A
+//! dependency with a `subdir` is checked out sparsely, as `c1 sync` does.
+//! Archive dependencies are downloaded with curl (Invoke-WebRequest), checked
+//! against the SHA-256 in project.toml and extracted as `c1 sync` does, with
+//! the same marker, so a later `c1 sync` keeps them. Output is fully
+//! determined by project.lock and those entries (sorted, no timestamps) so
+//! the scripts can be committed and checked for staleness by `c1 verify`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::archive;
+use crate::error::{self, Context, Error};
+use crate::lockfile::{LockedPackage, Lockfile};
+use crate::log;
+use crate::safe_path;
+use crate::secrets::{self, Segment};
+use crate::subdir;
+
+pub const SH_SCRIPT: &str = "bootstrap.sh";
+pub const PS1_SCRIPT: &str = "bootstrap.ps1";
+
+/// An archive dependency as the scripts fetch it
+struct Archive {
+    name: String,
+    url: String,
+    sha256: String,
+    /// `.tar.gz`, `.zip`, ...
+    extension: &'static str,
+}
+
+/// The archive dependencies project.toml declares, sorted by name. One
+/// without a SHA-256 cannot be verified, so it is refused as `c1 sync`
+/// refuses it rather than left out of the scripts.
+fn archives() -> Result<Vec<Archive>, String> {
+    let Ok(config) = crate::read_config() else {
+        return Ok(Vec::new());
+    };
+    let mut archives = Vec::new();
+    for (name, value) in config.all_dependencies() {
+        let Some(url) = value.get("url").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let Some(sha256) = value.get("sha256").and_then(|v| v.as_str()) else {
+            return Err(format!(
+                "{} has no sha256 in project.toml; run `c1 add {} --archive --name {}` to record it",
+                name,
+                secrets::redact(url),
+                name
+            ));
+        };
+        let Some(extension) = archive::extension(url) else {
+            return Err(format!(
+                "{} is not an archive the bootstrap scripts can extract: {}",
+                name,
+                secrets::redact(url)
+            ));
+        };
+        archives.push(Archive {
+            name,
+            url: url.to_string(),
+            sha256: sha256.to_ascii_lowercase(),
+            extension,
+        });
+    }
+    archives.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(archives)
+}
+
+/// The `subdir` of each git dependency project.toml declares one for
+fn subdirs() -> BTreeMap<String, String> {
+    let Ok(config) = crate::read_config() else {
+        return BTreeMap::new();
+    };
+    config
+        .all_dependencies()
+        .iter()
+        .filter_map(|(name, value)| {
+            let subdir = subdir::of(value)?.trim_matches('/');
+            Some((name.clone(), subdir.to_string()))
+        })
+        .collect()
+}
+
+/// Write bootstrap.sh and bootstrap.ps1 into `output_dir`
+pub fn cmd_export_bootstrap(output_dir: &str) -> error::Result<()> {
+    let packages = load_locked_packages()?;
+    let archives = archives().map_err(Error::config)?;
+    let subdirs = subdirs();
+
+    fs::create_dir_all(output_dir).context(|| format!("failed to create {}", output_dir))?;
+
+    for (file, content) in [
+        (SH_SCRIPT, render_sh(&packages, &subdirs, &archives)),
+        (PS1_SCRIPT, render_ps1(&packages, &subdirs, &archives)),
+    ] {
+        let path = Path::new(output_dir).join(file);
+        safe_path::write(&path, content)
+            .context(|| format!("failed to write {}", path.display()))?;
+        log::status!("✓ Wrote {}", path.display());
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let path = Path::new(output_dir).join(SH_SCRIPT);
+        let _ = fs::set_permissions(&path, fs::Permissions::from_mode(0o755));
+    }
+
+    log::status!(
+        "Run {} before `cmake -B build` to fetch {} dependencies without c1",
+        Path::new(output_dir).join(SH_SCRIPT).display(),
+        packages.len() + archives.len()
+    );
+    Ok(())
+}
+
+/// Compare committed bootstrap scripts against what the lockfile produces.
+///
+/// Returns the names of scripts that are stale. Scripts that were never
+/// exported are not reported.
+pub fn stale_scripts() -> Vec<&'static str> {
+    let packages = match Lockfile::load() {
+        Ok(Some(lock)) => lock.packages,
+        _ => Vec::new(),
+    };
+    let archives = archives().unwrap_or_default();
+    let subdirs = subdirs();
+
+    let mut stale = Vec::new();
+    for (file, expected) in [
+        (SH_SCRIPT, render_sh(&packages, &subdirs, &archives)),
+        (PS1_SCRIPT, render_ps1(&packages, &subdirs, &archives)),
+    ] {
+        if let Ok(actual) = fs::read_to_string(file)
+            && actual != expected
+        {
+            stale.push(file);
+        }
+    }
+    stale
+}
+
+fn load_locked_packages() -> error::Result<Vec<LockedPackage>> {
+    match Lockfile::load().context(|| "failed to read project.lock".to_string())? {
+        Some(lock) => Ok(lock.packages),
+        None => {
+            Err(Error::usage("project.lock not found")
+                .hint("run `c1 sync` first to lock dependencies"))
+        }
+    }
+}
+
+fn render_sh(
+    packages: &[LockedPackage],
+    subdirs: &BTreeMap<String, String>,
+    archives: &[Archive],
+) -> String {
+    let mut script = String::from(
+        r#"#!/bin/sh
+# Generated by `c1 export bootstrap` from project.lock. Do not edit.
+# Fetches every dependency into external/ at its locked commit.
+set -eu
+cd "$(dirname "$0")"
+mkdir -p external
+
+fetch() {
+    name="$1"
+    url="$2"
+    commit="$3"
+    subdir="${4:-}"
+    dir="external/$name"
+    if [ ! -d "$dir/.git" ]; then
+        rm -rf "$dir"
+        if [ -n "$subdir" ]; then
+            git clone --quiet --sparse "$url" "$dir"
+        else
+            git clone --quiet "$url" "$dir"
+        fi
+    fi
+    git -C "$dir" fetch --quiet origin "$commit" 2>/dev/null || git -C "$dir" fetch --quiet origin
+    git -C "$dir" checkout --quiet --detach "$commit"
+    if [ -n "$subdir" ]; then
+        git -C "$dir" sparse-checkout set "$subdir"
+    elif [ "$(git -C "$dir" config --get core.sparseCheckout || true)" = true ]; then
+        git -C "$dir" sparse-checkout disable
+    fi
+    git -C "$dir" submodule update --quiet --init --recursive
+    echo "fetched $name at $commit"
+}
+"#,
+    );
+    if !archives.is_empty() {
+        script.push_str(
+            r#"
+fetch_archive() {
+    name="$1"
+    url="$2"
+    sha256="$3"
+    ext="$4"
+    dir="external/$name"
+    if [ -f "$dir/.c1-archive" ] && [ "$(cat "$dir/.c1-archive")" = "$sha256" ]; then
+        echo "$name already extracted"
+        return
+    fi
+    tmp="external/.$name.download"
+    rm -rf "$tmp"
+    mkdir -p "$tmp/files"
+    curl -fsSL -o "$tmp/archive$ext" "$url"
+    actual=$({ sha256sum "$tmp/archive$ext" 2>/dev/null || shasum -a 256 "$tmp/archive$ext"; } | cut -d ' ' -f 1)
+    if [ "$actual" != "$sha256" ]; then
+        rm -rf "$tmp"
+        echo "checksum mismatch for $name: expected sha256 $sha256, got $actual" >&2
+        exit 1
+    fi
+    case "$ext" in
+        .zip) unzip -q "$tmp/archive$ext" -d "$tmp/files" ;;
+        *) tar -xf "$tmp/archive$ext" -C "$tmp/files" ;;
+    esac
+    # Most archives hold one directory named after the release
+    root="$tmp/files"
+    if [ "$(ls -A "$tmp/files" | wc -l)" -eq 1 ]; then
+        only="$tmp/files/$(ls -A "$tmp/files")"
+        if [ -d "$only" ] && [ ! -L "$only" ]; then
+            root="$only"
+        fi
+    fi
+    echo "$sha256" > "$root/.c1-archive"
+    rm -rf "$dir"
+    mv "$root" "$dir"
+    rm -rf "$tmp"
+    echo "extracted $name (sha256 $sha256)"
+}
+"#,
+        );
+    }
+    script.push('\n');
+    for package in packages {
+        script.push_str(&format!(
+            "fetch {} {} {}",
+            sh_quote(&package.name),
+            sh_url(&package.git),
+            sh_quote(&package.commit)
+        ));
+        if let Some(subdir) = subdirs.get(&package.name) {
+            script.push_str(&format!(" {}", sh_quote(subdir)));
+        }
+        script.push('\n');
+    }
+    for archive in archives {
+        script.push_str(&format!(
+            "fetch_archive {} {} {} {}\n",
+            sh_quote(&archive.name),
+            sh_url(&archive.url),
+            sh_quote(&archive.sha256),
+            sh_quote(archive.extension)
+        ));
+    }
+    script
+}
+
+fn render_ps1(
+    packages: &[LockedPackage],
+    subdirs: &BTreeMap<String, String>,
+    archives: &[Archive],
+) -> String {
+    let mut script = String::from(
+        r#"# Generated by `c1 export bootstrap` from project.lock. Do not edit.
+# Fetches every dependency into external/ at its locked commit.
+$ErrorActionPreference = 'Stop'
+Set-Location -Path $PSScriptRoot
+New-Item -ItemType Directory -Force -Path external | Out-Null
+
+function Fetch-Dependency([string]$Name, [string]$Url, [string]$Commit, [string]$Subdir = '') {
+    $dir = Join-Path 'external' $Name
+    if (-not (Test-Path (Join-Path $dir '.git'))) {
+        if (Test-Path $dir) { Remove-Item -Recurse -Force $dir }
+        if ($Subdir) { git clone --quiet --sparse $Url $dir } else { git clone --quiet $Url $dir }
+        if ($LASTEXITCODE -ne 0) { throw "failed to clone $Name" }
+    }
+    git -C $dir fetch --quiet origin $Commit 2>$null
+    if ($LASTEXITCODE -ne 0) { git -C $dir fetch --quiet origin }
+    git -C $dir checkout --quiet --detach $Commit
+    if ($LASTEXITCODE -ne 0) { throw "failed to check out $Commit for $Name" }
+    if ($Subdir) {
+        git -C $dir sparse-checkout set $Subdir
+        if ($LASTEXITCODE -ne 0) { throw "failed to check out $Subdir of $Name" }
+    } elseif ((git -C $dir config --get core.sparseCheckout) -eq 'true') {
+        git -C $dir sparse-checkout disable
+    }
+    git -C $dir submodule update --quiet --init --recursive
+    Write-Output "fetched $Name at $Commit"
+}
+"#,
+    );
+    if !archives.is_empty() {
+        script.push_str(
+            r#"
+function Fetch-Archive([string]$Name, [string]$Url, [string]$Sha256, [string]$Extension) {
+    $dir = Join-Path 'external' $Name
+    $marker = Join-Path $dir '.c1-archive'
+    if ((Test-Path $marker) -and ((Get-Content -Raw $marker).Trim() -eq $Sha256)) {
+        Write-Output "$Name already extracted"
+        return
+    }
+    $tmp = Join-Path 'external' ".$Name.download"
+    if (Test-Path $tmp) { Remove-Item -Recurse -Force $tmp }
+    $files = Join-Path $tmp 'files'
+    New-Item -ItemType Directory -Force -Path $files | Out-Null
+    $archive = Join-Path $tmp "archive$Extension"
+    Invoke-WebRequest -UseBasicParsing -Uri $Url -OutFile $archive
+    $actual = (Get-FileHash -Algorithm SHA256 $archive).Hash.ToLowerInvariant()
+    if ($actual -ne $Sha256) {
+        Remove-Item -Recurse -Force $tmp
+        throw "checksum mismatch for ${Name}: expected sha256 $Sha256, got $actual"
+    }
+    if ($Extension -eq '.zip') {
+        Expand-Archive -Path $archive -DestinationPath $files
+    } else {
+        tar -xf $archive -C $files
+        if ($LASTEXITCODE -ne 0) { throw "failed to extract $Name" }
+    }
+    # Most archives hold one directory named after the release
+    $entries = @(Get-ChildItem -Force $files)
+    $root = $files
+    if ($entries.Count -eq 1 -and $entries[0].PSIsContainer) { $root = $entries[0].FullName }
+    [System.IO.File]::WriteAllText((Join-Path $root '.c1-archive'), "$Sha256`n")
+    if (Test-Path $dir) { Remove-Item -Recurse -Force $dir }
+    Move-Item $root $dir
+    if (Test-Path $tmp) { Remove-Item -Recurse -Force $tmp }
+    Write-Output "extracted $Name (sha256 $Sha256)"
+}
+"#,
+        );
+    }
+    script.push('\n');
+    for package in packages {
+        script.push_str(&format!(
+            "Fetch-Dependency {} {} {}",
+            ps_quote(&package.name),
+            ps_url(&package.git),
+            ps_quote(&package.commit)
+        ));
+        if let Some(subdir) = subdirs.get(&package.name) {
+            script.push_str(&format!(" {}", ps_quote(subdir)));
+        }
+        script.push('\n');
+    }
+    for archive in archives {
+        script.push_str(&format!(
+            "Fetch-Archive {} {} {} {}\n",
+            ps_quote(&archive.name),
+            ps_url(&archive.url),
+            ps_quote(&archive.sha256),
+            ps_quote(archive.extension)
+        ));
+    }
+    script
+}
+
+/// Quote a URL, leaving `${NAME}` placeholders to be expanded by the shell
+fn sh_url(url: &str) -> String {
+    secrets::segments(url)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Literal(text) => sh_quote(text),
+            Segment::Var(name) => format!("\"${{{}}}\"", name),
+        })
+        .collect()
+}
+
+/// Build a URL expression that reads `${NAME}` placeholders from `$env:NAME`
+fn ps_url(url: &str) -> String {
+    let parts: Vec<String> = secrets::segments(url)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Literal(text) => ps_quote(text),
+            Segment::Var(name) => format!("$env:{}", name),
+        })
+        .collect();
+    match parts.len() {
+        0 => "''".to_string(),
+        1 => parts.into_iter().next().unwrap(),
+        _ => format!("({})", parts.join(" + ")),
+    }
+}
+
+fn sh_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn ps_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::process::Command;
+
+    use super::{Archive, render_ps1, render_sh};
+    use crate::sha256::Sha256;
+    use crate::test_support::TempDir;
+
+    fn tarball(tmp: &TempDir) -> (String, String) {
+        tmp.write("src/libfoo-1.0/include/foo.h", "int foo(void);\n");
+        tmp.write("src/libfoo-1.0/src/foo.c", "int foo(void) { return 1; }\n");
+        let path = tmp.path().join("libfoo-1.0.tar.gz");
+        let status = Command::new("tar")
+            .arg("-czf")
+            .arg(&path)
+            .arg("-C")
+            .arg(tmp.path().join("src"))
+            .arg("libfoo-1.0")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let mut hasher = Sha256::new();
+        hasher.update(&fs::read(&path).unwrap());
+        (format!("file://{}", path.display()), hasher.finish_hex())
+    }
+
+    fn archive(url: &str, sha256: &str) -> Archive {
+        Archive {
+            name: "libfoo".to_string(),
+            url: url.to_string(),
+            sha256: sha256.to_string(),
+            extension: ".tar.gz",
+        }
+    }
+
+    fn run(tmp: &TempDir, script: &str) -> std::process::Output {
+        let project = tmp.path().join("project");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("bootstrap.sh"), script).unwrap();
+        Command::new("sh")
+            .arg("bootstrap.sh")
+            .current_dir(&project)
+            .output()
+            .unwrap()
+    }
+
+    #[test]
+    fn scripts_fetch_archive_dependencies() {
+        let archives = [archive("https://example.org/libfoo-1.0.tar.gz", "ab12")];
+        let sh = render_sh(&[], &BTreeMap::new(), &archives);
+        assert!(sh.contains(
+            "fetch_archive 'libfoo' 'https://example.org/libfoo-1.0.tar.gz' 'ab12' '.tar.gz'\n"
+        ));
+        let ps1 = render_ps1(&[], &BTreeMap::new(), &archives);
+        assert!(ps1.contains(
+            "Fetch-Archive 'libfoo' 'https://example.org/libfoo-1.0.tar.gz' 'ab12' '.tar.gz'\n"
+        ));
+        assert!(!render_sh(&[], &BTreeMap::new(), &[]).contains("fetch_archive"));
+    }
+
+    #[test]
+    fn sh_script_extracts_a_verified_archive() {
+        if !cfg!(unix) || Command::new("curl").arg("--version").output().is_err() {
+            eprintln!("skipping: needs sh and curl");
+            return;
+        }
+        let tmp = TempDir::new("bootstrap-archive");
+        let (url, sha256) = tarball(&tmp);
+        let output = run(
+            &tmp,
+            &render_sh(&[], &BTreeMap::new(), &[archive(&url, &sha256)]),
+        );
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        let dir = tmp.path().join("project/external/libfoo");
+        assert!(dir.join("include/foo.h").is_file());
+        assert!(dir.join("src/foo.c").is_file());
+        assert_eq!(
+            fs::read_to_string(dir.join(".c1-archive")).unwrap(),
+            format!("{}\n", sha256)
+        );
+        assert!(
+            !tmp.path()
+                .join("project/external/.libfoo.download")
+                .exists()
+        );
+
+        // The marker matches, so the second run does not download again
+        let output = run(
+            &tmp,
+            &render_sh(&[], &BTreeMap::new(), &[archive(&url, &sha256)]),
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).contains("libfoo already extracted"));
+    }
+
+    #[test]
+    fn sh_script_refuses_a_checksum_mismatch() {
+        if !cfg!(unix) || Command::new("curl").arg("--version").output().is_err() {
+            eprintln!("skipping: needs sh and curl");
+            return;
+        }
+        let tmp = TempDir::new("bootstrap-mismatch");
+        let (url, _) = tarball(&tmp);
+        let output = run(
+            &tmp,
+            &render_sh(&[], &BTreeMap::new(), &[archive(&url, &"0".repeat(64))]),
+        );
+        assert!(!output.status.success());
+        assert!(String::from_utf8_lossy(&output.stderr).contains("checksum mismatch for libfoo"));
+        assert!(!tmp.path().join("project/external/libfoo").exists());
+    }
+}
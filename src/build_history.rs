@@ -0,0 +1,447 @@
+//! Local build history behind `c1 stats builds`.
+//!
+//! Every `c1 build` appends one JSON object per line to `.c1/stats.jsonl`:
+//! when it ran, the profile, how long configure and compile took, warning and
+//! error counts, recompiled units, compiler cache hits and the size of the
+//! artifact.
+//! The report is computed from that file alone; nothing is ever sent anywhere.
+//!
+//! The archive is rewritten through a temporary file and a rename, so a killed
+//! build never leaves half a record behind, and is capped at `MAX_BYTES` by
+//! dropping the oldest records. Lines that still fail to parse (hand edits,
+//! records from a future version) are skipped and counted.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error;
+use crate::json::escape;
+use crate::log;
+
+const ARCHIVE_PATH: &str = ".c1/stats.jsonl";
+/// Roughly a few thousand builds
+const MAX_BYTES: usize = 512 * 1024;
+const WEEK: u64 = 7 * 24 * 60 * 60;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+pub struct Record {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// "debug" or "release"
+    pub profile: String,
+    pub success: bool,
+    pub configure_ms: u64,
+    pub build_ms: u64,
+    pub warnings: u64,
+    pub errors: u64,
+    pub recompiled: u64,
+    pub units: u64,
+    pub cache_hits: Option<u64>,
+    pub cache_misses: Option<u64>,
+    pub artifact_bytes: Option<u64>,
+}
+
+impl Record {
+    fn to_json(&self) -> String {
+        let optional = |value: Option<u64>| value.map_or("null".to_string(), |v| v.to_string());
+        format!(
+            "{{\"timestamp\":{},\"profile\":\"{}\",\"success\":{},\"configure_ms\":{},\"build_ms\":{},\"warnings\":{},\"errors\":{},\"recompiled\":{},\"units\":{},\"cache_hits\":{},\"cache_misses\":{},\"artifact_bytes\":{}}}",
+            self.timestamp,
+            escape(&self.profile),
+            self.success,
+            self.configure_ms,
+            self.build_ms,
+            self.warnings,
+            self.errors,
+            self.recompiled,
+            self.units,
+            optional(self.cache_hits),
+            optional(self.cache_misses),
+            optional(self.artifact_bytes)
+        )
+    }
+
+    fn from_json(line: &str) -> Option<Record> {
+        let fields = parse_object(line)?;
+        let get = |key: &str| fields.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+        let number = |key: &str| match get(key)? {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        };
+        let optional = |key: &str| match get(key) {
+            Some(Value::Number(n)) => Some(Some(*n)),
+            Some(Value::Null) | None => Some(None),
+            _ => None,
+        };
+        Some(Record {
+            timestamp: number("timestamp")?,
+            profile: match get("profile")? {
+                Value::String(s) => s.clone(),
+                _ => return None,
+            },
+            success: match get("success")? {
+                Value::Bool(b) => *b,
+                _ => return None,
+            },
+            configure_ms: number("configure_ms")?,
+            build_ms: number("build_ms")?,
+            warnings: number("warnings")?,
+            errors: number("errors")?,
+            recompiled: number("recompiled")?,
+            units: number("units")?,
+            cache_hits: optional("cache_hits")?,
+            cache_misses: optional("cache_misses")?,
+            artifact_bytes: optional("artifact_bytes")?,
+        })
+    }
+
+    fn duration_ms(&self) -> u64 {
+        self.configure_ms + self.build_ms
+    }
+}
+
+/// Seconds since the Unix epoch, for `Record::timestamp`
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn millis(duration: Duration) -> u64 {
+    duration.as_millis().try_into().unwrap_or(u64::MAX)
+}
+
+/// Append a record to the archive. Failing to record never fails the build.
+pub fn append(record: &Record) {
+    let path = Path::new(ARCHIVE_PATH);
+    let mut lines: Vec<String> = fs::read_to_string(path)
+        .map(|content| content.lines().map(str::to_string).collect())
+        .unwrap_or_default();
+    lines.push(record.to_json());
+
+    // Oldest records go first once the archive outgrows its cap
+    let mut size: usize = lines.iter().map(|l| l.len() + 1).sum();
+    let mut drop = 0;
+    while size > MAX_BYTES && drop + 1 < lines.len() {
+        size -= lines[drop].len() + 1;
+        drop += 1;
+    }
+    let mut content = lines[drop..].join("\n");
+    content.push('\n');
+
+    if let Err(e) = write_atomic(path, &content) {
+        log::problem!("Warning: could not record build stats: {}", e);
+    }
+}
+
+fn write_atomic(path: &Path, content: &str) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+/// Valid records of the archive under `dir`, oldest first, and the number of
+/// lines that could not be parsed
+fn load(dir: &Path) -> (Vec<(Record, String)>, usize) {
+    let Ok(content) = fs::read_to_string(dir.join(ARCHIVE_PATH)) else {
+        return (Vec::new(), 0);
+    };
+    let mut records = Vec::new();
+    let mut skipped = 0;
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        match Record::from_json(line) {
+            Some(record) => records.push((record, line.trim().to_string())),
+            None => skipped += 1,
+        }
+    }
+    (records, skipped)
+}
+
+/// `c1 stats builds`: trends over the last `last` builds of this project, or
+/// of every member at a workspace root
+pub fn cmd_stats_builds(last: usize, json: bool) -> error::Result<()> {
+    let projects: Vec<(String, PathBuf)> = match crate::workspace::load()? {
+        Some(members) => members.into_iter().map(|m| (m.name, m.path)).collect(),
+        None => vec![(String::new(), PathBuf::from("."))],
+    };
+
+    if json {
+        print_json(&projects, last);
+        return Ok(());
+    }
+
+    for (i, (name, dir)) in projects.iter().enumerate() {
+        if !name.is_empty() {
+            if i > 0 {
+                println!();
+            }
+            println!("{}:", name);
+        }
+        let (records, skipped) = load(dir);
+        let start = records.len().saturating_sub(last);
+        report(&records[start..], &records, skipped);
+    }
+    Ok(())
+}
+
+fn print_json(projects: &[(String, PathBuf)], last: usize) {
+    let workspace = projects.iter().any(|(name, _)| !name.is_empty());
+    let mut objects = Vec::new();
+    let mut skipped_total = 0;
+    for (name, dir) in projects {
+        let (records, skipped) = load(dir);
+        skipped_total += skipped;
+        let start = records.len().saturating_sub(last);
+        for (_, raw) in &records[start..] {
+            if workspace {
+                // Tag each record with its member so they stay apart
+                objects.push(format!("{{\"member\":\"{}\",{}", escape(name), &raw[1..]));
+            } else {
+                objects.push(raw.clone());
+            }
+        }
+    }
+    println!("[{}]", objects.join(",\n"));
+    if skipped_total > 0 {
+        log::problem!(
+            "Warning: skipped {} corrupted line(s) in {}",
+            skipped_total,
+            ARCHIVE_PATH
+        );
+    }
+}
+
+fn report(window: &[(Record, String)], all: &[(Record, String)], skipped: usize) {
+    if window.is_empty() {
+        println!("No builds recorded yet; run `c1 build` first.");
+        if skipped > 0 {
+            println!("({} corrupted line(s) skipped)", skipped);
+        }
+        return;
+    }
+    let records: Vec<&Record> = window.iter().map(|(r, _)| r).collect();
+    let latest = records[records.len() - 1];
+    let failed = records.iter().filter(|r| !r.success).count();
+
+    let mut heading = format!("Last {} builds", records.len());
+    if failed > 0 {
+        heading.push_str(&format!(" ({} failed)", failed));
+    }
+    println!("{}:", heading);
+
+    let durations: Vec<u64> = records.iter().map(|r| r.duration_ms()).collect();
+    let mut sorted = durations.clone();
+    sorted.sort_unstable();
+    println!(
+        "  Duration  {}  last {}, median {} (configure {}, compile {})",
+        sparkline(&durations),
+        seconds(latest.duration_ms()),
+        seconds(sorted[sorted.len() / 2]),
+        seconds(latest.configure_ms),
+        seconds(latest.build_ms)
+    );
+
+    let warnings: Vec<u64> = records.iter().map(|r| r.warnings).collect();
+    let first = warnings[0];
+    println!(
+        "  Warnings  {}  {} -> {} ({})",
+        sparkline(&warnings),
+        first,
+        latest.warnings,
+        signed(latest.warnings as i64 - first as i64)
+    );
+
+    if let Some(size) = latest.artifact_bytes {
+        // Compare with the newest build that is at least a week old
+        let week_ago = latest.timestamp.saturating_sub(WEEK);
+        let baseline = all
+            .iter()
+            .map(|(r, _)| r)
+            .rfind(|r| r.timestamp <= week_ago && r.artifact_bytes.is_some());
+        match baseline.and_then(|r| r.artifact_bytes) {
+            Some(before) => println!(
+                "  Size      {} ({} since a week ago)",
+                bytes(size),
+                signed_bytes(size as i64 - before as i64)
+            ),
+            None => println!("  Size      {} (no build from a week ago)", bytes(size)),
+        }
+    }
+
+    let (hits, misses) = records.iter().fold((0, 0), |(h, m), r| {
+        (
+            h + r.cache_hits.unwrap_or(0),
+            m + r.cache_misses.unwrap_or(0),
+        )
+    });
+    if hits + misses > 0 {
+        println!(
+            "  Cache     {:.0}% hit rate ({} hits / {} misses)",
+            hits as f64 * 100.0 / (hits + misses) as f64,
+            hits,
+            misses
+        );
+    }
+    println!(
+        "  Last build: {} profile, recompiled {}/{} units, {} error(s)",
+        latest.profile, latest.recompiled, latest.units, latest.errors
+    );
+    if skipped > 0 {
+        println!("  ({} corrupted line(s) skipped)", skipped);
+    }
+}
+
+fn sparkline(values: &[u64]) -> String {
+    let min = values.iter().copied().min().unwrap_or(0);
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|&v| {
+            if max == min {
+                SPARKS[0]
+            } else {
+                SPARKS[((v - min) * (SPARKS.len() as u64 - 1) / (max - min)) as usize]
+            }
+        })
+        .collect()
+}
+
+fn seconds(ms: u64) -> String {
+    format!("{:.2}s", ms as f64 / 1000.0)
+}
+
+pub fn signed(delta: i64) -> String {
+    if delta > 0 {
+        format!("+{}", delta)
+    } else {
+        delta.to_string()
+    }
+}
+
+pub fn bytes(n: u64) -> String {
+    if n >= 1024 * 1024 {
+        format!("{:.1} MiB", n as f64 / (1024.0 * 1024.0))
+    } else if n >= 1024 {
+        format!("{:.1} KiB", n as f64 / 1024.0)
+    } else {
+        format!("{} B", n)
+    }
+}
+
+fn signed_bytes(delta: i64) -> String {
+    let sign = if delta > 0 {
+        "+"
+    } else if delta < 0 {
+        "-"
+    } else {
+        ""
+    };
+    format!("{}{}", sign, bytes(delta.unsigned_abs()))
+}
+
+pub enum Value {
+    Number(u64),
+    String(String),
+    Bool(bool),
+    Null,
+}
+
+/// Parse the flat objects this module writes: string keys mapping to
+/// unsigned integers, strings, booleans or null
+pub fn parse_object(line: &str) -> Option<Vec<(String, Value)>> {
+    let mut chars = line.trim().chars().peekable();
+    let mut fields = Vec::new();
+    if chars.next()? != '{' {
+        return None;
+    }
+    loop {
+        skip_whitespace(&mut chars);
+        match chars.next()? {
+            '}' if fields.is_empty() => break,
+            '"' => {}
+            _ => return None,
+        }
+        let key = parse_string(&mut chars)?;
+        skip_whitespace(&mut chars);
+        if chars.next()? != ':' {
+            return None;
+        }
+        skip_whitespace(&mut chars);
+        let value = match *chars.peek()? {
+            '"' => {
+                chars.next();
+                Value::String(parse_string(&mut chars)?)
+            }
+            c if c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(&c) = chars.peek()
+                    && c.is_ascii_digit()
+                {
+                    digits.push(c);
+                    chars.next();
+                }
+                Value::Number(digits.parse().ok()?)
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek()
+                    && c.is_ascii_alphabetic()
+                {
+                    word.push(c);
+                    chars.next();
+                }
+                match word.as_str() {
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    "null" => Value::Null,
+                    _ => return None,
+                }
+            }
+        };
+        fields.push((key, value));
+        skip_whitespace(&mut chars);
+        match chars.next()? {
+            ',' => {}
+            '}' => break,
+            _ => return None,
+        }
+    }
+    skip_whitespace(&mut chars);
+    chars.next().is_none().then_some(fields)
+}
+
+fn skip_whitespace(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+/// Read a string body after its opening quote
+fn parse_string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<String> {
+    let mut out = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(out),
+            '\\' => match chars.next()? {
+                '"' => out.push('"'),
+                '\\' => out.push('\\'),
+                '/' => out.push('/'),
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    out.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                }
+                _ => return None,
+            },
+            c => out.push(c),
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! `build/c1-build.log`: every command a build ran, for comparing machines
+//! and for `c1 build --replay-last`.
+//!
+//! Each `c1 build` (and each command that builds first) appends a header
+//! with the c1 command line, the time and the project directory, then one
+//! `$ (cd <dir>) <command>` line per external command, as `--show-commands`
+//! prints it, followed by its exit code and duration. Credentials are
+//! redacted as on the terminal. Past `MAX_SIZE` the log moves to
+//! `c1-build.log.1`, replacing the one before. Failing to write it never
+//! fails the build.
+//!
+//! `--replay-last` runs the commands of the last build recorded again, in
+//! their directories and as written, without any of c1's own steps, and
+//! stops at the first whose exit code differs from the recorded one.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{self, Error};
+use crate::{log, secrets, version_header};
+
+const FILE: &str = "c1-build.log";
+const MAX_SIZE: u64 = 1024 * 1024;
+const HEADER: &str = "== ";
+const COMMAND: &str = "$ (cd ";
+const EXIT: &str = "  exit ";
+
+/// The log of the build running in this process
+static PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// Recording goes on until this is dropped
+pub struct Session;
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Ok(mut current) = PATH.lock() {
+            *current = None;
+        }
+    }
+}
+
+/// Start recording a build whose directories are under `build_root`
+pub fn start(build_root: &Path) -> Session {
+    let path = build_root.join(FILE);
+    if fs::metadata(&path).is_ok_and(|m| m.len() > MAX_SIZE) {
+        let _ = fs::rename(&path, build_root.join(format!("{}.1", FILE)));
+    }
+    if let Ok(mut current) = PATH.lock() {
+        *current = Some(path);
+    }
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let dir = std::env::current_dir().unwrap_or_default();
+    append(&format!(
+        "\n{}c1 {} at {} in {}\n",
+        HEADER,
+        secrets::redact(&args.join(" ")),
+        version_header::iso8601(secs),
+        dir.display()
+    ));
+    Session
+}
+
+/// Record a command about to run, as `log::command` shows it
+pub fn command(dir: &str, line: &str) {
+    append(&format!("{}{}) {}\n", COMMAND, dir, line));
+}
+
+/// Record how the last command recorded ended; `None` when it did not start
+/// or was killed by a signal
+pub fn finished(code: Option<i32>, elapsed: Duration) {
+    let code = code.map_or("-".to_string(), |code| code.to_string());
+    append(&format!(
+        "{}{} after {:.2}s\n",
+        EXIT,
+        code,
+        elapsed.as_secs_f64()
+    ));
+}
+
+fn append(text: &str) {
+    let Some(path) = PATH.lock().ok().and_then(|path| path.clone()) else {
+        return;
+    };
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| OpenOptions::new().create(true).append(true).open(&path))
+        .and_then(|mut file| file.write_all(text.as_bytes()));
+    if let Err(e) = written
+        && !WARNED.swap(true, Ordering::Relaxed)
+    {
+        log::verbose!("Could not write {}: {}", path.display(), e);
+    }
+}
+
+/// One command of a recorded build
+struct Recorded {
+    dir: PathBuf,
+    line: String,
+    args: Vec<String>,
+    code: Option<i32>,
+}
+
+/// The header and commands of the last build in the log
+fn last_build(content: &str) -> Option<(&str, Vec<Recorded>)> {
+    let start = content.rfind(&format!("\n{}", HEADER))? + 1;
+    let mut lines = content[start..].lines();
+    let header = lines.next()?.trim_start_matches(HEADER);
+    let mut commands: Vec<Recorded> = Vec::new();
+    for line in lines {
+        if let Some(rest) = line.strip_prefix(COMMAND) {
+            let mut words = crate::split_command(rest).ok()?;
+            if words.len() < 2 {
+                continue;
+            }
+            let dir = words.remove(0);
+            let line = rest[rest.find(") ")? + 2..].to_string();
+            commands.push(Recorded {
+                dir: PathBuf::from(dir.trim_end_matches(')')),
+                line,
+                args: words,
+                code: None,
+            });
+        } else if let Some(rest) = line.strip_prefix(EXIT)
+            && let Some(last) = commands.last_mut()
+        {
+            last.code = rest.split_whitespace().next().and_then(|c| c.parse().ok());
+        }
+    }
+    Some((header, commands))
+}
+
+pub fn cmd_replay(out_dir: Option<&str>) -> error::Result<()> {
+    let path = crate::resolve_build_dir(out_dir).join(FILE);
+    let content = fs::read_to_string(&path).unwrap_or_default();
+    let Some((header, commands)) = last_build(&content).filter(|(_, c)| !c.is_empty()) else {
+        return Err(
+            Error::usage(format!("no build is recorded in {}", path.display()))
+                .hint("run `c1 build` first"),
+        );
+    };
+    log::status!(
+        "Replaying {} command(s) of `{}` from {}",
+        commands.len(),
+        header,
+        path.display()
+    );
+    for recorded in &commands {
+        log::status!("$ (cd {}) {}", recorded.dir.display(), recorded.line);
+        let mut command = Command::new(&recorded.args[0]);
+        command.args(&recorded.args[1..]).current_dir(&recorded.dir);
+        let code = command
+            .status()
+            .map_err(|e| Error::failure(format!("failed to run {}: {}", recorded.args[0], e)))?
+            .code();
+        // A command whose end was not recorded is taken as it comes
+        if recorded.code.is_some() && code != recorded.code {
+            let shown = |code: Option<i32>| code.map_or("-".to_string(), |c| c.to_string());
+            return Err(Error::build(format!(
+                "{} exited with {}, where the recorded build got {}",
+                recorded.args[0],
+                shown(code),
+                shown(recorded.code)
+            ))
+            .hint("the difference is in the tools or the files, not in what c1 decided to run"));
+        }
+    }
+    log::status!(
+        "✓ Replayed {} command(s) with the recorded results",
+        commands.len()
+    );
+    Ok(())
+}
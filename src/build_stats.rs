@@ -0,0 +1,283 @@
+//! Recompilation statistics reported after each build.
+//!
+//! Object file mtimes are snapshotted before and after `cmake --build`, which
+//! works the same for every generator: an object whose mtime moved was
+//! recompiled. The `.d` file next to each rebuilt object tells us which of its
+//! inputs changed, and counting how many translation units include each input
+//! makes a header that is rewritten on every build stand out.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::log;
+use crate::{compiler_cache, depfile};
+
+/// Captures the state of the build tree right before compiling
+pub struct Tracker {
+    build_dir: PathBuf,
+    objects: BTreeMap<PathBuf, SystemTime>,
+    cache_before: Option<CacheStats>,
+    started: Instant,
+}
+
+pub struct Report {
+    recompiled: Vec<Recompiled>,
+    up_to_date: usize,
+    triggers: Vec<Trigger>,
+    cache: Option<CacheStats>,
+    duration: Duration,
+}
+
+struct Recompiled {
+    source: String,
+    changed: Vec<PathBuf>,
+}
+
+struct Trigger {
+    path: PathBuf,
+    rebuilt: usize,
+    dependents: usize,
+}
+
+#[derive(Clone, Copy)]
+struct CacheStats {
+    tool: &'static str,
+    hits: u64,
+    misses: u64,
+}
+
+impl Tracker {
+    pub fn start(build_dir: &Path) -> Tracker {
+        let cache_before = compiler_cache::configured(build_dir).and_then(|tool| {
+            let (hits, misses) = compiler_cache::stats(tool)?;
+            Some(CacheStats { tool, hits, misses })
+        });
+        Tracker {
+            build_dir: build_dir.to_path_buf(),
+            objects: object_mtimes(build_dir),
+            cache_before,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn finish(self) -> Report {
+        let duration = self.started.elapsed();
+        let after = object_mtimes(&self.build_dir);
+
+        let mut recompiled = Vec::new();
+        let mut up_to_date = 0;
+        let mut all_deps = Vec::new();
+        let mut trigger_counts: BTreeMap<PathBuf, usize> = BTreeMap::new();
+
+        for (object, mtime) in &after {
+            let deps = depfile::read(&depfile_for(object))
+                .unwrap_or_default()
+                .into_iter()
+                .map(|dep| self.build_dir.join(dep))
+                .collect::<Vec<_>>();
+
+            match self.objects.get(object) {
+                Some(previous) if previous == mtime => up_to_date += 1,
+                previous => {
+                    let changed: Vec<PathBuf> = match previous {
+                        Some(previous) => deps
+                            .iter()
+                            .filter(|dep| modified(dep).is_some_and(|m| m > *previous))
+                            .cloned()
+                            .collect(),
+                        None => Vec::new(),
+                    };
+                    for dep in &changed {
+                        *trigger_counts.entry(dep.clone()).or_insert(0) += 1;
+                    }
+                    recompiled.push(Recompiled {
+                        source: source_name(&self.build_dir, object),
+                        changed,
+                    });
+                }
+            }
+            all_deps.push(deps);
+        }
+
+        let dependents = depfile::reverse_dependency_counts(&all_deps);
+        let mut triggers: Vec<Trigger> = trigger_counts
+            .into_iter()
+            .map(|(path, rebuilt)| Trigger {
+                dependents: dependents.get(&path).copied().unwrap_or(rebuilt),
+                path,
+                rebuilt,
+            })
+            .collect();
+        triggers.sort_by(|a, b| {
+            b.rebuilt
+                .cmp(&a.rebuilt)
+                .then(b.dependents.cmp(&a.dependents))
+        });
+
+        let cache = self.cache_before.and_then(|before| {
+            let (hits, misses) = compiler_cache::stats(before.tool)?;
+            Some(CacheStats {
+                tool: before.tool,
+                hits: hits.saturating_sub(before.hits),
+                misses: misses.saturating_sub(before.misses),
+            })
+        });
+
+        Report {
+            recompiled,
+            up_to_date,
+            triggers,
+            cache,
+            duration,
+        }
+    }
+}
+
+impl Report {
+    pub fn recompiled(&self) -> usize {
+        self.recompiled.len()
+    }
+
+    /// Translation units in the build tree, rebuilt or not
+    pub fn units(&self) -> usize {
+        self.recompiled.len() + self.up_to_date
+    }
+
+    /// Compiler cache hits and misses during this build, when one is in use
+    pub fn cache(&self) -> Option<(u64, u64)> {
+        self.cache.map(|c| (c.hits, c.misses))
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Print a one-line summary, or the full breakdown when `detailed`
+    pub fn print(&self, detailed: bool) {
+        let total = self.recompiled.len() + self.up_to_date;
+        let mut summary = format!(
+            "Recompiled {}/{} translation units ({} up to date)",
+            self.recompiled.len(),
+            total,
+            self.up_to_date
+        );
+        if let Some(cache) = self.cache {
+            summary.push_str(&format!(
+                ", {}: {} hits / {} misses",
+                cache.tool, cache.hits, cache.misses
+            ));
+        }
+        if let Some(top) = self.triggers.first() {
+            summary.push_str(&format!(
+                ", most rebuilds caused by {}",
+                display_path(&top.path)
+            ));
+        }
+        log::status!("{}", summary);
+
+        if !detailed {
+            return;
+        }
+
+        log::status!("  Compile time: {:.2}s", self.duration.as_secs_f64());
+        for unit in &self.recompiled {
+            if unit.changed.is_empty() {
+                log::status!("  rebuilt {} (new object or forced rebuild)", unit.source);
+            } else {
+                let changed: Vec<String> = unit.changed.iter().map(|p| display_path(p)).collect();
+                log::status!(
+                    "  rebuilt {} because of {}",
+                    unit.source,
+                    changed.join(", ")
+                );
+            }
+        }
+        if !self.triggers.is_empty() {
+            log::status!("  Changed inputs by impact:");
+            for trigger in &self.triggers {
+                log::status!(
+                    "    {:<40} forced {} rebuilds, included by {} units",
+                    display_path(&trigger.path),
+                    trigger.rebuilt,
+                    trigger.dependents
+                );
+            }
+        }
+    }
+}
+
+/// Collect the mtime of every object file under the build tree
+fn object_mtimes(build_dir: &Path) -> BTreeMap<PathBuf, SystemTime> {
+    let mut objects = BTreeMap::new();
+    let mut stack = vec![build_dir.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                stack.push(path);
+            } else if matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("o") | Some("obj")
+            ) && let Some(mtime) = modified(&path)
+            {
+                objects.insert(path, mtime);
+            }
+        }
+    }
+    objects
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// `foo.c.o` -> `foo.c.o.d`
+fn depfile_for(object: &Path) -> PathBuf {
+    let mut name = object.as_os_str().to_os_string();
+    name.push(".d");
+    PathBuf::from(name)
+}
+
+/// Turn `build/CMakeFiles/app.dir/src/foo.c.o` back into `src/foo.c`
+fn source_name(build_dir: &Path, object: &Path) -> String {
+    let relative = object.strip_prefix(build_dir).unwrap_or(object);
+    let mut parts = Vec::new();
+    let mut past_target_dir = false;
+    for component in relative.components() {
+        let part = component.as_os_str().to_string_lossy().to_string();
+        if past_target_dir {
+            parts.push(part);
+        } else if part.ends_with(".dir") {
+            past_target_dir = true;
+        }
+    }
+    if parts.is_empty() {
+        parts.push(relative.to_string_lossy().to_string());
+    }
+    let joined = parts.join("/");
+    joined
+        .strip_suffix(".o")
+        .or_else(|| joined.strip_suffix(".obj"))
+        .unwrap_or(&joined)
+        .to_string()
+}
+
+/// Show project files relative to the current directory
+fn display_path(path: &Path) -> String {
+    let normalized = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let cwd = std::env::current_dir()
+        .ok()
+        .and_then(|d| d.canonicalize().ok());
+    match cwd
+        .as_ref()
+        .and_then(|cwd| normalized.strip_prefix(cwd).ok())
+    {
+        Some(relative) => relative.display().to_string(),
+        None => normalized.display().to_string(),
+    }
+}
@@ -0,0 +1,221 @@
+//! `c1 check`: does everything still compile, without cmake or linking.
+//!
+//! Each of the project's sources is compiled with `-fsyntax-only`, in
+//! parallel, with the flags the build would use as far as project.toml
+//! tells them: the `[build] warnings` preset, the edition, `CFLAGS`,
+//! `[build] flags`, the target's and profile's flags, `[build] defines` and
+//! `--define`, the enabled features' macros and sources, the active
+//! platform's implementations, include/, `[build] include_dirs`, the
+//! directory of c1_version.h and the include directories of the
+//! dependencies in external/. The compiler is the one the build would
+//! pick, or the one the build directory was last configured with. Every file
+//! is checked and its diagnostics shown before the verdict, so one run
+//! reports every error. With `--changed`, only the sources that changed since
+//! the last check, or include a header that did, are (see header_deps).
+
+use std::collections::VecDeque;
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use crate::error::{self, Error};
+use crate::{
+    BuildOptions, auto_sources, cross, dep_wiring, features, header_deps, log, platforms, profiles,
+    subdir, version_header,
+};
+
+/// How the build would compile one of the project's sources, as far as
+/// project.toml tells
+pub struct Compilation {
+    pub compiler: String,
+    /// Everything but the source, which goes last
+    pub flags: Vec<String>,
+    /// The sources the enabled features add
+    feature_sources: Vec<String>,
+}
+
+pub fn cmd_check(options: &BuildOptions, jobs: Option<usize>, changed: bool) -> error::Result<()> {
+    let started = Instant::now();
+    let config = crate::read_config()?;
+    let platform = platforms::active_platform(&config, options.target_config.as_deref())
+        .map_err(Error::usage)?;
+    let Compilation {
+        compiler,
+        mut flags,
+        feature_sources,
+    } = compilation(options, &config)?;
+    flags.insert(0, "-fsyntax-only".to_string());
+
+    let (mut sources, _) = auto_sources::scan(&config);
+    sources.extend(feature_sources);
+    if let Some(platform) = &platform {
+        for module in config.platforms.keys() {
+            sources.push(platforms::source_file(module, platform));
+        }
+    }
+    sources.sort();
+    sources.dedup();
+    if sources.is_empty() {
+        log::status!("No sources to check");
+        return Ok(());
+    }
+    let record_path = crate::target_build_dir(options).join(header_deps::CHECK_RECORD);
+    let key = format!("{} {}", compiler, flags.join(" "));
+    let inputs = header_deps::snapshot(&sources);
+    let all = sources.len();
+    if changed {
+        match header_deps::load(&record_path).filter(|record| record.key == key) {
+            Some(record) => sources = header_deps::affected(&sources, &record.files, &inputs),
+            None => log::verbose!("No earlier check with these flags; checking every file"),
+        }
+        if sources.is_empty() {
+            log::status!("✓ No file changed since the last check ({} file(s))", all);
+            return Ok(());
+        }
+    }
+    log::verbose!("Compiler: {}", compiler);
+    log::verbose!("Flags: {}", flags.join(" "));
+    log::status!("Checking {} file(s) with {}...", sources.len(), compiler);
+
+    let jobs = jobs
+        .or_else(|| thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1)
+        .clamp(1, sources.len());
+    let queue = Mutex::new(sources.iter().collect::<VecDeque<_>>());
+    let failed = Mutex::new(Vec::new());
+    let launch_error = Mutex::new(None);
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| {
+                loop {
+                    let Some(source) = queue.lock().unwrap().pop_front() else {
+                        break;
+                    };
+                    let output = match Command::new(&compiler).args(&flags).arg(source).output() {
+                        Ok(output) => output,
+                        Err(e) => {
+                            *launch_error.lock().unwrap() = Some(e);
+                            queue.lock().unwrap().clear();
+                            break;
+                        }
+                    };
+                    let diagnostics = String::from_utf8_lossy(&output.stderr);
+                    // One file's verdict and diagnostics stay together
+                    let mut failed = failed.lock().unwrap();
+                    if output.status.success() {
+                        log::status!("  ✓ {}", source);
+                    } else {
+                        log::problem!("  ✗ {}", source);
+                        failed.push(source.clone());
+                    }
+                    if !diagnostics.trim().is_empty() {
+                        eprint!("{}", diagnostics);
+                    }
+                }
+            });
+        }
+    });
+
+    if let Some(e) = launch_error.into_inner().unwrap() {
+        return Err(Error::failure(format!("failed to run {}: {}", compiler, e))
+            .hint("install a C compiler, or name one with --compiler or [build] compiler"));
+    }
+    let failed = failed.into_inner().unwrap();
+    let mut passed = inputs;
+    passed.retain(|file, _| !failed.contains(file));
+    let record = header_deps::Record {
+        success: failed.is_empty(),
+        key,
+        files: passed,
+    };
+    header_deps::save(&record_path, &record);
+    if !failed.is_empty() {
+        return Err(Error::build(format!(
+            "{} of {} file(s) failed to compile: {}",
+            failed.len(),
+            sources.len(),
+            failed.join(", ")
+        )));
+    }
+    log::status!(
+        "✓ All {} file(s) compile ({:.1}s)",
+        sources.len(),
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+/// The compiler and flags for `options`, include directories and those of
+/// the dependencies in external/ included
+pub fn compilation(
+    options: &BuildOptions,
+    config: &crate::ProjectConfig,
+) -> error::Result<Compilation> {
+    let target = options
+        .target_config
+        .as_ref()
+        .and_then(|name| config.target.get(name));
+    let profile = profiles::resolve(&config.profile, options.release, options.profile.as_deref())?;
+    let (feature_defines, feature_sources) = features::defines_and_sources(
+        &config.features,
+        &options.features,
+        options.no_default_features,
+    )
+    .map_err(Error::usage)?;
+
+    let build_dir = crate::target_build_dir(options);
+    let prefix = target
+        .filter(|t| t.toolchain.is_none())
+        .and_then(|t| t.prefix.as_deref());
+    let compiler = match prefix {
+        Some(prefix) => cross::compiler(prefix),
+        None => crate::resolve_compiler(options.compiler.as_deref(), Some(config))
+            .map(|(compiler, _)| compiler)
+            .or_else(|| crate::cached_value(&build_dir, "CMAKE_C_COMPILER"))
+            .unwrap_or_else(|| "cc".to_string()),
+    };
+
+    // The warning preset first, as in the build
+    let mut flags = crate::warning_flags(config, Some(&compiler))?;
+    flags.push(format!(
+        "-std=c{}",
+        crate::c_standard(&config.project.edition)
+    ));
+    flags.extend(crate::env_flags("CFLAGS"));
+    flags.extend(config.build.flags.iter().cloned());
+    if let Some(target) = target {
+        flags.extend(target.cpu_flags.iter().cloned());
+        flags.extend(target.flags.iter().cloned());
+    }
+    let mut macros = config.build.defines.clone();
+    macros.extend(profile.defines);
+    macros.extend(options.defines.iter().cloned());
+    crate::check_defines(&macros)?;
+    macros.extend(feature_defines);
+    flags.extend(macros.iter().map(|d| format!("-D{}", d)));
+    flags.extend(profile.c_flags);
+    flags.push("-Iinclude".to_string());
+    flags.extend(config.build.include_dirs.iter().map(|d| format!("-I{}", d)));
+    version_header::generate(config, &build_dir)?;
+    if config.build.version_header != Some(false) {
+        flags.push(format!(
+            "-I{}",
+            build_dir.join(version_header::DIR).display()
+        ));
+    }
+    for name in config.dependencies.keys() {
+        let dir = subdir::root(name);
+        if dir.is_dir() {
+            for include in dep_wiring::include_dirs(name, &dir) {
+                flags.push(format!("-I{}", dir.join(include).display()));
+            }
+        }
+    }
+
+    Ok(Compilation {
+        compiler,
+        flags,
+        feature_sources,
+    })
+}
@@ -0,0 +1,185 @@
+//! What `c1 clean` removes besides the build directory, and
+//! `c1 clean --keep-deps`, which removes the project's own build outputs only.
+//!
+//! c1 also writes a few files into the project root that it can write again:
+//! the compile_commands.json link and, with `source_mode = "auto"`,
+//! c1_sources.cmake. A full clean removes those too, listing each, so no stale
+//! copy keeps steering clangd or cmake after the build directory is gone. A
+//! file is only removed when it is still c1's: a compile_commands.json the
+//! user put there is left alone, and so are CMakeLists.txt, project.toml and
+//! everything else they wrote; the `[generate]` outputs in the source tree
+//! only go with `--generated`. `--dry-run` lists what would go, with sizes.
+//!
+//! Dependencies in external/ rarely change but can take longest to compile.
+//! In every CMake build directory under the one being cleaned (each profile,
+//! target and sanitizer set has its own) the objects of the project's own
+//! targets, `CMakeFiles/<target>.dir`, and the files it linked are removed.
+//! The binary directories of dependencies (`external/`, and FetchContent's
+//! `_deps/`) stay, and so does CMake's cache and generator state, so the next
+//! build recompiles the project against the dependencies' existing objects.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::clone_cache::dir_size;
+use crate::error::{self, Context};
+use crate::{auto_sources, build_history, log, safe_path};
+
+/// Subdirectories of a build directory that belong to dependencies or hold
+/// generated code rather than the project's outputs
+const KEPT_DIRS: &[&str] = &["external", "_deps", "generated"];
+
+/// A file c1 writes outside the build directory
+struct Generated {
+    path: &'static str,
+    /// Whether the file there is c1's rather than the user's, given the
+    /// build directory
+    is_ours: fn(&Path, &Path) -> bool,
+}
+
+const GENERATED: &[Generated] = &[
+    Generated {
+        path: crate::COMPILE_COMMANDS,
+        is_ours: is_database_link,
+    },
+    Generated {
+        path: auto_sources::GENERATED,
+        is_ours: |_, _| true,
+    },
+];
+
+/// The files c1 generated outside `build_dir` that are there now
+pub fn generated(build_dir: &Path) -> Vec<PathBuf> {
+    GENERATED
+        .iter()
+        .filter(|generated| {
+            let path = Path::new(generated.path);
+            fs::symlink_metadata(path).is_ok() && (generated.is_ours)(path, build_dir)
+        })
+        .map(|generated| PathBuf::from(generated.path))
+        .collect()
+}
+
+/// A symlink, or the copy made where symlinks are unavailable: the same
+/// bytes as a database in one of the build directories
+fn is_database_link(path: &Path, build_dir: &Path) -> bool {
+    if fs::read_link(path).is_ok() {
+        return true;
+    }
+    let Ok(content) = fs::read(path) else {
+        return false;
+    };
+    let mut dirs = vec![build_dir.to_path_buf()];
+    dirs.extend(
+        fs::read_dir(build_dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|entry| entry.path()),
+    );
+    dirs.iter()
+        .any(|dir| fs::read(dir.join(path)).is_ok_and(|database| database == content))
+}
+
+/// The size of a file, or of everything in a directory
+pub fn size(path: &Path) -> u64 {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => dir_size(path),
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Remove each of `paths`, returning the bytes freed
+pub fn remove(paths: &[PathBuf]) -> error::Result<u64> {
+    let mut freed = 0;
+    for path in paths {
+        freed += size(path);
+        let result = if fs::symlink_metadata(path).is_ok_and(|meta| meta.is_dir()) {
+            safe_path::remove_dir_all(path)
+        } else {
+            safe_path::remove_file(path)
+        };
+        result.context(|| format!("failed to remove {}", path.display()))?;
+    }
+    Ok(freed)
+}
+
+/// What `--dry-run` prints: each path with its size, then the total
+pub fn print_plan(paths: &[PathBuf]) {
+    if paths.is_empty() {
+        log::status!("Nothing to clean.");
+        return;
+    }
+    let mut total = 0;
+    for path in paths {
+        let size = size(path);
+        total += size;
+        let slash = if path.is_dir() { "/" } else { "" };
+        log::status!(
+            "Would remove {}{} ({})",
+            path.display(),
+            slash,
+            build_history::bytes(size)
+        );
+    }
+    log::status!("{} would be reclaimed", build_history::bytes(total));
+}
+
+/// The project's own objects and linked files under `dir`
+pub fn own_outputs(dir: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    collect_own_outputs(dir, &mut found);
+    found
+}
+
+fn collect_own_outputs(dir: &Path, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let configured = dir.join("CMakeCache.txt").is_file();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() {
+            if !configured {
+                collect_own_outputs(&path, found);
+            } else if name == "CMakeFiles" {
+                target_objects(&path, found);
+            } else if !KEPT_DIRS.contains(&name.as_str()) {
+                // A sanitizer or coverage build nested in this one
+                collect_own_outputs(&path, found);
+            }
+        } else if configured && is_output(&name) {
+            found.push(path);
+        }
+    }
+}
+
+/// The `<target>.dir` object directories in a CMakeFiles directory
+fn target_objects(cmake_files: &Path, found: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(cmake_files).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if entry.file_type().is_ok_and(|t| t.is_dir())
+            && path.extension().is_some_and(|e| e == "dir")
+        {
+            found.push(path);
+        }
+    }
+}
+
+/// Whether a file at the top of a build directory is something the build
+/// linked or converted (an executable, library, image or map) rather than
+/// CMake's or c1's own state
+fn is_output(name: &str) -> bool {
+    let state = name.starts_with('.')
+        || name.starts_with("c1-")
+        || ["Makefile", "build.ninja", "rules.ninja"].contains(&name)
+        || [".cmake", ".txt", ".json", ".ninja"]
+            .iter()
+            .any(|ext| name.ends_with(ext));
+    !state
+}
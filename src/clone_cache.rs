@@ -0,0 +1,156 @@
+//! Shared mirrors of dependency repositories.
+//!
+//! Each git dependency is mirrored once under `<cache dir>/git/`, keyed by a
+//! hash of its URL, and `external/<name>` is cloned from that mirror on disk.
+//! Another project using the same library, or a `c1 sync --force`, then only
+//! downloads what is new upstream. Mirrors keep full history so that any tag,
+//! branch or commit can be checked out of them; clones made from a mirror
+//! share its objects through hard links where the filesystem allows.
+//!
+//! With `--offline` a mirror is used as it is, without fetching.
+//!
+//! A lock file per mirror keeps concurrent c1 processes from fetching into it
+//! or cloning from it at the same time. When the cache directory is unusable,
+//! dependencies are cloned straight from upstream as before. `c1 cache clean`
+//! deletes the whole cache directory through `safe_path`, which allows it as
+//! a root c1 owns although it lives outside any project.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::error::{self, Context};
+use crate::{log, progress, remote, safe_path, secrets};
+
+/// Cloning a large repository can take a while; only a lock this old is
+/// assumed to be left behind by a process that died
+const STALE_LOCK: Duration = Duration::from_secs(30 * 60);
+
+/// An up-to-date mirror, locked until dropped
+pub struct Mirror {
+    pub path: PathBuf,
+    /// How much bringing it up to date downloaded, when git said
+    pub received: Option<String>,
+    _lock: remote::CacheLock,
+}
+
+impl Mirror {
+    /// The mirror as a git remote
+    pub fn source(&self) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+}
+
+fn mirrors_dir() -> Option<PathBuf> {
+    Some(remote::cache_dir()?.join("git"))
+}
+
+/// Fetch upstream into the mirror of `url`, the dependency `name`, creating
+/// it on first use. `url` is the URL to fetch from, after `url_rewrite`;
+/// `real_url` is that with its credential placeholders expanded. Returns `None`, after a warning, when the mirror cannot be used.
+pub fn update(name: &str, url: &str, real_url: &str) -> Option<Mirror> {
+    let key = url.trim_end_matches('/').trim_end_matches(".git");
+    let path = mirrors_dir()?.join(format!("{:016x}.git", remote::fnv1a(key)));
+    let lock = remote::CacheLock::acquire(&path, STALE_LOCK);
+    if remote::is_offline() {
+        // Use what is cached, however old; never go to upstream
+        return path
+            .join("HEAD")
+            .exists()
+            .then_some(Mirror {
+                path,
+                received: None,
+                _lock: lock,
+            });
+    }
+
+    let mut cmd = Command::new("git");
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    if path.join("HEAD").exists() {
+        // The URL may carry a token that has since been rotated
+        let _ = crate::run_git(&path, &["remote", "set-url", "origin", real_url]);
+        cmd.arg("-C")
+            .arg(&path)
+            .args(["fetch", "--prune", "--tags", "origin"]);
+    } else {
+        log::status!("  Caching {}...", secrets::redact(url));
+        if let Some(dir) = path.parent()
+            && fs::create_dir_all(dir).is_err()
+        {
+            return None;
+        }
+        let _ = safe_path::remove_dir_all(&tmp);
+        cmd.args(["clone", "--mirror", real_url]).arg(&tmp);
+    }
+    let mut received = None;
+    let result = match progress::run_git(&mut cmd, name) {
+        Ok((output, size)) if output.status.success() => {
+            received = size;
+            Ok(())
+        }
+        Ok((output, _)) => Err(output.stderr.trim().to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    // A fresh mirror appears under its final name only once complete
+    let result = result.and_then(|_| {
+        if tmp.exists() {
+            fs::rename(&tmp, &path).map_err(|e| e.to_string())
+        } else {
+            Ok(())
+        }
+    });
+    match result {
+        Ok(()) => Some(Mirror {
+            path,
+            received,
+            _lock: lock,
+        }),
+        Err(e) => {
+            let _ = safe_path::remove_dir_all(&tmp);
+            // Kept with the rest of the dependency's output under `c1 sync -j`
+            log::print(
+                true,
+                format_args!(
+                    "  Warning: could not update the cached clone of {}, using upstream directly",
+                    secrets::redact(url)
+                ),
+            );
+            // Advice on access is given once the direct clone fails too
+            if !e.is_empty() && secrets::auth_failure(url, &e).is_none() {
+                log::print(true, format_args!("    {}", e));
+            }
+            None
+        }
+    }
+}
+
+/// `c1 cache clean`: delete every mirror and cached upstream query
+pub fn cmd_clean() -> error::Result<()> {
+    let Some(dir) = remote::cache_dir() else {
+        log::status!("No cache directory is configured, nothing to clean");
+        return Ok(());
+    };
+    if !dir.exists() {
+        log::status!("Cache {} is already empty", dir.display());
+        return Ok(());
+    }
+    let size = crate::build_history::bytes(dir_size(&dir));
+    safe_path::remove_dir_all(&dir).context(|| format!("failed to remove {}", dir.display()))?;
+    log::status!("✓ Removed {} ({})", dir.display(), size);
+    Ok(())
+}
+
+pub fn dir_size(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            Ok(_) => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            Err(_) => 0,
+        })
+        .sum()
+}
@@ -0,0 +1,152 @@
+//! Registering files in the SOURCES and HEADERS lists of CMakeLists.txt.
+//!
+//! New projects mark the end of each list with a `# @c1_sources` or
+//! `# @c1_headers` comment, and entries go right before it. When a
+//! reformatted or hand-written CMakeLists.txt lost the comment, entries go at
+//! the end of the `set(SOURCES ...)` or `set(HEADERS ...)` block instead,
+//! indented like the entries already there. A file that is already listed is
+//! left alone, so registering twice changes nothing.
+//!
+//! Features, variants and warning presets reach the project target through
+//! lines appended to CMakeLists.txt the first time a build needs them,
+//! recognized afterwards by the variable they use.
+
+use std::fs;
+
+use crate::error::{self, Context};
+use crate::{log, safe_path};
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+
+pub const SOURCES_MARKER: &str = "# @c1_sources";
+pub const HEADERS_MARKER: &str = "# @c1_headers";
+
+pub enum Registration {
+    Added,
+    AlreadyListed,
+    /// Neither the marker nor the `set()` block is there
+    NoList,
+}
+
+/// The list a file belongs in and the marker ending it: SOURCES for .c
+/// files, HEADERS otherwise
+fn list_for(entry: &str) -> (&'static str, &'static str) {
+    if entry.ends_with(".c") {
+        ("SOURCES", SOURCES_MARKER)
+    } else {
+        ("HEADERS", HEADERS_MARKER)
+    }
+}
+
+/// Add `entry` to the list it belongs in
+pub fn register(content: &mut String, entry: &str) -> Registration {
+    let (list, marker) = list_for(entry);
+    if is_listed(content, entry) {
+        return Registration::AlreadyListed;
+    }
+    if let Some(at) = content.find(marker) {
+        let line_start = content[..at].rfind('\n').map_or(0, |i| i + 1);
+        let indent = &content[line_start..at];
+        let indent = if indent.trim().is_empty() {
+            indent.to_string()
+        } else {
+            "    ".to_string()
+        };
+        content.insert_str(at, &format!("{}\n{}", entry, indent));
+        return Registration::Added;
+    }
+    match insert_in_set(content, list, entry) {
+        Some(updated) => {
+            *content = updated;
+            Registration::Added
+        }
+        None => Registration::NoList,
+    }
+}
+
+/// Append `block` to CMakeLists.txt unless it already uses `marker`; `what`
+/// names the wiring in the status line. Nothing is done without a
+/// CMakeLists.txt.
+pub fn ensure_block(marker: &str, block: &str, what: &str) -> error::Result<()> {
+    let Ok(content) = fs::read_to_string(CMAKE_PATH) else {
+        return Ok(());
+    };
+    if content.contains(marker) {
+        return Ok(());
+    }
+    let mut updated = content;
+    if !updated.ends_with('\n') {
+        updated.push('\n');
+    }
+    updated.push('\n');
+    updated.push_str(block);
+    safe_path::write(CMAKE_PATH, updated).context(|| format!("failed to update {}", CMAKE_PATH))?;
+    log::status!("✓ Added {} wiring to CMakeLists.txt", what);
+    Ok(())
+}
+
+/// Tell the user to add `entry` by hand after `register` found no list
+pub fn warn_unregistered(entry: &str) {
+    let (list, marker) = list_for(entry);
+    log::problem!(
+        "Warning: CMakeLists.txt has no `{}` marker or set({} ...) block; add {} to it by hand",
+        marker,
+        list,
+        entry
+    );
+}
+
+/// Whether a line names `entry` outside a comment
+fn is_listed(content: &str, entry: &str) -> bool {
+    content.lines().any(|line| {
+        let line = line.split('#').next().unwrap_or_default();
+        line.split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .any(|word| word == entry)
+    })
+}
+
+/// `content` with `entry` added before the `)` closing `set(<list> ...)`
+fn insert_in_set(content: &str, list: &str, entry: &str) -> Option<String> {
+    let mut offset = 0;
+    let mut start = None;
+    for line in content.split_inclusive('\n') {
+        let code = line.trim_start();
+        if let Some(rest) = code
+            .strip_prefix("set(")
+            .or_else(|| code.strip_prefix("set ("))
+            && rest.trim_start().split([' ', '\t', '\n', '\r', ')']).next() == Some(list)
+        {
+            start = Some(offset + line.len() - code.len());
+            break;
+        }
+        offset += line.len();
+    }
+    let start = start?;
+    let close = start + content[start..].find(')')?;
+    let line_start = content[..close].rfind('\n').map_or(0, |i| i + 1);
+
+    if content[line_start..close].trim().is_empty() {
+        // `)` on a line of its own: add a line before it
+        let indent = content[start..line_start]
+            .lines()
+            .skip(1)
+            .filter(|line| !line.trim().is_empty())
+            .last()
+            .map(|line| &line[..line.len() - line.trim_start().len()])
+            .unwrap_or("    ");
+        Some(format!(
+            "{}{}{}\n{}",
+            &content[..line_start],
+            indent,
+            entry,
+            &content[line_start..]
+        ))
+    } else {
+        Some(format!(
+            "{} {}{}",
+            &content[..close],
+            entry,
+            &content[close..]
+        ))
+    }
+}
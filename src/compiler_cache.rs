@@ -0,0 +1,113 @@
+//! Compiler caches: `[build] cache = "ccache"`.
+//!
+//! `ccache` or `sccache` is passed to cmake as `CMAKE_C_COMPILER_LAUNCHER`,
+//! so a rebuild after `c1 clean` or a profile switch takes objects from the
+//! cache instead of compiling them again. `auto` uses whichever of the two is
+//! on PATH, ccache first, and nothing when neither is. The launcher wraps
+//! whatever compiler `--compiler`, `[build] compiler`, `CC` or a target
+//! chose. A configured launcher that is not installed is warned about and
+//! the build goes on without it; `c1 build --no-cache` leaves it out too.
+//! The cache's hits and misses during a build are read from the tool's own
+//! statistics before and after it.
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{self, Error};
+use crate::{cross, log, user_config};
+
+pub const TOOLS: &[&str] = &["ccache", "sccache"];
+pub const SETTINGS: &[&str] = &["ccache", "sccache", "auto", "none"];
+
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+/// The launcher to configure with: `[build] cache`, else the user's
+/// `build.cache`, resolved against PATH. `None` when turned off or missing.
+pub fn launcher(setting: Option<&str>, no_cache: bool) -> error::Result<Option<&'static str>> {
+    let Some(setting) = setting.or_else(|| user_config::text("build.cache")) else {
+        return Ok(None);
+    };
+    if !SETTINGS.contains(&setting) {
+        return Err(
+            Error::config(format!("unknown [build] cache '{}'", setting))
+                .hint("use ccache, sccache, auto or none"),
+        );
+    }
+    if no_cache || setting == "none" {
+        return Ok(None);
+    }
+    if setting == "auto" {
+        let found = TOOLS
+            .iter()
+            .copied()
+            .find(|tool| cross::find_on_path(tool).is_some());
+        log::verbose!("Compiler cache: {}", found.unwrap_or("none found on PATH"));
+        return Ok(found);
+    }
+    let tool = TOOLS
+        .iter()
+        .copied()
+        .find(|tool| *tool == setting)
+        .expect("checked against SETTINGS");
+    if cross::find_on_path(tool).is_none() {
+        if !WARNED.swap(true, Ordering::Relaxed) {
+            log::problem!(
+                "Warning: [build] cache is {} but {} was not found on PATH; building without it",
+                tool,
+                tool
+            );
+        }
+        return Ok(None);
+    }
+    Ok(Some(tool))
+}
+
+/// The configure argument for `launcher`. With none, an earlier launcher is
+/// cleared from the cache, and nothing is passed when there never was one.
+pub fn configure_arg(build_dir: &Path, launcher: Option<&str>) -> Option<String> {
+    match launcher {
+        Some(tool) => Some(format!("-DCMAKE_C_COMPILER_LAUNCHER={}", tool)),
+        None => configured(build_dir).map(|_| "-DCMAKE_C_COMPILER_LAUNCHER=".to_string()),
+    }
+}
+
+/// The cache tool a build directory was configured to launch the compiler with
+pub fn configured(build_dir: &Path) -> Option<&'static str> {
+    let launcher = crate::cached_value(build_dir, "CMAKE_C_COMPILER_LAUNCHER")?;
+    let name = Path::new(&launcher).file_stem()?.to_str()?.to_string();
+    TOOLS.iter().copied().find(|tool| *tool == name)
+}
+
+/// The tool's hit and miss counters so far
+pub fn stats(tool: &str) -> Option<(u64, u64)> {
+    let mut command = Command::new(tool);
+    if tool == "ccache" {
+        command.arg("--print-stats");
+    } else {
+        command.arg("--show-stats");
+    }
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let (mut hits, mut misses) = (0, 0);
+    for line in text.lines() {
+        // ccache: `direct_cache_hit\t3`; sccache: `Cache hits    3`
+        let (key, value) = match line.split_once('\t') {
+            Some((key, value)) => (key.trim(), value),
+            None => match line.trim_end().rsplit_once(char::is_whitespace) {
+                Some((key, value)) => (key.trim(), value),
+                None => continue,
+            },
+        };
+        let value: u64 = value.trim().parse().unwrap_or(0);
+        match key {
+            "direct_cache_hit" | "preprocessed_cache_hit" | "Cache hits" => hits += value,
+            "cache_miss" | "Cache misses" => misses += value,
+            _ => {}
+        }
+    }
+    Some((hits, misses))
+}
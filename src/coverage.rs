@@ -0,0 +1,372 @@
+//! `c1 test --coverage`: line coverage of the project's own sources.
+//!
+//! The tests build in their own subdirectory of the build directory
+//! (`build/debug/coverage`) with `--coverage`, and the counters of an earlier
+//! run are deleted before the tests run, so the numbers are this run's
+//! alone. Afterwards gcov (`llvm-cov gcov` for clang) reads every object's
+//! counters; a line counts as covered when any test executed it. Files
+//! outside the project, under external/ or tests/, or generated into the
+//! build directory are left out. The summary table goes to stdout and an
+//! HTML report to `build/coverage`, made by lcov and genhtml when both are
+//! installed and by c1 itself otherwise. `--fail-under <pct>` fails the
+//! command when the total is lower.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{self, Context, Error};
+use crate::{cross, log, safe_path};
+
+pub const SUBDIR: &str = "coverage";
+pub const FLAGS: &str = "--coverage";
+
+/// Per source file, whether each executable line ran
+type Lines = BTreeMap<PathBuf, BTreeMap<u32, bool>>;
+
+/// Delete the counters an earlier run left in `build_dir`
+pub fn reset(build_dir: &Path) -> error::Result<()> {
+    for counters in files_with_extension(build_dir, "gcda") {
+        safe_path::remove_file(&counters)
+            .context(|| format!("failed to remove {}", counters.display()))?;
+    }
+    Ok(())
+}
+
+/// Collect the counters, print the summary and write the HTML report into
+/// `report_dir`
+pub fn report(build_dir: &Path, report_dir: &Path, fail_under: Option<f64>) -> error::Result<()> {
+    let root =
+        std::env::current_dir().context(|| "failed to read the current directory".to_string())?;
+    let clang = crate::cached_value(build_dir, "CMAKE_C_COMPILER")
+        .is_some_and(|compiler| compiler.contains("clang"));
+    let lines = collect(build_dir, &root, clang)?;
+    if lines.is_empty() {
+        return Err(Error::failure("no coverage data was produced")
+            .hint("coverage needs gcc or clang and tests that exercise src/"));
+    }
+
+    let shown = |path: &Path| {
+        path.strip_prefix(&root)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+    let width = lines
+        .keys()
+        .map(|path| shown(path).len())
+        .max()
+        .unwrap_or(0)
+        .max("Total".len());
+    println!(
+        "{:<width$}  {:>6}  {:>7}  {:>8}",
+        "File",
+        "Lines",
+        "Covered",
+        "Coverage",
+        width = width
+    );
+    let (mut total, mut covered) = (0, 0);
+    for (path, file_lines) in &lines {
+        let (lines, hits) = counts(file_lines);
+        total += lines;
+        covered += hits;
+        println!(
+            "{:<width$}  {:>6}  {:>7}  {:>7.1}%",
+            shown(path),
+            lines,
+            hits,
+            percent(hits, lines),
+            width = width
+        );
+    }
+    let total_percent = percent(covered, total);
+    println!(
+        "{:<width$}  {:>6}  {:>7}  {:>7.1}%",
+        "Total",
+        total,
+        covered,
+        total_percent,
+        width = width
+    );
+
+    if report_dir.exists() {
+        safe_path::remove_dir_all(report_dir)
+            .context(|| format!("failed to remove {}", report_dir.display()))?;
+    }
+    fs::create_dir_all(report_dir)
+        .context(|| format!("failed to create {}", report_dir.display()))?;
+    if clang || !genhtml(build_dir, report_dir) {
+        write_html(&lines, report_dir, &shown)?;
+    }
+    println!("HTML report: {}", report_dir.join("index.html").display());
+
+    if let Some(minimum) = fail_under
+        && total_percent < minimum
+    {
+        return Err(Error::failure(format!(
+            "total line coverage {:.1}% is below --fail-under {}%",
+            total_percent, minimum
+        )));
+    }
+    Ok(())
+}
+
+/// Run gcov over every object in `build_dir` and merge what it reports
+fn collect(build_dir: &Path, root: &Path, clang: bool) -> error::Result<Lines> {
+    let scratch = build_dir.join(".gcov");
+    let mut lines = Lines::new();
+    for notes in files_with_extension(build_dir, "gcno") {
+        if scratch.exists() {
+            safe_path::remove_dir_all(&scratch)
+                .context(|| format!("failed to remove {}", scratch.display()))?;
+        }
+        fs::create_dir_all(&scratch)
+            .context(|| format!("failed to create {}", scratch.display()))?;
+        let notes = std::path::absolute(&notes).unwrap_or(notes);
+        let mut gcov = if clang {
+            let mut command = Command::new("llvm-cov");
+            command.arg("gcov");
+            command
+        } else {
+            Command::new("gcov")
+        };
+        gcov.arg("-p")
+            .arg("-o")
+            .arg(notes.parent().unwrap_or(Path::new(".")))
+            .arg(&notes)
+            .current_dir(&scratch);
+        log::command(&gcov);
+        let output = gcov.output().map_err(|e| {
+            let tool = if clang { "llvm-cov" } else { "gcov" };
+            Error::failure(format!("failed to run {}: {}", tool, e))
+                .hint(format!("install {}, which comes with the compiler", tool))
+        })?;
+        if !output.status.success() {
+            log::verbose!(
+                "gcov failed for {}: {}",
+                notes.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            continue;
+        }
+        for report in files_with_extension(&scratch, "gcov") {
+            let Ok(text) = fs::read_to_string(&report) else {
+                continue;
+            };
+            merge(&text, root, build_dir, &mut lines);
+        }
+    }
+    if scratch.exists() {
+        let _ = safe_path::remove_dir_all(&scratch);
+    }
+    Ok(lines)
+}
+
+/// Add one .gcov file to `lines`, unless its source is not the project's own
+fn merge(text: &str, root: &Path, build_dir: &Path, lines: &mut Lines) {
+    let mut source = None;
+    let mut counts = Vec::new();
+    for line in text.lines() {
+        let mut fields = line.splitn(3, ':');
+        let (Some(count), Some(number), Some(rest)) = (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let Ok(number) = number.trim().parse::<u32>() else {
+            continue;
+        };
+        if number == 0 {
+            if let Some(path) = rest.strip_prefix("Source:") {
+                source = Some(root.join(path));
+            }
+            continue;
+        }
+        match count.trim() {
+            "-" => {}
+            "#####" | "=====" => counts.push((number, false)),
+            _ => counts.push((number, true)),
+        }
+    }
+    let Some(source) = source else {
+        return;
+    };
+    let build_dir = root.join(build_dir);
+    let Ok(relative) = source.strip_prefix(root) else {
+        return;
+    };
+    if source.starts_with(&build_dir)
+        || relative.starts_with("external")
+        || relative.starts_with("tests")
+        || !source.is_file()
+    {
+        return;
+    }
+    let file = lines.entry(source).or_default();
+    for (number, ran) in counts {
+        *file.entry(number).or_insert(false) |= ran;
+    }
+}
+
+/// lcov and genhtml's report, when both are installed and work
+fn genhtml(build_dir: &Path, report_dir: &Path) -> bool {
+    if cross::find_on_path("lcov").is_none() || cross::find_on_path("genhtml").is_none() {
+        return false;
+    }
+    let info = report_dir.join("coverage.info");
+    let steps: [Vec<&std::ffi::OsStr>; 3] = [
+        vec![
+            "--quiet".as_ref(),
+            "--capture".as_ref(),
+            "--directory".as_ref(),
+            build_dir.as_os_str(),
+            "--output-file".as_ref(),
+            info.as_os_str(),
+        ],
+        vec![
+            "--quiet".as_ref(),
+            "--remove".as_ref(),
+            info.as_os_str(),
+            "*/external/*".as_ref(),
+            "*/tests/*".as_ref(),
+            "/usr/*".as_ref(),
+            "--output-file".as_ref(),
+            info.as_os_str(),
+        ],
+        vec![
+            "--quiet".as_ref(),
+            info.as_os_str(),
+            "--output-directory".as_ref(),
+            report_dir.as_os_str(),
+        ],
+    ];
+    for (i, args) in steps.iter().enumerate() {
+        let mut command = Command::new(if i == 2 { "genhtml" } else { "lcov" });
+        command.args(args);
+        log::command(&command);
+        match command.output() {
+            Ok(output) if output.status.success() => {}
+            _ => {
+                log::problem!("Warning: lcov failed, writing c1's own HTML report instead");
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// A plain HTML report: an index with the table and a page per file with
+/// its lines marked
+fn write_html(
+    lines: &Lines,
+    report_dir: &Path,
+    shown: &dyn Fn(&Path) -> String,
+) -> error::Result<()> {
+    let mut rows = String::new();
+    let (mut total, mut covered) = (0, 0);
+    for (path, file_lines) in lines {
+        let name = shown(path);
+        let page = format!("{}.html", name.replace(['/', '\\'], "_"));
+        let (count, hits) = counts(file_lines);
+        total += count;
+        covered += hits;
+        rows.push_str(&format!(
+            "<tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td><td>{:.1}%</td></tr>\n",
+            escape(&page),
+            escape(&name),
+            count,
+            hits,
+            percent(hits, count)
+        ));
+
+        let source = fs::read_to_string(path).unwrap_or_default();
+        let mut body = String::new();
+        for (number, text) in source.lines().enumerate() {
+            let number = number as u32 + 1;
+            let class = match file_lines.get(&number) {
+                Some(true) => " class=\"hit\"",
+                Some(false) => " class=\"miss\"",
+                None => "",
+            };
+            body.push_str(&format!(
+                "<span{}>{:>5}  {}</span>\n",
+                class,
+                number,
+                escape(text)
+            ));
+        }
+        let page_path = report_dir.join(&page);
+        safe_path::write(
+            &page_path,
+            format!(
+                "{}<h1>{}</h1>\n<p>{} of {} lines covered ({:.1}%)</p>\n<pre>{}</pre>\n</body></html>\n",
+                HEAD,
+                escape(&name),
+                hits,
+                count,
+                percent(hits, count),
+                body
+            ),
+        )
+        .context(|| format!("failed to write {}", page_path.display()))?;
+    }
+    let index = report_dir.join("index.html");
+    safe_path::write(
+        &index,
+        format!(
+            "{}<h1>Line coverage</h1>\n<table>\n<tr><th>File</th><th>Lines</th><th>Covered</th><th>Coverage</th></tr>\n{}<tr><th>Total</th><th>{}</th><th>{}</th><th>{:.1}%</th></tr>\n</table>\n</body></html>\n",
+            HEAD,
+            rows,
+            total,
+            covered,
+            percent(covered, total)
+        ),
+    )
+    .context(|| format!("failed to write {}", index.display()))
+}
+
+const HEAD: &str = "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Coverage</title>\n\
+<style>body{font-family:sans-serif}td,th{padding:2px 12px;text-align:left}\
+.hit{background:#dfd}.miss{background:#fdd}</style></head><body>\n";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Executable and executed lines of one file
+fn counts(lines: &BTreeMap<u32, bool>) -> (usize, usize) {
+    (lines.len(), lines.values().filter(|ran| **ran).count())
+}
+
+fn percent(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        100.0
+    } else {
+        part as f64 * 100.0 / whole as f64
+    }
+}
+
+/// Every file under `dir` with this extension
+fn files_with_extension(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                stack.push(path);
+            } else if path.extension().is_some_and(|e| e == extension) {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    found
+}
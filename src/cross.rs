@@ -0,0 +1,85 @@
+//! Target presets: cross-compiling from a `[target.<name>]` table without a
+//! CMake toolchain file.
+//!
+//! ```toml
+//! [target.arm-none-eabi]
+//! prefix = "arm-none-eabi-"
+//! cpu_flags = ["-mcpu=cortex-m4", "-mthumb"]
+//! linker_script = "stm32.ld"
+//! ```
+//!
+//! The prefix names the tools: `arm-none-eabi-gcc` compiles, and cmake is
+//! pointed at `arm-none-eabi-objcopy` and `-nm`, which `--emit` and `c1 size`
+//! then use. A prefix without `linux` in it is a bare-metal target, so cmake
+//! checks the compiler by building a static library instead of a program it
+//! could not link. `cpu_flags` go to both the compiler and the linker, and
+//! the linker script to the linker as `-T`. A `toolchain` file in the same
+//! table wins over the prefix.
+
+use std::path::{Path, PathBuf};
+
+use crate::TargetConfig;
+use crate::error::{self, Error};
+
+/// The preset's compiler: `<prefix>gcc`
+pub fn compiler(prefix: &str) -> String {
+    format!("{}gcc", prefix)
+}
+
+/// Fail before configuring when the preset's compiler is not on PATH
+pub fn check_compiler(prefix: &str) -> error::Result<()> {
+    let compiler = compiler(prefix);
+    if find_on_path(&compiler).is_some() {
+        return Ok(());
+    }
+    let triple = prefix.trim_end_matches('-');
+    let package = match triple {
+        "avr" => "gcc-avr".to_string(),
+        triple => format!("gcc-{}", triple),
+    };
+    Err(
+        Error::config(format!("{} was not found on PATH", compiler)).hint(format!(
+            "install the {} toolchain (e.g. `apt install {}`) or put its bin/ directory on PATH",
+            triple, package
+        )),
+    )
+}
+
+pub fn find_on_path(program: &str) -> Option<PathBuf> {
+    let file_name = format!("{}{}", program, std::env::consts::EXE_SUFFIX);
+    let paths = std::env::var_os("PATH")?;
+    std::env::split_paths(&paths)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// cmake arguments describing the target the prefix names
+pub fn configure_args(prefix: &str) -> Vec<String> {
+    let mut args = vec![
+        format!("-DCMAKE_OBJCOPY={}objcopy", prefix),
+        format!("-DCMAKE_NM={}nm", prefix),
+    ];
+    if prefix.contains("linux") {
+        args.push("-DCMAKE_SYSTEM_NAME=Linux".to_string());
+    } else {
+        args.push("-DCMAKE_SYSTEM_NAME=Generic".to_string());
+        args.push("-DCMAKE_TRY_COMPILE_TARGET_TYPE=STATIC_LIBRARY".to_string());
+    }
+    args
+}
+
+/// Flags for the compiler and for the linker from `cpu_flags`
+pub fn flags(target: &TargetConfig) -> (Vec<String>, Vec<String>) {
+    (target.cpu_flags.clone(), target.cpu_flags.clone())
+}
+
+/// The linker flag for a `linker_script`, a target's or a variant's
+pub fn linker_script(script: &str) -> error::Result<String> {
+    let path = Path::new(script);
+    if !path.is_file() {
+        return Err(Error::config(format!("linker script {} not found", script)));
+    }
+    // The linker runs in the build directory
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    Ok(format!("-T{}", crate::flag_path(&path)))
+}
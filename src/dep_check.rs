@@ -0,0 +1,167 @@
+//! Before building: is external/ still what project.toml and project.lock
+//! ask for?
+//!
+//! Editing a tag in project.toml and forgetting `c1 sync` would otherwise
+//! build against the old checkout. Every declared git or path dependency must
+//! be present in external/, a git one checked out at its locked commit, its
+//! `rev`, or without a lock entry its tag or branch, and an archive one
+//! extracted from the archive with the declared checksum. A lock entry resolved
+//! from a different source than the manifest now declares is out of date too.
+//! Everything is read from the local checkouts, mostly without spawning git,
+//! so the check works offline and costs next to nothing. Then the files of
+//! each dependency are compared with the tree or hash project.lock recorded,
+//! which catches a dependency edited in place; git only rehashes the files
+//! that changed. `--no-verify-deps` skips both.
+
+use std::path::Path;
+
+use crate::error::{self, Error};
+use crate::{archive, git_head, lockfile, short_hash};
+
+/// Fail, listing every mismatch, when a declared dependency needs `c1 sync`
+pub fn verify(dependencies: &toml::Table) -> error::Result<()> {
+    let lock_present = Path::new(lockfile::LOCKFILE_PATH).exists();
+    let (names, stale): (Vec<&str>, Vec<String>) = dependencies
+        .iter()
+        .filter_map(|(name, value)| {
+            Some((name.as_str(), check(name, value.as_table()?, lock_present)?))
+        })
+        .unzip();
+    let subject = match names.as_slice() {
+        [] => return unmodified(dependencies),
+        [name] => format!("dependency {} is", name),
+        names => format!("dependencies {} are", names.join(", ")),
+    };
+    Err(Error::config(format!(
+        "{} out of date, run `c1 sync`:\n  {}",
+        subject,
+        stale.join("\n  ")
+    ))
+    .hint("pass --no-verify-deps to build with external/ as it is"))
+}
+
+/// Fail when a dependency's files differ from what project.lock recorded
+pub fn unmodified(dependencies: &toml::Table) -> error::Result<()> {
+    let modified = lockfile::detect_modified(dependencies);
+    if modified.is_empty() {
+        return Ok(());
+    }
+    let lines: Vec<String> = modified
+        .iter()
+        .map(|m| {
+            format!(
+                "{}: {} locks {}, external/{} has {}",
+                m.name,
+                lockfile::LOCKFILE_PATH,
+                short_hash(&m.locked),
+                m.name,
+                short_hash(&m.actual)
+            )
+        })
+        .collect();
+    Err(Error::config(format!(
+        "{} modified in external/:\n  {}",
+        match modified.as_slice() {
+            [m] => format!("dependency {} was", m.name),
+            _ => "dependencies were".to_string(),
+        },
+        lines.join("\n  ")
+    ))
+    .hint(
+        "`c1 sync --accept-changes` locks the changes, `c1 sync --force` restores the dependency",
+    ))
+}
+
+/// Whether external/<name> is what the entry `value` asks for
+pub fn is_current(name: &str, value: &toml::Value) -> bool {
+    let lock_present = Path::new(lockfile::LOCKFILE_PATH).exists();
+    value
+        .as_table()
+        .is_some_and(|table| check(name, table, lock_present).is_none())
+}
+
+/// What is wrong with one dependency, if anything
+fn check(name: &str, table: &toml::Table, lock_present: bool) -> Option<String> {
+    let dir = Path::new("external").join(name);
+    if table.contains_key("url") {
+        let declared = table.get("sha256").and_then(|v| v.as_str());
+        return match (archive::extracted_sha256(name), declared) {
+            _ if !dir.exists() => Some(format!("{}: not present in external/", name)),
+            (Some(extracted), Some(declared)) if !extracted.eq_ignore_ascii_case(declared) => {
+                Some(format!(
+                    "{}: extracted from an archive with sha256 {}, but project.toml declares {}",
+                    name, extracted, declared
+                ))
+            }
+            _ => None,
+        };
+    }
+    let Some(git) = table.get("git").and_then(|v| v.as_str()) else {
+        // Path dependencies are linked into external/; system ones live elsewhere
+        let linked = dir.exists() || dir.is_symlink();
+        return (table.contains_key("path") && !linked)
+            .then(|| format!("{}: not present in external/", name));
+    };
+    if !dir.exists() {
+        return Some(format!("{}: not present in external/", name));
+    }
+    let head = git_head::read_head(&dir);
+    let at = head.as_deref().map_or("no commit", short_hash);
+    let tag = table.get("tag").and_then(|v| v.as_str());
+    let branch = table.get("branch").and_then(|v| v.as_str());
+
+    // A pinned `rev` is its own lock
+    if let Some(rev) = table.get("rev").and_then(|v| v.as_str()) {
+        return (!head.as_deref().is_some_and(|head| head.starts_with(rev))).then(|| {
+            format!(
+                "{}: checked out at {}, but project.toml pins rev {}",
+                name, at, rev
+            )
+        });
+    }
+    if lock_present {
+        return match lockfile::locked_commit(name, git, tag, branch) {
+            Some(locked) if head.as_deref() == Some(locked.as_str()) => None,
+            Some(locked) => Some(format!(
+                "{}: checked out at {}, but {} locks {} (`c1 sync --accept-local` locks the checkout)",
+                name,
+                at,
+                lockfile::LOCKFILE_PATH,
+                short_hash(&locked)
+            )),
+            None => Some(lockfile::mismatch(name, git, tag, branch)),
+        };
+    }
+    // Without a lock, the checkout must at least be at the declared ref
+    if let Some(tag) = tag {
+        let tagged = crate::run_git(
+            &dir,
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("refs/tags/{}^{{commit}}", tag),
+            ],
+        );
+        return match tagged {
+            Ok(commit) if head.as_deref() == Some(commit.as_str()) => None,
+            Ok(_) => Some(format!(
+                "{}: checked out at {}, not at tag {}",
+                name, at, tag
+            )),
+            Err(_) => Some(format!("{}: tag {} is not in the checkout", name, tag)),
+        };
+    }
+    if let Some(branch) = branch {
+        let current = crate::run_git(&dir, &["rev-parse", "--abbrev-ref", "HEAD"]).ok();
+        return (current.as_deref() != Some(branch)).then(|| {
+            format!(
+                "{}: checked out on {}, not on branch {}",
+                name,
+                current.as_deref().unwrap_or("no branch"),
+                branch
+            )
+        });
+    }
+    None
+}
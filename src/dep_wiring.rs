@@ -0,0 +1,525 @@
+//! Hooking dependencies in external/ up to the project's CMakeLists.txt.
+//!
+//! Each dependency gets a managed block, `# @c1_dep <name>` through
+//! `# @c1_dep_end <name>`, inserted before the `# @c1_dependencies`
+//! placeholder (which sits after the project's target is defined). Wiring the
+//! same dependency again replaces its block, so `c1 add` and `c1 sync` can run
+//! any number of times without duplicating entries.
+//!
+//! A dependency with a CMakeLists.txt that defines a library is added with
+//! `add_subdirectory` and linked. Anything else is treated as plain code: its
+//! .c files are compiled into a static library `c1_dep_<name>` linked to the
+//! project, whose include path is its include/ directory, its src/ and root
+//! directories when they hold headers, or `include = ["..."]` from the
+//! dependency's entry in project.toml when detection guesses wrong. A
+//! dependency's code is compiled without the project's warning preset, which
+//! applies to the project's target only; `cflags = ["..."]` in its entry adds
+//! compile options to its library. With `subdir = "..."`, all of this
+//! happens in that subdirectory of external/<name> instead of its root. A
+//! pkg-config dependency is linked through `pkg_check_modules` and has
+//! nothing in external/.
+//!
+//! A dependency whose own project.toml has an `[export]` table also gets
+//! the export target described in `exports`, linked the same way.
+//!
+//! A dev-dependency, declared in `[dev-dependencies]` or required only
+//! through one, is not linked to the project: its block appends the target to
+//! `C1_DEV_LIBRARIES`, which the test and bench executables link.
+//!
+//! `c1 build` refreshes every block from project.toml and external/, and drops
+//! the blocks of dependencies nothing declares any more.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::exports;
+use crate::log;
+use crate::safe_path;
+use crate::subdir;
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+const PLACEHOLDER: &str = "# @c1_dependencies";
+const DEV_LIBRARIES: &str = "${C1_DEV_LIBRARIES}";
+
+/// Dependencies wired into CMakeLists.txt whose directory in external/ is
+/// gone, e.g. after `c1 clean --all`
+pub fn missing() -> Vec<String> {
+    wired()
+        .into_iter()
+        .filter(|(name, system)| {
+            let dir = Path::new("external").join(name);
+            !system && !dir.exists() && !dir.is_symlink()
+        })
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// The dependencies in external/ with a block in CMakeLists.txt
+pub fn wired_external() -> Vec<String> {
+    wired()
+        .into_iter()
+        .filter(|(_, system)| !system)
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// The dependencies with a block in CMakeLists.txt, and whether each is a
+/// pkg-config one
+fn wired() -> Vec<(String, bool)> {
+    let Ok(content) = fs::read_to_string(CMAKE_PATH) else {
+        return Vec::new();
+    };
+    let mut wired = Vec::new();
+    let mut lines = content.lines().map(str::trim);
+    while let Some(line) = lines.next() {
+        let Some(name) = line.strip_prefix("# @c1_dep ").map(str::trim) else {
+            continue;
+        };
+        let end = format!("# @c1_dep_end {}", name);
+        let system = lines
+            .by_ref()
+            .take_while(|line| *line != end)
+            .any(|line| line.starts_with("pkg_check_modules("));
+        wired.push((name.to_string(), system));
+    }
+    wired
+}
+
+/// Bring every block in step with `dependencies` and external/: rewire what
+/// is declared and present, and unwire what neither project.toml nor a
+/// dependency's own project.toml declares
+pub fn refresh(dependencies: &toml::Table) {
+    let mut declared: Vec<String> = dependencies.keys().cloned().collect();
+    if let Ok(entries) = fs::read_dir("external") {
+        for entry in entries.flatten() {
+            declared.extend(required_by(&entry.file_name().to_string_lossy()));
+        }
+    }
+
+    for (name, system) in wired() {
+        if !declared.contains(&name) {
+            remove(&name);
+        } else if !system && !dependencies.contains_key(&name) {
+            // Required by another dependency: refresh it like the others
+            if Path::new("external").join(&name).exists() {
+                wire(&name);
+            }
+        }
+    }
+    for (name, value) in dependencies {
+        match crate::pkg_config::package(value) {
+            Some(package) => wire_pkg_config(name, package),
+            None if Path::new("external").join(name).exists() => wire(name),
+            None => {}
+        }
+    }
+}
+
+/// Insert or refresh the block for `external/<name>`. Problems are reported
+/// as warnings: an unwired dependency is still usable by hand.
+pub fn wire(name: &str) {
+    let dir = subdir::root(name);
+    if !dir.is_dir() && dir != Path::new("external").join(name) {
+        log::problem!(
+            "Warning: {} does not exist; {} is not wired into CMakeLists.txt",
+            dir.display(),
+            name
+        );
+        return;
+    }
+    let dev = is_dev(name);
+    let block = match library_target(&dir) {
+        Some((target, interface)) => format!(
+            "add_subdirectory({} EXCLUDE_FROM_ALL)\n{}{}",
+            cmake_path(&dir),
+            if interface {
+                String::new()
+            } else {
+                compile_options(name, &target)
+            },
+            link(&target, dev)
+        ),
+        None => source_block(name, &dir, dev),
+    };
+    let export = exports::of(name);
+    let block = if export.is_empty() {
+        block
+    } else {
+        let target = exports::target(&library_name(name));
+        format!(
+            "{}{}{}",
+            block,
+            exports::cmake_block(&export, &target, &cmake_path(&dir)),
+            link(&target, dev)
+        )
+    };
+    insert(name, &block);
+    if dev {
+        link_dev_libraries();
+    }
+}
+
+/// Insert or refresh the block for a pkg-config dependency
+pub fn wire_pkg_config(name: &str, package: &str) {
+    let dev = is_dev(name);
+    insert(name, &crate::pkg_config::cmake_block(name, package, dev));
+    if dev {
+        link_dev_libraries();
+    }
+}
+
+/// The line linking `target` to the project, or for a dev-dependency adding
+/// it to what tests and benches link
+pub fn link(target: &str, dev: bool) -> String {
+    if dev {
+        format!("list(APPEND C1_DEV_LIBRARIES {})\n", target)
+    } else {
+        format!(
+            "target_link_libraries(${{PROJECT_NAME}} PRIVATE {})\n",
+            target
+        )
+    }
+}
+
+/// Whether `name` is needed by tests and benches only: declared in
+/// `[dev-dependencies]` or required through one, and not through
+/// `[dependencies]`
+fn is_dev(name: &str) -> bool {
+    let Some(manifest) = fs::read_to_string("project.toml")
+        .ok()
+        .and_then(|content| content.parse::<toml::Table>().ok())
+    else {
+        return false;
+    };
+    let closure = |section: &str| {
+        let mut seen = BTreeSet::new();
+        let mut pending: Vec<String> = manifest
+            .get(section)
+            .and_then(|table| table.as_table())
+            .map(|table| table.keys().cloned().collect())
+            .unwrap_or_default();
+        while let Some(name) = pending.pop() {
+            if seen.insert(name.clone()) {
+                pending.extend(required_by(&name));
+            }
+        }
+        seen
+    };
+    !closure("dependencies").contains(name) && closure("dev-dependencies").contains(name)
+}
+
+/// What `external/<name>/project.toml` declares in `[dependencies]`
+fn required_by(name: &str) -> Vec<String> {
+    let manifest = Path::new("external").join(name).join("project.toml");
+    fs::read_to_string(manifest)
+        .ok()
+        .and_then(|content| content.parse::<toml::Table>().ok())
+        .and_then(|table| table.get("dependencies")?.as_table().cloned())
+        .map(|table| table.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Make every test and the benches link `C1_DEV_LIBRARIES`; tests registered
+/// before dev-dependencies existed don't
+fn link_dev_libraries() {
+    let Ok(content) = fs::read_to_string(CMAKE_PATH) else {
+        return;
+    };
+    let mut updated = String::with_capacity(content.len());
+    let mut previous = "";
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim();
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let target = trimmed
+            .strip_prefix("add_test(NAME ")
+            .and_then(|rest| rest.split_whitespace().next());
+        if let Some(target) = target
+            && !previous.contains(DEV_LIBRARIES)
+        {
+            updated.push_str(&format!(
+                "{}target_link_libraries({} PRIVATE {})\n",
+                indent, target, DEV_LIBRARIES
+            ));
+        }
+        updated.push_str(line);
+        if trimmed.starts_with("target_include_directories(bench_${bench_name} ")
+            && !content.contains(&format!("bench_${{bench_name}} PRIVATE {}", DEV_LIBRARIES))
+        {
+            updated.push_str(&format!(
+                "{}target_link_libraries(bench_${{bench_name}} PRIVATE {})\n",
+                indent, DEV_LIBRARIES
+            ));
+        }
+        previous = trimmed;
+    }
+    if updated != content
+        && let Err(e) = safe_path::write(CMAKE_PATH, updated)
+    {
+        log::problem!(
+            "Warning: failed to link the tests to dev-dependencies in CMakeLists.txt: {}",
+            e
+        );
+    }
+}
+
+/// Drop the block for a dependency that is no longer declared
+pub fn remove(name: &str) {
+    let Ok(content) = fs::read_to_string(CMAKE_PATH) else {
+        return;
+    };
+    let begin = format!("# @c1_dep {}\n", name);
+    let end = format!("# @c1_dep_end {}", name);
+    let (Some(start), Some(stop)) = (content.find(&begin), content.find(&end)) else {
+        return;
+    };
+    if stop < start {
+        return;
+    }
+    let stop = stop + end.len();
+    let stop = stop + content[stop..].find('\n').map_or(0, |i| i + 1);
+    let updated = format!("{}{}", &content[..start], &content[stop..]);
+    if let Err(e) = safe_path::write(CMAKE_PATH, updated) {
+        log::problem!(
+            "Warning: failed to unwire {} from CMakeLists.txt: {}",
+            name,
+            e
+        );
+        return;
+    }
+    log::status!("  ✓ Unwired {} from CMakeLists.txt", name);
+}
+
+fn insert(name: &str, block: &str) {
+    let Ok(content) = fs::read_to_string(CMAKE_PATH) else {
+        return;
+    };
+    let begin = format!("# @c1_dep {}", name);
+    let end = format!("# @c1_dep_end {}", name);
+    let block = format!("{}\n{}{}\n", begin, block, end);
+
+    let updated = match (content.find(&begin), content.find(&end)) {
+        (Some(start), Some(stop)) if stop > start => {
+            let stop = stop + end.len();
+            let stop = stop + content[stop..].find('\n').map_or(0, |i| i + 1);
+            format!("{}{}{}", &content[..start], block, &content[stop..])
+        }
+        _ => match content.find(PLACEHOLDER) {
+            Some(at) => format!("{}{}{}", &content[..at], block, &content[at..]),
+            None if defines_target(&content) => {
+                // Projects created before the placeholder existed
+                let mut updated = content.clone();
+                if !updated.ends_with('\n') {
+                    updated.push('\n');
+                }
+                updated.push_str(&format!(
+                    "\n# Dependencies in external/, wired up by `c1 add` and `c1 sync`\n{}{}\n",
+                    block, PLACEHOLDER
+                ));
+                updated
+            }
+            None => {
+                log::problem!(
+                    "Warning: no add_executable/add_library in CMakeLists.txt; wire {} up by hand",
+                    name
+                );
+                return;
+            }
+        },
+    };
+    if updated == content {
+        return;
+    }
+    if let Err(e) = safe_path::write(CMAKE_PATH, updated) {
+        log::problem!(
+            "Warning: failed to wire {} into CMakeLists.txt: {}",
+            name,
+            e
+        );
+        return;
+    }
+    log::status!("  ✓ Wired {} into CMakeLists.txt", name);
+}
+
+/// The library target a dependency's own CMakeLists.txt defines, if any
+fn library_target(dir: &Path) -> Option<(String, bool)> {
+    let content = fs::read_to_string(dir.join("CMakeLists.txt")).ok()?;
+    let project = content.lines().find_map(|line| {
+        let args = line.trim_start().strip_prefix("project(")?;
+        args.split([' ', ')'])
+            .next()
+            .map(|s| s.trim_matches('"').to_string())
+    });
+    content.lines().find_map(|line| {
+        let args = line.trim_start().strip_prefix("add_library(")?;
+        let mut words = args.split_whitespace();
+        let target = words.next()?.trim_end_matches(')');
+        let kind = words.next();
+        if matches!(kind, Some("ALIAS") | Some("IMPORTED")) {
+            return None;
+        }
+        let interface = kind == Some("INTERFACE");
+        match target {
+            "${PROJECT_NAME}" => project.clone().map(|project| (project, interface)),
+            target => Some((target.to_string(), interface)),
+        }
+    })
+}
+
+/// Include path and sources for a dependency without a usable CMake build:
+/// a static library when it has sources, the include path alone otherwise
+fn source_block(name: &str, dir: &Path, dev: bool) -> String {
+    let base = cmake_path(dir);
+    let includes: Vec<String> = include_dirs(name, dir)
+        .iter()
+        .map(|include| match include.as_str() {
+            "." => format!("${{CMAKE_CURRENT_SOURCE_DIR}}/{}", base),
+            include => format!("${{CMAKE_CURRENT_SOURCE_DIR}}/{}/{}", base, include),
+        })
+        .collect();
+    let sources = dependency_sources(dir);
+    let library = library_name(name);
+    if sources.is_empty() && dev {
+        // Headers only, which tests and benches reach through the library
+        return format!(
+            "add_library({} INTERFACE)\n\
+             target_include_directories({} INTERFACE\n    {}\n)\n{}",
+            library,
+            library,
+            includes.join("\n    "),
+            link(&library, dev)
+        );
+    }
+    if sources.is_empty() {
+        return format!(
+            "target_include_directories(${{PROJECT_NAME}} PRIVATE\n    {}\n)\n",
+            includes.join("\n    ")
+        );
+    }
+
+    let mut block = format!("add_library({} STATIC\n", library);
+    for source in sources {
+        block.push_str(&format!("    {}/{}\n", base, source));
+    }
+    block.push_str(")\n");
+    block.push_str(&format!(
+        "target_include_directories({} PUBLIC\n    {}\n)\n",
+        library,
+        includes.join("\n    ")
+    ));
+    block.push_str(&compile_options(name, &library));
+    block.push_str(&link(&library, dev));
+    block
+}
+
+/// The line adding the dependency's `cflags` from project.toml to `target`,
+/// if it has any
+fn compile_options(name: &str, target: &str) -> String {
+    match entry_list(name, "cflags") {
+        Some(flags) if !flags.is_empty() => format!(
+            "target_compile_options({} PRIVATE {})\n",
+            target,
+            flags.join(" ")
+        ),
+        _ => String::new(),
+    }
+}
+
+/// A directory as CMakeLists.txt spells it, relative to the project
+fn cmake_path(dir: &Path) -> String {
+    dir.to_string_lossy().replace('\\', "/")
+}
+
+/// The CMake target plain code from `external/<name>` is compiled into
+fn library_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("c1_dep_{}", name)
+}
+
+/// Directories of a dependency to put on the include path, relative to it:
+/// its `include` entry in project.toml, or else include/, src/ when it holds
+/// headers and the root when it does, falling back to the root
+pub fn include_dirs(name: &str, dir: &Path) -> Vec<String> {
+    if let Some(includes) = include_override(name) {
+        return includes;
+    }
+    let has_headers = |dir: &Path| {
+        fs::read_dir(dir).is_ok_and(|entries| {
+            entries
+                .flatten()
+                .any(|e| e.path().extension().is_some_and(|ext| ext == "h"))
+        })
+    };
+    let mut includes = Vec::new();
+    if dir.join("include").is_dir() {
+        includes.push("include".to_string());
+    }
+    if has_headers(&dir.join("src")) {
+        includes.push("src".to_string());
+    }
+    if includes.is_empty() || has_headers(dir) {
+        includes.push(".".to_string());
+    }
+    includes
+}
+
+/// `include = ["..."]` from the dependency's entry in project.toml
+fn include_override(name: &str) -> Option<Vec<String>> {
+    let includes = entry_list(name, "include")?;
+    Some(
+        includes
+            .iter()
+            .map(|include| include.trim_end_matches('/').to_string())
+            .collect(),
+    )
+}
+
+/// The strings of `key` in the dependency's entry in project.toml
+fn entry_list(name: &str, key: &str) -> Option<Vec<String>> {
+    let manifest: toml::Table = fs::read_to_string("project.toml").ok()?.parse().ok()?;
+    let entry = ["dependencies", "dev-dependencies"]
+        .iter()
+        .find_map(|section| manifest.get(*section)?.get(name))?;
+    let values = entry.get(key)?.as_array()?;
+    Some(
+        values
+            .iter()
+            .filter_map(|value| value.as_str())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// .c files under src/ (recursively), or at the top level when there is no
+/// src/; programs, tests and examples are left out
+fn dependency_sources(dir: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    let src = dir.join("src");
+    if src.is_dir() {
+        crate::fmt::collect(&src, &mut files);
+    } else if let Ok(entries) = fs::read_dir(dir) {
+        files.extend(entries.flatten().map(|e| e.path()).filter(|p| p.is_file()));
+    }
+    let mut sources: Vec<String> = files
+        .iter()
+        .filter(|p| p.extension().is_some_and(|e| e == "c"))
+        .filter_map(|p| p.strip_prefix(dir).ok())
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .filter(|p| {
+            let lower = p.to_lowercase();
+            !(lower.ends_with("main.c") || lower.contains("test") || lower.contains("example"))
+        })
+        .collect();
+    sources.sort();
+    sources
+}
+
+fn defines_target(content: &str) -> bool {
+    content.lines().any(|line| {
+        let line = line.trim_start();
+        line.starts_with("add_executable(") || line.starts_with("add_library(")
+    })
+}
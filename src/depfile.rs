@@ -0,0 +1,151 @@
+//! Parsing of compiler-generated `.d` dependency files.
+//!
+//! gcc and clang emit Makefile-style rules (`obj.o: a.c b.h \`) next to each
+//! object when invoked with `-MD`, which CMake does for both the Makefile and
+//! Ninja generators. These rules tell us which headers a translation unit was
+//! compiled against, and therefore which edits forced it to recompile.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Parse the prerequisites of every rule in a depfile.
+///
+/// Handles line continuations, escaped spaces (`\ `), `$$` escapes and the
+/// phony `header.h:` rules emitted by `-MP`, which are skipped.
+pub fn parse(content: &str) -> Vec<PathBuf> {
+    let joined = content.replace("\\\r\n", " ").replace("\\\n", " ");
+    let mut deps = Vec::new();
+
+    for line in joined.lines() {
+        let Some(colon) = find_rule_colon(line) else {
+            continue;
+        };
+        for token in split_tokens(&line[colon + 1..]) {
+            let path = PathBuf::from(token);
+            if !deps.contains(&path) {
+                deps.push(path);
+            }
+        }
+    }
+    deps
+}
+
+/// Read and parse a depfile from disk
+pub fn read(path: &Path) -> Option<Vec<PathBuf>> {
+    fs::read_to_string(path).ok().map(|content| parse(&content))
+}
+
+/// Count how many translation units depend on each file.
+///
+/// A header included by many objects is expensive to touch; this is used to
+/// rank the files that triggered a rebuild.
+pub fn reverse_dependency_counts(depfiles: &[Vec<PathBuf>]) -> BTreeMap<PathBuf, usize> {
+    let mut counts = BTreeMap::new();
+    for deps in depfiles {
+        for dep in deps {
+            *counts.entry(dep.clone()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+/// Locate the rule separator, ignoring the `C:` of Windows drive letters
+fn find_rule_colon(line: &str) -> Option<usize> {
+    let bytes = line.as_bytes();
+    (0..bytes.len()).find(|&i| bytes[i] == b':' && !is_drive_letter(bytes, i))
+}
+
+fn is_drive_letter(bytes: &[u8], colon: usize) -> bool {
+    colon >= 1
+        && bytes[colon - 1].is_ascii_alphabetic()
+        && (colon == 1 || bytes[colon - 2].is_ascii_whitespace())
+        && matches!(bytes.get(colon + 1), Some(b'/') | Some(b'\\'))
+}
+
+/// Split a prerequisite list on unescaped whitespace
+fn split_tokens(list: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = list.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&' ') => {
+                current.push(' ');
+                chars.next();
+            }
+            '$' if chars.peek() == Some(&'$') => {
+                current.push('$');
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                if !current.is_empty() {
+                    tokens.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(deps: &[&str]) -> Vec<PathBuf> {
+        deps.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn joins_line_continuations() {
+        let content = "build/main.c.o: src/main.c \\\n  include/a.h \\\r\n  include/b.h\n";
+        assert_eq!(
+            parse(content),
+            paths(&["src/main.c", "include/a.h", "include/b.h"])
+        );
+    }
+
+    #[test]
+    fn keeps_escaped_spaces_in_paths() {
+        let content = "main.o: my\\ project/main.c my\\ project/inc/a\\ b.h price$$.h\n";
+        assert_eq!(
+            parse(content),
+            paths(&["my project/main.c", "my project/inc/a b.h", "price$.h"])
+        );
+    }
+
+    #[test]
+    fn reads_rules_with_several_targets() {
+        let content = "a.o a.d: a.c common.h\nb.o: b.c common.h\n";
+        assert_eq!(parse(content), paths(&["a.c", "common.h", "b.c"]));
+    }
+
+    #[test]
+    fn skips_the_phony_rules_of_mp() {
+        let content = "main.o: main.c a.h\na.h:\n";
+        assert_eq!(parse(content), paths(&["main.c", "a.h"]));
+    }
+
+    #[test]
+    fn does_not_split_at_windows_drive_letters() {
+        let content =
+            "C:/proj/build/main.c.obj: C:/proj/main.c \\\n  C:\\proj\\include\\a.h d:/sdk/b.h\n";
+        assert_eq!(
+            parse(content),
+            paths(&["C:/proj/main.c", "C:\\proj\\include\\a.h", "d:/sdk/b.h"])
+        );
+    }
+
+    #[test]
+    fn counts_the_units_including_each_file() {
+        let counts =
+            reverse_dependency_counts(&[paths(&["a.c", "common.h"]), paths(&["b.c", "common.h"])]);
+        assert_eq!(counts[&PathBuf::from("common.h")], 2);
+        assert_eq!(counts[&PathBuf::from("a.c")], 1);
+    }
+}
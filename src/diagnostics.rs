@@ -0,0 +1,240 @@
+//! The compiler's warnings and errors from `c1 build`, shown grouped by file.
+//!
+//! gcc and clang report problems as `file:line:col: warning: message`, which
+//! in cmake's build log scroll past between progress lines. Those lines (and
+//! the compiler's own source excerpts under them) are held back while the
+//! build runs; everything else the build prints passes through as it comes.
+//! Afterwards each file's diagnostics are shown together, each with the
+//! source line read from the file and a caret under the column, and a
+//! summary such as `3 errors, 12 warnings in 5 files`, colored as `--color`
+//! says. Under `--message-format json` each one is a `diagnostic` event
+//! instead. `[build] warnings_as_errors = true` fails a build that printed
+//! any warning in the project's own code; warnings from dependencies in
+//! external/ are shown but do not count.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{events, style};
+
+const SEVERITIES: &[&str] = &["fatal error", "error", "warning", "note"];
+
+#[derive(PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: Option<usize>,
+    /// `error`, `warning` or `note`; a fatal error counts as an error
+    pub severity: &'static str,
+    pub message: String,
+}
+
+/// A `file:line[:col]: severity: message` line
+pub fn parse(line: &str) -> Option<Diagnostic> {
+    let line = strip_colors(line.trim_end_matches(['\n', '\r']));
+    let (at, severity, message) = SEVERITIES.iter().find_map(|severity| {
+        let marker = format!(": {}: ", severity);
+        let at = line.find(&marker)?;
+        Some((at, *severity, &line[at + marker.len()..]))
+    })?;
+    let location = &line[..at];
+    // The file name may itself contain colons (`C:\src\a.c`), so read the
+    // numbers from the right
+    let mut parts = location.rsplitn(3, ':');
+    let last: usize = parts.next()?.parse().ok()?;
+    let middle = parts.next()?;
+    let (file, line_number, column) = match (middle.parse::<usize>(), parts.next()) {
+        (Ok(line_number), Some(file)) => (file, line_number, Some(last)),
+        // `file:line:` without a column
+        _ => (location.rsplit_once(':')?.0, last, None),
+    };
+    if file.is_empty() {
+        return None;
+    }
+    Some(Diagnostic {
+        file: file.to_string(),
+        line: line_number,
+        column,
+        severity: if severity == "fatal error" {
+            "error"
+        } else {
+            severity
+        },
+        message: message.to_string(),
+    })
+}
+
+/// Whether the build should hold `line` back: a diagnostic, or what the
+/// compiler prints around one (`  12 |   code`, `     |   ^~~`, `In function`)
+pub fn is_held(line: &str) -> bool {
+    let line = strip_colors(line.trim_end_matches(['\n', '\r']));
+    if parse(&line).is_some() {
+        return true;
+    }
+    let gutter = line
+        .trim_start()
+        .trim_start_matches(|c: char| c.is_ascii_digit() || c == '+');
+    if gutter.starts_with(" |") || (gutter.starts_with('|') && line.starts_with(' ')) {
+        return true;
+    }
+    line.ends_with(':') && (line.contains(": In function '") || line.ends_with(": At top level:"))
+}
+
+/// The diagnostics in the build's output, duplicates (a header's warning
+/// from every file that includes it) dropped
+pub fn collect(output: &[&str]) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for text in output {
+        for diagnostic in text.lines().filter_map(parse) {
+            if !diagnostics.contains(&diagnostic) {
+                diagnostics.push(diagnostic);
+            }
+        }
+    }
+    diagnostics
+}
+
+pub fn count(diagnostics: &[Diagnostic], severity: &str) -> usize {
+    diagnostics
+        .iter()
+        .filter(|d| d.severity == severity)
+        .count()
+}
+
+/// Diagnostics of `severity` in the project's own files, leaving out the
+/// dependencies in external/
+pub fn count_own(diagnostics: &[Diagnostic], severity: &str, build_dir: &Path) -> usize {
+    let external = fs::canonicalize("external").ok();
+    diagnostics
+        .iter()
+        .filter(|d| d.severity == severity)
+        .filter(|d| {
+            let path = fs::canonicalize(locate(&d.file, build_dir));
+            !matches!((path, &external), (Ok(path), Some(external)) if path.starts_with(external))
+        })
+        .count()
+}
+
+/// Show the diagnostics grouped by file, in the order the files first
+/// appeared, then the summary line. Relative paths are looked up in
+/// `build_dir` when they are not found from the project root.
+pub fn print(diagnostics: &[Diagnostic], build_dir: &Path) {
+    if diagnostics.is_empty() {
+        return;
+    }
+    if events::is_json() {
+        for d in diagnostics {
+            events::diagnostic(&d.file, d.line, d.column, d.severity, &d.message);
+        }
+        return;
+    }
+    let paint = |code: &str, text: &str| style::paint(true, code, text);
+    let mut files: Vec<&str> = Vec::new();
+    for d in diagnostics {
+        if !files.contains(&d.file.as_str()) {
+            files.push(&d.file);
+        }
+    }
+    eprintln!();
+    for file in &files {
+        let path = locate(file, build_dir);
+        let source = fs::read_to_string(&path).ok();
+        let shown = display_path(&path);
+        for d in diagnostics.iter().filter(|d| d.file == *file) {
+            let label = match d.severity {
+                "error" => paint("1;31", "error"),
+                "warning" => paint("1;33", "warning"),
+                _ => paint("1;36", d.severity),
+            };
+            eprintln!("{}: {}", label, paint("1", &d.message));
+            let location = match d.column {
+                Some(column) => format!("{}:{}:{}", shown, d.line, column),
+                None => format!("{}:{}", shown, d.line),
+            };
+            let number = d.line.to_string();
+            let pad = " ".repeat(number.len());
+            eprintln!("{}{} {}", pad, paint("1;34", "-->"), location);
+            let excerpt = source
+                .as_deref()
+                .and_then(|s| s.lines().nth(d.line.saturating_sub(1)))
+                .map(expand_tabs);
+            if let Some(excerpt) = excerpt {
+                let bar = paint("1;34", "|");
+                eprintln!("{} {}", pad, bar);
+                eprintln!("{} {} {}", paint("1;34", &number), bar, excerpt);
+                if let Some(column) = d.column {
+                    let indent = " ".repeat(column.saturating_sub(1));
+                    eprintln!("{} {} {}{}", pad, bar, indent, paint("1;32", "^"));
+                }
+            }
+            eprintln!();
+        }
+    }
+    eprintln!("{}", summary(diagnostics, files.len()));
+}
+
+/// `3 errors, 12 warnings in 5 files`
+pub fn summary(diagnostics: &[Diagnostic], files: usize) -> String {
+    let plural = |n: usize, word: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { "s" });
+    format!(
+        "{}, {} in {}",
+        plural(count(diagnostics, "error"), "error"),
+        plural(count(diagnostics, "warning"), "warning"),
+        plural(files, "file")
+    )
+}
+
+fn locate(file: &str, build_dir: &Path) -> PathBuf {
+    let path = Path::new(file);
+    if path.is_absolute() || path.exists() {
+        path.to_path_buf()
+    } else {
+        build_dir.join(path)
+    }
+}
+
+/// Project files relative to the current directory
+fn display_path(path: &Path) -> String {
+    let cwd = std::env::current_dir().ok();
+    let absolute = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    match cwd
+        .and_then(|cwd| fs::canonicalize(cwd).ok())
+        .and_then(|cwd| absolute.strip_prefix(cwd).ok().map(Path::to_path_buf))
+    {
+        Some(relative) => relative.display().to_string(),
+        None => path.display().to_string(),
+    }
+}
+
+/// Tabs as spaces to the next multiple of 8, the way gcc counts columns
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    for c in line.chars() {
+        if c == '\t' {
+            let width = 8 - expanded.chars().count() % 8;
+            expanded.push_str(&" ".repeat(width));
+        } else {
+            expanded.push(c);
+        }
+    }
+    expanded
+}
+
+/// Drop the escape sequences of `-fdiagnostics-color`
+fn strip_colors(line: &str) -> String {
+    let mut plain = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // ESC [ parameters final-byte
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
@@ -0,0 +1,121 @@
+//! `c1 doc`: API documentation generated with doxygen.
+//!
+//! The first run writes a Doxyfile at the project root, seeded from
+//! project.toml, which is then the user's to tweak. HTML goes into
+//! `<build dir>/doc/html`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{self, Context, Error};
+use crate::{log, safe_path};
+
+const DOXYFILE: &str = "Doxyfile";
+
+pub fn cmd_doc(open: bool) -> error::Result<()> {
+    let config = crate::read_config()?;
+    let output_dir = crate::resolve_build_dir(None).join("doc");
+
+    if !Path::new(DOXYFILE).exists() {
+        let doxyfile = doxyfile(
+            &config.project.name,
+            &config.project.version,
+            &config.project.description,
+            &output_dir,
+        );
+        safe_path::write(DOXYFILE, doxyfile)
+            .context(|| format!("failed to create {}", DOXYFILE))?;
+        log::status!("✓ Created {}", DOXYFILE);
+    }
+    // doxygen overwrites what it generated there before
+    safe_path::check(&output_dir, "overwrite")
+        .context(|| format!("failed to write {}", output_dir.display()))?;
+    fs::create_dir_all(&output_dir)
+        .context(|| format!("failed to create {}", output_dir.display()))?;
+
+    log::status!("Generating documentation...");
+    let status = match Command::new("doxygen").arg(DOXYFILE).status() {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::failure("doxygen not found.").hint(
+                "install doxygen (e.g. `apt install doxygen` or `brew install doxygen`) and make sure it is on PATH",
+            ));
+        }
+        Err(e) => return Err(Error::failure(format!("failed to run doxygen: {}", e))),
+    };
+    if !status.success() {
+        return Err(Error::failure("doxygen failed."));
+    }
+
+    let index = output_dir.join("html").join("index.html");
+    if !index.is_file() {
+        return Err(Error::failure(format!(
+            "doxygen did not produce {}",
+            index.display()
+        ))
+        .hint(format!(
+            "check OUTPUT_DIRECTORY and GENERATE_HTML in {}",
+            DOXYFILE
+        )));
+    }
+    log::status!("✓ Documentation generated: {}", index.display());
+
+    if open {
+        open_in_browser(&index)?;
+    }
+    Ok(())
+}
+
+/// The initial Doxyfile; only settings that differ from doxygen's defaults
+fn doxyfile(name: &str, version: &str, description: &str, output_dir: &Path) -> String {
+    let input: Vec<&str> = ["include", "src"]
+        .into_iter()
+        .filter(|dir| Path::new(dir).is_dir())
+        .collect();
+    format!(
+        r#"# Generated by `c1 doc`; edit freely, c1 will not overwrite it
+PROJECT_NAME           = "{}"
+PROJECT_NUMBER         = "{}"
+PROJECT_BRIEF          = "{}"
+INPUT                  = {}
+FILE_PATTERNS          = *.c *.h
+RECURSIVE              = YES
+OUTPUT_DIRECTORY       = {}
+EXTRACT_ALL            = YES
+OPTIMIZE_OUTPUT_FOR_C  = YES
+GENERATE_HTML          = YES
+GENERATE_LATEX         = NO
+QUIET                  = YES
+"#,
+        quote(name),
+        quote(version),
+        quote(description),
+        input.join(" "),
+        output_dir.display()
+    )
+}
+
+/// Escape a value for a double-quoted Doxyfile string
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn open_in_browser(path: &Path) -> error::Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(&path)
+        .status()
+        .map_err(|e| Error::failure(format!("failed to open {}: {}", path.display(), e)))?;
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! `--emit` and `[build] emit`: images for flashing, written next to the
+//! executable after a successful build.
+//!
+//! `bin` and `hex` are made with `objcopy -O binary` and `objcopy -O ihex`,
+//! using the objcopy cmake found for the compiler (`arm-none-eabi-objcopy` for
+//! a cross toolchain) and plain `objcopy` otherwise. `map` asks the linker for
+//! a map file through the link flags, so it takes effect at configure time
+//! like `--sanitize`.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{self, Error};
+use crate::{build_history, log};
+
+/// Every kind of artifact `--emit` accepts
+const KINDS: &[&str] = &["bin", "hex", "map"];
+
+/// Restricts `--emit` to the names in `KINDS`
+pub fn value_parser() -> clap::builder::PossibleValuesParser {
+    KINDS.iter().copied().into()
+}
+
+/// Reject `[build] emit` entries that are not in `KINDS`
+pub fn check(kinds: &[String]) -> error::Result<()> {
+    match kinds.iter().find(|kind| !KINDS.contains(&kind.as_str())) {
+        Some(kind) => Err(
+            Error::config(format!("unknown artifact '{}' in [build] emit", kind))
+                .hint(format!("expected any of: {}", KINDS.join(", "))),
+        ),
+        None => Ok(()),
+    }
+}
+
+fn map_file(build_dir: &Path, name: &str) -> PathBuf {
+    let build_dir = std::path::absolute(build_dir).unwrap_or_else(|_| build_dir.to_path_buf());
+    build_dir.join(format!("{}.map", name))
+}
+
+/// The link flag writing the map file, when one is asked for
+pub fn link_flags(kinds: &[String], build_dir: &Path, name: &str) -> Option<String> {
+    kinds
+        .iter()
+        .any(|kind| kind == "map")
+        .then(|| format!("-Wl,-Map={}", crate::flag_path(&map_file(build_dir, name))))
+}
+
+/// Write the `bin` and `hex` images of `executable` and report every
+/// artifact with its size
+pub fn write(
+    kinds: &[String],
+    build_dir: &Path,
+    executable: &Path,
+    name: &str,
+) -> error::Result<()> {
+    let objcopy = crate::cached_value(build_dir, "CMAKE_OBJCOPY")
+        .filter(|path| !path.is_empty() && !path.ends_with("-NOTFOUND"))
+        .unwrap_or_else(|| "objcopy".to_string());
+    for kind in KINDS.iter().filter(|k| kinds.iter().any(|kind| kind == *k)) {
+        let path = match *kind {
+            "map" => build_dir.join(format!("{}.map", name)),
+            format => {
+                let path = executable.with_file_name(format!("{}.{}", name, format));
+                let mut command = Command::new(&objcopy);
+                command
+                    .args(["-O", if format == "bin" { "binary" } else { "ihex" }])
+                    .arg(executable)
+                    .arg(&path);
+                log::command(&command);
+                let output = command.output().map_err(|e| {
+                    Error::failure(format!("failed to run {}: {}", objcopy, e))
+                        .hint("install binutils for the target, or drop bin and hex from --emit")
+                })?;
+                if !output.status.success() {
+                    return Err(Error::failure(format!(
+                        "{} could not write {}: {}",
+                        objcopy,
+                        path.display(),
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    )));
+                }
+                path
+            }
+        };
+        match std::fs::metadata(&path) {
+            Ok(metadata) => log::status!(
+                "✓ Emitted {} ({})",
+                path.display(),
+                build_history::bytes(metadata.len())
+            ),
+            Err(_) => log::problem!(
+                "Warning: the linker wrote no {}; does it support -Map?",
+                path.display()
+            ),
+        }
+    }
+    Ok(())
+}
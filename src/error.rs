@@ -0,0 +1,145 @@
+//! The error type command functions return, and the exit codes it maps to.
+//!
+//! `main` prints an error as `Error: <message>` (plus a `Hint:` line when it
+//! has one) and exits with a code that tells scripts what kind of failure it
+//! was:
+//!
+//! | code | meaning                                          |
+//! |------|--------------------------------------------------|
+//! | 1    | any other failure                                |
+//! | 2    | `c1 sync --max-time` ran out of time (not an error) |
+//! | 3    | cmake or the compiler failed                     |
+//! | 64   | bad arguments, or a command used in the wrong place |
+//! | 74   | reading or writing a file failed                 |
+//! | 78   | project.toml or another input is malformed       |
+
+use std::fmt;
+use std::io;
+
+use crate::{events, log};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Failure,
+    /// Stopped early with part of the work done, which is not an error
+    Partial,
+    Build,
+    Usage,
+    Io,
+    Config,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    kind: Kind,
+    message: String,
+    hint: Option<String>,
+}
+
+impl Error {
+    pub fn new(kind: Kind, message: impl Into<String>) -> Error {
+        Error {
+            kind,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    pub fn failure(message: impl Into<String>) -> Error {
+        Error::new(Kind::Failure, message)
+    }
+
+    pub fn partial(message: impl Into<String>) -> Error {
+        Error::new(Kind::Partial, message)
+    }
+
+    pub fn build(message: impl Into<String>) -> Error {
+        Error::new(Kind::Build, message)
+    }
+
+    pub fn usage(message: impl Into<String>) -> Error {
+        Error::new(Kind::Usage, message)
+    }
+
+    pub fn config(message: impl Into<String>) -> Error {
+        Error::new(Kind::Config, message)
+    }
+
+    /// Attach a suggestion printed after the error
+    pub fn hint(mut self, hint: impl Into<String>) -> Error {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self.kind {
+            Kind::Failure => 1,
+            Kind::Partial => 2,
+            Kind::Build => 3,
+            Kind::Usage => 64,
+            Kind::Io => 74,
+            Kind::Config => 78,
+        }
+    }
+
+    /// Print the error and its hint to stderr, or as an event on stdout
+    /// under `--message-format json`
+    pub fn report(&self) {
+        if events::is_json() {
+            let kind = match self.kind {
+                Kind::Failure => "failure",
+                Kind::Partial => "partial",
+                Kind::Build => "build",
+                Kind::Usage => "usage",
+                Kind::Io => "io",
+                Kind::Config => "config",
+            };
+            events::error(kind, &self.message, self.hint.as_deref());
+            return;
+        }
+        if self.kind == Kind::Partial {
+            log::status!("\n{}", self.message);
+            if let Some(hint) = &self.hint {
+                log::status!("{}", hint);
+            }
+            return;
+        }
+        log::problem!("Error: {}", self.message);
+        if let Some(hint) = &self.hint {
+            log::problem!("Hint: {}", hint);
+        }
+    }
+
+    /// Report the error and exit, for callers that cannot return it
+    pub fn exit(&self) -> ! {
+        self.report();
+        events::summary(self.exit_code());
+        std::process::exit(self.exit_code())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Most helpers report failures as plain strings
+impl From<String> for Error {
+    fn from(message: String) -> Error {
+        Error::failure(message)
+    }
+}
+
+/// Describe a failed file operation: `.context(|| format!("failed to create {}", path))`
+pub trait Context<T> {
+    fn context(self, what: impl FnOnce() -> String) -> Result<T>;
+}
+
+impl<T> Context<T> for io::Result<T> {
+    fn context(self, what: impl FnOnce() -> String) -> Result<T> {
+        self.map_err(|e| Error::new(Kind::Io, format!("{}: {}", what(), e)))
+    }
+}
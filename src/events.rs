@@ -0,0 +1,179 @@
+//! `--message-format json`: newline-delimited JSON events on stdout.
+//!
+//! Each line is one object whose `event` field says what happened:
+//!
+//! | event            | fields                                              |
+//! |------------------|-----------------------------------------------------|
+//! | `build_started`  | `profile`, `build_type`, `build_dir`                |
+//! | `build_finished` | `success`, `duration_ms`, `artifact`                |
+//! | `dependency`     | `name`, `success`, `commit`, `error`                |
+//! | `sync_summary`   | `synced`, `updated`, `up_to_date`, `skipped`, `failed` (`name`, `error` each) |
+//! | `file_created`   | `path`, absolute                                    |
+//! | `output`         | `stream` (`stdout`/`stderr`), `text`: a raw line from cmake, the compiler or git |
+//! | `diagnostic`     | `severity` (`error`/`warning`/`note`), `file`, `line`, `column`, `message` |
+//! | `error`          | `kind`, `message`, `hint`                           |
+//! | `summary`        | `success`, `exit_code`; always the last line        |
+//!
+//! The human-readable status lines are silenced in this mode. Commands whose
+//! whole output is a report (`c1 info`, `c1 list`) keep printing it, and so
+//! does the program `c1 run` starts.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::json;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Print one event; the field values are already JSON
+fn emit(event: &str, fields: &[(&str, String)]) {
+    if !is_json() {
+        return;
+    }
+    let mut line = format!("{{\"event\":{}", json::string(event));
+    for (key, value) in fields {
+        line.push_str(&format!(",{}:{}", json::string(key), value));
+    }
+    line.push('}');
+    println!("{}", line);
+}
+
+fn path(path: &Path) -> String {
+    json::string(&path.to_string_lossy())
+}
+
+pub fn build_started(profile: &str, build_type: &str, build_dir: &Path) {
+    emit(
+        "build_started",
+        &[
+            ("profile", json::string(profile)),
+            ("build_type", json::string(build_type)),
+            ("build_dir", path(build_dir)),
+        ],
+    );
+}
+
+pub fn build_finished(success: bool, duration: Duration, artifact: Option<&Path>) {
+    emit(
+        "build_finished",
+        &[
+            ("success", success.to_string()),
+            ("duration_ms", duration.as_millis().to_string()),
+            ("artifact", artifact.map_or("null".to_string(), path)),
+        ],
+    );
+}
+
+/// A dependency synced to `commit`, or that failed with an error
+pub fn dependency(name: &str, result: Result<Option<&str>, &str>) {
+    let (commit, error) = match result {
+        Ok(commit) => (commit, None),
+        Err(error) => (None, Some(error)),
+    };
+    emit(
+        "dependency",
+        &[
+            ("name", json::string(name)),
+            ("success", error.is_none().to_string()),
+            ("commit", json::optional(commit)),
+            ("error", json::optional(error)),
+        ],
+    );
+}
+
+/// The totals of `c1 sync`, after its `dependency` events
+pub fn sync_summary(
+    synced: usize,
+    updated: usize,
+    up_to_date: usize,
+    skipped: usize,
+    failed: &[(String, String)],
+) {
+    let failed: Vec<String> = failed
+        .iter()
+        .map(|(name, error)| {
+            format!(
+                "{{\"name\":{},\"error\":{}}}",
+                json::string(name),
+                json::string(error)
+            )
+        })
+        .collect();
+    emit(
+        "sync_summary",
+        &[
+            ("synced", synced.to_string()),
+            ("updated", updated.to_string()),
+            ("up_to_date", up_to_date.to_string()),
+            ("skipped", skipped.to_string()),
+            ("failed", format!("[{}]", failed.join(","))),
+        ],
+    );
+}
+
+pub fn file_created(file: &str) {
+    let file = std::path::absolute(file).unwrap_or_else(|_| file.into());
+    emit("file_created", &[("path", path(&file))]);
+}
+
+/// A line a child process printed, without its line ending
+pub fn output(stream: &str, text: &str) {
+    let text = text.trim_end_matches(['\n', '\r']);
+    if text.is_empty() {
+        return;
+    }
+    emit(
+        "output",
+        &[
+            ("stream", json::string(stream)),
+            ("text", json::string(text)),
+        ],
+    );
+}
+
+/// A compiler warning or error from the build
+pub fn diagnostic(file: &str, line: usize, column: Option<usize>, severity: &str, message: &str) {
+    emit(
+        "diagnostic",
+        &[
+            ("severity", json::string(severity)),
+            ("file", json::string(file)),
+            ("line", line.to_string()),
+            (
+                "column",
+                column.map_or("null".to_string(), |c| c.to_string()),
+            ),
+            ("message", json::string(message)),
+        ],
+    );
+}
+
+pub fn error(kind: &str, message: &str, hint: Option<&str>) {
+    emit(
+        "error",
+        &[
+            ("kind", json::string(kind)),
+            ("message", json::string(message)),
+            ("hint", json::optional(hint)),
+        ],
+    );
+}
+
+pub fn summary(exit_code: i32) {
+    emit(
+        "summary",
+        &[
+            ("success", (exit_code == 0).to_string()),
+            ("exit_code", exit_code.to_string()),
+        ],
+    );
+}
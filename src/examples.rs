@@ -0,0 +1,195 @@
+//! Example programs in examples/.
+//!
+//! Every `examples/<name>.c` is its own program with a `main`, registered in
+//! a managed block of CMakeLists.txt, `# @c1_examples` through
+//! `# @c1_examples_end`, as the target `<name>`. A library's examples link
+//! the library; an executable's are compiled with its sources except main.c.
+//! The targets are `EXCLUDE_FROM_ALL`, so a plain `c1 build` does not compile
+//! them: `c1 build --examples` builds them all and `c1 run --example <name>`
+//! just the one it runs.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::{self, Context, Error};
+use crate::{ProjectConfig, ProjectKind, log, safe_path};
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+const EXAMPLE_DIR: &str = "examples";
+const BEGIN: &str = "# @c1_examples";
+const END: &str = "# @c1_examples_end";
+
+/// The names of the .c files directly in examples/, sorted
+pub fn find() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(EXAMPLE_DIR) else {
+        return Vec::new();
+    };
+    let mut examples: Vec<String> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "c"))
+        .filter_map(|p| Some(p.file_stem()?.to_string_lossy().into_owned()))
+        .collect();
+    examples.sort();
+    examples
+}
+
+/// Check the example names and bring the CMakeLists block up to date
+pub fn wire(config: &ProjectConfig) -> error::Result<()> {
+    let examples = find();
+    let tests: Vec<String> = fs::read_dir("tests")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "c"))
+        .filter_map(|p| Some(p.file_stem()?.to_string_lossy().into_owned()))
+        .collect();
+    for example in &examples {
+        if !example
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(Error::config(format!(
+                "example '{}' must be named with only letters, numbers, '_' and '-'",
+                example
+            ))
+            .hint(format!("rename {}/{}.c", EXAMPLE_DIR, example)));
+        }
+        let clash = if *example == config.project.name {
+            Some("the project's executable".to_string())
+        } else if config.bin.iter().any(|bin| bin.name == *example) {
+            Some("a [[bin]] target".to_string())
+        } else if tests.contains(example) {
+            Some(format!("the test program tests/{}.c", example))
+        } else if crate::RESERVED_NAMES.contains(&example.as_str()) {
+            Some("a target CMake defines itself".to_string())
+        } else {
+            None
+        };
+        if let Some(clash) = clash {
+            return Err(Error::config(format!(
+                "example '{}' has the same target name as {}",
+                example, clash
+            ))
+            .hint(format!("rename {}/{}.c", EXAMPLE_DIR, example)));
+        }
+    }
+
+    let Ok(content) = fs::read_to_string(CMAKE_PATH) else {
+        return Ok(());
+    };
+    let block = block(&examples, config.project.kind == ProjectKind::Lib);
+    let updated = match (content.find(&format!("{}\n", BEGIN)), content.find(END)) {
+        (Some(start), Some(stop)) if stop > start => {
+            let stop = stop + END.len();
+            let stop = stop + content[stop..].find('\n').map_or(0, |i| i + 1);
+            // Drop the blank line that separated a removed block
+            let start = if block.is_empty() && content[..start].ends_with("\n\n") {
+                start - 1
+            } else {
+                start
+            };
+            format!("{}{}{}", &content[..start], block, &content[stop..])
+        }
+        _ if block.is_empty() => return Ok(()),
+        _ => {
+            let mut updated = content.clone();
+            if !updated.ends_with('\n') {
+                updated.push('\n');
+            }
+            updated.push('\n');
+            updated.push_str(&block);
+            updated
+        }
+    };
+    if updated != content {
+        safe_path::write(CMAKE_PATH, updated)
+            .context(|| format!("failed to update {}", CMAKE_PATH))?;
+        log::status!("✓ Updated example targets in CMakeLists.txt");
+    }
+    Ok(())
+}
+
+fn block(examples: &[String], library: bool) -> String {
+    if examples.is_empty() {
+        return String::new();
+    }
+    let mut block = format!(
+        "{}\n# Programs in examples/, built by `c1 build --examples` and `c1 run --example`; kept in sync by c1\n",
+        BEGIN
+    );
+    if !library {
+        block.push_str(&format!(
+            "set(C1_EXAMPLE_SOURCES ${{SOURCES}})\nlist(REMOVE_ITEM C1_EXAMPLE_SOURCES {})\n",
+            crate::main_source()
+        ));
+    }
+    for example in examples {
+        if library {
+            block.push_str(&format!(
+                "add_executable({name} EXCLUDE_FROM_ALL {dir}/{name}.c)\n\
+                 target_link_libraries({name} PRIVATE ${{PROJECT_NAME}})\n",
+                name = example,
+                dir = EXAMPLE_DIR
+            ));
+        } else {
+            block.push_str(&format!(
+                "add_executable({name} EXCLUDE_FROM_ALL {dir}/{name}.c ${{C1_EXAMPLE_SOURCES}})\n\
+                 target_include_directories({name} PRIVATE ${{CMAKE_CURRENT_SOURCE_DIR}}/include)\n",
+                name = example,
+                dir = EXAMPLE_DIR
+            ));
+        }
+    }
+    block.push_str(END);
+    block.push('\n');
+    block
+}
+
+/// The example `c1 run --example <name>` asks for
+pub fn select(requested: &str) -> error::Result<String> {
+    let examples = find();
+    if examples.iter().any(|example| example == requested) {
+        return Ok(requested.to_string());
+    }
+    let error = Error::usage(format!("no example named '{}'", requested));
+    Err(if examples.is_empty() {
+        error.hint(format!("add {}/{}.c to create it", EXAMPLE_DIR, requested))
+    } else {
+        error.hint(format!("available examples: {}", examples.join(", ")))
+    })
+}
+
+/// `c1 run --example` without a name
+pub fn print_list() -> error::Result<()> {
+    let examples = find();
+    if examples.is_empty() {
+        return Err(Error::usage("no examples found").hint(format!(
+            "each {}/<name>.c is an example program",
+            EXAMPLE_DIR
+        )));
+    }
+    println!("Available examples:");
+    for example in examples {
+        println!("  {}", example);
+    }
+    Ok(())
+}
+
+/// Every example for `c1 build --examples`, failing when there is none
+pub fn require() -> error::Result<Vec<String>> {
+    let examples = find();
+    if examples.is_empty() {
+        let what = if Path::new(EXAMPLE_DIR).is_dir() {
+            "examples/ has no .c files"
+        } else {
+            "examples/ not found"
+        };
+        return Err(Error::usage(what).hint(format!(
+            "each {}/<name>.c is an example program",
+            EXAMPLE_DIR
+        )));
+    }
+    Ok(examples)
+}
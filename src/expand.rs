@@ -0,0 +1,192 @@
+//! `c1 expand <file.c>`: a source file as the compiler sees it, after the
+//! preprocessor.
+//!
+//! The file is preprocessed with the exact command the build compiles it
+//! with, taken from compile_commands.json in the build directory, with `-c`,
+//! the object file and the dependency-file flags replaced by `-E -P`. The
+//! build directory is configured first, which does nothing when nothing
+//! changed, so the command follows the current flags, defines, features and
+//! profile. A file the database does not list, such as one not yet
+//! registered in CMakeLists.txt, gets the flags `c1 check` would use, with a
+//! warning. `--pretty` runs the result through clang-format, with the
+//! project's .clang-format.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::error::{self, Context, Error};
+use crate::{BuildOptions, build_history, check, log, safe_path};
+
+/// Compile flags that make no sense for `-E -P`, with and without a value
+const WITH_VALUE: &[&str] = &["-o", "-MF", "-MT", "-MQ"];
+const WITHOUT_VALUE: &[&str] = &["-c", "-MD", "-MMD"];
+
+pub fn cmd_expand(
+    options: &BuildOptions,
+    file: &str,
+    output: Option<&str>,
+    pretty: bool,
+) -> error::Result<()> {
+    let source = Path::new(file);
+    if !source.is_file() {
+        return Err(Error::usage(format!("{} not found", file)));
+    }
+    let config = crate::read_config()?;
+
+    let configure = BuildOptions {
+        configure_only: true,
+        emit: Vec::new(),
+        timings: false,
+        ..options.clone()
+    };
+    crate::cmd_build(&configure)?;
+    let build_dir = crate::target_build_dir(options);
+    let database = build_dir.join("compile_commands.json");
+
+    let (program, args, directory) = match lookup(&database, source) {
+        Some((args, directory)) => {
+            log::verbose!("Compile command from {}", database.display());
+            let mut args = preprocess_args(&args);
+            let program = args.remove(0);
+            (program, args, directory)
+        }
+        None => {
+            log::problem!(
+                "Warning: {} is not in {}, so it is expanded with the flags c1 check uses; \
+                 register it in CMakeLists.txt for the build's exact command",
+                file,
+                database.display()
+            );
+            let compilation = check::compilation(options, &config)?;
+            let mut args = compilation.flags;
+            args.extend(["-E".to_string(), "-P".to_string(), file.to_string()]);
+            (compilation.compiler, args, PathBuf::from("."))
+        }
+    };
+    let mut command = Command::new(&program);
+    command
+        .args(&args)
+        .current_dir(&directory)
+        .stderr(Stdio::inherit());
+    log::command(&command);
+    let result = command
+        .output()
+        .map_err(|e| Error::failure(format!("failed to run {}: {}", program, e)))?;
+    if !result.status.success() {
+        return Err(Error::build(format!("preprocessing {} failed", file)));
+    }
+
+    let mut expanded = result.stdout;
+    if pretty {
+        expanded = clang_format(&expanded, source)?;
+    }
+    match output {
+        Some(path) => {
+            safe_path::write(path, &expanded).context(|| format!("failed to write {}", path))?;
+            log::status!("✓ Wrote the expanded {} to {}", file, path);
+        }
+        None => {
+            let _ = io::stdout().write_all(&expanded);
+        }
+    }
+    Ok(())
+}
+
+/// The arguments and working directory the database compiles `source` with
+fn lookup(database: &Path, source: &Path) -> Option<(Vec<String>, PathBuf)> {
+    let content = fs::read_to_string(database).ok()?;
+    let wanted = fs::canonicalize(source).ok()?;
+    objects(&content).into_iter().find_map(|object| {
+        let fields = build_history::parse_object(object)?;
+        let field = |key: &str| {
+            fields.iter().find_map(|(k, v)| match v {
+                build_history::Value::String(s) if k == key => Some(s.clone()),
+                _ => None,
+            })
+        };
+        let directory = PathBuf::from(field("directory")?);
+        let file = directory.join(field("file")?);
+        if fs::canonicalize(&file).ok()? != wanted {
+            return None;
+        }
+        let args = crate::split_command(&field("command")?).ok()?;
+        (!args.is_empty()).then_some((args, directory))
+    })
+}
+
+/// The top-level objects of the database's array, as text
+fn objects(content: &str) -> Vec<&str> {
+    let mut found = Vec::new();
+    let (mut depth, mut start, mut in_string, mut escaped) = (0, 0, false, false);
+    for (i, c) in content.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => {
+                if depth == 0 {
+                    start = i;
+                }
+                depth += 1;
+            }
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    found.push(&content[start..=i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// A compile command turned into one that preprocesses to stdout
+fn preprocess_args(args: &[String]) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if WITH_VALUE.contains(&arg.as_str()) {
+            args.next();
+        } else if !WITHOUT_VALUE.contains(&arg.as_str()) {
+            kept.push(arg.clone());
+        }
+    }
+    kept.extend(["-E".to_string(), "-P".to_string()]);
+    kept
+}
+
+/// `text` formatted by clang-format with the style for `source`
+fn clang_format(text: &[u8], source: &Path) -> error::Result<Vec<u8>> {
+    let mut child = Command::new("clang-format")
+        .arg("--style=file")
+        .arg(crate::path_arg("--assume-filename=", source))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::failure("clang-format not found").hint(
+                "install clang-format (e.g. `apt install clang-format`), or leave out --pretty",
+            ),
+            _ => Error::failure(format!("failed to run clang-format: {}", e)),
+        })?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(text);
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| Error::failure(format!("failed to run clang-format: {}", e)))?;
+    if !output.status.success() {
+        return Err(Error::failure("clang-format failed on the expanded source"));
+    }
+    Ok(output.stdout)
+}
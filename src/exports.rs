@@ -0,0 +1,146 @@
+//! What a dependency asks of the projects that use it: the `[export]` table
+//! of `external/<name>/project.toml`.
+//!
+//! ```toml
+//! [export]
+//! defines = ["FOO_STATIC"]
+//! links = ["m"]
+//! include_dirs = ["port/posix"]
+//! ```
+//!
+//! The dependency's block in CMakeLists.txt turns these into an INTERFACE
+//! library, `c1_dep_<name>_export`, that is linked like the dependency
+//! itself, so the project (or, for a dev-dependency, the tests and benches)
+//! compiles with the defines, links the libraries and sees the include
+//! directories, which are relative to the dependency (its `subdir`, if it
+//! has one). Two dependencies
+//! exporting the same macro with different values fail the build rather
+//! than one quietly winning; `FOO` and `FOO=1` are the same value.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::error::{self, Error};
+use crate::subdir;
+
+#[derive(Deserialize, Default)]
+pub struct Export {
+    #[serde(default)]
+    pub defines: Vec<String>,
+    #[serde(default)]
+    pub links: Vec<String>,
+    #[serde(default)]
+    pub include_dirs: Vec<String>,
+}
+
+impl Export {
+    pub fn is_empty(&self) -> bool {
+        self.defines.is_empty() && self.links.is_empty() && self.include_dirs.is_empty()
+    }
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    export: Export,
+}
+
+/// What `external/<name>` (or its `subdir`) exports; nothing when it has no
+/// project.toml or no `[export]`
+pub fn of(name: &str) -> Export {
+    let manifest = subdir::root(name).join("project.toml");
+    fs::read_to_string(manifest)
+        .ok()
+        .and_then(|content| toml::from_str::<Manifest>(&content).ok())
+        .map(|manifest| manifest.export)
+        .unwrap_or_default()
+}
+
+/// The CMake target holding what `library` (a `c1_dep_<name>`) exports
+pub fn target(library: &str) -> String {
+    format!("{}_export", library)
+}
+
+/// The lines of a dependency's block defining its export target, which the
+/// caller links; empty when it exports nothing
+pub fn cmake_block(export: &Export, target: &str, base: &str) -> String {
+    if export.is_empty() {
+        return String::new();
+    }
+    let mut block = format!("add_library({} INTERFACE)\n", target);
+    if !export.defines.is_empty() {
+        block.push_str(&format!(
+            "target_compile_definitions({} INTERFACE {})\n",
+            target,
+            export.defines.join(" ")
+        ));
+    }
+    if !export.links.is_empty() {
+        block.push_str(&format!(
+            "target_link_libraries({} INTERFACE {})\n",
+            target,
+            export.links.join(" ")
+        ));
+    }
+    if !export.include_dirs.is_empty() {
+        let dirs: Vec<String> = export
+            .include_dirs
+            .iter()
+            .map(|dir| format!("${{CMAKE_CURRENT_SOURCE_DIR}}/{}/{}", base, dir))
+            .collect();
+        block.push_str(&format!(
+            "target_include_directories({} INTERFACE\n    {}\n)\n",
+            target,
+            dirs.join("\n    ")
+        ));
+    }
+    block
+}
+
+/// A define's macro and value, `FOO` meaning `FOO=1` as for the compiler
+fn split(define: &str) -> (&str, &str) {
+    define.split_once('=').unwrap_or((define, "1"))
+}
+
+/// The macros `dependencies` export with different values, one message each
+pub fn conflicts<'a>(dependencies: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+    // macro -> value -> the dependencies exporting it so
+    let mut exported: BTreeMap<String, BTreeMap<String, Vec<&str>>> = BTreeMap::new();
+    for name in dependencies {
+        for define in of(name).defines {
+            let (macro_name, value) = split(&define);
+            exported
+                .entry(macro_name.to_string())
+                .or_default()
+                .entry(value.to_string())
+                .or_default()
+                .push(name);
+        }
+    }
+    exported
+        .into_iter()
+        .filter(|(_, values)| values.len() > 1)
+        .map(|(define, values)| {
+            let by: Vec<String> = values
+                .iter()
+                .map(|(value, names)| format!("{}={} by {}", define, value, names.join(", ")))
+                .collect();
+            format!("{} is exported as {}", define, by.join(" and as "))
+        })
+        .collect()
+}
+
+/// Fail when two dependencies export one macro with different values
+pub fn check<'a>(dependencies: impl IntoIterator<Item = &'a String>) -> error::Result<()> {
+    let conflicts = conflicts(dependencies);
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    Err(Error::config(format!(
+        "dependencies export conflicting defines:\n    {}",
+        conflicts.join("\n    ")
+    ))
+    .hint("the dependencies cannot both be built as they ask; change one of their [export] tables or drop one of them"))
+}
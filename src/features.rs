@@ -0,0 +1,167 @@
+//! Compile-time features from the `[features]` table in project.toml.
+//!
+//! ```toml
+//! [features]
+//! default = ["logging"]
+//! logging = ["LOG_LEVEL=2"]
+//! tls = { defines = ["USE_MBEDTLS"], sources = ["src/tls.c"] }
+//! ```
+//!
+//! Every enabled feature defines `C1_FEATURE_<NAME>` plus its own defines and
+//! may add sources. They reach CMake as the `C1_FEATURE_DEFINES` and
+//! `C1_FEATURE_SOURCES` lists, which are passed on every configure so a
+//! feature turned off never lingers in the cache.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cmake_lists;
+use crate::error::{self, Error};
+use crate::log;
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+const CMAKE_MARKER: &str = "C1_FEATURE_DEFINES";
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Feature {
+    /// `name = ["DEFINE", ...]`, also the shape of `default = [...]`
+    Defines(Vec<String>),
+    Full {
+        #[serde(default)]
+        defines: Vec<String>,
+        #[serde(default)]
+        sources: Vec<String>,
+    },
+}
+
+impl Feature {
+    fn defines(&self) -> &[String] {
+        match self {
+            Feature::Defines(defines) => defines,
+            Feature::Full { defines, .. } => defines,
+        }
+    }
+
+    pub fn sources(&self) -> &[String] {
+        match self {
+            Feature::Defines(_) => &[],
+            Feature::Full { sources, .. } => sources,
+        }
+    }
+}
+
+/// The cmake arguments for the requested features, or a usage error naming
+/// the available ones
+pub fn configure_args(
+    features: &BTreeMap<String, Feature>,
+    requested: &[String],
+    no_default_features: bool,
+) -> error::Result<Vec<String>> {
+    let enabled = resolve(features, requested, no_default_features).map_err(Error::usage)?;
+    if !enabled.names.is_empty() {
+        log::status!("Features: {}", enabled.names.join(", "));
+        ensure_cmake_block()?;
+    }
+    // Only a CMakeLists that uses the lists gets them; cmake warns otherwise
+    let wired = fs::read_to_string(CMAKE_PATH).is_ok_and(|c| c.contains(CMAKE_MARKER));
+    if !wired {
+        return Ok(Vec::new());
+    }
+    Ok(vec![
+        format!("-DC1_FEATURE_DEFINES={}", enabled.defines.join(";")),
+        format!("-DC1_FEATURE_SOURCES={}", enabled.sources.join(";")),
+    ])
+}
+
+/// The enabled features' macros and sources, as the build passes them
+pub fn defines_and_sources(
+    features: &BTreeMap<String, Feature>,
+    requested: &[String],
+    no_default_features: bool,
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let enabled = resolve(features, requested, no_default_features)?;
+    Ok((enabled.defines, enabled.sources))
+}
+
+struct Enabled<'a> {
+    names: Vec<&'a str>,
+    defines: Vec<String>,
+    sources: Vec<String>,
+}
+
+/// The enabled features, their macros and their sources
+fn resolve<'a>(
+    features: &'a BTreeMap<String, Feature>,
+    requested: &'a [String],
+    no_default_features: bool,
+) -> Result<Enabled<'a>, String> {
+    let mut enabled: Vec<&str> = Vec::new();
+    if !no_default_features && let Some(default) = features.get("default") {
+        enabled.extend(default.defines().iter().map(String::as_str));
+    }
+    enabled.extend(requested.iter().map(String::as_str));
+    enabled.sort_unstable();
+    enabled.dedup();
+
+    let mut defines = Vec::new();
+    let mut sources = Vec::new();
+    for name in &enabled {
+        let feature = match features.get(*name) {
+            Some(feature) if *name != "default" => feature,
+            _ => {
+                let available: Vec<&str> = features
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|k| *k != "default")
+                    .collect();
+                return Err(format!(
+                    "unknown feature '{}' (available: {})",
+                    name,
+                    if available.is_empty() {
+                        "none".to_string()
+                    } else {
+                        available.join(", ")
+                    }
+                ));
+            }
+        };
+        defines.push(feature_macro(name));
+        defines.extend(feature.defines().iter().cloned());
+        sources.extend(feature.sources().iter().cloned());
+    }
+    Ok(Enabled {
+        names: enabled,
+        defines,
+        sources,
+    })
+}
+
+/// `serial-log` -> `C1_FEATURE_SERIAL_LOG`
+fn feature_macro(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("C1_FEATURE_{}", name)
+}
+
+/// Append the lines that apply the feature lists to the project target, the
+/// first time a feature is enabled
+fn ensure_cmake_block() -> error::Result<()> {
+    cmake_lists::ensure_block(
+        CMAKE_MARKER,
+        "# Features enabled by `c1 build --features` (see [features] in project.toml)\n\
+         target_compile_definitions(${PROJECT_NAME} PRIVATE ${C1_FEATURE_DEFINES})\n\
+         target_sources(${PROJECT_NAME} PRIVATE ${C1_FEATURE_SOURCES})\n",
+        "feature",
+    )
+}
@@ -0,0 +1,132 @@
+//! `c1 fmt`: run clang-format over the project's own sources.
+//!
+//! Only main.c, src/, include/ and tests/ are formatted; external/ and build/
+//! hold code c1 does not own, and .c1ignore can leave out more. `--check` never writes: each file is formatted
+//! to a buffer and compared, which is what CI wants.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{self, Error};
+use crate::ignore::Ignore;
+use crate::log;
+
+/// Written by `c1 new` / `c1 init` so formatting is reproducible across machines
+pub const CLANG_FORMAT: &str = r#"BasedOnStyle: LLVM
+IndentWidth: 4
+ColumnLimit: 100
+BreakBeforeBraces: Attach
+AllowShortFunctionsOnASingleLine: None
+AllowShortIfStatementsOnASingleLine: false
+PointerAlignment: Right
+SortIncludes: true
+"#;
+
+const SOURCE_DIRS: [&str; 3] = ["src", "include", "tests"];
+
+pub fn cmd_fmt(check: bool) -> error::Result<()> {
+    let files = source_files();
+    if files.is_empty() {
+        log::status!("No C sources to format");
+        return Ok(());
+    }
+
+    let mut unformatted = Vec::new();
+    for file in &files {
+        if !check {
+            if let Err(e) = format_in_place(file) {
+                return Err(clang_format_error(file, e));
+            }
+            continue;
+        }
+        match is_formatted(file) {
+            Ok(true) => {}
+            Ok(false) => unformatted.push(file.clone()),
+            Err(e) => return Err(clang_format_error(file, e)),
+        }
+    }
+
+    if !check {
+        log::status!("✓ Formatted {} file(s)", files.len());
+        return Ok(());
+    }
+    if unformatted.is_empty() {
+        log::status!("✓ {} file(s) are formatted", files.len());
+        return Ok(());
+    }
+    log::problem!("The following files are not formatted:");
+    for file in &unformatted {
+        log::problem!("  ✗ {}", file.display());
+    }
+    Err(
+        Error::failure(format!("{} file(s) are not formatted", unformatted.len()))
+            .hint("run `c1 fmt` to format them"),
+    )
+}
+
+/// main.c plus every .c/.h file under the project source directories
+fn source_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if Path::new("main.c").is_file() {
+        files.push(PathBuf::from("main.c"));
+    }
+    for dir in SOURCE_DIRS {
+        collect(Path::new(dir), &mut files);
+    }
+    Ignore::project().retain(&mut files);
+    files
+}
+
+/// Append every .c/.h file under `dir`, sorted
+pub fn collect(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            collect(&path, files);
+        } else if matches!(path.extension().and_then(|e| e.to_str()), Some("c") | Some("h")) {
+            files.push(path);
+        }
+    }
+}
+
+fn is_formatted(file: &Path) -> io::Result<bool> {
+    let output = Command::new("clang-format")
+        .arg("--style=file")
+        .arg(file)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(output.stdout == fs::read(file)?)
+}
+
+fn format_in_place(file: &Path) -> io::Result<()> {
+    let output = Command::new("clang-format")
+        .args(["--style=file", "-i"])
+        .arg(file)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn clang_format_error(file: &Path, e: io::Error) -> Error {
+    if e.kind() == io::ErrorKind::NotFound {
+        Error::failure("clang-format not found.").hint(
+            "install clang-format (e.g. `apt install clang-format` or `brew install clang-format`) and make sure it is on PATH",
+        )
+    } else {
+        Error::failure(format!("clang-format failed on {}: {}", file.display(), e))
+    }
+}
@@ -0,0 +1,224 @@
+//! `[generate]`: sources produced by a command before each build.
+//!
+//! ```toml
+//! [generate.tables]
+//! command = "python gen_tables.py data.csv"
+//! outputs = ["src/tables.c"]
+//! inputs = ["data.csv", "gen_tables.py"]
+//! ```
+//!
+//! Before cmake runs, `c1 build` runs each rule's command (through the shell,
+//! from the project root) when one of its outputs is missing or one of its
+//! inputs is newer than the oldest output; rules whose outputs are fresh are
+//! skipped. A command that fails, or leaves an output unwritten, fails the
+//! build. The `.c` outputs are compiled into the project's target and the
+//! directories of `.h` outputs are on its include path, through a managed
+//! block in CMakeLists.txt, `# @c1_generate` through `# @c1_generate_end`, so
+//! outputs can live under build/ as well as in the tree. Outputs under the
+//! build directory go with it on `c1 clean`; the ones in the tree are only
+//! removed by `c1 clean --generated`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{self, Context, Error};
+use crate::{log, safe_path, stream};
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+const BEGIN: &str = "# @c1_generate";
+const END: &str = "# @c1_generate_end";
+
+#[derive(Serialize, Deserialize)]
+pub struct Rule {
+    /// Shell command run from the project root
+    pub command: String,
+    /// Files the command writes, relative to the project root
+    pub outputs: Vec<String>,
+    /// Files the outputs are made from; without any, the rule runs only
+    /// when an output is missing
+    #[serde(default)]
+    pub inputs: Vec<String>,
+}
+
+/// Run the rules whose outputs are missing or stale, then bring the
+/// CMakeLists block up to date
+pub fn run(rules: &BTreeMap<String, Rule>) -> error::Result<()> {
+    for (name, rule) in rules {
+        check(name, rule)?;
+        if is_fresh(rule) {
+            log::verbose!("[generate] {} is up to date", name);
+            continue;
+        }
+        log::status!("Generating {} ({})...", name, rule.command);
+        for output in &rule.outputs {
+            if let Some(parent) = Path::new(output).parent()
+                && !parent.as_os_str().is_empty()
+            {
+                fs::create_dir_all(parent)
+                    .context(|| format!("failed to create {}", parent.display()))?;
+            }
+        }
+        let output = stream::run(&mut shell(&rule.command), str::to_string)
+            .map_err(|e| Error::build(format!("failed to run [generate] {}: {}", name, e)))?;
+        // Its stderr has been shown as it ran
+        if !output.status.success() {
+            return Err(
+                Error::build(format!("[generate] {} failed ({})", name, output.status))
+                    .hint(format!("the command was: {}", rule.command)),
+            );
+        }
+        if let Some(missing) = rule.outputs.iter().find(|o| !Path::new(o).exists()) {
+            return Err(Error::build(format!(
+                "[generate] {} did not write {}",
+                name, missing
+            )));
+        }
+        for output in &rule.outputs {
+            log::status!("  ✓ Generated {}", output);
+        }
+    }
+    wire(rules)
+}
+
+fn check(name: &str, rule: &Rule) -> error::Result<()> {
+    if rule.command.trim().is_empty() || rule.outputs.is_empty() {
+        return Err(Error::config(format!(
+            "[generate] {} needs a command and at least one output",
+            name
+        )));
+    }
+    if let Some(input) = rule.inputs.iter().find(|i| !Path::new(i).exists()) {
+        return Err(Error::config(format!(
+            "[generate] {}: input {} does not exist",
+            name, input
+        )));
+    }
+    Ok(())
+}
+
+/// Every output is there and none is older than an input
+fn is_fresh(rule: &Rule) -> bool {
+    let modified = |path: &String| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let outputs: Option<Vec<SystemTime>> = rule.outputs.iter().map(modified).collect();
+    let Some(oldest) = outputs.and_then(|times| times.into_iter().min()) else {
+        return false;
+    };
+    rule.inputs
+        .iter()
+        .filter_map(modified)
+        .all(|input| input <= oldest)
+}
+
+fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
+
+/// The outputs outside `build_dir` that are there now, for
+/// `c1 clean --generated`
+pub fn outputs_in_tree(rules: &BTreeMap<String, Rule>, build_dir: &Path) -> Vec<PathBuf> {
+    rules
+        .values()
+        .flat_map(|rule| &rule.outputs)
+        .map(PathBuf::from)
+        .filter(|path| !path.starts_with(build_dir) && path.exists())
+        .collect()
+}
+
+/// Keep the CMakeLists block listing the outputs in step with the rules
+fn wire(rules: &BTreeMap<String, Rule>) -> error::Result<()> {
+    let Ok(content) = fs::read_to_string(CMAKE_PATH) else {
+        return Ok(());
+    };
+    let block = block(rules);
+    let updated = match (content.find(BEGIN), content.find(END)) {
+        (Some(start), Some(stop)) if stop > start => {
+            let stop = stop + END.len();
+            let stop = stop + content[stop..].find('\n').map_or(0, |i| i + 1);
+            // Drop the blank line that separated a removed block
+            let start = if block.is_empty() && content[..start].ends_with("\n\n") {
+                start - 1
+            } else {
+                start
+            };
+            format!("{}{}{}", &content[..start], block, &content[stop..])
+        }
+        _ if block.is_empty() => return Ok(()),
+        _ => {
+            let mut updated = content.clone();
+            if !updated.ends_with('\n') {
+                updated.push('\n');
+            }
+            updated.push('\n');
+            updated.push_str(&block);
+            updated
+        }
+    };
+    if updated != content {
+        safe_path::write(CMAKE_PATH, updated)
+            .context(|| format!("failed to update {}", CMAKE_PATH))?;
+        log::status!("✓ Updated [generate] outputs in CMakeLists.txt");
+    }
+    Ok(())
+}
+
+fn block(rules: &BTreeMap<String, Rule>) -> String {
+    let path = |output: &Path| {
+        format!(
+            "${{CMAKE_CURRENT_SOURCE_DIR}}/{}",
+            output.to_string_lossy().replace('\\', "/")
+        )
+    };
+    let mut sources = Vec::new();
+    let mut include_dirs = Vec::new();
+    for output in rules.values().flat_map(|rule| &rule.outputs) {
+        let output = Path::new(output);
+        match output.extension().and_then(|e| e.to_str()) {
+            Some("c") => sources.push(path(output)),
+            Some("h") => {
+                let dir = output.parent().unwrap_or(Path::new(""));
+                let dir = path(dir).trim_end_matches('/').to_string();
+                if !include_dirs.contains(&dir) {
+                    include_dirs.push(dir);
+                }
+            }
+            _ => {}
+        }
+    }
+    if sources.is_empty() && include_dirs.is_empty() {
+        return String::new();
+    }
+    let mut block = format!(
+        "{}\n# Outputs of [generate] in project.toml, kept in sync by c1\n",
+        BEGIN
+    );
+    if !sources.is_empty() {
+        block.push_str(&format!(
+            "target_sources(${{PROJECT_NAME}} PRIVATE {})\n",
+            sources.join(" ")
+        ));
+    }
+    if !include_dirs.is_empty() {
+        block.push_str(&format!(
+            "target_include_directories(${{PROJECT_NAME}} PRIVATE {})\n",
+            include_dirs.join(" ")
+        ));
+    }
+    block.push_str(END);
+    block.push('\n');
+    block
+}
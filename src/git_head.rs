@@ -0,0 +1,150 @@
+//! Read the commit a checkout points at without spawning git.
+//!
+//! This runs at the start of every build, so it only touches a few small files:
+//! `.git/HEAD`, the loose ref it names, and `packed-refs` as a fallback. It
+//! follows `.git` files (submodules, worktrees) and `commondir` indirection,
+//! and handles detached heads.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Resolve HEAD of the repository checked out at `repo` to a commit hash
+pub fn read_head(repo: &Path) -> Option<String> {
+    let git_dir = git_dir(repo)?;
+    let common_dir = common_dir(&git_dir);
+
+    let mut target = fs::read_to_string(git_dir.join("HEAD")).ok()?.trim().to_string();
+    // Symbolic refs can in principle point at other symbolic refs
+    for _ in 0..5 {
+        let Some(reference) = target.strip_prefix("ref:").map(str::trim) else {
+            return is_hash(&target).then_some(target);
+        };
+        target = resolve_ref(&git_dir, &common_dir, reference)?;
+    }
+    None
+}
+
+/// Locate the git directory for a checkout, following `gitdir:` files
+fn git_dir(repo: &Path) -> Option<PathBuf> {
+    let dot_git = repo.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let content = fs::read_to_string(&dot_git).ok()?;
+    let path = content.trim().strip_prefix("gitdir:")?.trim();
+    let path = Path::new(path);
+    Some(if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        repo.join(path)
+    })
+}
+
+/// Worktrees keep shared refs in the directory named by `commondir`
+fn common_dir(git_dir: &Path) -> PathBuf {
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(content) => {
+            let path = Path::new(content.trim());
+            if path.is_absolute() {
+                path.to_path_buf()
+            } else {
+                git_dir.join(path)
+            }
+        }
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+/// Resolve a ref name to its content (a hash or another `ref:` line)
+fn resolve_ref(git_dir: &Path, common_dir: &Path, reference: &str) -> Option<String> {
+    for dir in [git_dir, common_dir] {
+        if let Ok(content) = fs::read_to_string(dir.join(reference)) {
+            return Some(content.trim().to_string());
+        }
+    }
+
+    let packed = fs::read_to_string(common_dir.join("packed-refs")).ok()?;
+    packed
+        .lines()
+        .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+        .find_map(|line| {
+            let (hash, name) = line.split_once(' ')?;
+            (name.trim() == reference).then(|| hash.to_string())
+        })
+}
+
+fn is_hash(value: &str) -> bool {
+    matches!(value.len(), 40 | 64) && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_head;
+    use crate::test_support::TempDir;
+
+    const A: &str = "1111111111111111111111111111111111111111";
+    const B: &str = "2222222222222222222222222222222222222222";
+
+    #[test]
+    fn follows_a_loose_branch_ref() {
+        let tmp = TempDir::new("git-head-loose");
+        tmp.write(".git/HEAD", "ref: refs/heads/main\n");
+        tmp.write(".git/refs/heads/main", &format!("{}\n", A));
+        assert_eq!(read_head(tmp.path()).as_deref(), Some(A));
+    }
+
+    #[test]
+    fn falls_back_to_packed_refs() {
+        let tmp = TempDir::new("git-head-packed");
+        tmp.write(".git/HEAD", "ref: refs/heads/main\n");
+        tmp.write(
+            ".git/packed-refs",
+            &format!(
+                "# pack-refs with: peeled fully-peeled sorted\n\
+                 {B} refs/tags/v1.0\n\
+                 ^{A}\n\
+                 {A} refs/heads/main\n"
+            ),
+        );
+        assert_eq!(read_head(tmp.path()).as_deref(), Some(A));
+    }
+
+    #[test]
+    fn prefers_a_loose_ref_over_a_packed_one() {
+        let tmp = TempDir::new("git-head-loose-first");
+        tmp.write(".git/HEAD", "ref: refs/heads/main\n");
+        tmp.write(".git/refs/heads/main", A);
+        tmp.write(".git/packed-refs", &format!("{} refs/heads/main\n", B));
+        assert_eq!(read_head(tmp.path()).as_deref(), Some(A));
+    }
+
+    #[test]
+    fn reads_a_detached_head() {
+        let tmp = TempDir::new("git-head-detached");
+        tmp.write(".git/HEAD", &format!("{}\n", B));
+        assert_eq!(read_head(tmp.path()).as_deref(), Some(B));
+    }
+
+    #[test]
+    fn follows_gitdir_files_and_commondir() {
+        let tmp = TempDir::new("git-head-worktree");
+        tmp.write(
+            "main/.git/packed-refs",
+            &format!("{} refs/heads/topic\n", A),
+        );
+        tmp.write("main/.git/worktrees/wt/HEAD", "ref: refs/heads/topic\n");
+        tmp.write("main/.git/worktrees/wt/commondir", "../..\n");
+        tmp.write("wt/.git", "gitdir: ../main/.git/worktrees/wt\n");
+        assert_eq!(read_head(&tmp.path().join("wt")).as_deref(), Some(A));
+    }
+
+    #[test]
+    fn gives_nothing_for_an_unborn_branch_or_no_repository() {
+        let tmp = TempDir::new("git-head-unborn");
+        assert_eq!(read_head(tmp.path()), None);
+        tmp.write(".git/HEAD", "ref: refs/heads/main\n");
+        assert_eq!(read_head(tmp.path()), None);
+        tmp.write(".git/HEAD", "not a hash\n");
+        assert_eq!(read_head(tmp.path()), None);
+    }
+}
@@ -0,0 +1,273 @@
+//! `c1 graph`: the dependency or header-inclusion graph for design docs.
+//!
+//! `--deps` (the default) draws the packages: the root, its `[dependencies]`
+//! and, for dependencies that are c1 projects, the dependencies in their own
+//! project.toml, as `c1 tree` walks them. `--modules` draws which of main.c,
+//! src/ and include/ includes which, from their `#include "..."` lines;
+//! `<...>` system headers and headers found outside the project are left out.
+//! Either is written as Graphviz dot or, with `--format json`, as nodes and
+//! edges. Edges that close a cycle are drawn in red and the cycles reported.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{self, Context};
+use crate::ignore::Ignore;
+use crate::{fmt, info, json, log, safe_path, tree};
+
+struct Graph {
+    /// Node ids, with the label to show for each
+    nodes: BTreeMap<String, String>,
+    edges: BTreeSet<(String, String)>,
+}
+
+pub fn cmd_graph(modules: bool, format: &str, output: Option<&str>) -> error::Result<()> {
+    let graph = if modules {
+        module_graph()
+    } else {
+        dependency_graph()?
+    };
+    let cycles = graph.cycles();
+    let text = match format {
+        "json" => graph.to_json(&cycles),
+        _ => graph.to_dot(if modules { "modules" } else { "dependencies" }, &cycles),
+    };
+
+    match output {
+        Some(path) => {
+            safe_path::write(path, &text).context(|| format!("failed to write {}", path))?;
+            log::status!("✓ Wrote {}", path);
+        }
+        None => print!("{}", text),
+    }
+    for cycle in &cycles {
+        log::problem!("Warning: cycle: {}", cycle.join(" -> "));
+    }
+    Ok(())
+}
+
+/// The root package and everything it depends on, directly or not
+fn dependency_graph() -> error::Result<Graph> {
+    let config = crate::read_config()?;
+    let root = config.project.name.clone();
+    let mut graph = Graph {
+        nodes: BTreeMap::new(),
+        edges: BTreeSet::new(),
+    };
+    graph.nodes.insert(
+        root.clone(),
+        format!("{} v{}", root, config.project.version),
+    );
+
+    let mut pending = vec![(root.clone(), info::declared(&config.dependencies))];
+    while let Some((parent, dependencies)) = pending.pop() {
+        for dependency in dependencies {
+            graph
+                .edges
+                .insert((parent.clone(), dependency.name.clone()));
+            if graph.nodes.contains_key(&dependency.name) {
+                continue;
+            }
+            graph.nodes.insert(
+                dependency.name.clone(),
+                format!("{} ({})", dependency.name, info::source(&dependency)),
+            );
+            let children = tree::declared_by(&dependency.name);
+            pending.push((dependency.name, children));
+        }
+    }
+    Ok(graph)
+}
+
+/// The project's sources and headers, linked by their `#include "..."` lines
+fn module_graph() -> Graph {
+    let includes = includes();
+    let mut graph = Graph {
+        nodes: includes.keys().map(|f| (f.clone(), f.clone())).collect(),
+        edges: BTreeSet::new(),
+    };
+    for (file, headers) in includes {
+        for header in headers {
+            graph.edges.insert((file.clone(), header));
+        }
+    }
+    graph
+}
+
+/// main.c and the files in src/ and include/, each with the project files
+/// its `#include "..."` lines name
+pub fn includes() -> BTreeMap<String, Vec<String>> {
+    let mut files = Vec::new();
+    if Path::new("main.c").is_file() {
+        files.push(PathBuf::from("main.c"));
+    }
+    fmt::collect(Path::new("src"), &mut files);
+    fmt::collect(Path::new("include"), &mut files);
+    Ignore::project().retain(&mut files);
+    let files: Vec<PathBuf> = files
+        .iter()
+        .map(|f| f.strip_prefix(".").unwrap_or(f).to_path_buf())
+        .collect();
+
+    files
+        .iter()
+        .map(|file| {
+            let content = fs::read_to_string(file).unwrap_or_default();
+            let headers = quoted_includes(&content)
+                .iter()
+                .filter_map(|header| resolve(file, header, &files))
+                .map(|target| id(&target))
+                .collect();
+            (id(file), headers)
+        })
+        .collect()
+}
+
+fn id(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// The names in the file's `#include "..."` lines
+fn quoted_includes(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let rest = line.trim_start().strip_prefix('#')?.trim_start();
+            let rest = rest.strip_prefix("include")?.trim_start();
+            let rest = rest.strip_prefix('"')?;
+            Some(rest[..rest.find('"')?].to_string())
+        })
+        .collect()
+}
+
+/// The project file `#include "<header>"` in `from` finds: next to `from`
+/// first, then in include/ and src/
+fn resolve(from: &Path, header: &str, files: &[PathBuf]) -> Option<PathBuf> {
+    let dir = from.parent().unwrap_or(Path::new(""));
+    [dir, Path::new("include"), Path::new("src")]
+        .iter()
+        .map(|dir| normalize(&dir.join(header)))
+        .find(|candidate| files.contains(candidate))
+}
+
+/// `path` without `.` and `dir/..` components
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+impl Graph {
+    /// Every cycle, each as the chain of nodes closing it, found once
+    fn cycles(&self) -> Vec<Vec<String>> {
+        let mut cycles = Vec::new();
+        let mut seen = BTreeSet::new();
+        let mut done = BTreeSet::new();
+        for node in self.nodes.keys() {
+            let mut stack = Vec::new();
+            self.find_cycles(node, &mut stack, &mut done, &mut seen, &mut cycles);
+        }
+        cycles
+    }
+
+    fn find_cycles(
+        &self,
+        node: &str,
+        stack: &mut Vec<String>,
+        done: &mut BTreeSet<String>,
+        seen: &mut BTreeSet<BTreeSet<String>>,
+        cycles: &mut Vec<Vec<String>>,
+    ) {
+        if let Some(start) = stack.iter().position(|n| n == node) {
+            let mut cycle = stack[start..].to_vec();
+            if seen.insert(cycle.iter().cloned().collect()) {
+                cycle.push(node.to_string());
+                cycles.push(cycle);
+            }
+            return;
+        }
+        if done.contains(node) {
+            return;
+        }
+        stack.push(node.to_string());
+        for (_, to) in self.edges.iter().filter(|(from, _)| from == node) {
+            self.find_cycles(to, stack, done, seen, cycles);
+        }
+        stack.pop();
+        done.insert(node.to_string());
+    }
+
+    fn to_dot(&self, name: &str, cycles: &[Vec<String>]) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut dot = format!("digraph {} {{\n    node [shape=box];\n", quote(name));
+        for (node, label) in &self.nodes {
+            dot.push_str(&format!("    {} [label={}];\n", quote(node), quote(label)));
+        }
+        for (from, to) in &self.edges {
+            let style = if in_cycle(cycles, from, to) {
+                " [color=red]"
+            } else {
+                ""
+            };
+            dot.push_str(&format!("    {} -> {}{};\n", quote(from), quote(to), style));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn to_json(&self, cycles: &[Vec<String>]) -> String {
+        let nodes: Vec<String> = self
+            .nodes
+            .iter()
+            .map(|(node, label)| {
+                format!(
+                    "{{\"id\":{},\"label\":{}}}",
+                    json::string(node),
+                    json::string(label)
+                )
+            })
+            .collect();
+        let edges: Vec<String> = self
+            .edges
+            .iter()
+            .map(|(from, to)| {
+                format!(
+                    "{{\"from\":{},\"to\":{},\"cycle\":{}}}",
+                    json::string(from),
+                    json::string(to),
+                    in_cycle(cycles, from, to)
+                )
+            })
+            .collect();
+        let cycles: Vec<String> = cycles
+            .iter()
+            .map(|cycle| {
+                let names: Vec<String> = cycle.iter().map(|n| json::string(n)).collect();
+                format!("[{}]", names.join(","))
+            })
+            .collect();
+        format!(
+            "{{\"nodes\":[{}],\"edges\":[{}],\"cycles\":[{}]}}\n",
+            nodes.join(","),
+            edges.join(","),
+            cycles.join(",")
+        )
+    }
+}
+
+fn in_cycle(cycles: &[Vec<String>], from: &str, to: &str) -> bool {
+    cycles.iter().any(|cycle| {
+        cycle
+            .windows(2)
+            .any(|pair| pair[0] == from && pair[1] == to)
+    })
+}
@@ -0,0 +1,184 @@
+//! Which of the project's files changed since it was last built or checked.
+//!
+//! After each build c1 writes `c1_inputs` in the build directory: whether the
+//! build succeeded, and the modification times main.c and the files under
+//! src/ and include/ had when it started. A failed build keeps the times of
+//! the last one that succeeded. Before `c1 run` starts the program it
+//! compares them with the files, and warns when the last build failed or a
+//! file changed since, such as one saved while the build ran.
+//!
+//! `c1 check` writes `c1_checked` the same way, with the compiler and flags
+//! it used; a source that failed is left out, so it counts as changed.
+//! `c1 check --changed` then checks only the sources that changed or that
+//! include, directly or through other headers, a file that did, following
+//! the `#include "..."` lines `c1 graph --modules` draws. Everything is
+//! checked when there is no earlier check with the same flags, or when a
+//! file went away.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::{graph, log, safe_path};
+
+pub const BUILD_RECORD: &str = "c1_inputs";
+pub const CHECK_RECORD: &str = "c1_checked";
+
+/// Files and their modification times, in nanoseconds since the epoch
+pub type Snapshot = BTreeMap<String, u128>;
+
+pub struct Record {
+    pub success: bool,
+    /// What the files were compiled with, when that matters
+    pub key: String,
+    pub files: Snapshot,
+}
+
+/// The project's files as they are now, and `extra` ones such as sources
+/// outside src/
+pub fn snapshot(extra: &[String]) -> Snapshot {
+    let mut files: BTreeSet<String> = graph::includes().into_keys().collect();
+    files.extend(extra.iter().cloned());
+    files
+        .into_iter()
+        .filter_map(|file| {
+            let modified = fs::metadata(&file).and_then(|m| m.modified()).ok()?;
+            let nanos = modified.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+            Some((file, nanos))
+        })
+        .collect()
+}
+
+pub fn load(path: &Path) -> Option<Record> {
+    let content = fs::read_to_string(path).ok()?;
+    let mut record = Record {
+        success: false,
+        key: String::new(),
+        files: Snapshot::new(),
+    };
+    for line in content.lines() {
+        if let Some(status) = line.strip_prefix("status ") {
+            record.success = status == "ok";
+        } else if let Some(key) = line.strip_prefix("key ") {
+            record.key = key.to_string();
+        } else if let Some((nanos, file)) = line.split_once(' ')
+            && let Ok(nanos) = nanos.parse()
+        {
+            record.files.insert(file.to_string(), nanos);
+        }
+    }
+    Some(record)
+}
+
+pub fn save(path: &Path, record: &Record) {
+    let mut content = format!(
+        "# Written by c1: the project's files when it last {}\nstatus {}\n",
+        if path.ends_with(CHECK_RECORD) {
+            "checked them"
+        } else {
+            "built them"
+        },
+        if record.success { "ok" } else { "failed" }
+    );
+    if !record.key.is_empty() {
+        content.push_str(&format!("key {}\n", record.key));
+    }
+    for (file, nanos) in &record.files {
+        content.push_str(&format!("{} {}\n", nanos, file));
+    }
+    let saved = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| safe_path::write(path, content));
+    if let Err(e) = saved {
+        log::verbose!("Could not write {}: {}", path.display(), e);
+    }
+}
+
+/// Record a build in `build_dir` that started with the files in `started`
+pub fn record_build(build_dir: &Path, success: bool, started: Snapshot) {
+    let path = build_dir.join(BUILD_RECORD);
+    let files = if success {
+        started
+    } else {
+        load(&path)
+            .filter(|record| record.success)
+            .map(|record| record.files)
+            .unwrap_or_default()
+    };
+    let record = Record {
+        success,
+        key: String::new(),
+        files,
+    };
+    save(&path, &record);
+}
+
+/// The files whose times differ between `then` and `now`, and those only
+/// one of them has
+pub fn changed(then: &Snapshot, now: &Snapshot) -> Vec<String> {
+    let mut changed: Vec<String> = now
+        .iter()
+        .filter(|(file, nanos)| then.get(*file) != Some(nanos))
+        .map(|(file, _)| file.clone())
+        .collect();
+    changed.extend(then.keys().filter(|file| !now.contains_key(*file)).cloned());
+    changed
+}
+
+/// The `sources` to check again after the changes between `then` and `now`:
+/// the ones that changed or include one that did; all of them when a file
+/// went away, since what included it cannot be told any more
+pub fn affected(sources: &[String], then: &Snapshot, now: &Snapshot) -> Vec<String> {
+    let changed: BTreeSet<String> = changed(then, now).into_iter().collect();
+    if changed.iter().any(|file| !now.contains_key(file)) {
+        return sources.to_vec();
+    }
+    let includes = graph::includes();
+    sources
+        .iter()
+        .filter(|source| {
+            let mut seen = BTreeSet::new();
+            let mut pending = vec![source.as_str()];
+            while let Some(file) = pending.pop() {
+                if changed.contains(file) {
+                    return true;
+                }
+                if seen.insert(file) {
+                    pending.extend(includes.get(file).into_iter().flatten().map(String::as_str));
+                }
+            }
+            false
+        })
+        .cloned()
+        .collect()
+}
+
+/// Warn before running `executable` when it may not be built from the files
+/// as they are now
+pub fn warn_if_stale(build_dir: &Path, executable: &Path) {
+    let Some(record) = load(&build_dir.join(BUILD_RECORD)) else {
+        return;
+    };
+    if !record.success {
+        log::problem!(
+            "Warning: the last build failed; {} is from an earlier one",
+            executable.display()
+        );
+        return;
+    }
+    let changed = changed(&record.files, &snapshot(&[]));
+    if let Some(first) = changed.first() {
+        let others = match changed.len() {
+            1 => String::new(),
+            n => format!(" and {} other file(s)", n - 1),
+        };
+        log::problem!(
+            "Warning: {}{} changed since {} was built; it is rebuilt on the next `c1 run`",
+            first,
+            others,
+            executable.display()
+        );
+    }
+}
@@ -0,0 +1,553 @@
+//! HTTP(S) downloads without shelling out to curl or wget.
+//!
+//! Requests go through the `Transport` trait; the one c1 uses is built on
+//! reqwest, which follows redirects, with the proxies `HTTP_PROXY`,
+//! `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` name. A dropped connection is resumed with a `Range` request;
+//! the body is hashed as it is written, so verifying a checksum costs no
+//! second pass over the file.
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::secrets;
+use crate::sha256::Sha256;
+
+/// Attempts per download, counting the first one
+const MAX_ATTEMPTS: u32 = 4;
+const MAX_REDIRECTS: usize = 10;
+
+pub trait Transport {
+    /// Start a GET of `url`, asking for the bytes from `offset` on when it
+    /// is non-zero
+    fn get(&self, url: &str, offset: u64) -> Result<Response, HttpError>;
+}
+
+pub struct Response {
+    pub status: u16,
+    /// URL after following redirects
+    pub final_url: String,
+    /// Length of this response's body, if the server sent it
+    pub content_length: Option<u64>,
+    pub body: Box<dyn Read>,
+}
+
+pub enum HttpError {
+    /// The server answered with a non-success status
+    Status { url: String, status: u16 },
+    /// Connecting or reading failed
+    Network { url: String, message: String },
+    /// The download completed but its content is not what was expected
+    Checksum {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+    Io { path: PathBuf, error: io::Error },
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HttpError::Status { url, status } => {
+                write!(f, "HTTP {} from {}", status, secrets::redact(url))
+            }
+            HttpError::Network { url, message } => write!(
+                f,
+                "download of {} failed: {}",
+                secrets::redact(url),
+                secrets::redact(message)
+            ),
+            HttpError::Checksum {
+                url,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "checksum mismatch for {}: expected sha256 {}, got {}",
+                secrets::redact(url),
+                expected,
+                actual
+            ),
+            HttpError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+/// Bytes received so far and the total size, when known
+pub struct Progress {
+    pub downloaded: u64,
+    pub total: Option<u64>,
+}
+
+/// The transport used for real downloads
+pub struct ReqwestTransport {
+    client: reqwest::blocking::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new() -> Result<ReqwestTransport, HttpError> {
+        ReqwestTransport::with_env(|name| std::env::var(name).ok())
+    }
+
+    /// A transport taking its proxies from the variables `var` looks up:
+    /// `HTTP_PROXY` for http URLs, `HTTPS_PROXY` for https ones and
+    /// `ALL_PROXY` for either, each also in lower case, except for the hosts
+    /// `NO_PROXY` lists
+    fn with_env(var: impl Fn(&str) -> Option<String>) -> Result<ReqwestTransport, HttpError> {
+        let lookup = |name: &str| {
+            var(name)
+                .or_else(|| var(&name.to_ascii_lowercase()))
+                .filter(|value| !value.is_empty())
+        };
+        let no_proxy = lookup("NO_PROXY").and_then(|list| reqwest::NoProxy::from_string(&list));
+        let setup_error = |message: String| HttpError::Network {
+            url: String::new(),
+            message,
+        };
+        let mut builder = reqwest::blocking::Client::builder()
+            .user_agent(concat!("c1/", env!("CARGO_PKG_VERSION")))
+            .redirect(reqwest::redirect::Policy::limited(MAX_REDIRECTS))
+            .connect_timeout(Duration::from_secs(30))
+            // No overall timeout: large archives on slow links are fine
+            .timeout(None)
+            // Only the proxies below, not ones reqwest finds by itself
+            .no_proxy();
+        for name in ["HTTP_PROXY", "HTTPS_PROXY", "ALL_PROXY"] {
+            let Some(url) = lookup(name) else {
+                continue;
+            };
+            let proxy = match name {
+                "HTTP_PROXY" => reqwest::Proxy::http(url),
+                "HTTPS_PROXY" => reqwest::Proxy::https(url),
+                _ => reqwest::Proxy::all(url),
+            }
+            .map_err(|e| setup_error(format!("{}: {}", name, e)))?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy.clone()));
+        }
+        let client = builder.build().map_err(|e| setup_error(e.to_string()))?;
+        Ok(ReqwestTransport { client })
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn get(&self, url: &str, offset: u64) -> Result<Response, HttpError> {
+        let mut request = self.client.get(url);
+        if offset > 0 {
+            request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+        }
+        let response = request.send().map_err(|e| HttpError::Network {
+            url: e.url().map_or_else(|| url.to_string(), |u| u.to_string()),
+            message: error_chain(&e),
+        })?;
+        Ok(Response {
+            status: response.status().as_u16(),
+            final_url: response.url().to_string(),
+            content_length: response.content_length(),
+            body: Box::new(response),
+        })
+    }
+}
+
+/// Download `url` to `dest`, returning the SHA-256 of the content.
+///
+/// Fails without leaving `dest` behind when `expected_sha256` is given and
+/// does not match.
+pub fn download(
+    url: &str,
+    dest: &Path,
+    expected_sha256: Option<&str>,
+    progress: &mut dyn FnMut(Progress),
+) -> Result<String, HttpError> {
+    let transport = ReqwestTransport::new()?;
+    download_with(&transport, url, dest, expected_sha256, progress)
+}
+
+pub fn download_with(
+    transport: &dyn Transport,
+    url: &str,
+    dest: &Path,
+    expected_sha256: Option<&str>,
+    progress: &mut dyn FnMut(Progress),
+) -> Result<String, HttpError> {
+    let partial = partial_path(dest);
+    let io_error = |error| HttpError::Io {
+        path: partial.clone(),
+        error,
+    };
+    let mut file = File::create(&partial).map_err(io_error)?;
+    let mut hasher = Sha256::new();
+    let mut written = 0u64;
+    let mut final_url = url.to_string();
+    let mut attempt = 1;
+
+    loop {
+        let result = transport.get(url, written).and_then(|response| {
+            final_url = response.final_url.clone();
+            if !(200..300).contains(&response.status) {
+                return Err(HttpError::Status {
+                    url: response.final_url,
+                    status: response.status,
+                });
+            }
+            // The server ignored the Range header: start over
+            if written > 0 && response.status != 206 {
+                file.set_len(0).map_err(io_error)?;
+                file.rewind().map_err(io_error)?;
+                hasher = Sha256::new();
+                written = 0;
+            }
+            let total = response.content_length.map(|len| len + written);
+            receive(response.body, &mut file, &mut hasher, &mut written, total, progress)
+                .map_err(|e| HttpError::Network {
+                    url: final_url.clone(),
+                    message: e.to_string(),
+                })
+        });
+
+        match result {
+            Ok(()) => break,
+            // Server errors and dropped connections are worth another try;
+            // 4xx and local I/O errors are not
+            Err(HttpError::Network { .. }) | Err(HttpError::Status { status: 500.., .. })
+                if attempt < MAX_ATTEMPTS =>
+            {
+                thread::sleep(Duration::from_millis(500 * 2u64.pow(attempt - 1)));
+                attempt += 1;
+            }
+            Err(e) => {
+                drop(file);
+                let _ = fs::remove_file(&partial);
+                return Err(e);
+            }
+        }
+    }
+
+    file.flush().map_err(io_error)?;
+    drop(file);
+    let actual = hasher.finish_hex();
+    if let Some(expected) = expected_sha256
+        && !expected.eq_ignore_ascii_case(&actual)
+    {
+        let _ = fs::remove_file(&partial);
+        return Err(HttpError::Checksum {
+            url: final_url,
+            expected: expected.to_ascii_lowercase(),
+            actual,
+        });
+    }
+    fs::rename(&partial, dest).map_err(|error| HttpError::Io {
+        path: dest.to_path_buf(),
+        error,
+    })?;
+    Ok(actual)
+}
+
+/// Copy a response body to disk, hashing and reporting progress as it goes.
+/// A body shorter than announced counts as an interrupted transfer.
+fn receive(
+    mut body: Box<dyn Read>,
+    file: &mut File,
+    hasher: &mut Sha256,
+    written: &mut u64,
+    total: Option<u64>,
+    progress: &mut dyn FnMut(Progress),
+) -> io::Result<()> {
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = match body.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        file.write_all(&buffer[..read])?;
+        hasher.update(&buffer[..read]);
+        *written += read as u64;
+        progress(Progress {
+            downloaded: *written,
+            total,
+        });
+    }
+    match total {
+        Some(total) if *written < total => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("connection closed after {} of {} bytes", written, total),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// reqwest's message alone ("error sending request") hides the cause
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    message
+}
+
+/// `libfoo.tar.gz` downloads to `libfoo.tar.gz.part` until verified
+fn partial_path(dest: &Path) -> PathBuf {
+    let mut name = dest.as_os_str().to_os_string();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+
+    const HELLO_SHA256: &str = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+    /// An HTTP server on a local port. Each connection gets what `answer`
+    /// returns for the request's head and its number, counting from 0, and
+    /// is closed after it.
+    struct TestServer {
+        url: String,
+        /// The head of each request received
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl TestServer {
+        fn start(answer: impl Fn(&str, usize) -> Vec<u8> + Send + 'static) -> TestServer {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let received = Arc::clone(&requests);
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(mut stream) = stream else {
+                        continue;
+                    };
+                    let head = read_head(&mut stream);
+                    let number = {
+                        let mut received = received.lock().unwrap();
+                        received.push(head.clone());
+                        received.len() - 1
+                    };
+                    let _ = stream.write_all(&answer(&head, number));
+                }
+            });
+            TestServer { url, requests }
+        }
+
+        /// The path each request asked for
+        fn paths(&self) -> Vec<String> {
+            self.requests
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|head| head.split(' ').nth(1).unwrap_or_default().to_string())
+                .collect()
+        }
+
+        /// The `Range` header of each request
+        fn ranges(&self) -> Vec<Option<String>> {
+            self.requests
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|head| header(head, "range"))
+                .collect()
+        }
+    }
+
+    fn read_head(stream: &mut TcpStream) -> String {
+        let mut head = Vec::new();
+        let mut byte = [0u8; 1];
+        while !head.ends_with(b"\r\n\r\n") && stream.read(&mut byte).is_ok_and(|n| n == 1) {
+            head.push(byte[0]);
+        }
+        String::from_utf8_lossy(&head).into_owned()
+    }
+
+    fn header(head: &str, name: &str) -> Option<String> {
+        head.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+    }
+
+    /// A response announcing `length` bytes of body, of which it sends `body`
+    fn reply(status: &str, headers: &str, length: usize, body: &str) -> Vec<u8> {
+        format!(
+            "HTTP/1.1 {}\r\nConnection: close\r\nContent-Length: {}\r\n{}\r\n{}",
+            status, length, headers, body
+        )
+        .into_bytes()
+    }
+
+    /// A transport that ignores the proxies of the environment running the
+    /// tests
+    fn direct() -> ReqwestTransport {
+        ReqwestTransport::with_env(|_| None).ok().unwrap()
+    }
+
+    fn fetch(
+        transport: &ReqwestTransport,
+        url: &str,
+        dest: &Path,
+        expected: Option<&str>,
+    ) -> Result<String, HttpError> {
+        download_with(transport, url, dest, expected, &mut |_| {})
+    }
+
+    #[test]
+    fn follows_a_redirect_chain() {
+        let tmp = TempDir::new("http-redirect");
+        let dest = tmp.path().join("a.txt");
+        let server =
+            TestServer::start(|head, _| match head.split(' ').nth(1).unwrap_or_default() {
+                "/a.txt" => reply("302 Found", "Location: /moved/a.txt\r\n", 0, ""),
+                "/moved/a.txt" => reply(
+                    "301 Moved Permanently",
+                    "Location: /mirror/a.txt\r\n",
+                    0,
+                    "",
+                ),
+                "/mirror/a.txt" => reply("200 OK", "", 11, "hello world"),
+                _ => reply("404 Not Found", "", 0, ""),
+            });
+        let url = format!("{}/a.txt", server.url);
+        let sha = fetch(&direct(), &url, &dest, Some(HELLO_SHA256))
+            .ok()
+            .unwrap();
+        assert_eq!(sha, HELLO_SHA256);
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "hello world");
+        assert_eq!(server.paths(), ["/a.txt", "/moved/a.txt", "/mirror/a.txt"]);
+        assert!(!partial_path(&dest).exists());
+    }
+
+    #[test]
+    fn resumes_a_dropped_download() {
+        let tmp = TempDir::new("http-resume");
+        let dest = tmp.path().join("a.txt");
+        let server = TestServer::start(|head, number| match (number, header(head, "range")) {
+            // The connection closes after 5 of 11 bytes
+            (0, _) => reply("200 OK", "", 11, "hello"),
+            (_, Some(range)) if range == "bytes=5-" => reply(
+                "206 Partial Content",
+                "Content-Range: bytes 5-10/11\r\n",
+                6,
+                " world",
+            ),
+            _ => reply("400 Bad Request", "", 0, ""),
+        });
+        let url = format!("{}/a.txt", server.url);
+        let sha = fetch(&direct(), &url, &dest, Some(HELLO_SHA256))
+            .ok()
+            .unwrap();
+        assert_eq!(sha, HELLO_SHA256);
+        assert_eq!(server.ranges(), [None, Some("bytes=5-".to_string())]);
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "hello world");
+    }
+
+    #[test]
+    fn starts_over_when_the_range_is_ignored() {
+        let tmp = TempDir::new("http-range-ignored");
+        let dest = tmp.path().join("a.txt");
+        let server = TestServer::start(|_, number| match number {
+            0 => reply("200 OK", "", 11, "hel"),
+            _ => reply("200 OK", "", 11, "hello world"),
+        });
+        let url = format!("{}/a.txt", server.url);
+        let sha = fetch(&direct(), &url, &dest, None).ok().unwrap();
+        assert_eq!(sha, HELLO_SHA256);
+        assert_eq!(server.ranges(), [None, Some("bytes=3-".to_string())]);
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "hello world");
+    }
+
+    #[test]
+    fn rejects_a_checksum_mismatch() {
+        let tmp = TempDir::new("http-checksum");
+        let dest = tmp.path().join("a.txt");
+        let server = TestServer::start(|head, _| {
+            if head.starts_with("GET /a.txt ") {
+                reply("302 Found", "Location: /mirror/a.txt\r\n", 0, "")
+            } else {
+                reply("200 OK", "", 11, "hello world")
+            }
+        });
+        let url = format!("{}/a.txt", server.url);
+        let expected = "0".repeat(64);
+        match fetch(&direct(), &url, &dest, Some(&expected)) {
+            Err(HttpError::Checksum {
+                url,
+                expected: wanted,
+                actual,
+            }) => {
+                assert_eq!(url, format!("{}/mirror/a.txt", server.url));
+                assert_eq!(wanted, expected);
+                assert_eq!(actual, HELLO_SHA256);
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("a mismatched download was accepted"),
+        }
+        assert!(!dest.exists());
+        assert!(!partial_path(&dest).exists());
+    }
+
+    #[test]
+    fn does_not_retry_client_errors() {
+        let tmp = TempDir::new("http-404");
+        let dest = tmp.path().join("a.txt");
+        let server = TestServer::start(|_, _| reply("404 Not Found", "", 0, ""));
+        let url = format!("{}/a.txt", server.url);
+        assert!(matches!(
+            fetch(&direct(), &url, &dest, None),
+            Err(HttpError::Status { status: 404, .. })
+        ));
+        assert_eq!(server.paths().len(), 1);
+        assert!(!partial_path(&dest).exists());
+    }
+
+    #[test]
+    fn goes_through_the_proxy_the_environment_names() {
+        let tmp = TempDir::new("http-proxy");
+        let dest = tmp.path().join("a.txt");
+        let proxy = TestServer::start(|_, _| reply("200 OK", "", 11, "hello world"));
+        let proxy_url = proxy.url.clone();
+        let transport =
+            ReqwestTransport::with_env(|name| (name == "http_proxy").then(|| proxy_url.clone()))
+                .ok()
+                .unwrap();
+        // A host that does not resolve: only the proxy can answer for it
+        let sha = fetch(&transport, "http://c1.invalid/a.txt", &dest, None)
+            .ok()
+            .unwrap();
+        assert_eq!(sha, HELLO_SHA256);
+        assert_eq!(proxy.paths(), ["http://c1.invalid/a.txt"]);
+    }
+
+    #[test]
+    fn bypasses_the_proxy_for_hosts_no_proxy_lists() {
+        let tmp = TempDir::new("http-no-proxy");
+        let dest = tmp.path().join("a.txt");
+        let proxy = TestServer::start(|_, _| reply("502 Bad Gateway", "", 0, ""));
+        let server = TestServer::start(|_, _| reply("200 OK", "", 11, "hello world"));
+        let proxy_url = proxy.url.clone();
+        let transport = ReqwestTransport::with_env(|name| match name {
+            "HTTP_PROXY" => Some(proxy_url.clone()),
+            "NO_PROXY" => Some("example.com, 127.0.0.1".to_string()),
+            _ => None,
+        })
+        .ok()
+        .unwrap();
+        let url = format!("{}/a.txt", server.url);
+        fetch(&transport, &url, &dest, Some(HELLO_SHA256))
+            .ok()
+            .unwrap();
+        assert_eq!(server.paths(), ["/a.txt"]);
+        assert!(proxy.paths().is_empty());
+    }
+}
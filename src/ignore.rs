@@ -0,0 +1,189 @@
+//! A small .gitignore matcher for commands that walk the project tree.
+//!
+//! Supports the common subset of the format: comments, `!` negation, a
+//! trailing `/` for directories only, patterns anchored by a `/`, and the `*`,
+//! `?` and `**` wildcards. The last matching rule wins, as in git.
+//!
+//! The same format excludes files from the commands that scan the project:
+//! `.c1ignore` at its root, after the implicit `/build/` and `/external/`,
+//! read once by `project()`. Source auto-discovery, `c1 fmt`, `c1 lint`,
+//! `c1 check`, `c1 package`, `c1 graph --modules` and the header map behind
+//! `c1 check --changed` leave out what it matches, and `c1 info --ignored`
+//! lists it. As in git, a file in an excluded directory cannot be brought
+//! back by a negation; exclude the directory's contents (`src/generated/*`)
+//! to keep some of them (`!src/generated/keep_this.c`).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// The project's exclusions, at its root
+pub const PROJECT_FILE: &str = ".c1ignore";
+
+/// What scanning the project always leaves out
+const IMPLICIT: &[&str] = &["/build/", "/external/"];
+
+pub struct Rule {
+    /// As written
+    pub text: String,
+    /// Line in .c1ignore, from 1; 0 for the implicit rules
+    pub line: usize,
+    pattern: String,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the whole relative path rather than any file name
+    anchored: bool,
+}
+
+pub struct Ignore {
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    /// Rules from an ignore file; a missing file ignores nothing
+    pub fn load(path: impl AsRef<Path>) -> Ignore {
+        let content = fs::read_to_string(path).unwrap_or_default();
+        Ignore {
+            rules: parse(content.lines().enumerate().map(|(i, line)| (i + 1, line))),
+        }
+    }
+
+    /// The project's exclusions: the implicit ones, then .c1ignore
+    pub fn project() -> &'static Ignore {
+        static PROJECT: OnceLock<Ignore> = OnceLock::new();
+        PROJECT.get_or_init(|| {
+            let content = fs::read_to_string(PROJECT_FILE).unwrap_or_default();
+            let implicit = IMPLICIT.iter().map(|line| (0, *line));
+            let own = content.lines().enumerate().map(|(i, line)| (i + 1, line));
+            Ignore {
+                rules: parse(implicit.chain(own)),
+            }
+        })
+    }
+
+    /// Whether `path`, relative to the directory of the ignore file and using
+    /// `/` separators, is ignored
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        self.excluding(path, is_dir).is_some()
+    }
+
+    /// The rule that excludes the file at `path`, or the directory it is in
+    pub fn excluded_by(&self, path: &str) -> Option<&Rule> {
+        path.match_indices('/')
+            .find_map(|(at, _)| self.excluding(&path[..at], true))
+            .or_else(|| self.excluding(path, false))
+    }
+
+    /// Drop the files that are excluded
+    pub fn retain(&self, files: &mut Vec<PathBuf>) {
+        files.retain(|file| {
+            let path = file.to_string_lossy().replace('\\', "/");
+            self.excluded_by(path.trim_start_matches("./")).is_none()
+        });
+    }
+
+    /// The rule that excludes `path` itself, whatever its directories
+    pub fn excluding(&self, path: &str, is_dir: bool) -> Option<&Rule> {
+        self.deciding(path, is_dir).filter(|rule| !rule.negated)
+    }
+
+    /// The last rule matching `path`
+    fn deciding(&self, path: &str, is_dir: bool) -> Option<&Rule> {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        self.rules.iter().rev().find(|rule| {
+            let subject = if rule.anchored { path } else { name };
+            (is_dir || !rule.dir_only) && glob(rule.pattern.as_bytes(), subject.as_bytes())
+        })
+    }
+}
+
+impl Rule {
+    /// Where the rule comes from, for `c1 info --ignored`
+    pub fn origin(&self) -> String {
+        match self.line {
+            0 => "implicit".to_string(),
+            line => format!("{}:{}", PROJECT_FILE, line),
+        }
+    }
+}
+
+fn parse<'a>(lines: impl Iterator<Item = (usize, &'a str)>) -> Vec<Rule> {
+    lines
+        .map(|(number, line)| (number, line.trim_end()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, text)| {
+            let (negated, line) = match text.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, text),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            Rule {
+                text: text.to_string(),
+                line: number,
+                pattern: line.trim_start_matches('/').to_string(),
+                negated,
+                dir_only,
+                anchored,
+            }
+        })
+        .collect()
+}
+
+/// `*` and `?` stop at `/`; `**` crosses directories
+pub fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        // `**/` matches whole directories only, so `**/gen.c` is not
+        // `regen.c`
+        [b'*', b'*', b'/', rest @ ..] => (0..=text.len())
+            .filter(|&i| i == 0 || text[i - 1] == b'/')
+            .any(|i| glob(rest, &text[i..])),
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| glob(rest, &text[i..])),
+        [b'*', rest @ ..] => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != b'/')
+            .any(|i| glob(rest, &text[i..])),
+        [b'?', rest @ ..] => {
+            matches!(text.first(), Some(&c) if c != b'/') && glob(rest, &text[1..])
+        }
+        [c, rest @ ..] => text.first() == Some(c) && glob(rest, &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        glob(pattern.as_bytes(), text.as_bytes())
+    }
+
+    #[test]
+    fn double_star_slash_matches_whole_directories() {
+        assert!(matches("**/gen.c", "gen.c"));
+        assert!(matches("**/gen.c", "src/gen.c"));
+        assert!(matches("**/gen.c", "src/a/b/gen.c"));
+        assert!(!matches("**/gen.c", "src/regen.c"));
+        assert!(matches("src/**/foo.c", "src/foo.c"));
+        assert!(matches("src/**/foo.c", "src/x/foo.c"));
+        assert!(!matches("src/**/foo.c", "src/xfoo.c"));
+    }
+
+    #[test]
+    fn trailing_double_star_matches_everything_below() {
+        assert!(matches("build/**", "build/a/b.o"));
+        assert!(matches("**", "any/path.c"));
+        assert!(!matches("build/**", "src/build.c"));
+    }
+
+    #[test]
+    fn single_star_stops_at_slash() {
+        assert!(matches("*.c", "main.c"));
+        assert!(!matches("*.c", "src/main.c"));
+        assert!(matches("src/?.c", "src/a.c"));
+        assert!(!matches("src/?.c", "src//.c"));
+    }
+}
@@ -0,0 +1,108 @@
+//! Header directories besides include/, through `[build] include_dirs` in
+//! project.toml.
+//!
+//! ```toml
+//! [build]
+//! include_dirs = ["gen", "legacy/inc"]
+//! ```
+//!
+//! Each entry, relative to the project root, becomes a
+//! `target_include_directories(${PROJECT_NAME} PRIVATE ...)` line in a managed
+//! block of CMakeLists.txt, `# @c1_include_dirs` through
+//! `# @c1_include_dirs_end`, brought in step with the manifest before every
+//! build. A directory that does not exist yet (headers generated during the
+//! build) is kept, with a warning.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::{self, Context, Error};
+use crate::log;
+use crate::safe_path;
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+const BEGIN: &str = "# @c1_include_dirs";
+const END: &str = "# @c1_include_dirs_end";
+
+/// Check the `include_dirs` entries and bring the CMakeLists block up to date
+pub fn wire(dirs: &[String]) -> error::Result<()> {
+    for dir in dirs {
+        check(dir)?;
+        if !Path::new(dir).is_dir() {
+            log::problem!(
+                "Warning: '{}' in [build] include_dirs is not a directory",
+                dir
+            );
+        }
+    }
+    let Ok(content) = fs::read_to_string(CMAKE_PATH) else {
+        return Ok(());
+    };
+    let block = block(dirs);
+    let updated = match (content.find(&format!("{}\n", BEGIN)), content.find(END)) {
+        (Some(start), Some(stop)) if stop > start => {
+            let stop = stop + END.len();
+            let stop = stop + content[stop..].find('\n').map_or(0, |i| i + 1);
+            // Drop the blank line that separated a removed block
+            let start = if block.is_empty() && content[..start].ends_with("\n\n") {
+                start - 1
+            } else {
+                start
+            };
+            format!("{}{}{}", &content[..start], block, &content[stop..])
+        }
+        _ if block.is_empty() => return Ok(()),
+        _ => {
+            let mut updated = content.clone();
+            if !updated.ends_with('\n') {
+                updated.push('\n');
+            }
+            updated.push('\n');
+            updated.push_str(&block);
+            updated
+        }
+    };
+    if updated != content {
+        safe_path::write(CMAKE_PATH, updated)
+            .context(|| format!("failed to update {}", CMAKE_PATH))?;
+        log::status!("✓ Updated include directories in CMakeLists.txt");
+    }
+    Ok(())
+}
+
+/// A project-relative path CMake can take as one argument
+fn check(dir: &str) -> error::Result<()> {
+    let valid = !dir.is_empty()
+        && !Path::new(dir).is_absolute()
+        && !dir
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '(' | ')' | '"' | '#' | '$' | ';'));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::config(format!(
+            "'{}' in [build] include_dirs is not a usable directory",
+            dir
+        ))
+        .hint("use paths relative to the project root, without spaces, such as \"gen\""))
+    }
+}
+
+fn block(dirs: &[String]) -> String {
+    if dirs.is_empty() {
+        return String::new();
+    }
+    let mut block = format!(
+        "{}\n# Header directories from [build] include_dirs in project.toml, kept in sync by c1\n",
+        BEGIN
+    );
+    for dir in dirs {
+        block.push_str(&format!(
+            "target_include_directories(${{PROJECT_NAME}} PRIVATE ${{CMAKE_CURRENT_SOURCE_DIR}}/{})\n",
+            dir.replace('\\', "/").trim_end_matches('/')
+        ));
+    }
+    block.push_str(END);
+    block.push('\n');
+    block
+}
@@ -0,0 +1,206 @@
+//! The package index behind `c1 search` and `c1 add <name>`.
+//!
+//! The index is one TOML file mapping package names to where they live:
+//!
+//! ```toml
+//! [packages.cjson]
+//! git = "https://github.com/DaveGamble/cJSON.git"
+//! description = "Ultralightweight JSON parser in ANSI C"
+//! tags = ["v1.7.17", "v1.7.18"]
+//! ```
+//!
+//! `index.url` in the user's config.toml says where it is: a `.toml` file
+//! over HTTP(S), a local file, or a git repository with `index.toml` at its
+//! root, such as the default community index. It is fetched into the cache
+//! directory on first use and kept until `c1 search --update-index`; with
+//! `--offline` only the cached copy is read. When it cannot be fetched,
+//! dependencies are still added by URL as before.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Deserialize;
+
+use crate::error::{self, Error};
+use crate::{http, log, remote, safe_path, user_config};
+
+/// The community index, when `index.url` names no other
+const DEFAULT_URL: &str = "https://github.com/weynechen/c1-index.git";
+/// The index file in a git repository
+const INDEX_FILE: &str = "index.toml";
+
+#[derive(Deserialize)]
+struct Index {
+    #[serde(default)]
+    packages: BTreeMap<String, Package>,
+}
+
+#[derive(Deserialize)]
+pub struct Package {
+    pub git: String,
+    #[serde(default)]
+    pub description: String,
+    /// Tags known to work, in any order
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl Package {
+    /// The newest of the recommended tags
+    pub fn latest_tag(&self) -> Option<&str> {
+        self.tags
+            .iter()
+            .max_by(|a, b| remote::version_cmp(a, b))
+            .map(String::as_str)
+    }
+}
+
+fn url() -> &'static str {
+    user_config::text("index.url").unwrap_or(DEFAULT_URL)
+}
+
+/// Where the fetched index is kept: the file, or the clone holding it
+fn cached(url: &str) -> Option<PathBuf> {
+    let key = format!("{:016x}", remote::fnv1a(url));
+    let dir = remote::cache_dir()?.join("index");
+    Some(if is_file_url(url) {
+        dir.join(format!("{}.toml", key))
+    } else {
+        dir.join(key)
+    })
+}
+
+fn is_file_url(url: &str) -> bool {
+    url.ends_with(".toml")
+}
+
+/// Fetch the index again, or for the first time
+fn fetch(url: &str, dest: &Path) -> error::Result<()> {
+    if remote::is_offline() {
+        return Err(Error::failure(format!(
+            "the package index {} is not cached and c1 is offline",
+            url
+        )));
+    }
+    if let Some(dir) = dest.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| Error::failure(format!("failed to create {}: {}", dir.display(), e)))?;
+    }
+    log::status!("Fetching the package index from {}...", url);
+    if is_file_url(url) {
+        http::download(url, dest, None, &mut |_| {})
+            .map_err(|e| Error::failure(format!("failed to download the package index: {}", e)))?;
+        return Ok(());
+    }
+    // A fresh shallow clone replaces the old one only once it worked
+    let partial = dest.with_extension("partial");
+    let _ = safe_path::remove_dir_all(&partial);
+    let mut clone = Command::new("git");
+    clone
+        .args(["clone", "--quiet", "--depth", "1", url])
+        .arg(&partial);
+    log::command(&clone);
+    let output = clone
+        .output()
+        .map_err(|e| Error::failure(format!("failed to run git: {}", e)))?;
+    if !output.status.success() {
+        let _ = safe_path::remove_dir_all(&partial);
+        return Err(Error::failure(format!(
+            "failed to clone the package index {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let _ = safe_path::remove_dir_all(dest);
+    fs::rename(&partial, dest)
+        .map_err(|e| Error::failure(format!("failed to update {}: {}", dest.display(), e)))
+}
+
+/// The index, fetched first when it is not cached or `update` asks for it
+fn load(update: bool) -> error::Result<BTreeMap<String, Package>> {
+    let url = url();
+    // A local file is read where it is
+    let file = if Path::new(url).is_file() {
+        PathBuf::from(url)
+    } else {
+        let dest = cached(url)
+            .ok_or_else(|| Error::failure("no cache directory for the package index"))?;
+        if update || !dest.exists() {
+            fetch(url, &dest)?;
+        }
+        if is_file_url(url) {
+            dest
+        } else {
+            dest.join(INDEX_FILE)
+        }
+    };
+    let content = fs::read_to_string(&file)
+        .map_err(|e| Error::failure(format!("failed to read {}: {}", file.display(), e)))?;
+    let index: Index = toml::from_str(&content).map_err(|e| {
+        Error::failure(format!(
+            "the package index {} is not valid: {}",
+            url,
+            e.message()
+        ))
+    })?;
+    Ok(index.packages)
+}
+
+/// Whether `spec` for `c1 add` is a package name rather than a URL, a
+/// `user/repo` shorthand or a path
+pub fn is_name(spec: &str) -> bool {
+    !spec.is_empty()
+        && spec
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && !spec.ends_with(".git")
+        && !Path::new(spec).exists()
+}
+
+/// The package `name` from the index
+pub fn resolve(name: &str) -> error::Result<Package> {
+    let fallback = "add it by URL instead, e.g. `c1 add user/repo` or `c1 add https://...`";
+    let mut packages = load(false).map_err(|e| e.hint(fallback))?;
+    packages.remove(name).ok_or_else(|| {
+        Error::usage(format!("no package named '{}' in the package index", name)).hint(format!(
+            "`c1 search {}` lists similar names, or {}",
+            name, fallback
+        ))
+    })
+}
+
+pub fn cmd_search(query: Option<&str>, update: bool) -> error::Result<()> {
+    let packages = load(update).map_err(|e| {
+        e.hint("`index.url` in `c1 config` sets the index; `c1 add <url>` works without one")
+    })?;
+    let Some(query) = query else {
+        log::status!("✓ The package index lists {} packages", packages.len());
+        return Ok(());
+    };
+    let query = query.to_lowercase();
+    // Names that match come before descriptions that do
+    let mut found: Vec<(&String, &Package)> = packages
+        .iter()
+        .filter(|(name, package)| {
+            name.to_lowercase().contains(&query)
+                || package.description.to_lowercase().contains(&query)
+        })
+        .collect();
+    found.sort_by_key(|(name, _)| !name.to_lowercase().contains(&query));
+    if found.is_empty() {
+        log::status!("No packages match '{}'", query);
+        return Ok(());
+    }
+    let width = found.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, package) in found {
+        let tag = package
+            .latest_tag()
+            .map(|tag| format!(" ({})", tag))
+            .unwrap_or_default();
+        let line = format!("{:<w$}  {}{}", name, package.description, tag, w = width);
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
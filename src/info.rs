@@ -0,0 +1,440 @@
+//! `c1 info`: what project.toml declares and what is on disk.
+//!
+//! Prints the project metadata, the compiler settings, every dependency with
+//! its source and whether `external/<name>` exists, and the state of the build
+//! directory. `--json` prints the same as one JSON object for editor plugins.
+//! With `--verbose`, the settings that can come from more than one place are
+//! listed with the layer each took effect from: project.toml, the user's
+//! config.toml, the environment or c1's default. `--ignored` lists instead
+//! what the commands scanning the project leave out, and the rule that
+//! excludes each (see ignore).
+
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{self, Error};
+use crate::ignore::Ignore;
+use crate::{exports, json, log, pkg_config, remote, secrets, user_config};
+
+/// A `[dependencies]` entry and whether it has been synced
+#[derive(Clone)]
+pub struct Dependency {
+    pub name: String,
+    pub git: Option<String>,
+    pub tag: Option<String>,
+    pub branch: Option<String>,
+    pub path: Option<String>,
+    /// The URL of a release archive dependency
+    pub archive: Option<String>,
+    /// The package of a system dependency found through pkg-config
+    pub pkg_config: Option<String>,
+    pub submodule: bool,
+    /// The subdirectory of the repository that holds the library
+    pub subdir: Option<String>,
+    /// Synced into external/, or for a system dependency, installed
+    pub present: bool,
+}
+
+/// The dependencies declared in `dependencies`, sorted by name
+pub fn declared(dependencies: &toml::Table) -> Vec<Dependency> {
+    dependencies
+        .iter()
+        .map(|(name, value)| {
+            let field = |key: &str| value.get(key).and_then(|v| v.as_str()).map(str::to_string);
+            let pkg_config = field(pkg_config::KEY);
+            let present = match &pkg_config {
+                Some(package) => pkg_config::check(package).is_ok(),
+                None => Path::new("external").join(name).exists(),
+            };
+            Dependency {
+                name: name.clone(),
+                git: field("git"),
+                tag: field("tag"),
+                branch: field("branch"),
+                path: field("path"),
+                archive: field("url"),
+                pkg_config,
+                submodule: value.get("submodule").and_then(|v| v.as_bool()) == Some(true),
+                subdir: field("subdir"),
+                present,
+            }
+        })
+        .collect()
+}
+
+pub fn cmd_info(json: bool) -> error::Result<()> {
+    if !Path::new("project.toml").exists() {
+        return Err(Error::usage(
+            "project.toml not found. Are you in a c1 project?",
+        ));
+    }
+    let config = crate::read_config()?;
+    let compiler = crate::resolve_compiler(None, Some(&config));
+
+    let dependencies = declared(&config.dependencies);
+    let warnings = config.build.warnings.as_deref().unwrap_or("none");
+    let warning_flags = crate::warning_flags(&config, compiler.as_ref().map(|(c, _)| c.as_str()))
+        .map_err(|e| e.to_string());
+
+    let build_dir = crate::resolve_build_dir(None);
+    // The debug and release builds, then a flat build directory from before
+    // builds were split by profile
+    let debug = crate::BuildOptions::default();
+    let release = crate::BuildOptions {
+        release: true,
+        ..Default::default()
+    };
+    let build_dirs = [
+        (crate::target_build_dir(&debug), false),
+        (crate::target_build_dir(&release), true),
+        (build_dir.clone(), false),
+    ];
+    let configured = build_dirs
+        .iter()
+        .any(|(dir, _)| dir.join("CMakeCache.txt").is_file());
+    let executable = build_dirs.iter().find_map(|(dir, release)| {
+        crate::executable_path(dir, &config.project.name, *release).ok()
+    });
+    let built = executable
+        .as_ref()
+        .and_then(|path| fs::metadata(path).ok()?.modified().ok())
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|age| age.as_secs());
+
+    if json {
+        let flags: Vec<String> = config.build.flags.iter().map(|f| json::string(f)).collect();
+        let warning_flags: Vec<String> = warning_flags
+            .iter()
+            .flatten()
+            .map(|f| json::string(f))
+            .collect();
+        let dependencies: Vec<String> = dependencies
+            .iter()
+            .map(|d| {
+                let export = exports::of(&d.name);
+                let list = |items: &[String]| {
+                    items.iter().map(|i| json::string(i)).collect::<Vec<_>>().join(",")
+                };
+                format!(
+                    "{{\"name\":{},\"git\":{},\"tag\":{},\"branch\":{},\"path\":{},\"archive\":{},\"pkg_config\":{},\"submodule\":{},\"subdir\":{},\"present\":{},\"exports\":{{\"defines\":[{}],\"links\":[{}],\"include_dirs\":[{}]}}}}",
+                    json::string(&d.name),
+                    json::optional(d.git.as_deref()),
+                    json::optional(d.tag.as_deref()),
+                    json::optional(d.branch.as_deref()),
+                    json::optional(d.path.as_deref()),
+                    json::optional(d.archive.as_deref()),
+                    json::optional(d.pkg_config.as_deref()),
+                    d.submodule,
+                    json::optional(d.subdir.as_deref()),
+                    d.present,
+                    list(&export.defines),
+                    list(&export.links),
+                    list(&export.include_dirs)
+                )
+            })
+            .collect();
+        let executable = executable.map(|path| path.display().to_string());
+        println!(
+            "{{\"name\":{},\"type\":{},\"version\":{},\"edition\":{},\"description\":{},\"license\":{},\"authors\":[{}],\"repository\":{},\"compiler\":{},\"flags\":[{}],\"warnings\":{},\"warning_flags\":[{}],\"dependencies\":[{}],\"build\":{{\"dir\":{},\"configured\":{},\"executable\":{},\"last_built\":{}}}}}",
+            json::string(&config.project.name),
+            json::string(if config.project.kind == crate::ProjectKind::Lib {
+                "lib"
+            } else {
+                "bin"
+            }),
+            json::string(&config.project.version),
+            json::string(&config.project.edition),
+            json::string(&config.project.description),
+            json::optional(config.project.license.as_deref()),
+            config
+                .project
+                .authors
+                .iter()
+                .map(|a| json::string(a))
+                .collect::<Vec<_>>()
+                .join(","),
+            json::optional(config.project.repository.as_deref()),
+            json::optional(compiler.as_ref().map(|(c, _)| c.as_str())),
+            flags.join(","),
+            json::string(warnings),
+            warning_flags.join(","),
+            dependencies.join(","),
+            json::string(&build_dir.display().to_string()),
+            configured,
+            json::optional(executable.as_deref()),
+            built.map_or("null".to_string(), |secs| secs.to_string())
+        );
+        return Ok(());
+    }
+
+    let project = &config.project;
+    let kind = if project.kind == crate::ProjectKind::Lib {
+        ", library"
+    } else {
+        ""
+    };
+    println!(
+        "{} {} (edition {}{})",
+        project.name, project.version, project.edition, kind
+    );
+    if !project.description.is_empty() {
+        println!("{}", project.description);
+    }
+    if let Some(license) = &project.license {
+        println!("License: {}", license);
+    }
+    if !project.authors.is_empty() {
+        println!("Authors: {}", project.authors.join(", "));
+    }
+    if let Some(repository) = &project.repository {
+        println!("Repository: {}", repository);
+    }
+    println!();
+    match &compiler {
+        Some((compiler, _)) => println!("Compiler:     {}", compiler),
+        None => println!("Compiler:     cmake's default"),
+    }
+    if !config.build.flags.is_empty() {
+        println!("Flags:        {}", config.build.flags.join(" "));
+    }
+    match &warning_flags {
+        Ok(flags) if flags.is_empty() => println!("Warnings:     {}", warnings),
+        Ok(flags) => println!("Warnings:     {} ({})", warnings, flags.join(" ")),
+        Err(e) => println!("Warnings:     {}", e),
+    }
+
+    println!();
+    if dependencies.is_empty() {
+        println!("Dependencies: none");
+    } else {
+        println!("Dependencies:");
+        let width = dependencies.iter().map(|d| d.name.len()).max().unwrap_or(0);
+        for d in &dependencies {
+            let mark = if d.present { "✓" } else { "✗" };
+            let state = match (d.present, &d.pkg_config) {
+                (true, _) => "",
+                (false, Some(_)) => "  (not installed)",
+                (false, None) => "  (not synced)",
+            };
+            log::print(
+                false,
+                format_args!("  {} {:width$}  {}{}", mark, d.name, source(d), state),
+            );
+            // What it adds to the project's own compile and link lines
+            let export = exports::of(&d.name);
+            let mut exported = Vec::new();
+            if !export.defines.is_empty() {
+                exported.push(format!("defines {}", export.defines.join(" ")));
+            }
+            if !export.links.is_empty() {
+                exported.push(format!("links {}", export.links.join(" ")));
+            }
+            if !export.include_dirs.is_empty() {
+                exported.push(format!("includes {}", export.include_dirs.join(" ")));
+            }
+            if !exported.is_empty() {
+                println!("    {:width$}  exports {}", "", exported.join("; "));
+            }
+        }
+        for conflict in exports::conflicts(dependencies.iter().map(|d| &d.name)) {
+            log::problem!("  Warning: {}", conflict);
+        }
+    }
+
+    println!();
+    let state = if configured {
+        "configured"
+    } else {
+        "not configured"
+    };
+    println!("Build dir:    {} ({})", build_dir.display(), state);
+    match (&executable, built) {
+        (Some(path), Some(secs)) => {
+            println!("Executable:   {}, built {}", path.display(), ago(secs))
+        }
+        (Some(path), None) => println!("Executable:   {}", path.display()),
+        (None, _) => println!("Executable:   not built"),
+    }
+
+    if log::is_verbose() {
+        println!();
+        match user_config::path() {
+            Some(path) if path.is_file() => println!("User config:  {}", path.display()),
+            Some(path) => println!("User config:  {} (not found)", path.display()),
+            None => println!("User config:  none (no config directory)"),
+        }
+        println!("Settings:");
+        let settings = settings(&config);
+        let width = settings
+            .iter()
+            .map(|(_, value, _)| value.len())
+            .max()
+            .unwrap_or(0);
+        for (name, value, layer) in &settings {
+            println!("  {:<10} {:<width$}  ({})", name, value, layer);
+        }
+    }
+    Ok(())
+}
+
+/// The settings with more than one source, each with its effective value and
+/// where that came from
+/// The files and directories the project's exclusions match, each with the
+/// rule that excludes it; an excluded directory is listed, not its files
+pub fn cmd_ignored() {
+    let mut excluded = Vec::new();
+    walk(Path::new("."), Ignore::project(), &mut excluded);
+    if excluded.is_empty() {
+        println!("Nothing is excluded");
+        return;
+    }
+    let width = excluded
+        .iter()
+        .map(|(path, _)| path.len())
+        .max()
+        .unwrap_or(0);
+    for (path, rule) in &excluded {
+        println!("{:width$}  {}", path, rule, width = width);
+    }
+}
+
+fn walk(dir: &Path, ignore: &Ignore, excluded: &mut Vec<(String, String)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+    entries.sort();
+    for path in entries {
+        let relative = path.strip_prefix(".").unwrap_or(&path);
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        if relative == ".git" {
+            continue;
+        }
+        let is_dir = path.is_dir();
+        match ignore.excluding(&relative, is_dir) {
+            Some(rule) => excluded.push((
+                if is_dir {
+                    format!("{}/", relative)
+                } else {
+                    relative
+                },
+                format!("{} ({})", rule.text, rule.origin()),
+            )),
+            None if is_dir => walk(&path, ignore, excluded),
+            None => {}
+        }
+    }
+}
+
+fn settings(config: &crate::ProjectConfig) -> Vec<(&'static str, String, &'static str)> {
+    let manifest: toml::Table = fs::read_to_string("project.toml")
+        .ok()
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default();
+    let layer = |section: &str, key: &str, in_user_config: bool| {
+        if manifest.get(section).and_then(|v| v.get(key)).is_some() {
+            "project.toml"
+        } else if in_user_config {
+            "user config"
+        } else {
+            "default"
+        }
+    };
+
+    let generator = user_config::text("build.generator");
+    let jobs = user_config::count("build.jobs");
+    let mut settings = vec![
+        match crate::resolve_compiler(None, Some(config)) {
+            Some((compiler, "CC")) => ("compiler", compiler, "environment: CC"),
+            Some((compiler, _)) => ("compiler", compiler, "project.toml"),
+            None => ("compiler", "cmake's default".to_string(), "default"),
+        },
+        (
+            "edition",
+            config.project.edition.clone(),
+            layer("project", "edition", false),
+        ),
+        (
+            "generator",
+            config
+                .build
+                .generator
+                .as_deref()
+                .or(generator)
+                .unwrap_or("cmake's default")
+                .to_string(),
+            layer("build", "generator", generator.is_some()),
+        ),
+        (
+            "jobs",
+            config
+                .build
+                .jobs
+                .or(jobs)
+                .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+                .unwrap_or(1)
+                .to_string(),
+            match layer("build", "jobs", jobs.is_some()) {
+                "default" => "default: CPU count",
+                layer => layer,
+            },
+        ),
+    ];
+
+    let offline = remote::is_offline();
+    let layer = if offline && !crate::offline_from_env() {
+        "--offline"
+    } else if std::env::var("C1_OFFLINE").is_ok_and(|v| !v.is_empty()) {
+        "C1_OFFLINE"
+    } else if user_config::flag("net.offline").is_some() {
+        "user config"
+    } else {
+        "default"
+    };
+    settings.push(("offline", offline.to_string(), layer));
+    settings
+}
+
+/// `git <url> tag v1` / `path ../lib` / `archive <url>` / `system package sdl2 (pkg-config)`
+pub fn source(d: &Dependency) -> String {
+    if let Some(package) = &d.pkg_config {
+        return format!("system package {} (pkg-config)", package);
+    }
+    let mut source = if let Some(path) = &d.path {
+        format!("path {}", path)
+    } else if let Some(url) = &d.archive {
+        format!("archive {}", secrets::redact(url))
+    } else {
+        let mut source = format!("git {}", d.git.as_deref().unwrap_or("?"));
+        if let Some(tag) = &d.tag {
+            source.push_str(&format!(" tag {}", tag));
+        } else if let Some(branch) = &d.branch {
+            source.push_str(&format!(" branch {}", branch));
+        }
+        source
+    };
+    if let Some(subdir) = &d.subdir {
+        source.push_str(&format!(" subdir {}", subdir));
+    }
+    if d.submodule {
+        source.push_str(" (submodule)");
+    }
+    source
+}
+
+/// How long before now `secs` (since the epoch) was
+fn ago(secs: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(secs, |d| d.as_secs());
+    let elapsed = now.saturating_sub(secs);
+    let (count, unit) = match elapsed {
+        0..60 => return "just now".to_string(),
+        60..3600 => (elapsed / 60, "minute"),
+        3600..86400 => (elapsed / 3600, "hour"),
+        _ => (elapsed / 86400, "day"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{} {}{} ago", count, unit, plural)
+}
@@ -0,0 +1,127 @@
+//! `c1 install` / `c1 uninstall`: put the release executable on PATH.
+//!
+//! A `type = "lib"` project installs its library instead (see lib_install).
+//!
+//! The executable is copied to `<prefix>/bin` (`--prefix`), to an exact
+//! directory (`--path`), or by default to `~/.local/bin`. Installing is the one
+//! place c1 deliberately writes outside the project, so the destination is an
+//! allowed root of `safe_path`, unless it holds the project; an existing file
+//! is only replaced with `--force`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{self, Context, Error};
+use crate::{BuildOptions, ProjectConfig, ProjectKind, lib_install, log, safe_path};
+
+pub fn cmd_install(prefix: Option<&str>, path: Option<&str>, force: bool) -> error::Result<()> {
+    let config = crate::read_config()?;
+    if config.project.kind == ProjectKind::Lib {
+        return lib_install::install(&config, &library_prefix(&config, prefix, path)?, force);
+    }
+    let dest = destination(&config.project.name, prefix, path)?;
+    if dest.exists() && !force {
+        return Err(Error::usage(format!("{} already exists", dest.display()))
+            .hint("pass --force to replace it"));
+    }
+
+    let options = BuildOptions {
+        release: true,
+        ..BuildOptions::default()
+    };
+    crate::cmd_build(&options)?;
+    let build_dir = crate::target_build_dir(&options);
+    let executable =
+        crate::executable_path(&build_dir, &config.project.name, true).map_err(|_| {
+            Error::failure(format!(
+                "Executable for '{}' not found",
+                config.project.name
+            ))
+        })?;
+
+    if let Some(dir) = dest.parent() {
+        fs::create_dir_all(dir).context(|| format!("failed to create {}", dir.display()))?;
+    }
+    let replaced = dest.exists();
+    safe_path::copy(&executable, &dest)
+        .context(|| format!("failed to install to {}", dest.display()))?;
+    if replaced {
+        log::status!("  Replaced existing {}", dest.display());
+    }
+    log::status!("✓ Installed {} to {}", config.project.name, dest.display());
+
+    if let Some(dir) = dest.parent()
+        && !on_path(dir)
+    {
+        log::problem!(
+            "Hint: {} is not on PATH; add it to run `{}` directly.",
+            dir.display(),
+            config.project.name
+        );
+    }
+    Ok(())
+}
+
+pub fn cmd_uninstall(prefix: Option<&str>, path: Option<&str>) -> error::Result<()> {
+    let config = crate::read_config()?;
+    if config.project.kind == ProjectKind::Lib {
+        return lib_install::uninstall(&config, &library_prefix(&config, prefix, path)?);
+    }
+    let dest = destination(&config.project.name, prefix, path)?;
+    if !dest.is_file() {
+        return Err(Error::failure(format!(
+            "{} is not installed at {}",
+            config.project.name,
+            dest.display()
+        )));
+    }
+    safe_path::remove_file(&dest).context(|| format!("failed to remove {}", dest.display()))?;
+    log::status!("✓ Removed {}", dest.display());
+    Ok(())
+}
+
+/// Where the executable goes, keeping the platform's `.exe` suffix
+fn destination(name: &str, prefix: Option<&str>, path: Option<&str>) -> error::Result<PathBuf> {
+    let dir = match (prefix, path) {
+        (_, Some(path)) => PathBuf::from(path),
+        (Some(prefix), None) => PathBuf::from(prefix).join("bin"),
+        (None, None) => home_dir()
+            .map(|home| home.join(".local").join("bin"))
+            .ok_or_else(|| {
+                Error::usage("cannot determine the home directory").hint("pass --prefix or --path")
+            })?,
+    };
+    safe_path::allow_root(&dir);
+    Ok(dir.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX)))
+}
+
+/// The prefix a library goes under; `--path` only places an executable
+fn library_prefix(
+    config: &ProjectConfig,
+    prefix: Option<&str>,
+    path: Option<&str>,
+) -> error::Result<PathBuf> {
+    match (prefix, path) {
+        (_, Some(_)) => Err(Error::usage(format!(
+            "'{}' is a library; --path only places an executable",
+            config.project.name
+        ))
+        .hint("pass --prefix, under which lib/ and include/ go")),
+        (Some(prefix), None) => Ok(PathBuf::from(prefix)),
+        (None, None) => home_dir().map(|home| home.join(".local")).ok_or_else(|| {
+            Error::usage("cannot determine the home directory").hint("pass --prefix")
+        }),
+    }
+    .inspect(|prefix| safe_path::allow_root(prefix))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    std::env::var_os(var)
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+fn on_path(dir: &Path) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths).any(|p| p == dir))
+}
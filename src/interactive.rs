@@ -0,0 +1,92 @@
+//! `c1 new --interactive` and `c1 init --interactive`: guided setup.
+//!
+//! Each setting is asked for on the terminal with its default in brackets,
+//! the value the command line and the user configuration would have used;
+//! an empty answer keeps it and an invalid one is asked again. All questions
+//! come before anything is written, so Ctrl-C (or Ctrl-D) leaves no
+//! half-created project behind. When stdin is not a terminal the defaults
+//! are taken as they are.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crate::error::{self, Error};
+use crate::{EDITIONS, NewProject, license, log};
+
+/// Ask for the project's name and settings, returning the name.
+/// `retry` is the command suggested for a rejected name.
+pub fn ask(name: Option<&str>, retry: &str, settings: &mut NewProject) -> error::Result<String> {
+    if !io::stdin().is_terminal() {
+        let Some(name) = name else {
+            return Err(Error::usage(
+                "a project name is needed and stdin is not a terminal to ask for it",
+            )
+            .hint("pass it on the command line: `c1 new <name>`"));
+        };
+        log::status!("stdin is not a terminal, using the defaults");
+        return Ok(name.to_string());
+    }
+
+    let name = loop {
+        let answer = prompt("Project name", name.unwrap_or(""))?;
+        if answer.is_empty() {
+            continue;
+        }
+        match crate::check_project_name(&answer, retry) {
+            Ok(()) => break answer,
+            Err(e) => e.report(),
+        }
+    };
+    settings.description = prompt("Description", &settings.description)?;
+    settings.edition = loop {
+        let question = format!("C edition ({})", EDITIONS.join(", "));
+        let answer = prompt(&question, &settings.edition)?;
+        // Accept `11` for c11, as project.toml does
+        let edition = format!("c{}", answer.strip_prefix('c').unwrap_or(&answer));
+        if EDITIONS.contains(&edition.as_str()) {
+            break edition;
+        }
+        log::problem!("Error: unknown edition '{}'", answer);
+    };
+    settings.compiler = prompt("C compiler", &settings.compiler)?;
+    settings.license = loop {
+        let current = settings.license.map_or("none", |l| l.id);
+        let answer = prompt(
+            "License (MIT, Apache-2.0, BSD-3-Clause, GPL-3.0 or none)",
+            current,
+        )?;
+        match license::parse(&answer) {
+            Ok(license) => break license,
+            Err(e) => e.report(),
+        }
+    };
+    settings.git = loop {
+        let current = if settings.git { "yes" } else { "no" };
+        match prompt("Initialize a git repository (yes/no)", current)?.as_str() {
+            "y" | "Y" | "yes" | "YES" => break true,
+            "n" | "N" | "no" | "NO" => break false,
+            answer => log::problem!("Error: answer yes or no, not '{}'", answer),
+        }
+    };
+    Ok(name)
+}
+
+/// One question; an empty answer is `default`, end of input aborts
+pub fn prompt(question: &str, default: &str) -> error::Result<String> {
+    if default.is_empty() {
+        eprint!("{}: ", question);
+    } else {
+        eprint!("{} [{}]: ", question, default);
+    }
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    let read = io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .map_err(|e| Error::failure(format!("failed to read the answer: {}", e)))?;
+    if read == 0 {
+        eprintln!();
+        return Err(Error::usage("aborted, nothing was created"));
+    }
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
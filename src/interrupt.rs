@@ -0,0 +1,75 @@
+//! Ctrl-C during work that must not be left half done.
+//!
+//! While a `Deferred` guard is alive, Ctrl-C only takes note. The git or
+//! cmake process running at the time gets the signal from the terminal as
+//! well and stops, so the step it was doing fails and the code around it
+//! removes what the step left behind. When the last guard goes, c1 exits
+//! with 130 as if Ctrl-C had stopped it there. A second Ctrl-C exits at
+//! once, for when a step hangs. Outside guarded work Ctrl-C stops c1 as
+//! usual.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::log;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+/// The handler in place before the first guard, such as `c1 watch`'s
+static PREVIOUS: AtomicUsize = AtomicUsize::new(0);
+
+/// Ctrl-C waits for the current step until this is dropped
+pub struct Deferred;
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        if DEPTH.fetch_sub(1, Ordering::SeqCst) != 1 {
+            return;
+        }
+        restore();
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            log::problem!("Interrupted");
+            std::process::exit(130);
+        }
+    }
+}
+
+/// Hold off Ctrl-C until the returned guard is dropped
+pub fn defer() -> Deferred {
+    if DEPTH.fetch_add(1, Ordering::SeqCst) == 0 {
+        install();
+    }
+    Deferred
+}
+
+/// Whether Ctrl-C was pressed during deferred work, which should then stop
+/// at the next point where it can clean up
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+fn install() {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            // SAFETY: _exit is async-signal-safe
+            unsafe { libc::_exit(130) };
+        }
+    }
+    let handler = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only touches an atomic and calls _exit
+    let previous = unsafe { libc::signal(libc::SIGINT, handler) };
+    PREVIOUS.store(previous, Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+fn restore() {
+    // SAFETY: puts back the disposition `install` replaced
+    unsafe { libc::signal(libc::SIGINT, PREVIOUS.load(Ordering::SeqCst)) };
+}
+
+/// Elsewhere Ctrl-C keeps its default effect
+#[cfg(not(unix))]
+fn install() {}
+
+#[cfg(not(unix))]
+fn restore() {}
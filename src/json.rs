@@ -0,0 +1,28 @@
+//! Just enough JSON writing for c1's `--json` outputs.
+//!
+//! The documents are small and flat, so they are assembled with `format!`
+//! around these helpers rather than pulling in a serializer.
+
+/// Escape `s` for use between double quotes
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// A quoted JSON string
+pub fn string(s: &str) -> String {
+    format!("\"{}\"", escape(s))
+}
+
+/// A quoted JSON string, or `null`
+pub fn optional(s: Option<&str>) -> String {
+    s.map_or("null".to_string(), string)
+}
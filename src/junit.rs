@@ -0,0 +1,467 @@
+//! `c1 test --format junit --output <file>`: the results as JUnit XML, which
+//! CI systems such as GitLab and Jenkins display.
+//!
+//! Each test program is a `<testsuite>` with the time it started and the
+//! host it ran on. Its cases come from the lines c1_test.h (`PASS name`, or
+//! the failed checks followed by `FAIL name`) or Unity
+//! (`file:line:name:PASS`, `...:FAIL:message`, `...:IGNORE`) prints, ignored
+//! cases being skipped ones. When those lines do not add up to the program's
+//! summary line, or it printed none, the program is a single case instead.
+//! A program that failed although its cases passed, such as one that crashed
+//! after them or that valgrind flagged, gets a case of its own for that.
+//! Programs the pattern left out are suites with one skipped case. The
+//! frameworks do not time cases, so only suites and whole-program cases have
+//! a time. What a program printed goes into the suite's `<system-out>` and
+//! `<system-err>`, and into the `<failure>` of a whole-program case.
+
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{self, Context};
+use crate::{safe_path, version_header};
+
+/// How one test program ran
+pub struct Program {
+    pub name: String,
+    pub started: SystemTime,
+    pub duration: Duration,
+    pub stdout: String,
+    pub stderr: String,
+    /// Why the program failed, if it did
+    pub failure: Option<String>,
+    /// Passed and failed cases from its summary line
+    pub summary: Option<(u32, u32)>,
+}
+
+enum Outcome {
+    Passed,
+    Failed { message: String, details: String },
+    Skipped(String),
+}
+
+struct Case {
+    name: String,
+    time: Option<Duration>,
+    outcome: Outcome,
+}
+
+/// Write the report for the programs that ran and those the pattern left out
+pub fn write(path: &Path, programs: &[Program], filtered: &[String]) -> error::Result<()> {
+    let host = hostname();
+    let mut suites: Vec<(String, Vec<Case>, Option<&Program>)> = programs
+        .iter()
+        .map(|program| (program.name.clone(), cases(program), Some(program)))
+        .collect();
+    for name in filtered {
+        let skipped = Case {
+            name: name.clone(),
+            time: None,
+            outcome: Outcome::Skipped("left out by the test pattern".to_string()),
+        };
+        suites.push((name.clone(), vec![skipped], None));
+    }
+
+    let all = suites.iter().flat_map(|(_, cases, _)| cases);
+    let (tests, failures, skipped) = counts(all);
+    let time: Duration = programs.iter().map(|p| p.duration).sum();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"c1 test\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" \
+         time=\"{:.3}\">\n",
+        tests,
+        failures,
+        skipped,
+        time.as_secs_f64()
+    ));
+    for (name, cases, program) in &suites {
+        suite(&mut xml, name, cases, *program, &host);
+    }
+    xml.push_str("</testsuites>\n");
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).context(|| format!("failed to create {}", parent.display()))?;
+    }
+    safe_path::write(path, xml).context(|| format!("failed to write {}", path.display()))
+}
+
+/// Tests, failures and skipped among `cases`
+fn counts<'a>(cases: impl Iterator<Item = &'a Case>) -> (usize, usize, usize) {
+    cases.fold((0, 0, 0), |(tests, failures, skipped), case| {
+        match case.outcome {
+            Outcome::Passed => (tests + 1, failures, skipped),
+            Outcome::Failed { .. } => (tests + 1, failures + 1, skipped),
+            Outcome::Skipped(_) => (tests + 1, failures, skipped + 1),
+        }
+    })
+}
+
+fn suite(xml: &mut String, name: &str, cases: &[Case], program: Option<&Program>, host: &str) {
+    let (tests, failures, skipped) = counts(cases.iter());
+    let started = program.map_or(SystemTime::now(), |p| p.started);
+    let secs = started
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    // JUnit timestamps carry no zone; these are UTC
+    let timestamp = version_header::iso8601(secs);
+    xml.push_str(&format!(
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"{}\" \
+         time=\"{:.3}\" timestamp=\"{}\" hostname=\"{}\">\n",
+        escape(name),
+        tests,
+        failures,
+        skipped,
+        program.map_or(0.0, |p| p.duration.as_secs_f64()),
+        timestamp.trim_end_matches('Z'),
+        escape(host)
+    ));
+    for case in cases {
+        let time = case
+            .time
+            .map(|time| format!(" time=\"{:.3}\"", time.as_secs_f64()))
+            .unwrap_or_default();
+        let open = format!(
+            "    <testcase name=\"{}\" classname=\"{}\"{}",
+            escape(&case.name),
+            escape(name),
+            time
+        );
+        match &case.outcome {
+            Outcome::Passed => xml.push_str(&format!("{}/>\n", open)),
+            Outcome::Failed { message, details } => xml.push_str(&format!(
+                "{}>\n      <failure message=\"{}\" type=\"failure\">{}</failure>\n    \
+                 </testcase>\n",
+                open,
+                escape(message),
+                escape(details)
+            )),
+            Outcome::Skipped(message) => xml.push_str(&format!(
+                "{}>\n      <skipped message=\"{}\"/>\n    </testcase>\n",
+                open,
+                escape(message)
+            )),
+        }
+    }
+    if let Some(program) = program {
+        xml.push_str(&format!(
+            "    <system-out>{}</system-out>\n    <system-err>{}</system-err>\n",
+            escape(&program.stdout),
+            escape(&program.stderr)
+        ));
+    }
+    xml.push_str("  </testsuite>\n");
+}
+
+/// The cases a program reported, or the program as one case when its output
+/// cannot be relied on
+fn cases(program: &Program) -> Vec<Case> {
+    let whole = |outcome| Case {
+        name: program.name.clone(),
+        time: Some(program.duration),
+        outcome,
+    };
+    let output = || format!("{}{}", program.stdout, program.stderr);
+    let mut cases = match program.summary {
+        Some(summary) => parse(&program.stdout).filter(|cases| {
+            let (tests, failures, skipped) = counts(cases.iter());
+            summary == ((tests - failures - skipped) as u32, failures as u32)
+        }),
+        None => None,
+    };
+    match (&mut cases, &program.failure) {
+        (None, None) => vec![whole(Outcome::Passed)],
+        (None, Some(failure)) => vec![whole(Outcome::Failed {
+            message: failure.clone(),
+            details: output(),
+        })],
+        (Some(cases), failure) => {
+            let failed = cases
+                .iter()
+                .any(|case| matches!(case.outcome, Outcome::Failed { .. }));
+            if let Some(failure) = failure
+                && !failed
+            {
+                cases.push(whole(Outcome::Failed {
+                    message: failure.clone(),
+                    details: output(),
+                }));
+            }
+            std::mem::take(cases)
+        }
+    }
+}
+
+/// The cases in c1_test.h's or Unity's output, if it has any
+fn parse(stdout: &str) -> Option<Vec<Case>> {
+    let mut cases = Vec::new();
+    // c1_test.h prints a case's failed checks before its FAIL line
+    let mut checks: Vec<&str> = Vec::new();
+    for line in stdout.lines() {
+        let case = |name: &str, outcome| Case {
+            name: name.to_string(),
+            time: None,
+            outcome,
+        };
+        if let Some(name) = line.strip_prefix("PASS ") {
+            cases.push(case(name.trim(), Outcome::Passed));
+            checks.clear();
+        } else if let Some(name) = line.strip_prefix("FAIL ") {
+            let message = checks.first().map_or("failed", |check| check.trim());
+            cases.push(case(
+                name.trim(),
+                Outcome::Failed {
+                    message: message.to_string(),
+                    details: checks.join("\n"),
+                },
+            ));
+            checks.clear();
+        } else if let Some((name, outcome)) = unity(line) {
+            cases.push(case(name, outcome));
+        } else if line.starts_with("  ") {
+            checks.push(line);
+        }
+    }
+    (!cases.is_empty()).then_some(cases)
+}
+
+/// A Unity result line, `file:line:name:STATUS[:message]`; the file may
+/// itself contain colons (`C:\tests\a.c`)
+fn unity(line: &str) -> Option<(&str, Outcome)> {
+    let parts: Vec<&str> = line.split(':').collect();
+    (2..parts.len()).find_map(|i| {
+        parts[i - 2].parse::<u32>().ok()?;
+        let message = parts[i + 1..].join(":").trim().to_string();
+        let outcome = match parts[i] {
+            "PASS" if message.is_empty() => Outcome::Passed,
+            "FAIL" => Outcome::Failed {
+                message: if message.is_empty() {
+                    "failed".to_string()
+                } else {
+                    message
+                },
+                details: line.to_string(),
+            },
+            "IGNORE" => Outcome::Skipped(if message.is_empty() {
+                "ignored".to_string()
+            } else {
+                message
+            }),
+            _ => return None,
+        };
+        Some((parts[i - 1], outcome))
+    })
+}
+
+/// Text as XML attribute or element content; characters XML 1.0 cannot
+/// hold at all are dropped
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() && c < ' ' => {}
+            '\u{FFFE}' | '\u{FFFF}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The name of this machine, for the suites' hostname attribute
+fn hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn program(stdout: &str, summary: Option<(u32, u32)>, failure: Option<&str>) -> Program {
+        Program {
+            name: "test_math".to_string(),
+            started: UNIX_EPOCH,
+            duration: Duration::from_millis(250),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            failure: failure.map(str::to_string),
+            summary,
+        }
+    }
+
+    fn cases_failing(stdout: &str, summary: Option<(u32, u32)>) -> Vec<Case> {
+        cases(&program(stdout, summary, Some("exit code 1")))
+    }
+
+    fn outcomes(cases: &[Case]) -> Vec<(&str, &str)> {
+        cases
+            .iter()
+            .map(|case| {
+                let outcome = match case.outcome {
+                    Outcome::Passed => "passed",
+                    Outcome::Failed { .. } => "failed",
+                    Outcome::Skipped(_) => "skipped",
+                };
+                (case.name.as_str(), outcome)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_unity_lines_with_windows_paths() {
+        let (name, outcome) = unity("C:\\src\\tests\\test_math.c:12:test_adds:PASS").unwrap();
+        assert_eq!(name, "test_adds");
+        assert!(matches!(outcome, Outcome::Passed));
+
+        let line =
+            "C:\\src\\tests\\test_math.c:20:test_divides:FAIL: Expected 2 Was 3: see C:\\log";
+        match unity(line) {
+            Some(("test_divides", Outcome::Failed { message, details })) => {
+                assert_eq!(message, "Expected 2 Was 3: see C:\\log");
+                assert_eq!(details, line);
+            }
+            _ => panic!("not a failed case"),
+        }
+
+        let (name, outcome) = unity("D:\\t\\test_math.c:31:test_later:IGNORE").unwrap();
+        assert_eq!(name, "test_later");
+        assert!(matches!(outcome, Outcome::Skipped(message) if message == "ignored"));
+
+        // No line number, or not a status Unity prints
+        assert!(unity("C:\\src\\test_math.c:test_adds:PASS").is_none());
+        assert!(unity("test_math.c:12:test_adds:PASSED").is_none());
+    }
+
+    #[test]
+    fn reads_cases_that_add_up_to_the_summary() {
+        let stdout = "PASS test_adds\n  test_math.c:9: expected 2, got 3\nFAIL test_divides\n";
+        let cases = cases(&program(stdout, Some((1, 1)), Some("exit code 1")));
+        assert_eq!(
+            outcomes(&cases),
+            [("test_adds", "passed"), ("test_divides", "failed")]
+        );
+        assert!(matches!(
+            &cases[1].outcome,
+            Outcome::Failed { message, .. } if message == "test_math.c:9: expected 2, got 3"
+        ));
+    }
+
+    #[test]
+    fn falls_back_to_one_case_when_the_counts_disagree() {
+        let stdout = "PASS test_adds\nPASS test_divides\n";
+        // The summary says a third case ran
+        let cases = cases(&program(stdout, Some((3, 0)), None));
+        assert_eq!(outcomes(&cases), [("test_math", "passed")]);
+
+        let cases = cases_failing(stdout, Some((2, 1)));
+        assert_eq!(outcomes(&cases), [("test_math", "failed")]);
+        match &cases[0].outcome {
+            Outcome::Failed { message, details } => {
+                assert_eq!(message, "exit code 1");
+                assert_eq!(details, stdout);
+            }
+            _ => unreachable!(),
+        }
+
+        // No summary line at all
+        let cases = cases_failing(stdout, None);
+        assert_eq!(outcomes(&cases), [("test_math", "failed")]);
+    }
+
+    #[test]
+    fn a_crash_after_passing_cases_is_a_case_of_its_own() {
+        let stdout = "PASS test_adds\n";
+        let cases = cases(&program(stdout, Some((1, 0)), Some("killed by SIGSEGV")));
+        assert_eq!(
+            outcomes(&cases),
+            [("test_adds", "passed"), ("test_math", "failed")]
+        );
+    }
+
+    #[test]
+    fn escapes_what_xml_cannot_hold_as_is() {
+        assert_eq!(
+            escape("a < b && c > d \"q\" 'a'"),
+            "a &lt; b &amp;&amp; c &gt; d &quot;q&quot; &apos;a&apos;"
+        );
+        assert_eq!(escape("]]>"), "]]&gt;");
+        // Colored output and a stray NUL or form feed are dropped
+        assert_eq!(escape("\u{1b}[31mred\u{1b}[0m\0\u{c}"), "[31mred[0m");
+        assert_eq!(escape("tab\tline\r\n"), "tab\tline\r\n");
+        assert_eq!(escape("\u{FFFE}\u{FFFF}ok"), "ok");
+    }
+
+    #[test]
+    fn writes_well_formed_xml_for_hostile_output() {
+        let tmp = TempDir::new("junit-hostile");
+        crate::safe_path::allow_root(tmp.path());
+        let path = tmp.path().join("report.xml");
+        let mut crashed = program(
+            "PASS <b>&\n]]>\u{1b}[1mbold\u{0}\n",
+            None,
+            Some("exit code 1: ]]> & \u{7}"),
+        );
+        crashed.stderr = "free(): invalid pointer \u{8}\n".to_string();
+        write(&path, &[crashed], &[]).unwrap();
+
+        let xml = fs::read_to_string(&path).unwrap();
+        assert!(
+            xml.chars()
+                .all(|c| c >= ' ' || matches!(c, '\t' | '\n' | '\r')),
+            "{:?}",
+            xml
+        );
+        assert!(!xml.contains("]]>"));
+        assert!(!xml.contains("<b>"));
+        // Every & starts an entity
+        for (i, _) in xml.match_indices('&') {
+            let entity = &xml[i..];
+            assert!(
+                ["&amp;", "&lt;", "&gt;", "&quot;", "&apos;"]
+                    .iter()
+                    .any(|e| entity.starts_with(e)),
+                "{}",
+                &entity[..entity.len().min(10)]
+            );
+        }
+        assert!(xml.contains("<failure message=\"exit code 1: ]]&gt; &amp; \""));
+    }
+
+    #[test]
+    fn records_filtered_programs_as_skipped() {
+        let tmp = TempDir::new("junit-filtered");
+        crate::safe_path::allow_root(tmp.path());
+        let path = tmp.path().join("report.xml");
+        write(
+            &path,
+            &[program("PASS test_adds\n", Some((1, 0)), None)],
+            &["test_io".to_string()],
+        )
+        .unwrap();
+
+        let xml = fs::read_to_string(&path).unwrap();
+        assert!(xml.contains(
+            "<testsuites name=\"c1 test\" tests=\"2\" failures=\"0\" errors=\"0\" skipped=\"1\""
+        ));
+        assert!(xml.contains(
+            "<testsuite name=\"test_io\" tests=\"1\" failures=\"0\" errors=\"0\" skipped=\"1\""
+        ));
+        assert!(xml.contains(
+            "<testcase name=\"test_io\" classname=\"test_io\">\n      \
+             <skipped message=\"left out by the test pattern\"/>"
+        ));
+        // A program left out has no output to report
+        assert_eq!(xml.matches("<system-out>").count(), 1);
+    }
+}
@@ -0,0 +1,297 @@
+//! `c1 install` / `c1 uninstall` for `type = "lib"` projects: the static
+//! library, its headers and the files other build systems find it by.
+//!
+//! Under the prefix (`--prefix`, default `~/.local`) go
+//!
+//! - `lib/lib<name>.a` (`<name>.lib` with MSVC), built in release mode
+//! - `include/`, a copy of the project's include/
+//! - `lib/cmake/<name>/<name>Config.cmake` and `<name>ConfigVersion.cmake`,
+//!   for `find_package(<name>)` and its imported target `<name>::<name>`
+//! - `lib/pkgconfig/<name>.pc`, for `pkg-config --cflags --libs <name>`
+//!
+//! Both carry the version from project.toml and link the system libraries
+//! of `[build] links`. The CMake package finds the prefix from where it was
+//! installed; the .pc file names the prefix it was installed to. A library
+//! that links dependencies from external/ needs its consumers to provide
+//! them; they are not installed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{self, Context, Error};
+use crate::{BuildOptions, ProjectConfig, log, safe_path};
+
+/// What installing the library into `prefix` writes, its headers aside
+struct Layout {
+    library: PathBuf,
+    config: PathBuf,
+    config_version: PathBuf,
+    pkg_config: PathBuf,
+}
+
+impl Layout {
+    fn new(prefix: &Path, name: &str, library_file: &str) -> Layout {
+        let cmake_dir = prefix.join("lib").join("cmake").join(name);
+        Layout {
+            library: prefix.join("lib").join(library_file),
+            config: cmake_dir.join(format!("{}Config.cmake", name)),
+            config_version: cmake_dir.join(format!("{}ConfigVersion.cmake", name)),
+            pkg_config: prefix
+                .join("lib")
+                .join("pkgconfig")
+                .join(format!("{}.pc", name)),
+        }
+    }
+
+    fn files(&self) -> [&PathBuf; 4] {
+        [
+            &self.library,
+            &self.config,
+            &self.config_version,
+            &self.pkg_config,
+        ]
+    }
+}
+
+/// The library's file name as the build writes it
+fn library_file(name: &str) -> String {
+    if cfg!(windows) {
+        format!("{}.lib", name)
+    } else {
+        format!("lib{}.a", name)
+    }
+}
+
+pub fn install(config: &ProjectConfig, prefix: &Path, force: bool) -> error::Result<()> {
+    let name = &config.project.name;
+    let prefix = std::path::absolute(prefix).unwrap_or_else(|_| prefix.to_path_buf());
+    let layout = Layout::new(&prefix, name, &library_file(name));
+    let headers = headers();
+    let existing = layout
+        .files()
+        .into_iter()
+        .cloned()
+        .chain(headers.iter().map(|h| prefix.join("include").join(h)))
+        .find(|path| path.exists());
+    if let Some(existing) = existing
+        && !force
+    {
+        return Err(
+            Error::usage(format!("{} already exists", existing.display()))
+                .hint("pass --force to replace it"),
+        );
+    }
+
+    let options = BuildOptions {
+        release: true,
+        ..BuildOptions::default()
+    };
+    crate::cmd_build(&options)?;
+    let library = built_library(&crate::target_build_dir(&options), name)?;
+
+    copy(&library, &layout.library)?;
+    for header in &headers {
+        copy(
+            &Path::new("include").join(header),
+            &prefix.join("include").join(header),
+        )?;
+    }
+    write(&layout.config, &cmake_config(config))?;
+    write(
+        &layout.config_version,
+        &cmake_config_version(&config.project.version),
+    )?;
+    write(&layout.pkg_config, &pkg_config(config, &prefix))?;
+    for path in layout.files() {
+        log::status!("  ✓ {}", path.display());
+    }
+    if !headers.is_empty() {
+        log::status!(
+            "  ✓ {} header(s) in {}",
+            headers.len(),
+            prefix.join("include").display()
+        );
+    }
+    log::status!(
+        "✓ Installed {} {} to {}",
+        name,
+        config.project.version,
+        prefix.display()
+    );
+    log::status!(
+        "  find_package({}) with CMAKE_PREFIX_PATH={}, or pkg-config {} with PKG_CONFIG_PATH={}",
+        name,
+        prefix.display(),
+        name,
+        layout.pkg_config.parent().unwrap_or(&prefix).display()
+    );
+    Ok(())
+}
+
+pub fn uninstall(config: &ProjectConfig, prefix: &Path) -> error::Result<()> {
+    let name = &config.project.name;
+    let layout = Layout::new(prefix, name, &library_file(name));
+    if !layout.library.is_file() {
+        return Err(Error::failure(format!(
+            "{} is not installed at {}",
+            name,
+            prefix.display()
+        )));
+    }
+    let headers = headers()
+        .into_iter()
+        .map(|h| prefix.join("include").join(h));
+    let installed: Vec<PathBuf> = layout
+        .files()
+        .into_iter()
+        .cloned()
+        .chain(headers)
+        .filter(|path| path.is_file())
+        .collect();
+    for path in &installed {
+        safe_path::remove_file(path).context(|| format!("failed to remove {}", path.display()))?;
+        log::verbose!("Removed {}", path.display());
+    }
+    // The directories only the library used go with it
+    if let Some(dir) = layout.config.parent() {
+        let _ = fs::remove_dir(dir);
+    }
+    log::status!(
+        "✓ Removed {} file(s) of {} from {}",
+        installed.len(),
+        name,
+        prefix.display()
+    );
+    Ok(())
+}
+
+/// The files under include/, relative to it
+fn headers() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect(Path::new("include"), &mut files);
+    files
+        .into_iter()
+        .filter_map(|file| Some(file.strip_prefix("include").ok()?.to_path_buf()))
+        .collect()
+}
+
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// Where the release build left the library; multi-config generators put
+/// it under Release/
+fn built_library(build_dir: &Path, name: &str) -> error::Result<PathBuf> {
+    let file = library_file(name);
+    [build_dir.join("Release").join(&file), build_dir.join(&file)]
+        .into_iter()
+        .find(|path| path.is_file())
+        .ok_or_else(|| {
+            Error::failure(format!(
+                "library {} not found in {}",
+                file,
+                build_dir.display()
+            ))
+            .hint("CMakeLists.txt should build it with add_library(${PROJECT_NAME} STATIC ...)")
+        })
+}
+
+fn copy(from: &Path, to: &Path) -> error::Result<()> {
+    if let Some(dir) = to.parent() {
+        fs::create_dir_all(dir).context(|| format!("failed to create {}", dir.display()))?;
+    }
+    safe_path::copy(from, to).context(|| format!("failed to install to {}", to.display()))?;
+    Ok(())
+}
+
+fn write(path: &Path, content: &str) -> error::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context(|| format!("failed to create {}", dir.display()))?;
+    }
+    safe_path::write(path, content).context(|| format!("failed to write {}", path.display()))
+}
+
+fn cmake_config(config: &ProjectConfig) -> String {
+    let name = &config.project.name;
+    format!(
+        r#"# {name} {version}, installed by c1
+# The prefix is three levels up from lib/cmake/{name}/
+get_filename_component(_{name}_prefix "${{CMAKE_CURRENT_LIST_DIR}}/../../.." ABSOLUTE)
+
+if(NOT TARGET {name}::{name})
+    add_library({name}::{name} STATIC IMPORTED)
+    set_target_properties({name}::{name} PROPERTIES
+        IMPORTED_LOCATION "${{_{name}_prefix}}/lib/{library}"
+        INTERFACE_INCLUDE_DIRECTORIES "${{_{name}_prefix}}/include"
+        INTERFACE_LINK_LIBRARIES "{links}"
+    )
+endif()
+
+set({name}_VERSION "{version}")
+set({name}_INCLUDE_DIRS "${{_{name}_prefix}}/include")
+set({name}_LIBRARIES {name}::{name})
+unset(_{name}_prefix)
+"#,
+        version = config.project.version,
+        library = library_file(name),
+        links = config.build.links.join(";"),
+    )
+}
+
+/// Any version with the same major number and at least the one asked for
+fn cmake_config_version(version: &str) -> String {
+    let major = version.split('.').next().unwrap_or(version);
+    format!(
+        r#"set(PACKAGE_VERSION "{version}")
+
+if(NOT PACKAGE_FIND_VERSION)
+    set(PACKAGE_VERSION_COMPATIBLE TRUE)
+elseif(PACKAGE_FIND_VERSION VERSION_GREATER PACKAGE_VERSION)
+    set(PACKAGE_VERSION_COMPATIBLE FALSE)
+elseif(PACKAGE_FIND_VERSION_MAJOR STREQUAL "{major}")
+    set(PACKAGE_VERSION_COMPATIBLE TRUE)
+    if(PACKAGE_FIND_VERSION VERSION_EQUAL PACKAGE_VERSION)
+        set(PACKAGE_VERSION_EXACT TRUE)
+    endif()
+else()
+    set(PACKAGE_VERSION_COMPATIBLE FALSE)
+endif()
+"#
+    )
+}
+
+fn pkg_config(config: &ProjectConfig, prefix: &Path) -> String {
+    let project = &config.project;
+    // The library is static, so its consumers link what it links
+    let links: String = config
+        .build
+        .links
+        .iter()
+        .map(|link| format!(" -l{}", link))
+        .collect();
+    format!(
+        "prefix={}\nlibdir=${{prefix}}/lib\nincludedir=${{prefix}}/include\n\n\
+         Name: {}\nDescription: {}\nVersion: {}\nCflags: -I${{includedir}}\n\
+         Libs: -L${{libdir}} -l{}{}\n",
+        prefix.to_string_lossy().replace('\\', "/"),
+        project.name,
+        if project.description.is_empty() {
+            &project.name
+        } else {
+            &project.description
+        },
+        project.version,
+        project.name,
+        links
+    )
+}
@@ -0,0 +1,169 @@
+//! LICENSE files for `c1 new --license` and `c1 init --license`.
+//!
+//! MIT and BSD-3-Clause are short enough to ship in full. For Apache-2.0 and
+//! GPL-3.0 the file holds the notice each license asks projects to apply,
+//! which points at the full text; c1 works offline and does not download it.
+//! The copyright line uses the current year and `git config user.name`.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{self, Error};
+
+pub struct License {
+    /// SPDX identifier, as recorded in project.toml
+    pub id: &'static str,
+    text: &'static str,
+}
+
+const LICENSES: &[License] = &[
+    License {
+        id: "MIT",
+        text: MIT,
+    },
+    License {
+        id: "Apache-2.0",
+        text: APACHE_2_0,
+    },
+    License {
+        id: "BSD-3-Clause",
+        text: BSD_3_CLAUSE,
+    },
+    License {
+        id: "GPL-3.0",
+        text: GPL_3_0,
+    },
+];
+
+/// The license for a `--license` value, matched case-insensitively; `none`
+/// means no license
+pub fn parse(id: &str) -> error::Result<Option<&'static License>> {
+    if id.eq_ignore_ascii_case("none") {
+        return Ok(None);
+    }
+    LICENSES
+        .iter()
+        .find(|license| license.id.eq_ignore_ascii_case(id))
+        .map(Some)
+        .ok_or_else(|| {
+            let names: Vec<&str> = LICENSES.iter().map(|l| l.id).collect();
+            Error::usage(format!("unknown license '{}'", id))
+                .hint(format!("supported licenses: {}, none", names.join(", ")))
+        })
+}
+
+impl License {
+    /// The LICENSE file contents for `project_name`
+    pub fn render(&self, project_name: &str) -> String {
+        let author = crate::run_git(".", &["config", "user.name"])
+            .ok()
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| format!("The {} authors", project_name));
+        self.text
+            .replace("{year}", &current_year().to_string())
+            .replace("{author}", &author)
+            .replace("{project}", project_name)
+    }
+}
+
+fn current_year() -> i64 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    // Days since the epoch to a proleptic Gregorian year
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    // The computation counts years from March; January and February belong
+    // to the next calendar year
+    year_of_era + era * 400 + i64::from(month_index >= 10)
+}
+
+const MIT: &str = "MIT License
+
+Copyright (c) {year} {author}
+
+Permission is hereby granted, free of charge, to any person obtaining a copy
+of this software and associated documentation files (the \"Software\"), to deal
+in the Software without restriction, including without limitation the rights
+to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+copies of the Software, and to permit persons to whom the Software is
+furnished to do so, subject to the following conditions:
+
+The above copyright notice and this permission notice shall be included in all
+copies or substantial portions of the Software.
+
+THE SOFTWARE IS PROVIDED \"AS IS\", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+SOFTWARE.
+";
+
+const BSD_3_CLAUSE: &str = "BSD 3-Clause License
+
+Copyright (c) {year}, {author}
+
+Redistribution and use in source and binary forms, with or without
+modification, are permitted provided that the following conditions are met:
+
+1. Redistributions of source code must retain the above copyright notice, this
+   list of conditions and the following disclaimer.
+
+2. Redistributions in binary form must reproduce the above copyright notice,
+   this list of conditions and the following disclaimer in the documentation
+   and/or other materials provided with the distribution.
+
+3. Neither the name of the copyright holder nor the names of its
+   contributors may be used to endorse or promote products derived from
+   this software without specific prior written permission.
+
+THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS \"AS IS\"
+AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT LIMITED TO, THE
+IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR A PARTICULAR PURPOSE ARE
+DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT HOLDER OR CONTRIBUTORS BE LIABLE
+FOR ANY DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL
+DAMAGES (INCLUDING, BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR
+SERVICES; LOSS OF USE, DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER
+CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY,
+OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+";
+
+const APACHE_2_0: &str = "Copyright {year} {author}
+
+Licensed under the Apache License, Version 2.0 (the \"License\");
+you may not use this file except in compliance with the License.
+You may obtain a copy of the License at
+
+    http://www.apache.org/licenses/LICENSE-2.0
+
+Unless required by applicable law or agreed to in writing, software
+distributed under the License is distributed on an \"AS IS\" BASIS,
+WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+See the License for the specific language governing permissions and
+limitations under the License.
+";
+
+const GPL_3_0: &str = "{project}
+Copyright (C) {year} {author}
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+GNU General Public License for more details.
+
+You should have received a copy of the GNU General Public License
+along with this program.  If not, see <https://www.gnu.org/licenses/>.
+";
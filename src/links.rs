@@ -0,0 +1,114 @@
+//! System libraries linked by name through `[build] links` in project.toml.
+//!
+//! ```toml
+//! [build]
+//! links = ["m", "pthread"]
+//! ```
+//!
+//! Each entry becomes a `target_link_libraries(${PROJECT_NAME} PRIVATE <lib>)`
+//! line in a managed block of CMakeLists.txt, `# @c1_link_libraries` through
+//! `# @c1_link_libraries_end`, brought in step with the manifest before every
+//! build, so removing an entry unlinks the library. `c1 add --link` appends to
+//! the list and updates the block in one step.
+
+use std::fs;
+
+use crate::error::{self, Context, Error};
+use crate::log;
+use crate::safe_path;
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+const BEGIN: &str = "# @c1_link_libraries";
+const END: &str = "# @c1_link_libraries_end";
+
+/// Check the `links` entries and bring the CMakeLists block up to date
+pub fn wire(links: &[String]) -> error::Result<()> {
+    for link in links {
+        check(link)?;
+    }
+    let Ok(content) = fs::read_to_string(CMAKE_PATH) else {
+        return Ok(());
+    };
+    let block = block(links);
+    let updated = match (content.find(&format!("{}\n", BEGIN)), content.find(END)) {
+        (Some(start), Some(stop)) if stop > start => {
+            let stop = stop + END.len();
+            let stop = stop + content[stop..].find('\n').map_or(0, |i| i + 1);
+            // Drop the blank line that separated a removed block
+            let start = if block.is_empty() && content[..start].ends_with("\n\n") {
+                start - 1
+            } else {
+                start
+            };
+            format!("{}{}{}", &content[..start], block, &content[stop..])
+        }
+        _ if block.is_empty() => return Ok(()),
+        _ => {
+            let mut updated = content.clone();
+            if !updated.ends_with('\n') {
+                updated.push('\n');
+            }
+            updated.push('\n');
+            updated.push_str(&block);
+            updated
+        }
+    };
+    if updated != content {
+        safe_path::write(CMAKE_PATH, updated)
+            .context(|| format!("failed to update {}", CMAKE_PATH))?;
+        log::status!("✓ Updated linked libraries in CMakeLists.txt");
+    }
+    Ok(())
+}
+
+/// `c1 add --link <lib>`: add `lib` to `[build] links` unless it is there
+pub fn cmd_add(lib: &str) -> error::Result<()> {
+    check(lib)?;
+    let mut links = crate::read_config()?.build.links;
+    if links.iter().any(|l| l == lib) {
+        log::status!("{} is already in [build] links", lib);
+    } else {
+        links.push(lib.to_string());
+        let value = toml::Value::Array(links.iter().cloned().map(toml::Value::String).collect());
+        crate::set_manifest_entry("build", "links", &value.to_string())?;
+        log::status!("✓ Added {} to [build] links in project.toml", lib);
+    }
+    wire(&links)
+}
+
+/// A library name or path CMake can take as one argument
+fn check(lib: &str) -> error::Result<()> {
+    let valid = !lib.is_empty()
+        && !lib.starts_with('-')
+        && !lib
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '(' | ')' | '"' | '#' | '$' | ';'));
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::config(format!(
+            "'{}' in [build] links is not a library name",
+            lib
+        ))
+        .hint("use names such as \"m\" or \"pthread\", without -l"))
+    }
+}
+
+fn block(links: &[String]) -> String {
+    if links.is_empty() {
+        return String::new();
+    }
+    let mut block = format!(
+        "{}\n# System libraries from [build] links in project.toml, kept in sync by c1\n",
+        BEGIN
+    );
+    for link in links {
+        block.push_str(&format!(
+            "target_link_libraries(${{PROJECT_NAME}} PRIVATE {})\n",
+            link
+        ));
+    }
+    block.push_str(END);
+    block.push('\n');
+    block
+}
@@ -0,0 +1,90 @@
+//! `c1 lint`: run clang-tidy over the project's sources.
+//!
+//! clang-tidy needs the exact flags each file is compiled with, which the
+//! generated CMakeLists exports as `compile_commands.json` in the build
+//! directory. Only main.c
+//! and src/ are linted; external/ is someone else's code.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{self, Error};
+use crate::fmt;
+use crate::ignore::Ignore;
+use crate::log;
+
+pub fn cmd_lint(fix: bool) -> error::Result<()> {
+    let config = crate::read_config()?;
+    let build_dir = crate::target_build_dir(&crate::BuildOptions::default());
+
+    if !build_dir.join("compile_commands.json").exists() {
+        log::status!("Configuring project to generate compile_commands.json...");
+        crate::configure_project(&build_dir, "Debug", &[])?;
+    }
+
+    let files = lint_sources();
+    if files.is_empty() {
+        log::status!("No C sources to lint");
+        return Ok(());
+    }
+
+    let mut diagnostics = 0;
+    let mut failed = Vec::new();
+    for file in &files {
+        let mut command = Command::new("clang-tidy");
+        command.arg("-p").arg(&build_dir).arg("--quiet");
+        if let Some(checks) = &config.lint.checks {
+            command.arg(format!("--checks={}", checks));
+        }
+        if fix {
+            command.arg("--fix");
+        }
+        let output = match command.arg(file).output() {
+            Ok(output) => output,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(Error::failure("clang-tidy not found.").hint(
+                    "install clang-tidy (e.g. `apt install clang-tidy` or `brew install llvm`) and make sure it is on PATH",
+                ));
+            }
+            Err(e) => return Err(Error::failure(format!("failed to run clang-tidy: {}", e))),
+        };
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        print!("{}", stdout);
+        if !output.status.success() {
+            eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        }
+        let count = stdout
+            .lines()
+            .filter(|line| line.contains(": warning:") || line.contains(": error:"))
+            .count();
+        diagnostics += count;
+        if count > 0 || !output.status.success() {
+            failed.push(file.display().to_string());
+        }
+    }
+
+    if failed.is_empty() {
+        log::status!("✓ {} file(s) passed lint", files.len());
+        return Ok(());
+    }
+    Err(Error::failure(format!(
+        "{} diagnostic(s) in {} file(s): {}",
+        diagnostics,
+        failed.len(),
+        failed.join(", ")
+    )))
+}
+
+/// main.c plus the .c files under src/
+fn lint_sources() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if Path::new("main.c").is_file() {
+        files.push(PathBuf::from("main.c"));
+    }
+    fmt::collect(Path::new("src"), &mut files);
+    files.retain(|f| f.extension().is_some_and(|e| e == "c"));
+    Ignore::project().retain(&mut files);
+    files
+}
@@ -0,0 +1,140 @@
+//! `c1 list`: audit what is actually checked out in external/.
+//!
+//! Every declared dependency is shown with its source and, once synced, the
+//! commit and `git describe` of its checkout. Local changes and checkouts that
+//! are not on the declared tag or branch are flagged, as are archives extracted
+//! from another checksum than the declared one; dependencies missing on
+//! disk are marked "not synced", and directories in external/ that the
+//! manifest doesn't mention are listed as untracked. System dependencies show
+//! the version pkg-config reports. Dev-dependencies are marked `(dev)`.
+
+use std::fs;
+
+use crate::{archive, info, log, pkg_config, run_git};
+
+pub fn cmd_list() {
+    let config = crate::load_config();
+    let declared = config.all_dependencies();
+    let dependencies = info::declared(&declared);
+
+    let untracked: Vec<String> = fs::read_dir("external")
+        .map(|entries| {
+            let mut names: Vec<String> = entries
+                .flatten()
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .filter(|name| !name.starts_with('.') && !declared.contains_key(name))
+                .collect();
+            names.sort();
+            names
+        })
+        .unwrap_or_default();
+
+    if dependencies.is_empty() && untracked.is_empty() {
+        println!("No dependencies");
+        return;
+    }
+
+    let width = dependencies
+        .iter()
+        .map(|d| d.name.len())
+        .chain(untracked.iter().map(String::len))
+        .max()
+        .unwrap_or(0);
+    for d in &dependencies {
+        let (state, problems) = match &d.pkg_config {
+            Some(package) if d.present => (
+                pkg_config::version(package).unwrap_or_else(|| "installed".to_string()),
+                Vec::new(),
+            ),
+            Some(_) => ("not installed".to_string(), Vec::new()),
+            None if d.present && d.archive.is_some() => inspect_archive(d, &declared),
+            None if d.present => inspect(d),
+            None => ("not synced".to_string(), Vec::new()),
+        };
+        let mark = if d.present && problems.is_empty() {
+            "✓"
+        } else {
+            "✗"
+        };
+        let dev = if config.dependencies.contains_key(&d.name) {
+            ""
+        } else {
+            " (dev)"
+        };
+        log::print(
+            false,
+            format_args!(
+                "  {} {:width$}  {:24}  {}{}",
+                mark,
+                d.name,
+                state,
+                info::source(d),
+                dev
+            ),
+        );
+        for problem in problems {
+            println!("      {}", problem);
+        }
+    }
+    for name in &untracked {
+        println!(
+            "  ? {:width$}  untracked (external/{} is not in project.toml)",
+            name, name
+        );
+    }
+}
+
+/// `sha256 9f86d081884c` for an extracted archive, or why it is not the
+/// declared one
+fn inspect_archive(d: &info::Dependency, dependencies: &toml::Table) -> (String, Vec<String>) {
+    let declared = dependencies
+        .get(&d.name)
+        .and_then(|v| v.get("sha256"))
+        .and_then(|v| v.as_str());
+    match archive::extracted_sha256(&d.name) {
+        Some(extracted) => {
+            let state = format!("sha256 {}", extracted.chars().take(12).collect::<String>());
+            let problems = match declared {
+                Some(declared) if !declared.eq_ignore_ascii_case(&extracted) => {
+                    vec!["extracted from another archive than project.toml declares".to_string()]
+                }
+                _ => Vec::new(),
+            };
+            (state, problems)
+        }
+        None => ("not extracted by c1".to_string(), Vec::new()),
+    }
+}
+
+/// `abc1234 (v1.2-3-gabc1234)` for a checkout, plus anything that doesn't
+/// match the manifest
+fn inspect(d: &info::Dependency) -> (String, Vec<String>) {
+    let dir = format!("external/{}", d.name);
+    let Ok(short) = run_git(&dir, &["rev-parse", "--short", "HEAD"]) else {
+        return ("not a git checkout".to_string(), Vec::new());
+    };
+    let describe = run_git(&dir, &["describe", "--tags", "--always"]).unwrap_or_default();
+    let state = if describe.is_empty() || describe == short {
+        short
+    } else {
+        format!("{} ({})", short, describe)
+    };
+
+    let mut problems = Vec::new();
+    if run_git(&dir, &["status", "--porcelain"]).is_ok_and(|s| !s.is_empty()) {
+        problems.push("working tree has local changes".to_string());
+    }
+    if let Some(tag) = &d.tag {
+        let exact = run_git(&dir, &["describe", "--tags", "--exact-match"]).ok();
+        if exact.as_deref() != Some(tag.as_str()) {
+            problems.push(format!("checked out {} instead of tag {}", describe, tag));
+        }
+    } else if let Some(branch) = &d.branch {
+        // A detached HEAD is how a locked commit is checked out
+        let current = run_git(&dir, &["rev-parse", "--abbrev-ref", "HEAD"]).unwrap_or_default();
+        if current != "HEAD" && current != *branch {
+            problems.push(format!("on branch {} instead of {}", current, branch));
+        }
+    }
+    (state, problems)
+}
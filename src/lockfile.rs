@@ -0,0 +1,520 @@
+//! project.lock: the exact commit each dependency was resolved to.
+//!
+//! The lockfile is regenerated by `c1 add` and `c1 sync` after every successful
+//! clone. Packages are kept sorted by name so the file diffs cleanly.
+//!
+//! `c1 sync --locked` enforces it instead: every git dependency must have an
+//! entry resolved from the source project.toml declares, must end up at
+//! exactly the locked commit, and the file is never written.
+//!
+//! Next to the commit, each entry records the checkout's git tree, and each
+//! archive dependency a `[[vendored]]` SHA-256 over its extracted files, so
+//! a dependency edited in place is caught even at the locked commit.
+//! `c1 sync --accept-changes` records external/ as it is, edits included.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::git_head;
+use crate::log;
+use crate::safe_path;
+use crate::sha256::Sha256;
+
+pub const LOCKFILE_PATH: &str = "project.lock";
+
+const HEADER: &str = "# This file is generated by c1. Do not edit it by hand.\n\n";
+
+static ENFORCED: AtomicBool = AtomicBool::new(false);
+
+static RECORDING: Mutex<()> = Mutex::new(());
+
+/// `--locked`: check project.lock, never update it
+pub fn set_enforced(enforced: bool) {
+    ENFORCED.store(enforced, Ordering::Relaxed);
+}
+
+pub fn is_enforced() -> bool {
+    ENFORCED.load(Ordering::Relaxed)
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Lockfile {
+    #[serde(default = "default_lock_version")]
+    pub version: u32,
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vendored: Vec<VendoredPackage>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct LockedPackage {
+    pub name: String,
+    pub git: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    pub commit: String,
+    /// Git tree of the checkout; local edits change it at the same commit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tree: Option<String>,
+}
+
+/// A dependency that is not a git checkout, such as an extracted archive
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct VendoredPackage {
+    pub name: String,
+    /// SHA-256 over the paths and contents of its files, see `content_hash`
+    pub content: String,
+}
+
+fn default_lock_version() -> u32 {
+    1
+}
+
+impl Lockfile {
+    /// Load project.lock, returning `None` when it does not exist
+    pub fn load() -> io::Result<Option<Lockfile>> {
+        if !Path::new(LOCKFILE_PATH).exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(LOCKFILE_PATH)?;
+        toml::from_str(&content)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        if is_enforced() {
+            return Ok(());
+        }
+        self.version = default_lock_version();
+        self.packages.sort_by(|a, b| a.name.cmp(&b.name));
+        self.vendored.sort_by(|a, b| a.name.cmp(&b.name));
+        let body = toml::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        safe_path::write(LOCKFILE_PATH, format!("{}{}", HEADER, body))
+    }
+
+    /// Insert or replace the entry for `package.name`
+    pub fn upsert(&mut self, package: LockedPackage) {
+        match self.packages.iter_mut().find(|p| p.name == package.name) {
+            Some(existing) => *existing = package,
+            None => self.packages.push(package),
+        }
+    }
+
+    /// Drop entries that are no longer declared in the manifest
+    pub fn retain_declared(&mut self, declared: &toml::Table) {
+        self.packages.retain(|p| declared.contains_key(&p.name));
+        self.vendored.retain(|p| declared.contains_key(&p.name));
+    }
+}
+
+/// A dependency whose checkout no longer matches project.lock
+pub struct Drift {
+    pub name: String,
+    pub locked: String,
+    pub actual: String,
+}
+
+/// The locked commit for a dependency, as long as the lock entry was resolved
+/// from the same source the manifest declares now
+pub fn locked_commit(
+    name: &str,
+    git: &str,
+    tag: Option<&str>,
+    branch: Option<&str>,
+) -> Option<String> {
+    let lock = Lockfile::load().ok()??;
+    lock.packages
+        .into_iter()
+        .find(|p| {
+            p.name == name
+                && p.git == git
+                && p.tag.as_deref() == tag
+                && p.branch.as_deref() == branch
+        })
+        .map(|p| p.commit)
+}
+
+/// Why a git dependency has no usable entry in project.lock, naming the
+/// source project.toml declares and the one that was locked
+pub fn mismatch(name: &str, git: &str, tag: Option<&str>, branch: Option<&str>) -> String {
+    let lock = match Lockfile::load() {
+        Ok(Some(lock)) => lock,
+        Ok(None) => return format!("{} not found", LOCKFILE_PATH),
+        Err(e) => return format!("{} is unreadable: {}", LOCKFILE_PATH, e),
+    };
+    let Some(locked) = lock.packages.iter().find(|p| p.name == name) else {
+        return format!("{}: not in {}", name, LOCKFILE_PATH);
+    };
+    let at = format!("(commit {})", crate::short_hash(&locked.commit));
+    let describe = |kind: &str, value: Option<&str>| match value {
+        Some(value) => format!("{} {}", kind, value),
+        None => format!("no {}", kind),
+    };
+    if locked.git != git {
+        format!(
+            "{}: project.toml declares {}, but {} locks {} {}",
+            name, git, LOCKFILE_PATH, locked.git, at
+        )
+    } else if locked.tag.as_deref() != tag {
+        format!(
+            "{}: project.toml declares {}, but {} locks {} {}",
+            name,
+            describe("tag", tag),
+            LOCKFILE_PATH,
+            describe("tag", locked.tag.as_deref()),
+            at
+        )
+    } else if locked.branch.as_deref() != branch {
+        format!(
+            "{}: project.toml declares {}, but {} locks {} {}",
+            name,
+            describe("branch", branch),
+            LOCKFILE_PATH,
+            describe("branch", locked.branch.as_deref()),
+            at
+        )
+    } else {
+        format!("{}: {} entry does not match project.toml", name, LOCKFILE_PATH)
+    }
+}
+
+/// Compare each locked dependency's on-disk HEAD with its locked commit.
+/// Dependencies that are not checked out are not reported.
+pub fn detect_drift() -> Vec<Drift> {
+    let Ok(Some(lock)) = Lockfile::load() else {
+        return Vec::new();
+    };
+    lock.packages
+        .into_iter()
+        .filter_map(|p| {
+            let actual = git_head::read_head(&Path::new("external").join(&p.name))?;
+            (actual != p.commit).then_some(Drift {
+                name: p.name,
+                locked: p.commit,
+                actual,
+            })
+        })
+        .collect()
+}
+
+/// Rewrite project.lock to match whatever is checked out in external/
+pub fn accept_local() -> io::Result<Vec<Drift>> {
+    let Some(mut lock) = Lockfile::load()? else {
+        return Ok(Vec::new());
+    };
+    let drift = detect_drift();
+    for d in &drift {
+        if let Some(package) = lock.packages.iter_mut().find(|p| p.name == d.name) {
+            package.commit = d.actual.clone();
+        }
+    }
+    if !drift.is_empty() {
+        lock.save()?;
+    }
+    Ok(drift)
+}
+
+/// Resolve the commit currently checked out in a dependency directory
+pub fn head_commit(dir: impl AsRef<Path>) -> Option<String> {
+    let mut git = Command::new("git");
+    git.arg("-C").arg(dir.as_ref()).args(["rev-parse", "HEAD"]);
+    log::command(&git);
+    let output = git.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Record the freshly cloned state of `external/<name>` in project.lock
+pub fn record(name: &str, git: &str, tag: Option<&str>, branch: Option<&str>) {
+    if is_enforced() {
+        return;
+    }
+    // Parallel syncs record as they finish; one read-modify-write at a time
+    let _guard = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    let dir = format!("external/{}", name);
+    let Some(commit) = head_commit(&dir) else {
+        log::problem!("Warning: could not resolve commit for {}, project.lock not updated", name);
+        return;
+    };
+
+    let mut lock = load_for_update();
+    // The commit's own tree, unless changes accepted at this commit are
+    // still there
+    let committed = committed_tree(Path::new(&dir));
+    let accepted = lock
+        .packages
+        .iter()
+        .find(|p| p.name == name && p.commit == commit)
+        .and_then(|p| p.tree.clone())
+        .filter(|tree| Some(tree) != committed.as_ref())
+        .filter(|tree| working_tree(Path::new(&dir)).as_ref() == Some(tree));
+    let tree = accepted.or(committed);
+    lock.upsert(LockedPackage {
+        name: name.to_string(),
+        git: git.to_string(),
+        tag: tag.map(str::to_string),
+        branch: branch.map(str::to_string),
+        commit,
+        tree,
+    });
+    if let Err(e) = lock.save() {
+        log::problem!("Warning: failed to write {}: {}", LOCKFILE_PATH, e);
+    }
+}
+
+/// Record the files freshly extracted into `external/<name>` in project.lock
+pub fn record_vendored(name: &str) {
+    if is_enforced() {
+        return;
+    }
+    let _guard = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(content) = content_hash(&Path::new("external").join(name)) else {
+        log::problem!(
+            "Warning: could not hash external/{}, project.lock not updated",
+            name
+        );
+        return;
+    };
+    let mut lock = load_for_update();
+    upsert_vendored(&mut lock, name, content);
+    if let Err(e) = lock.save() {
+        log::problem!("Warning: failed to write {}: {}", LOCKFILE_PATH, e);
+    }
+}
+
+fn upsert_vendored(lock: &mut Lockfile, name: &str, content: String) {
+    match lock.vendored.iter_mut().find(|p| p.name == name) {
+        Some(existing) => existing.content = content,
+        None => lock.vendored.push(VendoredPackage {
+            name: name.to_string(),
+            content,
+        }),
+    }
+}
+
+fn load_for_update() -> Lockfile {
+    match Lockfile::load() {
+        Ok(lock) => lock.unwrap_or_default(),
+        Err(e) => {
+            log::problem!("Warning: ignoring unreadable {}: {}", LOCKFILE_PATH, e);
+            Lockfile::default()
+        }
+    }
+}
+
+/// A dependency whose files no longer match what project.lock recorded
+pub struct Modified {
+    pub name: String,
+    pub locked: String,
+    pub actual: String,
+}
+
+/// Compare the files of each checked-out dependency named in `declared`
+/// with the tree or content hash project.lock recorded for it. Entries
+/// without one, from before c1 recorded them, are not checked, and neither
+/// are dependencies whose commit already differs from the lock.
+pub fn detect_modified(declared: &toml::Table) -> Vec<Modified> {
+    let Ok(Some(lock)) = Lockfile::load() else {
+        return Vec::new();
+    };
+    let mut modified = Vec::new();
+    for package in lock
+        .packages
+        .iter()
+        .filter(|p| declared.contains_key(&p.name))
+    {
+        let dir = Path::new("external").join(&package.name);
+        let Some(locked) = &package.tree else {
+            continue;
+        };
+        if git_head::read_head(&dir).as_deref() != Some(package.commit.as_str()) {
+            continue;
+        }
+        if let Some(actual) = working_tree(&dir)
+            && actual != *locked
+        {
+            modified.push(Modified {
+                name: package.name.clone(),
+                locked: locked.clone(),
+                actual,
+            });
+        }
+    }
+    for package in lock
+        .vendored
+        .iter()
+        .filter(|p| declared.contains_key(&p.name))
+    {
+        let dir = Path::new("external").join(&package.name);
+        if let Some(actual) = content_hash(&dir)
+            && actual != package.content
+        {
+            modified.push(Modified {
+                name: package.name.clone(),
+                locked: package.content.clone(),
+                actual,
+            });
+        }
+    }
+    modified
+}
+
+/// `c1 sync --accept-changes`: lock external/ as it is, the commits checked
+/// out and the files in them. Returns what changed in project.lock.
+pub fn accept_changes(declared: &toml::Table) -> io::Result<(Vec<Drift>, Vec<Modified>)> {
+    let Some(mut lock) = Lockfile::load()? else {
+        return Ok((Vec::new(), Vec::new()));
+    };
+    let drift = detect_drift();
+    let mut modified = Vec::new();
+    for package in &mut lock.packages {
+        let dir = Path::new("external").join(&package.name);
+        if let Some(d) = drift.iter().find(|d| d.name == package.name) {
+            package.commit = d.actual.clone();
+        }
+        let Some(actual) = working_tree(&dir) else {
+            continue;
+        };
+        if package.tree.as_ref() != Some(&actual) {
+            modified.push(Modified {
+                name: package.name.clone(),
+                locked: package.tree.clone().unwrap_or_default(),
+                actual: actual.clone(),
+            });
+            package.tree = Some(actual);
+        }
+    }
+    for (name, value) in declared {
+        let archive = value.as_table().is_some_and(|t| t.contains_key("url"));
+        let Some(actual) = archive
+            .then(|| content_hash(&Path::new("external").join(name)))
+            .flatten()
+        else {
+            continue;
+        };
+        let locked = lock.vendored.iter().find(|p| p.name == *name);
+        if locked.map(|p| &p.content) != Some(&actual) {
+            modified.push(Modified {
+                name: name.clone(),
+                locked: locked.map(|p| p.content.clone()).unwrap_or_default(),
+                actual: actual.clone(),
+            });
+            upsert_vendored(&mut lock, name, actual);
+        }
+    }
+    if !drift.is_empty() || !modified.is_empty() {
+        lock.save()?;
+    }
+    Ok((drift, modified))
+}
+
+/// The tree of the commit checked out in `dir`
+fn committed_tree(dir: &Path) -> Option<String> {
+    let mut git = Command::new("git");
+    git.arg("-C").arg(dir).args(["rev-parse", "HEAD^{tree}"]);
+    log::command(&git);
+    let output = git.output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The git tree of what is in `dir` now, uncommitted edits included; the
+/// commit's tree when nothing was changed. Files the checkout ignores are
+/// left out. A copy of the checkout's index is used, so git only hashes the
+/// files that changed and the checkout itself is left alone.
+pub fn working_tree(dir: &Path) -> Option<String> {
+    let git = |args: &[&str], index: Option<&Path>| {
+        let mut git = Command::new("git");
+        git.arg("-C").arg(dir).args(args);
+        if let Some(index) = index {
+            git.env("GIT_INDEX_FILE", index);
+        }
+        log::command(&git);
+        let output = git.output().ok()?;
+        output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    };
+    let index = PathBuf::from(git(&["rev-parse", "--git-path", "index"], None)?);
+    let index = if index.is_absolute() {
+        index
+    } else {
+        dir.join(index)
+    };
+    let scratch = std::env::temp_dir().join(format!(
+        "c1-index-{}-{}",
+        dir.file_name()?.to_string_lossy(),
+        std::process::id()
+    ));
+    let scratch = std::path::absolute(&scratch).unwrap_or(scratch);
+    if fs::copy(&index, &scratch).is_err() {
+        // A checkout without an index yet starts from the commit
+        git(&["read-tree", "HEAD"], Some(&scratch))?;
+    }
+    let tree =
+        git(&["add", "--all"], Some(&scratch)).and_then(|_| git(&["write-tree"], Some(&scratch)));
+    let _ = fs::remove_file(&scratch);
+    tree
+}
+
+/// SHA-256 over the relative path and content of every file under `dir`,
+/// in sorted order, for dependencies that are not git checkouts
+pub fn content_hash(dir: &Path) -> Option<String> {
+    if !dir.is_dir() {
+        return None;
+    }
+    let mut files = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        for entry in fs::read_dir(&current).ok()?.flatten() {
+            let path = entry.path();
+            let kind = entry.file_type().ok()?;
+            if kind.is_dir() {
+                if entry.file_name() != ".git" {
+                    stack.push(path);
+                }
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    let mut hasher = Sha256::new();
+    for file in files {
+        let relative = file
+            .strip_prefix(dir)
+            .ok()?
+            .to_string_lossy()
+            .replace('\\', "/");
+        let content = if file.is_symlink() {
+            fs::read_link(&file)
+                .ok()?
+                .to_string_lossy()
+                .into_owned()
+                .into_bytes()
+        } else {
+            fs::read(&file).ok()?
+        };
+        hasher.update(relative.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(content.len().to_string().as_bytes());
+        hasher.update(&[0]);
+        hasher.update(&content);
+    }
+    Some(hasher.finish_hex())
+}
@@ -0,0 +1,195 @@
+//! How much c1 says: `--quiet`, the default, or `--verbose`.
+//!
+//! Progress and status lines go through `status!`, which `--quiet` silences,
+//! and output of the cmake and git processes c1 runs is held back with it;
+//! errors, warnings and the artifact path `c1 build` ends with still print.
+//! Errors, warnings and failed steps go through `problem!`, always to stderr.
+//! `--verbose` adds `verbose!` lines on stderr: the exact command lines run,
+//! the resolved build directory and the output of commands c1 otherwise only
+//! captures. `c1 build --show-commands` shows the command lines alone, each
+//! with its directory; they also go to the build log while a build runs.
+//! What a command exists to show (`c1 list`, `c1 info`, the program `c1 run`
+//! starts) prints at every level.
+//!
+//! Work running on a worker thread can `capture` its lines and print them in
+//! one piece when done, so the output of parallel jobs does not interleave.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+use crate::{build_log, secrets, style};
+
+const QUIET: u8 = 0;
+const NORMAL: u8 = 1;
+const VERBOSE: u8 = 2;
+
+static LEVEL: AtomicU8 = AtomicU8::new(NORMAL);
+static SHOW_COMMANDS: AtomicBool = AtomicBool::new(false);
+
+/// Passes the level on to c1 processes started for workspace members
+const ENV_VAR: &str = "C1_LOG";
+/// Passes `--show-commands` on the same way
+const SHOW_COMMANDS_VAR: &str = "C1_SHOW_COMMANDS";
+
+pub fn set(quiet: bool, verbose: bool) {
+    let inherited = std::env::var(ENV_VAR).unwrap_or_default();
+    let level = if quiet || (!verbose && inherited == "quiet") {
+        QUIET
+    } else if verbose || inherited == "verbose" {
+        VERBOSE
+    } else {
+        NORMAL
+    };
+    LEVEL.store(level, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    LEVEL.load(Ordering::Relaxed) == QUIET
+}
+
+pub fn is_verbose() -> bool {
+    LEVEL.load(Ordering::Relaxed) == VERBOSE
+}
+
+/// Show every command line run, with its directory, from `--show-commands`
+pub fn show_commands(show: bool) {
+    let inherited = std::env::var_os(SHOW_COMMANDS_VAR).is_some();
+    SHOW_COMMANDS.store(show || inherited, Ordering::Relaxed);
+}
+
+fn is_showing_commands() -> bool {
+    SHOW_COMMANDS.load(Ordering::Relaxed)
+}
+
+/// Run a child c1 at the same level, and with the same colors, as this one
+pub fn pass_to(command: &mut Command) {
+    match LEVEL.load(Ordering::Relaxed) {
+        QUIET => command.env(ENV_VAR, "quiet"),
+        VERBOSE => command.env(ENV_VAR, "verbose"),
+        _ => command.env_remove(ENV_VAR),
+    };
+    if is_showing_commands() {
+        command.env(SHOW_COMMANDS_VAR, "1");
+    }
+    style::pass_to(command);
+}
+
+/// Show the command line about to run, with credentials in it redacted,
+/// and record it in the build log
+pub fn command(command: &Command) {
+    let mut line = quote(&command.get_program().to_string_lossy());
+    for arg in command.get_args() {
+        line.push(' ');
+        line.push_str(&quote(&arg.to_string_lossy()));
+    }
+    let line = secrets::redact(&line);
+    let dir = match command.get_current_dir() {
+        Some(dir) => std::path::absolute(dir).unwrap_or_else(|_| dir.to_path_buf()),
+        None => std::env::current_dir().unwrap_or_default(),
+    };
+    let dir = quote(&dir.to_string_lossy());
+    build_log::command(&dir, &line);
+    if is_showing_commands() {
+        print(true, format_args!("$ (cd {}) {}", dir, line));
+    } else if is_verbose() {
+        match command.get_current_dir() {
+            Some(dir) => print(true, format_args!("$ (cd {}) {}", dir.display(), line)),
+            None => print(true, format_args!("$ {}", line)),
+        }
+    }
+}
+
+fn quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_=./:,+@%^{}".contains(c))
+    {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+thread_local! {
+    /// Lines held back by `capture`, each marked with whether it is for stderr
+    static CAPTURED: RefCell<Option<Vec<(bool, String)>>> = const { RefCell::new(None) };
+}
+
+/// Lines `capture` held back
+pub struct Captured(Vec<(bool, String)>);
+
+impl Captured {
+    pub fn print(&self) {
+        for (stderr, line) in &self.0 {
+            if *stderr {
+                eprintln!("{}", style::line(true, line));
+            } else {
+                println!("{}", style::line(false, line));
+            }
+        }
+    }
+}
+
+/// Run `f` with the status and verbose lines it prints on this thread held
+/// back, and return them with its result
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Captured) {
+    CAPTURED.with(|captured| *captured.borrow_mut() = Some(Vec::new()));
+    let result = f();
+    let lines = CAPTURED.with(|captured| captured.borrow_mut().take().unwrap_or_default());
+    (result, Captured(lines))
+}
+
+/// Whether this thread's lines are being held back
+pub fn is_capturing() -> bool {
+    CAPTURED.with(|captured| captured.borrow().is_some())
+}
+
+/// Print a line, on stderr or stdout, unless it is being captured
+pub fn print(stderr: bool, line: fmt::Arguments) {
+    let held = CAPTURED.with(|captured| match captured.borrow_mut().as_mut() {
+        Some(lines) => {
+            lines.push((stderr, line.to_string()));
+            true
+        }
+        None => false,
+    });
+    if held {
+        return;
+    }
+    let line = style::line(stderr, &line.to_string());
+    if stderr {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+/// A progress or status line, silenced by `--quiet`
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !$crate::log::is_quiet() {
+            $crate::log::print(false, format_args!($($arg)*));
+        }
+    };
+}
+
+/// A detail shown on stderr only with `--verbose`
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::log::is_verbose() {
+            $crate::log::print(true, format_args!($($arg)*));
+        }
+    };
+}
+
+/// An error, warning or failed step, on stderr at every level
+macro_rules! problem {
+    ($($arg:tt)*) => {
+        $crate::log::print(true, format_args!($($arg)*))
+    };
+}
+
+pub(crate) use {problem, status, verbose};
@@ -1,14 +1,168 @@
+mod adopt;
+mod archive;
+mod audit;
+mod auto_sources;
+mod bench;
+mod bins;
+mod bootstrap;
+mod build_history;
+mod build_log;
+mod build_stats;
+mod check;
+mod clean;
+mod clone_cache;
+mod cmake_lists;
+mod compiler_cache;
+mod coverage;
+mod cross;
+mod dep_check;
+mod dep_wiring;
+mod diagnostics;
+mod depfile;
+mod doc;
+mod emit;
+mod error;
+mod events;
+mod examples;
+mod expand;
+mod exports;
+mod features;
+mod fmt;
+mod generate;
+mod git_head;
+mod graph;
+mod header_deps;
+mod http;
+mod ignore;
+mod include_dirs;
+mod index;
+mod info;
+mod install;
+mod interactive;
+mod interrupt;
+mod json;
+mod junit;
+mod lib_install;
+mod license;
+mod links;
+mod lint;
+mod list;
+mod lockfile;
+mod log;
+mod manifest_upgrade;
+mod matrix;
+mod missing_exe;
+mod module_kinds;
+mod outdated;
+mod package;
+mod pkg_config;
+mod platforms;
+mod profiles;
+mod progress;
+mod project_lock;
+mod prune;
+mod remote;
+mod remote_template;
+mod rename;
+mod rename_module;
+mod root;
+mod run_env;
+mod safe_path;
+mod sanitize;
+mod scaffold;
+mod secrets;
+mod sha256;
+mod size;
+mod source_check;
+mod stream;
+mod style;
+mod subdir;
+mod submodule;
+mod sync_journal;
+mod templates;
+#[cfg(test)]
+mod test_support;
+mod testing;
+mod toolchain_check;
+mod transitive;
+mod tree;
+mod update_verify;
+mod url_rewrite;
+mod user_config;
+mod valgrind;
+mod variants;
+mod vendor;
+mod version;
+mod version_header;
+mod warnings;
+mod watch;
+mod workspace;
+
 use clap::{Parser, Subcommand};
+use error::{Context, Error};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
 use std::fs;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
 #[command(name = "c1")]
 #[command(about = "A modern C project scaffolding and package management tool, like cargo for C")]
 struct Cli {
+    /// Allow destructive operations on paths outside the project root (asks for confirmation)
+    #[arg(long, global = true)]
+    allow_outside_project: bool,
+
+    /// Ignore cached upstream query results
+    #[arg(long, global = true)]
+    refresh: bool,
+
+    /// Never touch the network; use only external/ and the cache, or fail
+    /// (also set by C1_OFFLINE=1)
+    #[arg(long, global = true, conflicts_with = "refresh")]
+    offline: bool,
+
+    /// Show the cmake and git command lines run, the build directory and
+    /// full subprocess output
+    #[arg(short, long, global = true, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Print only errors and the path of the built artifact
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Fail instead of waiting when another c1 is building, syncing or
+    /// cleaning the project
+    #[arg(long, global = true)]
+    no_wait: bool,
+
+    /// Output format: human-readable text, or newline-delimited JSON events
+    #[arg(
+        long,
+        global = true,
+        value_name = "FORMAT",
+        default_value = "human",
+        value_parser = ["human", "json"]
+    )]
+    message_format: String,
+
+    /// Color the marks of c1's output: auto (on a terminal without NO_COLOR),
+    /// always or never
+    #[arg(
+        long,
+        global = true,
+        value_name = "WHEN",
+        default_value = "auto",
+        value_parser = ["auto", "always", "never"]
+    )]
+    color: String,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -18,47 +172,880 @@ enum Commands {
     /// Create a new C project in a new directory
     New {
         /// Project name (will create a directory with this name)
-        name: String,
+        #[arg(required_unless_present_any = ["list_templates", "interactive"])]
+        name: Option<String>,
+        /// Template to generate the project from
+        #[arg(long, default_value = templates::DEFAULT_TEMPLATE)]
+        template: String,
+        /// Create a library, short for --template lib
+        #[arg(long, conflicts_with = "template")]
+        lib: bool,
+        /// List the built-in templates
+        #[arg(long)]
+        list_templates: bool,
+        /// C standard the project is written against [default: c99]
+        #[arg(long, value_parser = EDITIONS.to_vec())]
+        edition: Option<String>,
+        /// Version control to initialize (skipped inside an existing git repository) [default: git]
+        #[arg(long, value_parser = ["git", "none"])]
+        vcs: Option<String>,
+        /// Write a LICENSE file: MIT, Apache-2.0, BSD-3-Clause, GPL-3.0 or none
+        #[arg(long, value_name = "ID")]
+        license: Option<String>,
+        /// Where main.c goes: the project root (flat) or src/ [default: flat]
+        #[arg(long, value_parser = ["flat", "src"])]
+        layout: Option<String>,
+        /// One-line description for project.toml and README.md
+        #[arg(long, value_name = "TEXT")]
+        description: Option<String>,
+        /// Author as `Name <email>`; repeat for several [default: git's user.name and user.email]
+        #[arg(long = "author", value_name = "AUTHOR")]
+        authors: Vec<String>,
+        /// URL of the project's source repository
+        #[arg(long, value_name = "URL")]
+        repository: Option<String>,
+        /// Commit dependencies in external/ instead of adding it to .gitignore
+        #[arg(long)]
+        vendor_deps: bool,
+        /// Ask for the name, description, edition, compiler, license and git on the terminal
+        #[arg(short, long, conflicts_with = "dry_run")]
+        interactive: bool,
+        /// Print the files and directories that would be created without creating them
+        #[arg(long, conflicts_with = "list_templates")]
+        dry_run: bool,
     },
     /// Initialize a new C project in the current directory
-    Init,
+    Init {
+        /// Version control to initialize (skipped inside an existing git repository) [default: git]
+        #[arg(long, value_parser = ["git", "none"])]
+        vcs: Option<String>,
+        /// Write a LICENSE file: MIT, Apache-2.0, BSD-3-Clause, GPL-3.0 or none
+        #[arg(long, value_name = "ID")]
+        license: Option<String>,
+        /// Adopt existing code: keep present files and register the .c/.h files found
+        #[arg(long)]
+        existing: bool,
+        /// Print what would be created (with --existing, also registered) without changing
+        /// anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Project name to use instead of the directory name
+        #[arg(long)]
+        name: Option<String>,
+        /// C standard the project is written against [default: c99]
+        #[arg(long, value_parser = EDITIONS.to_vec())]
+        edition: Option<String>,
+        /// Where main.c goes: the project root (flat) or src/ [default: flat, or src when
+        /// only src/main.c exists]
+        #[arg(long, value_parser = ["flat", "src"])]
+        layout: Option<String>,
+        /// One-line description for project.toml and README.md
+        #[arg(long, value_name = "TEXT")]
+        description: Option<String>,
+        /// Author as `Name <email>`; repeat for several [default: git's user.name and user.email]
+        #[arg(long = "author", value_name = "AUTHOR")]
+        authors: Vec<String>,
+        /// URL of the project's source repository
+        #[arg(long, value_name = "URL")]
+        repository: Option<String>,
+        /// Commit dependencies in external/ instead of adding it to .gitignore
+        #[arg(long)]
+        vendor_deps: bool,
+        /// Ask for the name, description, edition, compiler, license and git on the terminal
+        #[arg(short, long, conflicts_with = "dry_run")]
+        interactive: bool,
+    },
     /// Create a new module (generates .c and .h files)
     Create {
+        /// Module name
+        #[arg(required_unless_present = "list_kinds")]
+        name: Option<String>,
+        /// The shape of module to generate (see --list-kinds)
+        #[arg(
+            long,
+            default_value = "plain",
+            value_parser = clap::builder::PossibleValuesParser::new(module_kinds::NAMES),
+            conflicts_with_all = ["platforms", "add_platform", "header_only"]
+        )]
+        kind: String,
+        /// List the kinds of module `--kind` generates
+        #[arg(long, exclusive = true)]
+        list_kinds: bool,
+        /// Generate one implementation per platform (e.g. posix,stm32)
+        #[arg(long, value_delimiter = ',', conflicts_with = "add_platform")]
+        platforms: Vec<String>,
+        /// Add an implementation for another platform to an existing module
+        #[arg(long)]
+        add_platform: Option<String>,
+        /// Also generate tests/test_<name>.c and register it as a test
+        #[arg(long, conflicts_with = "add_platform")]
+        with_test: bool,
+        /// Put doxygen comment stubs in the new header
+        #[arg(long, conflicts_with = "add_platform")]
+        doc_comments: bool,
+        /// Generate only include/<name>.h, for modules of static inline functions
+        #[arg(long, conflicts_with_all = ["platforms", "add_platform"])]
+        header_only: bool,
+        /// Leave CMakeLists.txt alone; add the new files to it yourself
+        #[arg(long, conflicts_with_all = ["platforms", "add_platform"])]
+        no_cmake: bool,
+    },
+    /// Remove a module created by `c1 create`
+    Destroy {
         /// Module name
         name: String,
+        /// Remove the module even if other sources still include its header
+        #[arg(long)]
+        force: bool,
+    },
+    /// Rename a module created by `c1 create`, updating CMakeLists.txt and includes
+    Rename {
+        /// Current module name
+        old: String,
+        /// New module name
+        new: String,
     },
     /// Build and run the project
-    Run,
+    Run {
+        /// Build and run in release mode
+        #[arg(long)]
+        release: bool,
+        /// Use the `[profile.<name>]` settings from project.toml
+        #[arg(long, value_name = "NAME", conflicts_with = "release")]
+        profile: Option<String>,
+        /// Number of parallel compile jobs (defaults to the number of CPUs)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Build directory (overrides `[build] out_dir`, default `build`)
+        #[arg(long, value_name = "PATH")]
+        out_dir: Option<String>,
+        /// Build and run for the `[target.<name>]` table in project.toml
+        #[arg(long = "target", value_name = "NAME")]
+        target_config: Option<String>,
+        /// Features to enable in addition to the defaults
+        #[arg(long, value_delimiter = ',', value_name = "FEATURES")]
+        features: Vec<String>,
+        /// Do not enable the features listed in `[features] default`
+        #[arg(long)]
+        no_default_features: bool,
+        /// Instrument with sanitizers: address, undefined, thread, leak
+        #[arg(
+            long,
+            value_delimiter = ',',
+            value_name = "SANITIZERS",
+            value_parser = sanitize::value_parser()
+        )]
+        sanitize: Vec<String>,
+        /// Run the program under valgrind's memcheck and fail on the errors it finds
+        #[arg(long, conflicts_with = "sanitize")]
+        valgrind: bool,
+        /// Define a preprocessor macro, in addition to `[build] defines`
+        #[arg(short = 'D', long = "define", value_name = "KEY[=VALUE]")]
+        defines: Vec<String>,
+        /// Set an environment variable for the program, overriding `[env]`
+        #[arg(long = "env", value_name = "KEY=VALUE")]
+        env: Vec<String>,
+        /// Workspace member to run
+        #[arg(short, long)]
+        package: Option<String>,
+        /// Build even if external/ does not match project.toml and project.lock
+        #[arg(long)]
+        no_verify_deps: bool,
+        /// Which executable to run, when [[bin]] declares several
+        #[arg(long)]
+        bin: Option<String>,
+        /// Run examples/<NAME>.c instead (lists the examples without a name)
+        #[arg(
+            long,
+            value_name = "NAME",
+            num_args = 0..=1,
+            default_missing_value = "",
+            conflicts_with = "bin"
+        )]
+        example: Option<String>,
+        /// When the expected executable was not built but one other was, run that one
+        #[arg(long, conflicts_with = "example")]
+        guess: bool,
+        /// Build and run the `[variant.<name>]` board variant
+        #[arg(long, value_name = "NAME")]
+        variant: Option<String>,
+        /// Arguments for the program, after `--`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
     /// Build the project
     Build {
         /// Build in release mode
         #[arg(long)]
         release: bool,
+        /// Use the `[profile.<name>]` settings from project.toml
+        #[arg(long, value_name = "NAME", conflicts_with = "release")]
+        profile: Option<String>,
+        /// Number of parallel compile jobs (defaults to the number of CPUs)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Build directory (overrides `[build] out_dir`, default `build`)
+        #[arg(long, value_name = "PATH")]
+        out_dir: Option<String>,
+        /// CMake generator, e.g. Ninja (overrides `[build] generator`)
+        #[arg(short = 'G', long)]
+        generator: Option<String>,
+        /// C compiler, e.g. clang (overrides `[build] compiler` and `CC`)
+        #[arg(long, value_name = "COMPILER")]
+        compiler: Option<String>,
+        /// Also write these artifacts: bin, hex, map (overrides `[build] emit`)
+        #[arg(
+            long,
+            value_delimiter = ',',
+            value_name = "KINDS",
+            value_parser = emit::value_parser()
+        )]
+        emit: Vec<String>,
+        /// Define a preprocessor macro, in addition to `[build] defines`
+        #[arg(short = 'D', long = "define", value_name = "KEY[=VALUE]")]
+        defines: Vec<String>,
+        /// Show which files were recompiled and why
+        #[arg(long)]
+        timings: bool,
+        /// Build for the `[target.<name>]` table in project.toml; repeat to build several
+        #[arg(long = "target", value_name = "NAME", alias = "target-config")]
+        target_config: Vec<String>,
+        /// Build dev, release and every `[profile.<name>]`
+        #[arg(long, conflicts_with_all = ["release", "profile"])]
+        all_profiles: bool,
+        /// Build for the host and every `[target.<name>]`
+        #[arg(long, conflicts_with = "target_config")]
+        all_targets: bool,
+        /// Build the `[variant.<name>]` board variant from project.toml
+        #[arg(long, value_name = "NAME")]
+        variant: Option<String>,
+        /// Build every `[variant.<name>]` and summarize their sizes
+        #[arg(long, conflicts_with_all = ["variant", "all_profiles", "all_targets"])]
+        all_variants: bool,
+        /// With several profiles, targets or variants, stop at the first that fails to build
+        #[arg(long)]
+        fail_fast: bool,
+        /// Features to enable in addition to the defaults
+        #[arg(long, value_delimiter = ',', value_name = "FEATURES")]
+        features: Vec<String>,
+        /// Do not enable the features listed in `[features] default`
+        #[arg(long)]
+        no_default_features: bool,
+        /// Instrument with sanitizers: address, undefined, thread, leak
+        #[arg(
+            long,
+            value_delimiter = ',',
+            value_name = "SANITIZERS",
+            value_parser = sanitize::value_parser()
+        )]
+        sanitize: Vec<String>,
+        /// Workspace member to build (all members when omitted)
+        #[arg(short, long)]
+        package: Option<String>,
+        /// Keep building the remaining workspace members after a failure
+        #[arg(long)]
+        keep_going: bool,
+        /// Run the cmake configure step even if nothing changed since the last one
+        /// (with --configure-only, from an empty CMake cache)
+        #[arg(long)]
+        reconfigure: bool,
+        /// Only configure, e.g. to refresh compile_commands.json for an editor
+        #[arg(long, conflicts_with_all = ["emit", "timings", "examples"])]
+        configure_only: bool,
+        /// Build the programs in examples/ instead of the default targets
+        #[arg(long)]
+        examples: bool,
+        /// Build even if external/ does not match project.toml and project.lock
+        #[arg(long)]
+        no_verify_deps: bool,
+        /// Build without the compiler cache `[build] cache` names
+        #[arg(long)]
+        no_cache: bool,
+        /// Remove duplicate and missing files from the SOURCES and HEADERS lists
+        #[arg(long)]
+        fix_sources: bool,
+        /// Print every external command with its directory before running it
+        #[arg(long)]
+        show_commands: bool,
+        /// Run the commands of the last build in build/c1-build.log again, as recorded
+        #[arg(
+            long,
+            conflicts_with_all = [
+                "release", "profile", "target_config", "all_profiles", "all_targets",
+                "variant", "all_variants", "configure_only", "reconfigure"
+            ]
+        )]
+        replay_last: bool,
+    },
+    /// Compile every source with -fsyntax-only, skipping cmake and linking
+    Check {
+        /// Check with the release profile's flags
+        #[arg(long)]
+        release: bool,
+        /// Use the `[profile.<name>]` settings from project.toml
+        #[arg(long, value_name = "NAME", conflicts_with = "release")]
+        profile: Option<String>,
+        /// Check for the `[target.<name>]` table in project.toml
+        #[arg(long = "target", value_name = "NAME")]
+        target_config: Option<String>,
+        /// Features to enable in addition to the defaults
+        #[arg(long, value_delimiter = ',', value_name = "FEATURES")]
+        features: Vec<String>,
+        /// Do not enable the features listed in `[features] default`
+        #[arg(long)]
+        no_default_features: bool,
+        /// Define a preprocessor macro, in addition to `[build] defines`
+        #[arg(short = 'D', long = "define", value_name = "KEY[=VALUE]")]
+        defines: Vec<String>,
+        /// C compiler, e.g. clang (overrides `[build] compiler` and `CC`)
+        #[arg(long, value_name = "COMPILER")]
+        compiler: Option<String>,
+        /// Number of files to compile at once (defaults to the number of CPUs)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Only check the sources that changed since the last check, or include a header
+        /// that did
+        #[arg(long)]
+        changed: bool,
+    },
+    /// Print a source file after the preprocessor, as the build compiles it
+    Expand {
+        /// The .c file to preprocess
+        file: String,
+        /// Write the result to this file instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+        /// Format the result with clang-format
+        #[arg(long)]
+        pretty: bool,
+        /// Expand with the release profile's flags
+        #[arg(long)]
+        release: bool,
+        /// Use the `[profile.<name>]` settings from project.toml
+        #[arg(long, value_name = "NAME", conflicts_with = "release")]
+        profile: Option<String>,
+        /// Expand for the `[target.<name>]` table in project.toml
+        #[arg(long = "target", value_name = "NAME")]
+        target_config: Option<String>,
+        /// Features to enable in addition to the defaults
+        #[arg(long, value_delimiter = ',', value_name = "FEATURES")]
+        features: Vec<String>,
+        /// Do not enable the features listed in `[features] default`
+        #[arg(long)]
+        no_default_features: bool,
+        /// Define a preprocessor macro, in addition to `[build] defines`
+        #[arg(short = 'D', long = "define", value_name = "KEY[=VALUE]")]
+        defines: Vec<String>,
+    },
+    /// Build the programs in tests/ and run them
+    Test {
+        /// Only build and run tests whose names contain this
+        pattern: Option<String>,
+        /// List the tests (matching the pattern) without building them
+        #[arg(long, conflicts_with = "init")]
+        list: bool,
+        /// Build and test in release mode
+        #[arg(long)]
+        release: bool,
+        /// Use the `[profile.<name>]` settings from project.toml
+        #[arg(long, value_name = "NAME", conflicts_with = "release")]
+        profile: Option<String>,
+        /// Number of parallel compile jobs (defaults to the number of CPUs)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Instrument with sanitizers: address, undefined, thread, leak
+        #[arg(
+            long,
+            value_delimiter = ',',
+            value_name = "SANITIZERS",
+            value_parser = sanitize::value_parser()
+        )]
+        sanitize: Vec<String>,
+        /// Run each test program under valgrind's memcheck; errors fail the test
+        #[arg(long, conflicts_with_all = ["sanitize", "init", "list"])]
+        valgrind: bool,
+        /// Generate tests/support/c1_test.h and a sample test that uses it
+        #[arg(long)]
+        init: bool,
+        /// Measure line coverage and write an HTML report to build/coverage
+        #[arg(long, conflicts_with_all = ["init", "list"])]
+        coverage: bool,
+        /// With --coverage, fail when total line coverage is below this percentage
+        #[arg(long, value_name = "PCT", requires = "coverage")]
+        fail_under: Option<f64>,
+        /// Report format: the console summary only, or also JUnit XML written to --output
+        #[arg(
+            long,
+            default_value = "human",
+            value_parser = ["human", "junit"],
+            conflicts_with_all = ["init", "list"]
+        )]
+        format: String,
+        /// The file to write the JUnit report to
+        #[arg(short, long, value_name = "FILE", required_if_eq("format", "junit"))]
+        output: Option<String>,
     },
     /// Add a git dependency to the project
     Add {
-        /// Git repository URL
-        url: String,
+        /// Git repository URL, `user/repo` for a GitHub repository, or a name from
+        /// the package index
+        #[arg(required_unless_present_any = ["path", "pkg_config", "link"])]
+        url: Option<String>,
+        /// Use a local directory instead of a git repository
+        #[arg(long, conflicts_with_all = ["url", "tag", "branch", "rev", "full_history"])]
+        path: Option<String>,
+        /// Use a system library found by pkg-config instead of a git repository
+        #[arg(
+            long,
+            value_name = "PACKAGE",
+            conflicts_with_all = ["url", "path", "tag", "branch", "rev", "full_history", "submodule"]
+        )]
+        pkg_config: Option<String>,
+        /// Link a system library (added to `[build] links`), e.g. `m`
+        #[arg(
+            long,
+            value_name = "LIB",
+            conflicts_with_all = [
+                "url", "path", "pkg_config", "tag", "branch", "rev", "full_history", "submodule",
+                "name"
+            ]
+        )]
+        link: Option<String>,
         /// Optional: specify a tag
         #[arg(long)]
         tag: Option<String>,
         /// Optional: specify a branch
         #[arg(long)]
         branch: Option<String>,
+        /// Optional: pin an exact commit
+        #[arg(long, conflicts_with_all = ["tag", "branch", "submodule"])]
+        rev: Option<String>,
+        /// Clone the full history instead of a shallow `--depth 1` clone
+        #[arg(long)]
+        full_history: bool,
+        /// Track the dependency as a git submodule of the project repository
+        #[arg(long, conflicts_with_all = ["path", "full_history"])]
+        submodule: bool,
+        /// Let this entry win over conflicting requirements of other dependencies
+        #[arg(long)]
+        allow_override: bool,
+        /// Name for the project.toml key and external/ directory (default: from the URL or path)
+        #[arg(long)]
+        name: Option<String>,
+        /// The URL is a .tar.gz or .zip release archive rather than a git repository
+        #[arg(
+            long,
+            conflicts_with_all = ["path", "pkg_config", "link", "tag", "branch", "rev", "full_history", "submodule"]
+        )]
+        archive: bool,
+        /// Only add the entry to project.toml; `c1 sync` clones it later
+        #[arg(long, conflicts_with_all = ["path", "pkg_config", "link", "archive"])]
+        no_clone: bool,
+        /// Print the entry and the clone command without changing anything
+        #[arg(long, conflicts_with_all = ["path", "pkg_config", "link", "archive", "no_clone"])]
+        dry_run: bool,
+        /// Add to `[dev-dependencies]`: linked into tests and benches only
+        #[arg(long, conflicts_with = "link")]
+        dev: bool,
+        /// Delete and re-clone external/<name>, even with uncommitted changes in it
+        #[arg(long, conflicts_with_all = ["path", "pkg_config", "link", "archive", "no_clone"])]
+        force: bool,
+        /// Use only this subdirectory of the repository, checked out sparsely
+        #[arg(
+            long,
+            value_name = "PATH",
+            conflicts_with_all = ["path", "pkg_config", "link", "archive", "submodule"]
+        )]
+        subdir: Option<String>,
+    },
+    /// Search the package index for libraries to `c1 add` by name
+    Search {
+        /// Text to find in package names and descriptions
+        #[arg(required_unless_present = "update_index")]
+        query: Option<String>,
+        /// Fetch the package index again before searching
+        #[arg(long)]
+        update_index: bool,
     },
     /// Clean the build directory
-    Clean,
+    Clean {
+        /// Build directory (overrides `[build] out_dir`, default `build`)
+        #[arg(long, value_name = "PATH")]
+        out_dir: Option<String>,
+        /// Remove only the release build
+        #[arg(long, conflicts_with_all = ["profile", "deps"])]
+        release: bool,
+        /// Remove only the debug build
+        #[arg(long, conflicts_with_all = ["release", "profile", "deps"])]
+        debug: bool,
+        /// Remove only the build of this profile (`dev` for the debug build)
+        #[arg(long, value_name = "NAME", conflicts_with = "deps")]
+        profile: Option<String>,
+        /// Also remove external/ and the sync journal
+        #[arg(long, conflicts_with = "deps")]
+        all: bool,
+        /// Remove only external/ and the sync journal
+        #[arg(long)]
+        deps: bool,
+        /// Keep the dependencies' compiled objects, removing only the project's own
+        #[arg(long, conflicts_with_all = ["all", "deps"])]
+        keep_deps: bool,
+        /// Do not ask before removing external/
+        #[arg(long, short = 'y')]
+        yes: bool,
+        /// List what would be removed, with sizes, without removing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Also remove the `[generate]` outputs that live in the source tree
+        #[arg(long, conflicts_with = "deps")]
+        generated: bool,
+    },
     /// Sync dependencies from project.toml
-    Sync,
+    Sync {
+        /// Lock the commits currently checked out in external/ instead of restoring project.lock
+        #[arg(long)]
+        accept_local: bool,
+        /// Like --accept-local, also locking local edits to the dependencies' files
+        #[arg(
+            long,
+            conflicts_with_all = ["accept_local", "max_time", "force", "prune", "locked", "frozen"]
+        )]
+        accept_changes: bool,
+        /// Stop starting new clones after this many seconds and report what is left
+        #[arg(long, value_name = "SECS", conflicts_with = "accept_local")]
+        max_time: Option<u64>,
+        /// Delete and re-clone every dependency instead of updating existing clones
+        /// (with --prune, also delete leftovers with uncommitted changes)
+        #[arg(long, conflicts_with = "accept_local")]
+        force: bool,
+        /// Delete directories in external/ that no dependency declares any more
+        #[arg(long, conflicts_with = "accept_local")]
+        prune: bool,
+        /// Let project.toml win over conflicting requirements of dependencies
+        #[arg(long)]
+        allow_override: bool,
+        /// Fail instead of updating project.lock: every dependency must be
+        /// locked from its declared source and end up at its locked commit
+        #[arg(long, conflicts_with = "accept_local")]
+        locked: bool,
+        /// Like --locked, without network access: the locked commits must
+        /// already be in external/ or the clone cache
+        #[arg(long, conflicts_with = "accept_local")]
+        frozen: bool,
+        /// Dependencies to fetch at once
+        #[arg(short, long, default_value_t = DEFAULT_SYNC_JOBS, value_name = "N")]
+        jobs: usize,
+        /// Skip `[dev-dependencies]`, for building only the main artifact
+        #[arg(long, conflicts_with_all = ["accept_local", "accept_changes"])]
+        no_dev: bool,
+        /// Stop at the first dependency that fails instead of syncing the rest
+        #[arg(long, conflicts_with_all = ["accept_local", "accept_changes"])]
+        fail_fast: bool,
+    },
+    /// Update dependencies to the latest commit of their branch or newest tag
+    Update {
+        /// Dependency to update (all dependencies when omitted)
+        name: Option<String>,
+        /// Rebuild after each upgrade (`--verify=test` also runs ctest) and
+        /// roll back the ones that fail
+        #[arg(
+            long,
+            value_name = "CHECK",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "build",
+            value_parser = ["build", "test"]
+        )]
+        verify: Option<String>,
+        /// Bisect upstream commits of a held-back dependency to find the
+        /// first bad one
+        #[arg(long, requires = "verify")]
+        bisect: bool,
+    },
+    /// Export files for environments without c1
+    Export {
+        #[command(subcommand)]
+        target: ExportTarget,
+    },
+    /// Verify that generated files are up to date with project.lock
+    Verify,
+    /// Check the project for common problems
+    Doctor,
+    /// List dependencies with the commits checked out in external/
+    List,
+    /// Show the dependency tree, including dependencies of c1 dependencies
+    Tree {
+        /// Show at most this many levels below the root
+        #[arg(long)]
+        depth: Option<usize>,
+        /// Show what depends on this package instead
+        #[arg(long, value_name = "NAME")]
+        invert: Option<String>,
+    },
+    /// List git dependencies with newer tags or commits upstream
+    Outdated {
+        /// Exit with an error when any dependency is outdated
+        #[arg(long)]
+        exit_code: bool,
+    },
+    /// Report the license, origin and commit of each dependency in external/ and vendor/
+    Audit {
+        /// Output format
+        #[arg(long, default_value = "human", value_parser = ["human", "json", "csv"])]
+        format: String,
+    },
+    /// Export the dependency graph, or the header-inclusion graph, as dot or JSON
+    Graph {
+        /// Graph the packages in [dependencies], recursively (the default)
+        #[arg(long, conflicts_with = "modules")]
+        deps: bool,
+        /// Graph the #include "..." relations between main.c, src/ and include/
+        #[arg(long)]
+        modules: bool,
+        /// Output format
+        #[arg(long, default_value = "dot", value_parser = ["dot", "json"])]
+        format: String,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Show project metadata, dependencies and build status
+    Info {
+        /// Print the information as JSON
+        #[arg(long)]
+        json: bool,
+        /// List the files build/, external/ and .c1ignore exclude from scanning, and why
+        #[arg(long, conflicts_with = "json")]
+        ignored: bool,
+    },
+    /// Format main.c, src/, include/ and tests/ with clang-format
+    Fmt {
+        /// Report unformatted files and exit non-zero instead of rewriting them
+        #[arg(long)]
+        check: bool,
+    },
+    /// Run clang-tidy on the project sources
+    Lint {
+        /// Apply clang-tidy's suggested fixes
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Build in release mode and copy the executable onto PATH; a library is installed
+    /// with its headers, CMake package and pkg-config file
+    Install {
+        /// Install into <prefix>/bin (default: ~/.local/bin), a library into <prefix>/lib
+        /// and <prefix>/include (default: ~/.local)
+        #[arg(long, conflicts_with = "path")]
+        prefix: Option<String>,
+        /// Install into exactly this directory
+        #[arg(long)]
+        path: Option<String>,
+        /// Replace an existing file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Remove an executable or library installed by `c1 install`
+    Uninstall {
+        /// Remove from <prefix>/bin (default: ~/.local/bin), a library from <prefix>
+        /// (default: ~/.local)
+        #[arg(long, conflicts_with = "path")]
+        prefix: Option<String>,
+        /// Remove from exactly this directory
+        #[arg(long)]
+        path: Option<String>,
+    },
+    /// Create build/<name>-<version>.tar.gz with the project's sources
+    Package {
+        /// Include the dependencies in external/
+        #[arg(long)]
+        vendored: bool,
+        /// List the archive's contents after creating it
+        #[arg(long)]
+        verify: bool,
+    },
+    /// Build benches/*.c in Release mode and time them
+    Bench {
+        /// Run only this benchmark (benches/<name>.c)
+        #[arg(long)]
+        bench: Option<String>,
+        /// Number of times to run each benchmark
+        #[arg(long, default_value_t = 10)]
+        iterations: u32,
+    },
+    /// Build, then show the executable's flash and RAM usage
+    Size {
+        /// Measure the release build
+        #[arg(long)]
+        release: bool,
+        /// Build for the `[target.<name>]` table in project.toml
+        #[arg(long = "target", value_name = "NAME")]
+        target_config: Option<String>,
+        /// Measure the `[variant.<name>]` board variant
+        #[arg(long, value_name = "NAME")]
+        variant: Option<String>,
+        /// Show the change since the last `c1 size`
+        #[arg(long)]
+        diff: bool,
+        /// Also list the N largest symbols
+        #[arg(long, value_name = "N")]
+        top: Option<usize>,
+    },
+    /// Generate API documentation with doxygen
+    Doc {
+        /// Open the generated documentation in the default browser
+        #[arg(long)]
+        open: bool,
+    },
+    /// Rename the project in project.toml and CMakeLists.txt
+    RenameProject {
+        /// New project name
+        new_name: String,
+        /// Show the changes without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Update an older project.toml to the current layout, keeping a backup in
+    /// project.toml.bak
+    UpgradeManifest,
+    /// Print the project version, or bump it in project.toml
+    Version {
+        /// patch, minor, major, or an explicit version such as 1.4.0
+        #[arg(value_name = "BUMP")]
+        bump: Option<String>,
+        /// Commit project.toml and tag the commit v<version>
+        #[arg(long, requires = "bump")]
+        tag: bool,
+        /// Bump even with uncommitted changes in the working tree
+        #[arg(long, requires = "bump")]
+        allow_dirty: bool,
+        /// Allow setting a version that is not newer than the current one
+        #[arg(long, requires = "bump")]
+        force: bool,
+    },
+    /// Show trends from locally recorded statistics
+    Stats {
+        #[command(subcommand)]
+        target: StatsTarget,
+    },
+    /// Rebuild whenever a source file changes
+    Watch {
+        /// Also run the program, or the tests, after each build
+        #[arg(long, value_name = "WHAT", value_parser = ["run", "test"])]
+        exec: Option<String>,
+        /// Build in release mode
+        #[arg(long)]
+        release: bool,
+    },
+    /// Manage the global cache of dependency clones and upstream queries
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Read or change the user configuration (~/.config/c1/config.toml)
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print a setting, e.g. `build.generator`
+    Get { key: String },
+    /// Change a setting, e.g. `c1 config set defaults.new.compiler clang`
+    Set {
+        key: String,
+        /// The value; a list such as `defaults.new.flags` is given space-separated
+        #[arg(allow_hyphen_values = true)]
+        value: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Delete the cache directory
+    Clean,
+}
+
+#[derive(Subcommand)]
+enum StatsTarget {
+    /// Build durations, warnings and artifact size over recent builds
+    Builds {
+        /// Number of most recent builds to include
+        #[arg(long, default_value_t = 20)]
+        last: usize,
+        /// Print the raw records as a JSON array
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ExportTarget {
+    /// Generate bootstrap.sh / bootstrap.ps1 that fetch locked dependencies
+    Bootstrap {
+        /// Directory to write the scripts into
+        #[arg(long, default_value = ".")]
+        output_dir: String,
+    },
 }
 
 #[derive(Serialize, Deserialize)]
 struct ProjectConfig {
+    /// Layout of project.toml; missing in manifests from before it had one
+    #[serde(default, skip_serializing_if = "is_zero")]
+    schema_version: i64,
     project: Project,
     #[serde(default)]
     dependencies: toml::Table,
+    /// Only for tests and benches; never required of the project's consumers
+    #[serde(default, rename = "dev-dependencies")]
+    dev_dependencies: toml::Table,
     #[serde(default)]
     build: BuildConfig,
+    #[serde(default)]
+    lint: LintConfig,
+    #[serde(default)]
+    run: RunConfig,
+    /// Platform-abstraction modules and their implementations
+    #[serde(default)]
+    platforms: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    target: BTreeMap<String, TargetConfig>,
+    #[serde(default)]
+    features: BTreeMap<String, features::Feature>,
+    /// Environment for `c1 run`
+    #[serde(default)]
+    env: BTreeMap<String, run_env::EnvValue>,
+    /// Executables besides the one built from main.c
+    #[serde(default)]
+    bin: Vec<bins::Bin>,
+    /// `[profile.dev]`, `[profile.release]` and custom profiles
+    #[serde(default)]
+    profile: BTreeMap<String, profiles::Profile>,
+    /// Commands writing sources before each build
+    #[serde(default)]
+    generate: BTreeMap<String, generate::Rule>,
+    /// Board variants, built with `--variant`
+    #[serde(default)]
+    variant: BTreeMap<String, variants::Variant>,
+    /// License policy for `c1 audit`
+    #[serde(default)]
+    audit: audit::Policy,
+}
+
+fn is_zero(n: &i64) -> bool {
+    *n == 0
+}
+
+impl ProjectConfig {
+    /// `[dependencies]` and `[dev-dependencies]` together; a name in both is
+    /// the normal dependency
+    fn all_dependencies(&self) -> toml::Table {
+        let mut all = self.dev_dependencies.clone();
+        all.extend(self.dependencies.clone());
+        all
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -66,201 +1053,1503 @@ struct Project {
     name: String,
     #[serde(default = "default_version")]
     version: String,
-    #[serde(default = "default_edition")]
+    /// Normalized to the `cNN` form
+    #[serde(default = "default_edition", deserialize_with = "deserialize_edition")]
     edition: String,
     #[serde(default)]
     description: String,
+    /// `lib` when src/ builds a library (main.c, if any, is a demo linking it)
+    #[serde(default, rename = "type")]
+    kind: ProjectKind,
+    /// Whether main.c is at the top of the project or in src/
+    #[serde(default)]
+    layout: Layout,
+    /// SPDX identifier of the project's license
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    license: Option<String>,
+    /// `Name <email>` of each author
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    authors: Vec<String>,
+    /// URL of the project's source repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    repository: Option<String>,
+    /// Whether external/ is committed (true) or in .gitignore (false)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    vendor: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ProjectKind {
+    #[default]
+    Bin,
+    Lib,
+}
+
+/// Where a project keeps main.c
+#[derive(Serialize, Deserialize, Default, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum Layout {
+    #[default]
+    Flat,
+    Src,
+}
+
+impl Layout {
+    fn main_source(self) -> &'static str {
+        match self {
+            Layout::Flat => "main.c",
+            Layout::Src => "src/main.c",
+        }
+    }
+}
+
+/// The project's main.c where it actually is: at the top, or in src/ for a
+/// project with `layout = "src"`
+fn main_source() -> &'static str {
+    if !Path::new("main.c").exists() && Path::new("src/main.c").exists() {
+        Layout::Src.main_source()
+    } else {
+        Layout::Flat.main_source()
+    }
 }
 
 #[derive(Serialize, Deserialize, Default)]
 struct BuildConfig {
-    #[serde(default = "default_compiler")]
-    compiler: String,
+    /// C compiler when `--compiler` is not given; `CC`, then cmake's default if unset
+    #[serde(default)]
+    compiler: Option<String>,
     #[serde(default)]
     flags: Vec<String>,
+    /// Platform used when no `--target` is given
+    #[serde(default)]
+    platform: Option<String>,
+    /// Parallel compile jobs when `-j` is not given
+    #[serde(default)]
+    jobs: Option<usize>,
+    /// Build directory when `--out-dir` is not given
+    #[serde(default)]
+    out_dir: Option<String>,
+    /// CMake generator when `--generator` is not given; cmake's default if unset
+    #[serde(default)]
+    generator: Option<String>,
+    /// System libraries to link, e.g. `m` or `pthread`
+    #[serde(default)]
+    links: Vec<String>,
+    /// "auto" to compile whatever is in src/ instead of the lists in
+    /// CMakeLists.txt; "list" (the default) otherwise
+    #[serde(default)]
+    source_mode: Option<String>,
+    /// Patterns of files auto mode leaves out, e.g. `src/experimental/**`
+    #[serde(default)]
+    exclude: Vec<String>,
+    /// Header directories besides include/, relative to the project root
+    #[serde(default)]
+    include_dirs: Vec<String>,
+    /// Preprocessor macros for every build, as `NAME` or `NAME=VALUE`
+    #[serde(default)]
+    defines: Vec<String>,
+    /// Artifacts to write after each build when `--emit` is not given
+    #[serde(default)]
+    emit: Vec<String>,
+    /// Whether builds write c1_version.h; on unless set to false
+    #[serde(default)]
+    version_header: Option<bool>,
+    /// Compiler cache to launch the compiler through: ccache, sccache, auto
+    /// or none
+    #[serde(default)]
+    cache: Option<String>,
+    /// Warning preset: none, default, strict or pedantic; no flags if unset
+    #[serde(default)]
+    warnings: Option<String>,
+    /// Fail the build when the compiler printed any warning, and pass -Werror
+    #[serde(default)]
+    warnings_as_errors: bool,
+    /// Oldest cmake to configure with; CMakeLists.txt's minimum if unset
+    #[serde(default)]
+    min_cmake: Option<String>,
+    /// Oldest version of the compiler to build with; gcc 7 or clang 6 if unset
+    #[serde(default)]
+    min_compiler: Option<String>,
 }
 
-fn default_version() -> String {
-    "0.1.0".to_string()
-}
-
-fn default_edition() -> String {
-    "99".to_string()
+/// A `[target.<name>]` table, selected with `c1 build --target <name>`
+#[derive(Serialize, Deserialize, Default)]
+struct TargetConfig {
+    #[serde(default)]
+    platform: Option<String>,
+    /// CMake toolchain file for cross-compiling, relative to the project root
+    #[serde(default)]
+    toolchain: Option<String>,
+    /// Extra compiler flags, passed as CMAKE_C_FLAGS
+    #[serde(default)]
+    flags: Vec<String>,
+    /// Tool name prefix, e.g. `arm-none-eabi-` for `arm-none-eabi-gcc`,
+    /// when there is no toolchain file
+    #[serde(default)]
+    prefix: Option<String>,
+    /// CPU selection flags for both compiling and linking, e.g. `-mthumb`
+    #[serde(default)]
+    cpu_flags: Vec<String>,
+    /// Linker script, relative to the project root
+    #[serde(default)]
+    linker_script: Option<String>,
+    /// Command that runs the target's binaries on this host, e.g. `qemu-arm`;
+    /// empty to run them directly despite `[run] runner`
+    #[serde(default)]
+    runner: Option<String>,
 }
 
-fn default_compiler() -> String {
-    "gcc".to_string()
+/// Options shared by `c1 build` and the build step of `c1 run`
+#[derive(Clone, Default)]
+struct BuildOptions {
+    release: bool,
+    jobs: Option<usize>,
+    out_dir: Option<String>,
+    generator: Option<String>,
+    compiler: Option<String>,
+    /// Artifacts to write besides the executable: bin, hex, map
+    emit: Vec<String>,
+    /// Macros from `--define`, on top of `[build] defines`
+    defines: Vec<String>,
+    timings: bool,
+    target_config: Option<String>,
+    features: Vec<String>,
+    no_default_features: bool,
+    reconfigure: bool,
+    /// Stop after the configure step
+    configure_only: bool,
+    /// CMake targets to build; everything when empty
+    targets: Vec<String>,
+    /// Build every example, from `--examples`
+    examples: bool,
+    /// Skip checking external/ against project.toml and project.lock
+    no_verify_deps: bool,
+    /// Leave out the compiler cache, from `--no-cache`
+    no_cache: bool,
+    /// Rewrite bad SOURCES and HEADERS entries, from `--fix-sources`
+    fix_sources: bool,
+    sanitize: Vec<String>,
+    /// Instrument for `c1 test --coverage`
+    coverage: bool,
+    /// Run the programs under valgrind, from `--valgrind`
+    valgrind: bool,
+    /// Run the only executable built when the expected one is missing,
+    /// from `c1 run --guess`
+    guess: bool,
+    /// A profile other than dev and release, from `--profile`
+    profile: Option<String>,
+    /// The `[variant.<name>]` to build, from `--variant`
+    variant: Option<String>,
 }
 
-fn main() {
-    let cli = Cli::parse();
-
-    match cli.command {
-        Commands::New { name } => cmd_new(name),
-        Commands::Init => cmd_init(),
-        Commands::Create { name } => cmd_create(name),
-        Commands::Run => cmd_run(),
-        Commands::Build { release } => cmd_build(release),
-        Commands::Add { url, tag, branch } => cmd_add(url, tag, branch),
-        Commands::Clean => cmd_clean(),
-        Commands::Sync => cmd_sync(),
+impl BuildOptions {
+    /// The equivalent `c1 build` arguments, for building workspace members
+    fn to_args(&self) -> Vec<String> {
+        let mut args = vec!["build".to_string()];
+        // `--profile` implies its build type
+        if self.release && self.profile.is_none() {
+            args.push("--release".to_string());
+        }
+        if let Some(jobs) = self.jobs {
+            args.push(format!("--jobs={}", jobs));
+        }
+        if let Some(out_dir) = &self.out_dir {
+            args.push("--out-dir".to_string());
+            args.push(out_dir.clone());
+        }
+        if let Some(generator) = &self.generator {
+            args.push("--generator".to_string());
+            args.push(generator.clone());
+        }
+        if let Some(compiler) = &self.compiler {
+            args.push("--compiler".to_string());
+            args.push(compiler.clone());
+        }
+        if !self.emit.is_empty() {
+            args.push(format!("--emit={}", self.emit.join(",")));
+        }
+        for define in &self.defines {
+            args.push(format!("--define={}", define));
+        }
+        if self.timings {
+            args.push("--timings".to_string());
+        }
+        if let Some(target_config) = &self.target_config {
+            args.push("--target".to_string());
+            args.push(target_config.clone());
+        }
+        if !self.features.is_empty() {
+            args.push(format!("--features={}", self.features.join(",")));
+        }
+        if self.no_default_features {
+            args.push("--no-default-features".to_string());
+        }
+        if self.reconfigure {
+            args.push("--reconfigure".to_string());
+        }
+        if self.configure_only {
+            args.push("--configure-only".to_string());
+        }
+        if self.examples {
+            args.push("--examples".to_string());
+        }
+        if self.no_verify_deps {
+            args.push("--no-verify-deps".to_string());
+        }
+        if self.no_cache {
+            args.push("--no-cache".to_string());
+        }
+        if self.fix_sources {
+            args.push("--fix-sources".to_string());
+        }
+        if !self.sanitize.is_empty() {
+            args.push(format!("--sanitize={}", self.sanitize.join(",")));
+        }
+        if let Some(profile) = &self.profile {
+            args.push(format!("--profile={}", profile));
+        }
+        if let Some(variant) = &self.variant {
+            args.push(format!("--variant={}", variant));
+        }
+        args
     }
-}
 
-/// Check if directory is empty (only hidden files allowed)
-fn is_dir_empty(path: &Path) -> io::Result<bool> {
-    if !path.exists() {
-        return Ok(true);
+    /// Apply `--profile`: dev and release mean building without and with
+    /// `--release`; other profiles build on the build type they inherit
+    fn with_profile(mut self, profile: Option<String>) -> error::Result<BuildOptions> {
+        let Some(name) = profile else {
+            return Ok(self);
+        };
+        let declared = match profiles::subdir(Some(&name)) {
+            Some(_) => read_config()?.profile,
+            None => BTreeMap::new(),
+        };
+        self.release = profiles::is_release(&declared, &name)?;
+        self.profile = profiles::subdir(Some(&name)).map(str::to_string);
+        Ok(self)
     }
-    for entry in fs::read_dir(path)? {
-        let entry = entry?;
-        let name = entry.file_name();
-        let name_str = name.to_string_lossy();
-        // Allow hidden files (starting with .)
-        if !name_str.starts_with('.') {
-            return Ok(false);
+
+    /// Apply `--variant`: its target and profile, unless `--target`,
+    /// `--profile` or `--release` chose others
+    fn with_variant(mut self, variant: Option<String>) -> error::Result<BuildOptions> {
+        let Some(name) = variant else {
+            return Ok(self);
+        };
+        let config = read_config()?;
+        let variant = variants::get(&config.variant, &name)?;
+        if self.target_config.is_none() {
+            self.target_config = variant.target.clone();
         }
+        if let Some(profile) = &variant.profile
+            && self.profile.is_none()
+            && !self.release
+        {
+            self = self.with_profile(Some(profile.clone()))?;
+        }
+        self.variant = Some(name);
+        Ok(self)
     }
-    Ok(true)
 }
 
-/// Get project name from current directory
-fn get_current_dir_name() -> String {
-    std::env::current_dir()
-        .ok()
-        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
-        .unwrap_or_else(|| "my_project".to_string())
+#[derive(Serialize, Deserialize, Default)]
+struct LintConfig {
+    /// clang-tidy `--checks` expression
+    #[serde(default)]
+    checks: Option<String>,
 }
 
-/// Create a new project in a new directory
-fn cmd_new(name: String) {
-    let target_dir = std::env::current_dir()
-        .expect("Failed to get current directory")
-        .join(&name);
-
-    // Check if target directory already exists
-    if target_dir.exists() {
-        eprintln!("Error: Directory '{}' already exists.", name);
-        std::process::exit(1);
-    }
-
-    // Create project directory
-    fs::create_dir_all(&target_dir).expect("Failed to create project directory");
-    println!("Creating project '{}' in '{}'...", name, name);
-
-    // Initialize project in the new directory
-    init_project_in_dir(&target_dir, &name);
+#[derive(Serialize, Deserialize, Default)]
+struct RunConfig {
+    /// Extra valgrind arguments for `--valgrind`, e.g. `["--track-origins=yes"]`
+    #[serde(default)]
+    valgrind_args: Vec<String>,
+    /// Command that runs every binary, e.g. `wine`; a target's own wins
+    #[serde(default)]
+    runner: Option<String>,
+}
 
-    println!("✓ Project '{}' created successfully!", name);
+fn default_version() -> String {
+    "0.1.0".to_string()
 }
 
-/// Initialize a project in the current directory
-fn cmd_init() {
-    let target_dir = std::env::current_dir().expect("Failed to get current directory");
-    let project_name = get_current_dir_name();
+/// Supported `edition` values
+const EDITIONS: &[&str] = &["c90", "c99", "c11", "c17", "c23"];
 
-    // Safety check: directory must be empty (except hidden files)
-    if !is_dir_empty(&target_dir).expect("Failed to check directory") {
-        eprintln!("Error: Directory is not empty. c1 init must be run in an empty directory.");
-        std::process::exit(1);
-    }
+fn default_edition() -> String {
+    "c99".to_string()
+}
 
-    println!("Initializing project '{}'...", project_name);
+/// The edition of a new project without `--edition`: the user's default, or c99
+fn new_edition() -> String {
+    user_config::text("defaults.new.edition").map_or_else(default_edition, str::to_string)
+}
 
-    // Initialize project in current directory
-    init_project_in_dir(&target_dir, &project_name);
+/// Whether a new project gets a git repository: `--vcs`, the user's default, or yes
+fn new_vcs(vcs: Option<String>) -> bool {
+    vcs.as_deref()
+        .or_else(|| user_config::text("defaults.new.vcs"))
+        .unwrap_or("git")
+        == "git"
+}
 
-    println!("✓ Project '{}' initialized successfully!", project_name);
+/// Whether a new project commits external/: `--vendor-deps`, else the
+/// user's default
+fn new_vendor(vendor_deps: bool) -> bool {
+    vendor_deps || user_config::flag("defaults.new.vendor_deps").unwrap_or(false)
 }
 
-/// Common function to initialize project files in a directory
-fn init_project_in_dir(target_dir: &Path, project_name: &str) {
-    // Switch to target directory for subsequent operations
-    let original_dir = std::env::current_dir().expect("Failed to get current directory");
-    if target_dir != original_dir {
-        std::env::set_current_dir(target_dir).expect("Failed to change to project directory");
+/// The license of a new project: `--license`, else the user's default
+fn new_license(license: Option<String>) -> error::Result<Option<&'static license::License>> {
+    match license.as_deref().or_else(|| user_config::text("defaults.new.license")) {
+        Some(id) => license::parse(id),
+        None => Ok(None),
     }
+}
 
-    // Create directory structure
-    fs::create_dir_all("src").expect("Failed to create src directory");
-    fs::create_dir_all("include").expect("Failed to create include directory");
-    fs::create_dir_all("external").expect("Failed to create external directory");
-    fs::create_dir_all("build").expect("Failed to create build directory");
+/// What `c1 new` and `c1 init` put in a project besides its name and template
+struct NewProject {
+    edition: String,
+    description: String,
+    /// `[project] authors`, by default the git user
+    authors: Vec<String>,
+    repository: Option<String>,
+    /// `[build] compiler` in project.toml
+    compiler: String,
+    /// `[build] warnings` in project.toml
+    warnings: &'static str,
+    git: bool,
+    license: Option<&'static license::License>,
+    vendor: bool,
+    layout: Layout,
+}
 
-    // Create main.c (placed in project root directory)
-    let main_c = format!(
-        r#"#include <stdio.h>
+impl NewProject {
+    /// The settings from the command line, then the user's defaults
+    fn resolve(
+        edition: Option<String>,
+        vcs: Option<String>,
+        license: Option<String>,
+        layout: Option<String>,
+        vendor_deps: bool,
+    ) -> error::Result<NewProject> {
+        Ok(NewProject {
+            edition: edition.unwrap_or_else(new_edition),
+            description: "A C project created with c1".to_string(),
+            authors: Vec::new(),
+            repository: None,
+            compiler: user_config::text("defaults.new.compiler")
+                .unwrap_or("gcc")
+                .to_string(),
+            warnings: warnings::NEW_PROJECT,
+            git: new_vcs(vcs),
+            license: new_license(license)?,
+            vendor: new_vendor(vendor_deps),
+            layout: if layout.as_deref() == Some("src") {
+                Layout::Src
+            } else {
+                Layout::Flat
+            },
+        })
+    }
 
-int main(void) {{
-    printf("Hello {}\n");
-    return 0;
-}}
-"#,
-        project_name
-    );
-    fs::write("main.c", main_c).expect("Failed to create main.c");
+    /// Apply `--description`, `--author` and `--repository`; without
+    /// `--author` the project is credited to the git user
+    fn with_metadata(
+        mut self,
+        description: Option<String>,
+        authors: Vec<String>,
+        repository: Option<String>,
+    ) -> NewProject {
+        if let Some(description) = description {
+            self.description = description;
+        }
+        self.authors = if authors.is_empty() {
+            git_author().into_iter().collect()
+        } else {
+            authors
+        };
+        self.repository = repository;
+        self
+    }
+}
 
-    // Create CMakeLists.txt
-    let cmake_content = format!(
-        r#"cmake_minimum_required(VERSION 3.16)
-project({} C)
+/// `Name <email>` from git's `user.name` and `user.email`
+fn git_author() -> Option<String> {
+    let setting = |key: &str| {
+        run_git(".", &["config", key])
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    match (setting("user.name"), setting("user.email")) {
+        (Some(name), Some(email)) => Some(format!("{} <{}>", name, email)),
+        (Some(name), None) => Some(name),
+        (None, Some(email)) => Some(format!("<{}>", email)),
+        (None, None) => None,
+    }
+}
 
-set(CMAKE_C_STANDARD 99)
-set(CMAKE_C_STANDARD_REQUIRED ON)
-set(CMAKE_EXPORT_COMPILE_COMMANDS ON)
+/// Accept `c11` as well as the bare `11` older manifests used
+fn deserialize_edition<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let edition = String::deserialize(deserializer)?;
+    let normalized = format!("c{}", edition.strip_prefix('c').unwrap_or(&edition));
+    if EDITIONS.contains(&normalized.as_str()) {
+        Ok(normalized)
+    } else {
+        Err(serde::de::Error::custom(format!(
+            "unknown edition '{}' (supported: {})",
+            edition,
+            EDITIONS.join(", ")
+        )))
+    }
+}
 
-# Source files list
-set(SOURCES
-    main.c
-    # @c1_sources
-)
+/// The CMAKE_C_STANDARD value for a normalized edition: `c11` -> `11`
+fn c_standard(edition: &str) -> &str {
+    edition.trim_start_matches('c')
+}
 
-# Header files list
-set(HEADERS
-    # @c1_headers
-)
+fn main() {
+    let cli = Cli::parse();
+    let json = cli.message_format == "json";
+    events::set_json(json);
+    // JSON events replace the status lines on stdout
+    log::set(cli.quiet || json, cli.verbose && !json);
+    style::set(&cli.color);
+    safe_path::set_allow_outside_project(cli.allow_outside_project);
+    remote::set_refresh(cli.refresh);
+    remote::set_offline(cli.offline || offline_from_env());
+    project_lock::set_no_wait(cli.no_wait);
 
-add_executable(${{PROJECT_NAME}} ${{SOURCES}} ${{HEADERS}})
+    let result = run(cli.command);
+    project_lock::release();
+    if let Err(e) = result {
+        e.exit();
+    }
+    events::summary(0);
+}
 
-# Include directories: following Pitchfork convention, headers in include/
-target_include_directories(${{PROJECT_NAME}} PRIVATE 
-    ${{CMAKE_CURRENT_SOURCE_DIR}}/include
-)
+/// C1_OFFLINE when set (`0` turning it off), else `[net] offline` from the
+/// user's config
+fn offline_from_env() -> bool {
+    match std::env::var("C1_OFFLINE") {
+        Ok(v) if !v.is_empty() => v != "0",
+        _ => user_config::flag("net.offline").unwrap_or(false),
+    }
+}
 
-# Default linked libraries (reserved example)
-# target_link_libraries(${{PROJECT_NAME}} PRIVATE m)
-"#,
-        project_name
-    );
-    fs::write("CMakeLists.txt", cmake_content).expect("Failed to create CMakeLists.txt");
+/// Dispatch a subcommand
+fn run(command: Commands) -> error::Result<()> {
+    match &command {
+        Commands::New { .. }
+        | Commands::Init { .. }
+        | Commands::Cache { .. }
+        | Commands::Config { .. } => {}
+        Commands::Build { .. }
+        | Commands::Check { .. }
+        | Commands::Expand { .. }
+        | Commands::Run { .. }
+        | Commands::Test { .. }
+        | Commands::Clean { .. }
+        | Commands::Sync { .. }
+        | Commands::Add { .. }
+        | Commands::Rename { .. } => root::enter(true)?,
+        _ => root::enter(false)?,
+    }
+    // Commands that write to build/ or external/ take turns
+    let locked = match &command {
+        Commands::Build { .. } => Some("build"),
+        Commands::Run { .. } => Some("run"),
+        Commands::Test { .. } => Some("test"),
+        Commands::Expand { .. } => Some("expand"),
+        Commands::Clean { .. } => Some("clean"),
+        Commands::Sync { .. } => Some("sync"),
+        Commands::Add { .. } => Some("add"),
+        _ => None,
+    };
+    if let Some(name) = locked {
+        project_lock::acquire(name)?;
+    }
+    match command {
+        Commands::New {
+            name,
+            template,
+            lib,
+            list_templates,
+            edition,
+            vcs,
+            license,
+            layout,
+            vendor_deps,
+            description,
+            authors,
+            repository,
+            interactive,
+            dry_run,
+        } => {
+            if list_templates {
+                templates::print_list();
+                return Ok(());
+            }
+            let template = if lib { "lib".to_string() } else { template };
+            let mut settings = NewProject::resolve(edition, vcs, license, layout, vendor_deps)?
+                .with_metadata(description, authors, repository);
+            let name = if interactive {
+                interactive::ask(name.as_deref(), "c1 new", &mut settings)?
+            } else {
+                name.expect("clap requires a name")
+            };
+            cmd_new(name, &template, &settings, dry_run)?
+        }
+        Commands::Init {
+            vcs,
+            license,
+            existing,
+            dry_run,
+            name,
+            edition,
+            layout,
+            vendor_deps,
+            description,
+            authors,
+            repository,
+            interactive,
+        } => {
+            // Existing code with src/main.c keeps it there
+            let layout = layout.or_else(|| {
+                (existing && main_source() == Layout::Src.main_source()).then(|| "src".to_string())
+            });
+            let mut settings = NewProject::resolve(edition, vcs, license, layout, vendor_deps)?
+                .with_metadata(description, authors, repository);
+            let name = init_name(name)?;
+            let name = if interactive {
+                interactive::ask(Some(&name), "c1 init --name", &mut settings)?
+            } else {
+                name
+            };
+            if existing {
+                settings.warnings = warnings::EXISTING_CODE;
+                adopt::cmd_init_existing(&name, &settings, dry_run)?
+            } else {
+                cmd_init(&name, &settings, dry_run)?
+            }
+        }
+        Commands::Create {
+            name,
+            kind,
+            list_kinds,
+            platforms,
+            add_platform,
+            with_test,
+            doc_comments,
+            header_only,
+            no_cmake,
+        } => match name {
+            Some(name) if !list_kinds => cmd_create(
+                name,
+                platforms,
+                add_platform,
+                &NewModule {
+                    kind: module_kinds::parse(&kind),
+                    with_test,
+                    doc_comments,
+                    header_only,
+                    cmake: !no_cmake,
+                },
+            )?,
+            _ => module_kinds::cmd_list(),
+        },
+        Commands::Destroy { name, force } => cmd_destroy(name, force)?,
+        Commands::Rename { old, new } => rename_module::cmd_rename(&old, &new)?,
+        Commands::Run {
+            release,
+            profile,
+            jobs,
+            out_dir,
+            target_config,
+            features,
+            no_default_features,
+            sanitize,
+            valgrind,
+            defines,
+            env,
+            package,
+            no_verify_deps,
+            bin,
+            example,
+            guess,
+            variant,
+            args,
+        } => {
+            let options = BuildOptions {
+                release,
+                jobs,
+                out_dir,
+                target_config,
+                features,
+                no_default_features,
+                sanitize,
+                valgrind,
+                defines,
+                no_verify_deps,
+                guess,
+                ..BuildOptions::default()
+            }
+            .with_profile(profile)?
+            .with_variant(variant)?;
+            match (workspace::load()?, example.as_deref()) {
+                (_, Some("")) => examples::print_list()?,
+                (Some(members), _) => workspace::cmd_run(
+                    &members,
+                    package.as_deref(),
+                    &options,
+                    &env,
+                    &args,
+                    bin.as_deref(),
+                    example.as_deref(),
+                )?,
+                (None, example) => cmd_run(&options, &env, &args, bin.as_deref(), example)?,
+            }
+        }
+        Commands::Build {
+            release,
+            profile,
+            jobs,
+            out_dir,
+            generator,
+            compiler,
+            emit,
+            defines,
+            timings,
+            target_config,
+            all_profiles,
+            all_targets,
+            variant,
+            all_variants,
+            fail_fast,
+            features,
+            no_default_features,
+            sanitize,
+            package,
+            keep_going,
+            reconfigure,
+            configure_only,
+            examples,
+            no_verify_deps,
+            no_cache,
+            fix_sources,
+            show_commands,
+            replay_last,
+        } => {
+            log::show_commands(show_commands);
+            if replay_last {
+                return build_log::cmd_replay(out_dir.as_deref());
+            }
+            let options = BuildOptions {
+                release,
+                jobs,
+                out_dir,
+                generator,
+                compiler,
+                emit,
+                defines,
+                timings,
+                target_config: match target_config.as_slice() {
+                    [target] => Some(target.clone()),
+                    _ => None,
+                },
+                features,
+                no_default_features,
+                reconfigure,
+                configure_only,
+                targets: Vec::new(),
+                examples,
+                no_verify_deps,
+                no_cache,
+                fix_sources,
+                sanitize,
+                coverage: false,
+                valgrind: false,
+                guess: false,
+                profile: None,
+                variant: None,
+            }
+            .with_profile(profile)?
+            .with_variant(variant)?;
+            let matrix = all_profiles || all_targets || target_config.len() > 1;
+            match workspace::load()? {
+                Some(_) if matrix || all_variants => {
+                    return Err(Error::usage(
+                        "--all-profiles, --all-targets, --all-variants and several --target \
+                         are not supported in a workspace",
+                    )
+                    .hint("run the build in a member's directory"));
+                }
+                None if all_variants => variants::cmd_build_all(&options, fail_fast)?,
+                None if matrix => matrix::cmd_build(
+                    &options,
+                    all_profiles,
+                    all_targets,
+                    &target_config,
+                    fail_fast,
+                )?,
+                Some(members) => {
+                    workspace::cmd_build(&members, package.as_deref(), &options, keep_going)?
+                }
+                None => {
+                    cmd_build(&options)?;
+                    if log::is_quiet()
+                        && !events::is_json()
+                        && let Some(path) = artifact_path(&options)
+                    {
+                        println!("{}", path.display());
+                    }
+                }
+            }
+        }
+        Commands::Test {
+            pattern,
+            list,
+            release,
+            profile,
+            jobs,
+            sanitize,
+            valgrind,
+            init,
+            coverage,
+            fail_under,
+            format,
+            output,
+        } => {
+            let options = BuildOptions {
+                release,
+                jobs,
+                sanitize,
+                coverage,
+                valgrind,
+                ..BuildOptions::default()
+            }
+            .with_profile(profile)?;
+            let report = match (format.as_str(), output) {
+                ("junit", output) => output.map(|output| root::rebase(&output)),
+                (_, Some(_)) => {
+                    return Err(
+                        Error::usage("--output is for the JUnit report").hint("add --format junit")
+                    );
+                }
+                (_, None) => None,
+            };
+            testing::cmd_test(
+                &options,
+                pattern.as_deref(),
+                list,
+                init,
+                fail_under,
+                report.as_deref(),
+            )?
+        }
+        Commands::Add {
+            url,
+            path,
+            pkg_config,
+            link,
+            tag,
+            branch,
+            rev,
+            full_history,
+            submodule,
+            allow_override,
+            name,
+            archive,
+            no_clone,
+            dry_run,
+            dev,
+            force,
+            subdir,
+        } => match (link, path, pkg_config, url) {
+            (Some(link), _, _, _) => links::cmd_add(&link)?,
+            (None, Some(path), _, _) => cmd_add_path(root::rebase(&path), name, dev)?,
+            (None, None, Some(package), _) => cmd_add_pkg_config(package, name, dev)?,
+            (None, None, None, Some(url)) if archive => cmd_add_archive(url, name, dev)?,
+            (None, None, None, Some(url)) => cmd_add(
+                url,
+                AddOptions {
+                    tag,
+                    branch,
+                    rev,
+                    shallow: !full_history,
+                    submodule,
+                    allow_override,
+                    name,
+                    no_clone,
+                    dry_run,
+                    dev,
+                    force,
+                    subdir,
+                },
+            )?,
+            (None, None, None, None) => {
+                unreachable!("clap requires a url, --path, --pkg-config or --link")
+            }
+        },
+        Commands::Search {
+            query,
+            update_index,
+        } => index::cmd_search(query.as_deref(), update_index)?,
+        Commands::Clean {
+            out_dir,
+            release,
+            debug,
+            profile,
+            all,
+            deps,
+            keep_deps,
+            yes,
+            dry_run,
+            generated,
+        } => {
+            if !deps {
+                let only = (release || debug || profile.is_some())
+                    .then(|| profiles::dir_name(profile.as_deref(), release));
+                cmd_clean(out_dir.as_deref(), only, keep_deps, generated, dry_run)?;
+            }
+            if all || deps {
+                clean_dependencies(yes, dry_run)?;
+            }
+        }
+        Commands::Sync {
+            accept_local,
+            accept_changes,
+            max_time,
+            force,
+            prune,
+            allow_override,
+            locked,
+            frozen,
+            jobs,
+            no_dev,
+            fail_fast,
+        } => {
+            lockfile::set_enforced(locked || frozen);
+            if frozen {
+                remote::set_offline(true);
+            }
+            if accept_local {
+                cmd_sync_accept_local()?
+            } else if accept_changes {
+                cmd_sync_accept_changes()?
+            } else {
+                let max_time = max_time.map(Duration::from_secs);
+                let mode = SyncMode {
+                    force,
+                    wire: true,
+                    jobs,
+                    fail_fast,
+                };
+                cmd_sync(max_time, mode, prune, allow_override, no_dev)?
+            }
+        }
+        Commands::Update {
+            name,
+            verify,
+            bisect,
+        } => cmd_update(
+            name,
+            verify.as_deref().and_then(update_verify::Oracle::parse),
+            bisect,
+        )?,
+        Commands::Export { target } => match target {
+            ExportTarget::Bootstrap { output_dir } => {
+                bootstrap::cmd_export_bootstrap(&root::rebase(&output_dir))?
+            }
+        },
+        Commands::Verify => cmd_verify()?,
+        Commands::Doctor => cmd_doctor(),
+        Commands::Info { json, ignored } => {
+            if ignored {
+                info::cmd_ignored()
+            } else {
+                info::cmd_info(json)?
+            }
+        }
+        Commands::List => list::cmd_list(),
+        Commands::Tree { depth, invert } => tree::cmd_tree(depth, invert.as_deref())?,
+        Commands::Outdated { exit_code } => outdated::cmd_outdated(exit_code)?,
+        Commands::Audit { format } => audit::cmd_audit(&format)?,
+        Commands::Graph {
+            deps: _,
+            modules,
+            format,
+            output,
+        } => graph::cmd_graph(modules, &format, output.as_deref().map(root::rebase).as_deref())?,
+        Commands::Fmt { check } => fmt::cmd_fmt(check)?,
+        Commands::Lint { fix } => lint::cmd_lint(fix)?,
+        Commands::Doc { open } => doc::cmd_doc(open)?,
+        Commands::Install {
+            prefix,
+            path,
+            force,
+        } => {
+            let prefix = prefix.as_deref().map(root::rebase);
+            let path = path.as_deref().map(root::rebase);
+            install::cmd_install(prefix.as_deref(), path.as_deref(), force)?
+        }
+        Commands::Uninstall { prefix, path } => {
+            let prefix = prefix.as_deref().map(root::rebase);
+            let path = path.as_deref().map(root::rebase);
+            install::cmd_uninstall(prefix.as_deref(), path.as_deref())?
+        }
+        Commands::Package { vendored, verify } => package::cmd_package(vendored, verify)?,
+        Commands::Check {
+            release,
+            profile,
+            target_config,
+            features,
+            no_default_features,
+            defines,
+            compiler,
+            jobs,
+            changed,
+        } => {
+            let options = BuildOptions {
+                release,
+                profile,
+                target_config,
+                features,
+                no_default_features,
+                defines,
+                compiler,
+                ..BuildOptions::default()
+            };
+            check::cmd_check(&options, jobs, changed)?
+        }
+        Commands::Expand {
+            file,
+            output,
+            pretty,
+            release,
+            profile,
+            target_config,
+            features,
+            no_default_features,
+            defines,
+        } => {
+            let options = BuildOptions {
+                release,
+                target_config,
+                features,
+                no_default_features,
+                defines,
+                ..BuildOptions::default()
+            }
+            .with_profile(profile)?;
+            let output = output.as_deref().map(root::rebase);
+            expand::cmd_expand(&options, &root::rebase(&file), output.as_deref(), pretty)?
+        }
+        Commands::Bench { bench, iterations } => bench::cmd_bench(bench.as_deref(), iterations)?,
+        Commands::Size {
+            release,
+            target_config,
+            variant,
+            diff,
+            top,
+        } => {
+            let options = BuildOptions {
+                release,
+                target_config,
+                ..BuildOptions::default()
+            }
+            .with_variant(variant)?;
+            size::cmd_size(&options, diff, top)?
+        }
+        Commands::RenameProject { new_name, dry_run } => {
+            rename::cmd_rename_project(&new_name, dry_run)?
+        }
+        Commands::UpgradeManifest => manifest_upgrade::cmd_upgrade()?,
+        Commands::Version {
+            bump,
+            tag,
+            allow_dirty,
+            force,
+        } => version::cmd_version(bump.as_deref(), tag, allow_dirty, force)?,
+        Commands::Stats { target } => match target {
+            StatsTarget::Builds { last, json } => build_history::cmd_stats_builds(last, json)?,
+        },
+        Commands::Watch { exec, release } => watch::cmd_watch(exec.as_deref(), release)?,
+        Commands::Cache { action } => match action {
+            CacheAction::Clean => clone_cache::cmd_clean()?,
+        },
+        Commands::Config { action } => match action {
+            ConfigAction::Get { key } => user_config::cmd_get(&key)?,
+            ConfigAction::Set { key, value } => user_config::cmd_set(&key, &value)?,
+        },
+    }
+    Ok(())
+}
+
+/// Check if directory is empty (only hidden files allowed)
+fn is_dir_empty(path: &Path) -> io::Result<bool> {
+    if !path.exists() {
+        return Ok(true);
+    }
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name_str = name.to_string_lossy();
+        // Allow hidden files (starting with .)
+        if !name_str.starts_with('.') {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Get project name from current directory
+fn get_current_dir_name() -> String {
+    std::env::current_dir()
+        .ok()
+        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "my_project".to_string())
+}
+
+/// CMake names of its own targets, which a project of the same name would clash with
+const RESERVED_NAMES: &[&str] = &[
+    "all",
+    "clean",
+    "depend",
+    "edit_cache",
+    "help",
+    "install",
+    "package",
+    "package_source",
+    "rebuild_cache",
+    "test",
+    "ALL_BUILD",
+    "INSTALL",
+    "PACKAGE",
+    "RUN_TESTS",
+    "ZERO_CHECK",
+];
+
+/// Check that `name` works as a directory, the CMake project and target name,
+/// and (with '-' as '_') a C identifier; `retry` is the command to suggest a
+/// fixed name with
+fn check_project_name(name: &str, retry: &str) -> error::Result<()> {
+    let problem = if name.is_empty() {
+        "must not be empty"
+    } else if name.contains(['/', '\\']) {
+        "must not contain path separators"
+    } else if name.starts_with('-') {
+        "must not start with '-'"
+    } else if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        "must contain only letters, numbers, '-' and '_'"
+    } else if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        "must start with a letter or '_'"
+    } else if RESERVED_NAMES.contains(&name) {
+        "is the name of a target CMake creates itself"
+    } else {
+        return Ok(());
+    };
+    Err(Error::usage(format!("Project name '{}' {}", name, problem))
+        .hint(format!("try `{} {}`", retry, sanitize_project_name(name))))
+}
+
+/// The closest name `check_project_name` accepts; accented Latin letters
+/// lose their accents, other characters become '_'
+fn sanitize_project_name(name: &str) -> String {
+    let mut sanitized = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' {
+            sanitized.push(c);
+        } else if let Some(plain) = transliterate(c) {
+            sanitized.push_str(plain);
+        } else if !sanitized.is_empty() && !sanitized.ends_with('_') {
+            sanitized.push('_');
+        }
+    }
+    let mut sanitized = sanitized
+        .trim_start_matches('-')
+        .trim_end_matches('_')
+        .to_string();
+    if sanitized.is_empty() {
+        return "my_project".to_string();
+    }
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert_str(0, "project_");
+    }
+    if RESERVED_NAMES.contains(&sanitized.as_str()) {
+        sanitized.push_str("_app");
+    }
+    sanitized
+}
+
+/// The ASCII spelling of an accented Latin letter
+fn transliterate(c: char) -> Option<&'static str> {
+    const TABLE: &[(&str, &str)] = &[
+        ("àáâãäåāăą", "a"),
+        ("ÀÁÂÃÄÅĀĂĄ", "A"),
+        ("çćĉċč", "c"),
+        ("ÇĆĈĊČ", "C"),
+        ("ďđ", "d"),
+        ("ĎĐ", "D"),
+        ("èéêëēĕėęě", "e"),
+        ("ÈÉÊËĒĔĖĘĚ", "E"),
+        ("ĝğġģ", "g"),
+        ("ĜĞĠĢ", "G"),
+        ("ìíîïĩīĭįı", "i"),
+        ("ÌÍÎÏĨĪĬĮİ", "I"),
+        ("ñńņňł", "n"),
+        ("ÑŃŅŇ", "N"),
+        ("òóôõöøōŏő", "o"),
+        ("ÒÓÔÕÖØŌŎŐ", "O"),
+        ("ŕŗř", "r"),
+        ("ŔŖŘ", "R"),
+        ("śŝşš", "s"),
+        ("ŚŜŞŠ", "S"),
+        ("ţťŧ", "t"),
+        ("ŢŤŦ", "T"),
+        ("ùúûüũūŭůűų", "u"),
+        ("ÙÚÛÜŨŪŬŮŰŲ", "U"),
+        ("ýÿ", "y"),
+        ("ÝŸ", "Y"),
+        ("źżž", "z"),
+        ("ŹŻŽ", "Z"),
+        ("ß", "ss"),
+        ("æ", "ae"),
+        ("Æ", "AE"),
+    ];
+    TABLE
+        .iter()
+        .find(|(letters, _)| letters.contains(c))
+        .map(|(_, plain)| *plain)
+}
+
+/// `c1 init --name`, or else the name of the current directory
+fn init_name(name: Option<String>) -> error::Result<String> {
+    match name {
+        Some(name) => {
+            check_project_name(&name, "c1 init --name")?;
+            Ok(name)
+        }
+        None => {
+            let dir = std::env::current_dir()
+                .context(|| "failed to read the current directory".to_string())?;
+            let dir_name = dir.file_name().unwrap_or_default();
+            // A name that is not UTF-8 cannot be spelled out, only suggested
+            let Some(name) = dir_name.to_str() else {
+                return Err(Error::usage(format!(
+                    "the directory name '{}' is not valid UTF-8 and cannot be the project name",
+                    dir_name.to_string_lossy()
+                ))
+                .hint(format!(
+                    "try `c1 init --name {}`",
+                    sanitize_project_name(&dir_name.to_string_lossy())
+                )));
+            };
+            check_project_name(name, "c1 init --name")?;
+            Ok(name.to_string())
+        }
+    }
+}
+
+/// Create a new project in a new directory
+fn cmd_new(
+    name: String,
+    template: &str,
+    settings: &NewProject,
+    dry_run: bool,
+) -> error::Result<()> {
+    // Resolve the template before touching the filesystem
+    let builtin = templates::find(template);
+    if builtin.is_none() && !remote_template::is_remote(template) {
+        return Err(Error::usage(format!("Unknown template '{}'", template)).hint(format!(
+            "available templates: {}, or a git URL such as user/repo",
+            templates::names()
+        )));
+    }
+
+    check_project_name(&name, "c1 new")?;
+    let target_dir = std::env::current_dir()
+        .context(|| "failed to read the current directory".to_string())?
+        .join(&name);
+
+    // Check if target directory already exists
+    if target_dir.exists() {
+        return Err(Error::usage(format!("Directory '{}' already exists.", name)));
+    }
+    let remote = match builtin {
+        Some(_) => None,
+        None => Some(remote_template::fetch(template, &name)?),
+    };
+    let git = scaffold::git_plan(settings.git);
+    let root = format!("{}/", name);
+    if dry_run {
+        let paths = match (&remote, builtin) {
+            (Some(remote), _) => remote.files(&name, settings)?,
+            (None, Some(template)) => project_paths(&name, template, settings),
+            (None, None) => unreachable!("the template was resolved above"),
+        };
+        println!("Would create project '{}':", name);
+        scaffold::print(&root, &paths, git, true);
+        return Ok(());
+    }
+
+    // Create project directory; Ctrl-C from here on removes it again
+    let _deferred = interrupt::defer();
+    fs::create_dir_all(&target_dir)
+        .context(|| format!("failed to create {}", target_dir.display()))?;
+    log::status!("Creating project '{}' in '{}'...", name, name);
+
+    // Initialize project in the new directory, without leaving a broken
+    // skeleton behind when that fails halfway or is interrupted
+    let result = match (&remote, builtin) {
+        (Some(remote), _) => {
+            init_project_in_dir(&target_dir, || remote.instantiate(&name, settings))
+        }
+        (None, Some(template)) => init_project_in_dir(&target_dir, || {
+            write_project_files(&name, template, settings)
+        }),
+        (None, None) => unreachable!("the template was resolved above"),
+    };
+    let result = result.and_then(|_| {
+        if interrupt::interrupted() {
+            Err(Error::failure("interrupted"))
+        } else {
+            Ok(())
+        }
+    });
+    if let Err(e) = result {
+        if let Err(cleanup) = safe_path::remove_dir_all(&target_dir) {
+            log::problem!("Warning: failed to remove {}: {}", target_dir.display(), cleanup);
+        }
+        return Err(e);
+    }
+
+    let paths = match builtin {
+        Some(template) => project_paths(&name, template, settings),
+        None => scaffold::walk(&target_dir),
+    };
+    log::status!("✓ Project '{}' created successfully:", name);
+    scaffold::print(&root, &paths, git, false);
+    Ok(())
+}
+
+/// Initialize a project in the current directory
+fn cmd_init(project_name: &str, settings: &NewProject, dry_run: bool) -> error::Result<()> {
+    let target_dir = std::env::current_dir()
+        .context(|| "failed to read the current directory".to_string())?;
+
+    // Safety check: directory must be empty (except hidden files)
+    if !is_dir_empty(&target_dir).context(|| format!("failed to list {}", target_dir.display()))? {
+        return Err(Error::usage(
+            "Directory is not empty. c1 init must be run in an empty directory.",
+        )
+        .hint("to adopt the code already here, run `c1 init --existing`"));
+    }
+
+    let template = templates::find(templates::DEFAULT_TEMPLATE).expect("default template exists");
+    let paths = project_paths(project_name, template, settings);
+    let git = scaffold::git_plan(settings.git);
+    if dry_run {
+        println!(
+            "Would initialize project '{}' in the current directory:",
+            project_name
+        );
+        scaffold::print("./", &paths, git, true);
+        return Ok(());
+    }
+
+    log::status!("Initializing project '{}'...", project_name);
+
+    // Initialize project in current directory
+    init_project_in_dir(&target_dir, || {
+        write_project_files(project_name, template, settings)
+    })?;
+
+    log::status!("✓ Project '{}' initialized successfully:", project_name);
+    scaffold::print("./", &paths, git, false);
+    Ok(())
+}
+
+/// Common function to initialize project files in a directory: run `write`
+/// with `target_dir` as the current directory
+fn init_project_in_dir(
+    target_dir: &Path,
+    write: impl FnOnce() -> error::Result<()>,
+) -> error::Result<()> {
+    // Switch to target directory for subsequent operations
+    let original_dir = std::env::current_dir()
+        .context(|| "failed to read the current directory".to_string())?;
+    if target_dir != original_dir {
+        std::env::set_current_dir(target_dir)
+            .context(|| format!("failed to enter {}", target_dir.display()))?;
+    }
+
+    let result = write();
+
+    // Restore original working directory
+    if target_dir != original_dir {
+        std::env::set_current_dir(&original_dir)
+            .context(|| format!("failed to return to {}", original_dir.display()))?;
+    }
+    result
+}
+
+/// Write the project skeleton into the current directory
+fn write_project_files(
+    project_name: &str,
+    template: &templates::Template,
+    settings: &NewProject,
+) -> error::Result<()> {
+    for (path, content) in project_files(project_name, template, settings) {
+        match content {
+            Some(content) => create_file(&path, &content)?,
+            None => fs::create_dir_all(&path).context(|| format!("failed to create {}", path))?,
+        }
+    }
+
+    git_init(settings.git);
+
+    Ok(())
+}
+
+/// The project skeleton in the order it is written, as (path, content);
+/// directories end in `/` and have no content
+fn project_files(
+    project_name: &str,
+    template: &templates::Template,
+    settings: &NewProject,
+) -> Vec<(String, Option<String>)> {
+    // Directory structure
+    let mut files: Vec<(String, Option<String>)> = ["src", "include", "external", "build"]
+        .iter()
+        .chain(template.extra_dirs)
+        .map(|dir| (format!("{}/", dir), None))
+        .collect();
+
+    // main.c, CMakeLists.txt and any other template files
+    for (path, content) in template.files(
+        project_name,
+        c_standard(&settings.edition),
+        settings.layout.main_source(),
+    ) {
+        files.push((path, Some(content)));
+    }
 
-    // Create project.toml
-    let project_toml = format!(
-        r#"[project]
+    files.push((
+        "project.toml".to_string(),
+        Some(project_toml(project_name, template, settings)),
+    ));
+    if let Some(license) = settings.license {
+        files.push(("LICENSE".to_string(), Some(license.render(project_name))));
+    }
+    files.push((
+        "README.md".to_string(),
+        Some(readme(project_name, settings)),
+    ));
+    // .gitignore is also useful to an enclosing repository
+    files.push((
+        ".gitignore".to_string(),
+        Some(vendor::gitignore(GITIGNORE, settings.vendor)),
+    ));
+    // .clang-format for `c1 fmt`
+    files.push((
+        ".clang-format".to_string(),
+        Some(fmt::CLANG_FORMAT.to_string()),
+    ));
+    files
+}
+
+/// The paths `write_project_files` creates
+fn project_paths(
+    project_name: &str,
+    template: &templates::Template,
+    settings: &NewProject,
+) -> Vec<String> {
+    project_files(project_name, template, settings)
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect()
+}
+
+/// The project.toml of a new project
+fn project_toml(project_name: &str, template: &templates::Template, settings: &NewProject) -> String {
+    // The template's flags, then any the user adds to every new project
+    let mut flags: Vec<String> = template.flags.iter().map(|f| f.to_string()).collect();
+    for flag in user_config::list("defaults.new.flags") {
+        if !flags.contains(&flag) {
+            flags.push(flag);
+        }
+    }
+    format!(
+        r#"schema_version = {}
+
+[project]
 name = "{}"
 version = "0.1.0"
-edition = "c99"
-description = "A C project created with c1"
-
+edition = "{}"
+description = {}
+{}{}{}{}{}{}
 [dependencies]
 # Add your dependencies here
 # Example:
 # arc-c = {{ git = "https://github.com/weynechen/arc-c.git", tag = "v0.5.0" }}
 
 [build]
-compiler = "gcc"
-flags = ["-O3", "-Wall"]
+compiler = {}
+flags = [{}]
+warnings = "{}"  # none, default, strict or pedantic
 "#,
-        project_name
-    );
-    fs::write("project.toml", project_toml).expect("Failed to create project.toml");
+        manifest_upgrade::SCHEMA_VERSION,
+        project_name,
+        settings.edition,
+        toml::Value::String(settings.description.clone()),
+        if settings.authors.is_empty() {
+            String::new()
+        } else {
+            let authors: Vec<String> = settings
+                .authors
+                .iter()
+                .map(|a| toml::Value::String(a.clone()).to_string())
+                .collect();
+            format!("authors = [{}]\n", authors.join(", "))
+        },
+        settings
+            .repository
+            .as_ref()
+            .map_or(String::new(), |url| format!(
+                "repository = {}\n",
+                toml::Value::String(url.clone())
+            )),
+        if template.library { "type = \"lib\"\n" } else { "" },
+        if settings.layout == Layout::Src { "layout = \"src\"  # main.c is in src/\n" } else { "" },
+        settings.license.map_or(String::new(), |l| format!("license = \"{}\"\n", l.id)),
+        if settings.vendor { "vendor = true  # external/ is committed, not ignored\n" } else { "" },
+        toml::Value::String(settings.compiler.clone()),
+        flags
+            .iter()
+            .map(|f| format!("\"{}\"", f))
+            .collect::<Vec<_>>()
+            .join(", "),
+        settings.warnings
+    )
+}
 
-    // Create README.md
-    let readme = format!(
+/// The README.md of a new project
+fn readme(project_name: &str, settings: &NewProject) -> String {
+    let (main, src) = match settings.layout {
+        Layout::Flat => ("├── main.c         # Main entry point\n", "Source files"),
+        Layout::Src => ("", "Source files, main.c is the main entry point"),
+    };
+    format!(
         r#"# {}
 
-A C project created with c1.
-
+{}
+{}
 ## Building
 
 ```bash
@@ -276,489 +2565,3518 @@ c1 run
 
 ## Project Structure
 
-```txt
-.
-├── main.c         # Main entry point
-├── CMakeLists.txt
-├── include/       # Header files
-├── src/           # Source files
-├── external/      # External dependencies
-├── build/         # Build output directory
-└── project.toml   # Project configuration
-```
-"#,
-        project_name
-    );
-    fs::write("README.md", readme).expect("Failed to create README.md");
+```txt
+.
+{}├── CMakeLists.txt
+├── include/       # Header files
+├── src/           # {}
+├── external/      # External dependencies
+├── build/         # Build output directory
+└── project.toml   # Project configuration
+```
+"#,
+        project_name,
+        sentence(&settings.description),
+        settings
+            .repository
+            .as_ref()
+            .map_or(String::new(), |url| format!("\nSource: {}\n", url)),
+        main,
+        src
+    )
+}
+
+/// `text` ending in a full stop
+fn sentence(text: &str) -> String {
+    if text.is_empty() || text.ends_with(['.', '!', '?']) {
+        text.to_string()
+    } else {
+        format!("{}.", text)
+    }
+}
+
+/// The .gitignore of a new project
+const GITIGNORE: &str = r#"# Build directory 
+/build
+
+# c1 working state
+/.c1
+
+# Link to build/compile_commands.json for clangd
+/compile_commands.json
+
+# IDE
+/.idea
+/.vscode
+
+# Compiled files
+*.o
+*.a
+*.so
+*.exe
+/cmake-build-*
+"#;
+
+/// Run git init, unless asked not to or it would nest a repository inside
+/// the one the project is being created in
+fn git_init(git: bool) {
+    let inside_repo = || {
+        run_git(".", &["rev-parse", "--is-inside-work-tree"]).is_ok_and(|out| out == "true")
+    };
+    if git && inside_repo() {
+        log::status!("Inside an existing git repository, skipping git init");
+    } else if git {
+        let mut init = Command::new("git");
+        init.arg("init");
+        log::command(&init);
+        match init.output() {
+            Ok(output) => {
+                if output.status.success() {
+                    log::status!("Initialized git repository");
+                } else {
+                    log::problem!("Warning: git init failed");
+                }
+            }
+            Err(_) => {
+                log::problem!("Warning: git not found, skipping git init");
+            }
+        }
+    }
+}
+
+/// Write a scaffolded file, naming it in the error
+fn create_file(path: &str, content: &str) -> error::Result<()> {
+    safe_path::write(path, content).context(|| format!("failed to create {}", path))?;
+    events::file_created(path);
+    Ok(())
+}
+
+/// What `c1 create` generates besides the module's name and platforms
+struct NewModule {
+    kind: module_kinds::Kind,
+    /// Also tests/test_<name>.c
+    with_test: bool,
+    doc_comments: bool,
+    /// Only include/<name>.h
+    header_only: bool,
+    /// Register the files in CMakeLists.txt
+    cmake: bool,
+}
+
+fn cmd_create(
+    name: String,
+    platforms: Vec<String>,
+    add_platform: Option<String>,
+    module: &NewModule,
+) -> error::Result<()> {
+    let NewModule {
+        kind,
+        with_test,
+        doc_comments,
+        header_only,
+        cmake,
+    } = *module;
+    check_module_name(&name)?;
+    if name.contains('/') && (add_platform.is_some() || !platforms.is_empty()) {
+        return Err(Error::usage(
+            "Platform modules cannot be nested in subdirectories",
+        ));
+    }
+
+    // Root discovery has moved to the nearest project.toml, if there is one;
+    // without it the module would land in whatever directory this is
+    if !Path::new("project.toml").is_file() {
+        return Err(Error::usage(
+            "not inside a c1 project (no project.toml here or in any parent)",
+        )
+        .hint(format!(
+            "run `c1 init` first to scaffold the project, then `c1 create {}`",
+            name
+        )));
+    }
+
+    // Recreate the layout directories, which git drops when they are empty
+    fs::create_dir_all("src").context(|| "failed to create src/".to_string())?;
+    fs::create_dir_all("include").context(|| "failed to create include/".to_string())?;
+
+    let test_file = format!("tests/test_{}.c", name.replace('/', "_"));
+    if with_test && Path::new(&test_file).exists() {
+        return Err(Error::usage(format!("{} already exists", test_file)));
+    }
+
+    if let Some(platform) = add_platform {
+        return platforms::cmd_add_platform(&name, &platform);
+    }
+    if !platforms.is_empty() {
+        platforms::cmd_create_platforms(&name, &platforms, doc_comments)?;
+        if with_test {
+            create_module_test(&name, &test_file)?;
+        }
+        return Ok(());
+    }
+
+    let src_file = format!("src/{}.c", name);
+    let header_file = format!("include/{}.h", name);
+
+    // Check if files already exist
+    if !header_only && Path::new(&src_file).exists() {
+        return Err(Error::usage(format!("{} already exists", src_file)));
+    }
+    if Path::new(&header_file).exists() {
+        return Err(Error::usage(format!("{} already exists", header_file)));
+    }
+
+    if header_only {
+        create_header_only_module(&name, &header_file, doc_comments)?;
+        if cmake {
+            update_cmake_lists(&name, false)?;
+        }
+        if with_test {
+            create_module_test(&name, &test_file)?;
+        }
+        return Ok(());
+    }
+
+    let header_content = module_kinds::header(&name, kind, doc_comments);
+    let src_content = module_kinds::source(&name, kind);
+
+    // Nested modules live in subdirectories of src/ and include/
+    for file in [&src_file, &header_file] {
+        if let Some(dir) = Path::new(file).parent() {
+            fs::create_dir_all(dir).context(|| format!("failed to create {}/", dir.display()))?;
+        }
+    }
+
+    // Write files
+    safe_path::write(&src_file, &src_content)
+        .context(|| format!("failed to create {}", src_file))?;
+    if let Err(e) = safe_path::write(&header_file, &header_content) {
+        // Best-effort rollback of the source file
+        let _ = safe_path::remove_file(&src_file);
+        return Err(e).context(|| format!("failed to create {}", header_file));
+    }
+
+    log::status!("✓ Created {} and {}", src_file, header_file);
+    events::file_created(&src_file);
+    events::file_created(&header_file);
+
+    if cmake {
+        update_cmake_lists(&name, true)?;
+    }
+
+    if with_test {
+        create_module_test(&name, &test_file)?;
+    }
+    Ok(())
+}
+
+/// Write tests/test_<name>.c and register it as a CTest test
+fn create_module_test(name: &str, test_file: &str) -> error::Result<()> {
+    fs::create_dir_all("tests").context(|| "failed to create tests/".to_string())?;
+    let test_content = format!(
+        r#"#include <assert.h>
+
+#include "{}.h"
+
+int main(void) {{
+    // TODO: Exercise the {} module
+    assert(1);
+    return 0;
+}}
+"#,
+        name, name
+    );
+    create_file(test_file, &test_content)?;
+    log::status!("✓ Created {}", test_file);
+
+    register_test(&format!("test_{}", name.replace('/', "_")), test_file)
+}
+
+/// Module names are identifiers, optionally nested with `/` (`drivers/uart`)
+fn check_module_name(name: &str) -> error::Result<()> {
+    let valid = name.split('/').all(|part| {
+        !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_')
+    });
+    if !valid {
+        return Err(Error::usage(format!(
+            "Invalid module name '{}': use letters, numbers and underscores, with '/' between directories (e.g. drivers/uart)",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Where tests find `c1_test.h` from `c1 test --init`
+const TEST_SUPPORT_INCLUDE: &str = "${CMAKE_CURRENT_SOURCE_DIR}/tests/support";
+
+/// Add a test executable to CMakeLists.txt, creating the tests block (which
+/// links every project source except main.c) on first use
+fn register_test(target: &str, test_file: &str) -> error::Result<()> {
+    let cmake_path = "CMakeLists.txt";
+    let Ok(mut content) = fs::read_to_string(cmake_path) else {
+        log::problem!("Warning: CMakeLists.txt not found, skipping test registration");
+        return Ok(());
+    };
+
+    let placeholder = "# @c1_tests";
+    if !content.contains(placeholder) {
+        if !content.ends_with('\n') {
+            content.push('\n');
+        }
+        content.push_str(&format!(
+            r#"
+# Tests: one executable per file in tests/, run with `ctest --test-dir build`
+enable_testing()
+set(C1_TEST_SOURCES ${{SOURCES}})
+list(REMOVE_ITEM C1_TEST_SOURCES {})
+{}
+"#,
+            main_source(),
+            placeholder
+        ));
+    }
+
+    let entry = format!(
+        r#"add_executable({target} {test_file} ${{C1_TEST_SOURCES}})
+target_include_directories({target} PRIVATE ${{CMAKE_CURRENT_SOURCE_DIR}}/include {TEST_SUPPORT_INCLUDE})
+target_link_libraries({target} PRIVATE ${{C1_DEV_LIBRARIES}})
+add_test(NAME {target} COMMAND {target})
+{placeholder}"#
+    );
+    let new_content = content.replacen(placeholder, &entry, 1);
+
+    safe_path::write(cmake_path, new_content)
+        .context(|| format!("failed to update {}", cmake_path))?;
+    log::status!("✓ Registered {} in CMakeLists.txt", target);
+    Ok(())
+}
+
+/// Delete a module's files and unregister them from CMakeLists.txt
+fn cmd_destroy(name: String, force: bool) -> error::Result<()> {
+    check_module_name(&name)?;
+
+    let src_file = format!("src/{}.c", name);
+    let header_file = format!("include/{}.h", name);
+    let cmake = fs::read_to_string("CMakeLists.txt").unwrap_or_default();
+    let registered = |file: &str| cmake.lines().any(|line| line.trim() == file);
+
+    let files: Vec<&String> = [&src_file, &header_file]
+        .into_iter()
+        .filter(|f| Path::new(f).exists())
+        .collect();
+    if files.is_empty() && !registered(&src_file) && !registered(&header_file) {
+        return Err(Error::usage(format!(
+            "Module '{}' not found ({} and {} do not exist)",
+            name, src_file, header_file
+        )));
+    }
+
+    // Refuse to break sources that still include the header
+    let users = header_users(&name, &src_file);
+    if !users.is_empty() {
+        let included = format!(
+            "{} is still included by:\n  {}",
+            header_file,
+            users.join("\n  ")
+        );
+        if !force {
+            return Err(Error::usage(included)
+                .hint("remove those includes first, or pass --force to delete the module anyway"));
+        }
+        log::problem!("Warning: {}", included);
+    }
+
+    for file in files {
+        safe_path::remove_file(file).context(|| format!("failed to remove {}", file))?;
+        log::status!("✓ Removed {}", file);
+    }
+
+    if registered(&src_file) || registered(&header_file) {
+        let mut updated: String = cmake
+            .lines()
+            .filter(|line| line.trim() != src_file && line.trim() != header_file)
+            .collect::<Vec<_>>()
+            .join("\n");
+        if cmake.ends_with('\n') {
+            updated.push('\n');
+        }
+        safe_path::write("CMakeLists.txt", updated)
+            .context(|| "failed to update CMakeLists.txt".to_string())?;
+        log::status!("✓ Updated CMakeLists.txt");
+    }
+    Ok(())
+}
+
+/// `file:line` of every project source that includes `<name>.h`, other than
+/// the module's own source file
+fn header_users(name: &str, own_source: &str) -> Vec<String> {
+    let include = format!("#include \"{}.h\"", name);
+    let mut files = Vec::new();
+    if Path::new("main.c").is_file() {
+        files.push(PathBuf::from("main.c"));
+    }
+    fmt::collect(Path::new("src"), &mut files);
+    fmt::collect(Path::new("include"), &mut files);
+
+    let mut users = Vec::new();
+    for file in files {
+        if file == Path::new(own_source) {
+            continue;
+        }
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        for (i, line) in content.lines().enumerate() {
+            let compact: String = line.split_whitespace().collect::<Vec<_>>().join(" ");
+            if compact.starts_with(&include) {
+                users.push(format!("{}:{}", file.display(), i + 1));
+            }
+        }
+    }
+    users
+}
+
+/// Header for a new module, with an include guard
+/// Write include/<name>.h for `c1 create --header-only`
+fn create_header_only_module(
+    name: &str,
+    header_file: &str,
+    doc_comments: bool,
+) -> error::Result<()> {
+    if let Some(dir) = Path::new(header_file).parent() {
+        fs::create_dir_all(dir).context(|| format!("failed to create {}/", dir.display()))?;
+    }
+    create_file(header_file, &header_only_module(name, doc_comments))?;
+    log::status!("✓ Created {}", header_file);
+    Ok(())
+}
+
+/// A module header with a commented stub for the single-header
+/// `<NAME>_IMPLEMENTATION` pattern, for definitions too big to inline
+fn header_only_module(name: &str, doc_comments: bool) -> String {
+    let header = module_header(name, doc_comments);
+    let macro_name = format!("{}_IMPLEMENTATION", name.replace('/', "_").to_uppercase());
+    let stub = format!(
+        r#"// Definitions that should not be inline can go in the block below. Exactly
+// one .c file then compiles them by defining the macro before including:
+//
+//     #define {macro_name}
+//     #include "{name}.h"
+//
+// #ifdef {macro_name}
+// #endif // {macro_name}
+
+"#
+    );
+    let end = header.rfind("#endif").unwrap_or(header.len());
+    format!("{}{}{}", &header[..end], stub, &header[end..])
+}
+
+fn module_header(name: &str, doc_comments: bool) -> String {
+    let declarations = if doc_comments {
+        r#"/**
+ * @brief TODO: Describe this function.
+ *
+ * @param TODO
+ * @return TODO
+ */
+// TODO: Add your declarations here"#
+    } else {
+        "// TODO: Add your declarations here"
+    };
+    header_with(name, doc_comments, declarations)
+}
+
+/// A module header around `declarations`, with its guard and, for doxygen,
+/// a @file block
+fn header_with(name: &str, doc_comments: bool, declarations: &str) -> String {
+    // Create header guard macro name from the full path: drivers/uart -> _DRIVERS_UART_H
+    let guard_name = format!("_{}_H", name.replace('/', "_").to_uppercase());
+
+    // Doxygen skips files without a @file block unless EXTRACT_ALL is set
+    let file_doc = if doc_comments {
+        format!(
+            r#"/**
+ * @file {}.h
+ * @brief TODO: Describe the {} module.
+ */
+
+"#,
+            name, name
+        )
+    } else {
+        String::new()
+    };
+
+    format!(
+        r#"{}#ifndef {}
+#define {}
+
+{}
+
+#endif // {}
+"#,
+        file_doc, guard_name, guard_name, declarations, guard_name
+    )
+}
+
+/// Source file for a new module, including its header
+fn module_source(name: &str) -> String {
+    format!(
+        r#"#include "{}.h"
+
+// TODO: Add your implementation here
+"#,
+        name
+    )
+}
+
+/// Register a module's header, and its source when it has one
+fn update_cmake_lists(module_name: &str, with_source: bool) -> error::Result<()> {
+    let cmake_path = "CMakeLists.txt";
+    if auto_sources::is_auto() {
+        log::verbose!("source_mode is auto: the next build lists the new files");
+        return Ok(());
+    }
+    
+    if !Path::new(cmake_path).exists() {
+        log::problem!("Warning: CMakeLists.txt not found, skipping automatic registration");
+        return Ok(());
+    }
+
+    let content = match fs::read_to_string(cmake_path) {
+        Ok(content) => content,
+        Err(e) => {
+            log::problem!("Warning: failed to read CMakeLists.txt ({}), skipping automatic registration", e);
+            return Ok(());
+        }
+    };
+
+    let mut entries = vec![format!("include/{}.h", module_name)];
+    if with_source {
+        entries.insert(0, format!("src/{}.c", module_name));
+    }
+    let mut new_content = content.clone();
+    for entry in &entries {
+        if let cmake_lists::Registration::NoList = cmake_lists::register(&mut new_content, entry) {
+            cmake_lists::warn_unregistered(entry);
+        }
+    }
+    if new_content == content {
+        return Ok(());
+    }
+
+    safe_path::write(cmake_path, new_content)
+        .context(|| format!("failed to update {}", cmake_path))?;
+
+    log::status!("✓ Updated CMakeLists.txt");
+    Ok(())
+}
+
+/// Load and parse project.toml
+fn read_config() -> error::Result<ProjectConfig> {
+    let config_path = "project.toml";
+
+    if !Path::new(config_path).exists() {
+        return Err(Error::usage("project.toml not found. Are you in a c1 project?"));
+    }
+
+    let content =
+        fs::read_to_string(config_path).context(|| format!("failed to read {}", config_path))?;
+    let config: ProjectConfig = toml::from_str(&content).map_err(|e| {
+        let error = Error::config(format!("failed to parse project.toml: {}", e));
+        if manifest_upgrade::is_old(&content) {
+            error.hint("it may be from an older c1; `c1 upgrade-manifest` updates it")
+        } else {
+            error
+        }
+    })?;
+    manifest_upgrade::hint(config.schema_version);
+    Ok(config)
+}
+
+/// `read_config` for callers that don't return errors, exiting on failure
+fn load_config() -> ProjectConfig {
+    read_config().unwrap_or_else(|e| e.exit())
+}
+
+/// Get project name from the project.toml in `dir`
+fn get_project_name_from_config(dir: &Path) -> Option<String> {
+    let config_path = dir.join("project.toml");
+    if !config_path.exists() {
+        return None;
+    }
+    let content = fs::read_to_string(config_path).ok()?;
+    let config: ProjectConfig = toml::from_str(&content).ok()?;
+    Some(config.project.name)
+}
+
+/// Where a build with these options goes: each `--variant`, each `--target`
+/// and each profile gets its own subdirectory so host and cross builds, or
+/// debug and release builds, never share a CMake cache
+fn target_build_dir(options: &BuildOptions) -> PathBuf {
+    let mut build_dir = resolve_build_dir(options.out_dir.as_deref());
+    if let Some(variant) = &options.variant {
+        build_dir.push("variants");
+        build_dir.push(variant);
+    }
+    if let Some(target) = &options.target_config {
+        build_dir.push(target);
+    }
+    build_dir.push(profiles::dir_name(
+        options.profile.as_deref(),
+        options.release,
+    ));
+    if let Some(subdir) = sanitize::subdir(&options.sanitize) {
+        build_dir.push(subdir);
+    }
+    if options.coverage {
+        build_dir.push(coverage::SUBDIR);
+    }
+    build_dir
+}
+
+/// The build directory: `--out-dir`, then `[build] out_dir`, then `build`.
+/// Relative paths are relative to the project root. A configured one is
+/// c1's to clean even outside the project.
+fn resolve_build_dir(out_dir: Option<&str>) -> PathBuf {
+    let configured = out_dir.map(str::to_string).or_else(|| {
+        let content = fs::read_to_string("project.toml").ok()?;
+        let config: ProjectConfig = toml::from_str(&content).ok()?;
+        config.build.out_dir
+    });
+    if let Some(dir) = &configured {
+        safe_path::allow_root(dir);
+    }
+    PathBuf::from(configured.unwrap_or_else(|| "build".to_string()))
+}
+
+/// Build the project with cmake
+fn cmd_build(options: &BuildOptions) -> error::Result<()> {
+    let started = Instant::now();
+    let _log = build_log::start(&resolve_build_dir(options.out_dir.as_deref()));
+    sanitize::check(&options.sanitize)?;
+    let build_dir = target_build_dir(options);
+    log::verbose!("Build directory: {}", build_dir.display());
+
+    // A bare CMake project without project.toml still builds
+    let config = (Path::new("project.toml").exists()
+        || options.target_config.is_some()
+        || !options.features.is_empty())
+    .then(read_config)
+    .transpose()?;
+    let no_profiles = BTreeMap::new();
+    let profile = profiles::resolve(
+        config.as_ref().map_or(&no_profiles, |c| &c.profile),
+        options.release,
+        options.profile.as_deref(),
+    )?;
+    let build_type = profile.build_type;
+    let variant = match (&options.variant, &config) {
+        (Some(name), Some(config)) => Some(variants::get(&config.variant, name)?),
+        (Some(name), None) => {
+            return Err(Error::config(format!(
+                "variant '{}' needs a project.toml to declare it",
+                name
+            )));
+        }
+        (None, _) => None,
+    };
+    let platform = match &config {
+        Some(config) => platforms::active_platform(config, options.target_config.as_deref())
+            .map_err(Error::usage)?,
+        None => None,
+    };
+    // -j wins over `[build] jobs`, then the user's config, then the CPU count
+    let jobs = options
+        .jobs
+        .or_else(|| config.as_ref().and_then(|c| c.build.jobs))
+        .or_else(|| user_config::count("build.jobs"))
+        .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1);
+    if jobs == 0 {
+        return Err(Error::usage("the number of build jobs must be at least 1"));
+    }
+
+    if let Some(config) = &config {
+        // Dev-dependencies left out by `c1 sync --no-dev` are not missing
+        let mut declared = config.all_dependencies();
+        declared.retain(|name, _| {
+            config.dependencies.contains_key(name) || Path::new("external").join(name).exists()
+        });
+        if !options.no_verify_deps {
+            dep_check::verify(&declared)?;
+        }
+        dep_wiring::refresh(&declared);
+        exports::check(&dep_wiring::wired_external())?;
+    }
+    let missing = dep_wiring::missing();
+    if !missing.is_empty() {
+        return Err(Error::config(format!(
+            "dependencies not present in external/: {}",
+            missing.join(", ")
+        ))
+        .hint("run `c1 sync` to fetch them"));
+    }
+    warn_on_dependency_drift();
+    let mut targets = options.targets.clone();
+    if options.examples {
+        targets.extend(examples::require()?);
+    }
+    if let Some(config) = &config {
+        generate::run(&config.generate)?;
+        bins::wire(&config.project.name, &config.bin)?;
+        examples::wire(config)?;
+        links::wire(&config.build.links)?;
+        include_dirs::wire(&config.build.include_dirs)?;
+        version_header::generate(config, &build_dir)?;
+        auto_sources::sync(config)?;
+        source_check::run(options.fix_sources)?;
+    }
+
+    let doing = if options.configure_only {
+        "Configuring"
+    } else {
+        "Building"
+    };
+    if let Some(name) = &options.variant {
+        log::status!("Variant: {}", name);
+    }
+    match &options.profile {
+        Some(name) => log::status!(
+            "{} project ({} profile, {} mode)...",
+            doing,
+            name,
+            build_type
+        ),
+        None => log::status!("{} project ({} mode)...", doing, build_type),
+    }
+    let profile_name = match &options.profile {
+        Some(name) => name.as_str(),
+        None if options.release => "release",
+        None => "dev",
+    };
+    events::build_started(profile_name, build_type, &build_dir);
+
+    // Step 1: cmake -B build -DCMAKE_BUILD_TYPE=...
+    let mut defines = Vec::new();
+    if let Some(platform) = platform {
+        defines.push(format!("-DC1_PLATFORM={}", platform));
+    }
+    let target = options
+        .target_config
+        .as_ref()
+        .and_then(|name| config.as_ref()?.target.get(name));
+    if let Some(toolchain) = target.and_then(|t| t.toolchain.as_ref()) {
+        let path = Path::new(toolchain);
+        if !path.is_file() {
+            return Err(Error::config(format!("toolchain file {} not found", toolchain)));
+        }
+        // cmake resolves a relative toolchain path against the build directory
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        defines.push(format!("-DCMAKE_TOOLCHAIN_FILE={}", path.display()));
+    }
+    // A preset's prefix names the tools, unless a toolchain file does
+    let prefix = target
+        .filter(|t| t.toolchain.is_none())
+        .and_then(|t| t.prefix.as_deref());
+    if let Some(prefix) = prefix {
+        if options.compiler.is_none() {
+            cross::check_compiler(prefix)?;
+        }
+        defines.extend(cross::configure_args(prefix));
+    }
+    defines.extend(profile.cmake_args);
+    // A toolchain file or preset names its own compiler, so only --compiler
+    // replaces it
+    let compiler = match (target.and_then(|t| t.toolchain.as_ref()), prefix) {
+        (Some(_), _) => options
+            .compiler
+            .clone()
+            .map(|compiler| (compiler, "--compiler")),
+        (None, Some(prefix)) => Some(match &options.compiler {
+            Some(compiler) => (compiler.clone(), "--compiler"),
+            None => (cross::compiler(prefix), "the target's prefix"),
+        }),
+        (None, None) => resolve_compiler(options.compiler.as_deref(), config.as_ref()),
+    };
+    match &compiler {
+        Some((compiler, source)) => {
+            log::verbose!("C compiler: {} (from {})", compiler, source);
+            if *source != "CC"
+                && let Ok(cc) = std::env::var("CC")
+                && cc.trim() != compiler
+            {
+                log::verbose!("Ignoring CC={}: {} names the compiler", cc, source);
+            }
+            defines.push(format!("-DCMAKE_C_COMPILER={}", compiler));
+        }
+        None => log::verbose!("C compiler: cmake's default"),
+    }
+    reset_if_compiler_changed(&build_dir, compiler.as_ref().map(|(c, _)| c.as_str()))?;
+    // The launcher wraps whichever compiler was chosen above
+    let launcher = compiler_cache::launcher(
+        config.as_ref().and_then(|c| c.build.cache.as_deref()),
+        options.no_cache,
+    )?;
+    defines.extend(compiler_cache::configure_arg(&build_dir, launcher));
+
+    let shown = |flags: &[String]| {
+        if flags.is_empty() {
+            "(none)".to_string()
+        } else {
+            flags.join(" ")
+        }
+    };
+    // Later flags win, so the environment's come first, then `[build]
+    // flags`, and the command line's last; the warning preset goes on the
+    // project's target only
+    let mut c_flags = env_flags("CFLAGS");
+    if let Some(config) = &config {
+        c_flags.extend(config.build.flags.iter().cloned());
+    }
+    let mut linker_flags = env_flags("LDFLAGS");
+    if let Some(target) = target {
+        let (cpu_flags, link_flags) = cross::flags(target);
+        c_flags.extend(cpu_flags);
+        c_flags.extend(target.flags.iter().cloned());
+        linker_flags.extend(link_flags);
+    }
+    if let Some(variant) = variant {
+        c_flags.extend(variant.flags.iter().cloned());
+    }
+    // A variant's linker script replaces the target's
+    let linker_script = variant
+        .and_then(|v| v.linker_script.as_ref())
+        .or_else(|| target?.linker_script.as_ref());
+    if let Some(script) = linker_script {
+        linker_flags.push(cross::linker_script(script)?);
+    }
+    // `[build] defines`, the variant's, the profile's, then --define, so the
+    // command line wins
+    let mut macros = config
+        .as_ref()
+        .map(|c| c.build.defines.clone())
+        .unwrap_or_default();
+    if let Some(variant) = variant {
+        macros.extend(variant.defines.iter().cloned());
+    }
+    macros.extend(profile.defines);
+    macros.extend(options.defines.iter().cloned());
+    check_defines(&macros)?;
+    log::verbose!("Defines: {}", shown(&macros));
+    c_flags.extend(macros.iter().map(|d| format!("-D{}", d)));
+    c_flags.extend(profile.c_flags);
+    if let Some(flags) = sanitize::flags(&options.sanitize) {
+        c_flags.push(flags.clone());
+        linker_flags.push(flags);
+    }
+    if options.coverage {
+        c_flags.push(coverage::FLAGS.to_string());
+        linker_flags.push(coverage::FLAGS.to_string());
+    }
+    // --emit wins over `[build] emit`
+    let emit = match (&options.emit, &config) {
+        (emit, _) if !emit.is_empty() => emit.clone(),
+        (_, Some(config)) => {
+            emit::check(&config.build.emit)?;
+            config.build.emit.clone()
+        }
+        (_, None) => Vec::new(),
+    };
+    let name = get_project_name_from_config(Path::new(".")).unwrap_or_else(get_current_dir_name);
+    linker_flags.extend(emit::link_flags(&emit, &build_dir, &name));
+    log::verbose!("C flags: {}", shown(&c_flags));
+    log::verbose!("Linker flags: {}", shown(&linker_flags));
+    // Always passed, empty when there are none, so flags dropped since the
+    // last configure do not stay in cmake's cache
+    defines.push(format!("-DCMAKE_C_FLAGS={}", c_flags.join(" ")));
+    let linker_flags = linker_flags.join(" ");
+    defines.push(format!("-DCMAKE_EXE_LINKER_FLAGS={}", linker_flags));
+    defines.push(format!("-DCMAKE_SHARED_LINKER_FLAGS={}", linker_flags));
+    if let Some(config) = &config {
+        // The manifest, not CMakeLists.txt, decides the C standard
+        defines.push(format!(
+            "-DCMAKE_C_STANDARD={}",
+            c_standard(&config.project.edition)
+        ));
+        let compiler = compiler.as_ref().map(|(c, _)| c.as_str());
+        defines.extend(warnings::configure_args(&warning_flags(config, compiler)?)?);
+        defines.extend(features::configure_args(
+            &config.features,
+            &options.features,
+            options.no_default_features,
+        )?);
+        defines.extend(variants::configure_args(variant)?);
+    }
+    let generator = options
+        .generator
+        .clone()
+        .or_else(|| config.as_ref().and_then(|c| c.build.generator.clone()))
+        .or_else(|| user_config::text("build.generator").map(str::to_string));
+    if let Some(generator) = generator {
+        reset_if_generator_changed(&build_dir, &generator)?;
+        defines.push("-G".to_string());
+        defines.push(generator);
+    }
+    let configure_started = Instant::now();
+    let cache = build_dir.join("CMakeCache.txt");
+    if options.configure_only && options.reconfigure && cache.is_file() {
+        safe_path::remove_file(&cache)
+            .context(|| format!("failed to remove {}", cache.display()))?;
+    }
+    if options.reconfigure || !configuration_is_current(&build_dir, build_type, &defines) {
+        // Without a compiler named anywhere cmake picks cc, unless a
+        // toolchain file picks one
+        let checked = match &compiler {
+            Some((compiler, _)) => Some(compiler.as_str()),
+            None if target.and_then(|t| t.toolchain.as_ref()).is_none() => Some("cc"),
+            None => None,
+        };
+        let build = config.as_ref().map(|c| &c.build);
+        toolchain_check::check(
+            checked,
+            build.and_then(|b| b.min_cmake.as_deref()),
+            build.and_then(|b| b.min_compiler.as_deref()),
+        )?;
+        configure_project(&build_dir, build_type, &defines)?;
+    } else {
+        log::status!("CMake configuration is up to date (pass --reconfigure to run it anyway)");
+    }
+    expose_compile_commands(&build_dir);
+    let configure_time = configure_started.elapsed();
+    if options.configure_only {
+        events::build_finished(true, started.elapsed(), None);
+        let database = build_dir.join(COMPILE_COMMANDS);
+        if database.is_file() {
+            log::status!("✓ Configured; compilation database at {}", database.display());
+            if Path::new(COMPILE_COMMANDS).exists() {
+                log::status!("  linked from ./{}", COMPILE_COMMANDS);
+            }
+        } else {
+            log::status!("✓ Configured {}", build_dir.display());
+        }
+        return Ok(());
+    }
+
+    // Step 2: cmake --build build
+    let inputs = header_deps::snapshot(&[]);
+    let tracker = build_stats::Tracker::start(&build_dir);
+    let mut build = Command::new("cmake");
+    build
+        .arg("--build")
+        .arg(&build_dir)
+        .args(["--config", build_type])
+        .args(["--parallel", &jobs.to_string()]);
+    for target in &targets {
+        build.args(["--target", target]);
+    }
+    if log::is_verbose() {
+        // Have the build tool echo every compiler command line
+        build.arg("--verbose");
+    }
+    // Diagnostics are shown grouped by file once the build is done
+    let cmake_build = stream::run_holding(&mut build, str::to_string, diagnostics::is_held);
+
+    let output = cmake_build.map_err(|e| {
+        Error::failure(format!("failed to run cmake --build: {}", e))
+            .hint("make sure cmake is installed")
+    })?;
+    let report = tracker.finish();
+    let found = diagnostics::collect(&[&output.stdout, &output.stderr]);
+    diagnostics::print(&found, &build_dir);
+    let warnings = diagnostics::count_own(&found, "warning", &build_dir);
+    let denied = warnings > 0 && config.as_ref().is_some_and(|c| c.build.warnings_as_errors);
+    let success = output.status.success() && !denied;
+    record_build(options, &build_dir, configure_time, &report, &output);
+    header_deps::record_build(&build_dir, success, inputs);
+    events::build_finished(success, started.elapsed(), artifact_path(options).as_deref());
+    if !output.status.success() {
+        return Err(Error::build("Build failed."));
+    }
+    if denied {
+        return Err(Error::build(format!(
+            "Build failed: {} warning{} and [build] warnings_as_errors is set.",
+            warnings,
+            if warnings == 1 { "" } else { "s" }
+        )));
+    }
+    log::status!("✓ Build completed successfully!");
+    report.print(options.timings);
+    if !emit.is_empty() && targets.is_empty() {
+        let executable = executable_path(&build_dir, &name, options.release).map_err(|_| {
+            Error::failure(format!("Executable for '{}' not found", name))
+                .hint("--emit works from the project's executable; a library has none")
+        })?;
+        emit::write(&emit, &build_dir, &executable, &name)?;
+    }
+    Ok(())
+}
+
+/// The project's executable from a build with these options, if there is one
+fn artifact_path(options: &BuildOptions) -> Option<PathBuf> {
+    let name = get_project_name_from_config(Path::new(".")).unwrap_or_else(get_current_dir_name);
+    executable_path(&target_build_dir(options), &name, options.release).ok()
+}
+
+/// Append this build to the local history shown by `c1 stats builds`
+fn record_build(
+    options: &BuildOptions,
+    build_dir: &Path,
+    configure_time: Duration,
+    report: &build_stats::Report,
+    output: &stream::Captured,
+) {
+    let mut warnings = 0;
+    let mut errors = 0;
+    for text in [&output.stdout, &output.stderr] {
+        for line in text.lines() {
+            if line.contains("warning:") {
+                warnings += 1;
+            } else if line.contains("error:") {
+                errors += 1;
+            }
+        }
+    }
+    let artifact_bytes = get_project_name_from_config(Path::new("."))
+        .and_then(|name| executable_path(build_dir, &name, options.release).ok())
+        .and_then(|path| fs::metadata(path).ok())
+        .map(|m| m.len());
+    let cache = report.cache();
+
+    build_history::append(&build_history::Record {
+        timestamp: build_history::now(),
+        profile: match &options.profile {
+            Some(name) => name.clone(),
+            None if options.release => "release".to_string(),
+            None => "debug".to_string(),
+        },
+        success: output.status.success(),
+        configure_ms: build_history::millis(configure_time),
+        build_ms: build_history::millis(report.duration()),
+        warnings,
+        errors,
+        recompiled: report.recompiled() as u64,
+        units: report.units() as u64,
+        cache_hits: cache.map(|(hits, _)| hits),
+        cache_misses: cache.map(|(_, misses)| misses),
+        artifact_bytes,
+    });
+}
+
+/// Where the build for the given profile left the executable, or every path
+/// that was tried.
+///
+/// Multi-config generators (Visual Studio, Xcode, Ninja Multi-Config) put
+/// each configuration in its own subdirectory, so `build/<name>` may be a
+/// stale artifact from another profile; prefer `build/<Config>/<name>`.
+/// On Windows the file carries an `.exe` suffix.
+fn executable_path(
+    build_dir: &Path,
+    project_name: &str,
+    release: bool,
+) -> Result<PathBuf, Vec<PathBuf>> {
+    let build_type = if release { "Release" } else { "Debug" };
+    let file_name = format!("{}{}", project_name, std::env::consts::EXE_SUFFIX);
+    let candidates = [
+        build_dir.join(build_type).join(&file_name),
+        build_dir.join(&file_name),
+    ];
+    match candidates.iter().find(|path| path.is_file()) {
+        Some(path) => Ok(path.clone()),
+        None => Err(candidates.to_vec()),
+    }
+}
+
+/// `path` as it can go into CMAKE_C_FLAGS or the linker flags, which the
+/// build tool hands to a shell that splits them at spaces
+fn flag_path(path: &Path) -> String {
+    let path = path.display().to_string();
+    if path.contains(char::is_whitespace) {
+        format!("\"{}\"", path)
+    } else {
+        path
+    }
+}
+
+/// `--option=<path>` as one argument, without going through a string that
+/// would lose a path that is not UTF-8
+fn path_arg(option: &str, path: &Path) -> std::ffi::OsString {
+    let mut arg = std::ffi::OsString::from(option);
+    arg.push(path);
+    arg
+}
+
+/// A variable a build directory was configured with, from CMakeCache.txt
+fn cached_value(build_dir: &Path, name: &str) -> Option<String> {
+    let cache = fs::read_to_string(build_dir.join("CMakeCache.txt")).ok()?;
+    cache.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.split(':').next() == Some(name)).then(|| value.to_string())
+    })
+}
+
+/// The C compiler a build uses and where it was chosen: `--compiler`, then
+/// `[build] compiler`, then `CC`; `None` leaves the choice to cmake
+fn resolve_compiler(
+    cli: Option<&str>,
+    config: Option<&ProjectConfig>,
+) -> Option<(String, &'static str)> {
+    if let Some(compiler) = cli {
+        return Some((compiler.to_string(), "--compiler"));
+    }
+    if let Some(compiler) = config.and_then(|c| c.build.compiler.as_ref()) {
+        return Some((compiler.clone(), "project.toml"));
+    }
+    std::env::var("CC")
+        .ok()
+        .map(|cc| cc.trim().to_string())
+        .filter(|cc| !cc.is_empty())
+        .map(|cc| (cc, "CC"))
+}
+
+/// The flags of the `[build] warnings` preset for `compiler`
+fn warning_flags(config: &ProjectConfig, compiler: Option<&str>) -> error::Result<Vec<String>> {
+    let preset = config.build.warnings.as_deref();
+    warnings::check(preset)?;
+    let as_errors = config.build.warnings_as_errors;
+    if preset.is_none_or(|preset| preset == "none") && !as_errors {
+        return Ok(Vec::new());
+    }
+    let family = warnings::Family::of(compiler);
+    let flags = warnings::flags(preset, family, as_errors);
+    log::verbose!(
+        "Warnings: {} for {}: {}",
+        preset.unwrap_or("none"),
+        family.name(),
+        flags.join(" ")
+    );
+    Ok(flags)
+}
+
+/// `NAME` or `NAME=VALUE`, with nothing cmake would split CMAKE_C_FLAGS at
+fn check_defines(defines: &[String]) -> error::Result<()> {
+    for define in defines {
+        let name = define.split('=').next().unwrap_or_default();
+        let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name || define.contains(char::is_whitespace) {
+            return Err(Error::usage(format!("invalid define '{}'", define))
+                .hint("defines are NAME or NAME=VALUE, without spaces"));
+        }
+    }
+    Ok(())
+}
+
+/// The flags in an environment variable such as `CFLAGS`, split on whitespace
+fn env_flags(name: &str) -> Vec<String> {
+    std::env::var(name)
+        .map(|value| value.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// cmake refuses to switch generators in a configured build directory, so
+/// drop the cache (keeping everything else) when a different one is asked for
+fn reset_if_generator_changed(build_dir: &Path, generator: &str) -> error::Result<()> {
+    let Some(cached) = cached_value(build_dir, "CMAKE_GENERATOR") else {
+        return Ok(());
+    };
+    if cached == generator {
+        return Ok(());
+    }
+    log::status!(
+        "Build directory was configured for {}, reconfiguring for {}...",
+        cached, generator
+    );
+    reset_cache(build_dir)
+}
+
+/// A compiler is fixed when a build directory is first configured, so drop
+/// the cache when the last configure (per its stamp) used a different one
+fn reset_if_compiler_changed(build_dir: &Path, compiler: Option<&str>) -> error::Result<()> {
+    let Ok(stamp) = fs::read_to_string(build_dir.join(CONFIGURE_STAMP)) else {
+        return Ok(());
+    };
+    if !build_dir.join("CMakeCache.txt").is_file() {
+        return Ok(());
+    }
+    let previous = stamp
+        .lines()
+        .find_map(|line| line.strip_prefix("-DCMAKE_C_COMPILER="));
+    if previous == compiler {
+        return Ok(());
+    }
+    log::status!(
+        "Build directory was configured for {}, reconfiguring for {}...",
+        previous.unwrap_or("cmake's default compiler"),
+        compiler.unwrap_or("cmake's default compiler")
+    );
+    reset_cache(build_dir)
+}
+
+/// Remove the CMake cache from `build_dir`, keeping the build outputs
+fn reset_cache(build_dir: &Path) -> error::Result<()> {
+    let cache = build_dir.join("CMakeCache.txt");
+    let cmake_files = build_dir.join("CMakeFiles");
+    let result = safe_path::remove_file(&cache).and_then(|_| {
+        if cmake_files.exists() {
+            safe_path::remove_dir_all(&cmake_files)
+        } else {
+            Ok(())
+        }
+    });
+    result
+        .context(|| format!("failed to reset {}", build_dir.display()))
+        .map_err(|e| e.hint("run `c1 clean` and build again."))
+}
+
+/// Where clangd looks for the compilation database
+const COMPILE_COMMANDS: &str = "compile_commands.json";
+
+/// Make the compilation database cmake wrote into `build_dir` visible at the
+/// project root: a symlink, or a copy refreshed on every build where
+/// symlinks are unavailable
+fn expose_compile_commands(build_dir: &Path) {
+    let source = build_dir.join(COMPILE_COMMANDS);
+    let link = Path::new(COMPILE_COMMANDS);
+    if !source.is_file() || fs::read_link(link).is_ok_and(|target| target == source) {
+        return;
+    }
+    let result = if fs::symlink_metadata(link).is_ok() {
+        safe_path::remove_file(link)
+    } else {
+        Ok(())
+    };
+    if let Err(e) = result.and_then(|_| link_file(&source, link)) {
+        log::problem!("Warning: failed to create {}: {}", COMPILE_COMMANDS, e);
+    }
+}
+
+/// Drop a root compile_commands.json symlink whose target is gone
+fn remove_dangling_compile_commands() {
+    let link = Path::new(COMPILE_COMMANDS);
+    if fs::read_link(link).is_ok() && !link.exists() {
+        let _ = safe_path::remove_file(link);
+    }
+}
+
+#[cfg(unix)]
+fn link_file(source: &Path, target: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(source, target).or_else(|_| fs::copy(source, target).map(|_| ()))
+}
+
+/// Symlinks need extra privileges on Windows, so fall back to copying
+#[cfg(not(unix))]
+fn link_file(source: &Path, target: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    if std::os::windows::fs::symlink_file(source, target).is_ok() {
+        return Ok(());
+    }
+    fs::copy(source, target).map(|_| ())
+}
+
+/// The arguments of the last successful configure, inside the build directory
+const CONFIGURE_STAMP: &str = "c1-configure.stamp";
+
+fn configure_fingerprint(build_type: &str, defines: &[String]) -> String {
+    let mut fingerprint = format!("-DCMAKE_BUILD_TYPE={}\n", build_type);
+    for define in defines {
+        fingerprint.push_str(define);
+        fingerprint.push('\n');
+    }
+    fingerprint
+}
+
+/// Whether configuring again would change nothing: the build directory was
+/// last configured with the same arguments (profile, flags, features,
+/// toolchain) and neither CMakeLists.txt nor project.toml changed since
+fn configuration_is_current(build_dir: &Path, build_type: &str, defines: &[String]) -> bool {
+    let Ok(configured) = fs::metadata(build_dir.join("CMakeCache.txt")).and_then(|m| m.modified())
+    else {
+        return false;
+    };
+    let stamp = fs::read_to_string(build_dir.join(CONFIGURE_STAMP)).unwrap_or_default();
+    if stamp != configure_fingerprint(build_type, defines)
+        || cached_value(build_dir, "CMAKE_BUILD_TYPE").as_deref() != Some(build_type)
+    {
+        return false;
+    }
+    ["CMakeLists.txt", "project.toml", auto_sources::GENERATED].iter().all(|file| {
+        fs::metadata(file)
+            .and_then(|m| m.modified())
+            .map_or(true, |modified| modified <= configured)
+    })
+}
+
+/// Run the cmake configure step with its output shown live
+fn configure_project(build_dir: &Path, build_type: &str, defines: &[String]) -> error::Result<()> {
+    let mut configure = Command::new("cmake");
+    configure
+        .arg("-B")
+        .arg(build_dir)
+        .arg(format!("-DCMAKE_BUILD_TYPE={}", build_type))
+        .args(defines);
+    if log::is_quiet() {
+        // Problems are reported on stderr, which stays
+        configure.stdout(std::process::Stdio::null());
+    }
+    log::command(&configure);
+    let started = Instant::now();
+    let status = configure.status().map_err(|e| {
+        build_log::finished(None, started.elapsed());
+        Error::failure(format!("failed to run cmake: {}", e)).hint("make sure cmake is installed")
+    })?;
+    build_log::finished(status.code(), started.elapsed());
+    if !status.success() {
+        let _ = safe_path::remove_file(build_dir.join(CONFIGURE_STAMP));
+        return Err(Error::build("CMake configuration failed."));
+    }
+    let _ = safe_path::write(
+        build_dir.join(CONFIGURE_STAMP),
+        configure_fingerprint(build_type, defines),
+    );
+    Ok(())
+}
+
+/// Build and run the project, passing `args` to the program
+fn cmd_run(
+    options: &BuildOptions,
+    env: &[String],
+    args: &[String],
+    bin: Option<&str>,
+    example: Option<&str>,
+) -> error::Result<()> {
+    let config = Path::new("project.toml")
+        .exists()
+        .then(read_config)
+        .transpose()?;
+    let executable_name = match &config {
+        // Examples are left out of the default build, so name the one to build
+        _ if let Some(example) = example => examples::select(example)?,
+        Some(config)
+            if config.project.kind == ProjectKind::Lib
+                && config.bin.is_empty()
+                && !Path::new(main_source()).is_file() =>
+        {
+            return Err(Error::usage(format!(
+                "'{}' is a library without an executable to run",
+                config.project.name
+            ))
+            .hint("add a main.c that links the library (see `c1 new --template lib-demo`), or use `c1 build`"));
+        }
+        Some(config) => bins::select(&config.project.name, &config.bin, bin)?,
+        None => get_current_dir_name(),
+    };
+    let project_root = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let no_env = BTreeMap::new();
+    let vars = run_env::resolve(
+        config.as_ref().map_or(&no_env, |c| &c.env),
+        env,
+        &project_root,
+    )
+    .map_err(Error::usage)?;
+
+    // A cross-compiled binary only runs here through a configured runner:
+    // the target's own, else `[run] runner`. An unknown target name is
+    // reported by cmd_build.
+    let target = options
+        .target_config
+        .as_ref()
+        .and_then(|name| Some((name, config.as_ref()?.target.get(name)?)));
+    let runner = target
+        .and_then(|(_, target)| target.runner.clone())
+        .or_else(|| config.as_ref()?.run.runner.clone())
+        .filter(|runner| !runner.trim().is_empty());
+    let runner = runner
+        .as_deref()
+        .map(split_command)
+        .transpose()
+        .map_err(|e| Error::config(format!("invalid runner: {}", e)))?
+        .unwrap_or_default();
+    if let Some((name, target)) = target
+        && (target.toolchain.is_some() || target.prefix.is_some())
+        && runner.is_empty()
+    {
+        return Err(Error::usage(format!(
+            "target '{}' is cross-compiled and cannot run on this host",
+            name
+        ))
+        .hint(format!(
+            "set `runner` under [target.{}] (e.g. runner = \"qemu-arm\") or use `c1 build --target {}`.",
+            name, name
+        )));
+    }
+    if options.valgrind && !runner.is_empty() {
+        return Err(Error::usage(format!(
+            "--valgrind cannot run the program through the runner `{}`",
+            runner.join(" ")
+        )));
+    }
+
+    let options = &BuildOptions {
+        targets: example.map_or(Vec::new(), |_| vec![executable_name.clone()]),
+        ..options.clone()
+    };
+    cmd_build(options)?;
+
+    let build_dir = target_build_dir(options);
+    let executable = match executable_path(&build_dir, &executable_name, options.release) {
+        Ok(path) => path,
+        Err(probed) if example.is_none() => {
+            missing_exe::locate(&build_dir, &executable_name, &probed, options.guess)?
+        }
+        Err(probed) => {
+            let probed: Vec<String> = probed
+                .iter()
+                .map(|path| format!("\n  {}", path.display()))
+                .collect();
+            return Err(Error::failure(format!(
+                "Executable for '{}' not found. Looked in:{}",
+                executable_name,
+                probed.concat()
+            )));
+        }
+    };
+
+    header_deps::warn_if_stale(&build_dir, &executable);
+
+    // The build is done; the program may run for as long as it likes
+    project_lock::release();
+    log::status!("\nRunning {}...\n", executable.display());
+
+    // Run the executable, through the runner or valgrind when there is one
+    let mut valgrind_log = None;
+    let mut command = match runner.split_first() {
+        Some((program, runner_args)) => {
+            let mut command = Command::new(program);
+            command.args(runner_args).arg(&executable);
+            command
+        }
+        None if options.valgrind => {
+            let extra = config.as_ref().map_or(&[][..], |c| &c.run.valgrind_args);
+            let (command, log_file) = valgrind::command(&executable, &build_dir, extra)?;
+            valgrind_log = Some(log_file);
+            command
+        }
+        None => Command::new(&executable),
+    };
+    command.args(args).envs(vars);
+    if !runner.is_empty() {
+        log::command(&command);
+    }
+    let status = command.status().map_err(|e| match runner.first() {
+        Some(program) => Error::failure(format!("failed to run the runner `{}`: {}", program, e))
+            .hint("install it, or change `runner` in project.toml"),
+        None => Error::failure(format!("failed to run executable: {}", e)),
+    })?;
+    if let Some(log_file) = valgrind_log
+        && let Some(errors) = valgrind::errors(status, &log_file)
+    {
+        return Err(Error::failure(format!("valgrind found {}", errors)));
+    }
+
+    // The program's own exit code is passed through unchanged
+    if !status.success()
+        && let Some(code) = status.code()
+    {
+        std::process::exit(code);
+    }
+    Ok(())
+}
+
+/// Split a command line into words the way a POSIX shell would: on
+/// whitespace, with '...' and "..." quoting and backslash escapes
+fn split_command(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(format!("unterminated ' in `{}`", line)),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(format!("unterminated \" in `{}`", line)),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(format!("unterminated \" in `{}`", line)),
+                    }
+                }
+            }
+            '\\' => {
+                let escaped = chars.next().ok_or_else(|| format!("trailing \\ in `{}`", line))?;
+                word.get_or_insert_with(String::new).push(escaped);
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// How `c1 add` should fetch and record a git dependency
+struct AddOptions {
+    tag: Option<String>,
+    branch: Option<String>,
+    rev: Option<String>,
+    shallow: bool,
+    submodule: bool,
+    allow_override: bool,
+    /// Overrides the name derived from the URL
+    name: Option<String>,
+    /// Edit project.toml but leave the clone to `c1 sync`
+    no_clone: bool,
+    /// Only show what would be written and run
+    dry_run: bool,
+    /// Declare it in `[dev-dependencies]`
+    dev: bool,
+    /// Re-clone even over local changes in external/<name>
+    force: bool,
+    /// Only this subdirectory of the repository is the library
+    subdir: Option<String>,
+}
+
+/// Add a git dependency to the project
+fn cmd_add(url: String, options: AddOptions) -> error::Result<()> {
+    let AddOptions {
+        tag,
+        branch,
+        rev,
+        shallow,
+        submodule,
+        allow_override,
+        name,
+        no_clone,
+        dry_run,
+        dev,
+        force,
+        subdir,
+    } = options;
+    let config_path = "project.toml";
+
+    if !Path::new(config_path).exists() {
+        return Err(Error::usage("project.toml not found. Are you in a c1 project?"));
+    }
+
+    // A bare name is looked up in the package index, which also recommends a tag
+    let (url, tag, name) = if index::is_name(&url) {
+        let package = index::resolve(&url)?;
+        let tag = match (&tag, &branch, &rev) {
+            (None, None, None) => package.latest_tag().map(str::to_string),
+            _ => tag,
+        };
+        log::status!(
+            "Using {}{} from the package index",
+            package.git,
+            tag.as_deref()
+                .map(|tag| format!(" at {}", tag))
+                .unwrap_or_default()
+        );
+        (package.git, tag, name.or(Some(url)))
+    } else {
+        (url, tag, name)
+    };
+
+    let expanded = normalize_git_url(&url);
+    if expanded != url {
+        log::status!("Using {}", expanded);
+    }
+    let url = expanded;
+
+    if let Some(rev) = &rev
+        && (rev.len() < 7 || !rev.chars().all(|c| c.is_ascii_hexdigit()))
+    {
+        return Err(Error::usage(format!(
+            "--rev expects a commit hash of at least 7 hex digits, got '{}'",
+            rev
+        )));
+    }
+
+    if let Some(subdir) = &subdir {
+        subdir::check(subdir).map_err(Error::usage)?;
+    }
+
+    // Extract package name from git URL
+    let pkg_name = dependency_name(name, &url)?;
+    let config = read_config()?;
+    check_dependency_section(&config, &pkg_name, dev)?;
+    let mut declared = config.all_dependencies();
+    if let Some(existing) = declared.get(&pkg_name)
+        && existing
+            .get("git")
+            .and_then(|g| g.as_str())
+            .is_none_or(|git| normalize_url(git) != normalize_url(&url))
+    {
+        let source = match (existing.get("git"), existing.get("path")) {
+            (Some(git), _) => secrets::redact(git.as_str().unwrap_or_default()),
+            (None, Some(path)) => format!("path {}", path),
+            _ => existing.to_string(),
+        };
+        return Err(Error::usage(format!(
+            "a dependency named '{}' already comes from {}",
+            pkg_name, source
+        ))
+        .hint(format!(
+            "add this one under another name, e.g. `c1 add {} --name <alias>`",
+            url
+        )));
+    }
+    let entry = git_dependency_entry(
+        &url,
+        tag.as_deref(),
+        branch.as_deref(),
+        rev.as_deref(),
+        shallow,
+        submodule,
+        subdir.as_deref(),
+    );
+
+    // Fail before cloning when the new entry contradicts what the
+    // dependencies already in external/ require
+    let mut unchanged = false;
+    if let Ok(mut parsed) = toml::from_str::<toml::Table>(&format!("entry = {}", entry))
+        && let Some(value) = parsed.remove("entry")
+    {
+        // Adding the same thing again is a no-op once external/ has it
+        unchanged = declared
+            .get(&pkg_name)
+            .is_some_and(|previous| same_source(previous, &value));
+        if unchanged && dep_check::is_current(&pkg_name, &value) {
+            log::status!("✓ {} is already up to date", pkg_name);
+            return Ok(());
+        }
+        declared.insert(pkg_name.clone(), value);
+    }
+    let resolver = transitive::Resolver::new(&declared, allow_override);
+    resolver.check()?;
+
+    if dry_run {
+        println!("Would add to project.toml:");
+        println!("  {} = {}", pkg_name, secrets::redact(&entry));
+        println!("Would run:");
+        let target = format!("external/{}", pkg_name);
+        let url = secrets::redact(&url);
+        let mut clone = if submodule {
+            "git submodule add".to_string()
+        } else {
+            "git clone".to_string()
+        };
+        match (submodule, branch.as_deref().or(tag.as_deref())) {
+            (true, _) => {
+                if let Some(branch) = &branch {
+                    clone.push_str(&format!(" -b {}", branch));
+                }
+            }
+            (false, Some(reference)) => {
+                clone.push_str(&format!(" --branch {} --single-branch", reference))
+            }
+            (false, None) => {}
+        }
+        if shallow && !submodule {
+            clone.push_str(" --depth 1");
+        }
+        if subdir.is_some() {
+            clone.push_str(" --sparse");
+        }
+        println!("  {} {} {}", clone, url, target);
+        if let Some(rev) = &rev {
+            println!("  git -C {} checkout {}", target, rev);
+        }
+        if let Some(subdir) = &subdir {
+            println!("  git -C {} sparse-checkout set {}", target, subdir);
+        }
+        log::status!("Nothing was changed; run without --dry-run to apply");
+        return Ok(());
+    }
+    if no_clone || remote::is_offline() {
+        if !unchanged {
+            declare_dependency(&pkg_name, &entry, dev)?;
+        }
+        log::status!("✓ Added {} to project.toml", pkg_name);
+        if no_clone {
+            log::status!("Run `c1 sync` to clone it");
+        } else {
+            log::problem!(
+                "Warning: --offline: {} was not cloned; run `c1 sync` once online",
+                pkg_name
+            );
+        }
+        return Ok(());
+    }
+
+    log::status!("Adding dependency: {}...", pkg_name);
+
+    // Ensure external directory exists
+    create_external_dir()?;
+    vendor::check()?;
+
+    // A clone of the same URL moves to the requested ref in place; a
+    // pinned revision is checked out just like a locked commit
+    let (tag, branch, rev) = (tag.as_deref(), branch.as_deref(), rev.as_deref());
+    let subdir = subdir.as_deref();
+    let fetched = if submodule {
+        submodule::add(&pkg_name, &url, tag, branch)
+    } else if !force && refresh_clone(&pkg_name, &url, tag, branch, rev)? {
+        Ok(())
+    } else {
+        let dir = Path::new("external").join(&pkg_name);
+        if !force && !dir.is_symlink() && dir.join(".git").exists() && prune::is_dirty(&dir) {
+            return Err(Error::failure(format!(
+                "{} has uncommitted changes and would have to be re-cloned",
+                dir.display()
+            ))
+            .hint("commit or stash them there, or pass --force to delete them"));
+        }
+        clone_dependency(&pkg_name, &url, tag, branch, shallow, rev, subdir.is_some())
+    };
+    fetched?;
+    if !submodule {
+        subdir::apply(&pkg_name, subdir)?;
+    }
+
+    // Update project.toml
+    if !unchanged {
+        declare_dependency(&pkg_name, &entry, dev)?;
+    }
+
+    log::status!("  ✓ Added {} to project.toml", pkg_name);
+    dep_wiring::wire(&pkg_name);
+    fetch_transitive(&pkg_name, resolver)
+}
+
+/// Fetch what a newly added dependency requires, if it is a c1 project
+fn fetch_transitive(name: &str, mut resolver: transitive::Resolver) -> error::Result<()> {
+    let queue: VecDeque<_> = resolver.expand(name)?.into();
+    if queue.is_empty() {
+        return Ok(());
+    }
+    let journal = sync_journal::Journal::load();
+    let mode = SyncMode {
+        force: false,
+        wire: true,
+        jobs: DEFAULT_SYNC_JOBS,
+        fail_fast: false,
+    };
+    let outcome = sync_queue(queue, &mut resolver, &journal, &mode, None)?;
+    if !outcome.failed.is_empty() {
+        return Err(Error::failure(format!(
+            "{} dependencies of {} failed to sync; run `c1 sync` to retry them",
+            outcome.failed.len(),
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Whether two git entries ask for the same URL and ref
+fn same_source(a: &toml::Value, b: &toml::Value) -> bool {
+    ["git", "tag", "branch", "rev", "submodule", "subdir"]
+        .iter()
+        .all(|key| a.get(key) == b.get(key))
+}
+
+/// Add a local path dependency to the project
+fn cmd_add_path(path: String, name: Option<String>, dev: bool) -> error::Result<()> {
+    let config_path = "project.toml";
+
+    if !Path::new(config_path).exists() {
+        return Err(Error::usage("project.toml not found. Are you in a c1 project?"));
+    }
+
+    let pkg_name = dependency_name(name, path.trim_end_matches(['/', '\\']))?;
+    check_dependency_section(&read_config()?, &pkg_name, dev)?;
+
+    log::status!("Adding path dependency: {}...", pkg_name);
+
+    create_external_dir()?;
+    vendor::check()?;
+    link_path_dependency(&pkg_name, &path)?;
+
+    declare_dependency(
+        &pkg_name,
+        &inline_table([("path", path.as_str().into())]),
+        dev,
+    )?;
+
+    log::status!("  ✓ Added {} to project.toml", pkg_name);
+    dep_wiring::wire(&pkg_name);
+    Ok(())
+}
+
+/// Add a release archive dependency to the project, recording its checksum
+fn cmd_add_archive(url: String, name: Option<String>, dev: bool) -> error::Result<()> {
+    if !Path::new("project.toml").exists() {
+        return Err(Error::usage("project.toml not found. Are you in a c1 project?"));
+    }
+    let pkg_name = match name {
+        Some(name) => dependency_name(Some(name), &url)?,
+        None => archive::name_from_url(&url),
+    };
+    let config = read_config()?;
+    check_dependency_section(&config, &pkg_name, dev)?;
+    if let Some(existing) = config.all_dependencies().get(&pkg_name)
+        && existing.get("url").and_then(|u| u.as_str()) != Some(url.as_str())
+    {
+        return Err(Error::usage(format!(
+            "a dependency named '{}' is already declared",
+            pkg_name
+        ))
+        .hint(format!(
+            "add this one under another name, e.g. `c1 add {} --archive --name <alias>`",
+            url
+        )));
+    }
+
+    log::status!("Adding archive dependency: {}...", pkg_name);
+    create_external_dir()?;
+    vendor::check()?;
+    let sha256 = archive::fetch(&pkg_name, &url, None)?;
+
+    declare_dependency(
+        &pkg_name,
+        &inline_table([
+            ("url", url.as_str().into()),
+            ("sha256", sha256.as_str().into()),
+        ]),
+        dev,
+    )?;
+    log::status!("  ✓ Added {} to project.toml (sha256 {})", pkg_name, sha256);
+    dep_wiring::wire(&pkg_name);
+    Ok(())
+}
+
+/// Add a system library found through pkg-config to the project
+fn cmd_add_pkg_config(package: String, name: Option<String>, dev: bool) -> error::Result<()> {
+    if !Path::new("project.toml").exists() {
+        return Err(Error::usage("project.toml not found. Are you in a c1 project?"));
+    }
+    let pkg_name = dependency_name(name, &package)?;
+    check_dependency_section(&read_config()?, &pkg_name, dev)?;
+
+    log::status!("Adding system dependency: {}...", pkg_name);
+    pkg_config::check(&package).map_err(Error::config)?;
+    if let Some(version) = pkg_config::version(&package) {
+        log::status!("  ✓ Found {} {}", package, version);
+    }
+
+    declare_dependency(
+        &pkg_name,
+        &inline_table([(pkg_config::KEY, package.as_str().into())]),
+        dev,
+    )?;
+    log::status!("  ✓ Added {} to project.toml", pkg_name);
+    dep_wiring::wire_pkg_config(&pkg_name, &package);
+    Ok(())
+}
+
+/// `--name`, checked for use as a TOML key and directory, or else the name
+/// derived from the URL or path
+fn dependency_name(name: Option<String>, source: &str) -> error::Result<String> {
+    let Some(name) = name else {
+        return Ok(extract_package_name(source));
+    };
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid {
+        return Err(Error::usage(format!(
+            "dependency name '{}' must contain only letters, numbers, '_', '-' and '.'",
+            name
+        )));
+    }
+    Ok(name)
+}
+
+/// A git URL compared loosely: no trailing slash or `.git`
+fn normalize_url(url: &str) -> &str {
+    url.trim_end_matches('/').trim_end_matches(".git")
+}
+
+/// Ensure external/ exists before dependencies are put in it
+fn create_external_dir() -> error::Result<()> {
+    fs::create_dir_all("external").context(|| "failed to create external/".to_string())
+}
+
+/// Make a local directory available as external/<name>. Relative paths are
+/// resolved against the project root (where project.toml lives).
+fn link_path_dependency(name: &str, path: &str) -> Result<(), String> {
+    let source = Path::new(path);
+    if !source.exists() {
+        return Err(format!("Path '{}' for {} does not exist", path, name));
+    }
+    if !source.is_dir() {
+        return Err(format!("Path '{}' for {} is not a directory", path, name));
+    }
+    let source = source
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve '{}': {}", path, e))?;
+
+    let target_dir = format!("external/{}", name);
+    if fs::symlink_metadata(&target_dir).is_ok() {
+        safe_path::remove_dir_all(&target_dir)
+            .map_err(|e| format!("Failed to remove {}: {}", target_dir, e))?;
+    }
+
+    link_dir(&source, Path::new(&target_dir))
+        .map_err(|e| format!("Failed to link {} to {}: {}", target_dir, source.display(), e))?;
+    log::status!("  ✓ Linked {} to {}", target_dir, source.display());
+    Ok(())
+}
+
+#[cfg(unix)]
+fn link_dir(source: &Path, target: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(source, target)
+}
+
+/// Symlinks need extra privileges on Windows, so fall back to copying
+#[cfg(not(unix))]
+fn link_dir(source: &Path, target: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    if std::os::windows::fs::symlink_dir(source, target).is_ok() {
+        return Ok(());
+    }
+    copy_dir_all(source, target)
+}
+
+#[cfg(not(unix))]
+fn copy_dir_all(source: &Path, target: &Path) -> io::Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let dest = target.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &dest)?;
+        } else {
+            fs::copy(entry.path(), dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// Clone a git dependency into external/<name>, replacing any existing checkout,
+/// and record the resolved commit in project.lock. Shallow clones only fetch
+/// the tip of the requested ref; sparse ones check out the top-level files
+/// until `subdir::apply` adds the subdirectory. The clone is made beside the
+/// old checkout and swapped in once complete, so a failed or interrupted
+/// one leaves external/<name> as it was.
+fn clone_dependency(
+    name: &str,
+    url: &str,
+    tag: Option<&str>,
+    branch: Option<&str>,
+    shallow: bool,
+    locked: Option<&str>,
+    sparse: bool,
+) -> Result<(), String> {
+    let started = Instant::now();
+    let target_dir = format!("external/{}", name);
+    // Expand credential placeholders before touching the network
+    let fetch_url = url_rewrite::rewrite(url);
+    let real_url = secrets::expand(&fetch_url)?;
+
+    // Clone from the shared mirror when there is one; it has full history,
+    // so a local clone of it is never shallow
+    let mirror = clone_cache::update(name, &fetch_url, &real_url);
+    if mirror.is_none() && remote::is_offline() {
+        return Err(format!(
+            "{} is not in {} or the clone cache, and --offline forbids cloning it",
+            name, target_dir
+        ));
+    }
+    let source = mirror.as_ref().map_or(real_url.clone(), clone_cache::Mirror::source);
+
+    let _deferred = interrupt::defer();
+    let partial = partial_clone_dir(name);
+    if Path::new(&partial).exists() {
+        safe_path::remove_dir_all(&partial)
+            .map_err(|e| format!("Failed to remove {}: {}", partial, e))?;
+    }
+
+    // Build git clone command
+    let mut cmd = Command::new("git");
+    // Cloning a tag detaches HEAD; git's advice about that is just noise here
+    cmd.args(["-c", "advice.detachedHead=false"]);
+    cmd.args(["clone", &source, &partial]);
+
+    // Add branch or tag if specified
+    if let Some(b) = branch {
+        cmd.args(["--branch", b, "--single-branch"]);
+    } else if let Some(t) = tag {
+        cmd.args(["--branch", t, "--single-branch"]);
+    }
+    if shallow && mirror.is_none() {
+        cmd.args(["--depth", "1"]);
+    }
+    if sparse {
+        cmd.arg("--sparse");
+    }
+
+    // Execute git clone, showing its progress as it goes
+    let cloned = match progress::run_git(&mut cmd, name) {
+        Ok((output, received)) if output.status.success() => (|| {
+            // From a mirror the download happened while updating it; a clone
+            // too small for git to report a size is summed up on disk
+            let received = mirror
+                .as_ref()
+                .and_then(|mirror| mirror.received.clone())
+                .or(received)
+                .unwrap_or_else(|| progress::disk_size(&Path::new(&partial).join(".git")));
+            if mirror.is_some() {
+                run_git(&partial, &["remote", "set-url", "origin", &real_url])?;
+            }
+            if let Some(commit) = locked {
+                let from = mirror.as_ref().map_or("origin".to_string(), clone_cache::Mirror::source);
+                pin_commit(&partial, commit, &from).map_err(|_| missing_commit(name, commit))?;
+            }
+            if interrupt::interrupted() {
+                return Err(format!("Cloning {} was interrupted", name));
+            }
+            // Replace the existing directory only now that the clone is whole
+            if fs::symlink_metadata(&target_dir).is_ok() {
+                log::status!("  Removing existing {}...", target_dir);
+                safe_path::remove_dir_all(&target_dir)
+                    .map_err(|e| format!("Failed to remove {}: {}", target_dir, e))?;
+            }
+            safe_path::rename(&partial, &target_dir)
+                .map_err(|e| format!("Failed to move {} to {}: {}", partial, target_dir, e))?;
+            log::status!(
+                "  ✓ Cloned {} to {} ({})",
+                name,
+                target_dir,
+                progress::summary(Some(&received), started.elapsed())
+            );
+            Ok(())
+        })(),
+        Ok((output, _)) => Err(match secrets::auth_failure(&fetch_url, &output.stderr) {
+            Some(advice) => format!("Failed to clone {}: {}", name, advice),
+            None => format!("Failed to clone {}\n    {}", name, output.stderr.trim()),
+        }),
+        Err(e) => Err(format!("Error cloning {}: {}", name, e)),
+    };
+    if cloned.is_err() && Path::new(&partial).exists() {
+        let _ = safe_path::remove_dir_all(&partial);
+    }
+    cloned?;
+    lockfile::record(name, url, tag, branch);
+    Ok(())
+}
+
+/// Where a dependency is cloned before it replaces external/<name>
+fn partial_clone_dir(name: &str) -> String {
+    format!("external/.{}.partial", name)
+}
+
+/// Remove the clones an interrupted or killed `c1 sync` left beside the
+/// dependencies, before syncing again
+fn remove_partial_clones() {
+    let Ok(entries) = fs::read_dir("external") else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        if !(file_name.starts_with('.') && file_name.ends_with(".partial")) {
+            continue;
+        }
+        let path = format!("external/{}", file_name);
+        match safe_path::remove_dir_all(&path) {
+            Ok(()) => log::status!("Removed {}, left by an interrupted sync", path),
+            Err(e) => log::problem!("Warning: failed to remove {}: {}", path, e),
+        }
+    }
+}
+
+/// Bring an existing clone in external/<name> to the locked commit, or else
+/// to the tip of its tag or branch, reporting whether HEAD moved. Returns
+/// `Ok(false)` when there is nothing to update in place (no clone, not a git
+/// checkout, or cloned from a different URL) and a fresh clone is needed.
+fn refresh_clone(
+    name: &str,
+    url: &str,
+    tag: Option<&str>,
+    branch: Option<&str>,
+    locked: Option<&str>,
+) -> Result<bool, String> {
+    let dir = format!("external/{}", name);
+    let path = Path::new(&dir);
+    // A symlink is a former path dependency; replace it rather than touch
+    // the directory it points at
+    if path.is_symlink() || !path.join(".git").exists() {
+        return Ok(false);
+    }
+    let fetch_url = url_rewrite::rewrite(url);
+    let real_url = secrets::expand(&fetch_url)?;
+    match run_git(&dir, &["remote", "get-url", "origin"]) {
+        Ok(origin) if origin == real_url => {}
+        Ok(_) => {
+            log::status!("  {} was cloned from a different URL, re-cloning", dir);
+            return Ok(false);
+        }
+        Err(_) => return Ok(false),
+    }
+    let Some(old) = lockfile::head_commit(&dir) else {
+        return Ok(false);
+    };
+
+    let started = Instant::now();
+    let shallow = path.join(".git/shallow").exists();
+    let mirror = clone_cache::update(name, &fetch_url, &real_url);
+    if mirror.is_none() && remote::is_offline() {
+        verify_offline(name, &dir, &old, tag, branch, locked)?;
+        lockfile::record(name, url, tag, branch);
+        return Ok(true);
+    }
+    let source = mirror.as_ref().map_or("origin".to_string(), clone_cache::Mirror::source);
+    let fetch = |refspec: &str| {
+        let mut args = vec!["fetch", "--quiet"];
+        if shallow {
+            args.extend(["--depth", "1"]);
+        }
+        args.extend([source.as_str(), refspec]);
+        run_git(&dir, &args).map(|_| ()).map_err(|e| {
+            let e = secrets::auth_failure(&fetch_url, &e).unwrap_or(e);
+            format!("Failed to fetch {}: {}", name, e)
+        })
+    };
+    match (locked, tag) {
+        // Already at the locked commit: nothing to download
+        (Some(commit), _) if old.starts_with(commit) => {}
+        (Some(commit), _) => {
+            // Servers may refuse to serve a bare commit; checkout then
+            // unshallows as a fallback
+            let _ = fetch(commit);
+            checkout_detached(&dir, commit).map_err(|_| missing_commit(name, commit))?;
+        }
+        (None, Some(tag)) => {
+            fetch(&format!("refs/tags/{0}:refs/tags/{0}", tag))?;
+            checkout_detached(&dir, &format!("tags/{}", tag))?;
+        }
+        (None, None) => {
+            fetch(branch.unwrap_or("HEAD"))?;
+            let current = run_git(&dir, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+            match branch.unwrap_or(&current) {
+                "HEAD" => checkout_detached(&dir, "FETCH_HEAD")?,
+                local => {
+                    run_git(&dir, &["checkout", "--quiet", "-B", local, "FETCH_HEAD"])?;
+                }
+            }
+        }
+    }
+
+    let new = lockfile::head_commit(&dir).unwrap_or_default();
+    if new == old {
+        log::status!("  ✓ {} up to date ({})", name, short_hash(&new));
+    } else {
+        log::status!(
+            "  ✓ Updated {} {} → {} ({})",
+            name,
+            short_hash(&old),
+            short_hash(&new),
+            progress::summary(
+                mirror.as_ref().and_then(|mirror| mirror.received.as_deref()),
+                started.elapsed()
+            )
+        );
+    }
+    lockfile::record(name, url, tag, branch);
+    Ok(true)
+}
+
+/// Check out the wanted commit of an existing clone from what it already
+/// has, for `--offline` with nothing in the clone cache
+fn verify_offline(
+    name: &str,
+    dir: &str,
+    head: &str,
+    tag: Option<&str>,
+    branch: Option<&str>,
+    locked: Option<&str>,
+) -> Result<(), String> {
+    let has = |object: &str| {
+        run_git(dir, &["rev-parse", "--verify", "--quiet", &format!("{}^{{commit}}", object)])
+    };
+    let missing = |what: String| {
+        format!(
+            "{} of {} is not available locally, and --offline forbids fetching it",
+            what, name
+        )
+    };
+    match (locked, tag, branch) {
+        (Some(commit), _, _) if head.starts_with(commit) => {}
+        (Some(commit), _, _) => {
+            has(commit).map_err(|_| missing(format!("Commit {}", short_hash(commit))))?;
+            checkout_detached(dir, commit)?;
+        }
+        (None, Some(tag), _) => {
+            let commit = has(&format!("refs/tags/{}", tag))
+                .map_err(|_| missing(format!("Tag {}", tag)))?;
+            if commit != head {
+                checkout_detached(dir, &commit)?;
+            }
+        }
+        (None, None, Some(branch)) => {
+            let current = run_git(dir, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+            if current != branch {
+                has(&format!("refs/heads/{}", branch))
+                    .map_err(|_| missing(format!("Branch {}", branch)))?;
+                run_git(dir, &["checkout", "--quiet", branch])?;
+            }
+        }
+        // Whatever the clone has checked out is the default branch
+        (None, None, None) => {}
+    }
+    let commit = lockfile::head_commit(dir).unwrap_or_default();
+    log::status!("  ✓ {} verified offline ({})", name, short_hash(&commit));
+    Ok(())
+}
+
+/// The error for a locked or pinned commit that upstream doesn't have
+fn missing_commit(name: &str, commit: &str) -> String {
+    format!(
+        "Commit {} not found upstream for {}; check the hash and that it has been pushed",
+        commit, name
+    )
+}
+
+/// Move a fresh clone to the commit recorded in project.lock or pinned with
+/// `rev`, fetching it from `source` if the clone lacks it
+fn pin_commit(dir: &str, commit: &str, source: &str) -> Result<(), String> {
+    if lockfile::head_commit(dir).is_some_and(|head| head.starts_with(commit)) {
+        return Ok(());
+    }
+    // A shallow clone only has the branch tip; ask for the exact commit first
+    if run_git(dir, &["cat-file", "-e", &format!("{}^{{commit}}", commit)]).is_err() {
+        let _ = run_git(dir, &["fetch", "--quiet", "--depth", "1", source, commit]);
+    }
+    checkout_detached(dir, commit)?;
+    log::status!("  ✓ Checked out pinned commit {}", short_hash(commit));
+    Ok(())
+}
+
+/// Dependencies are cloned shallowly unless they opt out with `shallow = false`
+fn is_shallow(table: &toml::Table) -> bool {
+    table.get("shallow").and_then(|v| v.as_bool()).unwrap_or(true)
+}
+
+/// Extract package name from git URL
+fn extract_package_name(url: &str) -> String {
+    // Handle URLs like:
+    // - https://github.com/user/repo.git
+    // - git@github.com:user/repo.git
+    // - git@github.com:repo.git
+    // - https://github.com/user/repo
+    let url = url.trim_end_matches(['/', '\\']).trim_end_matches(".git");
+    let last = url.rsplit(['/', ':', '\\']).next().unwrap_or_default();
+
+    // The name becomes a directory under external/ and a manifest key
+    let name: String = last
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim_start_matches('.');
+    if name.is_empty() {
+        "unknown".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Expand the `user/repo` shorthand to its GitHub URL. Full URLs, scp-style
+/// `git@host:path` and existing local directories are kept as they are.
+fn normalize_git_url(url: &str) -> String {
+    let is_segment = |s: &str| {
+        !s.is_empty()
+            && !s.starts_with('.')
+            && s.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    };
+    match url.split_once('/') {
+        Some((user, repo)) if is_segment(user) && is_segment(repo) && !Path::new(url).exists() => {
+            format!(
+                "https://github.com/{}/{}.git",
+                user,
+                repo.trim_end_matches(".git")
+            )
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Build the inline table for a git dependency entry
+fn git_dependency_entry(
+    url: &str,
+    tag: Option<&str>,
+    branch: Option<&str>,
+    rev: Option<&str>,
+    shallow: bool,
+    submodule: bool,
+    subdir: Option<&str>,
+) -> String {
+    let mut fields: Vec<(&str, toml_edit::Value)> = vec![("git", url.into())];
+    if let Some(t) = tag {
+        fields.push(("tag", t.into()));
+    } else if let Some(b) = branch {
+        fields.push(("branch", b.into()));
+    } else if let Some(r) = rev {
+        fields.push(("rev", r.into()));
+    }
+    if submodule {
+        fields.push(("submodule", true.into()));
+    } else if !shallow {
+        fields.push(("shallow", false.into()));
+    }
+    if let Some(subdir) = subdir {
+        fields.push(("subdir", subdir.into()));
+    }
+    inline_table(fields)
+}
+
+/// `{ key = value, ... }` as it goes into project.toml, with strings quoted
+/// and escaped as TOML needs (a Windows path's backslashes included)
+fn inline_table<'a>(fields: impl IntoIterator<Item = (&'a str, toml_edit::Value)>) -> String {
+    let mut table: toml_edit::InlineTable = fields.into_iter().collect();
+    table.fmt();
+    table.to_string()
+}
+
+/// Update a dependency's entry in project.toml, in `[dev-dependencies]`
+/// when that is where it is declared
+fn update_project_toml(name: &str, entry: &str) -> error::Result<()> {
+    let config = read_config()?;
+    let dev = config.dev_dependencies.contains_key(name) && !config.dependencies.contains_key(name);
+    declare_dependency(name, entry, dev)
+}
+
+/// Write a dependency's entry to `[dependencies]`, or `[dev-dependencies]`
+fn declare_dependency(name: &str, entry: &str, dev: bool) -> error::Result<()> {
+    let section = if dev { "dev-dependencies" } else { "dependencies" };
+    set_manifest_entry(section, name, entry)
+}
 
-    // Create .gitignore
-    let gitignore = r#"# Build directory 
-/build
+/// Refuse to declare `name` in one dependency table while the other has it;
+/// moving it is done by hand
+fn check_dependency_section(config: &ProjectConfig, name: &str, dev: bool) -> error::Result<()> {
+    let (other, section) = if dev {
+        (&config.dependencies, "[dependencies]")
+    } else {
+        (&config.dev_dependencies, "[dev-dependencies]")
+    };
+    if !other.contains_key(name) {
+        return Ok(());
+    }
+    Err(Error::usage(format!(
+        "'{}' is already declared in {} of project.toml",
+        name, section
+    ))
+    .hint(format!("remove it from {} first to move it", section)))
+}
 
-# IDE
-/.idea
-/.vscode
+/// Insert or replace `name = entry` in the `[section]` table of project.toml
+fn set_manifest_entry(section: &str, name: &str, entry: &str) -> error::Result<()> {
+    let config_path = "project.toml";
+    let content =
+        fs::read_to_string(config_path).context(|| format!("failed to read {}", config_path))?;
+    let updated = with_manifest_entry(&content, section, name, entry)?;
+    write_project_toml(config_path, &updated)
+}
 
-# Compiled files
-*.o
-*.a
-*.so
-*.exe
-/cmake-build-*
-"#;
-    fs::write(".gitignore", gitignore).expect("Failed to create .gitignore");
+/// `content` of project.toml with `name = entry` set in `[section]`, the
+/// rest of it byte for byte as it was
+fn with_manifest_entry(
+    content: &str,
+    section: &str,
+    name: &str,
+    entry: &str,
+) -> error::Result<String> {
+    let mut manifest: toml_edit::DocumentMut = content
+        .parse()
+        .map_err(|e| Error::config(format!("failed to parse project.toml: {}", e)))?;
+    let mut value: toml_edit::Value = entry
+        .parse()
+        .map_err(|e| Error::failure(format!("invalid manifest entry for {}: {}", name, e)))?;
 
-    // Run git init
-    match Command::new("git").args(["init"]).output() {
-        Ok(output) => {
-            if output.status.success() {
-                println!("Initialized git repository");
-            } else {
-                eprintln!("Warning: git init failed");
+    let table = manifest
+        .entry(section)
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .ok_or_else(|| Error::config(format!("`{}` in project.toml is not a table", section)))?;
+    match table.get_mut(name) {
+        // Replace the value in place, keeping the key's position and any comment after it
+        Some(existing) => {
+            if let Some(old) = existing.as_value() {
+                *value.decor_mut() = old.decor().clone();
             }
+            *existing = toml_edit::Item::Value(value);
         }
-        Err(_) => {
-            eprintln!("Warning: git not found, skipping git init");
+        None => {
+            table.insert(name, toml_edit::Item::Value(value));
         }
     }
 
-    // Restore original working directory
-    if target_dir != original_dir {
-        std::env::set_current_dir(&original_dir).expect("Failed to restore original directory");
+    let mut updated = manifest.to_string();
+    // toml_edit ends the last line; a file that did not, still does not
+    if !content.ends_with('\n') && updated.ends_with('\n') {
+        updated.pop();
     }
+    Ok(updated)
 }
 
-fn cmd_create(name: String) {
-    // Validate module name (only alphanumeric and underscore)
-    if !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
-        eprintln!("Error: Module name must contain only letters, numbers, and underscores");
-        std::process::exit(1);
-    }
+fn write_project_toml(config_path: &str, content: &str) -> error::Result<()> {
+    safe_path::write(config_path, content).context(|| format!("failed to write {}", config_path))
+}
 
-    // Require an initialized c1 project before creating modules
-    if !Path::new("project.toml").exists() && !Path::new("CMakeLists.txt").exists() {
-        eprintln!("Error: Not a c1 project (project.toml / CMakeLists.txt not found).");
-        eprintln!("Hint: Run `c1 init` first to scaffold the project, then `c1 create {}`.", name);
-        std::process::exit(1);
+/// Clean the build directory, or only the subdirectory of one profile;
+/// with `keep_deps`, only the project's own outputs in it
+fn cmd_clean(
+    out_dir: Option<&str>,
+    only: Option<&str>,
+    keep_deps: bool,
+    generated_outputs: bool,
+    dry_run: bool,
+) -> error::Result<()> {
+    let mut build_dir = resolve_build_dir(out_dir);
+    // What c1 wrote outside the build directory goes with a full clean only,
+    // and the outputs of [generate] there with --generated
+    let mut generated = if only.is_none() && !keep_deps {
+        clean::generated(&build_dir)
+    } else {
+        Vec::new()
+    };
+    if generated_outputs && let Ok(config) = read_config() {
+        generated.extend(generate::outputs_in_tree(&config.generate, &build_dir));
+    }
+    if let Some(subdir) = only {
+        build_dir.push(subdir);
     }
 
-    // Ensure Pitchfork layout directories exist
-    if let Err(e) = fs::create_dir_all("src") {
-        eprintln!("Error: Failed to create src/ directory: {}", e);
-        std::process::exit(1);
+    if dry_run {
+        let mut planned = match keep_deps {
+            true => clean::own_outputs(&build_dir),
+            false if build_dir.exists() => vec![build_dir],
+            false => Vec::new(),
+        };
+        planned.extend(generated);
+        clean::print_plan(&planned);
+        return Ok(());
     }
-    if let Err(e) = fs::create_dir_all("include") {
-        eprintln!("Error: Failed to create include/ directory: {}", e);
-        std::process::exit(1);
+
+    if !build_dir.exists() && generated.is_empty() {
+        log::status!("Build directory does not exist, nothing to clean.");
+        return Ok(());
     }
 
-    let src_file = format!("src/{}.c", name);
-    let header_file = format!("include/{}.h", name);
+    if keep_deps {
+        log::status!("Cleaning {}...", build_dir.display());
+        let freed = clean::remove(&clean::own_outputs(&build_dir))?;
+        log::status!(
+            "✓ Removed the project's own build outputs, kept the dependencies' ({} reclaimed)",
+            build_history::bytes(freed)
+        );
+        return Ok(());
+    }
 
-    // Check if files already exist
-    if Path::new(&src_file).exists() {
-        eprintln!("Error: {} already exists", src_file);
-        std::process::exit(1);
+    let mut freed = 0;
+    if build_dir.exists() {
+        log::status!("Cleaning {}...", build_dir.display());
+        // Remove all contents in build directory, then recreate it empty
+        freed += clone_cache::dir_size(&build_dir);
+        safe_path::remove_dir_all(&build_dir)
+            .context(|| format!("failed to clean {}", build_dir.display()))?;
+        if only.is_none() {
+            fs::create_dir_all(&build_dir)
+                .context(|| format!("failed to recreate {}", build_dir.display()))?;
+        }
     }
-    if Path::new(&header_file).exists() {
-        eprintln!("Error: {} already exists", header_file);
-        std::process::exit(1);
+    for path in &generated {
+        let size = clean::remove(std::slice::from_ref(path))?;
+        freed += size;
+        log::status!(
+            "  Removed {} ({})",
+            path.display(),
+            build_history::bytes(size)
+        );
     }
+    remove_dangling_compile_commands();
+    log::status!(
+        "✓ Build directory cleaned successfully! ({} reclaimed)",
+        build_history::bytes(freed)
+    );
+    Ok(())
+}
 
-    // Create header guard macro name
-    let guard_name = format!("_{}_H", name.to_uppercase());
+/// Remove external/, which `c1 sync` can recreate, after confirming since
+/// clones there may carry local changes
+fn clean_dependencies(yes: bool, dry_run: bool) -> error::Result<()> {
+    let external = Path::new("external");
+    if dry_run {
+        if external.exists() {
+            clean::print_plan(&[external.to_path_buf()]);
+        }
+        return Ok(());
+    }
+    if !external.exists() {
+        log::status!("external/ does not exist, nothing to clean.");
+        sync_journal::clear();
+        return Ok(());
+    }
+    if !yes
+        && !safe_path::confirm(
+            "Remove external/? Local changes to dependencies will be lost [y/N] ",
+        )
+    {
+        return Err(
+            Error::usage("external/ was not removed").hint("pass --yes to skip the question")
+        );
+    }
 
-    // Generate header file content
-    let header_content = format!(
-        r#"#ifndef {}
-#define {}
+    log::status!("Removing dependencies...");
+    safe_path::remove_dir_all(external).context(|| "failed to remove external/".to_string())?;
+    sync_journal::clear();
+    log::status!("✓ Dependencies removed; run `c1 sync` to fetch them again");
+    Ok(())
+}
 
-// TODO: Add your declarations here
+/// Dependencies `c1 sync` fetches at once unless `--jobs` says otherwise
+const DEFAULT_SYNC_JOBS: usize = 4;
 
-#endif // {}
-"#,
-        guard_name, guard_name, guard_name
-    );
+/// How `c1 sync` goes about fetching
+struct SyncMode {
+    /// Fresh clones instead of updating existing ones
+    force: bool,
+    /// Wire each dependency into CMakeLists.txt
+    wire: bool,
+    jobs: usize,
+    /// Start no more dependencies after one failed
+    fail_fast: bool,
+}
 
-    // Generate source file content
-    let src_content = format!(
-        r#"#include "{}.h"
+fn cmd_sync(
+    max_time: Option<Duration>,
+    mut mode: SyncMode,
+    prune: bool,
+    allow_override: bool,
+    no_dev: bool,
+) -> error::Result<()> {
+    // A workspace root fetches all members' dependencies into one external/
+    let (dependencies, skipped, wire) = match workspace::load()? {
+        Some(members) => (workspace::merged_dependencies(&members)?, Vec::new(), false),
+        None => {
+            let config = read_config()?;
+            // --no-dev leaves dev-dependencies as they are in external/,
+            // still locked and not pruned
+            let skipped: Vec<String> = config
+                .dev_dependencies
+                .keys()
+                .filter(|name| no_dev && !config.dependencies.contains_key(*name))
+                .cloned()
+                .collect();
+            (config.all_dependencies(), skipped, true)
+        }
+    };
+    let mut to_sync = dependencies.clone();
+    to_sync.retain(|name, _| !skipped.iter().any(|s| s == name));
+    if lockfile::is_enforced() {
+        check_locked(&to_sync)?;
+    }
+    let mut resolver = transitive::Resolver::new(&dependencies, allow_override);
+    resolver.record_present(&skipped)?;
+    mode.wire = wire;
+    remove_partial_clones();
+    sync_dependencies(&to_sync, &skipped, &mut resolver, max_time, &mode)?;
+    if lockfile::is_enforced() {
+        dep_check::unmodified(&to_sync)?;
+    }
+    prune::run(&resolver.all(), prune, mode.force)
+}
 
-// TODO: Add your implementation here
-"#,
-        name
-    );
+/// `--locked`: before touching anything, check that project.lock has an
+/// entry from the declared source for each git dependency
+fn check_locked(dependencies: &toml::Table) -> error::Result<()> {
+    if !Path::new(lockfile::LOCKFILE_PATH).exists() {
+        return Err(
+            Error::config(format!("{} not found, but --locked was given", lockfile::LOCKFILE_PATH))
+                .hint("run `c1 sync` without --locked to create it, and commit it"),
+        );
+    }
+    let mismatches: Vec<String> = dependencies
+        .iter()
+        .filter_map(|(name, value)| {
+            let table = value.as_table()?;
+            let git = table.get("git")?.as_str()?;
+            // A pinned `rev` is its own lock
+            if table.contains_key("rev") {
+                return None;
+            }
+            let tag = table.get("tag").and_then(|v| v.as_str());
+            let branch = table.get("branch").and_then(|v| v.as_str());
+            lockfile::locked_commit(name, git, tag, branch)
+                .is_none()
+                .then(|| lockfile::mismatch(name, git, tag, branch))
+        })
+        .collect();
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    Err(Error::config(format!(
+        "{} is out of date with project.toml:\n  {}",
+        lockfile::LOCKFILE_PATH,
+        mismatches.join("\n  ")
+    ))
+    .hint("run `c1 sync` without --locked to update project.lock, and commit it"))
+}
 
-    // Write files
-    if let Err(e) = fs::write(&src_file, &src_content) {
-        eprintln!("Error: Failed to create {}: {}", src_file, e);
-        std::process::exit(1);
+/// Fetch every dependency in `dependencies` into external/, then show what
+/// happened to each, `skipped` (left out by `--no-dev`) included. The sync
+/// fails when any dependency did.
+fn sync_dependencies(
+    dependencies: &toml::Table,
+    skipped: &[String],
+    resolver: &mut transitive::Resolver,
+    max_time: Option<Duration>,
+    mode: &SyncMode,
+) -> error::Result<()> {
+    if dependencies.is_empty() {
+        log::status!("No dependencies to sync");
+        return Ok(());
     }
-    if let Err(e) = fs::write(&header_file, &header_content) {
-        eprintln!("Error: Failed to create {}: {}", header_file, e);
-        // Best-effort rollback of the source file
-        let _ = fs::remove_file(&src_file);
-        std::process::exit(1);
+
+    // Resolve credential placeholders up front so a missing token fails
+    // before any clone starts
+    let unresolved: Vec<String> = dependencies
+        .iter()
+        .filter_map(|(name, value)| {
+            let url = value.as_table()?.get("git")?.as_str()?;
+            secrets::expand(&url_rewrite::rewrite(url))
+                .err()
+                .map(|e| format!("{}: {}", name, e))
+        })
+        .collect();
+    if !unresolved.is_empty() {
+        return Err(Error::config(unresolved.join("\n")));
     }
+    resolver.check()?;
 
-    println!("✓ Created {} and {}", src_file, header_file);
+    // Ensure external directory exists
+    create_external_dir()?;
+    vendor::check()?;
 
-    // Update CMakeLists.txt
-    update_cmake_lists(&name);
-}
+    // Dependencies finished by an interrupted earlier run are skipped
+    let journal = sync_journal::Journal::load();
+    let queue = dependencies
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    let started = Instant::now();
+    let deadline = max_time.map(|budget| started + budget);
+    let outcome = sync_queue(queue, resolver, &journal, mode, deadline)?;
 
-fn update_cmake_lists(module_name: &str) {
-    let cmake_path = "CMakeLists.txt";
-    
-    if !Path::new(cmake_path).exists() {
-        eprintln!("Warning: CMakeLists.txt not found, skipping automatic registration");
-        return;
+    // Forget locked packages that nothing requires any more
+    if let Ok(Some(mut lock)) = lockfile::Lockfile::load() {
+        let required = resolver.all();
+        let stale: Vec<&str> = lock
+            .packages
+            .iter()
+            .map(|p| p.name.as_str())
+            .filter(|name| !required.contains_key(*name))
+            .collect();
+        if lockfile::is_enforced() && !stale.is_empty() {
+            return Err(Error::config(format!(
+                "{} locks {}, which nothing depends on any more",
+                lockfile::LOCKFILE_PATH,
+                stale.join(", ")
+            ))
+            .hint("run `c1 sync` without --locked to update project.lock, and commit it"));
+        }
+        lock.retain_declared(&resolver.all());
+        if let Err(e) = lock.save() {
+            log::problem!("Warning: failed to write {}: {}", lockfile::LOCKFILE_PATH, e);
+        }
     }
 
-    let content = fs::read_to_string(cmake_path).expect("Failed to read CMakeLists.txt");
-
-    // Update SOURCES section (placeholder keeps its existing indent on the line)
-    let src_placeholder = "# @c1_sources";
-    let src_entry = format!("src/{}.c\n    {}", module_name, src_placeholder);
-    let new_content = content.replace(src_placeholder, &src_entry);
+    let elapsed = started.elapsed().as_secs_f64();
+    print_sync_summary(&outcome, skipped);
+    if !outcome.remaining.is_empty() {
+        return Err(Error::partial(format!(
+            "Time budget used up, {} dependencies not synced: {}",
+            outcome.remaining.len(),
+            outcome.remaining.join(", ")
+        ))
+        .hint("Run `c1 sync` again to continue where this run stopped."));
+    }
+    if !outcome.failed.is_empty() {
+        let error = Error::failure(format!(
+            "{} of {} dependencies failed to sync in {:.1}s",
+            outcome.failed.len(),
+            outcome.done.len() + outcome.failed.len() + outcome.abandoned.len(),
+            elapsed
+        ));
+        return Err(if outcome.abandoned.is_empty() {
+            error.hint("fix the errors above and run `c1 sync` again to retry the failed ones")
+        } else {
+            error.hint("run `c1 sync` without --fail-fast to try the rest anyway")
+        });
+    }
 
-    // Update HEADERS section
-    let header_placeholder = "# @c1_headers";
-    let header_entry = format!("include/{}.h\n    {}", module_name, header_placeholder);
-    let new_content = new_content.replace(header_placeholder, &header_entry);
+    sync_journal::clear();
+    log::status!(
+        "\n✓ Dependency sync complete: {} ready in {:.1}s",
+        outcome.done.len(),
+        elapsed
+    );
+    Ok(())
+}
 
-    fs::write(cmake_path, new_content).expect("Failed to update CMakeLists.txt");
+/// What happened to a dependency that synced
+#[derive(Clone, Copy, PartialEq)]
+enum Synced {
+    /// Fetched into external/ for the first time
+    Fetched,
+    /// Already in external/, now at another commit
+    Updated,
+    /// Already in external/ as wanted
+    UpToDate,
+}
 
-    println!("✓ Updated CMakeLists.txt");
+/// What `sync_queue` got done
+#[derive(Default)]
+struct SyncOutcome {
+    /// Each synced dependency with its commit, if it has one
+    done: Vec<(String, Synced, Option<String>)>,
+    /// Each failed dependency with its error
+    failed: Vec<(String, String)>,
+    /// Dependencies not started before the deadline
+    remaining: Vec<String>,
+    /// Dependencies not started after a failure, with `--fail-fast`
+    abandoned: Vec<String>,
 }
 
-/// Get project name from project.toml
-fn get_project_name_from_config() -> Option<String> {
-    let config_path = "project.toml";
-    if !Path::new(config_path).exists() {
-        return None;
+/// One line per dependency with its result, then the totals; the totals
+/// are also a `sync_summary` event
+fn print_sync_summary(outcome: &SyncOutcome, skipped: &[String]) {
+    let mut rows: Vec<(&str, &str, String)> = Vec::new();
+    for (name, synced, commit) in &outcome.done {
+        let result = match synced {
+            Synced::Fetched => "synced",
+            Synced::Updated => "updated",
+            Synced::UpToDate => "up to date",
+        };
+        rows.push((
+            name,
+            result,
+            commit.as_deref().map_or("", short_hash).to_string(),
+        ));
     }
-    let content = fs::read_to_string(config_path).ok()?;
-    let config: ProjectConfig = toml::from_str(&content).ok()?;
-    Some(config.project.name)
+    for (name, error) in &outcome.failed {
+        let reason = error.lines().next().unwrap_or_default().trim();
+        rows.push((name, "failed", reason.to_string()));
+    }
+    for name in skipped {
+        rows.push((name, "skipped", "--no-dev".to_string()));
+    }
+    for name in &outcome.abandoned {
+        rows.push((name, "skipped", "--fail-fast".to_string()));
+    }
+    for name in &outcome.remaining {
+        rows.push((name, "skipped", "--max-time".to_string()));
+    }
+    rows.sort_by(|a, b| a.0.cmp(b.0));
+
+    let count = |synced: Synced| outcome.done.iter().filter(|(_, s, _)| *s == synced).count();
+    let skipped = skipped.len() + outcome.abandoned.len() + outcome.remaining.len();
+    events::sync_summary(
+        count(Synced::Fetched),
+        count(Synced::Updated),
+        count(Synced::UpToDate),
+        skipped,
+        &outcome.failed,
+    );
+    if rows.len() < 2 && outcome.failed.is_empty() {
+        return;
+    }
+    let width = rows
+        .iter()
+        .map(|(name, _, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    log::status!("\nSummary:");
+    for (name, result, detail) in &rows {
+        let line = format!(
+            "  {:<width$}  {:<10}  {}",
+            name,
+            result,
+            detail,
+            width = width
+        );
+        log::status!("{}", line.trim_end());
+    }
+    log::status!(
+        "{} synced, {} updated, {} up to date, {} skipped, {} failed",
+        count(Synced::Fetched),
+        count(Synced::Updated),
+        count(Synced::UpToDate),
+        skipped,
+        outcome.failed.len()
+    );
 }
 
-/// Build the project with cmake
-fn cmd_build(release: bool) {
-    let build_type = if release { "Release" } else { "Debug" };
-    let build_dir = "build";
+/// Sync each queued dependency, then whatever the c1 projects among them
+/// declare, on up to `jobs` threads. With more than one job, each
+/// dependency's output is held back and printed in one piece when it is done.
+/// With `fail_fast`, no dependency starts after one failed.
+fn sync_queue(
+    queue: VecDeque<(String, toml::Value)>,
+    resolver: &mut transitive::Resolver,
+    journal: &sync_journal::Journal,
+    mode: &SyncMode,
+    deadline: Option<Instant>,
+) -> error::Result<SyncOutcome> {
+    let mut outcome = SyncOutcome::default();
+    let stop = AtomicBool::new(false);
+    let mut wave: Vec<(String, toml::Value)> = queue.into();
+    while !wave.is_empty() {
+        if stop.load(Ordering::Relaxed) {
+            outcome
+                .abandoned
+                .extend(wave.drain(..).map(|(name, _)| name));
+            break;
+        }
+        let workers = mode.jobs.clamp(1, wave.len());
+        let pending: Mutex<VecDeque<_>> = Mutex::new(
+            wave.drain(..)
+                .map(|(name, value)| {
+                    let parent = resolver.required_by(&name).map(str::to_string);
+                    (name, value, parent)
+                })
+                .collect(),
+        );
+        let (sender, receiver) = mpsc::channel();
+        let mut next = Vec::new();
+        thread::scope(|scope| -> error::Result<()> {
+            for _ in 0..workers {
+                let sender = sender.clone();
+                let pending = &pending;
+                let stop = &stop;
+                scope.spawn(move || {
+                    loop {
+                        if stop.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        let Some((name, value, parent)) =
+                            pending.lock().unwrap_or_else(|e| e.into_inner()).pop_front()
+                        else {
+                            break;
+                        };
+                        // Out of time: don't start another clone, report it instead
+                        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                            let _ = sender.send((name, value, None, None));
+                            continue;
+                        }
+                        let sync = || -> Result<Synced, String> {
+                            match &parent {
+                                Some(parent) => log::status!(
+                                    "Syncing dependency: {} (required by {})...",
+                                    name,
+                                    parent
+                                ),
+                                None => log::status!("Syncing dependency: {}...", name),
+                            }
+                            let dir = Path::new("external").join(&name);
+                            let before = dir.exists().then(|| git_head::read_head(&dir));
+                            sync_dependency(&name, &value, journal, mode.force)?;
+                            Ok(match before {
+                                _ if pkg_config::package(&value).is_some() => Synced::UpToDate,
+                                None => Synced::Fetched,
+                                Some(head) if head == git_head::read_head(&dir) => Synced::UpToDate,
+                                Some(_) => Synced::Updated,
+                            })
+                        };
+                        let (result, output) = if workers > 1 {
+                            let (result, output) = log::capture(sync);
+                            (result, Some(output))
+                        } else {
+                            (sync(), None)
+                        };
+                        if result.is_err() && mode.fail_fast {
+                            stop.store(true, Ordering::Relaxed);
+                        }
+                        let _ = sender.send((name, value, Some(result), output));
+                    }
+                });
+            }
+            drop(sender);
 
-    println!("Building project ({} mode)...", build_type);
+            for (name, value, result, output) in receiver {
+                // Other workers' meters may be on screen
+                let _suspended = progress::suspend();
+                if let Some(output) = output {
+                    output.print();
+                }
+                match result {
+                    None => outcome.remaining.push(name),
+                    Some(Ok(synced)) => {
+                        if mode.wire {
+                            match pkg_config::package(&value) {
+                                Some(package) => dep_wiring::wire_pkg_config(&name, package),
+                                None => dep_wiring::wire(&name),
+                            }
+                        }
+                        let commit = git_head::read_head(&Path::new("external").join(&name));
+                        events::dependency(&name, Ok(commit.as_deref()));
+                        next.extend(resolver.expand(&name)?);
+                        outcome.done.push((name, synced, commit));
+                    }
+                    Some(Err(e)) => {
+                        log::problem!("  ✗ {}", e);
+                        events::dependency(&name, Err(&e));
+                        outcome.failed.push((name, e));
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        let pending = pending.into_inner().unwrap_or_else(|e| e.into_inner());
+        outcome
+            .abandoned
+            .extend(pending.into_iter().map(|(name, _, _)| name));
+        wave = next;
+    }
+    Ok(outcome)
+}
 
-    // Step 1: cmake -B build -DCMAKE_BUILD_TYPE=...
-    let cmake_config = Command::new("cmake")
-        .args(["-B", build_dir, &format!("-DCMAKE_BUILD_TYPE={}", build_type)])
-        .output();
+/// Sync one dependency, skipping it when the journal shows it is already done
+fn sync_dependency(
+    name: &str,
+    value: &toml::Value,
+    journal: &sync_journal::Journal,
+    force: bool,
+) -> Result<(), String> {
+    let Some(table) = value.as_table() else {
+        return Err(format!("Invalid dependency format for {}", name));
+    };
+    let subdir = subdir::of(value);
+    if let Some(subdir) = subdir {
+        subdir::check(subdir).map_err(|e| format!("{}: {}", name, e))?;
+    }
 
-    match cmake_config {
-        Ok(output) => {
-            if !output.status.success() {
-                eprintln!("CMake configuration failed:");
-                eprintln!("{}", String::from_utf8_lossy(&output.stderr));
-                std::process::exit(1);
-            }
-        }
-        Err(e) => {
-            eprintln!("Failed to run cmake: {}", e);
-            eprintln!("Make sure cmake is installed.");
-            std::process::exit(1);
+    if let Some(git_url) = table.get("git").and_then(|v| v.as_str()) {
+        let tag = table.get("tag").and_then(|v| v.as_str());
+        let branch = table.get("branch").and_then(|v| v.as_str());
+        // A pinned `rev` takes the place of the locked commit
+        let locked = match table.get("rev").and_then(|v| v.as_str()) {
+            Some(rev) => Some(rev.to_string()),
+            None => lockfile::locked_commit(name, git_url, tag, branch),
+        };
+        if lockfile::is_enforced() && locked.is_none() {
+            return Err(lockfile::mismatch(name, git_url, tag, branch));
         }
-    }
 
-    // Step 2: cmake --build build
-    let cmake_build = Command::new("cmake")
-        .args(["--build", build_dir])
-        .output();
-
-    match cmake_build {
-        Ok(output) => {
-            if output.status.success() {
-                println!("✓ Build completed successfully!");
-            } else {
-                eprintln!("Build failed:");
-                eprintln!("{}", String::from_utf8_lossy(&output.stderr));
-                std::process::exit(1);
+        if !force && let Some(commit) = journal.verified(name, value, locked.as_deref()) {
+            log::status!("  ✓ {} already synced ({})", name, short_hash(commit));
+            return Ok(());
+        }
+        if submodule::is_submodule(table) {
+            // The parent repository pins the commit, not project.lock
+            submodule::sync(name, git_url, tag, branch)?;
+            subdir::verify(name, subdir)?;
+        } else {
+            if force || !refresh_clone(name, git_url, tag, branch, locked.as_deref())? {
+                clone_dependency(
+                    name,
+                    git_url,
+                    tag,
+                    branch,
+                    is_shallow(table),
+                    locked.as_deref(),
+                    subdir.is_some(),
+                )?;
             }
+            subdir::apply(name, subdir)?;
         }
-        Err(e) => {
-            eprintln!("Failed to run cmake --build: {}", e);
-            std::process::exit(1);
+        let head = git_head::read_head(&Path::new("external").join(name));
+        if lockfile::is_enforced()
+            && let Some(locked) = &locked
+            && !head.as_deref().is_some_and(|head| head.starts_with(locked.as_str()))
+        {
+            return Err(format!(
+                "{}: external/{} is at {}, but {} locks {}{}",
+                name,
+                name,
+                head.as_deref().map_or("no commit", short_hash),
+                lockfile::LOCKFILE_PATH,
+                short_hash(locked),
+                match (tag, branch) {
+                    (Some(tag), _) => format!(" (tag {})", tag),
+                    (None, Some(branch)) => format!(" (branch {})", branch),
+                    (None, None) => String::new(),
+                }
+            ));
         }
+        if let Some(commit) = head
+            && let Err(e) = sync_journal::record(name, value, &commit)
+        {
+            log::problem!("Warning: failed to update the sync journal: {}", e);
+        }
+        Ok(())
+    } else if let Some(path) = table.get("path").and_then(|v| v.as_str()) {
+        link_path_dependency(name, path)?;
+        subdir::verify(name, subdir)
+    } else if let Some(url) = table.get("url").and_then(|v| v.as_str()) {
+        let sha256 = table.get("sha256").and_then(|v| v.as_str());
+        archive::sync(name, url, sha256, force)?;
+        subdir::verify(name, subdir)
+    } else if let Some(package) = pkg_config::package(value) {
+        // System packages are installed, not fetched
+        pkg_config::check(package)?;
+        match pkg_config::version(package) {
+            Some(version) => log::status!("  ✓ {} {} found by pkg-config", package, version),
+            None => log::status!("  ✓ {} found by pkg-config", package),
+        }
+        Ok(())
+    } else {
+        Err(format!(
+            "No 'git' URL, archive 'url', 'path' or '{}' specified for {}",
+            pkg_config::KEY,
+            name
+        ))
     }
 }
 
-/// Build and run the project
-fn cmd_run() {
-    // First build the project (debug mode)
-    cmd_build(false);
+/// Report problems that c1 can detect without building
+fn cmd_doctor() {
+    let mut warnings = Vec::new();
 
-    // Get project name for executable
-    let project_name = get_project_name_from_config()
-        .unwrap_or_else(|| get_current_dir_name());
+    // Literal credentials in files that get committed
+    if let Ok(content) = fs::read_to_string("project.toml")
+        && let Ok(config) = toml::from_str::<ProjectConfig>(&content)
+    {
+        for (name, value) in &config.all_dependencies() {
+            if let Some(url) = value.get("git").and_then(|v| v.as_str())
+                && let Some(kind) = secrets::literal_credential(url)
+            {
+                warnings.push(format!(
+                    "project.toml: dependency '{}' has {} in its URL; use a placeholder like https://${{GIT_TOKEN}}@host/... instead",
+                    name, kind
+                ));
+            }
+        }
+    }
+    if let Ok(Some(lock)) = lockfile::Lockfile::load() {
+        for package in &lock.packages {
+            if let Some(kind) = secrets::literal_credential(&package.git) {
+                warnings.push(format!(
+                    "{}: package '{}' has {} in its URL",
+                    lockfile::LOCKFILE_PATH,
+                    package.name,
+                    kind
+                ));
+            }
+        }
+    }
 
-    let executable = format!("build/{}", project_name);
+    if warnings.is_empty() {
+        log::status!("✓ No problems found");
+        return;
+    }
+    for warning in &warnings {
+        log::problem!("Warning: {}", warning);
+    }
+}
 
-    if !Path::new(&executable).exists() {
-        eprintln!("Error: Executable '{}' not found.", executable);
-        std::process::exit(1);
+/// Accept out-of-band changes in external/ by locking the on-disk commits
+fn cmd_sync_accept_local() -> error::Result<()> {
+    let drift = lockfile::accept_local()
+        .context(|| format!("failed to update {}", lockfile::LOCKFILE_PATH))?;
+    if drift.is_empty() {
+        log::status!("project.lock already matches external/");
+        return Ok(());
+    }
+    for d in &drift {
+        log::status!(
+            "  ✓ Locked {} at {} (was {})",
+            d.name,
+            short_hash(&d.actual),
+            short_hash(&d.locked)
+        );
     }
+    log::status!("✓ Updated {}", lockfile::LOCKFILE_PATH);
+    Ok(())
+}
 
-    println!("\nRunning {}...\n", executable);
+fn cmd_sync_accept_changes() -> error::Result<()> {
+    let dependencies = read_config()?.all_dependencies();
+    let (drift, modified) = lockfile::accept_changes(&dependencies)
+        .context(|| format!("failed to update {}", lockfile::LOCKFILE_PATH))?;
+    if drift.is_empty() && modified.is_empty() {
+        log::status!("project.lock already matches external/");
+        return Ok(());
+    }
+    for d in &drift {
+        log::status!(
+            "  ✓ Locked {} at {} (was {})",
+            d.name,
+            short_hash(&d.actual),
+            short_hash(&d.locked)
+        );
+    }
+    for m in &modified {
+        log::status!(
+            "  ✓ Locked the files of {} as {}{}",
+            m.name,
+            short_hash(&m.actual),
+            if m.locked.is_empty() {
+                String::new()
+            } else {
+                format!(" (was {})", short_hash(&m.locked))
+            }
+        );
+    }
+    log::status!("✓ Updated {}", lockfile::LOCKFILE_PATH);
+    Ok(())
+}
 
-    // Run the executable
-    let status = Command::new(&executable)
-        .status();
+/// Warn when dependencies were changed in external/ behind c1's back
+fn warn_on_dependency_drift() {
+    let drift = lockfile::detect_drift();
+    if drift.is_empty() {
+        return;
+    }
+    for d in &drift {
+        log::problem!(
+            "Warning: dependency '{}' is checked out at {} but project.lock records {}",
+            d.name,
+            short_hash(&d.actual),
+            short_hash(&d.locked)
+        );
+    }
+    log::problem!(
+        "  Run `c1 sync --accept-local` to lock the on-disk state, or `c1 sync` to restore the locked commits."
+    );
+}
 
-    match status {
-        Ok(s) => {
-            if !s.success() {
-                if let Some(code) = s.code() {
-                    std::process::exit(code);
-                }
-            }
-        }
-        Err(e) => {
-            eprintln!("Failed to run executable: {}", e);
-            std::process::exit(1);
-        }
+/// Check generated files against project.lock
+fn cmd_verify() -> error::Result<()> {
+    let stale = bootstrap::stale_scripts();
+    if stale.is_empty() {
+        log::status!("✓ Generated files are up to date");
+        return Ok(());
+    }
+    for file in &stale {
+        log::problem!("✗ {} is out of date with project.lock", file);
     }
+    Err(Error::failure("generated files are out of date")
+        .hint("run `c1 export bootstrap` to regenerate"))
 }
 
-/// Add a git dependency to the project
-fn cmd_add(url: String, tag: Option<String>, branch: Option<String>) {
-    let config_path = "project.toml";
+/// Update dependencies in place to the newest tag or latest branch commit
+fn cmd_update(
+    name: Option<String>,
+    verify: Option<update_verify::Oracle>,
+    bisect: bool,
+) -> error::Result<()> {
+    update_verify::recover()?;
+    let dependencies = read_config()?.all_dependencies();
 
-    if !Path::new(config_path).exists() {
-        eprintln!("Error: project.toml not found. Are you in a c1 project?");
-        std::process::exit(1);
+    if let Some(ref n) = name
+        && !dependencies.contains_key(n)
+    {
+        return Err(Error::usage(format!("Dependency '{}' not found in project.toml", n)));
     }
 
-    // Extract package name from git URL
-    let pkg_name = extract_package_name(&url);
+    create_external_dir()?;
+    let lock_present = Path::new(lockfile::LOCKFILE_PATH).exists();
+    let mut summary = update_verify::Summary::default();
 
-    println!("Adding dependency: {}...", pkg_name);
+    for (dep_name, value) in &dependencies {
+        if name.as_ref().is_some_and(|n| n != dep_name) {
+            continue;
+        }
+        log::status!("Updating dependency: {}...", dep_name);
 
-    // Ensure external directory exists
-    fs::create_dir_all("external").expect("Failed to create external directory");
+        let Some(table) = value.as_table() else {
+            log::problem!("  ✗ Invalid dependency format for {}", dep_name);
+            continue;
+        };
+        if table.contains_key("path") {
+            log::status!("  {} is a path dependency, nothing to update", dep_name);
+            continue;
+        }
+        if table.contains_key(pkg_config::KEY) {
+            log::status!("  {} is a system package, nothing to update", dep_name);
+            continue;
+        }
+        if let Some(rev) = table.get("rev").and_then(|v| v.as_str()) {
+            log::status!("  {} is pinned to {}, nothing to update", dep_name, short_hash(rev));
+            continue;
+        }
+        let Some(git_url) = table.get("git").and_then(|v| v.as_str()) else {
+            log::problem!("  ✗ No 'git' URL specified for {}", dep_name);
+            continue;
+        };
+        let tag = table.get("tag").and_then(|v| v.as_str());
+        let branch = table.get("branch").and_then(|v| v.as_str());
+        let target_dir = format!("external/{}", dep_name);
 
-    let target_dir = format!("external/{}", pkg_name);
+        // Nothing to update in place, fall back to a fresh clone
+        if !Path::new(&target_dir).join(".git").exists() {
+            let subdir = subdir::of(value);
+            let cloned = clone_dependency(
+                dep_name,
+                git_url,
+                tag,
+                branch,
+                is_shallow(table),
+                None,
+                subdir.is_some(),
+            )
+            .and_then(|_| subdir::apply(dep_name, subdir));
+            if let Err(e) = cloned {
+                log::problem!("  ✗ {}", e);
+            }
+            continue;
+        }
 
-    // Remove existing directory if it exists
-    if Path::new(&target_dir).exists() {
-        println!("  Removing existing {}...", target_dir);
-        fs::remove_dir_all(&target_dir).expect("Failed to remove existing directory");
-    }
+        let old = lockfile::head_commit(&target_dir).unwrap_or_default();
 
-    // Build git clone command
-    let mut cmd = Command::new("git");
-    cmd.args(["clone", &url, &target_dir]);
+        // Ask the (cached) remote first so an up-to-date dependency costs no fetch
+        match upstream_commit(git_url, tag, branch) {
+            Ok(Some(upstream)) if upstream == old => {
+                log::status!("  {} is up to date ({})", dep_name, short_hash(&old));
+                continue;
+            }
+            Ok(_) if remote::is_offline() => {
+                log::problem!("  ✗ {} has upstream changes, but --offline was given", dep_name);
+                continue;
+            }
+            Ok(_) | Err(remote::RemoteError::Failed(_)) => {}
+            Err(e) => {
+                log::problem!("  ✗ {}: {}", dep_name, e);
+                continue;
+            }
+        }
 
-    // Add branch or tag if specified
-    if let Some(ref b) = branch {
-        cmd.args(["--branch", b, "--single-branch"]);
-    } else if let Some(ref t) = tag {
-        cmd.args(["--branch", t, "--single-branch"]);
-    }
+        if verify.is_some()
+            && let Err(e) = update_verify::begin(dep_name, &old, table)
+        {
+            log::problem!("  ✗ {}: {}", dep_name, e);
+            continue;
+        }
 
-    // Execute git clone
-    match cmd.output() {
-        Ok(output) => {
-            if output.status.success() {
-                println!("  ✓ Cloned {} to {}", pkg_name, target_dir);
-            } else {
-                eprintln!("  ✗ Failed to clone {}", pkg_name);
-                eprintln!("    {}", String::from_utf8_lossy(&output.stderr));
-                std::process::exit(1);
+        let new_tag = match update_checkout(&target_dir, tag) {
+            Ok(new_tag) => new_tag,
+            Err(e) => {
+                log::problem!("  ✗ Failed to update {}: {}", dep_name, e);
+                if verify.is_some()
+                    && let Err(e) = update_verify::rollback(dep_name)
+                {
+                    log::problem!("  ✗ Failed to restore {}: {}", dep_name, e);
+                }
+                continue;
             }
+        };
+        let new = lockfile::head_commit(&target_dir).unwrap_or_default();
+
+        if old == new {
+            log::status!("  {} is up to date ({})", dep_name, short_hash(&new));
+            update_verify::finish(dep_name);
+            continue;
         }
-        Err(e) => {
-            eprintln!("  ✗ Error cloning {}: {}", pkg_name, e);
-            std::process::exit(1);
+        log::status!("  ✓ {} {} → {}", dep_name, short_hash(&old), short_hash(&new));
+
+        // Keep the manifest pointing at the tag we moved to
+        if let Some(ref t) = new_tag
+            && Some(t.as_str()) != tag
+        {
+            let entry = git_dependency_entry(
+                git_url,
+                Some(t),
+                None,
+                None,
+                is_shallow(table),
+                submodule::is_submodule(table),
+                subdir::of(value),
+            );
+            match update_project_toml(dep_name, &entry) {
+                Ok(()) => log::status!("  ✓ Updated {} tag to {} in project.toml", dep_name, t),
+                Err(e) => log::problem!("  ✗ {}", e),
+            }
+        }
+        if let Some(oracle) = verify
+            && !update_verify::verify(dep_name, &old, &new, oracle, bisect, &mut summary)?
+        {
+            continue;
+        }
+        if lock_present {
+            lockfile::record(dep_name, git_url, new_tag.as_deref().or(tag), branch);
         }
     }
 
-    // Update project.toml
-    update_project_toml(&pkg_name, &url, tag, branch);
-
-    println!("✓ Added {} to project.toml", pkg_name);
+    summary.print();
+    if summary.has_held_back() {
+        return Err(Error::failure("some dependencies were held back"));
+    }
+    Ok(())
 }
 
-/// Extract package name from git URL
-fn extract_package_name(url: &str) -> String {
-    // Handle URLs like:
-    // - https://github.com/user/repo.git
-    // - git@github.com:user/repo.git
-    // - https://github.com/user/repo
-    let url = url.trim_end_matches(".git");
-    let name = url
-        .rsplit('/')
-        .next()
-        .or_else(|| url.rsplit(':').next())
-        .unwrap_or("unknown");
-    name.to_string()
+/// The commit `c1 update` would move to, according to `git ls-remote`
+fn upstream_commit(
+    url: &str,
+    tag: Option<&str>,
+    branch: Option<&str>,
+) -> Result<Option<String>, remote::RemoteError> {
+    let refs = remote::ls_remote(url)?;
+    Ok(match (tag, branch) {
+        (Some(_), _) => remote::newest_tag(&refs)
+            .and_then(|t| remote::ref_commit(&refs, &format!("refs/tags/{}", t))),
+        (None, Some(b)) => remote::ref_commit(&refs, &format!("refs/heads/{}", b)),
+        (None, None) => remote::ref_commit(&refs, "HEAD"),
+    })
 }
 
-/// Update project.toml with new dependency
-fn update_project_toml(name: &str, url: &str, tag: Option<String>, branch: Option<String>) {
-    let config_path = "project.toml";
-    let content = fs::read_to_string(config_path).expect("Failed to read project.toml");
+/// Move an existing checkout forward. Tag dependencies jump to the newest tag
+/// (which is returned); branch and default-branch dependencies fast-forward.
+fn update_checkout(dir: &str, tag: Option<&str>) -> Result<Option<String>, String> {
+    if tag.is_none() {
+        run_git(dir, &["pull", "--ff-only", "--quiet"])?;
+        return Ok(None);
+    }
 
-    // Build dependency entry
-    let dep_entry = if let Some(t) = tag {
-        format!("{} = {{ git = \"{}\", tag = \"{}\" }}", name, url, t)
-    } else if let Some(b) = branch {
-        format!("{} = {{ git = \"{}\", branch = \"{}\" }}", name, url, b)
-    } else {
-        format!("{} = {{ git = \"{}\" }}", name, url)
+    run_git(dir, &["fetch", "--tags", "--quiet", "origin"])?;
+    let tags = run_git(dir, &["tag", "--list", "--sort=-v:refname"])?;
+    let Some(newest) = tags.lines().next().map(str::to_string) else {
+        return Err("no tags found upstream".to_string());
     };
+    checkout_detached(dir, &format!("tags/{}", newest))?;
+    Ok(Some(newest))
+}
 
-    // Find [dependencies] section and add entry
-    let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
-    let mut dep_section_idx = None;
-    let mut insert_idx = None;
-
-    for (i, line) in lines.iter().enumerate() {
-        if line.trim() == "[dependencies]" {
-            dep_section_idx = Some(i);
-        } else if dep_section_idx.is_some() {
-            // Check if we hit another section
-            if line.trim().starts_with('[') && !line.trim().starts_with("[dependencies") {
-                insert_idx = Some(i);
-                break;
-            }
-            // Check if this dependency already exists
-            if line.trim().starts_with(&format!("{} ", name)) || line.trim().starts_with(&format!("{}=", name)) {
-                // Replace existing entry
-                lines[i] = dep_entry.clone();
-                fs::write(config_path, lines.join("\n")).expect("Failed to write project.toml");
-                return;
-            }
+/// Check out `target` in a detached HEAD. A shallow clone may not contain the
+/// requested commit, in which case the history is unshallowed and the checkout
+/// retried instead of failing.
+fn checkout_detached(dir: &str, target: &str) -> Result<(), String> {
+    let checkout = ["checkout", "--quiet", "--detach", target];
+    match run_git(dir, &checkout) {
+        Ok(_) => Ok(()),
+        Err(e) if Path::new(dir).join(".git/shallow").exists() => {
+            log::status!("  {} not in shallow clone, fetching full history...", target);
+            run_git(dir, &["fetch", "--unshallow", "--tags", "--quiet", "origin"])
+                .map_err(|fetch_err| format!("{} (unshallow failed: {})", e, fetch_err))?;
+            run_git(dir, &checkout).map(|_| ())
         }
+        Err(e) => Err(e),
     }
+}
 
-    // Insert after [dependencies] section
-    if let Some(idx) = dep_section_idx {
-        let insert_pos = insert_idx.unwrap_or(lines.len());
-        // Find the first non-comment, non-empty line after [dependencies]
-        let mut pos = idx + 1;
-        while pos < insert_pos {
-            let line = lines[pos].trim();
-            if !line.is_empty() && !line.starts_with('#') {
-                break;
-            }
-            pos += 1;
-        }
-        lines.insert(pos, dep_entry);
-    } else {
-        // No [dependencies] section, add it
-        lines.push(String::new());
-        lines.push("[dependencies]".to_string());
-        lines.push(dep_entry);
+/// Run a git command inside `dir`, returning trimmed stdout
+fn run_git(dir: impl AsRef<Path>, args: &[&str]) -> Result<String, String> {
+    let mut git = Command::new("git");
+    git.arg("-C").arg(dir.as_ref()).args(args);
+    log::command(&git);
+    let started = Instant::now();
+    let output = git.output().map_err(|e| {
+        build_log::finished(None, started.elapsed());
+        format!("failed to run git: {}", e)
+    })?;
+    build_log::finished(output.status.code(), started.elapsed());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.trim().is_empty() {
+        log::verbose!("{}", secrets::redact(stderr.trim_end()));
     }
+    if !output.status.success() {
+        return Err(secrets::redact(String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
 
-    fs::write(config_path, lines.join("\n")).expect("Failed to write project.toml");
+fn short_hash(hash: &str) -> &str {
+    &hash[..hash.len().min(7)]
 }
 
-/// Clean the build directory
-fn cmd_clean() {
-    let build_dir = "build";
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if !Path::new(build_dir).exists() {
-        println!("Build directory does not exist, nothing to clean.");
-        return;
+    #[test]
+    fn sanitized_names_pass_the_name_check() {
+        for (name, expected) in [
+            ("Team α", "Team"),
+            ("C Projects", "C_Projects"),
+            ("café-crème", "cafe-creme"),
+            ("Straße", "Strasse"),
+            ("2048 game", "project_2048_game"),
+            ("test", "test_app"),
+            ("αβγ", "my_project"),
+        ] {
+            let sanitized = sanitize_project_name(name);
+            assert_eq!(sanitized, expected, "for {:?}", name);
+            assert!(check_project_name(&sanitized, "c1 init --name").is_ok());
+        }
     }
 
-    println!("Cleaning build directory...");
-
-    // Remove all contents in build directory
-    match fs::remove_dir_all(build_dir) {
-        Ok(_) => {
-            // Recreate empty build directory
-            fs::create_dir_all(build_dir).expect("Failed to recreate build directory");
-            println!("✓ Build directory cleaned successfully!");
-        }
-        Err(e) => {
-            eprintln!("Failed to clean build directory: {}", e);
-            std::process::exit(1);
+    /// `entry` as `set_manifest_entry` and the manifest reader see it
+    fn parse_entry(entry: &str) -> toml::Table {
+        let mut parsed: toml::Table = toml::from_str(&format!("entry = {}", entry)).unwrap();
+        match parsed.remove("entry") {
+            Some(toml::Value::Table(table)) => table,
+            other => panic!("not a table: {:?}", other),
         }
     }
-}
 
-fn cmd_sync() {
-    let config_path = "project.toml";
-    
-    if !Path::new(config_path).exists() {
-        eprintln!("Error: project.toml not found");
-        std::process::exit(1);
+    const MANIFEST: &str = "\
+# The app's manifest
+[project]
+name = \"app\"   # keep it short
+version = \"0.1.0\"
+
+[dependencies]
+# Pinned for the 2.x API
+json = { git = \"https://example.com/json.git\", tag = \"v2.1\" } # see #12
+log = {git=\"https://example.com/log.git\",branch=\"main\"}
+
+[build]
+links = [ \"m\",  \"pthread\" ]";
+
+    /// `MANIFEST` with `from` replaced by `to`, which must occur once
+    fn changed(from: &str, to: &str) -> String {
+        assert_eq!(MANIFEST.matches(from).count(), 1, "{:?}", from);
+        MANIFEST.replace(from, to)
     }
 
-    let content = fs::read_to_string(config_path).expect("Failed to read project.toml");
-    let config: ProjectConfig = match toml::from_str(&content) {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Error parsing project.toml: {}", e);
-            std::process::exit(1);
-        }
-    };
+    #[test]
+    fn replacing_a_manifest_entry_changes_only_its_value() {
+        let updated = with_manifest_entry(
+            MANIFEST,
+            "dependencies",
+            "json",
+            "{ git = \"https://example.com/json.git\", tag = \"v2.2\" }",
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(updated, changed("tag = \"v2.1\"", "tag = \"v2.2\""));
 
-    if config.dependencies.is_empty() {
-        println!("No dependencies to sync");
-        return;
+        let updated = with_manifest_entry(MANIFEST, "project", "version", "\"0.2.0\"")
+            .ok()
+            .unwrap();
+        assert_eq!(updated, changed("\"0.1.0\"", "\"0.2.0\""));
+
+        // The last line, without a newline after it
+        let updated = with_manifest_entry(MANIFEST, "build", "links", "[\"m\"]")
+            .ok()
+            .unwrap();
+        assert_eq!(updated, changed("[ \"m\",  \"pthread\" ]", "[\"m\"]"));
     }
 
-    // Ensure external directory exists
-    fs::create_dir_all("external").expect("Failed to create external directory");
-
-    for (name, value) in config.dependencies {
-        println!("Syncing dependency: {}...", name);
-        
-        if let Some(table) = value.as_table() {
-            if let Some(git_url) = table.get("git").and_then(|v| v.as_str()) {
-                let target_dir = format!("external/{}", name);
-                
-                // Remove existing directory if it exists
-                if Path::new(&target_dir).exists() {
-                    println!("  Removing existing {}...", target_dir);
-                    fs::remove_dir_all(&target_dir).expect("Failed to remove existing directory");
-                }
+    #[test]
+    fn inserting_a_manifest_entry_adds_only_its_line() {
+        let updated = with_manifest_entry(
+            MANIFEST,
+            "dependencies",
+            "zlib",
+            "{ git = \"https://example.com/zlib.git\" }",
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(
+            updated,
+            changed(
+                "branch=\"main\"}\n",
+                "branch=\"main\"}\nzlib = { git = \"https://example.com/zlib.git\" }\n"
+            )
+        );
 
-                // Build git clone command
-                let mut cmd = Command::new("git");
-                cmd.args(["clone", git_url, &target_dir]);
+        let updated = with_manifest_entry(MANIFEST, "build", "out_dir", "\"out\"")
+            .ok()
+            .unwrap();
+        assert_eq!(updated, format!("{}\nout_dir = \"out\"", MANIFEST));
+    }
 
-                // Add branch or tag if specified
-                if let Some(branch) = table.get("branch").and_then(|v| v.as_str()) {
-                    cmd.args(["--branch", branch, "--single-branch"]);
-                } else if let Some(tag) = table.get("tag").and_then(|v| v.as_str()) {
-                    cmd.args(["--branch", tag, "--single-branch"]);
-                }
+    #[test]
+    fn manifest_entries_escape_their_values() {
+        let entry = inline_table([("path", "..\\tests\\lib".into())]);
+        assert_eq!(parse_entry(&entry)["path"].as_str(), Some("..\\tests\\lib"));
+        assert!(entry.parse::<toml_edit::Value>().is_ok());
 
-                // Execute git clone
-                match cmd.output() {
-                    Ok(output) => {
-                        if output.status.success() {
-                            println!("  ✓ Cloned {} to {}", name, target_dir);
-                        } else {
-                            eprintln!("  ✗ Failed to clone {}", name);
-                            eprintln!("    {}", String::from_utf8_lossy(&output.stderr));
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("  ✗ Error cloning {}: {}", name, e);
-                    }
-                }
-            } else {
-                eprintln!("  ✗ No 'git' URL specified for {}", name);
-            }
-        } else {
-            eprintln!("  ✗ Invalid dependency format for {}", name);
-        }
+        let url = "https://example.com/a\"b.git";
+        let entry = git_dependency_entry(
+            url,
+            Some("v1.0"),
+            None,
+            None,
+            false,
+            false,
+            Some("C:\\sub dir"),
+        );
+        let table = parse_entry(&entry);
+        assert_eq!(table["git"].as_str(), Some(url));
+        assert_eq!(table["tag"].as_str(), Some("v1.0"));
+        assert_eq!(table["shallow"].as_bool(), Some(false));
+        assert_eq!(table["subdir"].as_str(), Some("C:\\sub dir"));
+    }
+
+    #[test]
+    fn git_entries_keep_the_usual_layout() {
+        assert_eq!(
+            git_dependency_entry(
+                "https://example.com/a.git",
+                None,
+                Some("main"),
+                None,
+                true,
+                false,
+                None
+            ),
+            "{ git = \"https://example.com/a.git\", branch = \"main\" }"
+        );
+        assert_eq!(
+            git_dependency_entry(
+                "https://example.com/a.git",
+                None,
+                None,
+                None,
+                true,
+                true,
+                None
+            ),
+            "{ git = \"https://example.com/a.git\", submodule = true }"
+        );
     }
 
-    println!("\n✓ Dependency sync complete");
+    #[test]
+    fn flag_paths_with_spaces_are_quoted() {
+        assert_eq!(flag_path(Path::new("/opt/sdk/link.ld")), "/opt/sdk/link.ld");
+        assert_eq!(
+            flag_path(Path::new("/home/me/C Projects/link.ld")),
+            "\"/home/me/C Projects/link.ld\""
+        );
+        assert_eq!(
+            path_arg("--gcov-tool=", Path::new("/opt/My Tools/gcov")),
+            std::ffi::OsString::from("--gcov-tool=/opt/My Tools/gcov")
+        );
+    }
 }
@@ -0,0 +1,189 @@
+//! `schema_version` and `c1 upgrade-manifest`.
+//!
+//! `c1 new` and `c1 init` write `schema_version = 1` at the top of
+//! project.toml. A manifest without it comes from an older c1: it still
+//! parses, with defaults for what it lacks, and commands reading it print a
+//! one-line hint once. `c1 upgrade-manifest` brings it to the current
+//! layout with toml_edit, so comments and formatting stay:
+//!
+//! - a bare `edition = "99"` (or `99`) becomes `"c99"`
+//! - `[build] warnings = "none"` spells out what a manifest without the key
+//!   gets, next to the presets it can switch to
+//! - `layout = "src"` is written when main.c is only in src/
+//! - `schema_version` is set
+//!
+//! Each change is listed, and the original is kept as project.toml.bak. A
+//! manifest that is already current is left alone, so running it twice
+//! changes nothing.
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{self, Context, Error};
+use crate::{log, safe_path};
+
+/// The layout this c1 writes
+pub const SCHEMA_VERSION: i64 = 1;
+
+const MANIFEST: &str = "project.toml";
+const BACKUP: &str = "project.toml.bak";
+
+static HINTED: AtomicBool = AtomicBool::new(false);
+
+/// Print, once, that project.toml is older or newer than this c1
+pub fn hint(version: i64) {
+    if version == SCHEMA_VERSION || HINTED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    if version < SCHEMA_VERSION {
+        log::problem!(
+            "Hint: project.toml is from an older c1; run `c1 upgrade-manifest` to update it"
+        );
+    } else {
+        log::problem!(
+            "Warning: project.toml has schema_version {}, newer than this c1 knows ({}); \
+             consider upgrading c1",
+            version,
+            SCHEMA_VERSION
+        );
+    }
+}
+
+/// Whether `content` lacks the current `schema_version`, for parse errors
+/// that an upgrade may fix
+pub fn is_old(content: &str) -> bool {
+    content
+        .parse::<toml::Table>()
+        .ok()
+        .and_then(|table| table.get("schema_version")?.as_integer())
+        .is_none_or(|version| version < SCHEMA_VERSION)
+}
+
+pub fn cmd_upgrade() -> error::Result<()> {
+    let content =
+        fs::read_to_string(MANIFEST).context(|| format!("failed to read {}", MANIFEST))?;
+    let mut manifest: toml_edit::DocumentMut = content
+        .parse()
+        .map_err(|e| Error::config(format!("failed to parse {}: {}", MANIFEST, e)))?;
+    let version = manifest.get("schema_version").and_then(|v| v.as_integer());
+    if let Some(version) = version.filter(|v| *v > SCHEMA_VERSION) {
+        return Err(Error::config(format!(
+            "{} has schema_version {}, newer than this c1 knows ({})",
+            MANIFEST, version, SCHEMA_VERSION
+        ))
+        .hint("upgrade c1 instead"));
+    }
+
+    let mut changes = Vec::new();
+    changes.extend(edition(&mut manifest));
+    changes.extend(warnings(&mut manifest));
+    changes.extend(layout(&mut manifest));
+    if version != Some(SCHEMA_VERSION) {
+        set_version(&mut manifest, version.is_none());
+        changes.push(format!("schema_version = {}", SCHEMA_VERSION));
+    }
+    if changes.is_empty() {
+        log::status!(
+            "{} is already at schema_version {}, nothing to upgrade",
+            MANIFEST,
+            SCHEMA_VERSION
+        );
+        return Ok(());
+    }
+
+    safe_path::write(BACKUP, &content).context(|| format!("failed to write {}", BACKUP))?;
+    safe_path::write(MANIFEST, manifest.to_string())
+        .context(|| format!("failed to write {}", MANIFEST))?;
+    for change in &changes {
+        log::status!("  ✓ {}", change);
+    }
+    log::status!(
+        "✓ Upgraded {} to schema_version {} (the original is in {})",
+        MANIFEST,
+        SCHEMA_VERSION,
+        BACKUP
+    );
+    Ok(())
+}
+
+/// Write `schema_version` at the top, after the comments that opened the file
+fn set_version(manifest: &mut toml_edit::DocumentMut, new: bool) {
+    manifest.insert("schema_version", toml_edit::value(SCHEMA_VERSION));
+    if !new {
+        return;
+    }
+    let first = manifest
+        .iter_mut()
+        .filter_map(|(_, item)| item.as_table_mut())
+        .min_by_key(|table| table.position());
+    let opening = first.map(|table| {
+        let opening = table
+            .decor()
+            .prefix()
+            .and_then(|p| p.as_str())
+            .unwrap_or("")
+            .to_string();
+        table.decor_mut().set_prefix("\n");
+        opening
+    });
+    if let Some(mut key) = manifest.key_mut("schema_version") {
+        key.leaf_decor_mut().set_prefix(opening.unwrap_or_default());
+    }
+}
+
+/// `edition = "99"` or `99` to `"c99"`
+fn edition(manifest: &mut toml_edit::DocumentMut) -> Option<String> {
+    let value = manifest
+        .get_mut("project")?
+        .get_mut("edition")?
+        .as_value_mut()?;
+    let bare = match value {
+        toml_edit::Value::Integer(n) => n.value().to_string(),
+        toml_edit::Value::String(s) if !s.value().starts_with('c') => s.value().clone(),
+        _ => return None,
+    };
+    let edition = format!("c{}", bare);
+    if !crate::EDITIONS.contains(&edition.as_str()) {
+        return None;
+    }
+    let decor = value.decor().clone();
+    *value = toml_edit::Value::from(edition.as_str());
+    *value.decor_mut() = decor;
+    Some(format!(
+        "[project] edition = \"{}\" (was {})",
+        edition, bare
+    ))
+}
+
+/// Spell out the preset a manifest without `[build] warnings` gets
+fn warnings(manifest: &mut toml_edit::DocumentMut) -> Option<String> {
+    let build = manifest
+        .entry("build")
+        .or_insert_with(toml_edit::table)
+        .as_table_mut()?;
+    if build.contains_key("warnings") {
+        return None;
+    }
+    let mut value = toml_edit::value("none");
+    if let Some(value) = value.as_value_mut() {
+        value
+            .decor_mut()
+            .set_suffix("  # none, default, strict or pedantic");
+    }
+    build.insert("warnings", value);
+    Some("[build] warnings = \"none\", as before; \"default\" or \"strict\" turn checks on".into())
+}
+
+/// `layout = "src"` where c1 so far went by main.c's place
+fn layout(manifest: &mut toml_edit::DocumentMut) -> Option<String> {
+    let project = manifest.get_mut("project")?.as_table_like_mut()?;
+    if project.contains_key("layout")
+        || Path::new("main.c").exists()
+        || !Path::new("src/main.c").exists()
+    {
+        return None;
+    }
+    project.insert("layout", toml_edit::value("src"));
+    Some("[project] layout = \"src\", where main.c is".to_string())
+}
@@ -0,0 +1,229 @@
+//! Build matrices: `c1 build --all-profiles`, `--all-targets` and repeated
+//! `--target`.
+//!
+//! Every combination of profile (dev, release and each `[profile.<name>]`)
+//! and target (the host and each `[target.<name>]`) builds as its own
+//! `c1 build` in its usual build directory, so a later matrix build reuses
+//! what an earlier one configured. A failed combination does not stop the
+//! others unless `--fail-fast` is given; the command fails afterwards when
+//! any did. The summary lists each combination's result and its executable,
+//! or its build directory when there is none.
+//!
+//! The first combination runs alone, since it may refresh CMakeLists.txt
+//! and external/. The configure steps of the rest are independent of each
+//! other and run up to `-j` at a time before the builds, whose own output
+//! reports any configure error.
+
+use std::process::{Child, Command, Stdio};
+
+use crate::error::{self, Error};
+use crate::{BuildOptions, log};
+
+struct Combination {
+    profile: String,
+    /// `host` for builds without `--target`
+    target: String,
+    options: BuildOptions,
+}
+
+/// Build every combination of the selected profiles and targets
+pub fn cmd_build(
+    options: &BuildOptions,
+    all_profiles: bool,
+    all_targets: bool,
+    targets: &[String],
+    fail_fast: bool,
+) -> error::Result<()> {
+    let config = crate::read_config()?;
+    let profiles: Vec<String> = if all_profiles {
+        let mut names = vec!["dev".to_string(), "release".to_string()];
+        names.extend(
+            config
+                .profile
+                .keys()
+                .filter(|name| *name != "dev" && *name != "release")
+                .cloned(),
+        );
+        names
+    } else {
+        vec![profile_name(options)]
+    };
+    let targets: Vec<Option<String>> = if all_targets {
+        std::iter::once(None)
+            .chain(config.target.keys().cloned().map(Some))
+            .collect()
+    } else if targets.is_empty() {
+        vec![None]
+    } else {
+        let mut unique: Vec<Option<String>> = Vec::new();
+        for target in targets {
+            if !unique.iter().flatten().any(|t| t == target) {
+                unique.push(Some(target.clone()));
+            }
+        }
+        unique
+    };
+
+    let mut combinations = Vec::new();
+    for target in &targets {
+        for profile in &profiles {
+            let mut options = BuildOptions {
+                target_config: target.clone(),
+                ..options.clone()
+            };
+            if all_profiles {
+                options = options.with_profile(Some(profile.clone()))?;
+            }
+            combinations.push(Combination {
+                profile: profile.clone(),
+                target: target.clone().unwrap_or_else(|| "host".to_string()),
+                options,
+            });
+        }
+    }
+
+    let mut results: Vec<Option<bool>> = vec![None; combinations.len()];
+    for (i, combination) in combinations.iter().enumerate() {
+        if i == 1 {
+            configure_rest(&combinations[1..], options.jobs);
+        }
+        log::status!(
+            "==> Building {} for {}",
+            combination.profile,
+            combination.target
+        );
+        let ok = run_c1(&combination.options.to_args());
+        results[i] = Some(ok);
+        if !ok {
+            log::problem!(
+                "✗ {} for {} failed to build",
+                combination.profile,
+                combination.target
+            );
+            if fail_fast {
+                break;
+            }
+        }
+    }
+
+    print_summary(&combinations, &results);
+    let failed = results.iter().filter(|r| **r == Some(false)).count();
+    if failed > 0 {
+        let error = Error::build(format!(
+            "{} of {} builds failed",
+            failed,
+            combinations.len()
+        ));
+        return Err(if fail_fast {
+            error.hint("run without --fail-fast to try the remaining combinations anyway")
+        } else {
+            error
+        });
+    }
+    Ok(())
+}
+
+/// `dev`, `release` or the `--profile` of a single-profile matrix
+fn profile_name(options: &BuildOptions) -> String {
+    match &options.profile {
+        Some(name) => name.clone(),
+        None if options.release => "release".to_string(),
+        None => "dev".to_string(),
+    }
+}
+
+/// Run `c1 build --configure-only` for these combinations, up to `jobs` at
+/// a time. Failures are left for the build that follows to report.
+fn configure_rest(combinations: &[Combination], jobs: Option<usize>) {
+    let jobs = jobs
+        .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1);
+    if jobs < 2 || combinations.len() < 2 {
+        return;
+    }
+    log::verbose!(
+        "Configuring {} combinations, {} at a time",
+        combinations.len(),
+        jobs
+    );
+    let mut running: Vec<Child> = Vec::new();
+    for combination in combinations {
+        if running.len() == jobs {
+            let _ = running.remove(0).wait();
+        }
+        let options = BuildOptions {
+            configure_only: true,
+            emit: Vec::new(),
+            timings: false,
+            examples: false,
+            ..combination.options.clone()
+        };
+        let mut command = Command::new(crate::workspace::current_exe());
+        command
+            .args(options.to_args())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        crate::project_lock::pass_to(&mut command);
+        if let Ok(child) = command.spawn() {
+            running.push(child);
+        }
+    }
+    for mut child in running {
+        let _ = child.wait();
+    }
+}
+
+pub fn run_c1(args: &[String]) -> bool {
+    let mut command = Command::new(crate::workspace::current_exe());
+    command.args(args);
+    log::pass_to(&mut command);
+    crate::project_lock::pass_to(&mut command);
+    command.status().is_ok_and(|s| s.success())
+}
+
+/// One line per combination: its result and its executable or build
+/// directory
+fn print_summary(combinations: &[Combination], results: &[Option<bool>]) {
+    let profile_width = combinations
+        .iter()
+        .map(|c| c.profile.len())
+        .max()
+        .unwrap_or(0)
+        .max("Profile".len());
+    let target_width = combinations
+        .iter()
+        .map(|c| c.target.len())
+        .max()
+        .unwrap_or(0)
+        .max("Target".len());
+    println!();
+    println!(
+        "{:<pw$}  {:<tw$}  {:<7}  Artifact",
+        "Profile",
+        "Target",
+        "Result",
+        pw = profile_width,
+        tw = target_width
+    );
+    for (combination, result) in combinations.iter().zip(results) {
+        let (status, artifact) = match result {
+            Some(true) => {
+                let artifact = crate::artifact_path(&combination.options)
+                    .unwrap_or_else(|| crate::target_build_dir(&combination.options));
+                ("ok", artifact.display().to_string())
+            }
+            Some(false) => ("failed", String::new()),
+            None => ("skipped", String::new()),
+        };
+        let line = format!(
+            "{:<pw$}  {:<tw$}  {:<7}  {}",
+            combination.profile,
+            combination.target,
+            status,
+            artifact,
+            pw = profile_width,
+            tw = target_width
+        );
+        println!("{}", line.trim_end());
+    }
+}
@@ -0,0 +1,137 @@
+//! `c1 run` when the executable project.toml names was not built.
+//!
+//! c1 looks for `<build dir>/<name>`, so renaming the `project()` or the
+//! `add_executable` target in CMakeLists.txt without project.toml leaves the
+//! build working and `c1 run` failing. Instead of only listing the paths it
+//! tried, c1 lists the executables the build did produce (the files marked
+//! executable, or `.exe` on Windows, apart from CMake's own and the tests')
+//! with the likely fix. When there is exactly one, `c1 run --guess` runs it,
+//! and on a terminal c1 offers to.
+
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+
+use crate::error::{self, Error};
+use crate::{log, rename, safe_path};
+
+/// Files in a build directory that are never the project's program
+const NOT_PROGRAMS: &[&str] = &[
+    ".a", ".so", ".dylib", ".dll", ".lib", ".o", ".obj", ".cmake", ".sh", ".txt", ".json",
+    ".ninja", ".log", ".bin", ".hex", ".map",
+];
+
+/// The executable to run in place of the missing `expected`, or an error
+/// explaining what was built instead
+pub fn locate(
+    build_dir: &Path,
+    expected: &str,
+    probed: &[PathBuf],
+    guess: bool,
+) -> error::Result<PathBuf> {
+    let found = candidates(build_dir);
+    if let [only] = found.as_slice() {
+        if guess {
+            log::problem!(
+                "Warning: '{}' was not built, running {} instead",
+                expected,
+                only.display()
+            );
+            return Ok(only.clone());
+        }
+        if io::stdin().is_terminal() {
+            log::problem!("Executable for '{}' not found.", expected);
+            if safe_path::confirm(&format!("Run {} instead? [y/N] ", only.display())) {
+                return Ok(only.clone());
+            }
+        }
+    }
+
+    let mut message = format!("Executable for '{}' not found. Looked in:", expected);
+    for path in probed {
+        message.push_str(&format!("\n  {}", path.display()));
+    }
+    if found.is_empty() {
+        message.push_str("\nThe build produced no other executable.");
+    } else {
+        message.push_str("\nThe build produced:");
+        for path in &found {
+            message.push_str(&format!("\n  {}", path.display()));
+        }
+    }
+    log::verbose!("Scanned {} for executables", build_dir.display());
+
+    let mut hint = match rename::cmake_project_name() {
+        Some(cmake_name) if cmake_name != expected => format!(
+            "CMakeLists.txt names the project '{}' but project.toml says '{}'; \
+             run `c1 rename-project {}` to use the new name in project.toml too",
+            cmake_name, expected, cmake_name
+        ),
+        _ if found.is_empty() => {
+            "check that CMakeLists.txt still has add_executable(${PROJECT_NAME} ...)".to_string()
+        }
+        _ => format!(
+            "the program is built as `{}` only when add_executable in CMakeLists.txt \
+             uses ${{PROJECT_NAME}}",
+            expected
+        ),
+    };
+    if found.len() == 1 {
+        hint.push_str("; `c1 run --guess` runs the one that was built");
+    }
+    Err(Error::failure(message).hint(hint))
+}
+
+/// The programs at the top of `build_dir` and in its per-configuration
+/// subdirectories, without the tests' and benches'
+fn candidates(build_dir: &Path) -> Vec<PathBuf> {
+    let others: Vec<String> = ["tests", "benches"]
+        .iter()
+        .flat_map(|dir| fs::read_dir(dir).into_iter().flatten().flatten())
+        .filter_map(|entry| {
+            let path = entry.path();
+            (path.extension()? == "c").then(|| path.file_stem()?.to_str().map(str::to_string))?
+        })
+        .collect();
+    let mut found = Vec::new();
+    for dir in [
+        build_dir.to_path_buf(),
+        build_dir.join("Debug"),
+        build_dir.join("Release"),
+    ] {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let stem = name
+                .strip_suffix(std::env::consts::EXE_SUFFIX)
+                .unwrap_or(&name);
+            if !entry.file_type().is_ok_and(|t| t.is_file())
+                || name.starts_with('.')
+                || name.to_ascii_lowercase().starts_with("cmake")
+                || NOT_PROGRAMS.iter().any(|ext| name.ends_with(ext))
+                || others.iter().any(|other| other == stem)
+                || !is_executable(&path)
+            {
+                continue;
+            }
+            found.push(path);
+        }
+    }
+    found.sort();
+    found
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("exe"))
+}
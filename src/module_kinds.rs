@@ -0,0 +1,174 @@
+//! The shapes `c1 create --kind` generates a module in.
+//!
+//! `plain` is the empty header and source c1 has always written. `adt` is
+//! an opaque handle: the header only forward-declares the struct, with
+//! create/destroy functions, and the source defines it and hands out
+//! instances from a fixed pool, so the module needs no heap. `service` is a
+//! singleton with init/deinit around a static state struct. Function names
+//! are the module name with `/` replaced by `_`, as in test targets.
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Plain,
+    Adt,
+    Service,
+}
+
+/// Every kind, with what it generates, for `--list-kinds`
+const KINDS: [(&str, Kind, &str); 3] = [
+    (
+        "plain",
+        Kind::Plain,
+        "a header and a source with TODOs (the default)",
+    ),
+    (
+        "adt",
+        Kind::Adt,
+        "an opaque handle type with create/destroy, allocated from a static pool",
+    ),
+    (
+        "service",
+        Kind::Service,
+        "a singleton with init/deinit and a static state struct",
+    ),
+];
+
+/// The names `--kind` accepts
+pub const NAMES: [&str; 3] = [KINDS[0].0, KINDS[1].0, KINDS[2].0];
+
+/// The kind `--kind` names; clap has already checked it is one
+pub fn parse(name: &str) -> Kind {
+    KINDS
+        .iter()
+        .find(|(kind_name, _, _)| *kind_name == name)
+        .map_or(Kind::Plain, |(_, kind, _)| *kind)
+}
+
+pub fn cmd_list() {
+    for (name, _, description) in KINDS {
+        println!("{:<8} {}", name, description);
+    }
+}
+
+/// The C identifier functions and types of module `name` start with
+fn prefix(name: &str) -> String {
+    name.replace('/', "_")
+}
+
+/// A doxygen stub for a declaration, when they are asked for
+fn doc(doc_comments: bool, brief: &str) -> String {
+    if doc_comments {
+        format!("/**\n * @brief {}\n */\n", brief)
+    } else {
+        String::new()
+    }
+}
+
+/// The header of a module of this kind
+pub fn header(name: &str, kind: Kind, doc_comments: bool) -> String {
+    let id = prefix(name);
+    let declarations = match kind {
+        Kind::Plain => return crate::module_header(name, doc_comments),
+        Kind::Adt => format!(
+            "{}typedef struct {id} {id}_t;\n\n\
+             {}{id}_t *{id}_create(void);\n\n\
+             {}void {id}_destroy({id}_t *self);\n\n\
+             // TODO: Add accessors here",
+            doc(
+                doc_comments,
+                &format!("Opaque handle of the {} module.", name)
+            ),
+            doc(doc_comments, "Create an instance; NULL when none is left."),
+            doc(doc_comments, "Give an instance back; NULL is ignored."),
+        ),
+        Kind::Service => format!(
+            "{}int {id}_init(void);\n\n\
+             {}void {id}_deinit(void);\n\n\
+             // TODO: Add the service's functions here",
+            doc(
+                doc_comments,
+                &format!("Start the {} service; 0 on success.", name)
+            ),
+            doc(
+                doc_comments,
+                &format!("Stop the {} service; harmless when not started.", name)
+            ),
+        ),
+    };
+    crate::header_with(name, doc_comments, &declarations)
+}
+
+/// The source of a module of this kind
+pub fn source(name: &str, kind: Kind) -> String {
+    let id = prefix(name);
+    let max = format!("{}_MAX_INSTANCES", id.to_uppercase());
+    match kind {
+        Kind::Plain => crate::module_source(name),
+        Kind::Adt => format!(
+            r#"#include "{name}.h"
+
+#include <stdbool.h>
+#include <stddef.h>
+
+// Instances come from a fixed pool, so the module needs no heap
+#ifndef {max}
+#define {max} 4
+#endif
+
+struct {id} {{
+    bool in_use;
+    // TODO: Add the fields here
+}};
+
+static struct {id} {id}_pool[{max}];
+
+{id}_t *{id}_create(void) {{
+    for (size_t i = 0; i < {max}; i++) {{
+        if (!{id}_pool[i].in_use) {{
+            {id}_pool[i] = (struct {id}){{.in_use = true}};
+            // TODO: Initialize the fields
+            return &{id}_pool[i];
+        }}
+    }}
+    return NULL;
+}}
+
+void {id}_destroy({id}_t *self) {{
+    if (self == NULL) {{
+        return;
+    }}
+    // TODO: Release what the instance holds
+    self->in_use = false;
+}}
+"#
+        ),
+        Kind::Service => format!(
+            r#"#include "{name}.h"
+
+#include <stdbool.h>
+
+static struct {{
+    bool initialized;
+    // TODO: Add the service's state here
+}} {id}_state;
+
+int {id}_init(void) {{
+    if ({id}_state.initialized) {{
+        return 0;
+    }}
+    // TODO: Set up the service
+    {id}_state.initialized = true;
+    return 0;
+}}
+
+void {id}_deinit(void) {{
+    if (!{id}_state.initialized) {{
+        return;
+    }}
+    // TODO: Release what init set up
+    {id}_state.initialized = false;
+}}
+"#
+        ),
+    }
+}
@@ -0,0 +1,141 @@
+//! `c1 outdated`: which git dependencies have something newer upstream.
+//!
+//! A `tag` dependency is compared with the newest tag the remote advertises,
+//! ordered like `git tag --sort=v:refname` with a leading `v` ignored. A
+//! `branch` dependency, and one following the default branch or pinned to a
+//! `rev`, is compared by commit: the checkout in external/ against the tip of
+//! the branch (or of the remote's HEAD). The remote queries go through the
+//! cache in `remote`, and one that fails is reported in its row without
+//! stopping the others. Path and pkg-config dependencies have no upstream and
+//! are not listed.
+
+use std::cmp::Ordering;
+use std::path::Path;
+
+use crate::error::{self, Error};
+use crate::{lockfile, log, remote};
+
+struct Row {
+    name: String,
+    current: String,
+    latest: String,
+    behind: bool,
+}
+
+pub fn cmd_outdated(exit_code: bool) -> error::Result<()> {
+    let config = crate::read_config()?;
+    let mut rows = Vec::new();
+    for (name, value) in &config.all_dependencies() {
+        let Some(git) = value.get("git").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let field = |key: &str| value.get(key).and_then(|v| v.as_str());
+        log::verbose!("Checking {}...", name);
+        let row = match (field("tag"), field("branch")) {
+            (Some(tag), _) => compare_tag(name, git, tag),
+            (None, branch) => compare_commit(name, git, branch),
+        };
+        rows.push(row);
+    }
+
+    if rows.is_empty() {
+        log::status!("No git dependencies to check");
+        return Ok(());
+    }
+    print_table(&rows);
+
+    let behind = rows.iter().filter(|row| row.behind).count();
+    if behind == 0 {
+        log::status!("\nAll dependencies are up to date");
+    } else {
+        log::status!("\n* newer upstream; `c1 update <name>` moves to it");
+        if exit_code {
+            return Err(Error::failure(format!(
+                "{} {} outdated",
+                behind,
+                if behind == 1 {
+                    "dependency is"
+                } else {
+                    "dependencies are"
+                }
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn compare_tag(name: &str, git: &str, tag: &str) -> Row {
+    let latest = remote::ls_remote(git).map(|refs| remote::newest_tag(&refs));
+    let (latest, behind) = match latest {
+        Ok(Some(newest)) => {
+            let behind = remote::version_cmp(&newest, tag) == Ordering::Greater;
+            (newest, behind)
+        }
+        Ok(None) => ("(no tags)".to_string(), false),
+        Err(e) => (failure(&e), false),
+    };
+    Row {
+        name: name.to_string(),
+        current: tag.to_string(),
+        latest,
+        behind,
+    }
+}
+
+/// Compare the checkout with the tip of `branch`, or the remote's HEAD
+fn compare_commit(name: &str, git: &str, branch: Option<&str>) -> Row {
+    let dir = Path::new("external").join(name);
+    let current = lockfile::head_commit(&dir);
+    let reference = match branch {
+        Some(branch) => format!("refs/heads/{}", branch),
+        None => "HEAD".to_string(),
+    };
+    let tip = remote::ls_remote(git).map(|refs| remote::ref_commit(&refs, &reference));
+    let label = |commit: &str| match branch {
+        Some(branch) => format!("{} ({})", crate::short_hash(commit), branch),
+        None => crate::short_hash(commit).to_string(),
+    };
+
+    let (latest, behind) = match &tip {
+        Ok(Some(tip)) => (label(tip), current.as_ref().is_some_and(|c| c != tip)),
+        Ok(None) => (format!("({} not found)", reference), false),
+        Err(e) => (failure(e), false),
+    };
+    Row {
+        name: name.to_string(),
+        current: current.map_or("(not synced)".to_string(), |c| label(&c)),
+        latest,
+        behind,
+    }
+}
+
+/// A failed query, on one line of the table
+fn failure(error: &remote::RemoteError) -> String {
+    let error = error.to_string();
+    format!("error: {}", error.lines().next().unwrap_or_default())
+}
+
+fn print_table(rows: &[Row]) {
+    let width = |column: fn(&Row) -> &str, title: &str| {
+        rows.iter()
+            .map(|row| column(row).chars().count())
+            .chain([title.len()])
+            .max()
+            .unwrap_or_default()
+    };
+    let name_width = width(|row| &row.name, "Name");
+    let current_width = width(|row| &row.current, "Current");
+    println!(
+        "{:<name_width$}  {:<current_width$}  Latest",
+        "Name", "Current"
+    );
+    for row in rows {
+        println!(
+            "{:<name_width$}  {:<current_width$}  {}{}",
+            row.name,
+            row.current,
+            row.latest,
+            if row.behind { " *" } else { "" }
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! `c1 package`: a source tarball that builds without c1.
+//!
+//! The archive holds the project's sources, CMakeLists.txt and manifest under
+//! a `<name>-<version>/` directory, plus external/ with `--vendored`. build/,
+//! `.git` and whatever the project's .gitignore or .c1ignore matches are left
+//! out. A
+//! generated `PKG-INFO` at the top lists the `[project]` metadata: name,
+//! version, description, authors, license and repository.
+//!
+//! The tar is written here rather than by a `tar` binary so that the output is
+//! reproducible: files are sorted, owners are zeroed, and every mtime is
+//! `SOURCE_DATE_EPOCH` (or the time of the HEAD commit). `gzip -n` then
+//! compresses it without embedding a timestamp.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::build_history::bytes;
+use crate::error::{self, Context, Error};
+use crate::ignore::Ignore;
+use crate::{log, safe_path};
+
+/// What goes into the archive, when present
+const CONTENTS: &[&str] = &[
+    "main.c",
+    "src",
+    "include",
+    "tests",
+    "CMakeLists.txt",
+    "project.toml",
+    "project.lock",
+    "README.md",
+    "LICENSE",
+];
+
+pub fn cmd_package(vendored: bool, verify: bool) -> error::Result<()> {
+    let config = crate::read_config()?;
+    let stem = format!("{}-{}", config.project.name, config.project.version);
+    let build_dir = crate::resolve_build_dir(None);
+
+    let ignore = Ignore::load(".gitignore");
+    let mut files = Vec::new();
+    for entry in CONTENTS
+        .iter()
+        .chain(vendored.then_some(&"external"))
+        .map(Path::new)
+    {
+        collect(entry, &ignore, &build_dir, &mut files);
+    }
+    // --vendored packs external/ whatever the implicit rules say
+    files.retain(|file| {
+        Ignore::project()
+            .excluded_by(&unix_path(file))
+            .is_none_or(|rule| vendored && rule.line == 0)
+    });
+    if vendored && !Path::new("external").is_dir() {
+        log::problem!(
+            "Warning: --vendored given but external/ does not exist; run `c1 sync` first"
+        );
+    }
+    files.sort();
+
+    log::status!("Packaging {} ({} files)...", stem, files.len());
+    let mtime = source_date();
+    let mut tar = Vec::new();
+    for file in &files {
+        let data = fs::read(file).context(|| format!("failed to read {}", file.display()))?;
+        let name = format!("{}/{}", stem, unix_path(file));
+        append(&mut tar, &name, &data, mode(file), mtime)
+            .map_err(|e| Error::failure(format!("cannot archive {}: {}", file.display(), e)))?;
+    }
+    let info = pkg_info(&config.project);
+    append(
+        &mut tar,
+        &format!("{}/PKG-INFO", stem),
+        info.as_bytes(),
+        0o644,
+        mtime,
+    )
+    .map_err(|e| Error::failure(format!("cannot archive PKG-INFO: {}", e)))?;
+    // End of archive: two zero blocks
+    tar.extend_from_slice(&[0; 1024]);
+
+    fs::create_dir_all(&build_dir)
+        .context(|| format!("failed to create {}", build_dir.display()))?;
+    let tar_path = build_dir.join(format!("{}.tar", stem));
+    let archive = build_dir.join(format!("{}.tar.gz", stem));
+    safe_path::write(&tar_path, &tar)
+        .context(|| format!("failed to write {}", tar_path.display()))?;
+    // gzip replaces the archive of an earlier run in place
+    safe_path::check(&archive, "overwrite")
+        .context(|| format!("failed to write {}", archive.display()))?;
+    let status = match Command::new("gzip")
+        .args(["-n", "-9", "-f"])
+        .arg(&tar_path)
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::failure("gzip not found.").hint(format!(
+                "the uncompressed archive is at {}",
+                tar_path.display()
+            )));
+        }
+        Err(e) => return Err(Error::failure(format!("failed to run gzip: {}", e))),
+    };
+    if !status.success() {
+        return Err(Error::failure(format!(
+            "gzip failed on {}",
+            tar_path.display()
+        )));
+    }
+
+    let size = fs::metadata(&archive)
+        .context(|| format!("failed to read {}", archive.display()))?
+        .len();
+    log::status!("✓ Created {} ({})", archive.display(), bytes(size));
+
+    if verify {
+        verify_archive(&archive, files.len() + 1)?;
+    }
+    Ok(())
+}
+
+/// The `Key: value` lines of PKG-INFO, leaving out what the manifest does
+/// not declare
+fn pkg_info(project: &crate::Project) -> String {
+    let mut fields = vec![
+        ("Name", project.name.clone()),
+        ("Version", project.version.clone()),
+    ];
+    if !project.description.is_empty() {
+        fields.push(("Description", project.description.clone()));
+    }
+    for author in &project.authors {
+        fields.push(("Author", author.clone()));
+    }
+    if let Some(license) = &project.license {
+        fields.push(("License", license.clone()));
+    }
+    if let Some(repository) = &project.repository {
+        fields.push(("Repository", repository.clone()));
+    }
+    fields
+        .iter()
+        .map(|(key, value)| format!("{}: {}\n", key, value))
+        .collect()
+}
+
+/// Every file under `path` that is not ignored, build output or git metadata
+fn collect(path: &Path, ignore: &Ignore, build_dir: &Path, files: &mut Vec<PathBuf>) {
+    let is_dir = path.is_dir();
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    if !path.exists()
+        || name == ".git"
+        || path == build_dir
+        || ignore.is_ignored(&unix_path(path), is_dir)
+    {
+        return;
+    }
+    if !is_dir {
+        files.push(path.to_path_buf());
+        return;
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        collect(&entry.path(), ignore, build_dir, files);
+    }
+}
+
+fn unix_path(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// `SOURCE_DATE_EPOCH`, else the HEAD commit's time, else the epoch
+fn source_date() -> u64 {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .or_else(|| crate::run_git(".", &["log", "-1", "--format=%ct"]).ok())
+        .and_then(|secs| secs.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(unix)]
+fn mode(path: &Path) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    let executable = fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o111 != 0);
+    if executable { 0o755 } else { 0o644 }
+}
+
+#[cfg(not(unix))]
+fn mode(_path: &Path) -> u32 {
+    0o644
+}
+
+/// Append one regular file in ustar format
+fn append(tar: &mut Vec<u8>, name: &str, data: &[u8], mode: u32, mtime: u64) -> Result<(), String> {
+    let (prefix, name) = split_name(name).ok_or("path is too long for a tar archive")?;
+    let mut header = [0u8; 512];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    octal(&mut header[100..108], mode as u64);
+    octal(&mut header[108..116], 0);
+    octal(&mut header[116..124], 0);
+    octal(&mut header[124..136], data.len() as u64);
+    octal(&mut header[136..148], mtime);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+    // The checksum is computed with its own field set to spaces
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..154].copy_from_slice(format!("{:06o}", checksum).as_bytes());
+    header[154] = 0;
+
+    tar.extend_from_slice(&header);
+    tar.extend_from_slice(data);
+    tar.resize(tar.len().next_multiple_of(512), 0);
+    Ok(())
+}
+
+/// Split a path into ustar's 155-byte prefix and 100-byte name fields
+fn split_name(path: &str) -> Option<(&str, &str)> {
+    if path.len() <= 100 {
+        return Some(("", path));
+    }
+    path.match_indices('/')
+        .map(|(i, _)| (&path[..i], &path[i + 1..]))
+        .find(|(prefix, name)| prefix.len() <= 155 && name.len() <= 100)
+}
+
+/// A NUL-terminated octal number filling `field`
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+}
+
+/// List the archive with the system tar, checking it has every file
+fn verify_archive(archive: &Path, expected: usize) -> error::Result<()> {
+    let output = Command::new("tar")
+        .arg("-tzf")
+        .arg(archive)
+        .output()
+        .map_err(|e| Error::failure(format!("failed to run tar: {}", e)))?;
+    if !output.status.success() {
+        return Err(Error::failure(format!(
+            "{} is not a valid archive: {}",
+            archive.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let listing = String::from_utf8_lossy(&output.stdout);
+    for entry in listing.lines() {
+        println!("  {}", entry);
+    }
+    let count = listing.lines().count();
+    if count != expected {
+        return Err(Error::failure(format!(
+            "archive lists {} files, expected {}",
+            count, expected
+        )));
+    }
+    log::status!("✓ Verified {} files", count);
+    Ok(())
+}
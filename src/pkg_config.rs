@@ -0,0 +1,81 @@
+//! System libraries found through pkg-config.
+//!
+//! ```toml
+//! [dependencies]
+//! sdl2 = { pkg-config = "sdl2" }
+//! ```
+//!
+//! Such a dependency is never fetched: `c1 add --pkg-config` and `c1 sync`
+//! only check that pkg-config knows the package, then wire it into
+//! CMakeLists.txt with `pkg_check_modules`, which supplies its include paths,
+//! compile flags and libraries.
+
+use std::io;
+use std::process::Command;
+
+use crate::log;
+
+/// The dependency table key naming the package
+pub const KEY: &str = "pkg-config";
+
+/// The package a dependency entry asks pkg-config for, if it is one
+pub fn package(value: &toml::Value) -> Option<&str> {
+    value.get(KEY)?.as_str()
+}
+
+/// Fail unless pkg-config finds `package`
+pub fn check(package: &str) -> Result<(), String> {
+    let mut command = Command::new("pkg-config");
+    command.args(["--exists", package]);
+    log::command(&command);
+    match command.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(_) => {
+            let base = package.strip_prefix("lib").unwrap_or(package);
+            Err(format!(
+                "system package '{}' was not found by pkg-config; install its development files \
+                 (e.g. lib{}-dev or {}-devel) or add the directory of its .pc file to \
+                 PKG_CONFIG_PATH",
+                package, base, base
+            ))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(format!(
+            "pkg-config is not installed, but it is needed to find '{}'",
+            package
+        )),
+        Err(e) => Err(format!("failed to run pkg-config: {}", e)),
+    }
+}
+
+/// The installed version of `package`, when pkg-config knows it
+pub fn version(package: &str) -> Option<String> {
+    let mut command = Command::new("pkg-config");
+    command.args(["--modversion", package]);
+    log::command(&command);
+    let output = command.output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The CMakeLists.txt lines linking the project, or with `dev` its tests and
+/// benches, against `package`
+pub fn cmake_block(name: &str, package: &str, dev: bool) -> String {
+    let prefix: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let prefix = format!("C1_PKG_{}", prefix);
+    format!(
+        "find_package(PkgConfig REQUIRED)\n\
+         pkg_check_modules({prefix} REQUIRED IMPORTED_TARGET {package})\n{}",
+        crate::dep_wiring::link(&format!("PkgConfig::{}", prefix), dev)
+    )
+}
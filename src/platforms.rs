@@ -0,0 +1,238 @@
+//! Platform-abstraction modules: one header, one implementation per platform.
+//!
+//! `c1 create uart --platforms posix,stm32` writes `include/uart.h` plus
+//! `src/uart_posix.c` and `src/uart_stm32.c`, and records the mapping under
+//! `[platforms]` in project.toml. CMakeLists.txt gets a managed block per
+//! module that compiles only the source for `C1_PLATFORM`, which `c1 build`
+//! passes at configure time from `[target.<name>] platform` (selected with
+//! `--target`) or `[build] platform`.
+
+use std::fs;
+use std::path::Path;
+
+use crate::cmake_lists;
+use crate::error::{self, Context, Error};
+use crate::events;
+use crate::log;
+use crate::safe_path;
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+
+pub fn cmd_create_platforms(
+    name: &str,
+    platforms: &[String],
+    doc_comments: bool,
+) -> error::Result<()> {
+    for platform in platforms {
+        validate_platform(platform)?;
+    }
+    let config = crate::read_config()?;
+    if config.platforms.contains_key(name) {
+        return Err(
+            Error::usage(format!("{} already has platform implementations", name)).hint(format!(
+                "use `c1 create {} --add-platform <platform>` to add one",
+                name
+            )),
+        );
+    }
+
+    let header_file = format!("include/{}.h", name);
+    let sources: Vec<String> = platforms.iter().map(|p| source_file(name, p)).collect();
+    for file in std::iter::once(&header_file).chain(&sources) {
+        if Path::new(file).exists() {
+            return Err(Error::usage(format!("{} already exists", file)));
+        }
+    }
+
+    let mut written = Vec::new();
+    let files = std::iter::once((header_file.clone(), crate::module_header(name, doc_comments))).chain(
+        sources
+            .iter()
+            .map(|s| (s.clone(), crate::module_source(name))),
+    );
+    for (path, content) in files {
+        if let Err(e) = safe_path::write(&path, content) {
+            // Best-effort rollback of what was already written
+            for path in &written {
+                let _ = safe_path::remove_file(path);
+            }
+            return Err(e).context(|| format!("failed to create {}", path));
+        }
+        written.push(path);
+    }
+    log::status!("✓ Created {}", written.join(", "));
+    for path in &written {
+        events::file_created(path);
+    }
+
+    record(name, platforms)?;
+    update_cmake(name, platforms, Some(&header_file))
+}
+
+pub fn cmd_add_platform(name: &str, platform: &str) -> error::Result<()> {
+    validate_platform(platform)?;
+    let config = crate::read_config()?;
+    let Some(existing) = config.platforms.get(name) else {
+        return Err(
+            Error::usage(format!("{} is not a platform module", name)).hint(format!(
+                "create it with `c1 create {} --platforms <a,b>`",
+                name
+            )),
+        );
+    };
+    if existing.iter().any(|p| p == platform) {
+        return Err(Error::usage(format!(
+            "{} already has a {} implementation",
+            name, platform
+        )));
+    }
+
+    let source = source_file(name, platform);
+    if Path::new(&source).exists() {
+        return Err(Error::usage(format!("{} already exists", source)));
+    }
+    crate::create_file(&source, &crate::module_source(name))?;
+    log::status!("✓ Created {}", source);
+
+    let mut platforms = existing.clone();
+    platforms.push(platform.to_string());
+    record(name, &platforms)?;
+    update_cmake(name, &platforms, None)
+}
+
+/// The platform selected for this build, or an error naming the choices
+pub fn active_platform(
+    config: &crate::ProjectConfig,
+    target_config: Option<&str>,
+) -> Result<Option<String>, String> {
+    let platform = match target_config {
+        Some(name) => {
+            let Some(target) = config.target.get(name) else {
+                let available: Vec<&str> = config.target.keys().map(String::as_str).collect();
+                return Err(format!(
+                    "unknown target '{}' (available: {})",
+                    name,
+                    if available.is_empty() {
+                        "none".to_string()
+                    } else {
+                        available.join(", ")
+                    }
+                ));
+            };
+            target.platform.clone()
+        }
+        None => config.build.platform.clone(),
+    };
+
+    // Every platform module needs an implementation for the active platform
+    for (module, platforms) in &config.platforms {
+        let supported = platform.as_ref().is_some_and(|p| platforms.contains(p));
+        if !supported {
+            let wanted = match &platform {
+                Some(p) => format!("platform '{}'", p),
+                None => "this build (no platform selected)".to_string(),
+            };
+            return Err(format!(
+                "{} has no implementation for {}; available platforms: {}",
+                module,
+                wanted,
+                platforms.join(", ")
+            ));
+        }
+    }
+    Ok(platform)
+}
+
+fn validate_platform(platform: &str) -> error::Result<()> {
+    if platform.is_empty() || !platform.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return Err(Error::usage(
+            "Platform name must contain only letters, numbers, and underscores",
+        ));
+    }
+    Ok(())
+}
+
+pub fn source_file(name: &str, platform: &str) -> String {
+    format!("src/{}_{}.c", name, platform)
+}
+
+/// Write `name = ["a", "b"]` under `[platforms]` in project.toml
+fn record(name: &str, platforms: &[String]) -> error::Result<()> {
+    let list: Vec<String> = platforms.iter().map(|p| format!("\"{}\"", p)).collect();
+    crate::set_manifest_entry("platforms", name, &format!("[{}]", list.join(", ")))?;
+    log::status!("✓ Recorded {} platforms in project.toml", name);
+    Ok(())
+}
+
+/// Replace (or insert before the target definition) the managed block for
+/// `name`, and register `header` in HEADERS when given
+fn update_cmake(name: &str, platforms: &[String], header: Option<&str>) -> error::Result<()> {
+    let Ok(mut content) = fs::read_to_string(CMAKE_PATH) else {
+        log::problem!("Warning: CMakeLists.txt not found, skipping automatic registration");
+        return Ok(());
+    };
+
+    if let Some(header) = header
+        && let cmake_lists::Registration::NoList = cmake_lists::register(&mut content, header)
+    {
+        cmake_lists::warn_unregistered(header);
+    }
+
+    let begin = format!("# @c1_platform {}", name);
+    let end = format!("# @c1_platform_end {}", name);
+    let block = cmake_block(name, platforms, &begin, &end);
+    let updated = match (content.find(&begin), content.find(&end)) {
+        (Some(start), Some(stop)) if stop > start => {
+            let stop = stop + end.len();
+            let stop = stop + content[stop..].find('\n').map_or(0, |i| i + 1);
+            format!("{}{}{}", &content[..start], block, &content[stop..])
+        }
+        _ => {
+            let Some(target) = find_target_line(&content) else {
+                log::problem!(
+                    "Warning: no add_executable/add_library in CMakeLists.txt; add the {} sources by hand",
+                    name
+                );
+                return Ok(());
+            };
+            format!("{}{}\n{}", &content[..target], block, &content[target..])
+        }
+    };
+
+    safe_path::write(CMAKE_PATH, updated).context(|| format!("failed to update {}", CMAKE_PATH))?;
+    log::status!("✓ Updated CMakeLists.txt");
+    Ok(())
+}
+
+fn cmake_block(name: &str, platforms: &[String], begin: &str, end: &str) -> String {
+    let mut block = format!("{}\n", begin);
+    for (i, platform) in platforms.iter().enumerate() {
+        let keyword = if i == 0 { "if" } else { "elseif" };
+        block.push_str(&format!(
+            "{}(C1_PLATFORM STREQUAL \"{}\")\n    list(APPEND SOURCES {})\n",
+            keyword,
+            platform,
+            source_file(name, platform)
+        ));
+    }
+    block.push_str(&format!(
+        "else()\n    message(FATAL_ERROR \"{}: no implementation for platform '${{C1_PLATFORM}}' (available: {})\")\nendif()\n{}\n",
+        name,
+        platforms.join(", "),
+        end
+    ));
+    block
+}
+
+/// Byte offset of the line defining the build target
+fn find_target_line(content: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("add_executable(") || trimmed.starts_with("add_library(") {
+            return Some(offset);
+        }
+        offset += line.len();
+    }
+    None
+}
@@ -0,0 +1,206 @@
+//! Build profiles from the `[profile.<name>]` tables in project.toml.
+//!
+//! ```toml
+//! [profile.release]
+//! opt_level = 3
+//! lto = true
+//!
+//! [profile.perf]
+//! inherits = "release"
+//! debug = true
+//! defines = ["PERF_COUNTERS"]
+//! ```
+//!
+//! `dev` and `release` are CMake's Debug and Release build types; their tables
+//! only add to what those build types already do, so without them builds are
+//! unchanged. They build in `<build dir>/debug` and `<build dir>/release`, so
+//! switching between them keeps both CMake caches. Any other profile is
+//! selected with `--profile <name>`, starts from the settings and build type
+//! of the profile it inherits (`release` unless it says `dev`) and builds in
+//! `<build dir>/<name>`.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{self, Error};
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct Profile {
+    /// `dev` or `release`; only for profiles other than those two
+    #[serde(default)]
+    inherits: Option<String>,
+    #[serde(default)]
+    opt_level: Option<OptLevel>,
+    /// Emit debug information (`-g`)
+    #[serde(default)]
+    debug: Option<bool>,
+    /// Link-time optimization, through CMAKE_INTERPROCEDURAL_OPTIMIZATION
+    #[serde(default)]
+    lto: Option<bool>,
+    #[serde(default)]
+    defines: Vec<String>,
+    #[serde(default)]
+    flags: Vec<String>,
+}
+
+/// `opt_level = 2` or `opt_level = "s"`
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+enum OptLevel {
+    Number(u8),
+    Name(String),
+}
+
+/// What a profile asks of the configure step
+pub struct Settings {
+    pub build_type: &'static str,
+    /// Macros to define, as `NAME` or `NAME=VALUE`
+    pub defines: Vec<String>,
+    /// Added to CMAKE_C_FLAGS
+    pub c_flags: Vec<String>,
+    /// Other cmake arguments
+    pub cmake_args: Vec<String>,
+}
+
+/// Whether `--profile <name>` builds on the Release build type
+pub fn is_release(profiles: &BTreeMap<String, Profile>, name: &str) -> error::Result<bool> {
+    match name {
+        "dev" => Ok(false),
+        "release" => Ok(true),
+        _ => Ok(base(name, lookup(profiles, name)?)? == "release"),
+    }
+}
+
+/// The settings for `--profile <name>`, or for dev/release after `--release`
+pub fn resolve(
+    profiles: &BTreeMap<String, Profile>,
+    release: bool,
+    name: Option<&str>,
+) -> error::Result<Settings> {
+    let builtin = if release { "release" } else { "dev" };
+    let name = name.unwrap_or(builtin);
+    let (build_type, profile) = match name {
+        "dev" | "release" => (name, profiles.get(name).cloned().unwrap_or_default()),
+        _ => {
+            let profile = lookup(profiles, name)?;
+            let base = base(name, profile)?;
+            let inherited = profiles.get(base).cloned().unwrap_or_default();
+            (base, inherited.merge(profile))
+        }
+    };
+    let build_type = if build_type == "release" {
+        "Release"
+    } else {
+        "Debug"
+    };
+
+    let mut settings = Settings {
+        build_type,
+        defines: profile.defines.clone(),
+        c_flags: Vec::new(),
+        cmake_args: Vec::new(),
+    };
+    // The build type's own flags come after CMAKE_C_FLAGS on the command
+    // line, so optimization and debug info have to replace them
+    if profile.opt_level.is_some() || profile.debug.is_some() {
+        let mut flags = Vec::new();
+        match &profile.opt_level {
+            Some(level) => flags.push(format!("-O{}", opt_level(name, level)?)),
+            None if build_type == "Release" => flags.push("-O3".to_string()),
+            None => {}
+        }
+        if profile.debug.unwrap_or(build_type == "Debug") {
+            flags.push("-g".to_string());
+        }
+        if build_type == "Release" {
+            flags.push("-DNDEBUG".to_string());
+        }
+        settings.cmake_args.push(format!(
+            "-DCMAKE_C_FLAGS_{}={}",
+            build_type.to_uppercase(),
+            flags.join(" ")
+        ));
+    }
+    if let Some(lto) = profile.lto {
+        settings.cmake_args.push(format!(
+            "-DCMAKE_INTERPROCEDURAL_OPTIMIZATION={}",
+            if lto { "ON" } else { "OFF" }
+        ));
+    }
+    settings.c_flags.extend(profile.flags);
+    Ok(settings)
+}
+
+/// A profile chosen with `--profile`, unless it is the built-in dev or release
+pub fn subdir(name: Option<&str>) -> Option<&str> {
+    name.filter(|name| !matches!(*name, "dev" | "release"))
+}
+
+/// The build subdirectory of a profile: `debug` or `release` for the
+/// built-in ones, the profile's own name for the others
+pub fn dir_name(name: Option<&str>, release: bool) -> &str {
+    match subdir(name) {
+        Some(name) => name,
+        None if release || name == Some("release") => "release",
+        None => "debug",
+    }
+}
+
+fn lookup<'a>(profiles: &'a BTreeMap<String, Profile>, name: &str) -> error::Result<&'a Profile> {
+    profiles.get(name).ok_or_else(|| {
+        let mut names = vec!["dev", "release"];
+        names.extend(
+            profiles
+                .keys()
+                .map(String::as_str)
+                .filter(|n| !matches!(*n, "dev" | "release")),
+        );
+        Error::usage(format!("profile '{}' is not defined in project.toml", name))
+            .hint(format!("available profiles: {}", names.join(", ")))
+    })
+}
+
+fn base<'a>(name: &str, profile: &'a Profile) -> error::Result<&'a str> {
+    match profile.inherits.as_deref() {
+        None => Ok("release"),
+        Some(base @ ("dev" | "release")) => Ok(base),
+        Some(other) => Err(Error::config(format!(
+            "[profile.{}] inherits '{}', but profiles can only inherit dev or release",
+            name, other
+        ))),
+    }
+}
+
+fn opt_level(name: &str, level: &OptLevel) -> error::Result<String> {
+    let level = match level {
+        OptLevel::Number(n) => n.to_string(),
+        OptLevel::Name(s) => s.clone(),
+    };
+    if matches!(
+        level.as_str(),
+        "0" | "1" | "2" | "3" | "s" | "z" | "g" | "fast"
+    ) {
+        Ok(level)
+    } else {
+        Err(Error::config(format!(
+            "[profile.{}] opt_level '{}' is not one of 0, 1, 2, 3, s, z, g, fast",
+            name, level
+        )))
+    }
+}
+
+impl Profile {
+    /// `child`'s settings on top of `self`'s
+    fn merge(self, child: &Profile) -> Profile {
+        let child = child.clone();
+        Profile {
+            inherits: child.inherits,
+            opt_level: child.opt_level.or(self.opt_level),
+            debug: child.debug.or(self.debug),
+            lto: child.lto.or(self.lto),
+            defines: [self.defines, child.defines].concat(),
+            flags: [self.flags, child.flags].concat(),
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Live progress for the git transfers `c1 add` and `c1 sync` start, and
+//! the archives they download.
+//!
+//! git runs with `--progress` and its stderr is read line by line: a
+//! "Receiving objects:  45% (450/1000), 1.20 MiB | 2.00 MiB/s" line moves a
+//! bar, and a phase without a percentage ("Enumerating objects") turns a
+//! spinner. An archive download moves its bar by the bytes received, or turns
+//! the spinner when the server did not say how many. Each transfer in flight
+//! has a line of its own at the bottom of the terminal, so the dependencies
+//! `c1 sync -j` fetches side by side show one meter each; what is printed
+//! meanwhile does so under `suspend`, to land above them. When stderr is not
+//! a terminal, under `--quiet` and with `--message-format json` there is no
+//! meter. Either way git's own messages are only shown with `--verbose`: a
+//! failure is reported by c1, with advice instead of git's output when access
+//! was refused.
+
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::{build_history, clone_cache, events, log, secrets, stream};
+
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+const BAR_WIDTH: usize = 20;
+/// Lines are cut to this many characters, so none wraps and throws off the
+/// redrawing
+const MAX_WIDTH: usize = 79;
+
+struct Board {
+    next_id: usize,
+    /// Id, name and status of each transfer in flight, oldest first
+    lines: Vec<(usize, String, String)>,
+    /// Lines on screen from the last draw
+    drawn: usize,
+}
+
+static BOARD: Mutex<Board> = Mutex::new(Board {
+    next_id: 0,
+    lines: Vec::new(),
+    drawn: 0,
+});
+
+fn board() -> MutexGuard<'static, Board> {
+    BOARD.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Whether transfers get a live meter
+pub fn is_live() -> bool {
+    io::stderr().is_terminal() && !log::is_quiet() && !events::is_json()
+}
+
+/// Run git `command`, a clone or fetch for the dependency `name`, with a
+/// meter while it transfers. Returns git's output and, when git reported it,
+/// how much was received ("1.20 MiB").
+pub fn run_git(
+    command: &mut Command,
+    name: &str,
+) -> io::Result<(stream::Captured, Option<String>)> {
+    if !is_live() {
+        let output = stream::run_observed(command, secrets::redact, &mut |line| {
+            if !line.trim().is_empty() {
+                log::verbose!("{}", line.trim_end());
+            }
+        })?;
+        return Ok((output, None));
+    }
+    command.arg("--progress");
+    let mut meter = Meter::start(name);
+    let output = stream::run_observed(command, secrets::redact, &mut |line| meter.update(line))?;
+    Ok((output, meter.received.take()))
+}
+
+/// Keeps the meters off the screen, for other output to go where they were;
+/// they are drawn again below it when this is dropped
+pub struct Suspended(MutexGuard<'static, Board>);
+
+pub fn suspend() -> Suspended {
+    let mut board = board();
+    board.clear();
+    Suspended(board)
+}
+
+impl Drop for Suspended {
+    fn drop(&mut self) {
+        self.0.draw();
+    }
+}
+
+/// The meter of a download c1 makes itself, removed when dropped; it draws
+/// nothing when transfers get no meter
+pub struct Download(Option<Meter>);
+
+pub fn download(name: &str) -> Download {
+    Download(is_live().then(|| Meter::start(name)))
+}
+
+impl Download {
+    /// Show `downloaded` bytes received of `total`, when the server said
+    pub fn update(&mut self, downloaded: u64, total: Option<u64>) {
+        let Some(meter) = &mut self.0 else {
+            return;
+        };
+        let received = build_history::bytes(downloaded);
+        let status = match total.filter(|&total| total > 0) {
+            Some(total) => format!(
+                "{} Downloading, {} of {}",
+                bar((downloaded.min(total) * 100 / total) as usize),
+                received,
+                build_history::bytes(total)
+            ),
+            None => {
+                meter.ticks += 1;
+                format!(
+                    "{} Downloading, {}",
+                    SPINNER[meter.ticks % SPINNER.len()],
+                    received
+                )
+            }
+        };
+        meter.show(status);
+    }
+}
+
+/// "1.20 MiB, 3.1s", for the line reporting a finished transfer
+pub fn summary(received: Option<&str>, elapsed: Duration) -> String {
+    match received {
+        Some(received) => format!("{}, {:.1}s", received, elapsed.as_secs_f64()),
+        None => format!("{:.1}s", elapsed.as_secs_f64()),
+    }
+}
+
+/// The size of `dir` on disk, for a clone git did not report a transfer
+/// size for
+pub fn disk_size(dir: &Path) -> String {
+    build_history::bytes(clone_cache::dir_size(dir))
+}
+
+/// One transfer's line on the board, removed when dropped
+struct Meter {
+    id: usize,
+    ticks: usize,
+    received: Option<String>,
+}
+
+impl Meter {
+    fn start(name: &str) -> Meter {
+        let mut board = board();
+        let id = board.next_id;
+        board.next_id += 1;
+        board
+            .lines
+            .push((id, name.to_string(), format!("{} connecting", SPINNER[0])));
+        board.draw();
+        Meter {
+            id,
+            ticks: 0,
+            received: None,
+        }
+    }
+
+    /// Take in a line of git's stderr
+    fn update(&mut self, line: &str) {
+        let line = line.trim();
+        let line = line.strip_prefix("remote:").unwrap_or(line).trim_start();
+        let Some((phase, rest)) = line.split_once(':') else {
+            return;
+        };
+        if !(phase.ends_with("objects") || phase.ends_with("deltas") || phase == "Updating files") {
+            return;
+        }
+        let percent = rest
+            .split_once('%')
+            .and_then(|(before, _)| before.trim().parse::<usize>().ok());
+        if phase == "Receiving objects"
+            && let Some((_, after)) = rest.split_once("), ")
+        {
+            let size = after.split([',', '|']).next().unwrap_or_default().trim();
+            if !size.is_empty() && size != "done." {
+                self.received = Some(size.to_string());
+            }
+        }
+
+        self.ticks += 1;
+        let mut status = match percent {
+            Some(percent) => format!("{} {}", bar(percent), phase),
+            None => format!("{} {}", SPINNER[self.ticks % SPINNER.len()], phase),
+        };
+        if let Some(received) = &self.received {
+            status.push_str(&format!(", {}", received));
+        }
+        self.show(status);
+    }
+
+    /// Put `status` on this transfer's line
+    fn show(&self, status: String) {
+        let mut board = board();
+        match board.lines.iter_mut().find(|(id, _, _)| *id == self.id) {
+            // Redrawing an unchanged line only flickers
+            Some(entry) if entry.2 == status => return,
+            Some(entry) => entry.2 = status,
+            None => return,
+        }
+        board.draw();
+    }
+}
+
+/// "[#########-----------]  45%"
+fn bar(percent: usize) -> String {
+    let filled = percent.min(100) * BAR_WIDTH / 100;
+    format!(
+        "[{}{}] {:>3}%",
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        percent
+    )
+}
+
+impl Drop for Meter {
+    fn drop(&mut self) {
+        let mut board = board();
+        board.lines.retain(|(id, _, _)| *id != self.id);
+        board.draw();
+    }
+}
+
+impl Board {
+    /// Erase the lines drawn last time
+    fn clear(&mut self) {
+        if self.drawn > 0 {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "\x1b[{}A\r\x1b[J", self.drawn);
+            let _ = stderr.flush();
+            self.drawn = 0;
+        }
+    }
+
+    fn draw(&mut self) {
+        if self.drawn == 0 && self.lines.is_empty() {
+            return;
+        }
+        let width = self
+            .lines
+            .iter()
+            .map(|(_, name, _)| name.chars().count())
+            .max()
+            .unwrap_or_default();
+        let mut text = String::new();
+        if self.drawn > 0 {
+            text.push_str(&format!("\x1b[{}A", self.drawn));
+        }
+        text.push_str("\r\x1b[J");
+        for (_, name, status) in &self.lines {
+            let line = format!("  {:<width$}  {}", name, status);
+            text.extend(line.chars().take(MAX_WIDTH));
+            text.push('\n');
+        }
+        let mut stderr = io::stderr().lock();
+        let _ = stderr.write_all(text.as_bytes());
+        let _ = stderr.flush();
+        self.drawn = self.lines.len();
+    }
+}
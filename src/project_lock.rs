@@ -0,0 +1,157 @@
+//! One mutating c1 at a time per project.
+//!
+//! `c1 build`, `run`, `test`, `clean`, `sync` and `add` hold `.c1/lock` while
+//! they work, so a `c1 clean` in one terminal cannot delete the build
+//! directory a watcher's `c1 build` is writing into, and `c1 sync` cannot
+//! replace external/ mid-compile. The lock is an OS file lock (flock,
+//! LockFileEx) on that file, which the system drops with the process, so one
+//! left by a killed or crashed c1 is gone without anyone taking it over. The
+//! file holds its owner's PID and command for the messages of those waiting,
+//! and is emptied rather than removed on release: a c1 blocked on the old
+//! file would otherwise lock a file nobody else sees. Another invocation
+//! waits for the lock, saying who holds it, or fails at once with
+//! `--no-wait`.
+//!
+//! c1s started by the holder, such as the builds of a matrix, share its lock
+//! through C1_PROJECT_LOCK instead of waiting for their parent. `c1 run` lets
+//! go once the build is done, so a long-running program does not hold up the
+//! next build.
+
+use std::fs::{self, File, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::{self, Context, Error};
+use crate::log;
+
+const LOCK_DIR: &str = ".c1";
+const LOCK_PATH: &str = ".c1/lock";
+/// The lock a parent c1 holds on its children's behalf
+const ENV_VAR: &str = "C1_PROJECT_LOCK";
+const POLL: Duration = Duration::from_millis(200);
+
+static NO_WAIT: AtomicBool = AtomicBool::new(false);
+/// The lock this process holds
+static HELD: Mutex<Option<Held>> = Mutex::new(None);
+
+struct Held {
+    /// Absolute, as children compare it
+    path: PathBuf,
+    /// Locked for as long as it is open
+    file: File,
+}
+
+/// Fail instead of waiting for a lock another c1 holds (`--no-wait`)
+pub fn set_no_wait(no_wait: bool) {
+    NO_WAIT.store(no_wait, Ordering::Relaxed);
+}
+
+/// Take the project's lock for `command`, waiting while another c1 holds it
+pub fn acquire(command: &str) -> error::Result<()> {
+    let path = std::path::absolute(LOCK_PATH).unwrap_or_else(|_| PathBuf::from(LOCK_PATH));
+    if std::env::var_os(ENV_VAR).is_some_and(|held| Path::new(&held) == path) {
+        log::verbose!("Using the project lock held by the parent c1");
+        return Ok(());
+    }
+    let holding = HELD
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .is_some_and(|held| held.path == path);
+    if holding {
+        return Ok(());
+    }
+    fs::create_dir_all(LOCK_DIR).context(|| format!("failed to create {}", LOCK_DIR))?;
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .context(|| format!("failed to open {}", LOCK_PATH))?;
+
+    let mut announced = false;
+    loop {
+        match file.try_lock() {
+            Ok(()) => break,
+            Err(TryLockError::WouldBlock) => {}
+            Err(TryLockError::Error(e)) => {
+                return Err(e).context(|| format!("failed to lock {}", LOCK_PATH));
+            }
+        }
+        let holder = holder(&path);
+        if NO_WAIT.load(Ordering::Relaxed) {
+            return Err(
+                Error::failure(format!("the project is locked by {}", holder))
+                    .hint("try again when it is done, or leave out --no-wait to wait for it"),
+            );
+        }
+        if !announced {
+            log::status!("Blocking on the project lock held by {}...", holder);
+            announced = true;
+        }
+        thread::sleep(POLL);
+    }
+
+    // Whatever a previous holder wrote is replaced, not appended to
+    file.set_len(0)
+        .and_then(|()| writeln!(file, "{} {}", std::process::id(), command))
+        .context(|| format!("failed to write {}", LOCK_PATH))?;
+    *HELD.lock().unwrap_or_else(|e| e.into_inner()) = Some(Held { path, file });
+    release_at_exit();
+    Ok(())
+}
+
+/// Give up the lock, if this process holds it
+pub fn release() {
+    let Some(held) = HELD.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+        return;
+    };
+    let _ = held.file.set_len(0);
+    let _ = held.file.unlock();
+}
+
+/// Let a child c1 share the lock this process holds
+pub fn pass_to(command: &mut Command) {
+    if let Some(held) = HELD.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        command.env(ENV_VAR, &held.path);
+    }
+}
+
+/// Who holds the lock at `path`, as its owner line says
+fn holder(path: &Path) -> String {
+    let owner = fs::read_to_string(path).unwrap_or_default();
+    match parse(&owner) {
+        (Some(pid), command) => format!("PID {} (c1 {})", pid, command),
+        // Just locked and not written yet
+        (None, _) => "another c1".to_string(),
+    }
+}
+
+/// The PID and command written into a lock file
+fn parse(owner: &str) -> (Option<u32>, &str) {
+    let (pid, command) = owner.trim().split_once(' ').unwrap_or((owner.trim(), ""));
+    (pid.parse().ok(), command)
+}
+
+/// Empty the lock file on `std::process::exit` too, which skips destructors;
+/// the system drops the lock itself then, but not the owner line
+#[cfg(unix)]
+fn release_at_exit() {
+    static REGISTERED: std::sync::Once = std::sync::Once::new();
+    extern "C" fn on_exit() {
+        release();
+    }
+    // SAFETY: atexit only stores the function pointer
+    REGISTERED.call_once(|| unsafe {
+        libc::atexit(on_exit);
+    });
+}
+
+#[cfg(not(unix))]
+fn release_at_exit() {}
@@ -0,0 +1,98 @@
+//! Directories in external/ that no dependency accounts for any more.
+//!
+//! After a sync, every entry of external/ is compared with the dependencies
+//! project.toml declares, directly or through other dependencies. Leftovers
+//! are reported; `c1 sync --prune` deletes them along with their block in
+//! CMakeLists.txt. Only what c1 itself put there is deleted: the link of a
+//! path dependency (never what it points at), extracted archives and git
+//! clones. A clone with
+//! uncommitted changes is only deleted after a yes on the terminal, or with
+//! `--force`. Git submodules and directories that are not clones, such as
+//! vendored code, are left alone.
+
+use std::io::IsTerminal;
+use std::path::Path;
+
+use crate::error::{self, Context};
+use crate::{archive, dep_wiring, log, safe_path};
+
+/// Report the leftovers in external/, deleting them when `prune` is set
+pub fn run(required: &toml::Table, prune: bool, force: bool) -> error::Result<()> {
+    let orphans = orphans(required);
+    if orphans.is_empty() {
+        return Ok(());
+    }
+    if !prune {
+        log::problem!(
+            "Warning: external/ has directories no dependency declares: {}",
+            orphans.join(", ")
+        );
+        log::problem!("  Run `c1 sync --prune` to delete them.");
+        return Ok(());
+    }
+
+    for name in &orphans {
+        let dir = Path::new("external").join(name);
+        let git = dir.join(".git");
+        if dir.is_symlink() {
+            safe_path::remove_file(&dir)
+                .context(|| format!("failed to remove the link {}", dir.display()))?;
+        } else if dir.join(archive::MARKER).is_file() {
+            safe_path::remove_dir_all(&dir)
+                .context(|| format!("failed to remove {}", dir.display()))?;
+        } else if git.is_file() {
+            log::status!(
+                "  Kept {}: it is a git submodule; remove it with `git rm {}`",
+                dir.display(),
+                dir.display()
+            );
+            continue;
+        } else if !git.is_dir() {
+            log::status!("  Kept {}: it is not a clone made by c1", dir.display());
+            continue;
+        } else {
+            if !force && is_dirty(&dir) && !confirm_dirty(&dir) {
+                log::status!(
+                    "  Kept {}: it has uncommitted changes (pass --force to delete it anyway)",
+                    dir.display()
+                );
+                continue;
+            }
+            safe_path::remove_dir_all(&dir)
+                .context(|| format!("failed to remove {}", dir.display()))?;
+        }
+        dep_wiring::remove(name);
+        log::status!("  ✓ Pruned {}", dir.display());
+    }
+    Ok(())
+}
+
+/// Entries of external/ that are not required, sorted
+fn orphans(required: &toml::Table) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir("external") else {
+        return Vec::new();
+    };
+    let mut orphans: Vec<String> = entries
+        .flatten()
+        .filter(|e| e.path().is_dir() || e.path().is_symlink())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        // Hidden entries are not dependencies
+        .filter(|name| !name.starts_with('.') && !required.contains_key(name))
+        .collect();
+    orphans.sort();
+    orphans
+}
+
+/// Whether the clone has changes `git status` would show; a clone git cannot
+/// read counts as changed
+pub fn is_dirty(dir: &Path) -> bool {
+    !crate::run_git(dir, &["status", "--porcelain"]).is_ok_and(|status| status.is_empty())
+}
+
+fn confirm_dirty(dir: &Path) -> bool {
+    std::io::stdin().is_terminal()
+        && safe_path::confirm(&format!(
+            "{} has uncommitted changes. Delete it anyway? [y/N] ",
+            dir.display()
+        ))
+}
@@ -0,0 +1,476 @@
+//! Cached queries against upstream hosts.
+//!
+//! Commands that ask upstreams what is new (`c1 update`, and anything else
+//! that resolves versions) go through this layer instead of hitting the host
+//! every time. Responses are cached under the global cache directory, one
+//! file per URL, for `C1_CACHE_TTL` seconds (10 minutes by default).
+//! `--refresh` bypasses the cache and `--offline` refuses to go to the
+//! network at all. A lock file per URL keeps parallel c1 processes in a CI
+//! loop from issuing the same query at once.
+//!
+//! Cache files are plain text with a version line; anything that does not
+//! parse is treated as a miss and overwritten.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{log, safe_path, secrets, url_rewrite};
+
+static REFRESH: AtomicBool = AtomicBool::new(false);
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+const CACHE_VERSION: &str = "c1-remote-cache v1";
+const DEFAULT_TTL_SECS: u64 = 600;
+/// A lock older than this belongs to a process that died mid-query
+const STALE_LOCK: Duration = Duration::from_secs(120);
+
+pub fn set_refresh(refresh: bool) {
+    REFRESH.store(refresh, Ordering::Relaxed);
+}
+
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+pub enum RemoteError {
+    /// The host answered with HTTP 429 or an abuse-detection message
+    RateLimited {
+        url: String,
+        retry_after: Option<u64>,
+    },
+    /// `--offline` was given and nothing usable is cached
+    NotCached { url: String },
+    Failed(String),
+}
+
+impl fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteError::RateLimited { url, retry_after } => {
+                write!(f, "rate limited by {}", secrets::redact(url))?;
+                match retry_after {
+                    Some(secs) => write!(f, ", retry after {}s", secs),
+                    None => write!(f, ", retry later"),
+                }
+            }
+            RemoteError::NotCached { url } => write!(
+                f,
+                "--offline was given but no cached response exists for {}",
+                secrets::redact(url)
+            ),
+            RemoteError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// A ref advertised by `git ls-remote`
+pub struct RemoteRef {
+    pub name: String,
+    pub commit: String,
+}
+
+/// The refs of a git remote, from cache when fresh enough.
+///
+/// `url` is the manifest URL; it is rewritten by any `[source]` rule, and
+/// placeholders are expanded only for the actual query, so cache files never
+/// contain credentials.
+pub fn ls_remote(url: &str) -> Result<Vec<RemoteRef>, RemoteError> {
+    let url = &url_rewrite::rewrite(url);
+    let body = query(url, || {
+        let expanded = secrets::expand(url).map_err(RemoteError::Failed)?;
+        let mut git = Command::new("git");
+        git.args(["ls-remote", &expanded]);
+        log::command(&git);
+        let output = git
+            .output()
+            .map_err(|e| RemoteError::Failed(format!("failed to run git: {}", e)))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(classify_failure(url, &stderr));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    })?;
+
+    Ok(body
+        .lines()
+        .filter_map(|line| {
+            let (commit, name) = line.split_once('\t')?;
+            Some(RemoteRef {
+                name: name.trim().to_string(),
+                commit: commit.trim().to_string(),
+            })
+        })
+        .collect())
+}
+
+/// The commit a remote ref points at, peeling annotated tags
+pub fn ref_commit(refs: &[RemoteRef], name: &str) -> Option<String> {
+    let peeled = format!("{}^{{}}", name);
+    refs.iter()
+        .find(|r| r.name == peeled)
+        .or_else(|| refs.iter().find(|r| r.name == name))
+        .map(|r| r.commit.clone())
+}
+
+/// The highest version-sorted tag name advertised by the remote
+pub fn newest_tag(refs: &[RemoteRef]) -> Option<String> {
+    refs.iter()
+        .filter_map(|r| r.name.strip_prefix("refs/tags/"))
+        .filter(|name| !name.ends_with("^{}"))
+        .max_by(|a, b| version_cmp(a, b))
+        .map(str::to_string)
+}
+
+/// Compare tag names like `git tag --sort=v:refname`: digit runs
+/// numerically, the rest lexically, with a leading `v` ignored
+pub fn version_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    let (a, b) = (
+        chunks(a.trim_start_matches(['v', 'V'])),
+        chunks(b.trim_start_matches(['v', 'V'])),
+    );
+    for (x, y) in a.iter().zip(&b) {
+        let ordering = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            _ => x.cmp(y),
+        };
+        if ordering.is_ne() {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+fn chunks(s: &str) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let bytes = s.as_bytes();
+    for i in 1..=bytes.len() {
+        if i == bytes.len() || bytes[i].is_ascii_digit() != bytes[i - 1].is_ascii_digit() {
+            chunks.push(&s[start..i]);
+            start = i;
+        }
+    }
+    chunks
+}
+
+/// Run `fetch` for `key` unless a fresh cached response exists
+pub fn query(
+    key: &str,
+    fetch: impl FnOnce() -> Result<String, RemoteError>,
+) -> Result<String, RemoteError> {
+    let path = cache_file(key);
+    let refresh = REFRESH.load(Ordering::Relaxed);
+    query_in(path.as_deref(), key, is_offline(), refresh, ttl(), fetch)
+}
+
+/// `query` with the cache file, `--offline`, `--refresh` and TTL given
+fn query_in(
+    path: Option<&Path>,
+    key: &str,
+    offline: bool,
+    refresh: bool,
+    ttl: Duration,
+    fetch: impl FnOnce() -> Result<String, RemoteError>,
+) -> Result<String, RemoteError> {
+    let Some(path) = path else {
+        return if offline {
+            Err(RemoteError::NotCached { url: key.to_string() })
+        } else {
+            fetch()
+        };
+    };
+
+    if offline {
+        // Any cached answer beats none when the network is off-limits
+        return read_cache(path, key, None).ok_or(RemoteError::NotCached { url: key.to_string() });
+    }
+    if !refresh && let Some(body) = read_cache(path, key, Some(ttl)) {
+        return Ok(body);
+    }
+
+    let _lock = CacheLock::acquire(path, STALE_LOCK);
+    // Another process may have filled the cache while we waited for the lock
+    if !refresh && let Some(body) = read_cache(path, key, Some(ttl)) {
+        return Ok(body);
+    }
+    let body = fetch()?;
+    write_cache(path, key, &body);
+    Ok(body)
+}
+
+/// Classify a failed query, recognising rate limiting by the usual wording
+fn classify_failure(url: &str, stderr: &str) -> RemoteError {
+    let lower = stderr.to_ascii_lowercase();
+    let rate_limited = lower.contains("429")
+        || lower.contains("rate limit")
+        || lower.contains("too many requests")
+        || lower.contains("abuse");
+    if !rate_limited {
+        let message = secrets::auth_failure(url, stderr).unwrap_or_else(|| stderr.trim().to_string());
+        return RemoteError::Failed(secrets::redact(&message));
+    }
+    RemoteError::RateLimited {
+        url: url.to_string(),
+        retry_after: retry_after(&lower),
+    }
+}
+
+/// Parse `retry-after: 60` / `retry after 60 seconds` out of an error message
+fn retry_after(message: &str) -> Option<u64> {
+    let lower = message.to_ascii_lowercase();
+    let start = lower.find("retry-after").or_else(|| lower.find("retry after"))?;
+    lower[start + "retry-after".len()..]
+        .trim_start_matches(|c: char| c == ':' || c.is_whitespace())
+        .split(|c: char| !c.is_ascii_digit())
+        .next()
+        .and_then(|n| n.parse().ok())
+}
+
+fn ttl() -> Duration {
+    let secs = std::env::var("C1_CACHE_TTL")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_TTL_SECS);
+    Duration::from_secs(secs)
+}
+
+/// `$XDG_CACHE_HOME/c1` (or `~/.cache/c1`, `%LOCALAPPDATA%\c1` on Windows)
+pub fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("c1"));
+    }
+    if cfg!(windows)
+        && let Some(dir) = std::env::var_os("LOCALAPPDATA")
+    {
+        return Some(PathBuf::from(dir).join("c1"));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache").join("c1"))
+}
+
+fn cache_file(key: &str) -> Option<PathBuf> {
+    Some(cache_dir()?.join("remote").join(format!("{:016x}", fnv1a(key))))
+}
+
+/// Stable across Rust releases, unlike `DefaultHasher`
+pub fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Read a cache entry, or `None` when missing, corrupt, for another key
+/// (hash collision) or older than `max_age`
+fn read_cache(path: &Path, key: &str, max_age: Option<Duration>) -> Option<String> {
+    let content = fs::read_to_string(path).ok()?;
+    let mut parts = content.splitn(4, '\n');
+    if parts.next()? != CACHE_VERSION {
+        return None;
+    }
+    if parts.next()?.strip_prefix("key ")? != key {
+        return None;
+    }
+    let fetched: u64 = parts.next()?.strip_prefix("fetched ")?.parse().ok()?;
+    let body = parts.next()?;
+
+    if let Some(max_age) = max_age {
+        let age = now_secs().saturating_sub(fetched);
+        if age > max_age.as_secs() {
+            return None;
+        }
+    }
+    Some(body.to_string())
+}
+
+fn write_cache(path: &Path, key: &str, body: &str) {
+    let Some(dir) = path.parent() else {
+        return;
+    };
+    if fs::create_dir_all(dir).is_err() {
+        return;
+    }
+    let content = format!("{}\nkey {}\nfetched {}\n{}", CACHE_VERSION, key, now_secs(), body);
+    // Written beside it and renamed, so a concurrent reader never sees half
+    // a file
+    let _ = safe_path::write(path, content);
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Exclusive lock on one cache entry, held for the duration of one query or
+/// fetch. A lock older than `stale` belongs to a process that died holding it.
+pub struct CacheLock {
+    path: Option<PathBuf>,
+}
+
+impl CacheLock {
+    pub fn acquire(cache_file: &Path, stale: Duration) -> CacheLock {
+        let path = cache_file.with_extension("lock");
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        loop {
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return CacheLock { path: Some(path) },
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(&path)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|m| m.elapsed().ok())
+                        .is_some_and(|age| age > stale);
+                    if stale {
+                        let _ = fs::remove_file(&path);
+                    } else {
+                        thread::sleep(Duration::from_millis(100));
+                    }
+                }
+                // Cache directory is unusable; query without serializing
+                Err(_) => return CacheLock { path: None },
+            }
+        }
+    }
+}
+
+impl Drop for CacheLock {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use std::cell::Cell;
+
+    const KEY: &str = "https://example.com/dep.git";
+    const TTL: Duration = Duration::from_secs(600);
+
+    /// A cache entry for `KEY` fetched `age` seconds ago
+    fn cached(tmp: &TempDir, age: u64, body: &str) -> PathBuf {
+        let path = tmp.path().join("remote").join("entry");
+        // Standing in for the cache directory
+        safe_path::allow_root(tmp.path());
+        let fetched = now_secs() - age;
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            format!(
+                "{}\nkey {}\nfetched {}\n{}",
+                CACHE_VERSION, KEY, fetched, body
+            ),
+        )
+        .unwrap();
+        path
+    }
+
+    /// `query_in` with a fetch that answers "fresh" and counts its calls
+    fn ask(path: Option<&Path>, offline: bool, refresh: bool, calls: &Cell<u32>) -> Option<String> {
+        query_in(path, KEY, offline, refresh, TTL, || {
+            calls.set(calls.get() + 1);
+            Ok("fresh".to_string())
+        })
+        .ok()
+    }
+
+    #[test]
+    fn answers_from_a_fresh_cache() {
+        let tmp = TempDir::new("remote-fresh");
+        let path = cached(&tmp, 60, "cached");
+        let calls = Cell::new(0);
+        assert_eq!(
+            ask(Some(&path), false, false, &calls).as_deref(),
+            Some("cached")
+        );
+        assert_eq!(calls.get(), 0);
+    }
+
+    #[test]
+    fn fetches_again_once_the_ttl_expires() {
+        let tmp = TempDir::new("remote-expired");
+        let path = cached(&tmp, TTL.as_secs() + 1, "cached");
+        let calls = Cell::new(0);
+        assert_eq!(
+            ask(Some(&path), false, false, &calls).as_deref(),
+            Some("fresh")
+        );
+        assert_eq!(calls.get(), 1);
+        // The new answer is cached in turn
+        assert_eq!(
+            ask(Some(&path), false, false, &calls).as_deref(),
+            Some("fresh")
+        );
+        assert_eq!(calls.get(), 1);
+        assert!(!path.with_extension("lock").exists());
+    }
+
+    #[test]
+    fn refresh_ignores_a_fresh_cache() {
+        let tmp = TempDir::new("remote-refresh");
+        let path = cached(&tmp, 60, "cached");
+        let calls = Cell::new(0);
+        assert_eq!(
+            ask(Some(&path), false, true, &calls).as_deref(),
+            Some("fresh")
+        );
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn offline_uses_an_expired_cache() {
+        let tmp = TempDir::new("remote-offline-stale");
+        let path = cached(&tmp, 30 * 24 * 3600, "cached");
+        let calls = Cell::new(0);
+        assert_eq!(
+            ask(Some(&path), true, false, &calls).as_deref(),
+            Some("cached")
+        );
+        assert_eq!(calls.get(), 0);
+    }
+
+    #[test]
+    fn offline_without_a_cache_fails_without_fetching() {
+        let tmp = TempDir::new("remote-offline-miss");
+        let path = tmp.path().join("remote").join("missing");
+        let calls = Cell::new(0);
+        for path in [Some(path.as_path()), None] {
+            let result = query_in(path, KEY, true, false, TTL, || {
+                calls.set(calls.get() + 1);
+                Ok(String::new())
+            });
+            assert!(matches!(result, Err(RemoteError::NotCached { .. })));
+        }
+        assert_eq!(calls.get(), 0);
+    }
+
+    #[test]
+    fn treats_a_corrupt_or_foreign_entry_as_a_miss() {
+        let tmp = TempDir::new("remote-corrupt");
+        let path = tmp.path().join("entry");
+        let calls = Cell::new(0);
+        fs::write(&path, "not a cache file").unwrap();
+        assert_eq!(
+            ask(Some(&path), false, false, &calls).as_deref(),
+            Some("fresh")
+        );
+        let other = format!("{}\nkey other\nfetched {}\nbody", CACHE_VERSION, now_secs());
+        fs::write(&path, other).unwrap();
+        assert_eq!(ask(Some(&path), true, false, &calls), None);
+        assert_eq!(calls.get(), 1);
+    }
+}
@@ -0,0 +1,290 @@
+//! Project templates from git: `c1 new foo --template <url>`.
+//!
+//! Anything `--template` takes that is not a built-in template is cloned as a
+//! git repository: a URL, `git@host:path`, the `user/repo` shorthand for
+//! GitHub, or a local repository. The clone's tree, without its .git
+//! directory, is copied into the new project with `{{project_name}}`,
+//! `{{version}}` and `{{edition}}` replaced in file contents and file names.
+//! A `template.toml` at the top of the repository can declare more
+//! placeholders, asked for on the terminal before anything is written:
+//!
+//! ```toml
+//! [variables]
+//! author = { prompt = "Author", default = "Jane Doe" }
+//! ```
+//!
+//! A template without a project.toml gets the one a built-in template would
+//! write, and the result must parse as a project before `c1 new` succeeds.
+//! Symbolic links in the template are skipped, so a template cannot copy
+//! files from outside its own repository.
+
+use std::fs;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{self, Context, Error};
+use crate::{
+    NewProject, ProjectConfig, interactive, log, remote, safe_path, secrets, templates,
+    url_rewrite, vendor,
+};
+
+const TEMPLATE_TOML: &str = "template.toml";
+
+/// Whether `--template` names a git repository rather than a built-in template
+pub fn is_remote(spec: &str) -> bool {
+    spec.contains("://")
+        || spec.starts_with("git@")
+        || spec.ends_with(".git")
+        || crate::normalize_git_url(spec) != spec
+        || Path::new(spec).join(".git").exists()
+}
+
+/// A template repository cloned into a temporary directory, removed again
+/// when dropped
+pub struct Fetched {
+    dir: PathBuf,
+    /// Placeholders from template.toml with the values chosen for them
+    variables: Vec<(String, String)>,
+}
+
+impl Drop for Fetched {
+    fn drop(&mut self) {
+        // A directory of our own under the system's temporary directory,
+        // which `fetch` let safe_path remove
+        let _ = safe_path::remove_dir_all(&self.dir);
+    }
+}
+
+/// Clone the template and settle its variables, before the project
+/// directory exists
+pub fn fetch(spec: &str, project_name: &str) -> error::Result<Fetched> {
+    let url = crate::normalize_git_url(spec);
+    if remote::is_offline() && !Path::new(&url).exists() {
+        return Err(Error::usage(format!(
+            "--offline forbids cloning the template {}",
+            secrets::redact(&url)
+        )));
+    }
+    let real_url = secrets::expand(&url_rewrite::rewrite(&url)).map_err(Error::config)?;
+    let mut fetched = Fetched {
+        dir: std::env::temp_dir().join(format!(
+            "c1-template-{}-{}",
+            project_name,
+            std::process::id()
+        )),
+        variables: Vec::new(),
+    };
+    safe_path::allow_root(&fetched.dir);
+    log::status!("Cloning template {}...", secrets::redact(&url));
+    let mut clone = Command::new("git");
+    clone
+        .args(["clone", "--quiet", "--depth", "1", &real_url])
+        .arg(&fetched.dir);
+    log::command(&clone);
+    let output = clone.output().map_err(|e| {
+        Error::failure(format!("failed to run git: {}", e)).hint("make sure git is installed")
+    })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::failure(format!(
+            "failed to clone the template {}\n    {}",
+            secrets::redact(&url),
+            secrets::redact(stderr.trim())
+        )));
+    }
+    fetched.variables = variables(&fetched.dir)?;
+    Ok(fetched)
+}
+
+/// The variables template.toml declares, each asked for on a terminal or
+/// else given its default
+fn variables(dir: &Path) -> error::Result<Vec<(String, String)>> {
+    let path = dir.join(TEMPLATE_TOML);
+    let Ok(content) = fs::read_to_string(&path) else {
+        return Ok(Vec::new());
+    };
+    let manifest: toml::Table = toml::from_str(&content).map_err(|e| {
+        Error::config(format!(
+            "failed to parse the template's {}: {}",
+            TEMPLATE_TOML, e
+        ))
+    })?;
+    let Some(declared) = manifest.get("variables") else {
+        return Ok(Vec::new());
+    };
+    let invalid = |name: &str| {
+        Error::config(format!(
+            "variable '{}' in the template's {} is invalid",
+            name, TEMPLATE_TOML
+        ))
+        .hint("declare it as `name = { prompt = \"Question\", default = \"value\" }`")
+    };
+    let declared = declared.as_table().ok_or_else(|| invalid("variables"))?;
+    let terminal = std::io::stdin().is_terminal();
+    let mut variables = Vec::new();
+    for (name, spec) in declared {
+        let valid_name = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && !["project_name", "version", "edition"].contains(&name.as_str());
+        let spec = spec
+            .as_table()
+            .filter(|_| valid_name)
+            .ok_or_else(|| invalid(name))?;
+        let text = |key: &str| spec.get(key).and_then(|v| v.as_str());
+        let default = text("default").unwrap_or("");
+        let value = if terminal {
+            interactive::prompt(text("prompt").unwrap_or(name), default)?
+        } else {
+            default.to_string()
+        };
+        variables.push((name.clone(), value));
+    }
+    Ok(variables)
+}
+
+impl Fetched {
+    /// Write the project into the current directory
+    pub fn instantiate(&self, project_name: &str, settings: &NewProject) -> error::Result<()> {
+        let fill = self.filler(project_name, settings);
+        copy_tree(&self.dir, Path::new("."), true, &fill)?;
+
+        if !Path::new("project.toml").exists() {
+            let template =
+                templates::find(templates::DEFAULT_TEMPLATE).expect("default template exists");
+            crate::create_file(
+                "project.toml",
+                &crate::project_toml(project_name, template, settings),
+            )?;
+            log::status!("The template has no project.toml, wrote the default one");
+        }
+        let content = fs::read_to_string("project.toml")
+            .context(|| "failed to read project.toml".to_string())?;
+        toml::from_str::<ProjectConfig>(&content)
+            .map_err(|e| Error::config(format!("the template's project.toml is invalid: {}", e)))?;
+        if let Some(license) = settings.license
+            && !Path::new("LICENSE").exists()
+        {
+            crate::create_file("LICENSE", &license.render(project_name))?;
+        }
+        if !Path::new(".gitignore").exists() {
+            crate::create_file(
+                ".gitignore",
+                &vendor::gitignore(crate::GITIGNORE, settings.vendor),
+            )?;
+        }
+        crate::git_init(settings.git);
+        Ok(())
+    }
+
+    /// The paths `instantiate` would create, for `c1 new --dry-run`;
+    /// directories end in `/`
+    pub fn files(&self, project_name: &str, settings: &NewProject) -> error::Result<Vec<String>> {
+        let fill = self.filler(project_name, settings);
+        let mut paths = Vec::new();
+        list_tree(&self.dir, "", true, &fill, &mut paths)?;
+        let mut add = |path: &str, wanted: bool| {
+            if wanted && !paths.iter().any(|p| p == path) {
+                paths.push(path.to_string());
+            }
+        };
+        add("project.toml", true);
+        add("LICENSE", settings.license.is_some());
+        add(".gitignore", true);
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Fill the placeholders in a file name or text
+    fn filler(&self, project_name: &str, settings: &NewProject) -> impl Fn(&str) -> String {
+        let mut placeholders = vec![
+            ("project_name".to_string(), project_name.to_string()),
+            ("version".to_string(), "0.1.0".to_string()),
+            ("edition".to_string(), settings.edition.clone()),
+        ];
+        placeholders.extend(self.variables.iter().cloned());
+        move |text: &str| {
+            placeholders
+                .iter()
+                .fold(text.to_string(), |text, (name, value)| {
+                    text.replace(&format!("{{{{{}}}}}", name), value)
+                })
+        }
+    }
+}
+
+/// The paths `copy_tree` would create under `prefix`
+fn list_tree(
+    from: &Path,
+    prefix: &str,
+    top: bool,
+    fill: &dyn Fn(&str) -> String,
+    paths: &mut Vec<String>,
+) -> error::Result<()> {
+    let entries = fs::read_dir(from).context(|| format!("failed to read {}", from.display()))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name == ".git" || (top && name == TEMPLATE_TOML) {
+            continue;
+        }
+        let path = format!("{}{}", prefix, fill(&name));
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => {
+                paths.push(format!("{}/", path));
+                list_tree(&entry.path(), &format!("{}/", path), false, fill, paths)?;
+            }
+            Ok(kind) if !kind.is_symlink() => paths.push(path),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Copy `from` into `to`, filling placeholders in names and text contents
+fn copy_tree(
+    from: &Path,
+    to: &Path,
+    top: bool,
+    fill: &dyn Fn(&str) -> String,
+) -> error::Result<()> {
+    let entries = fs::read_dir(from).context(|| format!("failed to read {}", from.display()))?;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name == ".git" || (top && name == TEMPLATE_TOML) {
+            continue;
+        }
+        let source = entry.path();
+        let filled = fill(&name);
+        // A value must not move a file out of the project
+        if filled.contains(['/', '\\']) || filled == ".." || filled.is_empty() {
+            return Err(Error::config(format!(
+                "the template's file name '{}' becomes '{}', which is not a file name",
+                name, filled
+            )));
+        }
+        let target = to.join(filled);
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_symlink() {
+            log::problem!("Warning: skipping {}: templates cannot contain links", name);
+        } else if kind.is_dir() {
+            fs::create_dir_all(&target)
+                .context(|| format!("failed to create {}", target.display()))?;
+            copy_tree(&source, &target, false, fill)?;
+        } else {
+            // Copying keeps the permissions, such as a script's executable bit
+            safe_path::copy(&source, &target)
+                .context(|| format!("failed to create {}", target.display()))?;
+            let bytes =
+                fs::read(&source).context(|| format!("failed to read {}", source.display()))?;
+            if let Ok(text) = String::from_utf8(bytes) {
+                let filled = fill(&text);
+                if filled != text {
+                    safe_path::write(&target, filled)
+                        .context(|| format!("failed to write {}", target.display()))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,321 @@
+//! `c1 rename-project`: change the project name everywhere c1 relies on it.
+//!
+//! c1 finds the executable as `build/<name>`, so project.toml and the
+//! `project()` line in CMakeLists.txt must agree. Those are rewritten; every
+//! other occurrence of the old name (README title, the hello-world string,
+//! macros) is only reported, since c1 cannot know what it means there.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{self, Context, Error};
+use crate::log;
+use crate::safe_path;
+
+/// One rewritten line
+struct LineChange {
+    line: usize,
+    before: String,
+    after: String,
+}
+
+/// A file c1 rewrites, with its new content
+struct FileEdit {
+    path: &'static str,
+    content: String,
+    changes: Vec<LineChange>,
+}
+
+pub fn cmd_rename_project(new_name: &str, dry_run: bool) -> error::Result<()> {
+    crate::check_project_name(new_name, "c1 rename-project")?;
+
+    let Some(old_name) = crate::get_project_name_from_config(Path::new(".")) else {
+        return Err(Error::usage(
+            "project.toml not found or has no [project] name",
+        ));
+    };
+    if old_name == new_name {
+        log::status!("Project is already named '{}', nothing to do", new_name);
+        return Ok(());
+    }
+
+    let mut edits = Vec::new();
+    match rename_in_manifest(&old_name, new_name) {
+        Some(edit) => edits.push(edit),
+        None => {
+            return Err(Error::config(format!(
+                "could not find `name = \"{}\"` under [project] in project.toml",
+                old_name
+            )));
+        }
+    }
+    match rename_in_cmake(&old_name, new_name) {
+        Some(edit) => edits.push(edit),
+        None if Path::new("CMakeLists.txt").exists() => log::problem!(
+            "Warning: CMakeLists.txt has no `project({} ...)` line; update the project name there by hand",
+            old_name
+        ),
+        None => {}
+    }
+
+    let prefix = if dry_run { "Would rename" } else { "Renaming" };
+    log::status!("{} project '{}' to '{}'", prefix, old_name, new_name);
+    for edit in &edits {
+        print_diff(edit);
+    }
+
+    let stale = stale_artifacts(&old_name);
+    for path in &stale {
+        let verb = if dry_run { "Would remove" } else { "Removing" };
+        log::status!("{} stale build artifact {}", verb, path.display());
+    }
+
+    let leftovers = other_occurrences(&old_name, &edits);
+    if !leftovers.is_empty() {
+        log::status!("\nOccurrences of '{}' left untouched:", old_name);
+        for leftover in &leftovers {
+            log::status!("  {}", leftover);
+        }
+    }
+
+    if dry_run {
+        log::status!("\nDry run: no files were changed");
+        return Ok(());
+    }
+
+    for edit in &edits {
+        safe_path::write(edit.path, &edit.content)
+            .context(|| format!("failed to write {}", edit.path))?;
+    }
+    for path in &stale {
+        let result = if path.is_dir() {
+            safe_path::remove_dir_all(path)
+        } else {
+            safe_path::remove_file(path)
+        };
+        if let Err(e) = result {
+            log::problem!("Warning: failed to remove {}: {}", path.display(), e);
+        }
+    }
+    log::status!("\n✓ Project renamed to '{}'", new_name);
+    Ok(())
+}
+
+/// Rewrite `name = "<old>"` in the [project] table, keeping everything else
+fn rename_in_manifest(old_name: &str, new_name: &str) -> Option<FileEdit> {
+    let content = fs::read_to_string("project.toml").ok()?;
+    let mut in_project = false;
+    let mut changes = Vec::new();
+    let lines: Vec<String> = content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') {
+                in_project = trimmed == "[project]";
+            } else if in_project
+                && changes.is_empty()
+                && let Some((key, value)) = trimmed.split_once('=')
+                && key.trim() == "name"
+                && value.trim().trim_matches(|c| c == '"' || c == '\'') == old_name
+            {
+                let indent = &line[..line.len() - line.trim_start().len()];
+                let after = format!("{}name = \"{}\"", indent, new_name);
+                changes.push(LineChange {
+                    line: i + 1,
+                    before: line.to_string(),
+                    after: after.clone(),
+                });
+                return after;
+            }
+            line.to_string()
+        })
+        .collect();
+
+    if changes.is_empty() {
+        return None;
+    }
+    Some(FileEdit {
+        path: "project.toml",
+        content: join_lines(&lines, &content),
+        changes,
+    })
+}
+
+/// Rewrite the first `project(<old> ...)` call in CMakeLists.txt
+fn rename_in_cmake(old_name: &str, new_name: &str) -> Option<FileEdit> {
+    let content = fs::read_to_string("CMakeLists.txt").ok()?;
+    let mut changes = Vec::new();
+    let lines: Vec<String> = content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if changes.is_empty()
+                && let Some(after) = rename_project_call(line, old_name, new_name)
+            {
+                changes.push(LineChange {
+                    line: i + 1,
+                    before: line.to_string(),
+                    after: after.clone(),
+                });
+                return after;
+            }
+            line.to_string()
+        })
+        .collect();
+
+    if changes.is_empty() {
+        return None;
+    }
+    Some(FileEdit {
+        path: "CMakeLists.txt",
+        content: join_lines(&lines, &content),
+        changes,
+    })
+}
+
+/// The name the first `project()` call in CMakeLists.txt gives
+pub fn cmake_project_name() -> Option<String> {
+    let content = fs::read_to_string("CMakeLists.txt").ok()?;
+    content.lines().find_map(|line| {
+        let trimmed = line.trim_start();
+        if !trimmed.to_ascii_lowercase().starts_with("project") {
+            return None;
+        }
+        let open = trimmed.find('(')?;
+        if !trimmed["project".len()..open].trim().is_empty() {
+            return None;
+        }
+        let name = trimmed[open + 1..]
+            .split(|c: char| c.is_whitespace() || c == ')')
+            .find(|word| !word.is_empty())?;
+        Some(name.trim_matches('"').to_string())
+    })
+}
+
+/// `project(old C)` -> `project(new C)`; also accepts `project ( "old" ...`
+fn rename_project_call(line: &str, old_name: &str, new_name: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+    if !trimmed.to_ascii_lowercase().starts_with("project") {
+        return None;
+    }
+    let open = trimmed.find('(')?;
+    if !trimmed["project".len()..open].trim().is_empty() {
+        return None;
+    }
+    let args = &trimmed[open + 1..];
+    let args_trimmed = args.trim_start();
+    let first_len = args_trimmed
+        .find(|c: char| c.is_whitespace() || c == ')')
+        .unwrap_or(args_trimmed.len());
+    let first = &args_trimmed[..first_len];
+    let quoted = first.starts_with('"');
+    if first.trim_matches('"') != old_name {
+        return None;
+    }
+
+    let indent = &line[..line.len() - trimmed.len()];
+    let leading = &args[..args.len() - args_trimmed.len()];
+    let replacement = if quoted {
+        format!("\"{}\"", new_name)
+    } else {
+        new_name.to_string()
+    };
+    Some(format!(
+        "{}{}{}{}{}",
+        indent,
+        &trimmed[..=open],
+        leading,
+        replacement,
+        &args_trimmed[first_len..]
+    ))
+}
+
+/// Re-join edited lines, keeping the original trailing newline
+fn join_lines(lines: &[String], original: &str) -> String {
+    let mut joined = lines.join("\n");
+    if original.ends_with('\n') {
+        joined.push('\n');
+    }
+    joined
+}
+
+fn print_diff(edit: &FileEdit) {
+    println!("--- a/{}", edit.path);
+    println!("+++ b/{}", edit.path);
+    for change in &edit.changes {
+        println!("@@ line {} @@", change.line);
+        println!("-{}", change.before);
+        println!("+{}", change.after);
+    }
+}
+
+/// Build outputs named after the old project that would otherwise linger, in
+/// the debug and release builds and a flat build directory from before those
+fn stale_artifacts(old_name: &str) -> Vec<PathBuf> {
+    let build = crate::resolve_build_dir(None);
+    [build.join("debug"), build.join("release"), build]
+        .iter()
+        .flat_map(|build| {
+            [
+                build.join(old_name),
+                build.join(format!("{}.exe", old_name)),
+                build.join("CMakeFiles").join(format!("{}.dir", old_name)),
+                // The cache still records the old project name
+                build.join("CMakeCache.txt"),
+            ]
+        })
+        .filter(|p| p.exists())
+        .collect()
+}
+
+/// `file:line: text` for every occurrence of the old name that is not edited
+fn other_occurrences(old_name: &str, edits: &[FileEdit]) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut stack = vec![PathBuf::from(".")];
+    while let Some(dir) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut entries: Vec<_> = entries.flatten().map(|e| e.path()).collect();
+        entries.sort();
+        for path in entries {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            if file_name.starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                if dir == Path::new(".") && (file_name == "build" || file_name == "external") {
+                    continue;
+                }
+                stack.push(path);
+                continue;
+            }
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let display = path.strip_prefix(".").unwrap_or(&path).display().to_string();
+            let edited = edits.iter().find(|e| e.path == display);
+            for (i, line) in content.lines().enumerate() {
+                if !contains_word(line, old_name) {
+                    continue;
+                }
+                if edited.is_some_and(|e| e.changes.iter().any(|c| c.line == i + 1)) {
+                    continue;
+                }
+                found.push(format!("{}:{}: {}", display, i + 1, line.trim()));
+            }
+        }
+    }
+    found
+}
+
+/// Whether `word` occurs in `line` not as part of a longer identifier
+fn contains_word(line: &str, word: &str) -> bool {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    line.match_indices(word).any(|(start, _)| {
+        let before = line[..start].chars().next_back();
+        let after = line[start + word.len()..].chars().next();
+        !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+    })
+}
@@ -0,0 +1,158 @@
+//! `c1 rename <old> <new>`: rename a module created by `c1 create`.
+//!
+//! src/<old>.c and include/<old>.h move to their new names, the header's
+//! include guard and `@file` line follow, the SOURCES and HEADERS entries in
+//! CMakeLists.txt are updated, and every `#include "<old>.h"` in main.c,
+//! src/, include/ and tests/ is rewritten. Every change is worked out before
+//! the first file is touched, so a module that cannot be renamed is left as
+//! it was.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{self, Context, Error};
+use crate::{fmt, log, safe_path};
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+
+pub fn cmd_rename(old: &str, new: &str) -> error::Result<()> {
+    crate::check_module_name(old)?;
+    crate::check_module_name(new)?;
+    if old == new {
+        return Err(Error::usage(format!("Module is already named '{}'", new)));
+    }
+
+    let moves: Vec<(String, String)> = [("src", "c"), ("include", "h")]
+        .iter()
+        .map(|(dir, ext)| {
+            (
+                format!("{}/{}.{}", dir, old, ext),
+                format!("{}/{}.{}", dir, new, ext),
+            )
+        })
+        .filter(|(from, _)| Path::new(from).is_file())
+        .collect();
+    if moves.is_empty() {
+        return Err(Error::usage(format!(
+            "Module '{}' not found (src/{}.c and include/{}.h do not exist)",
+            old, old, old
+        )));
+    }
+    for (_, to) in &moves {
+        if Path::new(to).exists() {
+            return Err(Error::usage(format!("{} already exists", to))
+                .hint("pick another name, or remove that file first"));
+        }
+    }
+
+    // New contents by the path they end up at
+    let mut edits: Vec<(PathBuf, String)> = Vec::new();
+    let old_include = format!("\"{}.h\"", old);
+    let new_include = format!("\"{}.h\"", new);
+    for file in sources() {
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        let mut updated = rewrite_includes(&content, &old_include, &new_include);
+        let destination = moves
+            .iter()
+            .find(|(from, _)| Path::new(from) == file)
+            .map_or_else(|| file.clone(), |(_, to)| PathBuf::from(to));
+        if destination == Path::new(&format!("include/{}.h", new)) {
+            updated = rewrite_header(&updated, old, new);
+        }
+        if updated != content {
+            edits.push((destination, updated));
+        }
+    }
+    if let Ok(cmake) = fs::read_to_string(CMAKE_PATH) {
+        let mut updated = cmake.clone();
+        for (from, to) in &moves {
+            updated = replace_word(&updated, from, to);
+        }
+        if updated != cmake {
+            edits.push((PathBuf::from(CMAKE_PATH), updated));
+        } else {
+            log::problem!(
+                "Warning: {} does not list the module's files; add {} to it by hand",
+                CMAKE_PATH,
+                moves
+                    .iter()
+                    .map(|(_, to)| to.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            );
+        }
+    }
+
+    for (from, to) in &moves {
+        if let Some(dir) = Path::new(to).parent() {
+            fs::create_dir_all(dir).context(|| format!("failed to create {}/", dir.display()))?;
+        }
+        fs::rename(from, to).context(|| format!("failed to move {} to {}", from, to))?;
+        log::status!("✓ Renamed {} to {}", from, to);
+    }
+    for (path, content) in &edits {
+        safe_path::write(path, content)
+            .context(|| format!("failed to update {}", path.display()))?;
+        log::status!("✓ Updated {}", path.display());
+    }
+    Ok(())
+}
+
+/// The .c and .h files that may include the module's header
+fn sources() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if Path::new("main.c").is_file() {
+        files.push(PathBuf::from("main.c"));
+    }
+    for dir in ["src", "include", "tests"] {
+        fmt::collect(Path::new(dir), &mut files);
+    }
+    files
+}
+
+/// `content` with `#include "<old>.h"` lines pointing at the new header
+fn rewrite_includes(content: &str, old_include: &str, new_include: &str) -> String {
+    content
+        .split_inclusive('\n')
+        .map(|line| {
+            let directive: String = line.split_whitespace().collect::<Vec<_>>().join(" ");
+            if directive.starts_with(&format!("#include {}", old_include))
+                || directive.starts_with(&format!("# include {}", old_include))
+            {
+                line.replacen(old_include, new_include, 1)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect()
+}
+
+/// The header with its include guard and `@file` line renamed
+fn rewrite_header(content: &str, old: &str, new: &str) -> String {
+    let guard = |name: &str| format!("_{}_H", name.replace('/', "_").to_uppercase());
+    let content = replace_word(content, &guard(old), &guard(new));
+    content.replace(&format!("@file {}.h", old), &format!("@file {}.h", new))
+}
+
+/// `text` with every occurrence of `from` that stands as a word of its own
+/// replaced by `to`
+fn replace_word(text: &str, from: &str, to: &str) -> String {
+    let is_word = |c: char| c.is_alphanumeric() || matches!(c, '_' | '/' | '.' | '-');
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find(from) {
+        result.push_str(&rest[..at]);
+        let before = result.chars().next_back();
+        let after = rest[at + from.len()..].chars().next();
+        if before.is_some_and(is_word) || after.is_some_and(is_word) {
+            result.push_str(from);
+        } else {
+            result.push_str(to);
+        }
+        rest = &rest[at + from.len()..];
+    }
+    result.push_str(rest);
+    result
+}
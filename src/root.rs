@@ -0,0 +1,81 @@
+//! Finding the project root, so commands work from any directory inside a
+//! project.
+//!
+//! Commands that act on a project walk up from the current directory to the
+//! nearest project.toml and run from there, as cargo does with Cargo.toml.
+//! Paths given on the command line keep meaning what they meant where c1 was
+//! started: `rebase` turns them into paths relative to the root.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::error::{self, Context, Error};
+use crate::log;
+
+/// Where c1 was started, relative to the root it moved to
+static SUBDIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// The nearest directory at or above `start` that holds a project.toml
+pub fn find(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| dir.join("project.toml").is_file())
+        .map(Path::to_path_buf)
+}
+
+/// Move to the project root. Without one, `required` commands fail and the
+/// others stay where they are.
+pub fn enter(required: bool) -> error::Result<()> {
+    let current =
+        std::env::current_dir().context(|| "failed to read the current directory".to_string())?;
+    let Some(root) = find(&current) else {
+        if required {
+            return Err(Error::usage(
+                "could not find project.toml in this directory or any parent",
+            )
+            .hint("create a project with `c1 new <name>` or `c1 init`"));
+        }
+        return Ok(());
+    };
+    if root == current {
+        return Ok(());
+    }
+    log::verbose!("Project root: {}", root.display());
+    std::env::set_current_dir(&root).context(|| format!("failed to enter {}", root.display()))?;
+    let subdir = current.strip_prefix(&root).unwrap_or(Path::new(""));
+    let _ = SUBDIR.set(subdir.to_path_buf());
+    Ok(())
+}
+
+/// A path given relative to where c1 was started, made relative to the root
+pub fn rebase(path: &str) -> String {
+    match SUBDIR.get() {
+        Some(subdir) if Path::new(path).is_relative() => normalize(&subdir.join(path))
+            .to_string_lossy()
+            .replace('\\', "/"),
+        _ => path.to_string(),
+    }
+}
+
+/// `path` with `.` and `dir/..` pairs removed, without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    if normalized.as_os_str().is_empty() {
+        normalized.push(".");
+    }
+    normalized
+}
@@ -0,0 +1,61 @@
+//! Environment for the program started by `c1 run`.
+//!
+//! ```toml
+//! [env]
+//! LOG_LEVEL = "debug"
+//! DATA_DIR = "${PROJECT_ROOT}/data"
+//! HOME_OVERRIDE = { value = "/tmp/home", force = true }
+//! ```
+//!
+//! Manifest entries are defaults: a variable already set in the environment
+//! c1 was started from wins unless the entry has `force = true`. Values given
+//! with `c1 run --env KEY=VALUE` always win. `${PROJECT_ROOT}` expands to the
+//! absolute path of the project.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EnvValue {
+    Plain(String),
+    Detailed {
+        value: String,
+        /// Override a variable that is already set
+        #[serde(default)]
+        force: bool,
+    },
+}
+
+/// The variables to set on the child, or an error for a malformed `--env`
+pub fn resolve(
+    manifest: &BTreeMap<String, EnvValue>,
+    overrides: &[String],
+    project_root: &Path,
+) -> Result<Vec<(String, String)>, String> {
+    let root = project_root.display().to_string();
+    let expand = |value: &str| value.replace("${PROJECT_ROOT}", &root);
+
+    let mut vars = BTreeMap::new();
+    for (key, value) in manifest {
+        let (value, force) = match value {
+            EnvValue::Plain(value) => (value, false),
+            EnvValue::Detailed { value, force } => (value, *force),
+        };
+        if force || std::env::var_os(key).is_none() {
+            vars.insert(key.clone(), expand(value));
+        }
+    }
+    for assignment in overrides {
+        let Some((key, value)) = assignment.split_once('=') else {
+            return Err(format!("--env expects KEY=VALUE, got '{}'", assignment));
+        };
+        if key.is_empty() {
+            return Err(format!("--env expects KEY=VALUE, got '{}'", assignment));
+        }
+        vars.insert(key.to_string(), expand(value));
+    }
+    Ok(vars.into_iter().collect())
+}
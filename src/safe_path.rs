@@ -0,0 +1,312 @@
+//! Guard rails for destructive filesystem operations.
+//!
+//! Any removal or overwrite of a path that was built from user or config input
+//! must go through this module. The target is resolved (following symlinks in
+//! its parent directories) and must land inside the project root or another
+//! directory c1 owns: its cache, a build directory the project configures
+//! elsewhere, or a temporary directory it made. Anything else is refused
+//! unless the user passed `--allow-outside-project` and confirms
+//! interactively.
+
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{log, remote};
+
+static ALLOW_OUTSIDE_PROJECT: AtomicBool = AtomicBool::new(false);
+/// Directories outside the project that c1 was told to write to, besides
+/// the cache
+static ROOTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Enable the `--allow-outside-project` escape hatch for this invocation
+pub fn set_allow_outside_project(allow: bool) {
+    ALLOW_OUTSIDE_PROJECT.store(allow, Ordering::Relaxed);
+}
+
+/// Let c1 remove and overwrite anything under `dir`, a directory it owns
+/// such as a configured build directory
+pub fn allow_root(dir: impl AsRef<Path>) {
+    if let Ok(dir) = resolve(dir.as_ref())
+        && let Ok(mut roots) = ROOTS.lock()
+        && !roots.contains(&dir)
+    {
+        roots.push(dir);
+    }
+}
+
+/// Recursively remove a directory after checking it against the policy
+pub fn remove_dir_all(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    check(path, "remove")?;
+    fs::remove_dir_all(path)
+}
+
+/// Remove a single file after checking it against the policy
+pub fn remove_file(path: impl AsRef<Path>) -> io::Result<()> {
+    let path = path.as_ref();
+    check(path, "remove")?;
+    fs::remove_file(path)
+}
+
+/// Overwrite a file after checking it against the policy. The new contents
+/// go to a temporary file beside it first and replace it in one rename, so
+/// an interrupted c1 leaves either the old file or the new one.
+pub fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let path = path.as_ref();
+    check(path, "overwrite")?;
+    // A symlink is written through, as before, rather than replaced
+    if path.is_symlink() {
+        return fs::write(path, contents);
+    }
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    fs::write(&tmp, contents)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        let _ = fs::set_permissions(&tmp, metadata.permissions());
+    }
+    fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+/// Copy a file over `to` after checking it against the policy, keeping the
+/// permissions of `from`
+pub fn copy(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<u64> {
+    let to = to.as_ref();
+    check(to, "overwrite")?;
+    fs::copy(from, to)
+}
+
+/// Move `from` into place at `to` after checking `to` against the policy
+pub fn rename(from: impl AsRef<Path>, to: impl AsRef<Path>) -> io::Result<()> {
+    let to = to.as_ref();
+    check(to, "replace")?;
+    fs::rename(from, to)
+}
+
+/// Verify that `path` may be destroyed, prompting when it lies outside the
+/// project and the user opted in with `--allow-outside-project`. Writes the
+/// helpers here do not cover, such as a file another tool writes, check first.
+pub fn check(path: impl AsRef<Path>, action: &str) -> io::Result<()> {
+    let path = path.as_ref();
+    let root = resolve(&std::env::current_dir()?)?;
+    let mut allowed: Vec<PathBuf> = remote::cache_dir()
+        .and_then(|dir| resolve(&dir).ok())
+        .into_iter()
+        .collect();
+    if let Ok(roots) = ROOTS.lock() {
+        allowed.extend(roots.iter().cloned());
+    }
+    check_in(&root, &allowed, path, action)
+}
+
+/// `check` against the project root `root` and the other directories c1
+/// owns, `allowed`, all resolved
+fn check_in(root: &Path, allowed: &[PathBuf], path: &Path, action: &str) -> io::Result<()> {
+    let target = resolve(path)?;
+
+    if target == root {
+        return Err(refusal(format!(
+            "refusing to {} the project root itself ({})",
+            action,
+            target.display()
+        )));
+    }
+    if target.starts_with(root) {
+        return Ok(());
+    }
+    // A directory holding the project, such as an `out_dir` of "..", would
+    // let everything through
+    if allowed
+        .iter()
+        .any(|dir| !root.starts_with(dir) && target.starts_with(dir))
+    {
+        return Ok(());
+    }
+
+    if !ALLOW_OUTSIDE_PROJECT.load(Ordering::Relaxed) {
+        return Err(refusal(format!(
+            "refusing to {} '{}': it resolves outside the project root ({}). \
+             Pass --allow-outside-project to override.",
+            action,
+            target.display(),
+            root.display()
+        )));
+    }
+
+    if !confirm(&format!(
+        "'{}' is outside the project root. Really {} it? [y/N] ",
+        target.display(),
+        action
+    )) {
+        return Err(refusal(format!(
+            "not confirmed, leaving '{}' untouched",
+            target.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Resolve a path to its absolute, symlink-free location.
+///
+/// The deepest existing ancestor is canonicalized so that symlinked
+/// directories and `..` components along the way are followed physically. The
+/// final component is kept as-is when it is itself a symlink, since removing a
+/// link only removes the link. Components that do not exist yet are
+/// normalized lexically.
+fn resolve(path: &Path) -> io::Result<PathBuf> {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+
+    let is_symlink = fs::symlink_metadata(&absolute)
+        .map(|m| m.file_type().is_symlink())
+        .unwrap_or(false);
+    let components: Vec<Component> = absolute.components().collect();
+    let limit = if is_symlink {
+        components.len() - 1
+    } else {
+        components.len()
+    };
+
+    let mut existing = PathBuf::new();
+    let mut consumed = 0;
+    while consumed < limit {
+        let next = existing.join(components[consumed]);
+        if consumed > 0 && !next.exists() {
+            break;
+        }
+        existing = next;
+        consumed += 1;
+    }
+
+    let mut resolved = existing.canonicalize()?;
+    for component in &components[consumed..] {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            other => resolved.push(other.as_os_str()),
+        }
+    }
+    Ok(resolved)
+}
+
+/// Ask a yes/no question on the terminal; anything but yes is no
+pub fn confirm(prompt: &str) -> bool {
+    if !io::stdin().is_terminal() {
+        log::problem!("Error: confirmation required but stdin is not a terminal");
+        return false;
+    }
+    eprint!("{}", prompt);
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes" | "YES")
+}
+
+fn refusal(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_in;
+    use crate::test_support::TempDir;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    /// A project, a directory beside it and a cache c1 owns
+    fn layout(tmp: &TempDir) -> (PathBuf, PathBuf, PathBuf) {
+        let project = tmp.path().join("project");
+        let outside = tmp.path().join("outside");
+        let cache = tmp.path().join("cache");
+        for dir in [&project, &outside, &cache] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        tmp.write("project/build/a.o", "");
+        tmp.write("outside/keep.txt", "");
+        (project, outside, cache)
+    }
+
+    fn allowed(project: &Path, allowed: &[PathBuf], path: &Path) -> bool {
+        check_in(project, allowed, path, "remove").is_ok()
+    }
+
+    #[test]
+    fn allows_paths_inside_the_project() {
+        let tmp = TempDir::new("safe-path-inside");
+        let (project, _, _) = layout(&tmp);
+        assert!(allowed(&project, &[], &project.join("build")));
+        assert!(allowed(&project, &[], &project.join("build/a.o")));
+        assert!(allowed(&project, &[], &project.join("build/not-yet/x")));
+    }
+
+    #[test]
+    fn refuses_the_project_root_itself() {
+        let tmp = TempDir::new("safe-path-root");
+        let (project, _, _) = layout(&tmp);
+        assert!(!allowed(&project, &[], &project));
+        assert!(!allowed(&project, &[], &project.join("build/..")));
+    }
+
+    #[test]
+    fn refuses_traversal_out_of_the_project() {
+        let tmp = TempDir::new("safe-path-traversal");
+        let (project, outside, _) = layout(&tmp);
+        assert!(!allowed(&project, &[], &project.join("..")));
+        assert!(!allowed(&project, &[], &project.join("../outside")));
+        assert!(!allowed(
+            &project,
+            &[],
+            &project.join("build/../../outside/keep.txt")
+        ));
+        assert!(!allowed(
+            &project,
+            &[],
+            &project.join("missing/../../outside")
+        ));
+        // An absolute path elsewhere
+        assert!(!allowed(&project, &[], &outside.join("keep.txt")));
+        assert!(!allowed(&project, &[], Path::new("/")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuses_escapes_through_symlinks() {
+        let tmp = TempDir::new("safe-path-symlink");
+        let (project, outside, _) = layout(&tmp);
+        std::os::unix::fs::symlink(&outside, project.join("link")).unwrap();
+        assert!(!allowed(&project, &[], &project.join("link/keep.txt")));
+        assert!(!allowed(&project, &[], &project.join("link/new")));
+        // Removing the link itself leaves what it points to alone
+        assert!(allowed(&project, &[], &project.join("link")));
+    }
+
+    #[test]
+    fn allows_the_directories_c1_owns() {
+        let tmp = TempDir::new("safe-path-owned");
+        let (project, outside, cache) = layout(&tmp);
+        let owned = [cache.clone()];
+        assert!(allowed(&project, &owned, &cache));
+        assert!(allowed(&project, &owned, &cache.join("git/dep.git")));
+        assert!(!allowed(&project, &owned, &outside));
+        assert!(!allowed(&project, &owned, &cache.join("../outside")));
+    }
+
+    #[test]
+    fn ignores_owned_directories_holding_the_project() {
+        let tmp = TempDir::new("safe-path-parent");
+        let (project, outside, _) = layout(&tmp);
+        let owned = [tmp.path().to_path_buf()];
+        assert!(!allowed(&project, &owned, tmp.path()));
+        assert!(!allowed(&project, &owned, &outside));
+    }
+}
@@ -0,0 +1,62 @@
+//! `--sanitize`: builds instrumented with the compiler's sanitizers.
+//!
+//! The sanitizers become `-fsanitize=...` compile and link flags passed at
+//! configure time, so a different set reconfigures the build. Each set builds
+//! in its own subdirectory of the build directory (`build/asan`,
+//! `build/asan-ubsan`), keeping instrumented objects apart from normal ones.
+
+use crate::error::{self, Error};
+
+/// Every sanitizer `--sanitize` accepts, with its short name, in the order
+/// they appear in directory names
+const SANITIZERS: &[(&str, &str)] = &[
+    ("address", "asan"),
+    ("undefined", "ubsan"),
+    ("thread", "tsan"),
+    ("leak", "lsan"),
+];
+
+/// Restricts `--sanitize` to the names in `SANITIZERS`
+pub fn value_parser() -> clap::builder::PossibleValuesParser {
+    SANITIZERS.iter().map(|(name, _)| *name).into()
+}
+
+/// Pairs that GCC and Clang refuse to instrument for together
+const INCOMPATIBLE: &[(&str, &str)] = &[("thread", "address"), ("thread", "leak")];
+
+/// The sanitizers asked for, in a fixed order and without repeats
+fn normalized(names: &[String]) -> Vec<(&'static str, &'static str)> {
+    SANITIZERS
+        .iter()
+        .filter(|(name, _)| names.iter().any(|n| n == name))
+        .copied()
+        .collect()
+}
+
+pub fn check(names: &[String]) -> error::Result<()> {
+    for (a, b) in INCOMPATIBLE {
+        if names.iter().any(|n| n == a) && names.iter().any(|n| n == b) {
+            return Err(Error::usage(format!(
+                "the {} and {} sanitizers cannot be used together",
+                a, b
+            ))
+            .hint("build with one of them at a time"));
+        }
+    }
+    Ok(())
+}
+
+/// The build subdirectory for these sanitizers, `None` for a normal build
+pub fn subdir(names: &[String]) -> Option<String> {
+    let short: Vec<&str> = normalized(names).iter().map(|(_, s)| *s).collect();
+    (!short.is_empty()).then(|| short.join("-"))
+}
+
+/// Flags for both compiling and linking
+pub fn flags(names: &[String]) -> Option<String> {
+    let names: Vec<&str> = normalized(names).iter().map(|(n, _)| *n).collect();
+    (!names.is_empty()).then(|| {
+        // Frame pointers give the sanitizers' stack traces their frames
+        format!("-fsanitize={} -fno-omit-frame-pointer", names.join(","))
+    })
+}
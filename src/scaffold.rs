@@ -0,0 +1,160 @@
+//! What `c1 new` and `c1 init` create, shown as a tree.
+//!
+//! `--dry-run` prints the files and directories a project would get, each
+//! with a short note on what it is for, and whether git init would run,
+//! without creating anything; the same tree is printed after a real run.
+//! Entries are sorted by name, directories and files alike, so the output
+//! only changes when the project would.
+//!
+//! ```text
+//! hello/
+//! ├── .clang-format   # style for c1 fmt
+//! ├── build/          # build output
+//! └── project.toml    # project configuration
+//! git init: yes
+//! ```
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::log;
+
+/// Whether `git init` would run in the current directory
+pub fn git_plan(git: bool) -> &'static str {
+    if !git {
+        "no (--vcs none)"
+    } else if crate::run_git(".", &["rev-parse", "--is-inside-work-tree"])
+        .is_ok_and(|out| out == "true")
+    {
+        "no (inside an existing git repository)"
+    } else {
+        "yes"
+    }
+}
+
+/// Print `paths` (directories end in `/`) as a tree under `root`, to stdout
+/// for a dry run and as status otherwise
+pub fn print(root: &str, paths: &[String], git: &str, dry_run: bool) {
+    // Every directory a file is in is an entry of its own
+    let mut entries = BTreeSet::new();
+    for path in paths {
+        let mut parent = String::new();
+        for part in path.trim_end_matches('/').split('/') {
+            if !parent.is_empty() {
+                entries.insert(format!("{}/", parent));
+                parent.push('/');
+            }
+            parent.push_str(part);
+        }
+        entries.insert(path.clone());
+    }
+    let mut lines = Vec::new();
+    tree("", "", &entries, &mut lines);
+    let width = lines
+        .iter()
+        .map(|(line, _)| line.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let show = |text: String| {
+        if dry_run {
+            println!("{}", text);
+        } else {
+            log::status!("{}", text);
+        }
+    };
+    show(root.to_string());
+    for (line, note) in lines {
+        if note.is_empty() {
+            show(line);
+        } else {
+            let pad = width - line.chars().count();
+            show(format!("{}{}   # {}", line, " ".repeat(pad), note));
+        }
+    }
+    show(format!("git init: {}", git));
+}
+
+/// The lines for the entries directly in `dir` (`""` or `"src/"`), each with
+/// its note
+fn tree(dir: &str, indent: &str, entries: &BTreeSet<String>, lines: &mut Vec<(String, String)>) {
+    let children: Vec<&String> = entries
+        .iter()
+        .filter(|path| {
+            path.strip_prefix(dir)
+                .is_some_and(|rest| !rest.is_empty() && !rest.trim_end_matches('/').contains('/'))
+        })
+        .collect();
+    for (i, path) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        let name = &path[dir.len()..];
+        lines.push((
+            format!("{}{}{}", indent, if last { "└── " } else { "├── " }, name),
+            describe(path).to_string(),
+        ));
+        if path.ends_with('/') {
+            let indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+            tree(path, &indent, entries, lines);
+        }
+    }
+}
+
+/// A short note on what a scaffolded file or directory is for
+fn describe(path: &str) -> &'static str {
+    let name = path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or(path);
+    match path {
+        "src/" => return "source files",
+        "include/" => return "header files",
+        "external/" => return "dependencies, fetched by c1 sync",
+        "build/" => return "build output",
+        "tests/" => return "tests, run by c1 test",
+        "examples/" => return "example programs",
+        "linker/" => return "linker scripts",
+        _ => {}
+    }
+    match name {
+        "main.c" => "program entry point",
+        "CMakeLists.txt" => "CMake build, kept up to date by c1",
+        "project.toml" => "project configuration",
+        "README.md" => "readme",
+        "LICENSE" => "license text",
+        ".gitignore" => "files git ignores",
+        ".clang-format" => "style for c1 fmt",
+        _ if path.ends_with('/') => "",
+        _ if name.ends_with(".h") => "header",
+        _ if name.ends_with(".c") => "C source",
+        _ if name.ends_with(".ld") => "linker script",
+        _ => "",
+    }
+}
+
+/// The files and directories under `dir`, relative to it, without .git
+pub fn walk(dir: &Path) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut stack = vec![(dir.to_path_buf(), String::new())];
+    while let Some((dir, prefix)) = stack.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name == ".git" {
+                continue;
+            }
+            let relative = format!("{}{}", prefix, name);
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                found.push(format!("{}/", relative));
+                stack.push((entry.path(), format!("{}/", relative)));
+            } else {
+                found.push(relative);
+            }
+        }
+    }
+    found.sort();
+    found
+}
@@ -0,0 +1,269 @@
+//! Credential placeholders in dependency URLs.
+//!
+//! project.toml is committed, so tokens must not live in it. A URL like
+//! `https://${GIT_TOKEN}@git.corp/team/lib.git` is stored verbatim and only
+//! expanded right before git is invoked, from the environment or the user's
+//! `~/.config/c1/credentials.toml` (never from a project-local file). Every
+//! value that was expanded is remembered so it can be redacted from anything
+//! c1 prints, including git's own stderr. When git is refused access,
+//! `auth_failure` turns its stderr into advice on how to give it some.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+static EXPANDED_SECRETS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+const REDACTED: &str = "***";
+
+/// A piece of a URL template: literal text or a `${NAME}` placeholder
+pub enum Segment<'a> {
+    Literal(&'a str),
+    Var(&'a str),
+}
+
+/// Split a URL template into literal text and placeholders
+pub fn segments(template: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start + 2..].find('}') else {
+            break;
+        };
+        if start > 0 {
+            segments.push(Segment::Literal(&rest[..start]));
+        }
+        segments.push(Segment::Var(&rest[start + 2..start + 2 + len]));
+        rest = &rest[start + 3 + len..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Literal(rest));
+    }
+    segments
+}
+
+/// Expand `${NAME}` placeholders in a dependency URL.
+///
+/// Fails with an actionable message naming every unset variable, without ever
+/// including an expanded value.
+pub fn expand(template: &str) -> Result<String, String> {
+    let mut credentials = None;
+    let mut missing = Vec::new();
+    let mut expanded = String::new();
+
+    for segment in segments(template) {
+        match segment {
+            Segment::Literal(text) => expanded.push_str(text),
+            Segment::Var(name) => {
+                let value = std::env::var(name).ok().filter(|v| !v.is_empty()).or_else(|| {
+                    credentials
+                        .get_or_insert_with(load_credentials)
+                        .get(name)
+                        .and_then(|v| v.as_str())
+                        .map(str::to_string)
+                });
+                match value {
+                    Some(value) => {
+                        remember(&value);
+                        expanded.push_str(&value);
+                    }
+                    None => missing.push(name.to_string()),
+                }
+            }
+        }
+    }
+
+    if !missing.is_empty() {
+        let hint = credentials_path()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "~/.config/c1/credentials.toml".to_string());
+        return Err(format!(
+            "URL '{}' needs {}. Set {} in the environment or in {}",
+            template,
+            missing
+                .iter()
+                .map(|m| format!("${{{}}}", m))
+                .collect::<Vec<_>>()
+                .join(", "),
+            missing.join(", "),
+            hint
+        ));
+    }
+    Ok(expanded)
+}
+
+/// Remove expanded secrets and URL passwords from text before printing it
+pub fn redact(text: &str) -> String {
+    let mut redacted = text.to_string();
+    for secret in EXPANDED_SECRETS.lock().unwrap().iter() {
+        redacted = redacted.replace(secret.as_str(), REDACTED);
+    }
+    redact_userinfo(&redacted)
+}
+
+/// When git's `stderr` from contacting `url` (the URL as written, not
+/// expanded) says it was refused access, what to do about it
+pub fn auth_failure(url: &str, stderr: &str) -> Option<String> {
+    let lower = stderr.to_ascii_lowercase();
+    let ssh = url.starts_with("ssh://") || (!url.contains("://") && url.contains('@'));
+    let host = host(url);
+    if lower.contains("host key verification failed") {
+        return Some(format!(
+            "{} is not a known SSH host yet\n    \
+             connect once with `ssh -T git@{}` to check and accept its key",
+            host, host
+        ));
+    }
+    let refused = [
+        "permission denied",
+        "authentication failed",
+        "could not read username",
+        "could not read password",
+        "terminal prompts disabled",
+        "access denied",
+        "returned error: 401",
+        "returned error: 403",
+    ]
+    .iter()
+    .any(|pattern| lower.contains(pattern))
+        // GitHub answers a private repository it won't show you like this
+        || (lower.contains("repository") && lower.contains("not found"));
+    if !refused {
+        return None;
+    }
+
+    let advice = if ssh {
+        format!(
+            "check that your key is loaded with `ssh-add -l` (start ssh-agent and `ssh-add` it if not)\n    \
+             check that {} accepts it with `ssh -T git@{}`",
+            host, host
+        )
+    } else if url.contains("${") {
+        "the token in the URL was refused; check that it has not expired and can read the repository"
+            .to_string()
+    } else {
+        format!(
+            "configure a git credential helper (`git config --global credential.helper store`, or your platform's)\n    \
+             or put a token placeholder in the URL, `https://${{GIT_TOKEN}}@{}/...`, and set GIT_TOKEN in the environment or {}\n    \
+             or use the SSH URL, `git@{}:...`",
+            host,
+            credentials_path()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "~/.config/c1/credentials.toml".to_string()),
+            host
+        )
+    };
+    Some(format!(
+        "{} refused access to {}\n    {}",
+        host,
+        redact(url),
+        advice
+    ))
+}
+
+/// The host of `scheme://[user@]host[:port]/...` or `user@host:path`
+fn host(url: &str) -> &str {
+    let authority = match url.split_once("://") {
+        Some((_, rest)) => rest.split('/').next().unwrap_or(rest),
+        None => url.split(':').next().unwrap_or(url),
+    };
+    let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    host.split(':').next().unwrap_or(host)
+}
+
+/// Heuristically detect credentials written literally into a URL
+pub fn literal_credential(url: &str) -> Option<&'static str> {
+    let Some(userinfo) = userinfo(url) else {
+        return query_token(url);
+    };
+    if userinfo.contains("${") {
+        return None;
+    }
+    if userinfo.contains(':') {
+        return Some("a password");
+    }
+    let looks_like_token = ["ghp_", "gho_", "github_pat_", "glpat-", "x-oauth"]
+        .iter()
+        .any(|prefix| userinfo.starts_with(prefix))
+        || (userinfo.len() >= 20 && userinfo.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'));
+    if looks_like_token {
+        return Some("an access token");
+    }
+    query_token(url)
+}
+
+fn query_token(url: &str) -> Option<&'static str> {
+    let query = url.split_once('?')?.1;
+    query
+        .split('&')
+        .any(|pair| {
+            let key = pair.split('=').next().unwrap_or("").to_ascii_lowercase();
+            key.contains("token") || key == "password"
+        })
+        .then_some("a token in the query string")
+}
+
+/// The `user[:password]` part of a `scheme://user@host` URL
+fn userinfo(url: &str) -> Option<&str> {
+    let after_scheme = url.split_once("://")?.1;
+    let authority = after_scheme.split('/').next()?;
+    authority.rsplit_once('@').map(|(userinfo, _)| userinfo)
+}
+
+/// Replace `scheme://user:password@` and token-only userinfo with `***`
+fn redact_userinfo(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find("://") {
+        let (head, tail) = rest.split_at(pos + 3);
+        out.push_str(head);
+        let authority_end = tail
+            .find(|c: char| c == '/' || c.is_whitespace() || c == '\'' || c == '"')
+            .unwrap_or(tail.len());
+        match tail[..authority_end].rfind('@') {
+            Some(at) if !tail[..at].contains("${") => {
+                let userinfo = &tail[..at];
+                match userinfo.split_once(':') {
+                    Some((user, _)) => out.push_str(&format!("{}:{}", user, REDACTED)),
+                    None if userinfo.len() >= 20 => out.push_str(REDACTED),
+                    None => out.push_str(userinfo),
+                }
+                rest = &tail[at..];
+            }
+            _ => rest = tail,
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn remember(secret: &str) {
+    let mut secrets = EXPANDED_SECRETS.lock().unwrap();
+    if !secrets.iter().any(|s| s == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// `$XDG_CONFIG_HOME/c1` (or `~/.config/c1`, `%APPDATA%\c1` on Windows)
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir).join("c1"));
+    }
+    if cfg!(windows)
+        && let Some(dir) = std::env::var_os("APPDATA")
+    {
+        return Some(PathBuf::from(dir).join("c1"));
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join("c1"))
+}
+
+fn credentials_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("credentials.toml"))
+}
+
+fn load_credentials() -> toml::Table {
+    credentials_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default()
+}
@@ -0,0 +1,234 @@
+//! `c1 size`: how much flash and RAM the executable takes.
+//!
+//! The project is built, then `size` (or `llvm-size`) reports the text, data
+//! and bss sections of the executable: flash holds text and data, RAM data
+//! and bss. For a cross build the binutils next to the toolchain's `nm`
+//! (`arm-none-eabi-size` beside `arm-none-eabi-nm`) are tried first. Each run
+//! records its numbers in `c1-size.json` in the build directory, which
+//! `--diff` compares against; `--top N` lists the largest symbols from
+//! `nm --size-sort`. Without binutils only the file size is reported.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::build_history::{self, Value};
+use crate::error::{self, Error};
+use crate::{BuildOptions, log, safe_path};
+
+const RECORD: &str = "c1-size.json";
+
+#[derive(Clone, Copy)]
+struct Sizes {
+    text: u64,
+    data: u64,
+    bss: u64,
+    file: u64,
+}
+
+pub fn cmd_size(options: &BuildOptions, diff: bool, top: Option<usize>) -> error::Result<()> {
+    let config = crate::read_config()?;
+    crate::cmd_build(options)?;
+    let build_dir = crate::target_build_dir(options);
+    let executable = crate::executable_path(&build_dir, &config.project.name, options.release)
+        .map_err(|_| {
+            Error::failure(format!(
+                "Executable for '{}' not found",
+                config.project.name
+            ))
+            .hint("c1 size measures the project's executable; a library has none")
+        })?;
+    let file = fs::metadata(&executable).map(|m| m.len()).unwrap_or(0);
+
+    let Some((text, data, bss)) = sections(&build_dir, &executable) else {
+        println!("{}: {} bytes", executable.display(), file);
+        log::status!("Note: neither size nor llvm-size was found; showing the file size only");
+        return Ok(());
+    };
+    let sizes = Sizes {
+        text,
+        data,
+        bss,
+        file,
+    };
+    let record = build_dir.join(RECORD);
+    let previous = if diff { load(&record) } else { None };
+    print_sizes(&executable, &sizes, previous.as_ref());
+    if diff && previous.is_none() {
+        log::status!("\nNo sizes recorded yet; the next `c1 size --diff` compares with these");
+    }
+    if let Err(e) = safe_path::write(&record, to_json(&sizes)) {
+        log::problem!(
+            "Warning: could not record sizes in {}: {}",
+            record.display(),
+            e
+        );
+    }
+
+    if let Some(count) = top {
+        print_top(&build_dir, &executable, count);
+    }
+    Ok(())
+}
+
+/// The first of the tools named `name` that runs: the one beside the
+/// toolchain's `nm` that cmake found, then the system's, then LLVM's
+fn tool_candidates(build_dir: &Path, name: &str) -> Vec<String> {
+    let mut candidates = Vec::new();
+    if let Some(nm) = crate::cached_value(build_dir, "CMAKE_NM") {
+        let nm = PathBuf::from(nm);
+        let file_name = nm.file_name().map(|n| n.to_string_lossy().to_string());
+        if let Some(prefix) = file_name.as_deref().and_then(|n| n.strip_suffix("nm")) {
+            candidates.push(
+                nm.with_file_name(format!("{}{}", prefix, name))
+                    .display()
+                    .to_string(),
+            );
+        }
+    }
+    candidates.push(name.to_string());
+    candidates.push(format!("llvm-{}", name));
+    candidates.dedup();
+    candidates
+}
+
+/// Run the first tool that works, returning its stdout
+fn run_tool(build_dir: &Path, name: &str, args: &[&str], file: &Path) -> Option<String> {
+    for tool in tool_candidates(build_dir, name) {
+        let mut command = Command::new(&tool);
+        command.args(args).arg(file);
+        log::command(&command);
+        match command.output() {
+            Ok(output) if output.status.success() => {
+                return Some(String::from_utf8_lossy(&output.stdout).to_string());
+            }
+            Ok(output) => log::verbose!(
+                "{} failed: {}",
+                tool,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(_) => log::verbose!("{} not found", tool),
+        }
+    }
+    None
+}
+
+/// text, data and bss from `size`'s Berkeley format:
+///
+/// ```text
+///    text    data     bss     dec     hex filename
+///    1418     544       8    1970     7b2 build/debug/app
+/// ```
+pub fn sections(build_dir: &Path, executable: &Path) -> Option<(u64, u64, u64)> {
+    let output = run_tool(build_dir, "size", &["-B"], executable)?;
+    let line = output.lines().nth(1)?;
+    let mut numbers = line.split_whitespace().map(|n| n.parse::<u64>().ok());
+    Some((numbers.next()??, numbers.next()??, numbers.next()??))
+}
+
+fn print_sizes(executable: &Path, sizes: &Sizes, previous: Option<&Sizes>) {
+    let rows = [
+        ("text", sizes.text, previous.map(|p| p.text), ""),
+        ("data", sizes.data, previous.map(|p| p.data), ""),
+        ("bss", sizes.bss, previous.map(|p| p.bss), ""),
+        (
+            "flash",
+            sizes.text + sizes.data,
+            previous.map(|p| p.text + p.data),
+            "text + data",
+        ),
+        (
+            "ram",
+            sizes.data + sizes.bss,
+            previous.map(|p| p.data + p.bss),
+            "data + bss",
+        ),
+        ("file", sizes.file, previous.map(|p| p.file), ""),
+    ];
+    println!("{}", executable.display());
+    for (name, value, before, note) in rows {
+        let mut line = format!(
+            "  {:<6} {:>10} B  {:>10}",
+            name,
+            value,
+            build_history::bytes(value)
+        );
+        if let Some(before) = before {
+            line.push_str(&format!(
+                "  {:>8}",
+                build_history::signed(value as i64 - before as i64)
+            ));
+        }
+        if !note.is_empty() {
+            line.push_str(&format!("  ({})", note));
+        }
+        println!("{}", line.trim_end());
+    }
+}
+
+/// The `count` largest symbols, from `nm --size-sort`
+fn print_top(build_dir: &Path, executable: &Path, count: usize) {
+    let Some(output) = run_tool(
+        build_dir,
+        "nm",
+        &["--print-size", "--size-sort", "--reverse-sort"],
+        executable,
+    ) else {
+        log::problem!("Warning: neither nm nor llvm-nm was found; cannot list symbols");
+        return;
+    };
+    // "<address> <size> <type> <name>", sizes in hex
+    let symbols: Vec<(u64, char, &str)> = output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let _address = fields.next()?;
+            let size = u64::from_str_radix(fields.next()?, 16).ok()?;
+            let kind = fields.next()?.chars().next()?;
+            Some((size, kind, fields.next()?))
+        })
+        .take(count)
+        .collect();
+    if symbols.is_empty() {
+        log::status!("\nNo sized symbols found (was the executable stripped?)");
+        return;
+    }
+    println!("\nLargest symbols:");
+    for (size, kind, name) in symbols {
+        println!("  {:>10} B  {:<6} {}", size, section_of(kind), name);
+    }
+}
+
+/// The section nm's symbol type letter stands for
+fn section_of(kind: char) -> &'static str {
+    match kind.to_ascii_lowercase() {
+        't' | 'w' => "text",
+        'r' => "rodata",
+        'd' | 'g' => "data",
+        'b' | 's' => "bss",
+        _ => "other",
+    }
+}
+
+fn to_json(sizes: &Sizes) -> String {
+    format!(
+        "{{\"text\":{},\"data\":{},\"bss\":{},\"file\":{}}}\n",
+        sizes.text, sizes.data, sizes.bss, sizes.file
+    )
+}
+
+/// The sizes the previous `c1 size` recorded in this build directory
+fn load(record: &Path) -> Option<Sizes> {
+    let content = fs::read_to_string(record).ok()?;
+    let fields = build_history::parse_object(&content)?;
+    let number = |key: &str| match fields.iter().find(|(k, _)| k == key)? {
+        (_, Value::Number(n)) => Some(*n),
+        _ => None,
+    };
+    Some(Sizes {
+        text: number("text")?,
+        data: number("data")?,
+        bss: number("bss")?,
+        file: number("file")?,
+    })
+}
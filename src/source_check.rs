@@ -0,0 +1,247 @@
+//! The checks `c1 build` runs on the SOURCES and HEADERS lists before cmake.
+//!
+//! A file listed twice (easy after editing CMakeLists.txt by hand and then
+//! running `c1 create`) or a file that no longer exists otherwise surfaces as
+//! a cmake or linker error far from its cause. Each is reported with its
+//! CMakeLists.txt line: a missing file fails the build, a duplicate is a
+//! warning. Headers under include/ whose paths differ only in case are
+//! warned about too, since they clash on the case-insensitive file systems of
+//! macOS and Windows; this also covers `source_mode = "auto"`, whose lists
+//! c1 writes itself. `c1 build --fix-sources` drops the duplicates and the
+//! missing files from CMakeLists.txt and shows what it changed as a diff.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::error::{self, Context, Error};
+use crate::ignore::Ignore;
+use crate::{auto_sources, log, safe_path};
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+const HINT: &str = "run `c1 build --fix-sources` to remove them from CMakeLists.txt";
+
+/// A file named in a `set(SOURCES ...)` or `set(HEADERS ...)` block
+struct Entry {
+    /// Line in CMakeLists.txt, from 0
+    line: usize,
+    /// As written
+    text: String,
+    /// Relative to the project root
+    path: String,
+}
+
+enum Problem {
+    Duplicate { first: usize },
+    Missing,
+}
+
+/// Check the lists, fixing what `fix` allows
+pub fn run(fix: bool) -> error::Result<()> {
+    let content = fs::read_to_string(CMAKE_PATH).ok();
+    let entries = match &content {
+        Some(content) if !auto_sources::is_auto() => entries(content),
+        _ => Vec::new(),
+    };
+    warn_case_clashes(&entries);
+
+    let mut problems: Vec<(&Entry, Problem)> = Vec::new();
+    let mut seen: BTreeMap<&str, usize> = BTreeMap::new();
+    for entry in &entries {
+        if let Some(first) = seen.get(entry.path.as_str()) {
+            problems.push((entry, Problem::Duplicate { first: *first }));
+        } else if !Path::new(&entry.path).exists() {
+            problems.push((entry, Problem::Missing));
+        } else {
+            seen.insert(&entry.path, entry.line);
+        }
+    }
+    let (Some(content), false) = (content, problems.is_empty()) else {
+        return Ok(());
+    };
+    if fix {
+        return rewrite(&content, &problems);
+    }
+
+    let mut missing = Vec::new();
+    for (entry, problem) in &problems {
+        match problem {
+            Problem::Duplicate { first } => log::problem!(
+                "Warning: {}:{}: {} is listed twice (first on line {})",
+                CMAKE_PATH,
+                entry.line + 1,
+                entry.text,
+                first + 1
+            ),
+            Problem::Missing => missing.push(format!(
+                "{}:{}: {} does not exist",
+                CMAKE_PATH,
+                entry.line + 1,
+                entry.text
+            )),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(Error::config(missing.join("\n")).hint(HINT));
+    }
+    log::problem!("Hint: {}", HINT);
+    Ok(())
+}
+
+/// The files in the SOURCES and HEADERS blocks of `content`, in order
+fn entries(content: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut inside = false;
+    for (number, line) in content.lines().enumerate() {
+        let mut code = line.split('#').next().unwrap_or_default().trim();
+        if !inside {
+            let Some(rest) = code
+                .strip_prefix("set(")
+                .or_else(|| code.strip_prefix("set ("))
+            else {
+                continue;
+            };
+            let rest = rest.trim_start();
+            let Some(rest) = rest
+                .strip_prefix("SOURCES")
+                .or_else(|| rest.strip_prefix("HEADERS"))
+                .filter(|rest| rest.is_empty() || rest.starts_with([' ', '\t', ')']))
+            else {
+                continue;
+            };
+            code = rest;
+        }
+        let (words, closed) = match code.find(')') {
+            Some(at) => (&code[..at], true),
+            None => (code, false),
+        };
+        for word in words.split_whitespace() {
+            let text = word.trim_matches('"');
+            let path = text
+                .strip_prefix("${CMAKE_CURRENT_SOURCE_DIR}/")
+                .unwrap_or(text)
+                .trim_start_matches("./");
+            // Variables and generator expressions are cmake's to resolve
+            if path.contains('$') || !path.contains('.') {
+                continue;
+            }
+            entries.push(Entry {
+                line: number,
+                text: text.to_string(),
+                path: path.to_string(),
+            });
+        }
+        inside = !closed;
+    }
+    entries
+}
+
+/// Warn about headers under include/ that only a case-sensitive file system
+/// tells apart
+fn warn_case_clashes(entries: &[Entry]) {
+    let mut headers = Vec::new();
+    collect_headers(Path::new("include"), &mut headers);
+    let mut by_case: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for header in headers {
+        by_case
+            .entry(header.to_lowercase())
+            .or_default()
+            .push(header);
+    }
+    for clashing in by_case.values().filter(|paths| paths.len() > 1) {
+        let described: Vec<String> = clashing
+            .iter()
+            .map(|path| match entries.iter().find(|e| &e.path == path) {
+                Some(entry) => format!("{} ({}:{})", path, CMAKE_PATH, entry.line + 1),
+                None => path.clone(),
+            })
+            .collect();
+        log::problem!(
+            "Warning: {} differ only in case and clash on macOS and Windows",
+            described.join(" and ")
+        );
+    }
+}
+
+fn collect_headers(dir: &Path, found: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_headers(&path, found);
+        } else if path.extension().is_some_and(|e| e == "h") {
+            let path = path.to_string_lossy().replace('\\', "/");
+            if Ignore::project().excluded_by(&path).is_none() {
+                found.push(path);
+            }
+        }
+    }
+}
+
+/// Remove the entries with problems from CMakeLists.txt and show the change
+fn rewrite(content: &str, problems: &[(&Entry, Problem)]) -> error::Result<()> {
+    let original: Vec<&str> = content.lines().collect();
+    let mut lines: Vec<Option<String>> = original.iter().map(|l| Some(l.to_string())).collect();
+    // Why each line changed, for the diff
+    let mut reasons: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for (entry, problem) in problems {
+        if let Some(Some(line)) = lines.get(entry.line) {
+            let updated = without_word(line, &entry.text);
+            lines[entry.line] = (!updated.trim().is_empty()).then_some(updated);
+        }
+        reasons.entry(entry.line).or_default().push(match problem {
+            Problem::Duplicate { first } => {
+                format!("{} is listed on line {} already", entry.text, first + 1)
+            }
+            Problem::Missing => format!("{} does not exist", entry.text),
+        });
+    }
+    let mut diff = format!("--- {}\n+++ {}\n", CMAKE_PATH, CMAKE_PATH);
+    for (number, why) in &reasons {
+        diff.push_str(&format!(
+            "@@ line {} @@ {}\n-{}\n",
+            number + 1,
+            why.join("; "),
+            original[*number]
+        ));
+        if let Some(line) = &lines[*number] {
+            diff.push_str(&format!("+{}\n", line));
+        }
+    }
+    let mut updated: String = lines.into_iter().flatten().map(|l| l + "\n").collect();
+    if !content.ends_with('\n') {
+        updated.pop();
+    }
+    safe_path::write(CMAKE_PATH, updated).context(|| format!("failed to update {}", CMAKE_PATH))?;
+    log::status!("{}", diff.trim_end());
+    log::status!(
+        "✓ Removed {} duplicate or missing file(s) from the lists in {}",
+        problems.len(),
+        CMAKE_PATH
+    );
+    Ok(())
+}
+
+/// `line` without the first whitespace-separated `word`
+fn without_word(line: &str, word: &str) -> String {
+    let mut offset = 0;
+    for part in line.split_inclusive(char::is_whitespace) {
+        let token = part.trim_end();
+        let rest = &line[offset + part.len()..];
+        // The block may close right after it
+        if let Some(token) = token.strip_suffix(')')
+            && token.trim_matches('"') == word
+        {
+            return format!("{}){}", line[..offset].trim_end(), rest);
+        }
+        if token.trim_matches('"') == word {
+            return format!("{}{}", &line[..offset], rest)
+                .trim_end()
+                .to_string();
+        }
+        offset += part.len();
+    }
+    line.to_string()
+}
@@ -0,0 +1,155 @@
+//! Run a child process with its output forwarded as it arrives.
+//!
+//! `Command::output()` holds everything back until the process exits, which
+//! makes a long compile or clone look hung. Here both pipes are read on their
+//! own threads and every line is passed through a filter (to redact
+//! credentials, say) before it is echoed to our stdout/stderr and kept for the
+//! caller. A carriage return ends a line too, so progress meters that redraw
+//! in place still update live. Under `--quiet` only stderr is echoed, nothing
+//! is while `log::capture` holds this thread's output back, and under
+//! `--message-format json` each line becomes an `output` event.
+//! `run_observed` hands stderr lines to a callback instead of echoing them,
+//! for git's progress meter to be drawn by `progress`, and `run_holding`
+//! keeps the lines a predicate picks out of both streams for the caller to
+//! show in its own way.
+
+use std::io::{self, Read, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::Instant;
+
+use crate::{build_log, events, log};
+
+pub struct Captured {
+    pub status: ExitStatus,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Spawn `command`, echoing its output through `filter` while it runs
+pub fn run(command: &mut Command, filter: fn(&str) -> String) -> io::Result<Captured> {
+    spawn(command, filter, |_| false, None)
+}
+
+/// Like `run`, but the lines `hold` picks are only captured, not echoed
+pub fn run_holding(
+    command: &mut Command,
+    filter: fn(&str) -> String,
+    hold: fn(&str) -> bool,
+) -> io::Result<Captured> {
+    spawn(command, filter, hold, None)
+}
+
+/// Like `run`, but each stderr line goes to `observe` rather than to our
+/// stderr
+pub fn run_observed(
+    command: &mut Command,
+    filter: fn(&str) -> String,
+    observe: &mut (dyn FnMut(&str) + Send),
+) -> io::Result<Captured> {
+    spawn(command, filter, |_| false, Some(observe))
+}
+
+fn spawn(
+    command: &mut Command,
+    filter: fn(&str) -> String,
+    hold: fn(&str) -> bool,
+    observe: Option<&mut (dyn FnMut(&str) + Send)>,
+) -> io::Result<Captured> {
+    log::command(command);
+    let started = Instant::now();
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .inspect_err(|_| build_log::finished(None, started.elapsed()))?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+
+    let capturing = log::is_capturing();
+    let (stdout, stderr) = thread::scope(|scope| {
+        let err_thread = scope.spawn(move || {
+            let echo = !capturing && observe.is_none();
+            forward(stderr, io::stderr(), "stderr", echo, filter, hold, observe)
+        });
+        let stdout = forward(
+            stdout,
+            io::stdout(),
+            "stdout",
+            !log::is_quiet() && !capturing,
+            filter,
+            hold,
+            None,
+        );
+        (stdout, err_thread.join().unwrap_or_default())
+    });
+    let status = child.wait()?;
+    build_log::finished(status.code(), started.elapsed());
+    Ok(Captured {
+        status,
+        stdout,
+        stderr,
+    })
+}
+
+/// Copy `source`, the child's stream `name`, to `sink` one line at a time
+/// (or only collect it when not `echo` or `hold` picks the line), returning
+/// everything read. Each line is also shown to `observe`.
+fn forward(
+    mut source: impl Read,
+    mut sink: impl Write,
+    name: &str,
+    echo: bool,
+    filter: fn(&str) -> String,
+    hold: fn(&str) -> bool,
+    mut observe: Option<&mut (dyn FnMut(&str) + Send)>,
+) -> String {
+    let mut captured = String::new();
+    let mut pending: Vec<u8> = Vec::new();
+    let mut buffer = [0u8; 8192];
+    loop {
+        let read = match source.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
+        };
+        pending.extend_from_slice(&buffer[..read]);
+        while let Some(end) = pending.iter().position(|&b| b == b'\n' || b == b'\r') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            let text = emit(&line, &mut sink, name, echo, &mut captured, filter, hold);
+            if let Some(observe) = observe.as_mut() {
+                observe(&text);
+            }
+        }
+    }
+    if !pending.is_empty() {
+        let text = emit(&pending, &mut sink, name, echo, &mut captured, filter, hold);
+        if let Some(observe) = observe.as_mut() {
+            observe(&text);
+        }
+    }
+    captured
+}
+
+fn emit(
+    line: &[u8],
+    sink: &mut impl Write,
+    name: &str,
+    echo: bool,
+    captured: &mut String,
+    filter: fn(&str) -> String,
+    hold: fn(&str) -> bool,
+) -> String {
+    let text = filter(&String::from_utf8_lossy(line));
+    if hold(&text) {
+        // The caller shows it
+    } else if events::is_json() {
+        events::output(name, &text);
+    } else if echo {
+        let _ = sink.write_all(text.as_bytes());
+        let _ = sink.flush();
+    }
+    captured.push_str(&text);
+    text
+}
@@ -0,0 +1,94 @@
+//! `--color auto|always|never`: colored marks in c1's own output.
+//!
+//! A line that starts (after its indentation) with `✓`, `✗`, `Error:`,
+//! `Warning:` or `Hint:` has that mark colored. With `auto`, the default,
+//! a stream gets colors only when it is a terminal and `NO_COLOR` is not set,
+//! so logs and pipes stay plain; `always` is for CI systems that render ANSI
+//! although they are not terminals. Where colors are off the check and cross
+//! become ASCII as well, `+` and `x`, which keeps the columns of the sync and
+//! add steps where they were. The choice is passed on to c1 processes started
+//! for workspace members and matrix builds.
+
+use std::io::{self, IsTerminal};
+use std::process::Command;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+const AUTO: u8 = 0;
+const ALWAYS: u8 = 1;
+const NEVER: u8 = 2;
+
+static CHOICE: AtomicU8 = AtomicU8::new(AUTO);
+
+/// Passes `--color always` or `never` on to child c1 processes
+const ENV_VAR: &str = "C1_COLOR";
+
+/// Marks a line can start with: the mark, its ASCII form and its color
+const MARKS: &[(&str, &str, &str)] = &[
+    ("✓", "+", "32"),
+    ("✗", "x", "31"),
+    ("Error:", "Error:", "1;31"),
+    ("Warning:", "Warning:", "1;33"),
+    ("Hint:", "Hint:", "36"),
+];
+
+pub fn set(choice: &str) {
+    let inherited = std::env::var(ENV_VAR).unwrap_or_default();
+    let choice = match (choice, inherited.as_str()) {
+        ("always", _) | ("auto", "always") => ALWAYS,
+        ("never", _) | ("auto", "never") => NEVER,
+        _ => AUTO,
+    };
+    CHOICE.store(choice, Ordering::Relaxed);
+}
+
+/// Whether stderr (or stdout) gets colors and glyphs
+pub fn enabled(stderr: bool) -> bool {
+    match CHOICE.load(Ordering::Relaxed) {
+        ALWAYS => true,
+        NEVER => false,
+        _ => {
+            let terminal = if stderr {
+                io::stderr().is_terminal()
+            } else {
+                io::stdout().is_terminal()
+            };
+            terminal && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        }
+    }
+}
+
+/// Run a child c1 with an explicit `--color` of this one
+pub fn pass_to(command: &mut Command) {
+    match CHOICE.load(Ordering::Relaxed) {
+        ALWAYS => command.env(ENV_VAR, "always"),
+        NEVER => command.env(ENV_VAR, "never"),
+        _ => command.env_remove(ENV_VAR),
+    };
+}
+
+/// Text in an ANSI color (SGR code), or as it is when colors are off
+pub fn paint(stderr: bool, code: &str, text: &str) -> String {
+    if enabled(stderr) {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// `line` as it is printed on stderr or stdout, its leading mark colored or
+/// spelled in ASCII
+pub fn line(stderr: bool, line: &str) -> String {
+    let rest = line.trim_start();
+    let indent = &line[..line.len() - rest.len()];
+    for (mark, ascii, code) in MARKS {
+        if let Some(text) = rest.strip_prefix(mark) {
+            let mark = if enabled(stderr) {
+                paint(stderr, code, mark)
+            } else {
+                ascii.to_string()
+            };
+            return format!("{}{}{}", indent, mark, text);
+        }
+    }
+    line.to_string()
+}
@@ -0,0 +1,91 @@
+//! `subdir = "libs/json"`: a dependency that lives in a subdirectory of its
+//! repository.
+//!
+//! The wiring in CMakeLists.txt, the include path of `c1 check` and the
+//! dependency's own project.toml are all looked up in
+//! `external/<name>/<subdir>` instead of the clone's root. A git clone is
+//! sparse: `git clone --sparse` followed by `git sparse-checkout set <subdir>`
+//! materializes that subtree (and the files at the top of the repository)
+//! only, which matters for large monorepos. Dropping the key turns the
+//! sparse checkout off again on the next sync. A subdirectory that does not
+//! exist once the dependency is checked out fails the add or sync.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::run_git;
+
+/// The `subdir` of a dependency entry
+pub fn of(entry: &toml::Value) -> Option<&str> {
+    entry.get("subdir").and_then(|v| v.as_str())
+}
+
+/// A subdirectory has to stay inside the clone
+pub fn check(subdir: &str) -> Result<(), String> {
+    let path = Path::new(subdir);
+    let inside = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if subdir.trim_matches('/').is_empty() || !inside {
+        return Err(format!(
+            "subdir '{}' must be a relative path inside the repository, e.g. libs/json",
+            subdir
+        ));
+    }
+    Ok(())
+}
+
+/// Where the library of dependency `name` is: external/<name>, or the
+/// subdirectory project.toml declares for it
+pub fn root(name: &str) -> PathBuf {
+    let dir = Path::new("external").join(name);
+    match declared(name) {
+        Some(subdir) => dir.join(subdir.trim_matches('/')),
+        None => dir,
+    }
+}
+
+/// The `subdir` of dependency `name` in project.toml
+fn declared(name: &str) -> Option<String> {
+    let manifest: toml::Table = fs::read_to_string("project.toml").ok()?.parse().ok()?;
+    let entry = ["dependencies", "dev-dependencies"]
+        .iter()
+        .find_map(|section| manifest.get(*section)?.get(name))?;
+    of(entry).map(str::to_string)
+}
+
+/// Narrow the git clone in external/<name> to `subdir`, or widen it back to
+/// the whole repository without one, then make sure the subdirectory is there
+pub fn apply(name: &str, subdir: Option<&str>) -> Result<(), String> {
+    let dir = format!("external/{}", name);
+    match subdir {
+        Some(subdir) => {
+            run_git(&dir, &["sparse-checkout", "set", subdir.trim_matches('/')])
+                .map_err(|e| format!("Failed to check out {} of {}: {}", subdir, name, e))?;
+        }
+        None => {
+            let sparse = run_git(&dir, &["config", "--get", "core.sparseCheckout"])
+                .is_ok_and(|value| value == "true");
+            if sparse {
+                run_git(&dir, &["sparse-checkout", "disable"])
+                    .map_err(|e| format!("Failed to check out all of {}: {}", name, e))?;
+            }
+        }
+    }
+    verify(name, subdir)
+}
+
+/// Fail when `subdir` is not a directory of external/<name>
+pub fn verify(name: &str, subdir: Option<&str>) -> Result<(), String> {
+    let Some(subdir) = subdir else {
+        return Ok(());
+    };
+    if Path::new("external").join(name).join(subdir).is_dir() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}: subdir {} does not exist in external/{}",
+            name, subdir, name
+        ))
+    }
+}
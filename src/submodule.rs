@@ -0,0 +1,137 @@
+//! Dependencies tracked as git submodules of the project repository.
+//!
+//! `c1 add <url> --submodule` registers `external/<name>` with
+//! `git submodule add` and marks the entry with `submodule = true`. From then
+//! on the parent repository pins the commit, and `c1 sync` runs
+//! `git submodule update --init` for the entry instead of cloning. Submodule
+//! and plain-clone dependencies can be mixed freely.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::{
+    checkout_detached, lockfile, log, remote, run_git, safe_path, secrets, short_hash, stream,
+};
+
+/// Whether a dependency entry asks for a submodule
+pub fn is_submodule(table: &toml::Table) -> bool {
+    table
+        .get("submodule")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Register `url` as the submodule external/<name>, at `tag` or on `branch`
+pub fn add(name: &str, url: &str, tag: Option<&str>, branch: Option<&str>) -> Result<(), String> {
+    // .gitmodules is committed, so it must never hold an expanded token
+    if secrets::expand(url)? != url {
+        return Err(format!(
+            "{} uses a credential placeholder, which a submodule would store expanded in .gitmodules; configure a git credential helper instead",
+            name
+        ));
+    }
+    run_git(".", &["rev-parse", "--show-toplevel"]).map_err(|_| {
+        "--submodule needs the project to be a git repository (run `git init` first)".to_string()
+    })?;
+
+    let path = format!("external/{}", name);
+    if is_registered(&path) {
+        log::status!("  {} is already a submodule", path);
+        return sync(name, url, tag, branch);
+    }
+    if Path::new(&path).exists() {
+        log::status!("  Removing existing {}...", path);
+        safe_path::remove_dir_all(&path)
+            .map_err(|e| format!("Failed to remove {}: {}", path, e))?;
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.args(["submodule", "add"]);
+    if let Some(b) = branch {
+        cmd.args(["-b", b]);
+    }
+    cmd.args([url, &path]);
+    match stream::run(&mut cmd, secrets::redact) {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            return Err(format!(
+                "Failed to add submodule {}\n    {}",
+                name,
+                output.stderr.trim()
+            ));
+        }
+        Err(e) => return Err(format!("Error adding submodule {}: {}", name, e)),
+    }
+
+    if let Some(t) = tag {
+        checkout_detached(&path, &format!("tags/{}", t))
+            .map_err(|e| format!("Failed to check out tag {} of {}: {}", t, name, e))?;
+        // Stage the tag's commit as the one the project pins
+        run_git(".", &["add", &path])?;
+    }
+    log::status!("  ✓ Added submodule {}", path);
+    lockfile::record(name, url, tag, branch);
+    Ok(())
+}
+
+/// Check out the commit the project repository pins for external/<name>,
+/// registering the submodule first if this clone of the project lacks it
+pub fn sync(name: &str, url: &str, tag: Option<&str>, branch: Option<&str>) -> Result<(), String> {
+    let path = format!("external/{}", name);
+    if !is_registered(&path) {
+        if remote::is_offline() {
+            return Err(format!(
+                "Submodule {} is not registered, and --offline forbids cloning it",
+                name
+            ));
+        }
+        return add(name, url, tag, branch);
+    }
+
+    let mut cmd = Command::new("git");
+    cmd.args(["submodule", "update", "--init"]);
+    // The parent repository pins the commit; offline it must already be here
+    if remote::is_offline() {
+        cmd.arg("--no-fetch");
+    }
+    cmd.args(["--", &path]);
+    match stream::run(&mut cmd, secrets::redact) {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            return Err(format!(
+                "Failed to update submodule {}\n    {}",
+                name,
+                output.stderr.trim()
+            ));
+        }
+        Err(e) => return Err(format!("Error updating submodule {}: {}", name, e)),
+    }
+
+    let commit = lockfile::head_commit(&path).unwrap_or_default();
+    log::status!(
+        "  ✓ Checked out submodule {} at {}",
+        path,
+        short_hash(&commit)
+    );
+    lockfile::record(name, url, tag, branch);
+    Ok(())
+}
+
+/// Whether .gitmodules has an entry for `path`
+fn is_registered(path: &str) -> bool {
+    run_git(
+        ".",
+        &[
+            "config",
+            "-f",
+            ".gitmodules",
+            "--get-regexp",
+            r"^submodule\..*\.path$",
+        ],
+    )
+    .is_ok_and(|paths| {
+        paths
+            .lines()
+            .any(|line| line.split_once(' ').is_some_and(|(_, p)| p == path))
+    })
+}
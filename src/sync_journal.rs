@@ -0,0 +1,94 @@
+//! Resumable `c1 sync`.
+//!
+//! Each dependency that finishes syncing is appended to `.c1/sync-journal`
+//! together with its manifest entry and the commit it ended up at. A re-run
+//! after an interrupted sync skips those dependencies, but only after checking
+//! that the entry is unchanged and `external/<name>` still has that commit
+//! checked out, so a journal left behind by a killed process can never hide a
+//! missing or modified checkout. The journal is removed once a sync completes.
+//!
+//! Records are single appended lines, so concurrent writers for different
+//! dependencies never interleave within a record.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::{git_head, safe_path};
+
+const JOURNAL_DIR: &str = ".c1";
+const JOURNAL_PATH: &str = ".c1/sync-journal";
+
+/// Dependencies completed by an earlier, unfinished sync
+pub struct Journal {
+    completed: BTreeMap<String, Completed>,
+}
+
+struct Completed {
+    entry: String,
+    commit: String,
+}
+
+impl Journal {
+    /// Load the journal; a missing or unreadable one is simply empty
+    pub fn load() -> Journal {
+        let mut completed = BTreeMap::new();
+        if let Ok(content) = fs::read_to_string(JOURNAL_PATH) {
+            for line in content.lines() {
+                let mut fields = line.splitn(3, '\t');
+                let (Some(name), Some(commit), Some(entry)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    // A torn last line from a killed process
+                    continue;
+                };
+                completed.insert(
+                    name.to_string(),
+                    Completed {
+                        entry: entry.to_string(),
+                        commit: commit.to_string(),
+                    },
+                );
+            }
+        }
+        Journal { completed }
+    }
+
+    /// The commit recorded for `name` if it is still valid: same manifest
+    /// entry, same locked commit, and actually checked out on disk
+    pub fn verified(&self, name: &str, entry: &toml::Value, locked: Option<&str>) -> Option<&str> {
+        let done = self.completed.get(name)?;
+        if done.entry != fingerprint(entry) {
+            return None;
+        }
+        if locked.is_some_and(|locked| locked != done.commit) {
+            return None;
+        }
+        let actual = git_head::read_head(&Path::new("external").join(name))?;
+        (actual == done.commit).then_some(done.commit.as_str())
+    }
+}
+
+/// Append a completed dependency to the journal
+pub fn record(name: &str, entry: &toml::Value, commit: &str) -> io::Result<()> {
+    fs::create_dir_all(JOURNAL_DIR)?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(JOURNAL_PATH)?;
+    // One write per record keeps appends from different workers whole
+    file.write_all(format!("{}\t{}\t{}\n", name, commit, fingerprint(entry)).as_bytes())
+}
+
+/// Remove the journal after a complete sync
+pub fn clear() {
+    if Path::new(JOURNAL_PATH).exists() {
+        let _ = safe_path::remove_file(JOURNAL_PATH);
+    }
+}
+
+/// The manifest entry on a single line, to notice edits between runs
+fn fingerprint(entry: &toml::Value) -> String {
+    entry.to_string().replace('\n', " ")
+}
@@ -0,0 +1,306 @@
+//! Built-in project templates for `c1 new --template`.
+//!
+//! A template decides what differs between kinds of projects: the entry point,
+//! the CMakeLists.txt, the default compiler flags and any extra directories.
+//! Everything else (project.toml layout, README, .gitignore) is shared and
+//! written by `init_project_in_dir`. Adding a template means adding an entry
+//! to `TEMPLATES`.
+
+pub const DEFAULT_TEMPLATE: &str = "minimal";
+
+pub struct Template {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Default `[build] flags` written to project.toml
+    pub flags: &'static [&'static str],
+    /// Directories created in addition to the Pitchfork layout
+    pub extra_dirs: &'static [&'static str],
+    /// Whether project.toml declares `type = "lib"`
+    pub library: bool,
+    /// Files to write, as (relative path, content), for a project name and
+    /// CMAKE_C_STANDARD value
+    files: fn(&str, &str) -> Vec<(String, String)>,
+}
+
+impl Template {
+    /// The files to write, with main.c at `main_source` (`main.c` or
+    /// `src/main.c`)
+    pub fn files(
+        &self,
+        project_name: &str,
+        c_standard: &str,
+        main_source: &str,
+    ) -> Vec<(String, String)> {
+        let mut files = (self.files)(project_name, c_standard);
+        if main_source != "main.c" {
+            for (path, content) in &mut files {
+                if path == "main.c" {
+                    *path = main_source.to_string();
+                } else if path == "CMakeLists.txt" {
+                    *content = content
+                        .replace("    main.c\n", &format!("    {}\n", main_source))
+                        .replace(" main.c)", &format!(" {})", main_source));
+                }
+            }
+        }
+        files
+    }
+}
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        name: "minimal",
+        description: "Hello-world executable (default)",
+        flags: &["-O3", "-Wall"],
+        extra_dirs: &[],
+        library: false,
+        files: minimal_files,
+    },
+    Template {
+        name: "lib",
+        description: "Static library with a public header in include/ and an example program",
+        flags: &["-O2", "-Wall", "-Wextra"],
+        extra_dirs: &["examples"],
+        library: true,
+        files: lib_files,
+    },
+    Template {
+        name: "lib-demo",
+        description: "Static library plus a demo executable from main.c that links it",
+        flags: &["-O2", "-Wall", "-Wextra"],
+        extra_dirs: &[],
+        library: true,
+        files: lib_demo_files,
+    },
+    Template {
+        name: "embedded",
+        description: "Bare-metal style super loop, size-optimized with unused sections dropped",
+        flags: &["-Os", "-Wall", "-Wextra", "-ffunction-sections", "-fdata-sections"],
+        extra_dirs: &["linker"],
+        library: false,
+        files: embedded_files,
+    },
+];
+
+/// Look up a built-in template by name
+pub fn find(name: &str) -> Option<&'static Template> {
+    TEMPLATES.iter().find(|t| t.name == name)
+}
+
+pub fn names() -> String {
+    TEMPLATES.iter().map(|t| t.name).collect::<Vec<_>>().join(", ")
+}
+
+pub fn print_list() {
+    println!("Available templates:");
+    for template in TEMPLATES {
+        println!("  {:<10} {}", template.name, template.description);
+    }
+}
+
+fn minimal_files(project_name: &str, c_standard: &str) -> Vec<(String, String)> {
+    // c1_version.h is written by every build, see `[build] version_header`
+    let main_c = format!(
+        r#"#include <stdio.h>
+
+#include "c1_version.h"
+
+int main(void) {{
+    printf("Hello {} (version %s)\n", C1_PROJECT_VERSION);
+    return 0;
+}}
+"#,
+        project_name
+    );
+    vec![
+        ("main.c".to_string(), main_c),
+        ("CMakeLists.txt".to_string(), executable_cmake(project_name, c_standard, "")),
+    ]
+}
+
+fn lib_files(project_name: &str, c_standard: &str) -> Vec<(String, String)> {
+    let module = project_name.replace('-', "_");
+    // Run with `c1 run --example basic`; c1 registers it before building
+    let example = format!(
+        r#"#include <stdio.h>
+
+#include "{module}.h"
+
+int main(void) {{
+    printf("{module} version %d\n", {module}_version());
+    return 0;
+}}
+"#
+    );
+    let mut files = library_module(&module);
+    files.push(("examples/basic.c".to_string(), example));
+    files.push((
+        "CMakeLists.txt".to_string(),
+        library_cmake(project_name, c_standard, &module, ""),
+    ));
+    files
+}
+
+fn lib_demo_files(project_name: &str, c_standard: &str) -> Vec<(String, String)> {
+    let module = project_name.replace('-', "_");
+    let main_c = format!(
+        r#"#include <stdio.h>
+
+#include "{module}.h"
+
+int main(void) {{
+    printf("{module} version %d\n", {module}_version());
+    return 0;
+}}
+"#
+    );
+    // The demo keeps the project's name as its file name so `c1 run` finds it
+    let extra = r#"
+# Demo program from main.c, linked against the library
+add_executable(${PROJECT_NAME}_demo main.c)
+set_target_properties(${PROJECT_NAME}_demo PROPERTIES OUTPUT_NAME ${PROJECT_NAME})
+target_link_libraries(${PROJECT_NAME}_demo PRIVATE ${PROJECT_NAME})
+"#;
+    let mut files = library_module(&module);
+    files.push(("main.c".to_string(), main_c));
+    files.push((
+        "CMakeLists.txt".to_string(),
+        library_cmake(project_name, c_standard, &module, extra),
+    ));
+    files
+}
+
+/// The library's starting module: include/<module>.h and src/<module>.c
+fn library_module(module: &str) -> Vec<(String, String)> {
+    let guard = format!("_{}_H", module.to_uppercase());
+    let header = format!(
+        r#"#ifndef {guard}
+#define {guard}
+
+int {module}_version(void);
+
+#endif // {guard}
+"#
+    );
+    let source = format!(
+        r#"#include "{module}.h"
+
+int {module}_version(void) {{
+    return 1;
+}}
+"#
+    );
+    vec![
+        (format!("include/{}.h", module), header),
+        (format!("src/{}.c", module), source),
+    ]
+}
+
+/// The CMakeLists.txt for a static library named after the project, built
+/// from the sources `c1 create` registers, with `extra` appended
+fn library_cmake(project_name: &str, c_standard: &str, module: &str, extra: &str) -> String {
+    format!(
+        r#"cmake_minimum_required(VERSION 3.16)
+project({project_name} C)
+
+set(CMAKE_C_STANDARD {c_standard})
+set(CMAKE_C_STANDARD_REQUIRED ON)
+set(CMAKE_EXPORT_COMPILE_COMMANDS ON)
+
+# Source files list
+set(SOURCES
+    src/{module}.c
+    # @c1_sources
+)
+
+# Header files list
+set(HEADERS
+    include/{module}.h
+    # @c1_headers
+)
+
+add_library(${{PROJECT_NAME}} STATIC ${{SOURCES}} ${{HEADERS}})
+
+# Consumers of the library see the headers in include/
+target_include_directories(${{PROJECT_NAME}} PUBLIC
+    ${{CMAKE_CURRENT_SOURCE_DIR}}/include
+)
+
+# Warnings from [build] warnings in project.toml, for the project's own code
+target_compile_options(${{PROJECT_NAME}} PRIVATE ${{C1_WARNING_FLAGS}})
+
+# Dependencies in external/, wired up by `c1 add` and `c1 sync`
+# @c1_dependencies
+{extra}"#
+    )
+}
+
+fn embedded_files(project_name: &str, c_standard: &str) -> Vec<(String, String)> {
+    let main_c = r#"#include <stdint.h>
+
+static void system_init(void) {
+    // TODO: Clock, GPIO and peripheral setup
+}
+
+int main(void) {
+    system_init();
+
+    for (;;) {
+        // TODO: Main loop
+    }
+}
+"#;
+    let extra = r#"
+# Drop unused functions and data from the image
+target_compile_options(${PROJECT_NAME} PRIVATE -ffunction-sections -fdata-sections)
+target_link_options(${PROJECT_NAME} PRIVATE -Wl,--gc-sections)
+
+# Linker scripts live in linker/, e.g.:
+# target_link_options(${PROJECT_NAME} PRIVATE -T${CMAKE_CURRENT_SOURCE_DIR}/linker/mcu.ld)
+"#;
+    vec![
+        ("main.c".to_string(), main_c.to_string()),
+        ("CMakeLists.txt".to_string(), executable_cmake(project_name, c_standard, extra)),
+    ]
+}
+
+/// The CMakeLists.txt for an executable built from main.c, with `extra`
+/// appended
+fn executable_cmake(project_name: &str, c_standard: &str, extra: &str) -> String {
+    format!(
+        r#"cmake_minimum_required(VERSION 3.16)
+project({} C)
+
+set(CMAKE_C_STANDARD {})
+set(CMAKE_C_STANDARD_REQUIRED ON)
+set(CMAKE_EXPORT_COMPILE_COMMANDS ON)
+
+# Source files list
+set(SOURCES
+    main.c
+    # @c1_sources
+)
+
+# Header files list
+set(HEADERS
+    # @c1_headers
+)
+
+add_executable(${{PROJECT_NAME}} ${{SOURCES}} ${{HEADERS}})
+
+# Include directories: following Pitchfork convention, headers in include/
+target_include_directories(${{PROJECT_NAME}} PRIVATE
+    ${{CMAKE_CURRENT_SOURCE_DIR}}/include
+)
+
+# Warnings from [build] warnings in project.toml, for the project's own code
+target_compile_options(${{PROJECT_NAME}} PRIVATE ${{C1_WARNING_FLAGS}})
+
+# Dependencies in external/, wired up by `c1 add` and `c1 sync`
+# @c1_dependencies
+
+# System libraries: list them in project.toml, e.g. `links = ["m"]` under [build]
+{}"#,
+        project_name, c_standard, extra
+    )
+}
@@ -0,0 +1,372 @@
+//! `c1 test`: build the programs in tests/ and run them.
+//!
+//! Every `tests/<name>.c` is its own program with a `main`, registered in
+//! CMakeLists.txt as the target `<name>` and linked with the project's sources
+//! except main.c. A program passes when it exits with 0. Programs written with
+//! tests/support/c1_test.h, which `c1 test --init` generates, or with Unity
+//! also print a summary line, from which the cases inside them are counted.
+//!
+//! A pattern selects the programs whose names contain it; only those are
+//! compiled, not just run. `--coverage` measures what the programs executed,
+//! see `coverage`; `--valgrind` runs them under memcheck, see `valgrind`.
+//! `--format junit --output <file>` also writes the results as JUnit XML,
+//! see `junit`.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Instant, SystemTime};
+
+use crate::error::{self, Context, Error};
+use crate::{BuildOptions, coverage, events, junit, log, safe_path, stream, valgrind};
+
+const TEST_DIR: &str = "tests";
+const SUPPORT_HEADER: &str = "tests/support/c1_test.h";
+const SAMPLE_TEST: &str = "tests/test_example.c";
+
+pub fn cmd_test(
+    options: &BuildOptions,
+    pattern: Option<&str>,
+    list: bool,
+    init: bool,
+    fail_under: Option<f64>,
+    junit_report: Option<&str>,
+) -> error::Result<()> {
+    if init {
+        return init_framework();
+    }
+    let available = find_tests()?;
+    let tests: Vec<String> = available
+        .iter()
+        .filter(|test| pattern.is_none_or(|pattern| test.contains(pattern)))
+        .cloned()
+        .collect();
+    if tests.is_empty() {
+        return Err(
+            Error::usage(format!("no test matches '{}'", pattern.unwrap_or_default()))
+                .hint(format!("available tests: {}", available.join(", "))),
+        );
+    }
+    if list {
+        for test in &tests {
+            println!("{}", test);
+        }
+        return Ok(());
+    }
+    let cmake = fs::read_to_string("CMakeLists.txt").unwrap_or_default();
+    for test in &tests {
+        if !cmake.contains(&format!("add_executable({} ", test)) {
+            crate::register_test(test, &format!("{}/{}.c", TEST_DIR, test))?;
+        }
+    }
+
+    let options = BuildOptions {
+        targets: tests.clone(),
+        ..options.clone()
+    };
+    crate::cmd_build(&options)?;
+    let build_dir = crate::target_build_dir(&options);
+    if options.coverage {
+        coverage::reset(&build_dir)?;
+    }
+    let valgrind_args = if options.valgrind {
+        crate::read_config()?.run.valgrind_args
+    } else {
+        Vec::new()
+    };
+
+    log::status!("\nRunning {} test program(s)...", tests.len());
+    let mut failed = Vec::new();
+    let (mut cases_passed, mut cases_failed) = (0, 0);
+    let mut programs = Vec::new();
+    for test in &tests {
+        let executable = crate::executable_path(&build_dir, test, options.release)
+            .map_err(|_| Error::failure(format!("Executable for test '{}' not found", test)))?;
+        let (mut command, valgrind_log) = if options.valgrind {
+            let (command, log_file) = valgrind::command(&executable, &build_dir, &valgrind_args)?;
+            (command, Some(log_file))
+        } else {
+            (Command::new(&executable), None)
+        };
+        let (started, timer) = (SystemTime::now(), Instant::now());
+        let output = stream::run(&mut command, str::to_string)
+            .map_err(|e| Error::failure(format!("failed to run test '{}': {}", test, e)))?;
+        let duration = timer.elapsed();
+        let memcheck = valgrind_log.and_then(|log_file| valgrind::errors(output.status, &log_file));
+        let cases = summary(&output.stdout);
+        if let Some((passed, failed)) = cases {
+            cases_passed += passed;
+            cases_failed += failed;
+        }
+        let detail = match cases {
+            Some((passed, 0)) => format!(" ({} passed)", passed),
+            Some((passed, failed)) => format!(" ({} of {} failed)", failed, passed + failed),
+            None => String::new(),
+        };
+        let failure = if let Some(errors) = memcheck {
+            Some(format!("valgrind found {}", errors))
+        } else if output.status.success() {
+            None
+        } else {
+            Some(format!("exited with {}", output.status))
+        };
+        match &failure {
+            Some(failure) => {
+                log::problem!("✗ {}{}: {}", test, detail, failure);
+                failed.push(test.as_str());
+            }
+            None => log::status!("✓ {}{}", test, detail),
+        }
+        programs.push(junit::Program {
+            name: test.clone(),
+            started,
+            duration,
+            stdout: output.stdout,
+            stderr: output.stderr,
+            failure,
+            summary: cases,
+        });
+    }
+    if let Some(report) = junit_report {
+        let filtered: Vec<String> = available
+            .iter()
+            .filter(|test| !tests.contains(test))
+            .cloned()
+            .collect();
+        junit::write(Path::new(report), &programs, &filtered)?;
+        log::status!("✓ Wrote the JUnit report to {}", report);
+    }
+
+    let cases = if cases_passed + cases_failed > 0 {
+        format!("; {} case(s) passed, {} failed", cases_passed, cases_failed)
+    } else {
+        String::new()
+    };
+    if options.coverage {
+        log::status!("\nCoverage of {} test program(s):", tests.len());
+        let report_dir =
+            crate::resolve_build_dir(options.out_dir.as_deref()).join(coverage::SUBDIR);
+        let covered = coverage::report(&build_dir, &report_dir, fail_under);
+        // Failed tests are the bigger news
+        if failed.is_empty() {
+            covered?;
+        }
+    }
+    if !failed.is_empty() {
+        return Err(Error::failure(format!(
+            "{} of {} test program(s) failed{}: {}",
+            failed.len(),
+            tests.len(),
+            cases,
+            failed.join(", ")
+        )));
+    }
+    log::status!("\n✓ All {} test program(s) passed{}", tests.len(), cases);
+    Ok(())
+}
+
+/// The names of the .c files directly in tests/, sorted
+fn find_tests() -> error::Result<Vec<String>> {
+    let layout_hint = "run `c1 test --init` for a sample test, or `c1 create <module> --with-test`";
+    let Ok(entries) = fs::read_dir(TEST_DIR) else {
+        return Err(Error::usage("tests/ not found").hint(layout_hint));
+    };
+    let mut tests: Vec<String> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == "c"))
+        .filter_map(|p| Some(p.file_stem()?.to_string_lossy().into_owned()))
+        .collect();
+    if tests.is_empty() {
+        return Err(Error::usage("tests/ has no .c files").hint(layout_hint));
+    }
+    tests.sort();
+    Ok(tests)
+}
+
+/// Passed and failed case counts from the last summary line a test printed:
+/// `C1TEST: 3 passed, 1 failed` from c1_test.h, or Unity's
+/// `4 Tests 1 Failures 0 Ignored`
+fn summary(stdout: &str) -> Option<(u32, u32)> {
+    stdout.lines().rev().find_map(|line| {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["C1TEST:", passed, "passed,", failed, "failed"] => {
+                Some((passed.parse().ok()?, failed.parse().ok()?))
+            }
+            [total, "Tests", failed, "Failures", ignored, "Ignored"] => {
+                let (total, failed, ignored): (u32, u32, u32) = (
+                    total.parse().ok()?,
+                    failed.parse().ok()?,
+                    ignored.parse().ok()?,
+                );
+                Some((total.checked_sub(failed + ignored)?, failed))
+            }
+            _ => None,
+        }
+    })
+}
+
+/// `c1 test --init`: write the assertion header and a sample test using it
+fn init_framework() -> error::Result<()> {
+    if !Path::new("CMakeLists.txt").exists() {
+        return Err(Error::usage(
+            "CMakeLists.txt not found. Are you in a c1 project?",
+        ));
+    }
+    if Path::new(SUPPORT_HEADER).exists() {
+        log::status!("{} already exists, keeping it", SUPPORT_HEADER);
+    } else {
+        fs::create_dir_all("tests/support")
+            .context(|| "failed to create tests/support/".to_string())?;
+        safe_path::write(SUPPORT_HEADER, FRAMEWORK)
+            .context(|| format!("failed to write {}", SUPPORT_HEADER))?;
+        log::status!("✓ Created {}", SUPPORT_HEADER);
+        events::file_created(SUPPORT_HEADER);
+    }
+    wire_support_includes()?;
+    if Path::new(SAMPLE_TEST).exists() {
+        log::status!("{} already exists, keeping it", SAMPLE_TEST);
+    } else {
+        safe_path::write(SAMPLE_TEST, SAMPLE)
+            .context(|| format!("failed to write {}", SAMPLE_TEST))?;
+        log::status!("✓ Created {}", SAMPLE_TEST);
+        events::file_created(SAMPLE_TEST);
+        crate::register_test("test_example", SAMPLE_TEST)?;
+    }
+    log::status!("\nRun the tests with `c1 test`");
+    Ok(())
+}
+
+/// Give the test targets registered before `--init` tests/support as an
+/// include directory; later ones get it from `register_test`
+fn wire_support_includes() -> error::Result<()> {
+    let cmake_path = "CMakeLists.txt";
+    let content =
+        fs::read_to_string(cmake_path).context(|| format!("failed to read {}", cmake_path))?;
+    let Some(start) = content.find("enable_testing()") else {
+        return Ok(());
+    };
+    let (head, tests) = content.split_at(start);
+    let include = "PRIVATE ${CMAKE_CURRENT_SOURCE_DIR}/include";
+    let wired = tests.replace(
+        &format!("{})", include),
+        &format!("{} {})", include, crate::TEST_SUPPORT_INCLUDE),
+    );
+    if wired == tests {
+        return Ok(());
+    }
+    safe_path::write(cmake_path, format!("{}{}", head, wired))
+        .context(|| format!("failed to update {}", cmake_path))?;
+    log::status!("✓ Added tests/support to the test include paths in CMakeLists.txt");
+    Ok(())
+}
+
+const FRAMEWORK: &str = r#"/*
+ * c1_test.h - a minimal unit test framework, generated by `c1 test --init`.
+ *
+ *     static void test_add(void) {
+ *         CHECK(add(1, 2) == 3);
+ *         CHECK_EQ_INT(add(2, 2), 4);
+ *     }
+ *
+ *     int main(void) {
+ *         RUN_TEST(test_add);
+ *         return TEST_SUMMARY();
+ *     }
+ *
+ * A failed check reports its location and the test goes on. RUN_TEST prints
+ * PASS or FAIL per test, and TEST_SUMMARY the line `c1 test` counts cases
+ * from, returning the program's exit status.
+ */
+#ifndef C1_TEST_H
+#define C1_TEST_H
+
+#include <stdio.h>
+#include <string.h>
+
+#if defined(__GNUC__) || defined(__clang__)
+#define C1_TEST_UNUSED __attribute__((unused))
+#else
+#define C1_TEST_UNUSED
+#endif
+
+static int c1_test_failed_checks C1_TEST_UNUSED;
+static int c1_test_passed C1_TEST_UNUSED;
+static int c1_test_failed C1_TEST_UNUSED;
+
+#define C1_TEST_FAIL(...)                                   \
+    do {                                                    \
+        printf("  %s:%d: ", __FILE__, __LINE__);            \
+        printf(__VA_ARGS__);                                \
+        printf("\n");                                       \
+        c1_test_failed_checks++;                            \
+    } while (0)
+
+#define CHECK(expr)                                         \
+    do {                                                    \
+        if (!(expr)) {                                      \
+            C1_TEST_FAIL("CHECK(%s) failed", #expr);        \
+        }                                                   \
+    } while (0)
+
+#define CHECK_EQ_INT(actual, expected)                      \
+    do {                                                    \
+        long long c1_a = (long long)(actual);               \
+        long long c1_e = (long long)(expected);             \
+        if (c1_a != c1_e) {                                 \
+            C1_TEST_FAIL("%s == %lld, expected %lld",       \
+                         #actual, c1_a, c1_e);              \
+        }                                                   \
+    } while (0)
+
+#define CHECK_EQ_STR(actual, expected)                      \
+    do {                                                    \
+        const char *c1_a = (actual);                        \
+        const char *c1_e = (expected);                      \
+        if (c1_a == NULL || c1_e == NULL                    \
+            || strcmp(c1_a, c1_e) != 0) {                   \
+            C1_TEST_FAIL("%s == \"%s\", expected \"%s\"",   \
+                         #actual, c1_a ? c1_a : "(null)",   \
+                         c1_e ? c1_e : "(null)");           \
+        }                                                   \
+    } while (0)
+
+#define RUN_TEST(fn)                                        \
+    do {                                                    \
+        int c1_before = c1_test_failed_checks;              \
+        fn();                                               \
+        if (c1_test_failed_checks == c1_before) {           \
+            printf("PASS %s\n", #fn);                       \
+            c1_test_passed++;                               \
+        } else {                                            \
+            printf("FAIL %s\n", #fn);                       \
+            c1_test_failed++;                               \
+        }                                                   \
+    } while (0)
+
+#define TEST_SUMMARY()                                      \
+    (printf("C1TEST: %d passed, %d failed\n",               \
+            c1_test_passed, c1_test_failed),                \
+     c1_test_failed == 0 ? 0 : 1)
+
+#endif // C1_TEST_H
+"#;
+
+const SAMPLE: &str = r#"#include "c1_test.h"
+
+static void test_arithmetic(void) {
+    CHECK(1 + 1 == 2);
+    CHECK_EQ_INT(2 * 3, 6);
+}
+
+static void test_strings(void) {
+    CHECK_EQ_STR("c1", "c1");
+}
+
+int main(void) {
+    RUN_TEST(test_arithmetic);
+    RUN_TEST(test_strings);
+    return TEST_SUMMARY();
+}
+"#;
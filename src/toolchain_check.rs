@@ -0,0 +1,300 @@
+//! Minimum cmake and compiler versions, checked before cmake configures.
+//!
+//! An old cmake fails the generated CMakeLists.txt with a wall of policy
+//! errors, and an old compiler rejects the warning presets' flags one by
+//! one. Before configuring, `c1 build` asks `cmake --version` and the
+//! compiler's `--version` instead and stops with one message naming both
+//! versions. cmake must be at least what `cmake_minimum_required` in
+//! CMakeLists.txt asks for (3.16 without one); gcc at least 7 and clang at
+//! least 6. `[build] min_cmake` and `min_compiler` in project.toml replace
+//! those. A version c1 cannot find or read is left for cmake to judge.
+//!
+//! What `--version` printed is cached in `~/.cache/c1/toolchain`, keyed by
+//! the binary's path and modification time, so only a new or upgraded tool
+//! is run again.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::UNIX_EPOCH;
+
+use crate::error::{self, Error};
+use crate::{cross, log, remote, safe_path};
+
+/// What the generated CMakeLists.txt asks for
+const DEFAULT_CMAKE: &str = "3.16";
+/// The oldest compilers the warning presets know the flags of
+const DEFAULT_GCC: &str = "7";
+const DEFAULT_CLANG: &str = "6";
+
+/// A tool's version and, for compilers, which compiler it is
+struct Probed {
+    version: String,
+    family: &'static str,
+}
+
+/// Fail when cmake or `compiler` is older than the project needs; `None`
+/// when a toolchain file chooses the compiler
+pub fn check(
+    compiler: Option<&str>,
+    min_cmake: Option<&str>,
+    min_compiler: Option<&str>,
+) -> error::Result<()> {
+    let required = min_cmake
+        .map(str::to_string)
+        .or_else(declared_cmake)
+        .unwrap_or_else(|| DEFAULT_CMAKE.to_string());
+    if let Some((path, cmake)) = probe("cmake") {
+        log::verbose!("cmake {} ({})", cmake.version, path.display());
+        if is_older(&cmake.version, &required) {
+            return Err(Error::config(format!(
+                "cmake {} is too old: this project needs {} or newer ({})",
+                cmake.version,
+                required,
+                path.display()
+            ))
+            .hint(format!(
+                "{}; [build] min_cmake in project.toml overrides the requirement",
+                cmake_hint()
+            )));
+        }
+    }
+
+    let Some((path, probed)) = compiler.and_then(probe) else {
+        return Ok(());
+    };
+    let required = match (min_compiler, probed.family) {
+        (Some(required), _) => required,
+        (None, "gcc") => DEFAULT_GCC,
+        (None, "clang") => DEFAULT_CLANG,
+        (None, _) => return Ok(()),
+    };
+    log::verbose!("{} {} ({})", probed.family, probed.version, path.display());
+    if is_older(&probed.version, required) {
+        return Err(Error::config(format!(
+            "{} {} is too old: this project needs {} or newer ({})",
+            probed.family,
+            probed.version,
+            required,
+            path.display()
+        ))
+        .hint(format!(
+            "{}; [build] min_compiler in project.toml overrides the requirement",
+            compiler_hint()
+        )));
+    }
+    Ok(())
+}
+
+fn is_older(version: &str, required: &str) -> bool {
+    remote::version_cmp(version, required.trim()).is_lt()
+}
+
+/// The version `cmake_minimum_required` in CMakeLists.txt names
+fn declared_cmake() -> Option<String> {
+    minimum_required(&fs::read_to_string("CMakeLists.txt").ok()?)
+}
+
+/// The version in the `cmake_minimum_required(VERSION ...)` call of
+/// `content`, whose command and keyword are case-insensitive
+fn minimum_required(content: &str) -> Option<String> {
+    let lower = content.to_ascii_lowercase();
+    let call = &lower[lower.find("cmake_minimum_required")?..];
+    let call = &call[..call.find(')')?];
+    let rest = &call[call.find("version")? + "version".len()..];
+    // `VERSION 3.16...3.28` asks for the first
+    let version: String = rest
+        .trim_start()
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let version = version.split("..").next()?.trim_end_matches('.');
+    (!version.is_empty()).then(|| version.to_string())
+}
+
+/// The version of `program`, a name on PATH or a path, from the cache or
+/// from running it with `--version`
+fn probe(program: &str) -> Option<(PathBuf, Probed)> {
+    let path = if Path::new(program).components().count() > 1 {
+        PathBuf::from(program)
+    } else {
+        cross::find_on_path(program)?
+    };
+    let modified = fs::metadata(&path)
+        .and_then(|m| m.modified())
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos();
+    let key = format!("{}\t{}", path.display(), modified);
+    let cache = remote::cache_dir().map(|dir| dir.join("toolchain"));
+    let cached = cache
+        .as_ref()
+        .and_then(|cache| fs::read_to_string(cache).ok())
+        .and_then(|content| {
+            content.lines().find_map(|line| {
+                let rest = line.strip_prefix(&key)?.strip_prefix('\t')?;
+                let (version, family) = rest.split_once('\t')?;
+                Some(Probed {
+                    version: version.to_string(),
+                    family: family_name(family),
+                })
+            })
+        });
+    if let Some(probed) = cached {
+        return Some((path, probed));
+    }
+
+    let output = Command::new(&path).arg("--version").output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let probed = Probed {
+        version: parse_version(text.lines().next()?)?,
+        family: family(&text),
+    };
+    if let Some(cache) = cache {
+        remember(&cache, &key, &probed);
+    }
+    Some((path, probed))
+}
+
+/// Replace the cache's line for `key`, dropping lines for other versions of
+/// the same binary
+fn remember(cache: &Path, key: &str, probed: &Probed) {
+    let path = key.split('\t').next().unwrap_or(key);
+    let mut lines: Vec<String> = fs::read_to_string(cache)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| line.split('\t').next() != Some(path))
+        .map(str::to_string)
+        .collect();
+    lines.push(format!("{}\t{}\t{}", key, probed.version, probed.family));
+    let Some(dir) = cache.parent() else {
+        return;
+    };
+    let _ = fs::create_dir_all(dir).and_then(|_| safe_path::write(cache, lines.join("\n") + "\n"));
+}
+
+/// The first word that starts with a digit and has a dot, without what
+/// follows the digits: `gcc (Ubuntu 13.2.0-4ubuntu3) 13.2.0` gives 13.2.0
+fn parse_version(line: &str) -> Option<String> {
+    line.split_whitespace().find_map(|word| {
+        let version: String = word
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == '.')
+            .collect();
+        let version = version.trim_end_matches('.');
+        (version.contains('.') && version.starts_with(|c: char| c.is_ascii_digit()))
+            .then(|| version.to_string())
+    })
+}
+
+/// Which compiler `--version` printed; cmake is "other"
+fn family(text: &str) -> &'static str {
+    let text = text.to_ascii_lowercase();
+    if text.contains("clang") {
+        "clang"
+    } else if text.contains("gcc") || text.contains("free software foundation") {
+        "gcc"
+    } else {
+        "other"
+    }
+}
+
+fn family_name(name: &str) -> &'static str {
+    match name {
+        "gcc" => "gcc",
+        "clang" => "clang",
+        _ => "other",
+    }
+}
+
+fn cmake_hint() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "upgrade it with `brew upgrade cmake` or from https://cmake.org/download/"
+    } else if cfg!(windows) {
+        "upgrade it with `winget upgrade Kitware.CMake` or from https://cmake.org/download/"
+    } else {
+        "`pip install --user cmake` or a release from https://cmake.org/download/ gives a \
+         current cmake on any distribution"
+    }
+}
+
+fn compiler_hint() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "update the Xcode command line tools (`xcode-select --install`) or `brew install llvm`"
+    } else if cfg!(windows) {
+        "update MSYS2 (`pacman -Syu`) or Visual Studio"
+    } else {
+        "install a newer gcc or clang from your distribution, which often packages several \
+         (gcc-13, clang-17), and select it with --compiler or [build] compiler"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_version_of_a_version_line() {
+        for (line, version) in [
+            ("gcc (Ubuntu 13.2.0-4ubuntu3) 13.2.0", "13.2.0"),
+            ("gcc-12 (GCC) 12.2.1 20230201", "12.2.1"),
+            ("Apple clang version 15.0.0 (clang-1500.1.0.2.5)", "15.0.0"),
+            ("Ubuntu clang version 18.1.3 (1ubuntu1)", "18.1.3"),
+            ("cmake version 3.28.3", "3.28.3"),
+            ("cmake version 3.31.0-rc2", "3.31.0"),
+        ] {
+            assert_eq!(parse_version(line).as_deref(), Some(version), "{}", line);
+        }
+        assert_eq!(
+            parse_version("tcc version 0.9.27."),
+            Some("0.9.27".to_string())
+        );
+        assert_eq!(parse_version("cc version 12"), None);
+        assert_eq!(parse_version(""), None);
+    }
+
+    #[test]
+    fn tells_gcc_from_clang() {
+        assert_eq!(family("gcc (GCC) 13.2.0\nCopyright (C) 2023"), "gcc");
+        // Debian's cc is gcc, which only its copyright line says
+        assert_eq!(
+            family(
+                "cc (Debian 12.2.0-14) 12.2.0\nCopyright (C) 2022 Free Software Foundation, Inc."
+            ),
+            "gcc"
+        );
+        assert_eq!(family("Apple clang version 15.0.0"), "clang");
+        assert_eq!(family("Ubuntu Clang version 18.1.3"), "clang");
+        assert_eq!(family("cmake version 3.28.3"), "other");
+    }
+
+    #[test]
+    fn reads_the_declared_cmake_minimum() {
+        assert_eq!(
+            minimum_required("cmake_minimum_required(VERSION 3.16)\nproject(app C)\n").as_deref(),
+            Some("3.16")
+        );
+        // A range asks for its lower end
+        assert_eq!(
+            minimum_required("cmake_minimum_required(VERSION 3.16...3.28)").as_deref(),
+            Some("3.16")
+        );
+        assert_eq!(
+            minimum_required("CMAKE_MINIMUM_REQUIRED(\n  VERSION 3.20\n  FATAL_ERROR\n)")
+                .as_deref(),
+            Some("3.20")
+        );
+    }
+
+    #[test]
+    fn reads_a_lower_case_version_keyword_from_its_own_call() {
+        let content =
+            "cmake_minimum_required(version 3.20)\nproject(app VERSION 0.1.0 LANGUAGES C)\n";
+        assert_eq!(minimum_required(content).as_deref(), Some("3.20"));
+        // Without a version of its own, the call does not borrow project()'s
+        let content = "cmake_minimum_required()\nproject(app VERSION 0.1.0 LANGUAGES C)\n";
+        assert_eq!(minimum_required(content), None);
+        assert_eq!(minimum_required("project(app VERSION 0.1.0)\n"), None);
+    }
+}
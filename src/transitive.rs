@@ -0,0 +1,199 @@
+//! Dependencies of dependencies.
+//!
+//! A dependency that is itself a c1 project declares its own
+//! `[dependencies]` in `external/<name>/project.toml`. After it is fetched,
+//! its git dependencies join the set to sync, recursively, and land flat in
+//! the root's external/. Each package is fetched once: a second requirement
+//! for the same name must agree on the URL and ref, otherwise sync stops and
+//! shows the chain of packages behind each requirement. Conflicts among the
+//! packages already in external/ are found before anything is fetched.
+//! `--allow-override` lets the root manifest win over nested ones. A
+//! dependency's `[dev-dependencies]` are its own business and never fetched.
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::error::{self, Error};
+use crate::{log, subdir};
+
+#[derive(Clone)]
+struct Requirement {
+    value: toml::Value,
+    /// Who asked for it: `project.toml`, then each package down to this one
+    chain: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct Resolver {
+    requirements: BTreeMap<String, Requirement>,
+    /// Resolve conflicts with a direct dependency in its favour
+    allow_override: bool,
+}
+
+impl Resolver {
+    /// Start from the dependencies the project declares directly
+    pub fn new(direct: &toml::Table, allow_override: bool) -> Resolver {
+        let requirements = direct
+            .iter()
+            .map(|(name, value)| {
+                let requirement = Requirement {
+                    value: value.clone(),
+                    chain: vec!["project.toml".to_string(), name.clone()],
+                };
+                (name.clone(), requirement)
+            })
+            .collect();
+        Resolver {
+            requirements,
+            allow_override,
+        }
+    }
+
+    /// Walk the packages already in external/ and fail on any conflict among
+    /// them, without touching the network
+    pub fn check(&self) -> error::Result<()> {
+        let mut preview = self.clone();
+        let mut pending: Vec<String> = preview.requirements.keys().cloned().collect();
+        while let Some(name) = pending.pop() {
+            pending.extend(
+                preview
+                    .record(&name, false)?
+                    .into_iter()
+                    .map(|(name, _)| name),
+            );
+        }
+        Ok(())
+    }
+
+    /// Record what the packages already in external/ under `names` require,
+    /// recursively, without syncing anything: `c1 sync --no-dev` keeps the
+    /// dev-dependencies it skips, and what they pulled in, out of the prune
+    /// list and in project.lock
+    pub fn record_present(&mut self, names: &[String]) -> error::Result<()> {
+        let mut pending = names.to_vec();
+        while let Some(name) = pending.pop() {
+            pending.extend(self.record(&name, false)?.into_iter().map(|(name, _)| name));
+        }
+        Ok(())
+    }
+
+    /// Record what the c1 project in external/<parent> declares, returning
+    /// the packages not required before, which still need to be synced
+    pub fn expand(&mut self, parent: &str) -> error::Result<Vec<(String, toml::Value)>> {
+        self.record(parent, true)
+    }
+
+    fn record(&mut self, parent: &str, verbose: bool) -> error::Result<Vec<(String, toml::Value)>> {
+        let manifest = subdir::root(parent).join("project.toml");
+        let Some(declared) = fs::read_to_string(&manifest)
+            .ok()
+            .and_then(|content| content.parse::<toml::Table>().ok())
+            .and_then(|table| table.get("dependencies")?.as_table().cloned())
+        else {
+            return Ok(Vec::new());
+        };
+        let parent_chain = self
+            .requirements
+            .get(parent)
+            .map(|r| r.chain.clone())
+            .unwrap_or_else(|| vec![parent.to_string()]);
+
+        let mut added = Vec::new();
+        for (name, value) in declared {
+            if value.get("git").is_none() {
+                if !verbose {
+                    continue;
+                }
+                match crate::pkg_config::package(&value) {
+                    Some(package) => log::status!(
+                        "  Note: {} needs the system package {} (pkg-config), which is not fetched",
+                        parent, package
+                    ),
+                    None => log::status!(
+                        "  Note: {} depends on {} by path, which is not fetched",
+                        parent, name
+                    ),
+                }
+                continue;
+            }
+            let mut chain = parent_chain.clone();
+            chain.push(name.clone());
+            match self.requirements.get(&name) {
+                Some(existing) if compatible(&existing.value, &value) => {}
+                Some(existing) if self.allow_override && existing.chain.len() == 2 => {
+                    if verbose {
+                        log::problem!(
+                            "Warning: {} wants {}, using {} from project.toml instead",
+                            chain.join(" -> "),
+                            describe(&value),
+                            describe(&existing.value)
+                        );
+                    }
+                }
+                Some(existing) => {
+                    return Err(Error::config(format!(
+                        "conflicting requirements for {}:\n  {} wants {}\n  {} wants {}",
+                        name,
+                        existing.chain.join(" -> "),
+                        describe(&existing.value),
+                        chain.join(" -> "),
+                        describe(&value)
+                    ))
+                    .hint(
+                        "make both requirements use the same repository and a compatible tag/branch/rev, or pass --allow-override to use project.toml's",
+                    ));
+                }
+                None => {
+                    added.push((name.clone(), value.clone()));
+                    self.requirements.insert(name, Requirement { value, chain });
+                }
+            }
+        }
+        Ok(added)
+    }
+
+    /// The package that first required `name`, unless it is a direct dependency
+    pub fn required_by(&self, name: &str) -> Option<&str> {
+        let chain = &self.requirements.get(name)?.chain;
+        (chain.len() > 2).then(|| chain[chain.len() - 2].as_str())
+    }
+
+    /// Every package required directly or transitively
+    pub fn all(&self) -> toml::Table {
+        self.requirements
+            .iter()
+            .map(|(name, r)| (name.clone(), r.value.clone()))
+            .collect()
+    }
+}
+
+/// Same repository, and the same tag/branch/rev unless one side leaves the
+/// ref open
+fn compatible(a: &toml::Value, b: &toml::Value) -> bool {
+    let url = |v: &toml::Value| {
+        v.get("git")
+            .and_then(|g| g.as_str())
+            .map(|g| g.trim_end_matches('/').trim_end_matches(".git").to_string())
+    };
+    let reference = |v: &toml::Value| {
+        ["tag", "branch", "rev"].map(|key| v.get(key).and_then(|r| r.as_str()).map(str::to_string))
+    };
+    let open = |r: &[Option<String>; 3]| r.iter().all(Option::is_none);
+    let (ra, rb) = (reference(a), reference(b));
+    url(a) == url(b) && (ra == rb || open(&ra) || open(&rb))
+}
+
+/// `https://... tag v1`
+fn describe(value: &toml::Value) -> String {
+    let mut text = value
+        .get("git")
+        .and_then(|g| g.as_str())
+        .unwrap_or("?")
+        .to_string();
+    for key in ["tag", "branch", "rev"] {
+        if let Some(r) = value.get(key).and_then(|r| r.as_str()) {
+            text.push_str(&format!(" {} {}", key, r));
+        }
+    }
+    text
+}
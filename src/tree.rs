@@ -0,0 +1,193 @@
+//! `c1 tree`: the dependency graph as an indented tree.
+//!
+//! The root's `[dependencies]` and `[dev-dependencies]`, marked `(dev)`, are
+//! its children; a dependency that is itself a c1 project contributes the
+//! dependencies in `external/<name>/project.toml`, but not its dev ones.
+//! All dependencies live flat in the root's external/, so a package is one
+//! node however many times it is required. A package already expanded
+//! elsewhere is printed with `(*)`, and a cycle is cut and reported.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use crate::error::{self, Error};
+use crate::info::{self, Dependency};
+use crate::log;
+
+pub fn cmd_tree(depth: Option<usize>, invert: Option<&str>) -> error::Result<()> {
+    let config = crate::read_config()?;
+    let root = config.project.name.clone();
+    let mut graph = Graph {
+        root: root.clone(),
+        root_dependencies: info::declared(&config.all_dependencies()),
+        dev: config
+            .dev_dependencies
+            .keys()
+            .filter(|name| !config.dependencies.contains_key(*name))
+            .cloned()
+            .collect(),
+        cycles: Vec::new(),
+    };
+
+    match invert {
+        Some(name) => {
+            let parents = graph.parents();
+            if name != root && !parents.contains_key(name) {
+                return Err(Error::usage(format!(
+                    "'{}' is not in the dependency tree of {}",
+                    name, root
+                )));
+            }
+            println!("{}", name);
+            print_inverted(name, &parents, depth, "", &mut vec![name.to_string()]);
+        }
+        None => {
+            println!("{} v{}", root, config.project.version);
+            let mut expanded = BTreeSet::new();
+            let mut stack = vec![root.clone()];
+            graph.print(&root, depth, "", &mut stack, &mut expanded);
+        }
+    }
+
+    for cycle in &graph.cycles {
+        log::problem!("Warning: dependency cycle: {}", cycle.join(" -> "));
+    }
+    Ok(())
+}
+
+/// The dependencies `external/<name>/project.toml` declares; none when the
+/// dependency is not a c1 project
+pub fn declared_by(name: &str) -> Vec<Dependency> {
+    let manifest = Path::new("external").join(name).join("project.toml");
+    fs::read_to_string(manifest)
+        .ok()
+        .and_then(|content| content.parse::<toml::Table>().ok())
+        .and_then(|table| table.get("dependencies")?.as_table().cloned())
+        .map(|dependencies| info::declared(&dependencies))
+        .unwrap_or_default()
+}
+
+struct Graph {
+    root: String,
+    root_dependencies: Vec<Dependency>,
+    /// The root's dev-dependencies
+    dev: BTreeSet<String>,
+    /// Cycles found while walking, each as the chain of names closing it
+    cycles: Vec<Vec<String>>,
+}
+
+impl Graph {
+    /// The dependencies a package declares; none when it has no project.toml
+    fn children(&self, name: &str) -> Vec<Dependency> {
+        if name == self.root {
+            return self.root_dependencies.clone();
+        }
+        declared_by(name)
+    }
+
+    fn print(
+        &mut self,
+        name: &str,
+        depth: Option<usize>,
+        prefix: &str,
+        stack: &mut Vec<String>,
+        expanded: &mut BTreeSet<String>,
+    ) {
+        if depth.is_some_and(|depth| stack.len() > depth) {
+            return;
+        }
+        let children = self.children(name);
+        for (i, child) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            let mut state = if child.present { "" } else { " (not synced)" }.to_string();
+            if name == self.root && self.dev.contains(&child.name) {
+                state.push_str(" (dev)");
+            }
+            let label = format!("{} ({}){}", child.name, info::source(child), state);
+
+            if let Some(start) = stack.iter().position(|n| *n == child.name) {
+                println!("{}{}{} (cycle)", prefix, branch, label);
+                let mut cycle = stack[start..].to_vec();
+                cycle.push(child.name.clone());
+                self.cycles.push(cycle);
+            } else if !expanded.insert(child.name.clone()) {
+                println!("{}{}{} (*)", prefix, branch, label);
+            } else {
+                println!("{}{}{}", prefix, branch, label);
+                stack.push(child.name.clone());
+                self.print(
+                    &child.name,
+                    depth,
+                    &format!("{}{}", prefix, indent),
+                    stack,
+                    expanded,
+                );
+                stack.pop();
+            }
+        }
+    }
+
+    /// For every package, the packages that depend on it
+    fn parents(&self) -> BTreeMap<String, BTreeSet<String>> {
+        let mut parents: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let mut pending = vec![self.root.clone()];
+        let mut seen = BTreeSet::new();
+        while let Some(name) = pending.pop() {
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            for child in self.children(&name) {
+                parents
+                    .entry(child.name.clone())
+                    .or_default()
+                    .insert(name.clone());
+                pending.push(child.name);
+            }
+        }
+        parents
+    }
+}
+
+/// Print who depends on `name`, up to the root
+fn print_inverted(
+    name: &str,
+    parents: &BTreeMap<String, BTreeSet<String>>,
+    depth: Option<usize>,
+    prefix: &str,
+    stack: &mut Vec<String>,
+) {
+    if depth.is_some_and(|depth| stack.len() > depth) {
+        return;
+    }
+    let Some(dependents) = parents.get(name) else {
+        return;
+    };
+    for (i, parent) in dependents.iter().enumerate() {
+        let last = i + 1 == dependents.len();
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        if stack.contains(parent) {
+            println!("{}{}{} (cycle)", prefix, branch, parent);
+            continue;
+        }
+        println!("{}{}{}", prefix, branch, parent);
+        stack.push(parent.clone());
+        print_inverted(
+            parent,
+            parents,
+            depth,
+            &format!("{}{}", prefix, indent),
+            stack,
+        );
+        stack.pop();
+    }
+}
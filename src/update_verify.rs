@@ -0,0 +1,375 @@
+//! `c1 update --verify`: only keep upgrades that still build.
+//!
+//! Each dependency is moved on its own and the project is rebuilt (and its
+//! tests run with `--verify=test`). A dependency that breaks the build is
+//! rolled back to the commit it was at before, and its manifest entry is
+//! restored, so project.lock never records it; the captured output is shown
+//! in the final report. `--bisect` walks the upstream commits between the old
+//! and new revision with the same check to name the first bad one.
+//!
+//! Before a dependency is touched, its previous commit, branch and manifest
+//! entry are written to `.c1/update-rollback`. The entry is removed once the
+//! upgrade is verified or rolled back, so anything still listed there belongs
+//! to an interrupted run and is restored by the next `c1 update`.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::{self, Error};
+use crate::log;
+use crate::safe_path;
+
+const ROLLBACK_DIR: &str = ".c1";
+const ROLLBACK_PATH: &str = ".c1/update-rollback";
+/// Lines of build or test output kept for each held-back dependency
+const OUTPUT_LINES: usize = 30;
+
+#[derive(Clone, Copy)]
+pub enum Oracle {
+    Build,
+    Test,
+}
+
+impl Oracle {
+    pub fn parse(value: &str) -> Option<Oracle> {
+        match value {
+            "build" => Some(Oracle::Build),
+            "test" => Some(Oracle::Test),
+            _ => None,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Oracle::Build => "c1 build",
+            Oracle::Test => "c1 build + ctest",
+        }
+    }
+}
+
+/// A dependency's state before the update, enough to put it back
+struct Saved {
+    name: String,
+    commit: String,
+    /// Branch checked out at the time, or empty for a detached HEAD
+    branch: String,
+    entry: String,
+}
+
+/// What happened to each dependency that had upstream changes
+#[derive(Default)]
+pub struct Summary {
+    upgraded: Vec<(String, String, String)>,
+    held_back: Vec<HeldBack>,
+}
+
+struct HeldBack {
+    name: String,
+    kept: String,
+    rejected: String,
+    output: String,
+    first_bad: Option<String>,
+}
+
+impl Summary {
+    pub fn print(&self) {
+        if self.upgraded.is_empty() && self.held_back.is_empty() {
+            return;
+        }
+        log::status!("\nVerified update summary:");
+        for (name, old, new) in &self.upgraded {
+            log::status!(
+                "  ✓ {} upgraded {} → {}",
+                name,
+                crate::short_hash(old),
+                crate::short_hash(new)
+            );
+        }
+        for held in &self.held_back {
+            log::problem!(
+                "  ✗ {} held back at {} ({} failed)",
+                held.name,
+                crate::short_hash(&held.kept),
+                crate::short_hash(&held.rejected)
+            );
+            if let Some(first_bad) = &held.first_bad {
+                log::status!("    first bad upstream commit: {}", first_bad);
+            }
+            for line in held.output.lines() {
+                log::status!("    | {}", line);
+            }
+        }
+    }
+
+    pub fn has_held_back(&self) -> bool {
+        !self.held_back.is_empty()
+    }
+}
+
+/// Remember how to undo the update of `name`, before touching its checkout
+pub fn begin(name: &str, commit: &str, table: &toml::Table) -> Result<(), String> {
+    let dir = format!("external/{}", name);
+    let branch = crate::run_git(&dir, &["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .unwrap_or_default();
+    let mut saved = load();
+    saved.retain(|s| s.name != name);
+    saved.push(Saved {
+        name: name.to_string(),
+        commit: commit.to_string(),
+        branch,
+        entry: inline_entry(table),
+    });
+    store(&saved).map_err(|e| format!("failed to write {}: {}", ROLLBACK_PATH, e))
+}
+
+/// The update of `name` is settled, drop its rollback record
+pub fn finish(name: &str) {
+    let mut saved = load();
+    saved.retain(|s| s.name != name);
+    if let Err(e) = store(&saved) {
+        log::problem!("Warning: failed to write {}: {}", ROLLBACK_PATH, e);
+    }
+}
+
+/// Restore `name` to its recorded commit and manifest entry
+pub fn rollback(name: &str) -> Result<(), String> {
+    let Some(saved) = load().into_iter().find(|s| s.name == name) else {
+        return Ok(());
+    };
+    restore(&saved)?;
+    finish(name);
+    Ok(())
+}
+
+/// Undo upgrades left half-verified by an interrupted `c1 update --verify`
+pub fn recover() -> error::Result<()> {
+    for saved in load() {
+        log::status!(
+            "Restoring {} to {} after an interrupted verified update...",
+            saved.name,
+            crate::short_hash(&saved.commit)
+        );
+        if let Err(e) = restore(&saved) {
+            return Err(
+                Error::failure(format!("failed to restore {}: {}", saved.name, e)).hint(format!(
+                    "fix the checkout by hand, then remove {}",
+                    ROLLBACK_PATH
+                )),
+            );
+        }
+        finish(&saved.name);
+    }
+    Ok(())
+}
+
+/// Check the freshly updated `name` with `oracle`, rolling it back when the
+/// check fails. Returns whether the upgrade was kept.
+pub fn verify(
+    name: &str,
+    old: &str,
+    new: &str,
+    oracle: Oracle,
+    bisect: bool,
+    summary: &mut Summary,
+) -> error::Result<bool> {
+    log::status!("  Verifying {} with {}...", name, oracle.describe());
+    let output = match check(oracle) {
+        Ok(()) => {
+            log::status!("  ✓ {} passes", name);
+            finish(name);
+            summary
+                .upgraded
+                .push((name.to_string(), old.to_string(), new.to_string()));
+            return Ok(true);
+        }
+        Err(output) => output,
+    };
+    log::problem!("  ✗ {} fails {}", crate::short_hash(new), oracle.describe());
+
+    let first_bad = if bisect {
+        bisect_commits(name, old, new, oracle)
+    } else {
+        None
+    };
+
+    match rollback(name) {
+        Ok(()) => log::status!("  ✓ Rolled {} back to {}", name, crate::short_hash(old)),
+        Err(e) => {
+            return Err(
+                Error::failure(format!("failed to roll back {}: {}", name, e)).hint(format!(
+                    "run `c1 update` again to retry the rollback recorded in {}",
+                    ROLLBACK_PATH
+                )),
+            );
+        }
+    }
+    summary.held_back.push(HeldBack {
+        name: name.to_string(),
+        kept: old.to_string(),
+        rejected: new.to_string(),
+        output,
+        first_bad,
+    });
+    Ok(false)
+}
+
+/// Build (and test) the project in a child process, returning its output
+/// from the first error on when it fails
+fn check(oracle: Oracle) -> Result<(), String> {
+    let exe = std::env::current_exe().unwrap_or_else(|_| "c1".into());
+    run_captured(Command::new(exe).arg("build"))?;
+    if let Oracle::Test = oracle {
+        let build_dir = crate::target_build_dir(&crate::BuildOptions::default());
+        run_captured(
+            Command::new("ctest")
+                .arg("--test-dir")
+                .arg(build_dir)
+                .arg("--output-on-failure"),
+        )?;
+    }
+    Ok(())
+}
+
+fn run_captured(command: &mut Command) -> Result<(), String> {
+    let output = command.output().map_err(|e| format!("failed to run: {}", e))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let lines: Vec<&str> = text.lines().collect();
+    // Later errors are mostly fallout of the first one
+    let start = lines
+        .iter()
+        .position(|line| line.contains("error") || line.contains("Failed"))
+        .unwrap_or(0);
+    let end = lines.len().min(start + OUTPUT_LINES);
+    Err(lines[start..end].join("\n"))
+}
+
+/// Binary-search the upstream commits between `old` (good) and `new` (bad)
+/// for the first one that fails `oracle`
+fn bisect_commits(name: &str, old: &str, new: &str, oracle: Oracle) -> Option<String> {
+    let dir = format!("external/{}", name);
+    if Path::new(&dir).join(".git/shallow").exists()
+        && let Err(e) = crate::run_git(&dir, &["fetch", "--unshallow", "--quiet", "origin"])
+    {
+        log::problem!("  ✗ Cannot bisect {}: {}", name, e);
+        return None;
+    }
+    let range = format!("{}..{}", old, new);
+    let commits: Vec<String> =
+        match crate::run_git(&dir, &["rev-list", "--reverse", "--ancestry-path", &range]) {
+            Ok(list) => list.lines().map(str::to_string).collect(),
+            Err(e) => {
+                log::problem!("  ✗ Cannot bisect {}: {}", name, e);
+                return None;
+            }
+        };
+    if commits.is_empty() {
+        return None;
+    }
+
+    // commits[bad] is known to fail; everything up to `good` is known to pass
+    let mut good: Option<usize> = None;
+    let mut bad = commits.len() - 1;
+    log::status!("  Bisecting {} commits of {}...", commits.len(), name);
+    loop {
+        let low = good.map_or(0, |g| g + 1);
+        if low >= bad {
+            break;
+        }
+        let mid = low + (bad - low) / 2;
+        if let Err(e) = crate::checkout_detached(&dir, &commits[mid]) {
+            log::problem!("  ✗ Bisect stopped: {}", e);
+            return None;
+        }
+        let passed = check(oracle).is_ok();
+        log::status!(
+            "    {} {}",
+            crate::short_hash(&commits[mid]),
+            if passed { "good" } else { "bad" }
+        );
+        if passed {
+            good = Some(mid);
+        } else {
+            bad = mid;
+        }
+    }
+
+    let subject = crate::run_git(&dir, &["log", "-1", "--format=%s", &commits[bad]])
+        .unwrap_or_default();
+    Some(format!("{} {}", crate::short_hash(&commits[bad]), subject).trim_end().to_string())
+}
+
+fn restore(saved: &Saved) -> Result<(), String> {
+    let dir = format!("external/{}", saved.name);
+    if saved.branch.is_empty() {
+        crate::checkout_detached(&dir, &saved.commit)?;
+    } else {
+        // Put the branch itself back, so a later fast-forward still works
+        crate::run_git(
+            &dir,
+            &["checkout", "--quiet", "-B", &saved.branch, &saved.commit],
+        )?;
+    }
+
+    let current = crate::load_config()
+        .all_dependencies()
+        .get(&saved.name)
+        .and_then(|v| v.as_table())
+        .map(inline_entry);
+    if current.as_deref() != Some(saved.entry.as_str()) {
+        crate::update_project_toml(&saved.name, &saved.entry).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// A dependency table as a one-line inline table, as written to project.toml
+fn inline_entry(table: &toml::Table) -> String {
+    let fields: Vec<String> = table
+        .iter()
+        .map(|(key, value)| format!("{} = {}", key, value))
+        .collect();
+    format!("{{ {} }}", fields.join(", "))
+}
+
+fn load() -> Vec<Saved> {
+    let Ok(content) = fs::read_to_string(ROLLBACK_PATH) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\t');
+            Some(Saved {
+                name: fields.next()?.to_string(),
+                commit: fields.next()?.to_string(),
+                branch: fields.next()?.to_string(),
+                entry: fields.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Rewrite the rollback records, removing the file once none are left
+fn store(saved: &[Saved]) -> std::io::Result<()> {
+    if saved.is_empty() {
+        if Path::new(ROLLBACK_PATH).exists() {
+            safe_path::remove_file(ROLLBACK_PATH)?;
+        }
+        return Ok(());
+    }
+    fs::create_dir_all(ROLLBACK_DIR)?;
+    let content: String = saved
+        .iter()
+        .map(|s| format!("{}\t{}\t{}\t{}\n", s.name, s.commit, s.branch, s.entry))
+        .collect();
+    // Written beside it and renamed, so an interrupted write keeps the
+    // previous records
+    safe_path::write(ROLLBACK_PATH, content)
+}
@@ -0,0 +1,72 @@
+//! Fetching dependencies from somewhere other than the URL in project.toml.
+//!
+//! A rule in the user's config.toml (see `user_config`),
+//!
+//! ```toml
+//! [source."https://github.com/mycorp"]
+//! replace-with = "git@gitlab.internal:mirror"
+//! ```
+//!
+//! or the same as `"https://github.com/mycorp" = "git@gitlab.internal:mirror"`
+//! in its `[net] mirrors` table, fetches `https://github.com/mycorp/lib.git` from
+//! `git@gitlab.internal:mirror/lib.git`. A key matches a whole URL or the
+//! part of one before a `/` or `:`, and the longest matching key wins.
+//! project.toml and project.lock keep the URL as written; git, the clone
+//! cache and the `ls-remote` cache only ever see the rewritten one, so a
+//! dependency reached under both URLs is fetched and cached once.
+
+use std::sync::OnceLock;
+
+use crate::{log, secrets, user_config};
+
+static RULES: OnceLock<Vec<(String, String)>> = OnceLock::new();
+
+/// The URL to fetch `url` from
+pub fn rewrite(url: &str) -> String {
+    let rules = RULES.get_or_init(load);
+    let Some((prefix, replacement)) = rules
+        .iter()
+        .filter(|(prefix, _)| matches(url, prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+    else {
+        return url.to_string();
+    };
+    let rewritten = format!("{}{}", replacement, &url[prefix.len()..]);
+    log::verbose!(
+        "Fetching {} from {}",
+        secrets::redact(url),
+        secrets::redact(&rewritten)
+    );
+    rewritten
+}
+
+fn matches(url: &str, prefix: &str) -> bool {
+    let Some(rest) = url.strip_prefix(prefix) else {
+        return false;
+    };
+    rest.is_empty() || prefix.ends_with(['/', ':']) || rest.starts_with(['/', ':'])
+}
+
+/// The `[source]` rules and `[net] mirrors` of the user's config.toml
+fn load() -> Vec<(String, String)> {
+    let mut rules = Vec::new();
+    for (prefix, rule) in user_config::table("source").into_iter().flatten() {
+        match rule.get("replace-with").and_then(|v| v.as_str()) {
+            Some(replacement) => rules.push((prefix.clone(), replacement.to_string())),
+            None => log::problem!(
+                "Warning: [source.\"{}\"] in config.toml has no replace-with string; ignoring it",
+                prefix
+            ),
+        }
+    }
+    for (prefix, replacement) in user_config::table("net.mirrors").into_iter().flatten() {
+        match replacement.as_str() {
+            Some(replacement) => rules.push((prefix.clone(), replacement.to_string())),
+            None => log::problem!(
+                "Warning: net.mirrors.\"{}\" in config.toml is not a string; ignoring it",
+                prefix
+            ),
+        }
+    }
+    rules
+}
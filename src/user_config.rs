@@ -0,0 +1,304 @@
+//! The user's own defaults, in config.toml in the c1 config directory
+//! (`$XDG_CONFIG_HOME/c1`, `~/.config/c1` or `%APPDATA%\c1`), next to
+//! credentials.toml.
+//!
+//! ```toml
+//! [defaults.new]   # c1 new and c1 init
+//! compiler = "clang"
+//! edition = "c11"
+//! license = "MIT"
+//! vcs = "none"
+//! vendor_deps = true
+//! flags = ["-Wshadow"]
+//!
+//! [build]          # where project.toml's [build] does not say
+//! generator = "Ninja"
+//! jobs = 8
+//! cache = "auto"
+//!
+//! [index]        # c1 search and c1 add <name>
+//! url = "https://example.com/c1-index.toml"
+//!
+//! [net]
+//! offline = false
+//! mirrors = { "https://github.com/mycorp" = "git@gitlab.internal:mirror" }
+//! ```
+//!
+//! Command-line flags beat project.toml, which beats this file, which beats
+//! c1's built-in defaults. The file is read once; a value of the wrong type
+//! is reported and ignored. `c1 config get/set` read and edit it, keeping
+//! its comments and layout.
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use crate::error::{self, Context, Error};
+use crate::{license, log, safe_path, secrets};
+
+enum Kind {
+    Text,
+    Flag,
+    Count,
+    List,
+    OneOf(&'static [&'static str]),
+    License,
+}
+
+/// The settings c1 reads, besides `net.mirrors.<prefix>` and
+/// `source.<prefix>.replace-with`
+const SETTINGS: &[(&str, Kind)] = &[
+    ("defaults.new.compiler", Kind::Text),
+    ("defaults.new.edition", Kind::OneOf(crate::EDITIONS)),
+    ("defaults.new.license", Kind::License),
+    ("defaults.new.vcs", Kind::OneOf(&["git", "none"])),
+    ("defaults.new.vendor_deps", Kind::Flag),
+    ("defaults.new.flags", Kind::List),
+    ("build.generator", Kind::Text),
+    ("build.jobs", Kind::Count),
+    ("build.cache", Kind::OneOf(crate::compiler_cache::SETTINGS)),
+    ("index.url", Kind::Text),
+    ("net.offline", Kind::Flag),
+];
+
+static CONFIG: OnceLock<toml::Table> = OnceLock::new();
+
+pub fn path() -> Option<PathBuf> {
+    secrets::config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// The file's contents, without the values that failed validation
+fn config() -> &'static toml::Table {
+    CONFIG.get_or_init(|| {
+        let Some(path) = path() else {
+            return toml::Table::new();
+        };
+        let Ok(content) = fs::read_to_string(&path) else {
+            return toml::Table::new();
+        };
+        let mut table: toml::Table = match toml::from_str(&content) {
+            Ok(table) => table,
+            Err(e) => {
+                log::problem!(
+                    "Warning: ignoring {}, which is not valid TOML: {}",
+                    path.display(),
+                    e.message()
+                );
+                return toml::Table::new();
+            }
+        };
+        for (key, kind) in SETTINGS {
+            let Some(value) = lookup(&table, key) else {
+                continue;
+            };
+            if let Err(e) = check(kind, value) {
+                log::problem!("Warning: ignoring {} in {}: {}", key, path.display(), e);
+                remove(&mut table, key);
+            }
+        }
+        table
+    })
+}
+
+fn lookup<'a>(table: &'a toml::Table, key: &str) -> Option<&'a toml::Value> {
+    let (parents, last) = key.rsplit_once('.').map_or(("", key), |(p, l)| (p, l));
+    let mut table = table;
+    for name in parents.split('.').filter(|name| !name.is_empty()) {
+        table = table.get(name)?.as_table()?;
+    }
+    table.get(last)
+}
+
+fn remove(table: &mut toml::Table, key: &str) {
+    let (parents, last) = key.rsplit_once('.').map_or(("", key), |(p, l)| (p, l));
+    let mut table = table;
+    for name in parents.split('.').filter(|name| !name.is_empty()) {
+        let Some(inner) = table.get_mut(name).and_then(|v| v.as_table_mut()) else {
+            return;
+        };
+        table = inner;
+    }
+    table.remove(last);
+}
+
+fn check(kind: &Kind, value: &toml::Value) -> Result<(), String> {
+    match (kind, value) {
+        (Kind::Text, toml::Value::String(_)) | (Kind::Flag, toml::Value::Boolean(_)) => Ok(()),
+        (Kind::Count, toml::Value::Integer(n)) if *n >= 1 => Ok(()),
+        (Kind::List, toml::Value::Array(items)) if items.iter().all(|v| v.is_str()) => Ok(()),
+        (Kind::OneOf(choices), toml::Value::String(s)) if choices.contains(&s.as_str()) => Ok(()),
+        (Kind::OneOf(choices), _) => Err(format!("expected one of {}", choices.join(", "))),
+        (Kind::License, toml::Value::String(s)) => {
+            license::parse(s).map(|_| ()).map_err(|e| e.to_string())
+        }
+        (Kind::Text | Kind::License, _) => Err("expected a string".to_string()),
+        (Kind::Flag, _) => Err("expected true or false".to_string()),
+        (Kind::Count, _) => Err("expected a whole number of at least 1".to_string()),
+        (Kind::List, _) => Err("expected a list of strings".to_string()),
+    }
+}
+
+pub fn text(key: &str) -> Option<&'static str> {
+    lookup(config(), key)?.as_str()
+}
+
+pub fn flag(key: &str) -> Option<bool> {
+    lookup(config(), key)?.as_bool()
+}
+
+pub fn count(key: &str) -> Option<usize> {
+    lookup(config(), key)?.as_integer().map(|n| n as usize)
+}
+
+pub fn list(key: &str) -> Vec<String> {
+    lookup(config(), key)
+        .and_then(|v| v.as_array())
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(|v| v.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn table(key: &str) -> Option<&'static toml::Table> {
+    lookup(config(), key)?.as_table()
+}
+
+/// The kind of value `key` takes, or `None` for a key c1 does not read
+fn kind_of(names: &[&str]) -> Option<&'static Kind> {
+    match names {
+        ["net", "mirrors", _] | ["source", _, "replace-with"] => Some(&Kind::Text),
+        _ => SETTINGS
+            .iter()
+            .find(|(key, _)| key.split('.').eq(names.iter().copied()))
+            .map(|(_, kind)| kind),
+    }
+}
+
+/// Split a dotted key, which may quote parts holding dots of their own
+fn parse_key(key: &str) -> error::Result<Vec<String>> {
+    let unknown = || {
+        Error::usage(format!("Unknown setting '{}'", key)).hint(format!(
+            "settings: {}, net.mirrors.\"<url prefix>\", source.\"<url prefix>\".replace-with",
+            SETTINGS
+                .iter()
+                .map(|(key, _)| *key)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    };
+    let names: Vec<String> = toml_edit::Key::parse(key)
+        .map_err(|_| unknown())?
+        .iter()
+        .map(|name| name.get().to_string())
+        .collect();
+    let refs: Vec<&str> = names.iter().map(String::as_str).collect();
+    match kind_of(&refs) {
+        Some(_) => Ok(names),
+        None => Err(unknown()),
+    }
+}
+
+pub fn cmd_get(key: &str) -> error::Result<()> {
+    let names = parse_key(key)?;
+    let mut value = None;
+    let mut table = config();
+    for (i, name) in names.iter().enumerate() {
+        if i + 1 == names.len() {
+            value = table.get(name);
+        } else {
+            match table.get(name).and_then(|v| v.as_table()) {
+                Some(inner) => table = inner,
+                None => break,
+            }
+        }
+    }
+    match value {
+        Some(toml::Value::String(s)) => println!("{}", s),
+        Some(value) => println!("{}", value),
+        None => {
+            return Err(Error::failure(format!("{} is not set", key)));
+        }
+    }
+    Ok(())
+}
+
+pub fn cmd_set(key: &str, value: &str) -> error::Result<()> {
+    let names = parse_key(key)?;
+    let refs: Vec<&str> = names.iter().map(String::as_str).collect();
+    let kind = kind_of(&refs).expect("parse_key only accepts known keys");
+    let value = parse_value(kind, value).map_err(|e| Error::usage(format!("{}: {}", key, e)))?;
+
+    let path = path().ok_or_else(|| {
+        Error::config("no config directory: set XDG_CONFIG_HOME or HOME".to_string())
+    })?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).context(|| format!("failed to read {}", path.display())),
+    };
+    let mut document: toml_edit::DocumentMut = content
+        .parse()
+        .map_err(|e| Error::config(format!("failed to parse {}: {}", path.display(), e)))?;
+
+    let (last, parents) = names.split_last().expect("keys have at least one part");
+    let mut table = document.as_table_mut();
+    for name in parents {
+        let entry = table.entry(name).or_insert_with(|| {
+            let mut inner = toml_edit::Table::new();
+            inner.set_implicit(true);
+            toml_edit::Item::Table(inner)
+        });
+        table = entry.as_table_mut().ok_or_else(|| {
+            Error::config(format!("`{}` in {} is not a table", name, path.display()))
+        })?;
+    }
+    let shown = value.to_string();
+    match table.get_mut(last).and_then(|item| item.as_value_mut()) {
+        // Keep the comment after the old value
+        Some(existing) => {
+            let decor = existing.decor().clone();
+            *existing = value;
+            *existing.decor_mut() = decor;
+        }
+        None => {
+            table.insert(last, toml_edit::Item::Value(value));
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context(|| format!("failed to create {}", dir.display()))?;
+        // c1's own configuration directory
+        safe_path::allow_root(dir);
+    }
+    safe_path::write(&path, document.to_string())
+        .context(|| format!("failed to write {}", path.display()))?;
+    log::status!("✓ Set {} = {} in {}", key, shown.trim(), path.display());
+    Ok(())
+}
+
+/// `value` from the command line as the TOML value `kind` takes
+fn parse_value(kind: &Kind, value: &str) -> Result<toml_edit::Value, String> {
+    let parsed = match kind {
+        Kind::Flag => match value {
+            "true" => toml_edit::Value::from(true),
+            "false" => toml_edit::Value::from(false),
+            _ => return Err("expected true or false".to_string()),
+        },
+        Kind::Count => match value.parse::<i64>() {
+            Ok(n) if n >= 1 => toml_edit::Value::from(n),
+            _ => return Err("expected a whole number of at least 1".to_string()),
+        },
+        // Flags separated by spaces, as they would be typed
+        Kind::List => toml_edit::Value::Array(value.split_whitespace().collect()),
+        Kind::Text | Kind::OneOf(_) | Kind::License => toml_edit::Value::from(value),
+    };
+    let check_value: toml::Value = toml::from_str::<toml::Table>(&format!("v = {}", parsed))
+        .ok()
+        .and_then(|mut table| table.remove("v"))
+        .ok_or_else(|| format!("cannot store '{}'", value))?;
+    check(kind, &check_value)?;
+    Ok(parsed)
+}
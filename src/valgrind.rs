@@ -0,0 +1,117 @@
+//! `--valgrind`: `c1 run` and `c1 test` under valgrind's memcheck.
+//!
+//! For platforms where the sanitizers are not an option. The program runs
+//! as `valgrind --error-exitcode=99 --leak-check=full <program>`, with
+//! `[run] valgrind_args` from project.toml added before the program, and
+//! keeps its own stdin, stdout and stderr. Valgrind's report goes to
+//! `<build dir>/valgrind/<program>.log` rather than into the program's
+//! output; when it found errors the report is printed and the command fails
+//! with a count of the invalid accesses and definitely lost bytes.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+
+use crate::error::{self, Context, Error};
+use crate::{cross, log};
+
+/// What valgrind exits with when it found errors; the program's own exit
+/// code passes through otherwise
+const ERROR_EXIT_CODE: i32 = 99;
+
+/// `valgrind ... <executable>`, writing its report to the returned log file
+/// in `build_dir`
+pub fn command(
+    executable: &Path,
+    build_dir: &Path,
+    extra_args: &[String],
+) -> error::Result<(Command, PathBuf)> {
+    if cross::find_on_path("valgrind").is_none() {
+        return Err(Error::failure("valgrind was not found on PATH").hint(
+            "install it with the system's package manager (e.g. `apt install valgrind`), \
+             or use --sanitize where valgrind is not available",
+        ));
+    }
+    let dir = build_dir.join("valgrind");
+    fs::create_dir_all(&dir).context(|| format!("failed to create {}", dir.display()))?;
+    let name = executable.file_stem().unwrap_or(executable.as_os_str());
+    let log_file = dir.join(name).with_extension("log");
+
+    let mut command = Command::new("valgrind");
+    command
+        .arg(format!("--error-exitcode={}", ERROR_EXIT_CODE))
+        .arg("--leak-check=full")
+        .arg(crate::path_arg("--log-file=", &log_file))
+        .args(extra_args)
+        .arg(executable);
+    log::command(&command);
+    Ok((command, log_file))
+}
+
+/// The errors valgrind reported, as `2 invalid accesses, 40 bytes definitely
+/// lost in 1 block`, when it exited with its error code. The report itself
+/// is printed to stderr first.
+pub fn errors(status: ExitStatus, log_file: &Path) -> Option<String> {
+    if status.code() != Some(ERROR_EXIT_CODE) {
+        return None;
+    }
+    let report = fs::read_to_string(log_file).unwrap_or_default();
+    let mut lines = Vec::new();
+    let (mut invalid, mut errors) = (0, None);
+    let (mut lost_bytes, mut lost_blocks) = (0, 0);
+    for line in report.lines() {
+        // `==1234== Invalid read of size 4`
+        let text = match line.strip_prefix("==") {
+            Some(rest) => rest.split_once("== ").map_or("", |(_, text)| text),
+            None => line,
+        };
+        lines.push(text);
+        let text = text.trim();
+        if text.starts_with("Invalid ") {
+            invalid += 1;
+        } else if let Some(rest) = text.strip_prefix("definitely lost:") {
+            // `definitely lost: 1,024 bytes in 2 blocks`
+            let numbers: Vec<u64> = rest
+                .split_whitespace()
+                .filter_map(|word| word.replace(',', "").parse().ok())
+                .collect();
+            if let [bytes, blocks] = numbers[..] {
+                lost_bytes = bytes;
+                lost_blocks = blocks;
+            }
+        } else if let Some(rest) = text.strip_prefix("ERROR SUMMARY:") {
+            errors = rest
+                .split_whitespace()
+                .next()
+                .and_then(|count| count.replace(',', "").parse::<u64>().ok());
+        }
+    }
+    if errors == Some(0) {
+        return None;
+    }
+    eprintln!("\nvalgrind report ({}):", log_file.display());
+    for line in lines {
+        eprintln!("  {}", line);
+    }
+
+    let plural = |n: u64, word: &str| format!("{} {}{}", n, word, if n == 1 { "" } else { "s" });
+    let mut found = Vec::new();
+    if invalid > 0 {
+        found.push(format!(
+            "{} invalid access{}",
+            invalid,
+            if invalid == 1 { "" } else { "es" }
+        ));
+    }
+    if lost_bytes > 0 {
+        found.push(format!(
+            "{} definitely lost in {}",
+            plural(lost_bytes, "byte"),
+            plural(lost_blocks, "block")
+        ));
+    }
+    if found.is_empty() {
+        found.push(plural(errors.unwrap_or(1), "error"));
+    }
+    Some(found.join(", "))
+}
@@ -0,0 +1,237 @@
+//! Board variants: `[variant.<name>]` tables in project.toml, built with
+//! `c1 build --variant <name>` or all at once with `--all-variants`.
+//!
+//! ```toml
+//! [variant.rev_b]
+//! defines = ["BOARD_REV=2"]
+//! sources = ["boards/rev_b.c"]
+//! include_dirs = ["boards/rev_b"]
+//! target = "stm32"
+//! linker_script = "boards/rev_b.ld"
+//! ```
+//!
+//! A variant builds on everything else in the manifest: its `defines` come
+//! after `[build] defines` and before the profile's, its `flags` after the
+//! target's, and its `linker_script` replaces the target's. `target` and
+//! `profile` are what the build uses when `--target`, `--profile` or
+//! `--release` do not say otherwise. Its sources and include directories
+//! reach the project target through a block c1 appends to CMakeLists.txt
+//! the first time a variant has any, and the variant's sources are left out
+//! of the managed source list, so a build without `--variant` does not
+//! compile them. Each variant builds in `<build>/variants/<name>/`, where
+//! `c1 run`, `c1 size` and `--emit` find its artifacts.
+//!
+//! `--all-variants` builds each one as its own `c1 build --variant` and
+//! ends with a table of their results and section sizes.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{self, Error};
+use crate::{BuildOptions, cmake_lists, log, size};
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+const CMAKE_MARKER: &str = "C1_VARIANT_SOURCES";
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct Variant {
+    /// Macros on top of `[build] defines`
+    #[serde(default)]
+    pub defines: Vec<String>,
+    /// Sources only this variant compiles
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Header directories only this variant uses
+    #[serde(default)]
+    pub include_dirs: Vec<String>,
+    /// Compiler flags after the target's
+    #[serde(default)]
+    pub flags: Vec<String>,
+    /// Replaces the target's `linker_script`
+    #[serde(default)]
+    pub linker_script: Option<String>,
+    /// The `[target.<name>]` to build for without `--target`
+    #[serde(default)]
+    pub target: Option<String>,
+    /// The profile to build with without `--profile` or `--release`
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+/// The variant called `name`, or an error naming the ones there are
+pub fn get<'a>(variants: &'a BTreeMap<String, Variant>, name: &str) -> error::Result<&'a Variant> {
+    variants.get(name).ok_or_else(|| {
+        let available: Vec<&str> = variants.keys().map(String::as_str).collect();
+        Error::usage(format!(
+            "unknown variant '{}' (available: {})",
+            name,
+            if available.is_empty() {
+                "none".to_string()
+            } else {
+                available.join(", ")
+            }
+        ))
+        .hint("variants are the [variant.<name>] tables in project.toml")
+    })
+}
+
+/// Every variant's sources, which only a build of that variant compiles
+pub fn sources(variants: &BTreeMap<String, Variant>) -> Vec<String> {
+    variants
+        .values()
+        .flat_map(|variant| variant.sources.iter().cloned())
+        .collect()
+}
+
+/// The cmake arguments handing the variant's sources and include
+/// directories to CMakeLists.txt; empty lists when building without one,
+/// so a build after a variant's drops them again
+pub fn configure_args(variant: Option<&Variant>) -> error::Result<Vec<String>> {
+    let (sources, include_dirs) = match variant {
+        Some(variant) => (&variant.sources[..], &variant.include_dirs[..]),
+        None => (&[][..], &[][..]),
+    };
+    for source in sources {
+        if !Path::new(source).is_file() {
+            return Err(Error::config(format!(
+                "variant source {} not found",
+                source
+            )));
+        }
+    }
+    for dir in include_dirs {
+        if !Path::new(dir).is_dir() {
+            log::problem!(
+                "Warning: variant include directory '{}' does not exist",
+                dir
+            );
+        }
+    }
+    if !sources.is_empty() || !include_dirs.is_empty() {
+        ensure_cmake_block()?;
+    }
+    // Only a CMakeLists that uses the lists gets them; cmake warns otherwise
+    let wired = fs::read_to_string(CMAKE_PATH).is_ok_and(|c| c.contains(CMAKE_MARKER));
+    if !wired {
+        return Ok(Vec::new());
+    }
+    Ok(vec![
+        format!("-DC1_VARIANT_SOURCES={}", sources.join(";")),
+        format!("-DC1_VARIANT_INCLUDE_DIRS={}", include_dirs.join(";")),
+    ])
+}
+
+/// Append the lines that apply a variant's lists to the project target, the
+/// first time a variant has any
+fn ensure_cmake_block() -> error::Result<()> {
+    cmake_lists::ensure_block(
+        CMAKE_MARKER,
+        "# The variant built by `c1 build --variant` (see [variant] in project.toml)\n\
+         target_sources(${PROJECT_NAME} PRIVATE ${C1_VARIANT_SOURCES})\n\
+         target_include_directories(${PROJECT_NAME} PRIVATE ${C1_VARIANT_INCLUDE_DIRS})\n",
+        "variant",
+    )
+}
+
+/// What one variant's build left behind, for the summary
+struct Built {
+    name: String,
+    result: &'static str,
+    sections: Option<(u64, u64, u64)>,
+    artifact: String,
+}
+
+/// Build every variant, each as its own `c1 build --variant`
+pub fn cmd_build_all(options: &BuildOptions, fail_fast: bool) -> error::Result<()> {
+    let config = crate::read_config()?;
+    if config.variant.is_empty() {
+        return Err(Error::usage("project.toml declares no variants")
+            .hint("add a [variant.<name>] table for each board"));
+    }
+    let mut built: Vec<Built> = config
+        .variant
+        .keys()
+        .map(|name| Built {
+            name: name.clone(),
+            result: "skipped",
+            sections: None,
+            artifact: String::new(),
+        })
+        .collect();
+    for entry in &mut built {
+        let variant = options.clone().with_variant(Some(entry.name.clone()))?;
+        log::status!("==> Building variant {}", entry.name);
+        if !crate::matrix::run_c1(&variant.to_args()) {
+            entry.result = "failed";
+            log::problem!("✗ variant {} failed to build", entry.name);
+            if fail_fast {
+                break;
+            }
+            continue;
+        }
+        entry.result = "ok";
+        let build_dir = crate::target_build_dir(&variant);
+        match crate::artifact_path(&variant) {
+            Some(executable) => {
+                entry.sections = size::sections(&build_dir, &executable);
+                entry.artifact = executable.display().to_string();
+            }
+            None => entry.artifact = build_dir.display().to_string(),
+        }
+    }
+
+    print_summary(&built);
+    let failed = built.iter().filter(|b| b.result == "failed").count();
+    if failed > 0 {
+        let error = Error::build(format!(
+            "{} of {} variants failed to build",
+            failed,
+            built.len()
+        ));
+        return Err(if fail_fast {
+            error.hint("run without --fail-fast to build the remaining variants anyway")
+        } else {
+            error
+        });
+    }
+    Ok(())
+}
+
+fn print_summary(built: &[Built]) {
+    let width = built
+        .iter()
+        .map(|b| b.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("Variant".len());
+    println!();
+    println!(
+        "{:<w$}  {:<7}  {:>8}  {:>8}  {:>8}  Artifact",
+        "Variant",
+        "Result",
+        "text",
+        "data",
+        "bss",
+        w = width
+    );
+    for b in built {
+        let [text, data, bss] = match b.sections {
+            Some((text, data, bss)) => [text, data, bss].map(|n| n.to_string()),
+            None => ["-", "-", "-"].map(str::to_string),
+        };
+        let line = format!(
+            "{:<w$}  {:<7}  {:>8}  {:>8}  {:>8}  {}",
+            b.name,
+            b.result,
+            text,
+            data,
+            bss,
+            b.artifact,
+            w = width
+        );
+        println!("{}", line.trim_end());
+    }
+}
@@ -0,0 +1,111 @@
+//! Whether the dependencies in external/ are committed with the project.
+//!
+//! By default they are not: .gitignore has a `/external` line and `c1 sync`
+//! fetches them again. `vendor = true` under `[project]` (or `--vendor-deps`
+//! on `c1 new` and `c1 init`) commits them instead. `c1 add` and `c1 sync`
+//! bring .gitignore in step with the setting, adding or removing just that
+//! line, and warn when neither says what to do and git is about to pick up
+//! external/ anyway.
+
+use std::fs;
+use std::process::Command;
+
+use crate::error::{self, Context};
+use crate::{log, safe_path};
+
+const GITIGNORE_PATH: &str = ".gitignore";
+const LINE: &str = "/external";
+const COMMENT: &str =
+    "# Dependencies, fetched again by `c1 sync` (`vendor = true` in project.toml commits them)";
+
+/// `content` with the `/external` line added, or removed when vendoring
+pub fn gitignore(content: &str, vendor: bool) -> String {
+    let ignores = |line: &str| {
+        matches!(
+            line.trim(),
+            "/external" | "/external/" | "external" | "external/"
+        )
+    };
+    if !vendor {
+        if content.lines().any(ignores) {
+            return content.to_string();
+        }
+        let mut updated = content.trim_end().to_string();
+        // The comment may have outlived a line removed by hand
+        if !updated.ends_with(COMMENT) {
+            if !updated.is_empty() {
+                updated.push_str("\n\n");
+            }
+            updated.push_str(COMMENT);
+        }
+        updated.push_str(&format!("\n{}\n", LINE));
+        return updated;
+    }
+    let mut lines: Vec<&str> = Vec::new();
+    for line in content.lines() {
+        if ignores(line) {
+            // Its comment and the blank line that separated them go too
+            if lines.last() == Some(&COMMENT) {
+                lines.pop();
+                if lines.last().is_some_and(|line| line.trim().is_empty()) {
+                    lines.pop();
+                }
+            }
+            continue;
+        }
+        lines.push(line);
+    }
+    let mut updated = lines.join("\n");
+    if content.ends_with('\n') && !updated.is_empty() {
+        updated.push('\n');
+    }
+    updated
+}
+
+/// For `c1 add` and `c1 sync`: apply `vendor` from project.toml to
+/// .gitignore, then warn when git would commit external/ by accident
+pub fn check() -> error::Result<()> {
+    let vendor = crate::read_config()
+        .ok()
+        .and_then(|config| config.project.vendor);
+    if let Some(vendor) = vendor {
+        let current = fs::read_to_string(GITIGNORE_PATH).unwrap_or_default();
+        let updated = gitignore(&current, vendor);
+        if updated != current {
+            safe_path::write(GITIGNORE_PATH, updated)
+                .context(|| format!("failed to update {}", GITIGNORE_PATH))?;
+            if vendor {
+                log::status!("✓ .gitignore no longer ignores external/ (vendor = true)");
+            } else {
+                log::status!("✓ .gitignore now ignores external/ (vendor = false)");
+            }
+        }
+    }
+    if vendor == Some(true) || !git(&["rev-parse", "--is-inside-work-tree"]) {
+        return Ok(());
+    }
+    if git(&["ls-files", "--error-unmatch", "external"]) {
+        log::problem!(
+            "Warning: dependencies in external/ are committed to git, but project.toml does not set `vendor = true`"
+        );
+        log::problem!(
+            "  Set `vendor = true` under [project] to keep them, or run `git rm -r --cached external` to stop committing them."
+        );
+    } else if !git(&["check-ignore", "--quiet", "external/"]) {
+        log::problem!(
+            "Warning: external/ is not ignored, so the next `git add` would commit the dependencies"
+        );
+        log::problem!(
+            "  Set `vendor = false` under [project] to have c1 add it to .gitignore, or `vendor = true` to commit them on purpose."
+        );
+    }
+    Ok(())
+}
+
+/// Whether a quiet git command succeeds
+fn git(args: &[&str]) -> bool {
+    let mut git = Command::new("git");
+    git.args(args);
+    log::command(&git);
+    git.output().is_ok_and(|output| output.status.success())
+}
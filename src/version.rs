@@ -0,0 +1,215 @@
+//! `c1 version`: print or bump the version in project.toml.
+//!
+//! `patch`, `minor` and `major` bump the current version, which must be
+//! semver (`1.4.0`, `2.0.0-rc.1`); a pre-release bumps to its own release
+//! when that is the same step, so `1.0.0-rc.1` goes to `1.0.0` with `major`.
+//! An explicit version is taken as given but must be newer unless `--force`
+//! says otherwise. Only the `version` value in project.toml changes. The
+//! working tree must be clean unless `--allow-dirty`, and `--tag` commits
+//! project.toml and tags the commit `v<version>`, which needs a clean tree
+//! either way.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::error::{self, Error};
+use crate::log;
+
+#[derive(Clone, PartialEq, Eq)]
+struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    /// Dot-separated pre-release identifiers, after `-`
+    pre: Vec<String>,
+    /// Build metadata after `+`, kept but never compared
+    build: Option<String>,
+}
+
+impl Version {
+    fn parse(text: &str) -> Option<Version> {
+        let (rest, build) = match text.split_once('+') {
+            Some((rest, build)) if is_identifiers(build) => (rest, Some(build.to_string())),
+            Some(_) => return None,
+            None => (text, None),
+        };
+        let (core, pre) = match rest.split_once('-') {
+            Some((core, pre)) if is_identifiers(pre) => {
+                (core, pre.split('.').map(str::to_string).collect())
+            }
+            Some(_) => return None,
+            None => (rest, Vec::new()),
+        };
+        let numbers: Vec<u64> = core
+            .split('.')
+            .map(|n| {
+                let leading_zero = n.len() > 1 && n.starts_with('0');
+                (!leading_zero && n.bytes().all(|b| b.is_ascii_digit()))
+                    .then(|| n.parse().ok())
+                    .flatten()
+            })
+            .collect::<Option<_>>()?;
+        let [major, minor, patch] = numbers[..] else {
+            return None;
+        };
+        Some(Version {
+            major,
+            minor,
+            patch,
+            pre,
+            build,
+        })
+    }
+
+    /// The version after a `patch`, `minor` or `major` bump
+    fn bump(&self, part: &str) -> Version {
+        let release = self.pre.is_empty();
+        let (major, minor, patch) = match part {
+            "major" if release || self.minor != 0 || self.patch != 0 => (self.major + 1, 0, 0),
+            "minor" if release || self.patch != 0 => (self.major, self.minor + 1, 0),
+            "patch" if release => (self.major, self.minor, self.patch + 1),
+            // A pre-release of the very version the bump would reach
+            _ => (self.major, self.minor, self.patch),
+        };
+        Version {
+            major,
+            minor,
+            patch,
+            pre: Vec::new(),
+            build: None,
+        }
+    }
+
+    /// Semver precedence: numbers, then a pre-release before its release
+    fn precedence(&self, other: &Version) -> Ordering {
+        let numbers =
+            (self.major, self.minor, self.patch).cmp(&(other.major, other.minor, other.patch));
+        numbers.then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => {
+                for (a, b) in self.pre.iter().zip(&other.pre) {
+                    let order = match (a.parse::<u64>(), b.parse::<u64>()) {
+                        (Ok(a), Ok(b)) => a.cmp(&b),
+                        (Ok(_), Err(_)) => Ordering::Less,
+                        (Err(_), Ok(_)) => Ordering::Greater,
+                        (Err(_), Err(_)) => a.cmp(b),
+                    };
+                    if order != Ordering::Equal {
+                        return order;
+                    }
+                }
+                self.pre.len().cmp(&other.pre.len())
+            }
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre.join("."))?;
+        }
+        if let Some(build) = &self.build {
+            write!(f, "+{}", build)?;
+        }
+        Ok(())
+    }
+}
+
+/// Non-empty dot-separated runs of ASCII letters, digits and `-`
+fn is_identifiers(text: &str) -> bool {
+    text.split('.')
+        .all(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-'))
+}
+
+pub fn cmd_version(
+    bump: Option<&str>,
+    tag: bool,
+    allow_dirty: bool,
+    force: bool,
+) -> error::Result<()> {
+    let config = crate::read_config()?;
+    let Some(bump) = bump else {
+        println!("{}", config.project.version);
+        return Ok(());
+    };
+    let current = Version::parse(&config.project.version).ok_or_else(|| {
+        Error::config(format!(
+            "version '{}' in project.toml is not semver (MAJOR.MINOR.PATCH)",
+            config.project.version
+        ))
+        .hint("set a version such as 1.4.0 with `c1 version 1.4.0`")
+    });
+    let new = match bump {
+        "patch" | "minor" | "major" => current?.bump(bump),
+        explicit => {
+            let new = Version::parse(explicit.strip_prefix('v').unwrap_or(explicit)).ok_or_else(
+                || {
+                    Error::usage(format!("'{}' is not a semver version", explicit))
+                        .hint("use patch, minor, major or a version such as 1.4.0")
+                },
+            )?;
+            // An invalid current version is simply replaced
+            if let Ok(current) = current
+                && new.precedence(&current) != Ordering::Greater
+                && !force
+            {
+                return Err(Error::usage(format!(
+                    "{} is not newer than the current version {}",
+                    new, current
+                ))
+                .hint("pass --force to set it anyway"));
+            }
+            new
+        }
+    };
+    if new.to_string() == config.project.version {
+        log::status!("Version is already {}", new);
+        return Ok(());
+    }
+
+    let in_git = crate::run_git(".", &["rev-parse", "--is-inside-work-tree"]).is_ok();
+    let dirty = in_git
+        && crate::run_git(".", &["status", "--porcelain"]).is_ok_and(|status| !status.is_empty());
+    if tag && !in_git {
+        return Err(Error::usage("--tag needs a git repository"));
+    }
+    if dirty && (tag || !allow_dirty) {
+        let error = Error::usage("the working tree has uncommitted changes");
+        return Err(if tag {
+            error.hint("commit or stash them first; --tag only commits project.toml")
+        } else {
+            error.hint("commit or stash them first, or pass --allow-dirty")
+        });
+    }
+    let tag_name = format!("v{}", new);
+    if tag
+        && crate::run_git(
+            ".",
+            &[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("refs/tags/{}", tag_name),
+            ],
+        )
+        .is_ok()
+    {
+        return Err(Error::usage(format!("tag {} already exists", tag_name)));
+    }
+
+    crate::set_manifest_entry("project", "version", &format!("\"{}\"", new))?;
+    log::status!("✓ Version {} -> {}", config.project.version, new);
+    if tag {
+        let message = format!("Release {}", new);
+        crate::run_git(".", &["commit", "--quiet", "-m", &message, "project.toml"])
+            .map_err(|e| Error::failure(format!("failed to commit project.toml: {}", e)))?;
+        crate::run_git(".", &["tag", "-a", &tag_name, "-m", &message])
+            .map_err(|e| Error::failure(format!("failed to create tag {}: {}", tag_name, e)))?;
+        log::status!("✓ Committed project.toml and tagged it {}", tag_name);
+    }
+    Ok(())
+}
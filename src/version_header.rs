@@ -0,0 +1,202 @@
+//! c1_version.h: the project's version and git state, for the program to
+//! report about itself.
+//!
+//! Before every build (and `c1 check`) c1 writes
+//! `<build dir>/generated/c1_version.h` and adds that directory to the
+//! project target's include path through a managed block of CMakeLists.txt,
+//! `# @c1_version_header` through `# @c1_version_header_end`:
+//!
+//! ```c
+//! #include "c1_version.h"
+//!
+//! printf("%s %s (%s)\n", C1_PROJECT_NAME, C1_PROJECT_VERSION, C1_GIT_DESCRIBE);
+//! ```
+//!
+//! Outside a git repository the git macros are empty strings and
+//! `C1_GIT_DIRTY` is 0. The file is only rewritten when something other than
+//! the timestamp changed, so an unchanged tree does not recompile the files
+//! that include it; `C1_BUILD_TIMESTAMP` is therefore the time of the last
+//! such change (or `SOURCE_DATE_EPOCH`). `[build] version_header = false`
+//! turns all of this off.
+
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{self, Context};
+use crate::{ProjectConfig, log, safe_path};
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+const BEGIN: &str = "# @c1_version_header";
+const END: &str = "# @c1_version_header_end";
+pub const DIR: &str = "generated";
+const FILE_NAME: &str = "c1_version.h";
+const TIMESTAMP_MACRO: &str = "#define C1_BUILD_TIMESTAMP ";
+
+/// Write the header into `build_dir` unless turned off, and bring the
+/// CMakeLists block in step with the setting
+pub fn generate(config: &ProjectConfig, build_dir: &Path) -> error::Result<()> {
+    let enabled = config.build.version_header.unwrap_or(true);
+    wire(enabled)?;
+    if !enabled {
+        return Ok(());
+    }
+    let dir = build_dir.join(DIR);
+    let path = dir.join(FILE_NAME);
+    let header = header(config);
+    let current = fs::read_to_string(&path).unwrap_or_default();
+    if without_timestamp(&current) == without_timestamp(&header) {
+        return Ok(());
+    }
+    fs::create_dir_all(&dir).context(|| format!("failed to create {}", dir.display()))?;
+    safe_path::write(&path, header).context(|| format!("failed to write {}", path.display()))?;
+    log::verbose!("Wrote {}", path.display());
+    Ok(())
+}
+
+fn header(config: &ProjectConfig) -> String {
+    let in_git = crate::run_git(".", &["rev-parse", "--is-inside-work-tree"]).is_ok();
+    let git = |args: &[&str]| {
+        in_git
+            .then(|| crate::run_git(".", args).ok())
+            .flatten()
+            .unwrap_or_default()
+    };
+    let hash = git(&["rev-parse", "--short", "HEAD"]);
+    let describe = git(&["describe", "--tags", "--always", "--dirty"]);
+    let dirty = !git(&["status", "--porcelain", "--untracked-files=no"]).is_empty();
+    format!(
+        r#"/* {file} - generated by c1 before every build, do not edit.
+ * Set `version_header = false` under [build] in project.toml to stop it. */
+#ifndef C1_VERSION_H
+#define C1_VERSION_H
+
+#define C1_PROJECT_NAME {name}
+#define C1_PROJECT_VERSION {version}
+#define C1_GIT_HASH {hash}
+#define C1_GIT_DESCRIBE {describe}
+#define C1_GIT_DIRTY {dirty}
+{timestamp_macro}{timestamp}
+
+#endif /* C1_VERSION_H */
+"#,
+        file = FILE_NAME,
+        name = c_string(&config.project.name),
+        version = c_string(&config.project.version),
+        hash = c_string(&hash),
+        describe = c_string(&describe),
+        dirty = u8::from(dirty),
+        timestamp_macro = TIMESTAMP_MACRO,
+        timestamp = c_string(&timestamp()),
+    )
+}
+
+fn without_timestamp(header: &str) -> String {
+    header
+        .lines()
+        .filter(|line| !line.starts_with(TIMESTAMP_MACRO))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn c_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c if c.is_control() => quoted.push_str(&format!("\\x{:02x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// `SOURCE_DATE_EPOCH`, else now, as ISO 8601 UTC
+fn timestamp() -> String {
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|secs| secs.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        });
+    iso8601(secs)
+}
+
+/// Seconds since the epoch as ISO 8601 UTC
+pub fn iso8601(secs: u64) -> String {
+    let time = secs % 86_400;
+    // Days since the epoch to a proleptic Gregorian date
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+fn wire(enabled: bool) -> error::Result<()> {
+    let Ok(content) = fs::read_to_string(CMAKE_PATH) else {
+        return Ok(());
+    };
+    let block = if enabled {
+        format!(
+            "{}\n# c1_version.h, written by c1 before every build\n\
+             target_include_directories(${{PROJECT_NAME}} PRIVATE ${{CMAKE_BINARY_DIR}}/{})\n{}\n",
+            BEGIN, DIR, END
+        )
+    } else {
+        String::new()
+    };
+    let updated = match (content.find(&format!("{}\n", BEGIN)), content.find(END)) {
+        (Some(start), Some(stop)) if stop > start => {
+            let stop = stop + END.len();
+            let stop = stop + content[stop..].find('\n').map_or(0, |i| i + 1);
+            // Drop the blank line that separated a removed block
+            let start = if block.is_empty() && content[..start].ends_with("\n\n") {
+                start - 1
+            } else {
+                start
+            };
+            format!("{}{}{}", &content[..start], block, &content[stop..])
+        }
+        _ if block.is_empty() => return Ok(()),
+        _ => {
+            let mut updated = content.clone();
+            if !updated.ends_with('\n') {
+                updated.push('\n');
+            }
+            updated.push('\n');
+            updated.push_str(&block);
+            updated
+        }
+    };
+    if updated != content {
+        safe_path::write(CMAKE_PATH, updated)
+            .context(|| format!("failed to update {}", CMAKE_PATH))?;
+        log::status!("✓ Updated the c1_version.h include directory in CMakeLists.txt");
+    }
+    Ok(())
+}
@@ -0,0 +1,198 @@
+//! `[build] warnings`: compiler warning presets.
+//!
+//! | preset     | flags                                                          |
+//! |------------|----------------------------------------------------------------|
+//! | `none`     | nothing; the compiler's own defaults                           |
+//! | `default`  | `-Wall -Wextra`                                                |
+//! | `strict`   | `default` plus conversions, shadowing, prototypes, VLAs and more |
+//! | `pedantic` | `strict` plus `-Wpedantic` and the noisier checks              |
+//!
+//! gcc and clang share most of each list, with a few checks only one of them
+//! has; MSVC gets the matching `/W` level instead. A compiler that is neither
+//! gets only the flags both gcc and clang accept. `warnings_as_errors = true`
+//! adds `-Werror` (`/WX`). A project.toml without `warnings` gets no flags, as
+//! before presets existed; `c1 new` writes `strict` and `c1 init --existing`
+//! writes `default`.
+//!
+//! The flags apply to the project's own target only, through the
+//! `C1_WARNING_FLAGS` list c1 passes when configuring, so dependencies built
+//! from external/ keep compiling with their own flags (see `cflags` in
+//! dep_wiring). Tests, benches and examples get the compiler's defaults.
+//! Target options come after CFLAGS, the target's and the profile's flags,
+//! so turning one of the preset's warnings off takes a
+//! `target_compile_options` line after c1's in CMakeLists.txt.
+
+use std::fs;
+use std::process::Command;
+
+use crate::cmake_lists;
+use crate::error::{self, Error};
+
+const CMAKE_PATH: &str = "CMakeLists.txt";
+const CMAKE_MARKER: &str = "C1_WARNING_FLAGS";
+
+pub const PRESETS: &[&str] = &["none", "default", "strict", "pedantic"];
+
+/// What new projects get
+pub const NEW_PROJECT: &str = "strict";
+/// What adopted code gets, so a legacy codebase does not drown in warnings
+pub const EXISTING_CODE: &str = "default";
+
+const DEFAULT: &[&str] = &["-Wall", "-Wextra"];
+const STRICT: &[&str] = &[
+    "-Wconversion",
+    "-Wshadow",
+    "-Wstrict-prototypes",
+    "-Wmissing-prototypes",
+    "-Wvla",
+    "-Wformat=2",
+    "-Wundef",
+    "-Wpointer-arith",
+    "-Wcast-qual",
+    "-Wwrite-strings",
+    "-Wimplicit-fallthrough",
+];
+const STRICT_GCC: &[&str] = &[
+    "-Wlogical-op",
+    "-Wduplicated-cond",
+    "-Wduplicated-branches",
+    "-Wnull-dereference",
+];
+const STRICT_CLANG: &[&str] = &["-Wcomma", "-Wloop-analysis", "-Wnull-dereference"];
+const PEDANTIC: &[&str] = &[
+    "-Wpedantic",
+    "-Wswitch-enum",
+    "-Wdouble-promotion",
+    "-Wredundant-decls",
+];
+const PEDANTIC_GCC: &[&str] = &["-Wcast-align=strict"];
+const PEDANTIC_CLANG: &[&str] = &["-Wcast-align", "-Wunreachable-code"];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Family {
+    Gcc,
+    Clang,
+    Msvc,
+    Other,
+}
+
+impl Family {
+    /// The family of `compiler`, or of `cc` when cmake picks the compiler
+    pub fn of(compiler: Option<&str>) -> Family {
+        let compiler = compiler.unwrap_or("cc");
+        let name = compiler
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or(compiler)
+            .to_ascii_lowercase();
+        let name = name.strip_suffix(".exe").unwrap_or(&name);
+        if name == "cl" {
+            return Family::Msvc;
+        }
+        if name.contains("clang") {
+            return Family::Clang;
+        }
+        // cc and the like may be either; Apple's gcc is clang too
+        let Ok(output) = Command::new(compiler).arg("--version").output() else {
+            return Family::Other;
+        };
+        let version = String::from_utf8_lossy(&output.stdout).to_ascii_lowercase();
+        if version.contains("clang") {
+            Family::Clang
+        } else if version.contains("gcc") || version.contains("free software foundation") {
+            Family::Gcc
+        } else {
+            Family::Other
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Family::Gcc => "gcc",
+            Family::Clang => "clang",
+            Family::Msvc => "msvc",
+            Family::Other => "other",
+        }
+    }
+}
+
+/// An unknown preset is a config error
+pub fn check(preset: Option<&str>) -> error::Result<()> {
+    match preset {
+        Some(preset) if !PRESETS.contains(&preset) => Err(Error::config(format!(
+            "unknown [build] warnings preset '{}'",
+            preset
+        ))
+        .hint(format!("use one of: {}", PRESETS.join(", ")))),
+        _ => Ok(()),
+    }
+}
+
+/// The flags for `preset` (none when unset) on `family`, ending in the one
+/// that makes warnings errors when `as_errors`
+pub fn flags(preset: Option<&str>, family: Family, as_errors: bool) -> Vec<String> {
+    let level = preset.map_or(0, |preset| {
+        PRESETS.iter().position(|p| *p == preset).unwrap_or(0)
+    });
+    let mut flags: Vec<&str> = Vec::new();
+    if family == Family::Msvc {
+        flags.extend(match level {
+            0 => None,
+            1 => Some("/W3"),
+            2 => Some("/W4"),
+            _ => Some("/Wall"),
+        });
+        if as_errors {
+            flags.push("/WX");
+        }
+        return flags.into_iter().map(str::to_string).collect();
+    }
+    if level >= 1 {
+        flags.extend(DEFAULT);
+    }
+    if level >= 2 {
+        flags.extend(STRICT);
+        flags.extend(match family {
+            Family::Gcc => STRICT_GCC,
+            Family::Clang => STRICT_CLANG,
+            _ => &[],
+        });
+    }
+    if level >= 3 {
+        flags.extend(PEDANTIC);
+        flags.extend(match family {
+            Family::Gcc => PEDANTIC_GCC,
+            Family::Clang => PEDANTIC_CLANG,
+            _ => &[],
+        });
+    }
+    if as_errors {
+        flags.push("-Werror");
+    }
+    flags.into_iter().map(str::to_string).collect()
+}
+
+/// The cmake argument carrying `flags`, wiring them to the project's target
+/// in CMakeLists.txt the first time there are any
+pub fn configure_args(flags: &[String]) -> error::Result<Vec<String>> {
+    if !flags.is_empty() {
+        ensure_cmake_block()?;
+    }
+    // Only a CMakeLists that uses the list gets it; cmake warns otherwise
+    let wired = fs::read_to_string(CMAKE_PATH).is_ok_and(|c| c.contains(CMAKE_MARKER));
+    if !wired {
+        return Ok(Vec::new());
+    }
+    Ok(vec![format!("-D{}={}", CMAKE_MARKER, flags.join(";"))])
+}
+
+/// Projects from before the presets went on the target get the line new
+/// projects are created with
+fn ensure_cmake_block() -> error::Result<()> {
+    cmake_lists::ensure_block(
+        CMAKE_MARKER,
+        "# Warnings from [build] warnings in project.toml, for the project's own code\n\
+         target_compile_options(${PROJECT_NAME} PRIVATE ${C1_WARNING_FLAGS})\n",
+        "warning",
+    )
+}
@@ -0,0 +1,215 @@
+//! `c1 watch`: rebuild, and optionally rerun, whenever a source changes.
+//!
+//! The sources are polled rather than watched through OS notifications: main.c,
+//! src/, include/, tests/, CMakeLists.txt and project.toml. build/ and
+//! external/ are never looked at, so a build cannot trigger itself. Work
+//! starts once saves have settled for a moment.
+//!
+//! Each run is a child `c1 build`/`run`/`test` in its own process group, so
+//! stopping it on the next change also stops the program or tests it started.
+//! Ctrl-C stops the child before watch exits.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::error::{self, Error};
+use crate::log;
+
+const WATCHED: &[&str] = &[
+    "main.c",
+    "src",
+    "include",
+    "tests",
+    "CMakeLists.txt",
+    "project.toml",
+];
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+/// Editors often write a file several times per save
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+/// How long a stopped child gets to exit before it is killed
+#[cfg(unix)]
+const STOP_GRACE: Duration = Duration::from_secs(2);
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+type Snapshot = BTreeMap<PathBuf, SystemTime>;
+
+pub fn cmd_watch(exec: Option<&str>, release: bool) -> error::Result<()> {
+    if !Path::new("project.toml").exists() && !Path::new("CMakeLists.txt").exists() {
+        return Err(Error::usage(
+            "no project.toml or CMakeLists.txt here. Are you in a c1 project?",
+        ));
+    }
+    let mut args = vec![exec.unwrap_or("build").to_string()];
+    if release {
+        args.push("--release".to_string());
+    }
+    let verb = match exec {
+        Some("run") => "rebuilding and running",
+        Some("test") => "rebuilding and testing",
+        _ => "rebuilding",
+    };
+
+    handle_interrupt();
+    println!("Watching {} (Ctrl-C to stop)", WATCHED.join(", "));
+    let mut snapshot = snapshot();
+    let mut child = Some(spawn(&args)?);
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            if let Some(child) = child.take() {
+                stop(child);
+            }
+            println!("\nStopped watching");
+            return Ok(());
+        }
+        if let Some(running) = &mut child
+            && let Ok(Some(status)) = running.try_wait()
+        {
+            if status.success() {
+                log::print(
+                    false,
+                    format_args!("✓ c1 {} finished; waiting for changes...", args[0]),
+                );
+            } else {
+                log::problem!("✗ c1 {} failed; waiting for changes...", args[0]);
+            }
+            child = None;
+        }
+
+        let mut current = self::snapshot();
+        if current == snapshot {
+            continue;
+        }
+        loop {
+            thread::sleep(SETTLE_TIME);
+            let next = self::snapshot();
+            if next == current {
+                break;
+            }
+            current = next;
+        }
+        let changed = changes(&snapshot, &current);
+        snapshot = current;
+        let Some(first) = changed.first() else {
+            continue;
+        };
+        let more = match changed.len() {
+            1 => String::new(),
+            n => format!(" and {} more", n - 1),
+        };
+        println!(
+            "\nChange detected in {}{}, {}...",
+            first.display(),
+            more,
+            verb
+        );
+        if let Some(running) = child.take() {
+            stop(running);
+        }
+        child = Some(spawn(&args)?);
+    }
+}
+
+/// Modification times of every watched file
+fn snapshot() -> Snapshot {
+    let mut files = Snapshot::new();
+    for path in WATCHED {
+        collect(Path::new(path), &mut files);
+    }
+    files
+}
+
+fn collect(path: &Path, files: &mut Snapshot) {
+    let Ok(metadata) = fs::metadata(path) else {
+        return;
+    };
+    if metadata.is_file() {
+        if let Ok(modified) = metadata.modified() {
+            files.insert(path.to_path_buf(), modified);
+        }
+        return;
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        collect(&entry.path(), files);
+    }
+}
+
+/// Files added, removed or modified between two snapshots
+fn changes(before: &Snapshot, after: &Snapshot) -> Vec<PathBuf> {
+    let mut changed: Vec<PathBuf> = after
+        .iter()
+        .filter(|(path, modified)| before.get(*path) != Some(modified))
+        .map(|(path, _)| path.clone())
+        .collect();
+    changed.extend(
+        before
+            .keys()
+            .filter(|path| !after.contains_key(*path))
+            .cloned(),
+    );
+    changed
+}
+
+fn spawn(args: &[String]) -> error::Result<Child> {
+    let exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("c1"));
+    let mut command = Command::new(exe);
+    command.args(args);
+    log::pass_to(&mut command);
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+    command
+        .spawn()
+        .map_err(|e| Error::failure(format!("failed to start c1 {}: {}", args[0], e)))
+}
+
+/// Ask the child and everything it started to exit, then force it
+fn stop(mut child: Child) {
+    if let Ok(Some(_)) = child.try_wait() {
+        return;
+    }
+    #[cfg(unix)]
+    {
+        let group = -(child.id() as libc::pid_t);
+        // SAFETY: kill() has no memory-safety preconditions
+        unsafe { libc::kill(group, libc::SIGTERM) };
+        let deadline = std::time::Instant::now() + STOP_GRACE;
+        while std::time::Instant::now() < deadline {
+            if let Ok(Some(_)) = child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        // SAFETY: as above
+        unsafe { libc::kill(group, libc::SIGKILL) };
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+/// The child runs in its own process group and does not see the terminal's
+/// Ctrl-C, so catch it here and stop the child on the way out
+#[cfg(unix)]
+fn handle_interrupt() {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+    }
+    let handler = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+    unsafe { libc::signal(libc::SIGINT, handler) };
+}
+
+/// On Windows Ctrl-C reaches every process on the console, the child included
+#[cfg(not(unix))]
+fn handle_interrupt() {}
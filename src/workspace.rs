@@ -0,0 +1,281 @@
+//! Workspaces: a root project.toml listing member projects.
+//!
+//! ```toml
+//! [workspace]
+//! members = ["app1", "app2", "libs/common"]
+//! ```
+//!
+//! Building at the root builds every member by running c1 inside each member
+//! directory, so one member's failure cannot abort the process mid-way. Sync
+//! merges the members' dependencies and fetches them once into the root's
+//! external/ directory.
+
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::error::{self, Error};
+use crate::log;
+
+#[derive(Deserialize)]
+struct WorkspaceManifest {
+    workspace: Option<Workspace>,
+    #[serde(default)]
+    dependencies: toml::Table,
+}
+
+#[derive(Deserialize)]
+struct Workspace {
+    #[serde(default)]
+    members: Vec<String>,
+}
+
+pub struct Member {
+    pub path: PathBuf,
+    pub name: String,
+}
+
+/// Members of the workspace rooted at the current directory, or `None` when
+/// project.toml has no `[workspace]` table
+pub fn load() -> error::Result<Option<Vec<Member>>> {
+    let Some(workspace) = fs::read_to_string("project.toml")
+        .ok()
+        .and_then(|content| toml::from_str::<WorkspaceManifest>(&content).ok())
+        .and_then(|manifest| manifest.workspace)
+    else {
+        return Ok(None);
+    };
+
+    let members = workspace
+        .members
+        .iter()
+        .map(|member| {
+            let path = PathBuf::from(member);
+            if !path.join("project.toml").exists() {
+                return Err(Error::config(format!(
+                    "workspace member '{}' has no project.toml",
+                    member
+                )));
+            }
+            let name = crate::get_project_name_from_config(&path).unwrap_or_else(|| {
+                path.file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| member.clone())
+            });
+            Ok(Member { path, name })
+        })
+        .collect::<error::Result<_>>()?;
+    Ok(Some(members))
+}
+
+/// Build every member (or only `package`), reporting failures at the end
+pub fn cmd_build(
+    members: &[Member],
+    package: Option<&str>,
+    options: &crate::BuildOptions,
+    keep_going: bool,
+) -> error::Result<()> {
+    let selected: Vec<&Member> = match package {
+        Some(name) => vec![find(members, name)?],
+        None => members.iter().collect(),
+    };
+
+    let args = options.to_args();
+    let mut failed = Vec::new();
+    for member in selected {
+        log::status!("==> Building {} ({})", member.name, member.path.display());
+        if !run_c1(member, &args) {
+            log::problem!("✗ {} failed to build", member.name);
+            failed.push(member.name.as_str());
+            if !keep_going {
+                break;
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        let error = Error::failure(format!(
+            "{} member(s) failed: {}",
+            failed.len(),
+            failed.join(", ")
+        ));
+        if keep_going {
+            return Err(error);
+        }
+        return Err(error.hint("pass --keep-going to build the remaining members anyway"));
+    }
+    log::status!("\n✓ Workspace build completed successfully!");
+    Ok(())
+}
+
+/// Build and run one member, propagating its exit code
+pub fn cmd_run(
+    members: &[Member],
+    package: Option<&str>,
+    options: &crate::BuildOptions,
+    env: &[String],
+    args: &[String],
+    bin: Option<&str>,
+    example: Option<&str>,
+) -> error::Result<()> {
+    let member = match package {
+        Some(name) => find(members, name)?,
+        None if members.len() == 1 => &members[0],
+        None => {
+            return Err(Error::usage(
+                "this is a workspace, choose a member to run with -p <name>.",
+            )
+            .hint(format!("available members: {}", names(members))));
+        }
+    };
+    let mut command = Command::new(current_exe());
+    command.arg("run");
+    if options.release {
+        command.arg("--release");
+    }
+    if let Some(jobs) = options.jobs {
+        command.arg(format!("--jobs={}", jobs));
+    }
+    for assignment in env {
+        command.arg(format!("--env={}", assignment));
+    }
+    if options.no_verify_deps {
+        command.arg("--no-verify-deps");
+    }
+    if options.valgrind {
+        command.arg("--valgrind");
+    }
+    if options.guess {
+        command.arg("--guess");
+    }
+    if let Some(bin) = bin {
+        command.arg(format!("--bin={}", bin));
+    }
+    if let Some(example) = example {
+        command.arg(format!("--example={}", example));
+    }
+    if !args.is_empty() {
+        command.arg("--").args(args);
+    }
+    log::pass_to(&mut command);
+    // The member takes its own lock, and holds it only while it builds
+    crate::project_lock::release();
+    let status = command
+        .current_dir(&member.path)
+        .status();
+    match status {
+        Ok(s) => std::process::exit(s.code().unwrap_or(1)),
+        Err(e) => Err(Error::failure(format!(
+            "failed to run c1 in {}: {}",
+            member.path.display(),
+            e
+        ))),
+    }
+}
+
+/// Merge the root's and every member's `[dependencies]` into one table.
+///
+/// Relative `path` dependencies are rebased onto the workspace root. Two
+/// members declaring the same name differently is a hard error.
+pub fn merged_dependencies(members: &[Member]) -> error::Result<toml::Table> {
+    let root: Option<WorkspaceManifest> = fs::read_to_string("project.toml")
+        .ok()
+        .and_then(|c| toml::from_str(&c).ok());
+    let mut merged = toml::Table::new();
+    let mut origin: Vec<(String, String)> = Vec::new();
+    let mut conflicts = Vec::new();
+
+    let mut sources = vec![(
+        "workspace root".to_string(),
+        PathBuf::new(),
+        root.map(|r| r.dependencies).unwrap_or_default(),
+    )];
+    for member in members {
+        let deps = fs::read_to_string(member.path.join("project.toml"))
+            .ok()
+            .and_then(|c| toml::from_str::<WorkspaceManifest>(&c).ok())
+            .map(|m| m.dependencies)
+            .unwrap_or_default();
+        sources.push((member.name.clone(), member.path.clone(), deps));
+    }
+
+    for (who, base, deps) in sources {
+        for (name, value) in deps {
+            let value = rebase_path(value, &base);
+            match merged.get(&name) {
+                Some(existing) if *existing != value => {
+                    let first = origin
+                        .iter()
+                        .find(|(n, _)| *n == name)
+                        .map(|(_, w)| w.clone())
+                        .unwrap_or_default();
+                    conflicts.push(format!(
+                        "'{}' is declared as {} by {} but as {} by {}",
+                        name, existing, first, value, who
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    origin.push((name.clone(), who.clone()));
+                    merged.insert(name, value);
+                }
+            }
+        }
+    }
+
+    if !conflicts.is_empty() {
+        let conflicts: Vec<String> = conflicts
+            .iter()
+            .map(|conflict| format!("conflicting dependency {}", conflict))
+            .collect();
+        return Err(Error::config(conflicts.join("\n")));
+    }
+    Ok(merged)
+}
+
+fn rebase_path(value: toml::Value, base: &Path) -> toml::Value {
+    let toml::Value::Table(mut table) = value else {
+        return value;
+    };
+    if let Some(path) = table.get("path").and_then(|p| p.as_str())
+        && Path::new(path).is_relative()
+        && !base.as_os_str().is_empty()
+    {
+        let rebased = base.join(path).to_string_lossy().to_string();
+        table.insert("path".to_string(), toml::Value::String(rebased));
+    }
+    toml::Value::Table(table)
+}
+
+fn find<'a>(members: &'a [Member], name: &str) -> error::Result<&'a Member> {
+    members
+        .iter()
+        .find(|m| m.name == name || m.path == Path::new(name))
+        .ok_or_else(|| {
+            Error::usage(format!("no workspace member named '{}'", name))
+                .hint(format!("available members: {}", names(members)))
+        })
+}
+
+fn names(members: &[Member]) -> String {
+    members
+        .iter()
+        .map(|m| m.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn run_c1(member: &Member, args: &[String]) -> bool {
+    let mut command = Command::new(current_exe());
+    command.args(args).current_dir(&member.path);
+    log::pass_to(&mut command);
+    command
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+pub fn current_exe() -> PathBuf {
+    std::env::current_exe().unwrap_or_else(|_| PathBuf::from("c1"))
+}
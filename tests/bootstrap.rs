@@ -0,0 +1,153 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use common::{TempDir, c1, run};
+
+/// A bare repository at <tmp>/<name>.git holding `files`, committed once
+fn fixture_repo(tmp: &TempDir, name: &str, files: &[(&str, &str)]) -> String {
+    let work = tmp.path().join(format!("work-{}", name));
+    for (path, content) in files {
+        let path = work.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+    let git = |dir: &Path, args: &[&str]| {
+        let mut command = Command::new("git");
+        command
+            .current_dir(dir)
+            .args(args)
+            .env("GIT_AUTHOR_NAME", "Test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_NAME", "Test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com");
+        run(&mut command);
+    };
+    git(&work, &["init", "--quiet", "--initial-branch", "main"]);
+    git(&work, &["add", "-A"]);
+    git(&work, &["commit", "--quiet", "-m", "initial"]);
+    let bare = tmp.path().join(format!("{}.git", name));
+    git(
+        tmp.path(),
+        &[
+            "clone",
+            "--quiet",
+            "--bare",
+            work.to_str().unwrap(),
+            bare.to_str().unwrap(),
+        ],
+    );
+    format!("file://{}", bare.display())
+}
+
+/// Every file and directory under `dir` except git's own, relative and sorted
+fn tree(dir: &Path) -> Vec<PathBuf> {
+    fn walk(root: &Path, dir: &Path, out: &mut Vec<PathBuf>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.file_name().is_some_and(|name| name == ".git") {
+                continue;
+            }
+            out.push(path.strip_prefix(root).unwrap().to_path_buf());
+            if path.is_dir() {
+                walk(root, &path, out);
+            }
+        }
+    }
+    let mut out = Vec::new();
+    walk(dir, dir, &mut out);
+    out.sort();
+    out
+}
+
+fn head(dir: &Path) -> String {
+    let output = run(Command::new("git")
+        .current_dir(dir)
+        .args(["rev-parse", "HEAD"]));
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+#[test]
+fn script_reproduces_what_sync_checks_out() {
+    if !cfg!(unix) {
+        eprintln!("skipping: bootstrap.sh needs a POSIX shell");
+        return;
+    }
+    let tmp = TempDir::new("bootstrap");
+    let liba = fixture_repo(
+        &tmp,
+        "liba",
+        &[
+            ("include/a.h", "int a(void);\n"),
+            ("src/a.c", "int a(void) { return 1; }\n"),
+        ],
+    );
+    let mono = fixture_repo(
+        &tmp,
+        "mono",
+        &[
+            ("README", "monorepo\n"),
+            ("libs/json/include/json.h", "int json(void);\n"),
+            ("libs/json/src/json.c", "int json(void) { return 2; }\n"),
+            ("tools/gen/gen.c", "int main(void) { return 0; }\n"),
+        ],
+    );
+    run(c1(&tmp, tmp.path()).args(["new", "--vcs", "none", "app"]));
+    let project = tmp.path().join("app");
+    run(c1(&tmp, &project).args(["add", &liba, "--name", "liba"]));
+    run(c1(&tmp, &project).args(["add", &mono, "--name", "json", "--subdir", "libs/json"]));
+    run(c1(&tmp, &project).args(["sync"]));
+    run(c1(&tmp, &project).args(["export", "bootstrap"]));
+
+    // A checkout of the project as it is committed: no external/, no c1
+    let fresh = tmp.path().join("fresh");
+    fs::create_dir_all(&fresh).unwrap();
+    for file in ["project.toml", "project.lock", "bootstrap.sh"] {
+        fs::copy(project.join(file), fresh.join(file)).unwrap();
+    }
+    run(Command::new("sh").arg("bootstrap.sh").current_dir(&fresh));
+
+    let synced = project.join("external");
+    let bootstrapped = fresh.join("external");
+    assert_eq!(tree(&bootstrapped), tree(&synced));
+    for name in ["liba", "json"] {
+        assert_eq!(head(&bootstrapped.join(name)), head(&synced.join(name)));
+    }
+    // The sparse checkout leaves the rest of the monorepo out
+    assert!(bootstrapped.join("json/libs/json/include/json.h").is_file());
+    assert!(!bootstrapped.join("json/tools").exists());
+
+    // Running it again over the checkouts changes nothing
+    run(Command::new("sh").arg("bootstrap.sh").current_dir(&fresh));
+    assert_eq!(tree(&bootstrapped), tree(&synced));
+}
+
+#[test]
+fn verify_reports_a_stale_script() {
+    let tmp = TempDir::new("bootstrap-stale");
+    let liba = fixture_repo(&tmp, "liba", &[("include/a.h", "int a(void);\n")]);
+    run(c1(&tmp, tmp.path()).args(["new", "--vcs", "none", "app"]));
+    let project = tmp.path().join("app");
+    run(c1(&tmp, &project).args(["add", &liba, "--name", "liba"]));
+    run(c1(&tmp, &project).args(["export", "bootstrap"]));
+    let exported = fs::read_to_string(project.join("bootstrap.sh")).unwrap();
+
+    // Exporting again from the same lockfile gives the same script
+    run(c1(&tmp, &project).args(["export", "bootstrap"]));
+    assert_eq!(
+        fs::read_to_string(project.join("bootstrap.sh")).unwrap(),
+        exported
+    );
+
+    fs::write(
+        project.join("bootstrap.sh"),
+        exported.replace("liba", "libb"),
+    )
+    .unwrap();
+    let output = c1(&tmp, &project).args(["verify"]).output().unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("bootstrap.sh is out of date"), "{}", stderr);
+}
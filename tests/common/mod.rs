@@ -0,0 +1,86 @@
+//! Helpers shared by the integration tests, which run the c1 binary in
+//! throwaway directories.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// A directory of its own under the system's temporary directory, removed
+/// with everything in it when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!(
+            "c1-it-{}-{}-{}",
+            name,
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir.canonicalize().unwrap())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// c1 run in `dir`, with the user's configuration and cache kept out
+pub fn c1(home: &TempDir, dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_c1"));
+    command
+        .current_dir(dir)
+        .env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.path().join(".config"))
+        .env("XDG_CACHE_HOME", home.path().join(".cache"))
+        .env("GIT_AUTHOR_NAME", "Test")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "Test")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .env("NO_COLOR", "1")
+        .env_remove("CC")
+        .env_remove("CFLAGS")
+        .env_remove("LDFLAGS")
+        .env_remove("C1_OFFLINE");
+    command
+}
+
+/// Run `command`, failing the test with its output when it fails
+pub fn run(command: &mut Command) -> Output {
+    let output = command.output().expect("failed to start c1");
+    assert!(
+        output.status.success(),
+        "{:?} failed with {}\nstdout:\n{}\nstderr:\n{}",
+        command,
+        output.status,
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+pub fn read(path: impl AsRef<Path>) -> String {
+    let path = path.as_ref();
+    fs::read_to_string(path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+}
+
+/// Whether `program` runs, for tests that need a tool c1 drives
+pub fn has(program: &str) -> bool {
+    Command::new(program)
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{TempDir, c1, has, read, run};
+
+/// A `type = "lib"` project installed to a prefix builds and runs a
+/// consumer through pkg-config and through find_package
+#[test]
+fn installed_library_is_found_by_consumers() {
+    if !has("cmake") || !has("cc") {
+        eprintln!("skipping: the install round trip needs cmake and a C compiler");
+        return;
+    }
+    let tmp = TempDir::new("install");
+    run(c1(&tmp, tmp.path()).args(["new", "--lib", "--vcs", "none", "mylib"]));
+    let project = tmp.path().join("mylib");
+    // A system library the consumers must link too
+    let manifest = read(project.join("project.toml"));
+    fs::write(
+        project.join("project.toml"),
+        manifest.replace("[build]\n", "[build]\nlinks = [\"m\"]\n"),
+    )
+    .unwrap();
+    let prefix = tmp.path().join("prefix");
+    run(c1(&tmp, &project)
+        .arg("install")
+        .arg("--prefix")
+        .arg(&prefix));
+
+    assert!(prefix.join("lib/libmylib.a").is_file());
+    assert!(prefix.join("include/mylib.h").is_file());
+    assert!(prefix.join("lib/cmake/mylib/mylibConfig.cmake").is_file());
+    assert!(
+        prefix
+            .join("lib/cmake/mylib/mylibConfigVersion.cmake")
+            .is_file()
+    );
+    let pc = read(prefix.join("lib/pkgconfig/mylib.pc"));
+    assert!(
+        pc.contains(&format!("prefix={}\n", prefix.display())),
+        "{}",
+        pc
+    );
+    assert!(pc.contains("Version: 0.1.0"), "{}", pc);
+    assert!(pc.contains("-lmylib -lm"), "{}", pc);
+
+    let consumer = tmp.path().join("consumer");
+    fs::create_dir_all(&consumer).unwrap();
+    fs::write(
+        consumer.join("main.c"),
+        "#include <mylib.h>\n\nint main(void) {\n    return mylib_version() == 1 ? 0 : 1;\n}\n",
+    )
+    .unwrap();
+
+    if has("pkg-config") {
+        let flags = run(Command::new("pkg-config")
+            .args(["--cflags", "--libs", "mylib"])
+            .env("PKG_CONFIG_PATH", prefix.join("lib/pkgconfig")));
+        let flags = String::from_utf8_lossy(&flags.stdout).to_string();
+        let program = consumer.join("with-pkg-config");
+        run(Command::new("cc")
+            .arg(consumer.join("main.c"))
+            .arg("-o")
+            .arg(&program)
+            .args(flags.split_whitespace()));
+        run(&mut Command::new(&program));
+    }
+
+    fs::write(
+        consumer.join("CMakeLists.txt"),
+        "cmake_minimum_required(VERSION 3.16)\nproject(consumer C)\n\
+         find_package(mylib 0.1 REQUIRED)\n\
+         add_executable(consumer main.c)\n\
+         target_link_libraries(consumer mylib::mylib)\n",
+    )
+    .unwrap();
+    let build = consumer.join("build");
+    run(Command::new("cmake")
+        .arg("-S")
+        .arg(&consumer)
+        .arg("-B")
+        .arg(&build)
+        .arg(format!("-DCMAKE_PREFIX_PATH={}", prefix.display())));
+    run(Command::new("cmake").arg("--build").arg(&build));
+    let program = [build.join("consumer"), build.join("Debug/consumer.exe")]
+        .into_iter()
+        .find(|path| path.is_file())
+        .expect("the consumer was not built");
+    run(&mut Command::new(program));
+
+    // And it all goes again
+    run(c1(&tmp, &project)
+        .arg("uninstall")
+        .arg("--prefix")
+        .arg(&prefix));
+    for file in [
+        "lib/libmylib.a",
+        "include/mylib.h",
+        "lib/cmake/mylib/mylibConfig.cmake",
+        "lib/pkgconfig/mylib.pc",
+    ] {
+        assert!(!Path::new(&prefix).join(file).exists(), "{} is left", file);
+    }
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::fs;
+
+use common::{TempDir, c1, has, read, run};
+
+/// The checkout the request came from: spaces and a non-ASCII letter
+const PARENT: &str = "C Projects/Team α";
+
+#[test]
+fn creates_and_builds_a_project_under_spaces_and_unicode() {
+    let tmp = TempDir::new("paths-new");
+    let parent = tmp.path().join(PARENT);
+    fs::create_dir_all(&parent).unwrap();
+    run(c1(&tmp, &parent).args(["new", "--vcs", "none", "app"]));
+    let project = parent.join("app");
+    assert!(read(project.join("project.toml")).contains("name = \"app\""));
+    assert!(project.join("main.c").is_file());
+
+    if !has("cmake") {
+        eprintln!("skipping the build: it needs cmake");
+        return;
+    }
+    run(c1(&tmp, &project).arg("build"));
+    let built = [
+        project.join("build/debug/app"),
+        project.join("build/debug/Debug/app.exe"),
+        project.join("build/debug/app.exe"),
+    ];
+    assert!(
+        built.iter().any(|path| path.is_file()),
+        "no executable in {:?}",
+        built
+    );
+    let output = run(c1(&tmp, &project).arg("run"));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Hello app"));
+}
+
+#[test]
+fn init_suggests_an_ascii_name_for_an_accented_directory() {
+    let tmp = TempDir::new("paths-init");
+    let dir = tmp.path().join(PARENT).join("café");
+    fs::create_dir_all(&dir).unwrap();
+
+    let output = c1(&tmp, &dir)
+        .args(["init", "--vcs", "none"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(64));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("c1 init --name cafe"), "{}", stderr);
+    assert!(!dir.join("project.toml").exists());
+
+    run(c1(&tmp, &dir).args(["init", "--vcs", "none", "--name", "cafe"]));
+    assert!(read(dir.join("project.toml")).contains("name = \"cafe\""));
+}
+
+#[cfg(unix)]
+#[test]
+fn init_refuses_a_directory_name_that_is_not_utf8() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let tmp = TempDir::new("paths-not-utf8");
+    let dir = tmp.path().join(OsStr::from_bytes(b"lib\xff"));
+    // Some file systems (APFS) refuse such names in the first place
+    if fs::create_dir(&dir).is_err() {
+        eprintln!("skipping: the file system refuses names that are not UTF-8");
+        return;
+    }
+
+    let output = c1(&tmp, &dir)
+        .args(["init", "--vcs", "none"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(64));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("not valid UTF-8"), "{}", stderr);
+    assert!(stderr.contains("c1 init --name lib"), "{}", stderr);
+    assert!(!dir.join("project.toml").exists());
+}
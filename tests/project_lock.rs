@@ -0,0 +1,86 @@
+mod common;
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use common::{TempDir, c1, read, run};
+
+/// Lock .c1/lock of `project` as a c1 running `command` would
+fn hold(project: &Path, command: &str) -> File {
+    fs::create_dir_all(project.join(".c1")).unwrap();
+    let mut file = File::create(project.join(".c1/lock")).unwrap();
+    file.lock().unwrap();
+    writeln!(file, "{} {}", std::process::id(), command).unwrap();
+    file
+}
+
+fn output_text(output: &std::process::Output) -> String {
+    format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    )
+}
+
+#[test]
+fn no_wait_fails_while_another_c1_holds_the_lock() {
+    let tmp = TempDir::new("lock-held");
+    run(c1(&tmp, tmp.path()).args(["new", "--vcs", "none", "app"]));
+    let project = tmp.path().join("app");
+    let _held = hold(&project, "build");
+
+    let output = c1(&tmp, &project)
+        .args(["clean", "--no-wait"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let text = output_text(&output);
+    let holder = format!("locked by PID {} (c1 build)", std::process::id());
+    assert!(text.contains(&holder), "{}", text);
+}
+
+#[test]
+fn waits_for_the_holder_to_let_go() {
+    let tmp = TempDir::new("lock-wait");
+    run(c1(&tmp, tmp.path()).args(["new", "--vcs", "none", "app"]));
+    let project = tmp.path().join("app");
+    let held = hold(&project, "build");
+
+    let mut child = c1(&tmp, &project)
+        .arg("clean")
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(600));
+    assert!(child.try_wait().unwrap().is_none(), "clean did not wait");
+
+    drop(held);
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", output_text(&output));
+    let text = output_text(&output);
+    assert!(
+        text.contains("Blocking on the project lock held by PID"),
+        "{}",
+        text
+    );
+}
+
+#[test]
+fn a_lock_left_by_a_dead_process_does_not_block() {
+    let tmp = TempDir::new("lock-stale");
+    run(c1(&tmp, tmp.path()).args(["new", "--vcs", "none", "app"]));
+    let project = tmp.path().join("app");
+    // The owner line of a c1 that was killed; nothing holds the lock
+    fs::create_dir_all(project.join(".c1")).unwrap();
+    fs::write(project.join(".c1/lock"), "4194303 build\n").unwrap();
+
+    run(c1(&tmp, &project).args(["clean", "--no-wait"]));
+
+    // Emptied, not removed, once the clean is done
+    assert_eq!(read(project.join(".c1/lock")), "");
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::{TempDir, c1, read, run};
+
+#[test]
+fn renames_a_scaffolded_library() {
+    let tmp = TempDir::new("rename-project");
+    run(c1(&tmp, tmp.path()).args(["new", "--lib", "--vcs", "none", "oldlib"]));
+    let project = tmp.path().join("oldlib");
+    let header = read(project.join("include/oldlib.h"));
+
+    let output = run(c1(&tmp, &project).args(["rename-project", "newlib"]));
+
+    let manifest = read(project.join("project.toml"));
+    assert!(manifest.contains("name = \"newlib\""), "{}", manifest);
+    assert!(!manifest.contains("oldlib"), "{}", manifest);
+    let cmake = read(project.join("CMakeLists.txt"));
+    assert!(cmake.contains("project(newlib C)"), "{}", cmake);
+    assert!(!cmake.contains("project(oldlib"), "{}", cmake);
+    // Module files keep their names, so CMakeLists.txt still lists them and
+    // the header, include guard and all, is left alone
+    assert!(cmake.contains("src/oldlib.c"), "{}", cmake);
+    assert!(cmake.contains("include/oldlib.h"), "{}", cmake);
+    assert_eq!(read(project.join("include/oldlib.h")), header);
+    assert!(header.contains("#ifndef _OLDLIB_H"), "{}", header);
+
+    // What was left is reported
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("README.md:1: # oldlib"), "{}", stdout);
+    assert!(stdout.contains("src/oldlib.c:1:"), "{}", stdout);
+}
+
+#[test]
+fn dry_run_changes_nothing() {
+    let tmp = TempDir::new("rename-project-dry-run");
+    run(c1(&tmp, tmp.path()).args(["new", "--vcs", "none", "app"]));
+    let project = tmp.path().join("app");
+    let manifest = read(project.join("project.toml"));
+    let cmake = read(project.join("CMakeLists.txt"));
+
+    let output = run(c1(&tmp, &project).args(["rename-project", "tool", "--dry-run"]));
+
+    assert!(String::from_utf8_lossy(&output.stdout).contains("project(tool C)"));
+    assert_eq!(read(project.join("project.toml")), manifest);
+    assert_eq!(read(project.join("CMakeLists.txt")), cmake);
+}
+
+#[test]
+fn refuses_an_invalid_name() {
+    let tmp = TempDir::new("rename-project-invalid");
+    run(c1(&tmp, tmp.path()).args(["new", "--vcs", "none", "app"]));
+    let project = tmp.path().join("app");
+    let manifest = read(project.join("project.toml"));
+
+    let output = c1(&tmp, &project)
+        .args(["rename-project", "bad name"])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert_eq!(read(project.join("project.toml")), manifest);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use std::fs;
+
+use common::{TempDir, c1, read, run};
+
+#[test]
+fn clean_refuses_a_configured_out_dir_holding_the_project() {
+    let tmp = TempDir::new("safe-path-out-dir");
+    run(c1(&tmp, tmp.path()).args(["new", "--vcs", "none", "app"]));
+    let project = tmp.path().join("app");
+    let manifest = read(project.join("project.toml"));
+    fs::write(
+        project.join("project.toml"),
+        manifest.replace("[build]\n", "[build]\nout_dir = \"..\"\n"),
+    )
+    .unwrap();
+    fs::write(tmp.path().join("keep.txt"), "").unwrap();
+
+    let output = c1(&tmp, &project).arg("clean").output().unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--allow-outside-project"), "{}", stderr);
+    assert!(tmp.path().join("keep.txt").is_file());
+    assert!(project.join("project.toml").is_file());
+}
+
+#[test]
+fn uninstall_refuses_a_prefix_holding_the_project() {
+    let tmp = TempDir::new("safe-path-prefix");
+    run(c1(&tmp, tmp.path()).args(["new", "--lib", "--vcs", "none", "mylib"]));
+    let project = tmp.path().join("mylib");
+    let library = if cfg!(windows) {
+        "mylib.lib"
+    } else {
+        "libmylib.a"
+    };
+    let installed = tmp.path().join("lib").join(library);
+    fs::create_dir_all(installed.parent().unwrap()).unwrap();
+    fs::write(&installed, "").unwrap();
+
+    let output = c1(&tmp, &project)
+        .args(["uninstall", "--prefix", ".."])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--allow-outside-project"), "{}", stderr);
+    assert!(installed.is_file());
+
+    // A prefix beside the project is c1's to install into and remove from
+    let prefix = tmp.path().join("prefix");
+    let elsewhere = prefix.join("lib").join(library);
+    fs::create_dir_all(elsewhere.parent().unwrap()).unwrap();
+    fs::write(&elsewhere, "").unwrap();
+    run(c1(&tmp, &project)
+        .arg("uninstall")
+        .arg("--prefix")
+        .arg(&prefix));
+    assert!(!elsewhere.exists());
+}